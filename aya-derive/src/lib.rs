@@ -0,0 +1,85 @@
+//! Derive macros for [aya](https://docs.rs/aya).
+//!
+//! The macros are re-exported by aya and should be used from there, eg
+//! `#[derive(aya::Pod)]`.
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Meta, NestedMeta};
+
+/// Implements `aya::Pod` for a struct.
+///
+/// See the documentation of `aya::Pod` for the requirements the struct must
+/// meet.
+#[proc_macro_derive(Pod)]
+pub fn derive_pod(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match pod(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn pod(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "Pod can only be derived for structs",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "Pod can't be derived for generic structs",
+        ));
+    }
+    if !has_c_repr(&input)? {
+        return Err(Error::new_spanned(
+            name,
+            "Pod can only be derived for structs with #[repr(C)] or #[repr(transparent)]",
+        ));
+    }
+
+    let types = fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let message = format!("`{}` has padding bytes", name);
+
+    Ok(quote! {
+        const _: fn() = || {
+            fn assert_pod<T: ::aya::Pod>() {}
+            #(assert_pod::<#types>();)*
+        };
+
+        // padding bytes are uninitialized, so reading them as bytes would be
+        // undefined behavior
+        const _: () = assert!(
+            ::core::mem::size_of::<#name>() == 0 #(+ ::core::mem::size_of::<#types>())*,
+            #message
+        );
+
+        unsafe impl ::aya::Pod for #name {}
+    })
+}
+
+fn has_c_repr(input: &DeriveInput) -> Result<bool, Error> {
+    for attr in &input.attrs {
+        if !attr.path.is_ident("repr") {
+            continue;
+        }
+        if let Meta::List(list) = attr.parse_meta()? {
+            for nested in list.nested {
+                if let NestedMeta::Meta(Meta::Path(path)) = nested {
+                    if path.is_ident("C") || path.is_ident("transparent") {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(false)
+}
@@ -6,8 +6,11 @@
 #include <asm-generic/socket.h>
 #include <linux/pkt_sched.h>
 #include <linux/pkt_cls.h>
+#include <linux/if_xdp.h>
+#include <linux/netdev.h>
 
 /* workaround the fact that bindgen can't parse the IOC macros */
 int AYA_PERF_EVENT_IOC_ENABLE = PERF_EVENT_IOC_ENABLE;
 int AYA_PERF_EVENT_IOC_DISABLE = PERF_EVENT_IOC_DISABLE;
 int AYA_PERF_EVENT_IOC_SET_BPF = PERF_EVENT_IOC_SET_BPF;
+int AYA_PERF_EVENT_IOC_PAUSE_OUTPUT = PERF_EVENT_IOC_PAUSE_OUTPUT;
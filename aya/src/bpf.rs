@@ -1,31 +1,46 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
-    fs, io,
-    os::{raw::c_int, unix::io::RawFd},
+    fmt, fs, io, mem,
+    os::{
+        raw::c_int,
+        unix::io::{OwnedFd, RawFd},
+    },
     path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+use libc::ENOSPC;
 use thiserror::Error;
 
 use crate::{
+    features,
     generated::{
-        bpf_map_type::BPF_MAP_TYPE_PERF_EVENT_ARRAY, AYA_PERF_EVENT_IOC_DISABLE,
-        AYA_PERF_EVENT_IOC_ENABLE, AYA_PERF_EVENT_IOC_SET_BPF,
+        bpf_map_type::{BPF_MAP_TYPE_ARRAY, BPF_MAP_TYPE_PERF_EVENT_ARRAY, BPF_MAP_TYPE_RINGBUF},
+        AYA_PERF_EVENT_IOC_DISABLE, AYA_PERF_EVENT_IOC_ENABLE, AYA_PERF_EVENT_IOC_PAUSE_OUTPUT,
+        AYA_PERF_EVENT_IOC_SET_BPF, BPF_F_SLEEPABLE,
     },
-    maps::{Map, MapError, MapLock, MapRef, MapRefMut},
+    maps::{Map, MapError, MapFlags, MapLock, MapRef, MapRefMut},
     obj::{
-        btf::{Btf, BtfError},
-        Object, ParseError, ProgramSection,
+        btf::{Btf, BtfError, BtfFeatures, BtfKind},
+        decompress, is_bss_section, DecompressionError, KConfigError, KSymError, Object,
+        ParseError, ProgramSection,
     },
     programs::{
-        BtfTracePoint, CgroupSkb, CgroupSkbAttachType, KProbe, LircMode2, Lsm, PerfEvent,
-        ProbeKind, Program, ProgramData, ProgramError, RawTracePoint, SchedClassifier, SkMsg,
-        SkSkb, SkSkbKind, SockOps, SocketFilter, TracePoint, UProbe, Xdp,
+        tc, BtfTracePoint, CgroupAttachFlags, CgroupSkb, CgroupSkbAttachType, Extension, FEntry,
+        FExit, FlowDissector, Iter, KProbe, KProbeMulti, LircMode2, Lsm, LsmCgroup, LsmLoadError,
+        Netfilter, PerfEvent, ProbeKind, Program, ProgramData, ProgramError, ProgramFd,
+        RawTracePoint, SchedClassifier, SkMsg, SkReuseport, SkReuseportKind, SkSkb, SkSkbKind,
+        SockOps, SocketFilter, TcAttachType, TracePoint, TypedProgram, UProbe, UProbeMulti, Usdt,
+        VerifierLog, Xdp, XdpFlags, XdpRunConfig,
+    },
+    sys::{bpf_load_btf, bpf_map_update_elem_ptr},
+    util::{
+        kernel_config, memlock_rlimit_applies, possible_cpus, raise_memlock_rlimit,
+        sanitize_obj_name, validate_obj_name, KernelSymbols, KernelVersion, POSSIBLE_CPUS,
     },
-    sys::bpf_map_update_elem_ptr,
-    util::{possible_cpus, POSSIBLE_CPUS},
 };
 
 pub(crate) const BPF_OBJ_NAME_LEN: usize = 16;
@@ -33,8 +48,47 @@ pub(crate) const BPF_OBJ_NAME_LEN: usize = 16;
 pub(crate) const PERF_EVENT_IOC_ENABLE: c_int = AYA_PERF_EVENT_IOC_ENABLE;
 pub(crate) const PERF_EVENT_IOC_DISABLE: c_int = AYA_PERF_EVENT_IOC_DISABLE;
 pub(crate) const PERF_EVENT_IOC_SET_BPF: c_int = AYA_PERF_EVENT_IOC_SET_BPF;
+pub(crate) const PERF_EVENT_IOC_PAUSE_OUTPUT: c_int = AYA_PERF_EVENT_IOC_PAUSE_OUTPUT;
 
 /// Marker trait for types that can safely be converted to and from byte slices.
+///
+/// The trait is implemented for the integer types and arrays of `Pod` types.
+/// It can be derived for structs with [`derive(Pod)`](macro@crate::Pod),
+/// which checks at compile time that the struct is `#[repr(C)]`, that all its
+/// fields are `Pod` and that it has no padding bytes, which are uninitialized
+/// and can't be read as bytes.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use std::convert::TryFrom;
+/// use aya::{maps::HashMap, Pod};
+///
+/// #[derive(Clone, Copy, Pod)]
+/// #[repr(C)]
+/// struct ConnKey {
+///     saddr: u32,
+///     daddr: u32,
+///     sport: u16,
+///     dport: u16,
+///     _pad: [u8; 4],
+/// }
+///
+/// let conns = HashMap::<_, ConnKey, u64>::try_from(bpf.map("CONNECTIONS")?)?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+///
+/// Structs with padding bytes are rejected:
+///
+/// ```compile_fail
+/// #[derive(Clone, Copy, aya::Pod)]
+/// #[repr(C)]
+/// struct ConnKey {
+///     saddr: u32,
+///     sport: u16,
+/// }
+/// ```
 pub unsafe trait Pod: Copy + 'static {}
 
 macro_rules! unsafe_impl_pod {
@@ -47,6 +101,8 @@ macro_rules! unsafe_impl_pod {
 
 unsafe_impl_pod!(i8, u8, i16, u16, i32, u32, i64, u64);
 
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
 #[allow(non_camel_case_types)]
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
@@ -66,7 +122,6 @@ pub(crate) struct bpf_map_def {
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) enum PinningType {
     None = 0,
-    #[allow(dead_code)] // ByName is constructed from the BPF side
     ByName = 1,
 }
 
@@ -101,6 +156,28 @@ impl Default for PinningType {
 pub struct BpfLoader<'a> {
     btf: Option<Cow<'a, Btf>>,
     map_pin_path: Option<PathBuf>,
+    raise_memlock_rlimit: bool,
+    btf_fallback: bool,
+    token_fd: Option<RawFd>,
+    map_ifindex: Option<u32>,
+    program_filter: Option<ProgramFilter>,
+    extension_targets: HashMap<String, (RawFd, String)>,
+    map_fds: HashMap<String, RawFd>,
+    map_flags: HashMap<String, MapFlags>,
+    map_numa_nodes: HashMap<String, u32>,
+    map_names: HashMap<String, String>,
+    program_names: HashMap<String, String>,
+    name_prefix: Option<String>,
+    bound_maps: HashSet<String>,
+    section_aliases: HashMap<String, String>,
+}
+
+struct ProgramFilter(Box<dyn Fn(&str) -> bool>);
+
+impl fmt::Debug for ProgramFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgramFilter")
+    }
 }
 
 impl<'a> BpfLoader<'a> {
@@ -109,6 +186,20 @@ impl<'a> BpfLoader<'a> {
         BpfLoader {
             btf: Btf::from_sys_fs().ok().map(Cow::Owned),
             map_pin_path: None,
+            raise_memlock_rlimit: false,
+            btf_fallback: true,
+            token_fd: None,
+            map_ifindex: None,
+            program_filter: None,
+            extension_targets: HashMap::new(),
+            map_fds: HashMap::new(),
+            map_flags: HashMap::new(),
+            map_numa_nodes: HashMap::new(),
+            map_names: HashMap::new(),
+            program_names: HashMap::new(),
+            name_prefix: None,
+            bound_maps: HashSet::new(),
+            section_aliases: HashMap::new(),
         }
     }
 
@@ -154,6 +245,369 @@ impl<'a> BpfLoader<'a> {
         self
     }
 
+    /// Raises `RLIMIT_MEMLOCK` before creating maps and loading programs.
+    ///
+    /// Before Linux 5.11, the memory used by maps and programs is charged
+    /// against the locked memory limit of the process, and loading fails with
+    /// `EPERM` when the limit is exceeded. When enabled, the loader raises the
+    /// limit to infinity on those kernels. Raising the limit requires
+    /// `CAP_SYS_RESOURCE`. This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let bpf = BpfLoader::new()
+    ///     .raise_memlock_rlimit(true)
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn raise_memlock_rlimit(&mut self, raise: bool) -> &mut BpfLoader<'a> {
+        self.raise_memlock_rlimit = raise;
+        self
+    }
+
+    /// Sets whether programs are loaded without BTF if the kernel rejects the
+    /// BTF of the object.
+    ///
+    /// Before loading it, the loader rewrites the BTF kinds that the running
+    /// kernel doesn't support (`DATASEC`, `FLOAT`, global functions, ...), so
+    /// that objects compiled with recent toolchains also load on older
+    /// kernels. If the kernel still rejects the BTF, or doesn't support BTF at
+    /// all, the programs are loaded without it by default. Disable the
+    /// fallback to get a [`BtfError::LoadError`] instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let bpf = BpfLoader::new()
+    ///     .btf_fallback(false)
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn btf_fallback(&mut self, fallback: bool) -> &mut BpfLoader<'a> {
+        self.btf_fallback = fallback;
+        self
+    }
+
+    /// Sets the BPF token used to create the maps, load the BTF and load the
+    /// programs of the object.
+    ///
+    /// Tokens allow loading objects without `CAP_BPF` in the initial user
+    /// namespace, see [`BpfToken`](crate::BpfToken). The token must stay
+    /// open until the programs are loaded. Kernel feature detection doesn't
+    /// use the token.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[derive(thiserror::Error, Debug)]
+    /// # enum Error {
+    /// #     #[error(transparent)]
+    /// #     Token(#[from] aya::BpfTokenError),
+    /// #     #[error(transparent)]
+    /// #     Bpf(#[from] aya::BpfError)
+    /// # }
+    /// use std::os::unix::io::AsRawFd;
+    /// use aya::{BpfLoader, BpfToken, DEFAULT_BPFFS_PATH};
+    ///
+    /// let token = BpfToken::from_bpffs(DEFAULT_BPFFS_PATH)?;
+    /// let bpf = BpfLoader::new()
+    ///     .token(token.as_raw_fd())
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn token(&mut self, fd: RawFd) -> &mut BpfLoader<'a> {
+        self.token_fd = Some(fd);
+        self
+    }
+
+    /// Sets which programs are made available after loading.
+    ///
+    /// `filter` is called with the name of each program of the object. The
+    /// programs for which it returns `false` are skipped: their calls aren't
+    /// linked and they aren't returned by [`Bpf::program`] and
+    /// [`Bpf::programs`]. This makes loading objects that bundle many
+    /// programs cheaper, and lets them load even if some of the skipped
+    /// programs can't be linked. Skipped programs can be made available later
+    /// with [`Bpf::materialize_program`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let enabled = ["trace_connect", "trace_accept"];
+    /// let bpf = BpfLoader::new()
+    ///     .program_filter(move |name| enabled.contains(&name))
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn program_filter<F: Fn(&str) -> bool + 'static>(
+        &mut self,
+        filter: F,
+    ) -> &mut BpfLoader<'a> {
+        self.program_filter = Some(ProgramFilter(Box::new(filter)));
+        self
+    }
+
+    /// Sets the function replaced by the [`Extension`] program `name`.
+    ///
+    /// `target_prog` is the file descriptor of a loaded program and
+    /// `func_name` the name of one of its global functions. The descriptor is
+    /// duplicated when the object is loaded, and the BTF id of the function is
+    /// resolved when the extension is loaded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # let mut dispatcher = aya::Bpf::load_file("dispatcher.o")?;
+    /// use aya::{BpfLoader, programs::ProgramFd};
+    ///
+    /// let dispatcher_fd = dispatcher.program("dispatcher")?.fd().unwrap();
+    /// let mut bpf = BpfLoader::new()
+    ///     .extension_target("filter", dispatcher_fd, "slot0")
+    ///     .load_file("filter.o")?;
+    /// bpf.program_mut("filter")?.load()?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn extension_target(
+        &mut self,
+        name: &str,
+        target_prog: RawFd,
+        func_name: &str,
+    ) -> &mut BpfLoader<'a> {
+        self.extension_targets
+            .insert(name.to_owned(), (target_prog, func_name.to_owned()));
+        self
+    }
+
+    /// Uses the existing map `fd` for the map `name` instead of creating it.
+    ///
+    /// `fd` can refer to a pinned map or one received from another process.
+    /// The descriptor is duplicated when the object is loaded, after checking
+    /// that the type, key and value sizes, maximum number of entries and
+    /// flags of the map match its definition in the object. Otherwise loading
+    /// fails with [`MapError::IncompatibleMap`]. Reused maps aren't pinned and
+    /// their data isn't initialized.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// let other = aya::Bpf::load_file("other.o")?;
+    /// let events = other.map("EVENTS")?.try_clone_fd()?;
+    /// let bpf = BpfLoader::new()
+    ///     .set_map_fd("EVENTS", events.as_raw_fd())
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_map_fd(&mut self, name: &str, fd: RawFd) -> &mut BpfLoader<'a> {
+        self.map_fds.insert(name.to_owned(), fd);
+        self
+    }
+
+    /// Sets the flags the map `name` is created with, overriding the flags of
+    /// its definition in the object.
+    ///
+    /// Only the flags defined by [`MapFlags`] are overridden, the other flags
+    /// of the definition are kept.
+    ///
+    /// The right flags often depend on the running kernel, for instance
+    /// [`MapFlags::RDONLY_PROG`] is only supported since Linux 5.2.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::{BpfLoader, maps::MapFlags, util::KernelVersion};
+    ///
+    /// let flags = if KernelVersion::current()? >= KernelVersion::new(5, 2, 0) {
+    ///     MapFlags::RDONLY_PROG
+    /// } else {
+    ///     MapFlags::empty()
+    /// };
+    /// let bpf = BpfLoader::new()
+    ///     .map_flags("CONFIG", flags)
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn map_flags(&mut self, name: &str, flags: MapFlags) -> &mut BpfLoader<'a> {
+        self.map_flags.insert(name.to_owned(), flags);
+        self
+    }
+
+    /// Allocates the map `name` on the NUMA node `node`.
+    ///
+    /// This overrides the `numa_node` attribute of the map definition in the
+    /// object. By default the kernel allocates maps on any node, placing them
+    /// on the node that owns a NIC can reduce the latency of programs
+    /// processing its packets. Requires kernel 4.14 or newer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    /// use std::fs;
+    ///
+    /// let node = fs::read_to_string("/sys/class/net/eth0/device/numa_node")?;
+    /// let bpf = BpfLoader::new()
+    ///     .map_numa_node("FLOWS", node.trim().parse()?)
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn map_numa_node(&mut self, name: &str, node: u32) -> &mut BpfLoader<'a> {
+        self.map_numa_nodes.insert(name.to_owned(), node);
+        self
+    }
+
+    /// Offloads all the maps of the object to the network device `ifindex`.
+    ///
+    /// Programs offloaded with [`Xdp::set_offload_interface`] can only use
+    /// maps offloaded to the same device, and offloaded maps can't be used by
+    /// programs running on the host.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::{programs::Xdp, BpfLoader};
+    /// use std::fs;
+    ///
+    /// let ifindex = fs::read_to_string("/sys/class/net/eth0/ifindex")?;
+    /// let mut bpf = BpfLoader::new()
+    ///     .map_ifindex(ifindex.trim().parse()?)
+    ///     .load_file("file.o")?;
+    /// let program = bpf.program_mut_typed::<Xdp>("intercept_packets")?;
+    /// program.set_offload_interface(Some("eth0"))?;
+    /// program.load()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn map_ifindex(&mut self, ifindex: u32) -> &mut BpfLoader<'a> {
+        self.map_ifindex = Some(ifindex);
+        self
+    }
+
+    /// Sets the name of the map `name` in the kernel, as shown by `bpftool`.
+    ///
+    /// Maps are named after their name in the object by default, truncated to
+    /// 15 characters. `kernel_name` must be at most 15 characters long and
+    /// only contain alphanumeric characters, `_` and `.`, otherwise loading
+    /// fails with [`BpfError::InvalidObjectName`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let bpf = BpfLoader::new()
+    ///     .map_name("CONNECTIONS_BY_PID", "conn_by_pid")
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn map_name(&mut self, name: &str, kernel_name: &str) -> &mut BpfLoader<'a> {
+        self.map_names
+            .insert(name.to_owned(), kernel_name.to_owned());
+        self
+    }
+
+    /// Sets the name of the program `name` in the kernel, as shown by
+    /// `bpftool`.
+    ///
+    /// Programs are unnamed by default. The rules for `kernel_name` are the
+    /// same as for [`BpfLoader::map_name`].
+    pub fn program_name(&mut self, name: &str, kernel_name: &str) -> &mut BpfLoader<'a> {
+        self.program_names
+            .insert(name.to_owned(), kernel_name.to_owned());
+        self
+    }
+
+    /// Prefixes the kernel names of the maps and programs with `prefix`, so
+    /// that multiple instances of the same object can be told apart.
+    ///
+    /// The names set with [`BpfLoader::map_name`] and
+    /// [`BpfLoader::program_name`] aren't prefixed. The other names are made
+    /// valid by replacing the characters the kernel doesn't accept with `_`,
+    /// and are truncated to 15 characters.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// for instance in 0..4 {
+    ///     let bpf = BpfLoader::new()
+    ///         // the EVENTS map is named i0_EVENTS, i1_EVENTS, ...
+    ///         .name_prefix(&format!("i{}_", instance))
+    ///         .load_file("file.o")?;
+    /// }
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn name_prefix(&mut self, prefix: &str) -> &mut BpfLoader<'a> {
+        self.name_prefix = Some(prefix.to_owned());
+        self
+    }
+
+    /// Binds the map `name` to all the programs of the object when they're
+    /// loaded, with `BPF_PROG_BIND_MAP`.
+    ///
+    /// Bound maps live as long as the programs and are listed in their info,
+    /// even if the programs don't reference them, for instance maps only
+    /// accessed from user space or through a map of maps. The maps holding
+    /// `bpf_metadata_*` variables are always bound. Binding requires kernel
+    /// 5.10 or newer and is skipped on older kernels. With
+    /// [`BpfLoader::load_multi`], the map must be defined by the object or by
+    /// one loaded before it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let bpf = BpfLoader::new()
+    ///     .bind_map("STATS")
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn bind_map(&mut self, name: &str) -> &mut BpfLoader<'a> {
+        self.bound_maps.insert(name.to_owned());
+        self
+    }
+
+    /// Parses the sections named `section` or `section/...` as if they were
+    /// named `kind` or `kind/...`.
+    ///
+    /// This allows loading objects built by toolchains using section names
+    /// aya doesn't know about. `kind` is one of the section names aya
+    /// recognizes, like `xdp`, `kprobe` or `sk_skb/stream_parser`. A section
+    /// named `section` exactly defines a program named `section`. Aliases
+    /// take precedence over the built-in names, so they can also be used to
+    /// change the type of ambiguous sections, like `prog` which is loaded as
+    /// an XDP program by default. If `kind` isn't a section name aya
+    /// recognizes, loading fails with [`BpfError::ParseError`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let bpf = BpfLoader::new()
+    ///     // `ingress/filter` is loaded like `classifier/filter`
+    ///     .section_alias("ingress", "classifier")
+    ///     // `prog` is a tc program rather than an XDP program
+    ///     .section_alias("prog", "classifier")
+    ///     .load_file("file.o")?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn section_alias(&mut self, section: &str, kind: &str) -> &mut BpfLoader<'a> {
+        self.section_aliases
+            .insert(section.to_owned(), kind.to_owned());
+        self
+    }
+
     /// Loads eBPF bytecode from a file.
     ///
     /// # Examples
@@ -174,6 +628,10 @@ impl<'a> BpfLoader<'a> {
 
     /// Loads eBPF bytecode from a buffer.
     ///
+    /// `data` can be compressed with gzip or zstd, which is detected from its
+    /// magic bytes. Decompressing requires the `gzip` or `zstd` feature
+    /// respectively, so embedded objects can be shipped compressed.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -185,14 +643,235 @@ impl<'a> BpfLoader<'a> {
     /// # Ok::<(), aya::BpfError>(())
     /// ```
     pub fn load(&mut self, data: &[u8]) -> Result<Bpf, BpfError> {
-        let mut obj = Object::parse(data)?;
+        self.load_object(data, true)
+    }
+
+    /// Verifies the programs of an object file without leaving anything
+    /// loaded.
+    ///
+    /// See [`BpfLoader::verify_only`].
+    pub fn verify_only_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Vec<ProgramVerification>, BpfError> {
+        let path = path.as_ref();
+        self.verify_only(&fs::read(path).map_err(|error| BpfError::FileError {
+            path: path.to_owned(),
+            error,
+        })?)
+    }
+
+    /// Verifies the programs of an object without leaving anything loaded.
+    ///
+    /// The maps of the object are created and each program is loaded, which
+    /// runs the kernel verifier, then everything is unloaded. Pinned maps are
+    /// neither opened nor pinned. This can be used to check that an object
+    /// passes the verifier of a target kernel, for example in CI.
+    ///
+    /// Programs that need an attach target to be loaded use the target in
+    /// their section name, like [`Bpf::attach_all`] does. [`Extension`]
+    /// programs are loaded against the target set with
+    /// [`BpfLoader::extension_target`]. Programs skipped by
+    /// [`BpfLoader::program_filter`] aren't verified.
+    ///
+    /// Returns the result of each program, sorted by name. Errors that prevent
+    /// the programs from being loaded at all, like parsing or map creation
+    /// errors, are returned as a [`BpfError`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    ///
+    /// let results = BpfLoader::new().verify_only_file("file.o")?;
+    /// for program in &results {
+    ///     match &program.result {
+    ///         Ok(stats) => println!(
+    ///             "{}: ok, {} insns verified in {:?}",
+    ///             program.name, stats.verified_insns, stats.load_time
+    ///         ),
+    ///         Err(e) => println!("{}: {}", program.name, e),
+    ///     }
+    /// }
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn verify_only(&mut self, data: &[u8]) -> Result<Vec<ProgramVerification>, BpfError> {
+        let mut bpf = self.load_object(data, false)?;
+        let mut btf = None;
+        let mut results = bpf
+            .programs
+            .iter_mut()
+            .map(|(name, program)| {
+                let start = Instant::now();
+                let result = verify_program(name, program, &mut btf).and_then(|()| {
+                    let load_time = start.elapsed();
+                    let info = program.info()?;
+                    Ok(VerifierStats {
+                        verified_insns: info.verified_insns(),
+                        xlated_len: info.xlated_len(),
+                        jited_len: info.jited_len(),
+                        load_time,
+                    })
+                });
+                // fails if the program wasn't loaded
+                let _ = program.unload();
+                ProgramVerification {
+                    name: name.clone(),
+                    result,
+                }
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(results)
+    }
+
+    /// Loads several objects into a single [`Bpf`], sharing the maps they
+    /// have in common.
+    ///
+    /// `objects` are pairs of a namespace and the bytecode of an object, which
+    /// can be compressed like with [`BpfLoader::load`]. Maps with the same name
+    /// are created once and used by the programs of all the objects, so
+    /// probes split across objects can share state. Their definitions must
+    /// match. The global data maps, like `.bss`, belong to their object.
+    ///
+    /// The programs and the global data maps are namespaced: they're named
+    /// `namespace/name`, for [`Bpf::program`], [`BpfLoader::program_filter`]
+    /// and the other functions taking a program name.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::BpfLoader;
+    /// use std::fs;
+    ///
+    /// let net = fs::read("net.o")?;
+    /// let fs = fs::read("fs.o")?;
+    /// // both objects update the `EVENTS` map
+    /// let mut bpf = BpfLoader::new().load_multi(&[("net", &net), ("fs", &fs)])?;
+    /// bpf.program_mut("net/trace_connect")?.load()?;
+    /// bpf.program_mut("fs/trace_open")?.load()?;
+    /// let events = bpf.map("EVENTS")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BpfError::MapDefinitionMismatch`] if a map is defined
+    /// differently in two objects.
+    pub fn load_multi(&mut self, objects: &[(&str, &[u8])]) -> Result<Bpf, BpfError> {
+        let mut bpf = Bpf::empty();
+        let mut namespaces = HashSet::new();
+        for (namespace, data) in objects {
+            if !namespaces.insert(*namespace) {
+                return Err(BpfError::DuplicateNamespace {
+                    namespace: namespace.to_string(),
+                });
+            }
+            self.load_into(&mut bpf, Some(namespace), data, true)?;
+        }
+        self.set_extension_targets(&mut bpf)?;
+
+        Ok(bpf)
+    }
+
+    fn load_object(&mut self, data: &[u8], pin_maps: bool) -> Result<Bpf, BpfError> {
+        let mut bpf = Bpf::empty();
+        self.load_into(&mut bpf, None, data, pin_maps)?;
+        self.set_extension_targets(&mut bpf)?;
+
+        Ok(bpf)
+    }
+
+    fn load_into(
+        &mut self,
+        bpf: &mut Bpf,
+        namespace: Option<&str>,
+        data: &[u8],
+        pin_maps: bool,
+    ) -> Result<(), BpfError> {
+        let data = decompress(data)?;
+        let mut obj = Object::parse_with_aliases(&data, &self.section_aliases)?;
+        let prefix = namespace.map(|ns| format!("{}/", ns)).unwrap_or_default();
+
+        if self.raise_memlock_rlimit && memlock_rlimit_applies() {
+            raise_memlock_rlimit()
+                .map_err(|io_error| BpfError::RaiseMemlockRlimitError { io_error })?;
+        }
 
         if let Some(btf) = &self.btf {
             obj.relocate_btf(btf)?;
         }
 
+        let btf_fd = match &obj.btf {
+            Some(btf) => match load_btf(btf, self.token_fd) {
+                Ok(fd) => Some(fd),
+                // programs can be loaded without BTF, they just lose the debug
+                // info it provides
+                Err(_) if self.btf_fallback => None,
+                Err(e) => return Err(e.into()),
+            },
+            None => None,
+        };
+        bpf.btf_fds.extend(btf_fd);
+
+        if !obj.kconfig.is_empty() {
+            // missing values are reported by resolve_kconfig() for the externs
+            // that need them
+            let config = if obj.needs_kernel_config() {
+                kernel_config().ok()
+            } else {
+                None
+            };
+            let kernel_version = KernelVersion::current().ok().map(|version| version.code());
+            obj.resolve_kconfig(config.as_deref(), kernel_version)?;
+        }
+
+        if !obj.ksyms.is_empty() {
+            let ksyms = if obj.needs_kernel_symbols() {
+                Some(KernelSymbols::load().map_err(|error| BpfError::FileError {
+                    path: PathBuf::from("/proc/kallsyms"),
+                    error,
+                })?)
+            } else {
+                // only used to report the typed ksyms defined in modules
+                KernelSymbols::load().ok()
+            };
+            obj.resolve_ksyms(ksyms.as_ref(), self.btf.as_deref())?;
+        }
+
+        // bpftool finds the `bpf_metadata_*` variables through the BTF type of
+        // the map holding them, their data section
+        let metadata_sections = obj.metadata_sections();
+        let metadata_types = match &obj.btf {
+            Some(btf) => obj
+                .maps
+                .values()
+                .filter(|map| {
+                    map.section_offset.is_none() && metadata_sections.contains(&map.section_index)
+                })
+                .filter_map(|map| {
+                    let type_id = btf.id_by_type_name_kind(&map.name, BtfKind::DataSec).ok()?;
+                    Some((map.name.clone(), type_id))
+                })
+                .collect::<HashMap<_, _>>(),
+            None => HashMap::new(),
+        };
+
+        // shared by the maps that have BTF types, to decode their keys and values
+        let maps_btf = obj.btf.clone().map(Arc::new);
         let mut maps = Vec::new();
+        let mut keys = Vec::new();
+        // the maps created by a previous object, borrowing their descriptors
+        // to relocate this object
+        let mut shared_maps = Vec::new();
         for (_, mut obj) in obj.maps.drain() {
+            if let Some(type_id) = metadata_types.get(&obj.name) {
+                obj.btf_value_type_id = Some(*type_id);
+            }
+            if obj.btf_key_type_id.is_some() || obj.btf_value_type_id.is_some() {
+                obj.btf = maps_btf.clone();
+            }
             if obj.def.map_type == BPF_MAP_TYPE_PERF_EVENT_ARRAY as u32 && obj.def.max_entries == 0
             {
                 obj.def.max_entries = possible_cpus()
@@ -202,13 +881,56 @@ impl<'a> BpfLoader<'a> {
                     })?
                     .len() as u32;
             }
+            // the calls to the bpf_ringbuf_*() helpers are removed when ring
+            // buffers aren't supported, so the map is only referenced and a
+            // minimal array can take its place
+            if obj.def.map_type == BPF_MAP_TYPE_RINGBUF as u32 && !features().ringbuf() {
+                obj.def.map_type = BPF_MAP_TYPE_ARRAY as u32;
+                obj.def.key_size = mem::size_of::<u32>() as u32;
+                obj.def.value_size = mem::size_of::<u32>() as u32;
+                obj.def.max_entries = 1;
+            }
+            // global data maps are private to their object
+            let key = if obj.name.starts_with('.') {
+                format!("{}{}", prefix, obj.name)
+            } else {
+                obj.name.clone()
+            };
+            if let Some(flags) = self.map_flags.get(&key) {
+                // the flags MapFlags doesn't model, like BPF_F_MMAPABLE, are
+                // kept as defined in the object
+                obj.def.map_flags = (obj.def.map_flags & !MapFlags::all().bits()) | flags.bits();
+            }
+            if let Some(node) = self.map_numa_nodes.get(&key) {
+                obj.numa_node = Some(*node);
+            }
+            if let Some(lock) = bpf.maps.get(&key) {
+                let shared = lock
+                    .try_read()
+                    .map_err(|_| MapError::BorrowError { name: key.clone() })?;
+                if !same_definition(&shared.obj.def, &obj.def) {
+                    return Err(BpfError::MapDefinitionMismatch { name: key });
+                }
+                shared_maps.push((obj, shared.fd));
+                continue;
+            }
+
+            let kernel_name = self
+                .kernel_name(&self.map_names, &key, &obj.name)?
+                .unwrap_or_else(|| obj.name.clone());
             let mut map = Map {
                 obj,
                 fd: None,
                 pinned: false,
             };
+            if let Some(fd) = self.map_fds.get(&key) {
+                map.reuse_fd(*fd)?;
+                keys.push(key);
+                maps.push(map);
+                continue;
+            }
             let fd = match map.obj.def.pinning {
-                PinningType::ByName => {
+                PinningType::ByName if pin_maps => {
                     let path = match &self.map_pin_path {
                         Some(p) => p,
                         None => return Err(BpfError::NoPinPath),
@@ -220,15 +942,22 @@ impl<'a> BpfLoader<'a> {
                             fd as RawFd
                         }
                         Err(_) => {
-                            let fd = map.create()?;
+                            let fd = map.create_with_token(
+                                &kernel_name,
+                                self.map_ifindex,
+                                self.token_fd,
+                                btf_fd,
+                            )?;
                             map.pin(path)?;
                             fd
                         }
                     }
                 }
-                PinningType::None => map.create()?,
+                _ => {
+                    map.create_with_token(&kernel_name, self.map_ifindex, self.token_fd, btf_fd)?
+                }
             };
-            if !map.obj.data.is_empty() && map.obj.name != ".bss" {
+            if !map.obj.data.is_empty() && !is_bss_section(&map.obj.name) {
                 bpf_map_update_elem_ptr(fd, &0 as *const _, map.obj.data.as_mut_ptr(), 0).map_err(
                     |(code, io_error)| MapError::SyscallError {
                         call: "bpf_map_update_elem".to_owned(),
@@ -237,90 +966,362 @@ impl<'a> BpfLoader<'a> {
                     },
                 )?;
             }
+            keys.push(key);
             maps.push(map);
         }
 
-        obj.relocate_maps(maps.as_slice())?;
+        let created = maps.len();
+        maps.extend(shared_maps.into_iter().map(|(obj, fd)| Map {
+            obj,
+            fd,
+            pinned: false,
+        }));
+        let relocated = obj.relocate_maps(maps.as_slice());
+        // the descriptors of the shared maps are owned by the object that
+        // created them
+        for mut map in maps.drain(created..) {
+            map.fd.take();
+        }
+        relocated?;
+
+        // the maps the programs don't reference are bound to them, so they
+        // live as long as the programs
+        let mut bound_maps = keys
+            .iter()
+            .zip(&maps)
+            .filter(|(key, map)| {
+                (map.obj.section_offset.is_none()
+                    && metadata_sections.contains(&map.obj.section_index))
+                    || self.bound_maps.contains(*key)
+            })
+            .filter_map(|(_, map)| map.fd)
+            .collect::<Vec<_>>();
+        for name in &self.bound_maps {
+            if let Some(lock) = bpf.maps.get(name) {
+                let map = lock
+                    .try_read()
+                    .map_err(|_| MapError::BorrowError { name: name.clone() })?;
+                bound_maps.extend(map.fd);
+            }
+        }
+
+        // programs skipped by the filter are linked when they're materialized
+        let mut skipped = HashMap::new();
+        if let Some(filter) = &self.program_filter {
+            let names = obj
+                .programs
+                .keys()
+                .filter(|name| !(filter.0)(&format!("{}{}", prefix, name)))
+                .cloned()
+                .collect::<Vec<_>>();
+            for name in names {
+                let program = obj.programs.remove(&name).unwrap();
+                skipped.insert(format!("{}{}", prefix, name), program);
+            }
+        }
         obj.relocate_calls()?;
 
-        let programs = obj
-            .programs
-            .drain()
-            .map(|(name, obj)| {
-                let section = obj.section.clone();
-                let data = ProgramData {
-                    obj,
-                    name: name.clone(),
-                    fd: None,
-                    links: Vec::new(),
-                    expected_attach_type: None,
-                    attach_btf_obj_fd: None,
-                    attach_btf_id: None,
-                };
-                let program = match section {
-                    ProgramSection::KProbe { .. } => Program::KProbe(KProbe {
-                        data,
-                        kind: ProbeKind::KProbe,
-                    }),
-                    ProgramSection::KRetProbe { .. } => Program::KProbe(KProbe {
-                        data,
-                        kind: ProbeKind::KRetProbe,
-                    }),
-                    ProgramSection::UProbe { .. } => Program::UProbe(UProbe {
-                        data,
-                        kind: ProbeKind::UProbe,
-                    }),
-                    ProgramSection::URetProbe { .. } => Program::UProbe(UProbe {
-                        data,
-                        kind: ProbeKind::URetProbe,
-                    }),
-                    ProgramSection::TracePoint { .. } => Program::TracePoint(TracePoint { data }),
-                    ProgramSection::SocketFilter { .. } => {
-                        Program::SocketFilter(SocketFilter { data })
-                    }
-                    ProgramSection::Xdp { .. } => Program::Xdp(Xdp { data }),
-                    ProgramSection::SkMsg { .. } => Program::SkMsg(SkMsg { data }),
-                    ProgramSection::SkSkbStreamParser { .. } => Program::SkSkb(SkSkb {
-                        data,
-                        kind: SkSkbKind::StreamParser,
-                    }),
-                    ProgramSection::SkSkbStreamVerdict { .. } => Program::SkSkb(SkSkb {
-                        data,
-                        kind: SkSkbKind::StreamVerdict,
-                    }),
-                    ProgramSection::SockOps { .. } => Program::SockOps(SockOps { data }),
-                    ProgramSection::SchedClassifier { .. } => {
-                        Program::SchedClassifier(SchedClassifier { data })
-                    }
-                    ProgramSection::CgroupSkbIngress { .. } => Program::CgroupSkb(CgroupSkb {
-                        data,
-                        expected_attach_type: Some(CgroupSkbAttachType::Ingress),
-                    }),
-                    ProgramSection::CgroupSkbEgress { .. } => Program::CgroupSkb(CgroupSkb {
-                        data,
-                        expected_attach_type: Some(CgroupSkbAttachType::Egress),
-                    }),
-                    ProgramSection::LircMode2 { .. } => Program::LircMode2(LircMode2 { data }),
-                    ProgramSection::PerfEvent { .. } => Program::PerfEvent(PerfEvent { data }),
-                    ProgramSection::RawTracePoint { .. } => {
-                        Program::RawTracePoint(RawTracePoint { data })
-                    }
-                    ProgramSection::Lsm { .. } => Program::Lsm(Lsm { data }),
-                    ProgramSection::BtfTracePoint { .. } => {
-                        Program::BtfTracePoint(BtfTracePoint { data })
-                    }
-                };
+        let token_fd = self.token_fd;
+        let run_configs = obj.xdp_run_configs();
+        for (name, obj) in obj.programs.drain() {
+            let key = format!("{}{}", prefix, name);
+            let kernel_name = self.kernel_name(&self.program_names, &key, &name)?;
+            let mut program = new_program(name, obj, btf_fd, token_fd);
+            set_xdp_run_config(&mut program, &run_configs);
+            program.data_mut().kernel_name = kernel_name;
+            program.data_mut().bound_maps = bound_maps.clone();
+            bpf.programs.insert(key, program);
+        }
+        let mut kernel_names = HashMap::new();
+        for key in skipped.keys() {
+            let name = key.strip_prefix(&prefix).unwrap_or(key);
+            if let Some(kernel_name) = self.kernel_name(&self.program_names, key, name)? {
+                kernel_names.insert(key.clone(), kernel_name);
+            }
+        }
 
-                (name, program)
-            })
-            .collect();
-        Ok(Bpf {
-            maps: maps
-                .drain(..)
-                .map(|map| (map.obj.name.clone(), MapLock::new(map)))
-                .collect(),
-            programs,
-        })
+        if !skipped.is_empty() {
+            obj.programs = skipped;
+            bpf.deferred.push(DeferredPrograms {
+                obj,
+                prefix,
+                btf_fd,
+                token_fd,
+                extension_targets: HashMap::new(),
+                kernel_names,
+                bound_maps,
+            });
+        }
+        bpf.maps
+            .extend(keys.into_iter().zip(maps.into_iter().map(MapLock::new)));
+
+        Ok(())
+    }
+
+    // the kernel name of the map or program `key`, named `name` in the object
+    fn kernel_name(
+        &self,
+        names: &HashMap<String, String>,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<String>, BpfError> {
+        if let Some(kernel_name) = names.get(key) {
+            validate_obj_name(kernel_name).map_err(|reason| BpfError::InvalidObjectName {
+                name: kernel_name.clone(),
+                reason,
+            })?;
+            return Ok(Some(kernel_name.clone()));
+        }
+        Ok(self
+            .name_prefix
+            .as_ref()
+            .map(|prefix| sanitize_obj_name(&format!("{}{}", prefix, name))))
+    }
+
+    // extensions skipped by the filter get their target when they're
+    // materialized
+    fn set_extension_targets(&self, bpf: &mut Bpf) -> Result<(), BpfError> {
+        for (name, (target_prog, func_name)) in &self.extension_targets {
+            match bpf.programs.get_mut(name) {
+                Some(Program::Extension(p)) => p.set_target(target_prog, func_name)?,
+                Some(program) => {
+                    return Err(type_mismatch::<Extension>(name, program.type_name()).into())
+                }
+                None => {
+                    let deferred = bpf
+                        .deferred
+                        .iter_mut()
+                        .find(|deferred| deferred.obj.programs.contains_key(name))
+                        .ok_or_else(|| ProgramError::NotFound { name: name.clone() })?;
+                    let fd = target_prog.try_clone_fd()?;
+                    deferred
+                        .extension_targets
+                        .insert(name.clone(), (fd, func_name.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// sets the run config declared with libxdp's XDP_RUN_CONFIG() macro, which is
+// named after the function of the program
+fn set_xdp_run_config(program: &mut Program, run_configs: &HashMap<String, XdpRunConfig>) {
+    if let Program::Xdp(p) = program {
+        if let Some(config) = run_configs.get(&p.data.obj.function.name) {
+            p.run_config = *config;
+        }
+    }
+}
+
+pub(crate) fn new_program(
+    name: String,
+    obj: crate::obj::Program,
+    btf_fd: Option<RawFd>,
+    token_fd: Option<RawFd>,
+) -> Program {
+    let section = obj.section.clone();
+    let data = ProgramData {
+        obj,
+        name,
+        fd: None,
+        links: Vec::new(),
+        expected_attach_type: None,
+        // sleepable programs are declared with the .s section suffix, eg lsm.s/
+        flags: if section.sleepable() {
+            BPF_F_SLEEPABLE
+        } else {
+            0
+        },
+        attach_btf_obj_fd: None,
+        attach_btf_id: None,
+        attach_prog_fd: None,
+        kernel_name: None,
+        bound_maps: Vec::new(),
+        btf_fd,
+        token_fd,
+        ifindex: None,
+    };
+    match section {
+        ProgramSection::KProbe { .. } => Program::KProbe(KProbe {
+            data,
+            kind: ProbeKind::KProbe,
+        }),
+        ProgramSection::KRetProbe { .. } => Program::KProbe(KProbe {
+            data,
+            kind: ProbeKind::KRetProbe,
+        }),
+        ProgramSection::KProbeMulti { .. } => Program::KProbeMulti(KProbeMulti {
+            data,
+            kind: ProbeKind::KProbe,
+        }),
+        ProgramSection::KRetProbeMulti { .. } => Program::KProbeMulti(KProbeMulti {
+            data,
+            kind: ProbeKind::KRetProbe,
+        }),
+        ProgramSection::UProbe { .. } => Program::UProbe(UProbe {
+            data,
+            kind: ProbeKind::UProbe,
+        }),
+        ProgramSection::URetProbe { .. } => Program::UProbe(UProbe {
+            data,
+            kind: ProbeKind::URetProbe,
+        }),
+        ProgramSection::UProbeMulti { .. } => Program::UProbeMulti(UProbeMulti {
+            data,
+            kind: ProbeKind::UProbe,
+        }),
+        ProgramSection::URetProbeMulti { .. } => Program::UProbeMulti(UProbeMulti {
+            data,
+            kind: ProbeKind::URetProbe,
+        }),
+        ProgramSection::Usdt { .. } => Program::Usdt(Usdt { data }),
+        ProgramSection::TracePoint { .. } => Program::TracePoint(TracePoint { data }),
+        ProgramSection::SocketFilter { .. } => Program::SocketFilter(SocketFilter { data }),
+        ProgramSection::Xdp { frags, .. } => Program::Xdp(Xdp {
+            data,
+            frags,
+            run_config: XdpRunConfig::default(),
+        }),
+        ProgramSection::SkMsg { .. } => Program::SkMsg(SkMsg { data }),
+        ProgramSection::SkSkbStreamParser { .. } => Program::SkSkb(SkSkb {
+            data,
+            kind: SkSkbKind::StreamParser,
+        }),
+        ProgramSection::SkSkbStreamVerdict { .. } => Program::SkSkb(SkSkb {
+            data,
+            kind: SkSkbKind::StreamVerdict,
+        }),
+        ProgramSection::SockOps { .. } => Program::SockOps(SockOps { data }),
+        ProgramSection::SchedClassifier { .. } => {
+            Program::SchedClassifier(SchedClassifier { data })
+        }
+        ProgramSection::CgroupSkbIngress { .. } => Program::CgroupSkb(CgroupSkb {
+            data,
+            expected_attach_type: Some(CgroupSkbAttachType::Ingress),
+        }),
+        ProgramSection::CgroupSkbEgress { .. } => Program::CgroupSkb(CgroupSkb {
+            data,
+            expected_attach_type: Some(CgroupSkbAttachType::Egress),
+        }),
+        ProgramSection::LircMode2 { .. } => Program::LircMode2(LircMode2 { data }),
+        ProgramSection::PerfEvent { .. } => Program::PerfEvent(PerfEvent { data }),
+        ProgramSection::RawTracePoint { .. } => Program::RawTracePoint(RawTracePoint { data }),
+        ProgramSection::Lsm { .. } => Program::Lsm(Lsm { data }),
+        ProgramSection::LsmCgroup { .. } => Program::LsmCgroup(LsmCgroup { data }),
+        ProgramSection::BtfTracePoint { .. } => Program::BtfTracePoint(BtfTracePoint { data }),
+        ProgramSection::FlowDissector { .. } => Program::FlowDissector(FlowDissector { data }),
+        ProgramSection::SkReuseport { .. } => Program::SkReuseport(SkReuseport {
+            data,
+            kind: SkReuseportKind::Select,
+        }),
+        ProgramSection::SkReuseportMigrate { .. } => Program::SkReuseport(SkReuseport {
+            data,
+            kind: SkReuseportKind::SelectOrMigrate,
+        }),
+        ProgramSection::Netfilter { .. } => Program::Netfilter(Netfilter { data }),
+        ProgramSection::Extension { .. } => Program::Extension(Extension { data, target: None }),
+        ProgramSection::FEntry { .. } => Program::FEntry(FEntry { data }),
+        ProgramSection::FExit { .. } => Program::FExit(FExit { data }),
+        ProgramSection::Iter { .. } => Program::Iter(Iter { data }),
+    }
+}
+
+// whether a map can be shared by objects defining it as `a` and `b`
+fn same_definition(a: &bpf_map_def, b: &bpf_map_def) -> bool {
+    a.map_type == b.map_type
+        && a.key_size == b.key_size
+        && a.value_size == b.value_size
+        && a.max_entries == b.max_entries
+        && a.map_flags == b.map_flags
+}
+
+pub(crate) fn load_btf(btf: &Btf, token_fd: Option<RawFd>) -> Result<RawFd, BtfError> {
+    let features = features();
+    let features = BtfFeatures {
+        btf_func: features.btf_func(),
+        btf_func_global: features.btf_func_global(),
+        btf_datasec: features.btf_datasec(),
+        btf_float: features.btf_float(),
+        btf_decl_tag: features.btf_decl_tag(),
+        btf_type_tag: features.btf_type_tag(),
+        btf_enum64: features.btf_enum64(),
+    };
+    let raw_btf = btf.sanitize(&features).to_bytes();
+
+    let mut log = VerifierLog::new();
+    let mut retries = 0;
+    loop {
+        match bpf_load_btf(&raw_btf, &mut log, token_fd) {
+            Ok(fd) => return Ok(fd as RawFd),
+            // retry with a log buffer, or a bigger one if the log didn't fit
+            Err((_, io_error)) if retries == 0 || io_error.raw_os_error() == Some(ENOSPC) => {
+                if retries == 10 {
+                    return Err(btf_load_error(io_error, log));
+                }
+                retries += 1;
+                log.grow();
+            }
+            Err((_, io_error)) => return Err(btf_load_error(io_error, log)),
+        }
+    }
+}
+
+fn btf_load_error(io_error: io::Error, mut log: VerifierLog) -> BtfError {
+    log.truncate();
+    BtfError::LoadError {
+        io_error,
+        verifier_log: log
+            .as_c_str()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "[none]".to_owned()),
+    }
+}
+
+/// The result of verifying a program with [`BpfLoader::verify_only`].
+#[derive(Debug)]
+pub struct ProgramVerification {
+    /// The name of the program.
+    pub name: String,
+    /// The statistics of the verification if the program was loaded, or the
+    /// error returned by the kernel, including the verifier log.
+    pub result: Result<VerifierStats, ProgramError>,
+}
+
+/// Statistics about a program that passed the verifier.
+#[derive(Debug, Clone, Copy)]
+pub struct VerifierStats {
+    /// The number of instructions processed by the verifier, 0 on kernels
+    /// older than 5.16.
+    pub verified_insns: u32,
+    /// The size in bytes of the instructions after they were rewritten by the
+    /// verifier.
+    pub xlated_len: u32,
+    /// The size in bytes of the native code, 0 if the program isn't JITed.
+    pub jited_len: u32,
+    /// How long loading the program took, including verification.
+    pub load_time: Duration,
+}
+
+// loads a program the way verify_only() does, using the section name as target
+fn verify_program(
+    name: &str,
+    program: &mut Program,
+    btf: &mut Option<Btf>,
+) -> Result<(), ProgramError> {
+    let lsm_error = |e| match e {
+        LsmLoadError::Btf(e) => ProgramError::BtfError(e),
+        LsmLoadError::Program(e) => e,
+    };
+    match program {
+        Program::BtfTracePoint(p) => p.load(name, kernel_btf(btf)?),
+        Program::FEntry(p) => p.load(name, kernel_btf(btf)?),
+        Program::FExit(p) => p.load(name, kernel_btf(btf)?),
+        Program::Iter(p) => p.load(name, kernel_btf(btf)?),
+        Program::Lsm(p) => p.load(name, kernel_btf(btf)?).map_err(lsm_error),
+        Program::LsmCgroup(p) => p.load(name, kernel_btf(btf)?).map_err(lsm_error),
+        program => program.load(),
     }
 }
 
@@ -335,9 +1336,38 @@ impl<'a> Default for BpfLoader<'a> {
 pub struct Bpf {
     maps: HashMap<String, MapLock>,
     programs: HashMap<String, Program>,
+    deferred: Vec<DeferredPrograms>,
+    btf_fds: Vec<RawFd>,
+}
+
+// the programs skipped by BpfLoader::program_filter, with the object they
+// need to be linked against. The programs are keyed by their namespaced name.
+struct DeferredPrograms {
+    obj: Object,
+    prefix: String,
+    btf_fd: Option<RawFd>,
+    token_fd: Option<RawFd>,
+    extension_targets: HashMap<String, (OwnedFd, String)>,
+    kernel_names: HashMap<String, String>,
+    bound_maps: Vec<RawFd>,
+}
+
+impl fmt::Debug for DeferredPrograms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.obj.programs.keys()).finish()
+    }
 }
 
 impl Bpf {
+    fn empty() -> Bpf {
+        Bpf {
+            maps: HashMap::new(),
+            programs: HashMap::new(),
+            deferred: Vec::new(),
+            btf_fds: Vec::new(),
+        }
+    }
+
     /// Loads eBPF bytecode from a file.
     ///
     /// Parses the given object code file and initializes the [maps](crate::maps) defined in it. If
@@ -517,6 +1547,61 @@ impl Bpf {
             })
     }
 
+    /// Returns a reference to the program with the given name and type.
+    ///
+    /// This is a shortcut for [`program`](Self::program) followed by a conversion to the concrete
+    /// program type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProgramError::NotFound`] if the program does not exist, and
+    /// [`ProgramError::ProgramTypeMismatch`] if the program is not of type `P`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let bpf = aya::Bpf::load(&[])?;
+    /// use aya::programs::UProbe;
+    ///
+    /// let program = bpf.program_typed::<UProbe>("SSL_read")?;
+    /// println!("program SSL_read is a {:?}", program.kind());
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn program_typed<P: TypedProgram>(&self, name: &str) -> Result<&P, ProgramError> {
+        let program = self.program(name)?;
+        P::from_program(program).ok_or_else(|| type_mismatch::<P>(name, program.type_name()))
+    }
+
+    /// Returns a mutable reference to the program with the given name and type.
+    ///
+    /// This is a shortcut for [`program_mut`](Self::program_mut) followed by a conversion to the
+    /// concrete program type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProgramError::NotFound`] if the program does not exist, and
+    /// [`ProgramError::ProgramTypeMismatch`] if the program is not of type `P`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let mut bpf = aya::Bpf::load(&[])?;
+    /// use aya::programs::UProbe;
+    ///
+    /// let program = bpf.program_mut_typed::<UProbe>("SSL_read")?;
+    /// program.load()?;
+    /// program.attach(Some("SSL_read"), 0, "libssl", None)?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn program_mut_typed<P: TypedProgram>(
+        &mut self,
+        name: &str,
+    ) -> Result<&mut P, ProgramError> {
+        let program = self.program_mut(name)?;
+        let found = program.type_name();
+        P::from_program_mut(program).ok_or_else(|| type_mismatch::<P>(name, found))
+    }
+
     /// An iterator over all the programs.
     ///
     /// # Examples
@@ -550,6 +1635,288 @@ impl Bpf {
     pub fn programs_mut(&mut self) -> impl Iterator<Item = &mut Program> {
         self.programs.values_mut()
     }
+
+    /// Returns the names of the programs skipped by
+    /// [`BpfLoader::program_filter`] that haven't been materialized.
+    pub fn skipped_programs(&self) -> impl Iterator<Item = &str> {
+        self.deferred
+            .iter()
+            .flat_map(|deferred| deferred.obj.programs.keys().map(String::as_str))
+    }
+
+    /// Makes a program skipped by [`BpfLoader::program_filter`] available.
+    ///
+    /// The calls of the program are linked, and the program is returned
+    /// ready to be loaded. Afterwards it's also returned by
+    /// [`Bpf::program`] and [`Bpf::programs`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProgramError::NotFound`] if the program wasn't skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::{BpfLoader, programs::KProbe};
+    /// use std::convert::TryInto;
+    ///
+    /// let mut bpf = BpfLoader::new()
+    ///     .program_filter(|name| name == "trace_connect")
+    ///     .load_file("file.o")?;
+    /// // later on, enable another probe
+    /// let program: &mut KProbe = bpf.materialize_program("trace_accept")?.try_into()?;
+    /// program.load()?;
+    /// program.attach("inet_csk_accept", 0)?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn materialize_program(&mut self, name: &str) -> Result<&mut Program, BpfError> {
+        let not_found = || ProgramError::NotFound {
+            name: name.to_owned(),
+        };
+        let index = self
+            .deferred
+            .iter()
+            .position(|deferred| deferred.obj.programs.contains_key(name))
+            .ok_or_else(not_found)?;
+        let deferred = &mut self.deferred[index];
+        let mut obj = deferred.obj.programs.remove(name).unwrap();
+        deferred.obj.relocate_program_calls(name, &mut obj)?;
+
+        let obj_name = name.strip_prefix(&deferred.prefix).unwrap_or(name);
+        let mut program = new_program(obj_name.to_owned(), obj, deferred.btf_fd, deferred.token_fd);
+        set_xdp_run_config(&mut program, &deferred.obj.xdp_run_configs());
+        program.data_mut().kernel_name = deferred.kernel_names.remove(name);
+        program.data_mut().bound_maps = deferred.bound_maps.clone();
+        if let Some(target) = deferred.extension_targets.remove(name) {
+            match &mut program {
+                Program::Extension(p) => p.target = Some(target),
+                program => return Err(type_mismatch::<Extension>(name, program.type_name()).into()),
+            }
+        }
+        if deferred.obj.programs.is_empty() {
+            self.deferred.remove(index);
+        }
+        Ok(self.programs.entry(name.to_owned()).or_insert(program))
+    }
+
+    /// Loads and attaches all the programs, using the attach targets encoded
+    /// in their section names.
+    ///
+    /// See [`Bpf::attach_all_with_targets`] for how the attach targets are
+    /// determined.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+    /// // attaches `kprobe/tcp_connect` and `tracepoint/syscalls/sys_enter_execve`
+    /// bpf.attach_all()?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn attach_all(&mut self) -> Result<(), BpfError> {
+        self.attach_all_with_targets(&HashMap::new())
+    }
+
+    /// Loads and attaches all the programs.
+    ///
+    /// The attach target of a program is looked up by program name in
+    /// `targets`, falling back to the name in the program's section:
+    ///
+    /// * [`KProbe`]: the kernel function, eg `kprobe/tcp_connect`.
+    /// * [`TracePoint`]: the category and name of the tracepoint, eg
+    ///   `tracepoint/syscalls/sys_enter_execve`.
+    /// * [`RawTracePoint`], [`BtfTracePoint`]: the name of the tracepoint.
+    /// * [`FEntry`], [`FExit`]: the kernel function, eg `fentry/tcp_connect`.
+    ///   Functions defined by kernel modules are supported.
+    /// * [`Iter`]: the iterator target, eg `iter/task`. Iterators that take
+    ///   parameters are attached with their defaults.
+    /// * [`Lsm`]: the name of the LSM hook.
+    ///
+    /// These program types can only be attached with an entry in `targets`:
+    ///
+    /// * [`Xdp`]: the interface.
+    /// * [`SchedClassifier`]: the interface, optionally followed by
+    ///   `/ingress` or `/egress`. Programs are attached to ingress by
+    ///   default, and the `clsact` qdisc is added if needed.
+    /// * [`UProbe`]: the target binary or library and the function, separated
+    ///   by `:`, eg `libc:malloc`.
+    /// * [`CgroupSkb`], [`SockOps`], [`LsmCgroup`]: the path of the cgroup.
+    ///
+    /// [`Extension`] programs are attached if their target was set with
+    /// [`BpfLoader::extension_target`] or [`Extension::set_target`].
+    ///
+    /// Programs that have already been loaded are skipped, so programs that
+    /// need custom attach logic can be loaded and attached before calling
+    /// this method. Programs whose attach target can't be determined, and
+    /// programs of the other types, are skipped too and left unloaded, so
+    /// an object is never left partially attached because of a missing
+    /// target.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::collections::HashMap;
+    ///
+    /// let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+    /// let mut targets = HashMap::new();
+    /// targets.insert("xdp_filter".to_owned(), "eth0".to_owned());
+    /// bpf.attach_all_with_targets(&targets)?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn attach_all_with_targets(
+        &mut self,
+        targets: &HashMap<String, String>,
+    ) -> Result<(), BpfError> {
+        let mut btf = None;
+        for (name, program) in self.programs.iter_mut() {
+            if program.fd().is_some() {
+                continue;
+            }
+            let target = targets.get(name).map(String::as_str);
+            // the keys of the programs loaded with load_multi are prefixed
+            // with the namespace, the section name isn't
+            let section_name = program.data().name.clone();
+            let section_target = target.unwrap_or(section_name.as_str());
+
+            match program {
+                Program::KProbe(p) => {
+                    p.load()?;
+                    p.attach(section_target, 0)?;
+                }
+                Program::TracePoint(p) => {
+                    let (category, tp_name) = match section_target.split_once('/') {
+                        Some(tp) => tp,
+                        None => continue,
+                    };
+                    p.load()?;
+                    p.attach(category, tp_name)?;
+                }
+                Program::RawTracePoint(p) => {
+                    p.load()?;
+                    p.attach(section_target)?;
+                }
+                Program::BtfTracePoint(p) => {
+                    p.load(section_target, kernel_btf(&mut btf)?)?;
+                    p.attach()?;
+                }
+                Program::FEntry(p) => {
+                    p.load(section_target, kernel_btf(&mut btf)?)?;
+                    p.attach()?;
+                }
+                Program::FExit(p) => {
+                    p.load(section_target, kernel_btf(&mut btf)?)?;
+                    p.attach()?;
+                }
+                Program::Iter(p) => {
+                    p.load(section_target, kernel_btf(&mut btf)?)?;
+                    p.attach()?;
+                }
+                Program::Lsm(p) => {
+                    p.load(section_target, kernel_btf(&mut btf)?)
+                        .map_err(|e| match e {
+                            LsmLoadError::Btf(e) => BpfError::BtfError(e),
+                            LsmLoadError::Program(e) => BpfError::ProgramError(e),
+                        })?;
+                    p.attach()?;
+                }
+                Program::Xdp(p) => {
+                    let interface = match target {
+                        Some(interface) => interface,
+                        None => continue,
+                    };
+                    p.load()?;
+                    p.attach(interface, XdpFlags::default())?;
+                }
+                Program::SchedClassifier(p) => {
+                    let target = match target {
+                        Some(target) => target,
+                        None => continue,
+                    };
+                    let (interface, attach_type) = match target.rsplit_once('/') {
+                        Some((interface, "ingress")) => (interface, TcAttachType::Ingress),
+                        Some((interface, "egress")) => (interface, TcAttachType::Egress),
+                        _ => (target, TcAttachType::Ingress),
+                    };
+                    // fails if the qdisc already exists
+                    let _ = tc::qdisc_add_clsact(interface);
+                    p.load()?;
+                    p.attach(interface, attach_type)?;
+                }
+                Program::UProbe(p) => {
+                    let (path, fn_name) = match target.and_then(|target| target.rsplit_once(':')) {
+                        Some(target) => target,
+                        None => continue,
+                    };
+                    p.load()?;
+                    p.attach(Some(fn_name), 0, path, None)?;
+                }
+                Program::CgroupSkb(p) => {
+                    let (path, attach_type) = match (target, *p.expected_attach_type()) {
+                        (Some(path), Some(attach_type)) => (path, attach_type),
+                        _ => continue,
+                    };
+                    p.load()?;
+                    p.attach_path(path, attach_type, CgroupAttachFlags::ALLOW_MULTI)?;
+                }
+                Program::SockOps(p) => {
+                    let path = match target {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    p.load()?;
+                    p.attach_path(path, CgroupAttachFlags::ALLOW_MULTI)?;
+                }
+                Program::Extension(p) => {
+                    if p.target.is_none() {
+                        continue;
+                    }
+                    p.load()?;
+                    p.attach()?;
+                }
+                Program::LsmCgroup(p) => {
+                    let path = match target {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    p.load(section_name.as_str(), kernel_btf(&mut btf)?)
+                        .map_err(|e| match e {
+                            LsmLoadError::Btf(e) => BpfError::BtfError(e),
+                            LsmLoadError::Program(e) => BpfError::ProgramError(e),
+                        })?;
+                    p.attach_path(path)?;
+                }
+                // the other program types have no attach target
+                _ => continue,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// loads the kernel BTF the first time it's needed
+fn kernel_btf(btf: &mut Option<Btf>) -> Result<&Btf, BtfError> {
+    if btf.is_none() {
+        *btf = Some(Btf::from_sys_fs()?);
+    }
+    Ok(btf.as_ref().unwrap())
+}
+
+fn type_mismatch<P: TypedProgram>(name: &str, found: &'static str) -> ProgramError {
+    ProgramError::ProgramTypeMismatch {
+        name: name.to_owned(),
+        expected: P::TYPE_NAME,
+        found,
+    }
+}
+
+impl Drop for Bpf {
+    fn drop(&mut self) {
+        // the programs that were loaded hold their own reference to the BTF
+        for fd in self.btf_fds.drain(..) {
+            unsafe { libc::close(fd) };
+        }
+    }
 }
 
 /// The error type returned by [`Bpf::load_file`] and [`Bpf::load`].
@@ -574,9 +1941,33 @@ pub enum BpfError {
     #[error("error parsing BPF object")]
     ParseError(#[from] ParseError),
 
+    #[error("failed to raise RLIMIT_MEMLOCK")]
+    RaiseMemlockRlimitError {
+        #[source]
+        io_error: io::Error,
+    },
+
     #[error("BTF error")]
     BtfError(#[from] BtfError),
 
+    #[error("error resolving kconfig externs")]
+    KConfigError(#[from] KConfigError),
+
+    #[error("error resolving kernel symbol externs")]
+    KSymError(#[from] KSymError),
+
+    #[error(transparent)]
+    DecompressionError(#[from] DecompressionError),
+
+    #[error("invalid map or program name `{name}`: {reason}")]
+    InvalidObjectName { name: String, reason: &'static str },
+
+    #[error("map `{name}` is defined differently in the loaded objects")]
+    MapDefinitionMismatch { name: String },
+
+    #[error("namespace `{namespace}` is used by more than one object")]
+    DuplicateNamespace { namespace: String },
+
     #[error("error relocating `{function}`")]
     RelocationError {
         function: String,
@@ -0,0 +1,640 @@
+//! Runtime probing of the eBPF features supported by the kernel.
+use std::{
+    ffi::{CStr, CString},
+    io, mem,
+    os::unix::io::RawFd,
+};
+
+use libc::{close, E2BIG, EBADF, EINVAL};
+use thiserror::Error;
+
+use crate::{
+    bpf_map_def,
+    generated::{
+        bpf_attach_type::{
+            BPF_CGROUP_GETSOCKOPT, BPF_CGROUP_INET4_CONNECT, BPF_LSM_MAC, BPF_NETFILTER,
+            BPF_PERF_EVENT, BPF_SK_LOOKUP, BPF_TRACE_FENTRY,
+        },
+        bpf_insn,
+        bpf_map_type::BPF_MAP_TYPE_ARRAY,
+        BPF_ALU64, BPF_CALL, BPF_DW, BPF_EXIT, BPF_F_NO_PREALLOC, BPF_IMM, BPF_JMP, BPF_K, BPF_LD,
+        BPF_MEM, BPF_MOV, BPF_PSEUDO_MAP_VALUE, BPF_ST, BTF_INT_SIGNED,
+    },
+    maps::MapType,
+    obj::btf::{Btf, BtfType},
+    programs::{ProgramType, VerifierLog},
+    sys::{
+        bpf_create_map, bpf_link_create, bpf_load_btf, bpf_load_program, is_btf_datasec_supported,
+        is_btf_decl_tag_supported, is_btf_enum64_supported, is_btf_float_supported,
+        is_btf_func_global_supported, is_btf_func_supported, is_btf_supported,
+        is_btf_type_tag_supported, BpfLoadProgramAttrs, MapBtf,
+    },
+    util::KernelVersion,
+};
+
+// the kernel internal ENOTSUPP, returned for some program types that can't be
+// loaded without an attach target
+const ENOTSUPP: i32 = 524;
+
+// helper ids, see enum bpf_func_id in include/uapi/linux/bpf.h
+const BPF_FUNC_PROBE_READ: u32 = 4;
+const BPF_FUNC_PROBE_READ_STR: u32 = 45;
+const BPF_FUNC_PROBE_READ_USER: u32 = 112;
+const BPF_FUNC_PROBE_READ_KERNEL: u32 = 113;
+const BPF_FUNC_PROBE_READ_USER_STR: u32 = 114;
+const BPF_FUNC_PROBE_READ_KERNEL_STR: u32 = 115;
+const BPF_FUNC_RINGBUF_OUTPUT: u32 = 130;
+const BPF_FUNC_RINGBUF_RESERVE: u32 = 131;
+const BPF_FUNC_RINGBUF_SUBMIT: u32 = 132;
+const BPF_FUNC_RINGBUF_DISCARD: u32 = 133;
+const BPF_FUNC_RINGBUF_QUERY: u32 = 134;
+const BPF_FUNC_GET_ATTACH_COOKIE: u32 = 174;
+
+const EOPNOTSUPP: i32 = 95;
+
+lazy_static! {
+    static ref FEATURES: Features = Features::probe();
+}
+
+/// The error type returned when a feature can't be probed.
+#[derive(Debug, Error)]
+pub enum FeatureProbeError {
+    /// A probe syscall failed for a reason other than the feature being
+    /// unsupported, for example because of missing privileges.
+    #[error("`{call}` failed")]
+    SyscallError {
+        call: String,
+        #[source]
+        io_error: io::Error,
+    },
+
+    /// Helpers can't be probed for program types that need an attach target.
+    #[error("can't probe helpers for {prog_type:?} programs")]
+    UnsupportedProgramType { prog_type: ProgramType },
+}
+
+/// Common kernel features, probed once and cached.
+///
+/// # Examples
+///
+/// ```no_run
+/// if aya::features().bpf_cookie() {
+///     // load the variant of the program that uses bpf_get_attach_cookie()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Features {
+    probe_read_kernel: bool,
+    ringbuf: bool,
+    btf: bool,
+    btf_func: bool,
+    btf_func_global: bool,
+    btf_datasec: bool,
+    perf_link: bool,
+    bpf_cookie: bool,
+    global_data: bool,
+    btf_float: bool,
+    btf_decl_tag: bool,
+    btf_type_tag: bool,
+    btf_enum64: bool,
+}
+
+impl Features {
+    fn probe() -> Features {
+        Features {
+            probe_read_kernel: is_helper_supported(ProgramType::KProbe, BPF_FUNC_PROBE_READ_KERNEL)
+                .unwrap_or(false),
+            ringbuf: is_helper_supported(ProgramType::KProbe, BPF_FUNC_RINGBUF_OUTPUT)
+                .unwrap_or(false),
+            perf_link: is_perf_link_supported(),
+            bpf_cookie: is_helper_supported(ProgramType::KProbe, BPF_FUNC_GET_ATTACH_COOKIE)
+                .unwrap_or(false),
+            global_data: is_global_data_supported(),
+            btf: is_btf_supported(),
+            btf_func: is_btf_func_supported(),
+            btf_func_global: is_btf_func_global_supported(),
+            btf_datasec: is_btf_datasec_supported(),
+            btf_float: is_btf_float_supported(),
+            btf_decl_tag: is_btf_decl_tag_supported(),
+            btf_type_tag: is_btf_type_tag_supported(),
+            btf_enum64: is_btf_enum64_supported(),
+        }
+    }
+
+    /// Returns whether the `bpf_probe_read_kernel()` helper is available.
+    pub fn probe_read_kernel(&self) -> bool {
+        self.probe_read_kernel
+    }
+
+    /// Returns whether ring buffers and the `bpf_ringbuf_*()` helpers are
+    /// available.
+    pub fn ringbuf(&self) -> bool {
+        self.ringbuf
+    }
+
+    /// Returns whether perf events can be attached with `bpf_link`s.
+    pub fn perf_link(&self) -> bool {
+        self.perf_link
+    }
+
+    /// Returns whether the `bpf_get_attach_cookie()` helper is available.
+    pub fn bpf_cookie(&self) -> bool {
+        self.bpf_cookie
+    }
+
+    /// Returns whether programs can access global variables (`.data`,
+    /// `.rodata` and `.bss`).
+    pub fn global_data(&self) -> bool {
+        self.global_data
+    }
+
+    /// Returns whether the kernel supports loading BTF.
+    pub fn btf(&self) -> bool {
+        self.btf
+    }
+
+    /// Returns whether BTF supports the `FUNC` and `FUNC_PROTO` kinds.
+    pub fn btf_func(&self) -> bool {
+        self.btf_func
+    }
+
+    /// Returns whether BTF supports functions with global linkage.
+    pub fn btf_func_global(&self) -> bool {
+        self.btf_func_global
+    }
+
+    /// Returns whether BTF supports the `VAR` and `DATASEC` kinds.
+    pub fn btf_datasec(&self) -> bool {
+        self.btf_datasec
+    }
+
+    /// Returns whether BTF supports the `FLOAT` kind.
+    pub fn btf_float(&self) -> bool {
+        self.btf_float
+    }
+
+    /// Returns whether BTF supports the `DECL_TAG` kind.
+    pub fn btf_decl_tag(&self) -> bool {
+        self.btf_decl_tag
+    }
+
+    /// Returns whether BTF supports the `TYPE_TAG` kind.
+    pub fn btf_type_tag(&self) -> bool {
+        self.btf_type_tag
+    }
+
+    /// Returns whether BTF supports the `ENUM64` kind.
+    pub fn btf_enum64(&self) -> bool {
+        self.btf_enum64
+    }
+}
+
+/// Returns the kernel features supported by the running kernel.
+///
+/// The features are probed the first time this function is called.
+pub fn features() -> &'static Features {
+    &FEATURES
+}
+
+/// Returns whether programs of type `prog_type` can be loaded.
+///
+/// The check is done by loading a trivial program of the given type.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::{is_prog_type_supported, programs::ProgramType};
+///
+/// if is_prog_type_supported(ProgramType::Lsm)? {
+///     // attach LSM programs
+/// } else {
+///     // fall back to kprobes
+/// }
+/// # Ok::<(), aya::FeatureProbeError>(())
+/// ```
+pub fn is_prog_type_supported(prog_type: ProgramType) -> Result<bool, FeatureProbeError> {
+    let insns = [mov64_imm(0, 0), exit()];
+    match probe_load(prog_type, &insns) {
+        Ok(fd) => {
+            unsafe { close(fd) };
+            Ok(true)
+        }
+        // these program types can't be loaded without an attach target. If the
+        // kernel knows the type, the verifier complains about the target.
+        Err((io_error, log)) if needs_attach_target(prog_type) => {
+            Ok(!log.is_empty() || io_error.raw_os_error() == Some(ENOTSUPP))
+        }
+        Err((io_error, _)) => unsupported("bpf_prog_load", io_error),
+    }
+}
+
+/// Returns whether maps of type `map_type` can be created.
+///
+/// The check is done by creating a small map of the given type.
+pub fn is_map_type_supported(map_type: MapType) -> Result<bool, FeatureProbeError> {
+    let mut def = bpf_map_def {
+        map_type: map_type.bpf_map_type() as u32,
+        key_size: 4,
+        value_size: 4,
+        max_entries: 1,
+        ..Default::default()
+    };
+    let name = CString::new("aya_probe").unwrap();
+
+    let ret = match map_type {
+        // struct_ops maps need a struct_ops type defined by the kernel, and
+        // were introduced together with struct_ops programs
+        MapType::StructOps => return is_prog_type_supported(ProgramType::StructOps),
+        MapType::SkStorage | MapType::InodeStorage | MapType::TaskStorage => {
+            def.max_entries = 0;
+            def.map_flags = BPF_F_NO_PREALLOC;
+            return is_local_storage_supported(&name, &def);
+        }
+        MapType::LpmTrie => {
+            def.key_size = 8;
+            def.map_flags = BPF_F_NO_PREALLOC;
+            bpf_create_map(&name, &def, None, None, None, None, None)
+        }
+        MapType::Queue | MapType::Stack | MapType::BloomFilter => {
+            def.key_size = 0;
+            bpf_create_map(&name, &def, None, None, None, None, None)
+        }
+        MapType::CgroupStorage | MapType::PerCpuCgroupStorage => {
+            // the key is a struct bpf_cgroup_storage_key
+            def.key_size = 16;
+            def.max_entries = 0;
+            bpf_create_map(&name, &def, None, None, None, None, None)
+        }
+        MapType::RingBuf => {
+            def.key_size = 0;
+            def.value_size = 0;
+            def.max_entries = 4096;
+            bpf_create_map(&name, &def, None, None, None, None, None)
+        }
+        MapType::ArrayOfMaps | MapType::HashOfMaps => {
+            let inner = bpf_map_def {
+                map_type: BPF_MAP_TYPE_ARRAY as u32,
+                ..def
+            };
+            let inner_fd = match bpf_create_map(&name, &inner, None, None, None, None, None) {
+                Ok(fd) => fd as RawFd,
+                Err((_, io_error)) => return unsupported("bpf_map_create", io_error),
+            };
+            let ret = bpf_create_map(&name, &def, None, None, Some(inner_fd), None, None);
+            unsafe { close(inner_fd) };
+            ret
+        }
+        _ => bpf_create_map(&name, &def, None, None, None, None, None),
+    };
+
+    match ret {
+        Ok(fd) => {
+            unsafe { close(fd as RawFd) };
+            Ok(true)
+        }
+        Err((_, io_error)) => unsupported("bpf_map_create", io_error),
+    }
+}
+
+/// Returns whether programs of type `prog_type` can call the helper with id
+/// `helper`.
+///
+/// The helper ids are listed in `enum bpf_func_id` in
+/// `include/uapi/linux/bpf.h`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::{is_helper_supported, programs::ProgramType};
+///
+/// // BPF_FUNC_ringbuf_output
+/// let ringbuf = is_helper_supported(ProgramType::KProbe, 130)?;
+/// # Ok::<(), aya::FeatureProbeError>(())
+/// ```
+pub fn is_helper_supported(prog_type: ProgramType, helper: u32) -> Result<bool, FeatureProbeError> {
+    if needs_attach_target(prog_type) {
+        return Err(FeatureProbeError::UnsupportedProgramType { prog_type });
+    }
+
+    let insns = [call(helper), exit()];
+    match probe_load(prog_type, &insns) {
+        Ok(fd) => {
+            unsafe { close(fd) };
+            Ok(true)
+        }
+        // an empty log means that the program type itself isn't supported
+        Err((_, log)) if log.is_empty() => Ok(false),
+        Err((_, log)) => Ok(!(log.contains("invalid func ") || log.contains("unknown func "))),
+    }
+}
+
+fn needs_attach_target(prog_type: ProgramType) -> bool {
+    matches!(
+        prog_type,
+        ProgramType::Tracing
+            | ProgramType::Extension
+            | ProgramType::Lsm
+            | ProgramType::StructOps
+            | ProgramType::Syscall
+    )
+}
+
+fn unsupported(call: &str, io_error: io::Error) -> Result<bool, FeatureProbeError> {
+    match io_error.raw_os_error() {
+        Some(EINVAL) | Some(E2BIG) => Ok(false),
+        _ => Err(FeatureProbeError::SyscallError {
+            call: call.to_owned(),
+            io_error,
+        }),
+    }
+}
+
+fn is_local_storage_supported(name: &CStr, def: &bpf_map_def) -> Result<bool, FeatureProbeError> {
+    // local storage maps require BTF for their key and value types
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let btf_fd = match bpf_load_btf(&btf.to_bytes(), &mut VerifierLog::new(), None) {
+        Ok(fd) => fd as RawFd,
+        // without BTF there can't be local storage
+        Err(_) => return Ok(false),
+    };
+    let btf = MapBtf {
+        fd: btf_fd,
+        key_type_id: int_type_id,
+        value_type_id: int_type_id,
+    };
+    let ret = bpf_create_map(name, def, None, None, None, None, Some(btf));
+    unsafe { close(btf_fd) };
+
+    match ret {
+        Ok(fd) => {
+            unsafe { close(fd as RawFd) };
+            Ok(true)
+        }
+        Err((_, io_error)) => unsupported("bpf_map_create", io_error),
+    }
+}
+
+fn is_perf_link_supported() -> bool {
+    let insns = [mov64_imm(0, 0), exit()];
+    let prog_fd = match probe_load(ProgramType::TracePoint, &insns) {
+        Ok(fd) => fd,
+        Err(_) => return false,
+    };
+    // the kernel checks the attach type before the perf event fd, so EBADF
+    // means that BPF_PERF_EVENT is a known attach type
+    let ret = bpf_link_create(prog_fd, -1, BPF_PERF_EVENT, 0);
+    unsafe { close(prog_fd) };
+
+    match ret {
+        Ok(fd) => {
+            unsafe { close(fd as RawFd) };
+            true
+        }
+        Err((_, io_error)) => io_error.raw_os_error() == Some(EBADF),
+    }
+}
+
+fn is_global_data_supported() -> bool {
+    let def = bpf_map_def {
+        map_type: BPF_MAP_TYPE_ARRAY as u32,
+        key_size: 4,
+        value_size: 32,
+        max_entries: 1,
+        ..Default::default()
+    };
+    let map_fd = match bpf_create_map(
+        &CString::new("aya_global").unwrap(),
+        &def,
+        None,
+        None,
+        None,
+        None,
+        None,
+    ) {
+        Ok(fd) => fd as RawFd,
+        Err(_) => return false,
+    };
+
+    let insns = [
+        // r1 = &map[0]
+        insn(
+            BPF_LD | BPF_DW | BPF_IMM,
+            1,
+            BPF_PSEUDO_MAP_VALUE as u8,
+            0,
+            map_fd,
+        ),
+        insn(0, 0, 0, 0, 0),
+        // *(u64 *)r1 = 42
+        insn(BPF_ST | BPF_DW | BPF_MEM, 1, 0, 0, 42),
+        mov64_imm(0, 0),
+        exit(),
+    ];
+    let ret = probe_load(ProgramType::SocketFilter, &insns);
+    unsafe { close(map_fd) };
+
+    match ret {
+        Ok(fd) => {
+            unsafe { close(fd) };
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn probe_load(prog_type: ProgramType, insns: &[bpf_insn]) -> Result<RawFd, (io::Error, String)> {
+    let (expected_attach_type, attach_btf_id) = match prog_type {
+        ProgramType::CgroupSockAddr => (Some(BPF_CGROUP_INET4_CONNECT), None),
+        ProgramType::CgroupSockopt => (Some(BPF_CGROUP_GETSOCKOPT), None),
+        ProgramType::SkLookup => (Some(BPF_SK_LOOKUP), None),
+        ProgramType::Netfilter => (Some(BPF_NETFILTER), None),
+        // type id 1 is never a function, the load is expected to fail
+        ProgramType::Tracing => (Some(BPF_TRACE_FENTRY), Some(1)),
+        ProgramType::Lsm => (Some(BPF_LSM_MAC), Some(1)),
+        ProgramType::Extension => (None, Some(1)),
+        _ => (None, None),
+    };
+    // kprobes need the kernel version on older kernels
+    let kernel_version = KernelVersion::current()
+        .map(|version| version.code())
+        .unwrap_or(0);
+    let license = CString::new("GPL").unwrap();
+    let mut log = VerifierLog::new();
+    log.grow();
+
+    let ret = bpf_load_program(BpfLoadProgramAttrs {
+        name: None,
+        ty: prog_type.bpf_prog_type(),
+        insns,
+        license: &license,
+        kernel_version,
+        expected_attach_type,
+        flags: 0,
+        attach_btf_obj_fd: None,
+        attach_btf_id,
+        attach_prog_fd: None,
+        prog_btf_fd: None,
+        token_fd: None,
+        prog_ifindex: None,
+        func_info: &[],
+        line_info: &[],
+        log: &mut log,
+    });
+
+    match ret {
+        Ok(fd) => Ok(fd as RawFd),
+        Err((_, io_error)) => {
+            log.truncate();
+            let log = log
+                .as_c_str()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            Err((io_error, log))
+        }
+    }
+}
+
+/// Rewrites the helper calls of `insns` that the running kernel doesn't
+/// support, like libbpf does, so that the same object can be loaded on older
+/// kernels.
+///
+/// * `bpf_probe_read_{kernel,user}[_str]()` become `bpf_probe_read[_str]()`,
+///   which read both kernel and user memory before 5.5.
+/// * The `bpf_ringbuf_*()` calls are replaced by their failure return value:
+///   no record is reserved, and `bpf_ringbuf_output()` returns
+///   `-EOPNOTSUPP`. Programs handling the failure of `bpf_ringbuf_reserve()`
+///   still pass the verifier and just don't produce events.
+pub(crate) fn sanitize_helper_calls(
+    insns: &mut [bpf_insn],
+    probe_read_kernel: bool,
+    ringbuf: bool,
+) {
+    for insn in insns.iter_mut() {
+        // calls to other functions and kfuncs have a non zero src_reg
+        if insn.code != (BPF_JMP | BPF_CALL) as u8 || insn.src_reg() != 0 {
+            continue;
+        }
+        match insn.imm as u32 {
+            BPF_FUNC_PROBE_READ_KERNEL | BPF_FUNC_PROBE_READ_USER if !probe_read_kernel => {
+                insn.imm = BPF_FUNC_PROBE_READ as i32;
+            }
+            BPF_FUNC_PROBE_READ_KERNEL_STR | BPF_FUNC_PROBE_READ_USER_STR if !probe_read_kernel => {
+                insn.imm = BPF_FUNC_PROBE_READ_STR as i32;
+            }
+            BPF_FUNC_RINGBUF_OUTPUT if !ringbuf => *insn = mov64_imm(0, -EOPNOTSUPP),
+            BPF_FUNC_RINGBUF_RESERVE
+            | BPF_FUNC_RINGBUF_SUBMIT
+            | BPF_FUNC_RINGBUF_DISCARD
+            | BPF_FUNC_RINGBUF_QUERY
+                if !ringbuf =>
+            {
+                *insn = mov64_imm(0, 0)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn insn(code: u32, dst_reg: u8, src_reg: u8, off: i16, imm: i32) -> bpf_insn {
+    let mut insn = unsafe { mem::zeroed::<bpf_insn>() };
+    insn.code = code as u8;
+    insn.set_dst_reg(dst_reg);
+    insn.set_src_reg(src_reg);
+    insn.off = off;
+    insn.imm = imm;
+    insn
+}
+
+fn mov64_imm(dst_reg: u8, imm: i32) -> bpf_insn {
+    insn(BPF_ALU64 | BPF_MOV | BPF_K, dst_reg, 0, 0, imm)
+}
+
+fn call(helper: u32) -> bpf_insn {
+    insn(BPF_JMP | BPF_CALL, 0, 0, 0, helper as i32)
+}
+
+fn exit() -> bpf_insn {
+    insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        generated::bpf_cmd,
+        sys::{override_syscall, Syscall},
+    };
+
+    #[test]
+    fn test_sanitize_helper_calls() {
+        let mut insns = [
+            call(BPF_FUNC_PROBE_READ_KERNEL),
+            call(BPF_FUNC_PROBE_READ_USER_STR),
+            call(BPF_FUNC_RINGBUF_RESERVE),
+            call(BPF_FUNC_RINGBUF_OUTPUT),
+            // a bpf-to-bpf call
+            insn(BPF_JMP | BPF_CALL, 0, 1, 0, BPF_FUNC_RINGBUF_OUTPUT as i32),
+        ];
+
+        let mut supported = insns;
+        sanitize_helper_calls(&mut supported, true, true);
+        assert!(supported
+            .iter()
+            .zip(insns.iter())
+            .all(|(a, b)| a.code == b.code && a.imm == b.imm));
+
+        sanitize_helper_calls(&mut insns, false, false);
+        assert_eq!(insns[0].imm, BPF_FUNC_PROBE_READ as i32);
+        assert_eq!(insns[1].imm, BPF_FUNC_PROBE_READ_STR as i32);
+        assert_eq!(insns[2].code, (BPF_ALU64 | BPF_MOV | BPF_K) as u8);
+        assert_eq!((insns[2].dst_reg(), insns[2].imm), (0, 0));
+        assert_eq!((insns[3].dst_reg(), insns[3].imm), (0, -EOPNOTSUPP));
+        assert_eq!(insns[4].code, (BPF_JMP | BPF_CALL) as u8);
+        assert_eq!(insns[4].imm, BPF_FUNC_RINGBUF_OUTPUT as i32);
+    }
+
+    #[test]
+    fn test_prog_type_not_supported() {
+        override_syscall(|_| Err((-1, io::Error::from_raw_os_error(EINVAL))));
+        assert!(!is_prog_type_supported(ProgramType::Xdp).unwrap());
+        assert!(!is_prog_type_supported(ProgramType::Lsm).unwrap());
+        assert!(!is_helper_supported(ProgramType::KProbe, BPF_FUNC_GET_ATTACH_COOKIE).unwrap());
+    }
+
+    #[test]
+    fn test_prog_type_supported() {
+        override_syscall(|call| match call {
+            Syscall::Bpf {
+                cmd: bpf_cmd::BPF_PROG_LOAD,
+                ..
+            } => Ok(i32::MAX as libc::c_long),
+            _ => Err((-1, io::Error::from_raw_os_error(EINVAL))),
+        });
+        assert!(is_prog_type_supported(ProgramType::Xdp).unwrap());
+    }
+
+    #[test]
+    fn test_probe_error() {
+        override_syscall(|_| Err((-1, io::Error::from_raw_os_error(libc::EPERM))));
+        assert_matches!(
+            is_prog_type_supported(ProgramType::Xdp),
+            Err(FeatureProbeError::SyscallError { .. })
+        );
+        assert_matches!(
+            is_map_type_supported(MapType::Hash),
+            Err(FeatureProbeError::SyscallError { .. })
+        );
+    }
+
+    #[test]
+    fn test_helper_probe_needs_attach_target() {
+        assert_matches!(
+            is_helper_supported(ProgramType::Lsm, BPF_FUNC_PROBE_READ_KERNEL),
+            Err(FeatureProbeError::UnsupportedProgramType { .. })
+        );
+    }
+}
@@ -93,6 +93,31 @@ pub const BPF_K: u32 = 0;
 pub const BPF_ALU64: u32 = 7;
 pub const BPF_DW: u32 = 24;
 pub const BPF_CALL: u32 = 128;
+pub const BPF_IMM: u32 = 0;
+pub const BPF_MEM: u32 = 96;
+pub const BPF_MOV: u32 = 176;
+pub const BPF_EXIT: u32 = 144;
+pub const BPF_X: u32 = 8;
+pub const BPF_AND: u32 = 80;
+pub const BPF_LSH: u32 = 96;
+pub const BPF_JEQ: u32 = 16;
+pub const BPF_F_ALLOW_OVERRIDE: u32 = 1;
+pub const BPF_F_ALLOW_MULTI: u32 = 2;
+pub const BPF_F_REPLACE: u32 = 4;
+pub const BPF_F_BEFORE: u32 = 8;
+pub const BPF_F_AFTER: u32 = 16;
+pub const BPF_F_ID: u32 = 32;
+pub const BPF_F_LINK: u32 = 8192;
+pub const BPF_F_SLEEPABLE: u32 = 16;
+pub const BPF_F_XDP_HAS_FRAGS: u32 = 32;
+pub const BPF_F_NO_PREALLOC: u32 = 1;
+pub const BPF_F_NUMA_NODE: u32 = 4;
+pub const BPF_F_LOCK: u32 = 4;
+pub const BPF_F_RDONLY_PROG: u32 = 128;
+pub const BPF_F_WRONLY_PROG: u32 = 256;
+pub const BPF_F_STACK_BUILD_ID: u32 = 32;
+pub const BPF_F_INNER_MAP: u32 = 4096;
+pub const BPF_F_TOKEN_FD: u32 = 65536;
 pub const BPF_PSEUDO_MAP_FD: u32 = 1;
 pub const BPF_PSEUDO_MAP_IDX: u32 = 5;
 pub const BPF_PSEUDO_MAP_VALUE: u32 = 2;
@@ -118,8 +143,13 @@ pub const XDP_FLAGS_HW_MODE: u32 = 8;
 pub const XDP_FLAGS_REPLACE: u32 = 16;
 pub const XDP_FLAGS_MODES: u32 = 14;
 pub const XDP_FLAGS_MASK: u32 = 31;
+pub const SO_BUSY_POLL: u32 = 46;
 pub const SO_ATTACH_BPF: u32 = 50;
 pub const SO_DETACH_BPF: u32 = 27;
+pub const SO_ATTACH_REUSEPORT_EBPF: u32 = 52;
+pub const SO_DETACH_REUSEPORT_BPF: u32 = 68;
+pub const SO_PREFER_BUSY_POLL: u32 = 69;
+pub const SO_BUSY_POLL_BUDGET: u32 = 70;
 pub const TC_H_MAJ_MASK: u32 = 4294901760;
 pub const TC_H_MIN_MASK: u32 = 65535;
 pub const TC_H_UNSPEC: u32 = 0;
@@ -130,6 +160,27 @@ pub const TC_H_MIN_PRIORITY: u32 = 65504;
 pub const TC_H_MIN_INGRESS: u32 = 65522;
 pub const TC_H_MIN_EGRESS: u32 = 65523;
 pub const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1;
+pub const TCA_CLS_FLAGS_SKIP_HW: u32 = 1;
+pub const TCA_CLS_FLAGS_SKIP_SW: u32 = 2;
+pub const XDP_SHARED_UMEM: u32 = 1;
+pub const XDP_COPY: u32 = 2;
+pub const XDP_ZEROCOPY: u32 = 4;
+pub const XDP_USE_NEED_WAKEUP: u32 = 8;
+pub const XDP_UMEM_UNALIGNED_CHUNK_FLAG: u32 = 1;
+pub const XDP_RING_NEED_WAKEUP: u32 = 1;
+pub const XDP_MMAP_OFFSETS: u32 = 1;
+pub const XDP_RX_RING: u32 = 2;
+pub const XDP_TX_RING: u32 = 3;
+pub const XDP_UMEM_REG: u32 = 4;
+pub const XDP_UMEM_FILL_RING: u32 = 5;
+pub const XDP_UMEM_COMPLETION_RING: u32 = 6;
+pub const XDP_STATISTICS: u32 = 7;
+pub const XDP_PGOFF_RX_RING: u32 = 0;
+pub const XDP_PGOFF_TX_RING: u32 = 2147483648;
+pub const XDP_UMEM_PGOFF_FILL_RING: u64 = 4294967296;
+pub const XDP_UMEM_PGOFF_COMPLETION_RING: u64 = 6442450944;
+pub const NETDEV_FAMILY_NAME: &[u8; 7usize] = b"netdev\0";
+pub const NETDEV_FAMILY_VERSION: u32 = 1;
 pub type __u8 = ::std::os::raw::c_uchar;
 pub type __s16 = ::std::os::raw::c_short;
 pub type __u16 = ::std::os::raw::c_ushort;
@@ -137,6 +188,8 @@ pub type __s32 = ::std::os::raw::c_int;
 pub type __u32 = ::std::os::raw::c_uint;
 pub type __s64 = ::std::os::raw::c_longlong;
 pub type __u64 = ::std::os::raw::c_ulonglong;
+pub type __be16 = __u16;
+pub type __be32 = __u32;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct bpf_insn {
@@ -225,6 +278,7 @@ pub enum bpf_cmd {
     BPF_ITER_CREATE = 33,
     BPF_LINK_DETACH = 34,
     BPF_PROG_BIND_MAP = 35,
+    BPF_TOKEN_CREATE = 36,
 }
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -296,6 +350,7 @@ pub enum bpf_prog_type {
     BPF_PROG_TYPE_LSM = 29,
     BPF_PROG_TYPE_SK_LOOKUP = 30,
     BPF_PROG_TYPE_SYSCALL = 31,
+    BPF_PROG_TYPE_NETFILTER = 32,
 }
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -342,7 +397,14 @@ pub enum bpf_attach_type {
     BPF_SK_REUSEPORT_SELECT = 39,
     BPF_SK_REUSEPORT_SELECT_OR_MIGRATE = 40,
     BPF_PERF_EVENT = 41,
-    __MAX_BPF_ATTACH_TYPE = 42,
+    BPF_TRACE_KPROBE_MULTI = 42,
+    BPF_LSM_CGROUP = 43,
+    BPF_STRUCT_OPS = 44,
+    BPF_NETFILTER = 45,
+    BPF_TCX_INGRESS = 46,
+    BPF_TCX_EGRESS = 47,
+    BPF_TRACE_UPROBE_MULTI = 48,
+    __MAX_BPF_ATTACH_TYPE = 49,
 }
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -366,6 +428,7 @@ pub union bpf_attr {
     pub enable_stats: bpf_attr__bindgen_ty_17,
     pub iter_create: bpf_attr__bindgen_ty_18,
     pub prog_bind_map: bpf_attr__bindgen_ty_19,
+    pub token_create: bpf_attr__bindgen_ty_20,
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -384,6 +447,8 @@ pub struct bpf_attr__bindgen_ty_1 {
     pub btf_value_type_id: __u32,
     pub btf_vmlinux_value_type_id: __u32,
     pub map_extra: __u64,
+    pub value_type_btf_obj_fd: __s32,
+    pub map_token_fd: __s32,
 }
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -438,6 +503,10 @@ pub struct bpf_attr__bindgen_ty_4 {
     pub _bitfield_align_1: [u8; 0],
     pub _bitfield_1: __BindgenBitfieldUnit<[u8; 4usize]>,
     pub fd_array: __u64,
+    pub core_relos: __u64,
+    pub core_relo_rec_size: __u32,
+    pub log_true_size: __u32,
+    pub prog_token_fd: __s32,
 }
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -533,6 +602,9 @@ pub struct bpf_attr__bindgen_ty_12 {
     pub btf_size: __u32,
     pub btf_log_size: __u32,
     pub btf_log_level: __u32,
+    pub btf_log_true_size: __u32,
+    pub btf_flags: __u32,
+    pub btf_token_fd: __s32,
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -568,6 +640,11 @@ pub union bpf_attr__bindgen_ty_14__bindgen_ty_2 {
     pub target_btf_id: __u32,
     pub __bindgen_anon_1: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_1,
     pub perf_event: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_2,
+    pub kprobe_multi: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_3,
+    pub tracing: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_4,
+    pub netfilter: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_5,
+    pub tcx: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_6,
+    pub uprobe_multi: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_7,
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -582,6 +659,52 @@ pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_2 {
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_3 {
+    pub flags: __u32,
+    pub cnt: __u32,
+    pub syms: __u64,
+    pub addrs: __u64,
+    pub cookies: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_4 {
+    pub target_btf_id: __u32,
+    pub cookie: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_5 {
+    pub pf: __u32,
+    pub hooknum: __u32,
+    pub priority: __s32,
+    pub flags: __u32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_6 {
+    pub __bindgen_anon_1: bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_6__bindgen_ty_1,
+    pub expected_revision: __u64,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_6__bindgen_ty_1 {
+    pub relative_fd: __u32,
+    pub relative_id: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_14__bindgen_ty_2__bindgen_ty_7 {
+    pub path: __u64,
+    pub offsets: __u64,
+    pub ref_ctr_offsets: __u64,
+    pub cookies: __u64,
+    pub cnt: __u32,
+    pub flags: __u32,
+    pub pid: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct bpf_attr__bindgen_ty_15 {
     pub link_fd: __u32,
     pub new_prog_fd: __u32,
@@ -613,6 +736,77 @@ pub struct bpf_attr__bindgen_ty_19 {
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+pub struct bpf_attr__bindgen_ty_20 {
+    pub flags: __u32,
+    pub bpffs_fd: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_map_info {
+    pub type_: __u32,
+    pub id: __u32,
+    pub key_size: __u32,
+    pub value_size: __u32,
+    pub max_entries: __u32,
+    pub map_flags: __u32,
+    pub name: [::std::os::raw::c_char; 16usize],
+    pub ifindex: __u32,
+    pub btf_vmlinux_value_type_id: __u32,
+    pub netns_dev: __u64,
+    pub netns_ino: __u64,
+    pub btf_id: __u32,
+    pub btf_key_type_id: __u32,
+    pub btf_value_type_id: __u32,
+    pub btf_vmlinux_id: __u32,
+    pub map_extra: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_btf_info {
+    pub btf: __u64,
+    pub btf_size: __u32,
+    pub id: __u32,
+    pub name: __u64,
+    pub name_len: __u32,
+    pub kernel_btf: __u32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct bpf_flow_keys {
+    pub nhoff: __u16,
+    pub thoff: __u16,
+    pub addr_proto: __u16,
+    pub is_frag: __u8,
+    pub is_first_frag: __u8,
+    pub is_encap: __u8,
+    pub ip_proto: __u8,
+    pub n_proto: __be16,
+    pub sport: __be16,
+    pub dport: __be16,
+    pub __bindgen_anon_1: bpf_flow_keys__bindgen_ty_1,
+    pub flags: __u32,
+    pub flow_label: __be32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union bpf_flow_keys__bindgen_ty_1 {
+    pub __bindgen_anon_1: bpf_flow_keys__bindgen_ty_1__bindgen_ty_1,
+    pub __bindgen_anon_2: bpf_flow_keys__bindgen_ty_1__bindgen_ty_2,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_flow_keys__bindgen_ty_1__bindgen_ty_1 {
+    pub ipv4_src: __be32,
+    pub ipv4_dst: __be32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_flow_keys__bindgen_ty_1__bindgen_ty_2 {
+    pub ipv6_src: [__u32; 4usize],
+    pub ipv6_dst: [__u32; 4usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct bpf_prog_info {
     pub type_: __u32,
     pub id: __u32,
@@ -676,6 +870,136 @@ impl bpf_prog_info {
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+pub struct bpf_func_info {
+    pub insn_off: __u32,
+    pub type_id: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_line_info {
+    pub insn_off: __u32,
+    pub file_name_off: __u32,
+    pub line_off: __u32,
+    pub line_col: __u32,
+}
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum bpf_cgroup_iter_order {
+    BPF_CGROUP_ITER_ORDER_UNSPEC = 0,
+    BPF_CGROUP_ITER_SELF_ONLY = 1,
+    BPF_CGROUP_ITER_DESCENDANTS_PRE = 2,
+    BPF_CGROUP_ITER_DESCENDANTS_POST = 3,
+    BPF_CGROUP_ITER_ANCESTORS_UP = 4,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union bpf_iter_link_info {
+    pub map: bpf_iter_link_info__bindgen_ty_1,
+    pub cgroup: bpf_iter_link_info__bindgen_ty_2,
+    pub task: bpf_iter_link_info__bindgen_ty_3,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_iter_link_info__bindgen_ty_1 {
+    pub map_fd: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_iter_link_info__bindgen_ty_2 {
+    pub order: bpf_cgroup_iter_order,
+    pub cgroup_fd: __u32,
+    pub cgroup_id: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_iter_link_info__bindgen_ty_3 {
+    pub tid: __u32,
+    pub pid: __u32,
+    pub pid_fd: __u32,
+}
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum bpf_link_type {
+    BPF_LINK_TYPE_UNSPEC = 0,
+    BPF_LINK_TYPE_RAW_TRACEPOINT = 1,
+    BPF_LINK_TYPE_TRACING = 2,
+    BPF_LINK_TYPE_CGROUP = 3,
+    BPF_LINK_TYPE_ITER = 4,
+    BPF_LINK_TYPE_NETNS = 5,
+    BPF_LINK_TYPE_XDP = 6,
+    BPF_LINK_TYPE_PERF_EVENT = 7,
+    BPF_LINK_TYPE_KPROBE_MULTI = 8,
+    BPF_LINK_TYPE_STRUCT_OPS = 9,
+    BPF_LINK_TYPE_NETFILTER = 10,
+    BPF_LINK_TYPE_TCX = 11,
+    BPF_LINK_TYPE_UPROBE_MULTI = 12,
+    MAX_BPF_LINK_TYPE = 13,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct bpf_link_info {
+    pub type_: __u32,
+    pub id: __u32,
+    pub prog_id: __u32,
+    pub __bindgen_anon_1: bpf_link_info__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union bpf_link_info__bindgen_ty_1 {
+    pub raw_tracepoint: bpf_link_info__bindgen_ty_1__bindgen_ty_1,
+    pub tracing: bpf_link_info__bindgen_ty_1__bindgen_ty_2,
+    pub cgroup: bpf_link_info__bindgen_ty_1__bindgen_ty_3,
+    pub netns: bpf_link_info__bindgen_ty_1__bindgen_ty_4,
+    pub xdp: bpf_link_info__bindgen_ty_1__bindgen_ty_5,
+    pub netfilter: bpf_link_info__bindgen_ty_1__bindgen_ty_6,
+    pub tcx: bpf_link_info__bindgen_ty_1__bindgen_ty_7,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_1 {
+    pub tp_name: __u64,
+    pub tp_name_len: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_2 {
+    pub attach_type: __u32,
+    pub target_obj_id: __u32,
+    pub target_btf_id: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_3 {
+    pub cgroup_id: __u64,
+    pub attach_type: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_4 {
+    pub netns_ino: __u32,
+    pub attach_type: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_5 {
+    pub ifindex: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_6 {
+    pub pf: __u32,
+    pub hooknum: __u32,
+    pub priority: __s32,
+    pub flags: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct bpf_link_info__bindgen_ty_1__bindgen_ty_7 {
+    pub ifindex: __u32,
+    pub attach_type: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct btf_header {
     pub magic: __u16,
     pub version: __u8,
@@ -717,8 +1041,10 @@ pub const BTF_KIND_VAR: ::std::os::raw::c_uint = 14;
 pub const BTF_KIND_DATASEC: ::std::os::raw::c_uint = 15;
 pub const BTF_KIND_FLOAT: ::std::os::raw::c_uint = 16;
 pub const BTF_KIND_DECL_TAG: ::std::os::raw::c_uint = 17;
-pub const NR_BTF_KINDS: ::std::os::raw::c_uint = 18;
-pub const BTF_KIND_MAX: ::std::os::raw::c_uint = 17;
+pub const BTF_KIND_TYPE_TAG: ::std::os::raw::c_uint = 18;
+pub const BTF_KIND_ENUM64: ::std::os::raw::c_uint = 19;
+pub const NR_BTF_KINDS: ::std::os::raw::c_uint = 20;
+pub const BTF_KIND_MAX: ::std::os::raw::c_uint = 19;
 pub type _bindgen_ty_37 = ::std::os::raw::c_uint;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -758,6 +1084,18 @@ pub struct btf_var_secinfo {
     pub offset: __u32,
     pub size: __u32,
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct btf_decl_tag {
+    pub component_idx: __s32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct btf_enum64 {
+    pub name_off: __u32,
+    pub val_lo32: __u32,
+    pub val_hi32: __u32,
+}
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum perf_type_id {
@@ -853,6 +1191,15 @@ pub enum perf_event_sample_format {
     PERF_SAMPLE_MAX = 1048576,
     __PERF_SAMPLE_CALLCHAIN_EARLY = 9223372036854775808,
 }
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum perf_event_read_format {
+    PERF_FORMAT_TOTAL_TIME_ENABLED = 1,
+    PERF_FORMAT_TOTAL_TIME_RUNNING = 2,
+    PERF_FORMAT_ID = 4,
+    PERF_FORMAT_GROUP = 8,
+    PERF_FORMAT_MAX = 16,
+}
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct perf_event_attr {
@@ -1626,6 +1973,20 @@ pub enum _bindgen_ty_81 {
     IFLA_XDP_EXPECTED_FD = 8,
     __IFLA_XDP_MAX = 9,
 }
+pub const XDP_ATTACHED_NONE: _bindgen_ty_152 = _bindgen_ty_152::XDP_ATTACHED_NONE;
+pub const XDP_ATTACHED_DRV: _bindgen_ty_152 = _bindgen_ty_152::XDP_ATTACHED_DRV;
+pub const XDP_ATTACHED_SKB: _bindgen_ty_152 = _bindgen_ty_152::XDP_ATTACHED_SKB;
+pub const XDP_ATTACHED_HW: _bindgen_ty_152 = _bindgen_ty_152::XDP_ATTACHED_HW;
+pub const XDP_ATTACHED_MULTI: _bindgen_ty_152 = _bindgen_ty_152::XDP_ATTACHED_MULTI;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum _bindgen_ty_152 {
+    XDP_ATTACHED_NONE = 0,
+    XDP_ATTACHED_DRV = 1,
+    XDP_ATTACHED_SKB = 2,
+    XDP_ATTACHED_HW = 3,
+    XDP_ATTACHED_MULTI = 4,
+}
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ifinfomsg {
@@ -1713,6 +2074,96 @@ pub enum _bindgen_ty_149 {
     TCA_BPF_ID = 11,
     __TCA_BPF_MAX = 12,
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sockaddr_xdp {
+    pub sxdp_family: __u16,
+    pub sxdp_flags: __u16,
+    pub sxdp_ifindex: __u32,
+    pub sxdp_queue_id: __u32,
+    pub sxdp_shared_umem_fd: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdp_ring_offset {
+    pub producer: __u64,
+    pub consumer: __u64,
+    pub desc: __u64,
+    pub flags: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdp_mmap_offsets {
+    pub rx: xdp_ring_offset,
+    pub tx: xdp_ring_offset,
+    pub fr: xdp_ring_offset,
+    pub cr: xdp_ring_offset,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdp_umem_reg {
+    pub addr: __u64,
+    pub len: __u64,
+    pub chunk_size: __u32,
+    pub headroom: __u32,
+    pub flags: __u32,
+    pub tx_metadata_len: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdp_statistics {
+    pub rx_dropped: __u64,
+    pub rx_invalid_descs: __u64,
+    pub tx_invalid_descs: __u64,
+    pub rx_ring_full: __u64,
+    pub rx_fill_ring_empty_descs: __u64,
+    pub tx_ring_empty_descs: __u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdp_desc {
+    pub addr: __u64,
+    pub len: __u32,
+    pub options: __u32,
+}
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum netdev_xdp_act {
+    NETDEV_XDP_ACT_BASIC = 1,
+    NETDEV_XDP_ACT_REDIRECT = 2,
+    NETDEV_XDP_ACT_NDO_XMIT = 4,
+    NETDEV_XDP_ACT_XSK_ZEROCOPY = 8,
+    NETDEV_XDP_ACT_HW_OFFLOAD = 16,
+    NETDEV_XDP_ACT_RX_SG = 32,
+    NETDEV_XDP_ACT_NDO_XMIT_SG = 64,
+    NETDEV_XDP_ACT_MASK = 127,
+}
+pub const NETDEV_A_DEV_IFINDEX: _bindgen_ty_150 = _bindgen_ty_150::NETDEV_A_DEV_IFINDEX;
+pub const NETDEV_A_DEV_PAD: _bindgen_ty_150 = _bindgen_ty_150::NETDEV_A_DEV_PAD;
+pub const NETDEV_A_DEV_XDP_FEATURES: _bindgen_ty_150 = _bindgen_ty_150::NETDEV_A_DEV_XDP_FEATURES;
+pub const NETDEV_A_DEV_XDP_ZC_MAX_SEGS: _bindgen_ty_150 =
+    _bindgen_ty_150::NETDEV_A_DEV_XDP_ZC_MAX_SEGS;
+pub const __NETDEV_A_DEV_MAX: _bindgen_ty_150 = _bindgen_ty_150::__NETDEV_A_DEV_MAX;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum _bindgen_ty_150 {
+    NETDEV_A_DEV_IFINDEX = 1,
+    NETDEV_A_DEV_PAD = 2,
+    NETDEV_A_DEV_XDP_FEATURES = 3,
+    NETDEV_A_DEV_XDP_ZC_MAX_SEGS = 4,
+    __NETDEV_A_DEV_MAX = 5,
+}
+pub const NETDEV_CMD_DEV_GET: _bindgen_ty_151 = _bindgen_ty_151::NETDEV_CMD_DEV_GET;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum _bindgen_ty_151 {
+    NETDEV_CMD_DEV_GET = 1,
+    NETDEV_CMD_DEV_ADD_NTF = 2,
+    NETDEV_CMD_DEV_DEL_NTF = 3,
+    NETDEV_CMD_DEV_CHANGE_NTF = 4,
+    __NETDEV_CMD_MAX = 5,
+}
 pub const AYA_PERF_EVENT_IOC_ENABLE: ::std::os::raw::c_int = 9216;
 pub const AYA_PERF_EVENT_IOC_DISABLE: ::std::os::raw::c_int = 9217;
 pub const AYA_PERF_EVENT_IOC_SET_BPF: ::std::os::raw::c_int = 1074013192;
+pub const AYA_PERF_EVENT_IOC_PAUSE_OUTPUT: ::std::os::raw::c_int = 1074013193;
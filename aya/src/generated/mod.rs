@@ -1,5 +1,8 @@
 #![allow(dead_code, non_camel_case_types, non_snake_case, clippy::all)]
 
+#[cfg(not(any(target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64")))]
+compile_error!("aya supports the x86_64, arm (armv7) and aarch64 architectures");
+
 mod btf_internal_bindings;
 #[cfg(target_arch = "aarch64")]
 mod linux_bindings_aarch64;
@@ -38,13 +38,25 @@ extern crate lazy_static;
 extern crate bitflags;
 
 mod bpf;
+mod features;
 mod generated;
+pub mod logger;
 pub mod maps;
 mod obj;
 pub mod programs;
 mod sys;
+mod token;
+pub mod trace_pipe;
 pub mod util;
+pub mod xdp;
 
+pub use aya_derive::Pod;
 pub use bpf::*;
-pub use obj::btf::{Btf, BtfError};
+pub use features::*;
+pub use obj::{
+    btf::{Btf, BtfError, BtfFuncArg, BtfValue},
+    DecompressionError, KConfigError, KSymError, MapDescription, ObjectDescription,
+    ProgramDescription, RelocationDescription, SectionDescription,
+};
 pub use object::Endianness;
+pub use token::*;
@@ -0,0 +1,296 @@
+//! Forwarding log records from eBPF programs to the [`log`] crate.
+//!
+//! eBPF programs send log records to user space through a perf event array
+//! named `AYA_LOGS` ([`LOG_MAP_NAME`]). [`BpfLogger`] reads the records from
+//! the map and forwards them to the [`log`] crate, so they're handled by
+//! whatever logger the application installed, like `env_logger`.
+//!
+//! # Record format
+//!
+//! Each record is written to the map with `bpf_perf_event_output()` and has
+//! the following layout, with integers in the native byte order:
+//!
+//! | Offset    | Size | Description                                        |
+//! |-----------|------|----------------------------------------------------|
+//! | 0         | 1    | level: 1 = error, 2 = warn, 3 = info, 4 = debug, 5 = trace |
+//! | 1         | 1    | length of the target, `T`                          |
+//! | 2         | 2    | length of the message, `M`                         |
+//! | 4         | `T`  | target, UTF-8                                      |
+//! | 4 + `T`   | `M`  | message, UTF-8                                     |
+//!
+//! Any bytes after the message are ignored, so records can be written from a
+//! fixed size buffer.
+//!
+//! An eBPF program using `aya-bpf` can write records like this:
+//!
+//! ```ignore
+//! #[map(name = "AYA_LOGS")]
+//! static mut AYA_LOGS: PerfEventByteArray = PerfEventByteArray::new(0);
+//!
+//! fn log_info<C: BpfContext>(ctx: &C, message: &[u8]) {
+//!     let target = b"my_program";
+//!     let mut record = [0u8; 64];
+//!     record[0] = 3; // info
+//!     record[1] = target.len() as u8;
+//!     record[2..4].copy_from_slice(&(message.len() as u16).to_ne_bytes());
+//!     record[4..4 + target.len()].copy_from_slice(target);
+//!     record[4 + target.len()..4 + target.len() + message.len()].copy_from_slice(message);
+//!     unsafe { AYA_LOGS.output(ctx, &record, 0) };
+//! }
+//! ```
+use std::{convert::TryFrom, io, os::unix::io::AsRawFd, str, time::Duration};
+
+use bytes::BytesMut;
+use log::{Level, Log, Record};
+use thiserror::Error;
+
+use crate::{
+    maps::{
+        perf::{Events, PerfBufferError, PerfEventArrayBuffer},
+        MapError, MapRefMut, PerfEventArray,
+    },
+    util::{online_cpus, poll_readable_any},
+    Bpf,
+};
+
+/// The name of the map eBPF programs send log records to.
+pub const LOG_MAP_NAME: &str = "AYA_LOGS";
+
+const RECORD_HEADER_LEN: usize = 4;
+
+/// Error type returned by [`BpfLogger`].
+#[derive(Debug, Error)]
+pub enum BpfLoggerError {
+    /// The log map couldn't be opened.
+    #[error(transparent)]
+    MapError(#[from] MapError),
+
+    /// A perf buffer of the log map couldn't be opened or read.
+    #[error(transparent)]
+    PerfBufferError(#[from] PerfBufferError),
+
+    /// An IO error occurred.
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+/// A log record sent by an eBPF program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogRecord<'a> {
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record, usually the name of the program.
+    pub target: &'a str,
+    /// The message.
+    pub message: &'a str,
+}
+
+impl<'a> LogRecord<'a> {
+    /// Parses a record, returning `None` if it's malformed.
+    ///
+    /// See the [module documentation](self) for the format.
+    pub fn parse(data: &'a [u8]) -> Option<LogRecord<'a>> {
+        if data.len() < RECORD_HEADER_LEN {
+            return None;
+        }
+        let level = match data[0] {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            _ => return None,
+        };
+        let target_len = data[1] as usize;
+        let message_len = u16::from_ne_bytes([data[2], data[3]]) as usize;
+        let data = &data[RECORD_HEADER_LEN..];
+        if data.len() < target_len + message_len {
+            return None;
+        }
+
+        Some(LogRecord {
+            level,
+            target: str::from_utf8(&data[..target_len]).ok()?,
+            message: str::from_utf8(&data[target_len..target_len + message_len]).ok()?,
+        })
+    }
+}
+
+/// Reads the log records sent by eBPF programs and forwards them to the
+/// [`log`] crate.
+///
+/// See the [module documentation](self) for how eBPF programs send records.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::logger::BpfLogger;
+/// use std::thread;
+///
+/// let mut logger = BpfLogger::init(&bpf)?;
+/// thread::spawn(move || loop {
+///     // blocks until records are available
+///     if let Err(e) = logger.poll(None) {
+///         eprintln!("error reading eBPF logs: {}", e);
+///     }
+/// });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct BpfLogger {
+    buffers: Vec<PerfEventArrayBuffer<MapRefMut>>,
+    out_bufs: Vec<BytesMut>,
+    logger: Option<Box<dyn Log>>,
+}
+
+impl BpfLogger {
+    /// Opens the [`LOG_MAP_NAME`] map of `bpf`, forwarding the records to the
+    /// global logger.
+    pub fn init(bpf: &Bpf) -> Result<BpfLogger, BpfLoggerError> {
+        let mut perf_array = PerfEventArray::try_from(bpf.map_mut(LOG_MAP_NAME)?)?;
+        let mut buffers = Vec::new();
+        for cpu_id in online_cpus()? {
+            buffers.push(perf_array.open(cpu_id, None)?);
+        }
+
+        Ok(BpfLogger {
+            buffers,
+            out_bufs: (0..16).map(|_| BytesMut::with_capacity(1024)).collect(),
+            logger: None,
+        })
+    }
+
+    /// Forwards the records to `logger` instead of the global logger.
+    pub fn set_logger<L: Log + 'static>(&mut self, logger: L) -> &mut BpfLogger {
+        self.logger = Some(Box::new(logger));
+        self
+    }
+
+    /// Waits until records are available or `timeout` expires, then forwards
+    /// all the available records. A `timeout` of `None` waits forever.
+    ///
+    /// Returns the number of records forwarded. Malformed records are skipped.
+    pub fn poll(&mut self, timeout: Option<Duration>) -> Result<usize, BpfLoggerError> {
+        if !self.buffers.iter().any(|buf| buf.readable()) {
+            let fds = self
+                .buffers
+                .iter()
+                .map(|buf| buf.as_raw_fd())
+                .collect::<Vec<_>>();
+            poll_readable_any(&fds, timeout)?;
+        }
+        self.flush()
+    }
+
+    /// Forwards the available records without waiting.
+    ///
+    /// Returns the number of records forwarded. The output buffers grow to
+    /// fit records larger than their initial size of 1024 bytes.
+    pub fn flush(&mut self) -> Result<usize, BpfLoggerError> {
+        let logger = match &self.logger {
+            Some(logger) => logger.as_ref(),
+            None => log::logger(),
+        };
+
+        let mut count = 0;
+        for buf in self.buffers.iter_mut() {
+            while buf.readable() {
+                let read = read_records(&mut self.out_bufs, |out_bufs| buf.read_events(out_bufs))?;
+                for data in self.out_bufs.iter().take(read) {
+                    if let Some(record) = LogRecord::parse(data) {
+                        logger.log(
+                            &Record::builder()
+                                .level(record.level)
+                                .target(record.target)
+                                .args(format_args!("{}", record.message))
+                                .build(),
+                        );
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+// reads records into `out_bufs` with `read_events`, growing the buffers when
+// the next record doesn't fit, and returns the number of records read
+fn read_records(
+    out_bufs: &mut [BytesMut],
+    mut read_events: impl FnMut(&mut [BytesMut]) -> Result<Events, PerfBufferError>,
+) -> Result<usize, PerfBufferError> {
+    loop {
+        match read_events(out_bufs) {
+            // the record is left in the perf buffer, read it again
+            Err(PerfBufferError::MoreSpaceNeeded { size }) => {
+                for buf in out_bufs.iter_mut() {
+                    buf.clear();
+                    buf.reserve(size);
+                }
+            }
+            result => return result.map(|events| events.read),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: u8, target: &str, message: &str) -> Vec<u8> {
+        let mut data = vec![level, target.len() as u8];
+        data.extend_from_slice(&(message.len() as u16).to_ne_bytes());
+        data.extend_from_slice(target.as_bytes());
+        data.extend_from_slice(message.as_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let mut data = record(3, "prog", "hello");
+        // padding added by the perf buffer
+        data.extend_from_slice(&[0, 0, 0]);
+        assert_eq!(
+            LogRecord::parse(&data),
+            Some(LogRecord {
+                level: Level::Info,
+                target: "prog",
+                message: "hello",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_record() {
+        assert_eq!(LogRecord::parse(&[3, 0]), None);
+        assert_eq!(LogRecord::parse(&record(0, "prog", "hello")), None);
+        let data = record(1, "prog", "hello");
+        assert_eq!(LogRecord::parse(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_read_oversized_record() {
+        let data = record(3, "prog", &"x".repeat(4000));
+        let mut out_bufs = vec![BytesMut::with_capacity(1024); 2];
+        let mut calls = 0;
+        let read = read_records(&mut out_bufs, |bufs| {
+            calls += 1;
+            let buf = &mut bufs[0];
+            if buf.capacity() < data.len() {
+                return Err(PerfBufferError::MoreSpaceNeeded { size: data.len() });
+            }
+            buf.extend_from_slice(&data);
+            Ok(Events { read: 1, lost: 0 })
+        })
+        .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(read, 1);
+        assert!(out_bufs.iter().all(|buf| buf.capacity() >= data.len()));
+        assert_eq!(
+            LogRecord::parse(&out_bufs[0]).map(|r| r.message.len()),
+            Some(4000)
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 };
 
 use crate::{
-    generated::bpf_map_type::BPF_MAP_TYPE_ARRAY,
+    generated::{bpf_map_type::BPF_MAP_TYPE_ARRAY, BPF_F_LOCK},
     maps::{IterableMap, Map, MapError, MapRef, MapRefMut},
     sys::{bpf_map_lookup_elem, bpf_map_update_elem},
     Pod,
@@ -92,6 +92,23 @@ impl<T: Deref<Target = Map>, V: Pod> Array<T, V> {
         value.ok_or(MapError::KeyNotFound)
     }
 
+    /// Returns the value stored at the given index, read while holding the
+    /// `struct bpf_spin_lock` embedded in the value.
+    ///
+    /// The copy is consistent with the updates eBPF programs make while
+    /// holding the lock with `bpf_spin_lock()`. The value type must contain a
+    /// `struct bpf_spin_lock`, otherwise [`MapError::NoSpinLock`] is returned.
+    /// The lock itself is not copied and reads as zero.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 5.1.
+    #[doc(alias = "BPF_F_LOCK")]
+    pub fn get_locked(&self, index: &u32) -> Result<V, MapError> {
+        self.inner.check_spin_lock()?;
+        self.get(index, BPF_F_LOCK as u64)
+    }
+
     /// An iterator over the elements of the array. The iterator item type is `Result<V,
     /// MapError>`.
     pub unsafe fn iter(&self) -> impl Iterator<Item = Result<V, MapError>> + '_ {
@@ -127,6 +144,16 @@ impl<T: Deref<Target = Map> + DerefMut<Target = Map>, V: Pod> Array<T, V> {
         })?;
         Ok(())
     }
+
+    /// Sets the value of the element at the given index, holding the `struct
+    /// bpf_spin_lock` embedded in the value during the update.
+    ///
+    /// The lock field of `value` is ignored. See [`Array::get_locked`].
+    #[doc(alias = "BPF_F_LOCK")]
+    pub fn set_locked(&mut self, index: u32, value: V, flags: u64) -> Result<(), MapError> {
+        self.inner.check_spin_lock()?;
+        self.set(index, value, flags | BPF_F_LOCK as u64)
+    }
 }
 
 impl<T: Deref<Target = Map>, V: Pod> IterableMap<u32, V> for Array<T, V> {
@@ -8,7 +8,9 @@ use std::{
 use crate::{
     generated::bpf_map_type::BPF_MAP_TYPE_PERCPU_ARRAY,
     maps::{IterableMap, Map, MapError, MapRef, MapRefMut, PerCpuValues},
-    sys::{bpf_map_lookup_elem_per_cpu, bpf_map_update_elem_per_cpu},
+    sys::{
+        bpf_map_lookup_elem_per_cpu, bpf_map_lookup_elem_per_cpu_into, bpf_map_update_elem_per_cpu,
+    },
     Pod,
 };
 
@@ -111,6 +113,36 @@ impl<T: Deref<Target = Map>, V: Pod> PerCpuArray<T, V> {
         value.ok_or(MapError::KeyNotFound)
     }
 
+    /// Reads the values - one for each CPU - stored at the given index into
+    /// `values`.
+    ///
+    /// Unlike [`PerCpuArray::get`], this doesn't allocate once `values` has
+    /// been used for a lookup, so the same values can be reused to read many
+    /// entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MapError::OutOfBounds`] if `index` is out of bounds, [`MapError::SyscallError`]
+    /// if `bpf_map_lookup_elem` fails.
+    pub fn get_into(
+        &self,
+        index: &u32,
+        values: &mut PerCpuValues<V>,
+        flags: u64,
+    ) -> Result<(), MapError> {
+        self.check_bounds(*index)?;
+        let fd = self.inner.fd_or_err()?;
+
+        let found = bpf_map_lookup_elem_per_cpu_into(fd, index, values, flags).map_err(
+            |(code, io_error)| MapError::SyscallError {
+                call: "bpf_map_lookup_elem".to_owned(),
+                code,
+                io_error,
+            },
+        )?;
+        found.ok_or(MapError::KeyNotFound)
+    }
+
     /// An iterator over the elements of the array. The iterator item type is
     /// `Result<PerCpuValues<V>, MapError>`.
     pub unsafe fn iter(&self) -> impl Iterator<Item = Result<PerCpuValues<V>, MapError>> + '_ {
@@ -29,23 +29,26 @@ use crate::{
 /// # let bpf = aya::Bpf::load(&[])?;
 /// use aya::maps::ProgramArray;
 /// use aya::programs::CgroupSkb;
-/// use std::convert::{TryFrom, TryInto};
+/// use std::convert::TryFrom;
 ///
 /// let mut prog_array = ProgramArray::try_from(bpf.map_mut("JUMP_TABLE")?)?;
-/// let prog_0: &CgroupSkb = bpf.program("example_prog_0")?.try_into()?;
-/// let prog_1: &CgroupSkb = bpf.program("example_prog_1")?.try_into()?;
-/// let prog_2: &CgroupSkb = bpf.program("example_prog_2")?.try_into()?;
+/// let prog_0 = bpf.program_typed::<CgroupSkb>("example_prog_0")?;
+/// let prog_1 = bpf.program_typed::<CgroupSkb>("example_prog_1")?;
+/// let prog_2 = bpf.program_typed::<CgroupSkb>("example_prog_2")?;
 ///
 /// let flags = 0;
 ///
 /// // bpf_tail_call(ctx, JUMP_TABLE, 0) will jump to prog_0
-/// prog_array.set(0, prog_0, flags);
+/// prog_array.set(0, prog_0, flags)?;
 ///
 /// // bpf_tail_call(ctx, JUMP_TABLE, 1) will jump to prog_1
-/// prog_array.set(1, prog_1, flags);
+/// prog_array.set(1, prog_1, flags)?;
 ///
 /// // bpf_tail_call(ctx, JUMP_TABLE, 2) will jump to prog_2
-/// prog_array.set(2, prog_2, flags);
+/// prog_array.set(2, prog_2, flags)?;
+///
+/// // bpf_tail_call(ctx, JUMP_TABLE, 2) will now fail and fall through
+/// prog_array.clear(2)?;
 /// # Ok::<(), aya::BpfError>(())
 /// ```
 #[doc(alias = "BPF_MAP_TYPE_PROG_ARRAY")]
@@ -97,7 +100,9 @@ impl<T: Deref<Target = Map> + DerefMut<Target = Map>> ProgramArray<T> {
     /// Sets the target program file descriptor for the given index in the jump table.
     ///
     /// When an eBPF program calls `bpf_tail_call(ctx, prog_array, index)`, control
-    /// flow will jump to `program`.
+    /// flow will jump to `program`. `program` can be any loaded program, or a
+    /// raw program file descriptor. Returns [`MapError::ProgramNotLoaded`] if
+    /// the program hasn't been loaded.
     pub fn set(&mut self, index: u32, program: &dyn ProgramFd, flags: u64) -> Result<(), MapError> {
         let fd = self.inner.fd_or_err()?;
         self.check_bounds(index)?;
@@ -117,10 +122,10 @@ impl<T: Deref<Target = Map> + DerefMut<Target = Map>> ProgramArray<T> {
     ///
     /// Calling `bpf_tail_call(ctx, prog_array, index)` on an index that has been cleared returns an
     /// error.
-    pub fn clear_index(&mut self, index: &u32) -> Result<(), MapError> {
+    pub fn clear(&mut self, index: u32) -> Result<(), MapError> {
         let fd = self.inner.fd_or_err()?;
-        self.check_bounds(*index)?;
-        bpf_map_delete_elem(fd, index)
+        self.check_bounds(index)?;
+        bpf_map_delete_elem(fd, &index)
             .map(|_| ())
             .map_err(|(code, io_error)| MapError::SyscallError {
                 call: "bpf_map_delete_elem".to_owned(),
@@ -128,6 +133,13 @@ impl<T: Deref<Target = Map> + DerefMut<Target = Map>> ProgramArray<T> {
                 io_error,
             })
     }
+
+    /// Clears the value at index in the jump table.
+    ///
+    /// Same as [`ProgramArray::clear`].
+    pub fn clear_index(&mut self, index: &u32) -> Result<(), MapError> {
+        self.clear(*index)
+    }
 }
 
 impl TryFrom<MapRef> for ProgramArray<MapRef> {
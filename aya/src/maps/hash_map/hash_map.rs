@@ -1,11 +1,16 @@
 use std::{
     convert::TryFrom,
     marker::PhantomData,
+    mem,
     ops::{Deref, DerefMut},
+    ptr,
 };
 
 use crate::{
-    generated::bpf_map_type::{BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_LRU_HASH},
+    generated::{
+        bpf_map_type::{BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_LRU_HASH},
+        BPF_F_LOCK,
+    },
     maps::{hash_map, IterableMap, Map, MapError, MapIter, MapKeys, MapRef, MapRefMut},
     sys::bpf_map_lookup_elem,
     Pod,
@@ -73,6 +78,24 @@ impl<T: Deref<Target = Map>, K: Pod, V: Pod> HashMap<T, K, V> {
         value.ok_or(MapError::KeyNotFound)
     }
 
+    /// Returns a copy of the value associated with the key, read while holding
+    /// the `struct bpf_spin_lock` embedded in the value.
+    ///
+    /// Unlike [`HashMap::get`], the copy is consistent with the updates eBPF
+    /// programs make while holding the lock with `bpf_spin_lock()`. The value
+    /// type must contain a `struct bpf_spin_lock`, otherwise
+    /// [`MapError::NoSpinLock`] is returned. The lock itself is not copied and
+    /// reads as zero.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 5.1.
+    #[doc(alias = "BPF_F_LOCK")]
+    pub fn get_locked(&self, key: &K) -> Result<V, MapError> {
+        self.inner.check_spin_lock()?;
+        unsafe { self.get(key, BPF_F_LOCK as u64) }
+    }
+
     /// An iterator visiting all key-value pairs in arbitrary order. The
     /// iterator item type is `Result<(K, V), MapError>`.
     pub unsafe fn iter(&self) -> MapIter<'_, K, V> {
@@ -92,6 +115,16 @@ impl<T: DerefMut<Target = Map>, K: Pod, V: Pod> HashMap<T, K, V> {
         hash_map::insert(&mut self.inner, key, value, flags)
     }
 
+    /// Inserts a key-value pair into the map, holding the `struct
+    /// bpf_spin_lock` embedded in the value while updating an existing value.
+    ///
+    /// The lock field of `value` is ignored. See [`HashMap::get_locked`].
+    #[doc(alias = "BPF_F_LOCK")]
+    pub fn insert_locked(&mut self, key: K, value: V, flags: u64) -> Result<(), MapError> {
+        self.inner.check_spin_lock()?;
+        hash_map::insert(&mut self.inner, key, value, flags | BPF_F_LOCK as u64)
+    }
+
     /// Removes a key from the map.
     pub fn remove(&mut self, key: &K) -> Result<(), MapError> {
         hash_map::remove(&mut self.inner, key)
@@ -106,6 +139,13 @@ impl<T: Deref<Target = Map>, K: Pod, V: Pod> IterableMap<K, V> for HashMap<T, K,
     unsafe fn get(&self, key: &K) -> Result<V, MapError> {
         HashMap::get(self, key, 0)
     }
+
+    fn value_from_bytes(&self, bytes: &[u8]) -> Option<V> {
+        if bytes.len() < mem::size_of::<V>() {
+            return None;
+        }
+        Some(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const V) })
+    }
 }
 
 impl<K: Pod, V: Pod> TryFrom<MapRef> for HashMap<MapRef, K, V> {
@@ -169,7 +209,13 @@ mod tests {
                 ..Default::default()
             },
             section_index: 0,
+            section_offset: None,
             data: Vec::new(),
+            inner: None,
+            numa_node: None,
+            btf_key_type_id: None,
+            btf_value_type_id: None,
+            btf: None,
         }
     }
 
@@ -222,7 +268,13 @@ mod tests {
                     ..Default::default()
                 },
                 section_index: 0,
+                section_offset: None,
                 data: Vec::new(),
+                inner: None,
+                numa_node: None,
+                btf_key_type_id: None,
+                btf_value_type_id: None,
+                btf: None,
             },
             fd: None,
             pinned: false,
@@ -282,7 +334,13 @@ mod tests {
                     ..Default::default()
                 },
                 section_index: 0,
+                section_offset: None,
                 data: Vec::new(),
+                inner: None,
+                numa_node: None,
+                btf_key_type_id: None,
+                btf_value_type_id: None,
+                btf: None,
             },
             fd: Some(42),
             pinned: false,
@@ -541,6 +599,50 @@ mod tests {
         assert_eq!(&items, &[(10, 100), (20, 200), (30, 300)])
     }
 
+    fn lookup_batch(attr: &bpf_attr) -> SysResult {
+        let batch = unsafe { attr.batch };
+        let keys = batch.keys as *mut u32;
+        let values = batch.values as *mut u32;
+        let (entries, ret): (&[(u32, u32)], _) = if batch.in_batch == 0 {
+            (&[(10, 100), (20, 200)], Ok(0))
+        } else {
+            (&[(30, 300)], sys_error(ENOENT))
+        };
+        for (i, (key, value)) in entries.iter().enumerate() {
+            unsafe {
+                *keys.add(i) = *key;
+                *values.add(i) = *value;
+            }
+        }
+        let attr = attr as *const bpf_attr as *mut bpf_attr;
+        unsafe { (*attr).batch.count = entries.len() as u32 };
+
+        ret
+    }
+
+    #[test]
+    fn test_iter_batch() {
+        override_syscall(|call| match call {
+            Syscall::Bpf {
+                cmd: bpf_cmd::BPF_MAP_LOOKUP_BATCH,
+                attr,
+            } => lookup_batch(attr),
+            _ => sys_error(EFAULT),
+        });
+        let map = Map {
+            obj: new_obj_map("TEST"),
+            fd: Some(42),
+            pinned: false,
+        };
+        let hm = HashMap::<_, u32, u32>::new(&map).unwrap();
+
+        let keys = unsafe { hm.keys() }.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(&keys, &[10, 20, 30]);
+
+        let items = unsafe { hm.iter() }.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(&items, &[(10, 100), (20, 200), (30, 300)])
+    }
+
     #[test]
     fn test_iter_key_deleted() {
         override_syscall(|call| match call {
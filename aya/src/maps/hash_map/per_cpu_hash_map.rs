@@ -10,7 +10,9 @@ use crate::{
     maps::{
         hash_map, IterableMap, Map, MapError, MapIter, MapKeys, MapRef, MapRefMut, PerCpuValues,
     },
-    sys::{bpf_map_lookup_elem_per_cpu, bpf_map_update_elem_per_cpu},
+    sys::{
+        bpf_map_lookup_elem_per_cpu, bpf_map_lookup_elem_per_cpu_into, bpf_map_update_elem_per_cpu,
+    },
     Pod,
 };
 
@@ -85,6 +87,29 @@ impl<T: Deref<Target = Map>, K: Pod, V: Pod> PerCpuHashMap<T, K, V> {
         values.ok_or(MapError::KeyNotFound)
     }
 
+    /// Reads the values - one for each CPU - associated with the key into
+    /// `values`.
+    ///
+    /// Unlike [`PerCpuHashMap::get`], this doesn't allocate once `values` has
+    /// been used for a lookup, so the same values can be reused to read many
+    /// keys.
+    pub unsafe fn get_into(
+        &self,
+        key: &K,
+        values: &mut PerCpuValues<V>,
+        flags: u64,
+    ) -> Result<(), MapError> {
+        let fd = self.inner.deref().fd_or_err()?;
+        let found = bpf_map_lookup_elem_per_cpu_into(fd, key, values, flags).map_err(
+            |(code, io_error)| MapError::SyscallError {
+                call: "bpf_map_lookup_elem".to_owned(),
+                code,
+                io_error,
+            },
+        )?;
+        found.ok_or(MapError::KeyNotFound)
+    }
+
     /// An iterator visiting all key-value pairs in arbitrary order. The
     /// iterator item type is `Result<(K, PerCpuValues<V>), MapError>`.
     pub unsafe fn iter(&self) -> MapIter<'_, K, PerCpuValues<V>> {
@@ -157,6 +182,11 @@ impl<T: Deref<Target = Map>, K: Pod, V: Pod> IterableMap<K, PerCpuValues<V>>
     unsafe fn get(&self, key: &K) -> Result<PerCpuValues<V>, MapError> {
         PerCpuHashMap::get(self, key, 0)
     }
+
+    fn value_from_bytes(&self, bytes: &[u8]) -> Option<PerCpuValues<V>> {
+        let mem = PerCpuValues::<V>::kernel_mem_from_bytes(bytes)?;
+        Some(unsafe { PerCpuValues::from_kernel_mem(mem) })
+    }
 }
 
 impl<K: Pod, V: Pod> TryFrom<MapRef> for PerCpuHashMap<MapRef, K, V> {
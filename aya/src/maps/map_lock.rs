@@ -5,7 +5,7 @@ use std::{
     sync::Arc,
 };
 
-use crate::maps::Map;
+use crate::maps::{Map, MapError};
 
 pub(crate) struct MapLockError;
 
@@ -58,6 +58,61 @@ pub struct MapRefMut {
     guard: RwLockWriteGuard<'static, Map>,
 }
 
+impl MapRef {
+    /// Creates a new reference to the same map which doesn't borrow the
+    /// original one.
+    ///
+    /// See [`MapRefMut::try_clone`].
+    pub fn try_clone(&self) -> Result<MapRef, MapError> {
+        Ok(MapRef::from(self.guard.try_clone()?))
+    }
+}
+
+impl MapRefMut {
+    /// Creates a new mutable reference to the same map which doesn't borrow
+    /// the original one.
+    ///
+    /// The map file descriptor is duplicated with [`Map::try_clone`]. Map
+    /// operations are atomic in the kernel, so the references can be used to
+    /// update the map concurrently, for example from different threads,
+    /// instead of sharing the map returned by [`Bpf::map_mut`](crate::Bpf::map_mut).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let bpf = aya::Bpf::load(&[])?;
+    /// use std::{convert::TryFrom, thread};
+    /// use aya::maps::HashMap;
+    ///
+    /// let counters = bpf.map_mut("COUNTERS")?;
+    /// let workers = (0..4u32)
+    ///     .map(|i| {
+    ///         let mut counters = HashMap::<_, u32, u64>::try_from(counters.try_clone()?)?;
+    ///         Ok(thread::spawn(move || counters.insert(i, 0, 0)))
+    ///     })
+    ///     .collect::<Result<Vec<_>, aya::maps::MapError>>()?;
+    /// for worker in workers {
+    ///     worker.join().unwrap()?;
+    /// }
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn try_clone(&self) -> Result<MapRefMut, MapError> {
+        Ok(MapRefMut::from(self.guard.try_clone()?))
+    }
+}
+
+impl From<Map> for MapRef {
+    fn from(map: Map) -> MapRef {
+        MapLock::new(map).try_read().ok().unwrap()
+    }
+}
+
+impl From<Map> for MapRefMut {
+    fn from(map: Map) -> MapRefMut {
+        MapLock::new(map).try_write().ok().unwrap()
+    }
+}
+
 impl Deref for MapRef {
     type Target = Map;
 
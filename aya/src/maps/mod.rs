@@ -16,12 +16,12 @@
 //!
 //! ```no_run
 //! # let mut bpf = aya::Bpf::load(&[])?;
-//! use std::convert::{TryFrom, TryInto};
+//! use std::convert::TryFrom;
 //! use aya::maps::SockMap;
 //! use aya::programs::SkMsg;
 //!
 //! let intercept_egress = SockMap::try_from(bpf.map_mut("INTERCEPT_EGRESS")?)?;
-//! let prog: &mut SkMsg = bpf.program_mut("intercept_egress_packet")?.try_into()?;
+//! let prog = bpf.program_mut_typed::<SkMsg>("intercept_egress_packet")?;
 //! prog.load()?;
 //! prog.attach(&intercept_egress)?;
 //! # Ok::<(), aya::BpfError>(())
@@ -33,16 +33,34 @@
 //! versa. Because of that, all map values must be plain old data and therefore
 //! implement the [Pod] trait.
 use std::{
-    convert::TryFrom, ffi::CString, io, marker::PhantomData, mem, ops::Deref, os::unix::io::RawFd,
-    path::Path, ptr,
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    fmt, io,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    path::Path,
+    ptr,
+    sync::Arc,
 };
 use thiserror::Error;
 
 use crate::{
-    generated::bpf_map_type,
-    obj,
-    sys::{bpf_create_map, bpf_get_object, bpf_map_get_next_key, bpf_pin_object},
-    util::nr_cpus,
+    bpf_map_def,
+    generated::{
+        bpf_map_type, BPF_F_INNER_MAP, BPF_F_NO_PREALLOC, BPF_F_RDONLY_PROG, BPF_F_STACK_BUILD_ID,
+        BPF_F_WRONLY_PROG,
+    },
+    obj::{
+        self,
+        btf::{Btf, BtfError, BtfValue},
+    },
+    sys::{
+        bpf_create_map, bpf_get_object, bpf_map_get_fd_by_id, bpf_map_get_info_by_fd,
+        bpf_map_get_next_key, bpf_map_lookup_batch, bpf_pin_object, MapBtf,
+    },
+    util::{dup_fd, is_memlock_error, nr_cpus},
     Pod,
 };
 
@@ -52,18 +70,22 @@ pub mod array;
 pub mod hash_map;
 pub mod perf;
 pub mod queue;
+pub mod ring_buf;
 pub mod sock;
 pub mod stack;
 pub mod stack_trace;
+pub mod xsk_map;
 
 pub use array::{Array, PerCpuArray, ProgramArray};
 pub use hash_map::{HashMap, PerCpuHashMap};
 pub use map_lock::*;
 pub use perf::PerfEventArray;
 pub use queue::Queue;
-pub use sock::{SockHash, SockMap};
+pub use ring_buf::RingBuf;
+pub use sock::{ReuseportSockArray, SockHash, SockMap};
 pub use stack::Stack;
 pub use stack_trace::StackTraceMap;
+pub use xsk_map::XskMap;
 
 #[derive(Error, Debug)]
 pub enum MapError {
@@ -96,6 +118,14 @@ pub enum MapError {
         io_error: io::Error,
     },
 
+    /// The map could not be created because `RLIMIT_MEMLOCK` is too low.
+    #[error("failed to create map `{name}`: the RLIMIT_MEMLOCK limit is too low, raise it with setrlimit() or BpfLoader::raise_memlock_rlimit()")]
+    MemlockLimitExceeded {
+        name: String,
+        #[source]
+        io_error: io::Error,
+    },
+
     #[error("failed to pin map `{name}` with code {code}")]
     PinError {
         name: String,
@@ -135,6 +165,161 @@ pub enum MapError {
 
     #[error("map `{name}` is already borrowed")]
     BorrowMutError { name: String },
+
+    #[error("the map `{name}` has no BTF type information")]
+    MissingBtf { name: String },
+
+    #[error("the values of map `{name}` don't contain a `struct bpf_spin_lock`")]
+    NoSpinLock { name: String },
+
+    #[error("the existing map doesn't match the definition of map `{name}`")]
+    IncompatibleMap { name: String },
+
+    #[error("error decoding the map data")]
+    BtfError(#[from] BtfError),
+}
+
+bitflags! {
+    /// Flags a map is created with.
+    ///
+    /// The flags of a map are read from its definition in the object and can
+    /// be overridden with [`BpfLoader::map_flags`](crate::BpfLoader::map_flags).
+    #[derive(Default)]
+    pub struct MapFlags: u32 {
+        /// Allocate the elements of hash maps on demand instead of when the
+        /// map is created.
+        const NO_PREALLOC = BPF_F_NO_PREALLOC;
+        /// eBPF programs can only read the map. Requires kernel 5.2 or newer.
+        const RDONLY_PROG = BPF_F_RDONLY_PROG;
+        /// eBPF programs can only write the map. Requires kernel 5.2 or newer.
+        const WRONLY_PROG = BPF_F_WRONLY_PROG;
+        /// Stack trace maps store the build ID of the object file and the
+        /// offset of each user space frame, instead of its address. See
+        /// [`StackTraceMap`]. Requires kernel 4.17 or newer.
+        const STACK_BUILD_ID = BPF_F_STACK_BUILD_ID;
+        /// The map is only used as the template of the inner maps of a map of
+        /// maps, so inner maps with a different `max_entries` can be used.
+        /// Requires kernel 5.10 or newer.
+        const INNER_MAP = BPF_F_INNER_MAP;
+    }
+}
+
+/// The type of an eBPF map, as known to the kernel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapType {
+    Hash,
+    Array,
+    ProgramArray,
+    PerfEventArray,
+    PerCpuHash,
+    PerCpuArray,
+    StackTrace,
+    CgroupArray,
+    LruHash,
+    LruPerCpuHash,
+    LpmTrie,
+    ArrayOfMaps,
+    HashOfMaps,
+    DevMap,
+    SockMap,
+    CpuMap,
+    XskMap,
+    SockHash,
+    CgroupStorage,
+    ReuseportSockArray,
+    PerCpuCgroupStorage,
+    Queue,
+    Stack,
+    SkStorage,
+    DevMapHash,
+    StructOps,
+    RingBuf,
+    InodeStorage,
+    TaskStorage,
+    BloomFilter,
+}
+
+impl MapType {
+    pub(crate) fn bpf_map_type(self) -> bpf_map_type {
+        use crate::generated::bpf_map_type::*;
+        match self {
+            MapType::Hash => BPF_MAP_TYPE_HASH,
+            MapType::Array => BPF_MAP_TYPE_ARRAY,
+            MapType::ProgramArray => BPF_MAP_TYPE_PROG_ARRAY,
+            MapType::PerfEventArray => BPF_MAP_TYPE_PERF_EVENT_ARRAY,
+            MapType::PerCpuHash => BPF_MAP_TYPE_PERCPU_HASH,
+            MapType::PerCpuArray => BPF_MAP_TYPE_PERCPU_ARRAY,
+            MapType::StackTrace => BPF_MAP_TYPE_STACK_TRACE,
+            MapType::CgroupArray => BPF_MAP_TYPE_CGROUP_ARRAY,
+            MapType::LruHash => BPF_MAP_TYPE_LRU_HASH,
+            MapType::LruPerCpuHash => BPF_MAP_TYPE_LRU_PERCPU_HASH,
+            MapType::LpmTrie => BPF_MAP_TYPE_LPM_TRIE,
+            MapType::ArrayOfMaps => BPF_MAP_TYPE_ARRAY_OF_MAPS,
+            MapType::HashOfMaps => BPF_MAP_TYPE_HASH_OF_MAPS,
+            MapType::DevMap => BPF_MAP_TYPE_DEVMAP,
+            MapType::SockMap => BPF_MAP_TYPE_SOCKMAP,
+            MapType::CpuMap => BPF_MAP_TYPE_CPUMAP,
+            MapType::XskMap => BPF_MAP_TYPE_XSKMAP,
+            MapType::SockHash => BPF_MAP_TYPE_SOCKHASH,
+            MapType::CgroupStorage => BPF_MAP_TYPE_CGROUP_STORAGE,
+            MapType::ReuseportSockArray => BPF_MAP_TYPE_REUSEPORT_SOCKARRAY,
+            MapType::PerCpuCgroupStorage => BPF_MAP_TYPE_PERCPU_CGROUP_STORAGE,
+            MapType::Queue => BPF_MAP_TYPE_QUEUE,
+            MapType::Stack => BPF_MAP_TYPE_STACK,
+            MapType::SkStorage => BPF_MAP_TYPE_SK_STORAGE,
+            MapType::DevMapHash => BPF_MAP_TYPE_DEVMAP_HASH,
+            MapType::StructOps => BPF_MAP_TYPE_STRUCT_OPS,
+            MapType::RingBuf => BPF_MAP_TYPE_RINGBUF,
+            MapType::InodeStorage => BPF_MAP_TYPE_INODE_STORAGE,
+            MapType::TaskStorage => BPF_MAP_TYPE_TASK_STORAGE,
+            MapType::BloomFilter => BPF_MAP_TYPE_BLOOM_FILTER,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_map(
+    name: &str,
+    kernel_name: &str,
+    def: &bpf_map_def,
+    numa_node: Option<u32>,
+    ifindex: Option<u32>,
+    inner_map_fd: Option<RawFd>,
+    token_fd: Option<RawFd>,
+    btf: Option<MapBtf>,
+) -> Result<RawFd, MapError> {
+    let c_name = CString::new(kernel_name).map_err(|_| MapError::InvalidName {
+        name: name.to_owned(),
+    })?;
+
+    let fd = bpf_create_map(
+        &c_name,
+        def,
+        numa_node,
+        ifindex,
+        inner_map_fd,
+        token_fd,
+        btf,
+    )
+    .map_err(|(code, io_error)| {
+        let name = name.to_owned();
+        if is_memlock_error(&io_error) {
+            MapError::MemlockLimitExceeded { name, io_error }
+        } else {
+            MapError::CreateError {
+                name,
+                code,
+                io_error,
+            }
+        }
+    })? as RawFd;
+
+    Ok(fd)
+}
+
+// loads the BTF object `btf_id` the kernel keeps for a map
+fn map_btf(btf_id: u32) -> Option<Arc<Btf>> {
+    Btf::from_id(btf_id).ok().map(Arc::new)
 }
 
 /// A generic handle to a BPF map.
@@ -149,21 +334,201 @@ pub struct Map {
 
 impl Map {
     pub fn create(&mut self) -> Result<RawFd, MapError> {
+        let name = self.obj.name.clone();
+        self.create_with_token(&name, None, None, None)
+    }
+
+    // creates the map with the name `kernel_name` in the kernel. If the map has
+    // BTF types, they're looked up in the BTF object `btf_fd`. If `ifindex` is
+    // set, the map is offloaded to that network device.
+    pub(crate) fn create_with_token(
+        &mut self,
+        kernel_name: &str,
+        ifindex: Option<u32>,
+        token_fd: Option<RawFd>,
+        btf_fd: Option<RawFd>,
+    ) -> Result<RawFd, MapError> {
         let name = self.obj.name.clone();
         if self.fd.is_some() {
             return Err(MapError::AlreadyCreated { name });
         }
 
-        let c_name =
-            CString::new(name.clone()).map_err(|_| MapError::InvalidName { name: name.clone() })?;
+        // the kernel requires a template of the inner maps to create a map of
+        // maps, it's only needed until the outer map is created
+        let inner_map_fd = match &self.obj.inner {
+            Some(inner) => Some(create_map(
+                &inner.name,
+                &inner.name,
+                &inner.def,
+                inner.numa_node,
+                ifindex,
+                None,
+                token_fd,
+                None,
+            )?),
+            None => None,
+        };
+        let btf = match (btf_fd, self.obj.btf_value_type_id) {
+            (Some(fd), Some(value_type_id)) => Some(MapBtf {
+                fd,
+                key_type_id: self.obj.btf_key_type_id.unwrap_or(0),
+                value_type_id,
+            }),
+            _ => None,
+        };
+        let create = |btf| {
+            create_map(
+                &name,
+                kernel_name,
+                &self.obj.def,
+                self.obj.numa_node,
+                ifindex,
+                inner_map_fd,
+                token_fd,
+                btf,
+            )
+        };
+        let mut ret = create(btf);
+        // like libbpf, retry without BTF if the kernel rejects the types. The
+        // map works the same, it only loses the introspection info.
+        if ret.is_err() && btf.is_some() {
+            ret = create(None);
+        }
+        if let Some(fd) = inner_map_fd {
+            unsafe { libc::close(fd) };
+        }
+        let fd = ret?;
 
-        let fd = bpf_create_map(&c_name, &self.obj.def).map_err(|(code, io_error)| {
-            MapError::CreateError {
-                name,
-                code,
+        self.fd = Some(fd);
+
+        Ok(fd)
+    }
+
+    /// Creates a map from a file descriptor.
+    ///
+    /// The descriptor can come from another process, for example received with
+    /// [`recv_fds`](crate::util::recv_fds). The definition of the map is read
+    /// from the kernel, so the returned map can be converted to any of the
+    /// typed maps, which validate its type, key size and value size.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[derive(Debug, thiserror::Error)]
+    /// # enum Error {
+    /// #     #[error(transparent)]
+    /// #     IO(#[from] std::io::Error),
+    /// #     #[error(transparent)]
+    /// #     Map(#[from] aya::maps::MapError),
+    /// # }
+    /// use std::convert::TryFrom;
+    /// use std::os::unix::net::UnixStream;
+    /// use aya::maps::{HashMap, Map, MapRefMut};
+    /// use aya::util::recv_fds;
+    ///
+    /// let socket = UnixStream::connect("/run/loader.sock")?;
+    /// let fd = recv_fds(&socket, 1)?.remove(0);
+    /// let map = Map::from_fd(fd)?;
+    /// let mut counters = HashMap::<_, u32, u64>::try_from(MapRefMut::from(map))?;
+    /// counters.insert(0, 0, 0)?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn from_fd(fd: OwnedFd) -> Result<Map, MapError> {
+        let info =
+            bpf_map_get_info_by_fd(fd.as_raw_fd()).map_err(|io_error| MapError::SyscallError {
+                call: "bpf_map_get_info_by_fd".to_owned(),
+                code: -1,
                 io_error,
+            })?;
+        let name = unsafe { CStr::from_ptr(info.name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        // the BTF is only used to decode keys and values, so maps created
+        // without it or whose BTF can't be read are still usable
+        let btf = if info.btf_id != 0 {
+            map_btf(info.btf_id)
+        } else {
+            None
+        };
+        let type_id = |id| {
+            if btf.is_some() && id != 0 {
+                Some(id)
+            } else {
+                None
             }
-        })? as RawFd;
+        };
+
+        Ok(Map {
+            obj: obj::Map {
+                name,
+                def: bpf_map_def {
+                    map_type: info.type_,
+                    key_size: info.key_size,
+                    value_size: info.value_size,
+                    max_entries: info.max_entries,
+                    map_flags: info.map_flags,
+                    id: info.id,
+                    ..Default::default()
+                },
+                section_index: 0,
+                section_offset: None,
+                data: Vec::new(),
+                inner: None,
+                numa_node: None,
+                btf_key_type_id: type_id(info.btf_key_type_id),
+                btf_value_type_id: type_id(info.btf_value_type_id),
+                btf,
+            },
+            fd: Some(fd.into_raw_fd()),
+            pinned: false,
+        })
+    }
+
+    /// Opens the map with the given id.
+    ///
+    /// This gives access to maps created by other processes, for example the
+    /// ones found with `bpftool map list`, without them having to be pinned.
+    /// The returned map can be converted to a typed map like the maps
+    /// returned by [`Map::from_fd`].
+    ///
+    /// Opening maps by id requires `CAP_SYS_ADMIN`.
+    pub fn from_id(id: u32) -> Result<Map, MapError> {
+        let fd = bpf_map_get_fd_by_id(id).map_err(|io_error| MapError::SyscallError {
+            call: "bpf_map_get_fd_by_id".to_owned(),
+            code: -1,
+            io_error,
+        })?;
+        Map::from_fd(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    // uses a copy of `fd` instead of creating the map, checking that the map
+    // it refers to matches the definition
+    pub(crate) fn reuse_fd(&mut self, fd: RawFd) -> Result<RawFd, MapError> {
+        let name = self.obj.name.clone();
+        if self.fd.is_some() {
+            return Err(MapError::AlreadyCreated { name });
+        }
+        let info = bpf_map_get_info_by_fd(fd).map_err(|io_error| MapError::SyscallError {
+            call: "bpf_map_get_info_by_fd".to_owned(),
+            code: -1,
+            io_error,
+        })?;
+        let def = &self.obj.def;
+        if info.type_ != def.map_type
+            || info.key_size != def.key_size
+            || info.value_size != def.value_size
+            || info.max_entries != def.max_entries
+            || info.map_flags != def.map_flags
+        {
+            return Err(MapError::IncompatibleMap { name });
+        }
+        let fd = dup_fd(fd)
+            .map_err(|io_error| MapError::SyscallError {
+                call: "fcntl".to_owned(),
+                code: -1,
+                io_error,
+            })?
+            .into_raw_fd();
 
         self.fd = Some(fd);
 
@@ -203,6 +568,110 @@ impl Map {
         bpf_map_type::try_from(self.obj.def.map_type)
     }
 
+    /// Returns the flags the map is created with.
+    ///
+    /// Flags aya doesn't know about are omitted.
+    pub fn flags(&self) -> MapFlags {
+        MapFlags::from_bits_truncate(self.obj.def.map_flags)
+    }
+
+    /// Borrows the map file descriptor.
+    ///
+    /// If the map has not been created, [`MapError::NotCreated`] is returned.
+    pub fn borrow_fd(&self) -> Result<BorrowedFd<'_>, MapError> {
+        let fd = self.fd_or_err()?;
+        // Safety: the descriptor is owned by the map and closed when it's
+        // dropped
+        Ok(unsafe { BorrowedFd::borrow_raw(fd) })
+    }
+
+    /// Duplicates the map file descriptor.
+    ///
+    /// The returned descriptor refers to the same map and keeps it alive
+    /// until it's closed, so it can be passed to other crates or processes.
+    pub fn try_clone_fd(&self) -> Result<OwnedFd, MapError> {
+        let fd = self.fd_or_err()?;
+        dup_fd(fd).map_err(|io_error| MapError::SyscallError {
+            call: "fcntl".to_owned(),
+            code: -1,
+            io_error,
+        })
+    }
+
+    /// Decodes the raw bytes of a key of the map using its BTF type.
+    ///
+    /// See [`Map::decode_value`].
+    pub fn decode_key(&self, data: &[u8]) -> Result<BtfValue, MapError> {
+        self.decode(self.obj.btf_key_type_id, data)
+    }
+
+    /// Decodes the raw bytes of a value of the map using its BTF type.
+    ///
+    /// This is meant to display the content of maps, for example when
+    /// debugging, without having to declare a matching Rust type. Only maps
+    /// declared in the `.maps` section and maps created with BTF information
+    /// by other loaders have value types. Otherwise [`MapError::MissingBtf`]
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let bpf = aya::Bpf::load(&[])?;
+    /// let map = bpf.map("CONNECTIONS")?;
+    /// // the raw bytes of a value, for example read with a typed map
+    /// let value = [0u8; 16];
+    /// println!("{}", map.decode_value(&value)?);
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn decode_value(&self, data: &[u8]) -> Result<BtfValue, MapError> {
+        self.decode(self.obj.btf_value_type_id, data)
+    }
+
+    // BPF_F_LOCK is rejected unless the value type has a bpf_spin_lock, check
+    // it here to return a better error than EINVAL
+    pub(crate) fn check_spin_lock(&self) -> Result<(), MapError> {
+        let has_lock = match (&self.obj.btf, self.obj.btf_value_type_id) {
+            (Some(btf), Some(type_id)) => btf.has_spin_lock(type_id)?,
+            _ => {
+                return Err(MapError::MissingBtf {
+                    name: self.obj.name.clone(),
+                })
+            }
+        };
+        if !has_lock {
+            return Err(MapError::NoSpinLock {
+                name: self.obj.name.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn decode(&self, type_id: Option<u32>, data: &[u8]) -> Result<BtfValue, MapError> {
+        match (&self.obj.btf, type_id) {
+            (Some(btf), Some(type_id)) => Ok(btf.decode_value(type_id, data)?),
+            _ => Err(MapError::MissingBtf {
+                name: self.obj.name.clone(),
+            }),
+        }
+    }
+
+    /// Creates a new handle to the same map.
+    ///
+    /// The new handle has its own duplicate of the map file descriptor, so it
+    /// doesn't borrow the [`Bpf`](crate::Bpf) instance the map was loaded by,
+    /// and can be moved to other threads and converted to a typed map. The map
+    /// is destroyed once all the handles and programs using it are dropped.
+    ///
+    /// See also [`MapRef::try_clone`] and [`MapRefMut::try_clone`].
+    pub fn try_clone(&self) -> Result<Map, MapError> {
+        let fd = self.try_clone_fd()?;
+        Ok(Map {
+            obj: self.obj.clone(),
+            fd: Some(fd.into_raw_fd()),
+            pinned: self.pinned,
+        })
+    }
+
     pub(crate) fn fd_or_err(&self) -> Result<RawFd, MapError> {
         self.fd.ok_or_else(|| MapError::NotCreated {
             name: self.obj.name.clone(),
@@ -245,13 +714,123 @@ pub(crate) trait IterableMap<K: Pod, V> {
     fn map(&self) -> &Map;
 
     unsafe fn get(&self, key: &K) -> Result<V, MapError>;
+
+    /// Decodes a value returned by `BPF_MAP_LOOKUP_BATCH`.
+    ///
+    /// Maps that don't support batched lookups return `None`, in which case
+    /// the value is looked up with [`IterableMap::get`].
+    fn value_from_bytes(&self, _bytes: &[u8]) -> Option<V> {
+        None
+    }
+}
+
+// number of entries requested with each BPF_MAP_LOOKUP_BATCH call
+const BATCH_SIZE: usize = 256;
+
+// Keys and values fetched with BPF_MAP_LOOKUP_BATCH.
+struct Batch {
+    key_size: usize,
+    value_size: usize,
+    capacity: usize,
+    // the position token passed to and returned by the kernel
+    in_batch: Vec<u8>,
+    out_batch: Vec<u8>,
+    keys: Vec<u8>,
+    values: Vec<u8>,
+    count: usize,
+    pos: usize,
+    started: bool,
+    done: bool,
+}
+
+impl Batch {
+    fn new(map: &Map) -> Option<Batch> {
+        use bpf_map_type::*;
+
+        let def = &map.obj.def;
+        let value_size = def.value_size as usize;
+        let value_size = match def.map_type {
+            x if x == BPF_MAP_TYPE_HASH as u32 || x == BPF_MAP_TYPE_LRU_HASH as u32 => value_size,
+            x if x == BPF_MAP_TYPE_PERCPU_HASH as u32
+                || x == BPF_MAP_TYPE_LRU_PERCPU_HASH as u32 =>
+            {
+                ((value_size + 7) & !7) * nr_cpus().ok()?
+            }
+            _ => return None,
+        };
+        let key_size = def.key_size as usize;
+        // hash maps use the bucket index as token, other maps use a key
+        let token_size = key_size.max(mem::size_of::<u32>());
+
+        Some(Batch {
+            key_size,
+            value_size,
+            capacity: BATCH_SIZE,
+            in_batch: vec![0u8; token_size],
+            out_batch: vec![0u8; token_size],
+            keys: Vec::new(),
+            values: Vec::new(),
+            count: 0,
+            pos: 0,
+            started: false,
+            done: false,
+        })
+    }
+
+    fn fill(&mut self, fd: RawFd) -> Result<(), (libc::c_long, io::Error)> {
+        loop {
+            self.keys.resize(self.capacity * self.key_size, 0);
+            self.values.resize(self.capacity * self.value_size, 0);
+            let mut count = self.capacity as u32;
+            let ret = bpf_map_lookup_batch(
+                fd,
+                if self.started {
+                    Some(&self.in_batch)
+                } else {
+                    None
+                },
+                &mut self.out_batch,
+                &mut self.keys,
+                &mut self.values,
+                &mut count,
+            );
+            match ret {
+                Ok(_) => {}
+                // there are no more entries, but count might still be > 0
+                Err((_, io_error)) if io_error.raw_os_error() == Some(libc::ENOENT) => {
+                    self.done = true
+                }
+                // a bucket holds more entries than the buffers can store
+                Err((_, io_error))
+                    if io_error.raw_os_error() == Some(libc::ENOSPC) && count == 0 =>
+                {
+                    self.capacity *= 2;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            mem::swap(&mut self.in_batch, &mut self.out_batch);
+            self.started = true;
+            self.count = count as usize;
+            self.pos = 0;
+            return Ok(());
+        }
+    }
 }
 
+type MapEntry<'a, K> = (K, Option<&'a [u8]>);
+
 /// Iterator returned by `map.keys()`.
+///
+/// Hash maps are read in chunks with `BPF_MAP_LOOKUP_BATCH` when the kernel
+/// supports it (5.6 or newer), otherwise the keys are retrieved one at a time
+/// with `BPF_MAP_GET_NEXT_KEY`.
 pub struct MapKeys<'coll, K: Pod> {
     map: &'coll Map,
     err: bool,
     key: Option<K>,
+    batch: Option<Batch>,
 }
 
 impl<'coll, K: Pod> MapKeys<'coll, K> {
@@ -260,14 +839,12 @@ impl<'coll, K: Pod> MapKeys<'coll, K> {
             map,
             err: false,
             key: None,
+            batch: Batch::new(map),
         }
     }
-}
 
-impl<K: Pod> Iterator for MapKeys<'_, K> {
-    type Item = Result<K, MapError>;
-
-    fn next(&mut self) -> Option<Result<K, MapError>> {
+    // Returns the next key, and its raw value if it was fetched in a batch.
+    fn next_entry(&mut self) -> Option<Result<MapEntry<'_, K>, MapError>> {
         if self.err {
             return None;
         }
@@ -280,10 +857,40 @@ impl<K: Pod> Iterator for MapKeys<'_, K> {
             }
         };
 
+        if let Some(batch) = self.batch.as_mut() {
+            if batch.pos == batch.count && !batch.done {
+                if let Err((code, io_error)) = batch.fill(fd) {
+                    if batch.started {
+                        self.err = true;
+                        return Some(Err(MapError::SyscallError {
+                            call: "bpf_map_lookup_batch".to_owned(),
+                            code,
+                            io_error,
+                        }));
+                    }
+                    // batched lookups aren't supported, use get_next_key
+                    self.batch = None;
+                }
+            }
+        }
+
+        if let Some(batch) = self.batch.as_mut() {
+            if batch.pos == batch.count {
+                return None;
+            }
+            let pos = batch.pos;
+            batch.pos += 1;
+            let key = unsafe {
+                ptr::read_unaligned(batch.keys[pos * batch.key_size..].as_ptr() as *const K)
+            };
+            let value = &batch.values[pos * batch.value_size..(pos + 1) * batch.value_size];
+            return Some(Ok((key, Some(value))));
+        }
+
         match bpf_map_get_next_key(fd, self.key.as_ref()) {
             Ok(Some(key)) => {
                 self.key = Some(key);
-                Some(Ok(key))
+                Some(Ok((key, None)))
             }
             Ok(None) => {
                 self.key = None;
@@ -301,6 +908,14 @@ impl<K: Pod> Iterator for MapKeys<'_, K> {
     }
 }
 
+impl<K: Pod> Iterator for MapKeys<'_, K> {
+    type Item = Result<K, MapError>;
+
+    fn next(&mut self) -> Option<Result<K, MapError>> {
+        self.next_entry().map(|entry| entry.map(|(key, _)| key))
+    }
+}
+
 /// Iterator returned by `map.iter()`.
 pub struct MapIter<'coll, K: Pod, V> {
     keys: MapKeys<'coll, K>,
@@ -322,10 +937,14 @@ impl<K: Pod, V> Iterator for MapIter<'_, K, V> {
     type Item = Result<(K, V), MapError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
         loop {
-            match self.keys.next() {
-                Some(Ok(key)) => {
-                    let value = unsafe { self.map.get(&key) };
+            match self.keys.next_entry() {
+                Some(Ok((key, bytes))) => {
+                    if let Some(value) = bytes.and_then(|bytes| map.value_from_bytes(bytes)) {
+                        return Some(Ok((key, value)));
+                    }
+                    let value = unsafe { map.get(&key) };
                     match value {
                         Ok(value) => return Some(Ok((key, value))),
                         Err(MapError::KeyNotFound) => continue,
@@ -339,6 +958,50 @@ impl<K: Pod, V> Iterator for MapIter<'_, K, V> {
     }
 }
 
+impl TryFrom<u32> for MapType {
+    type Error = MapError;
+
+    fn try_from(map_type: u32) -> Result<Self, Self::Error> {
+        use MapType::*;
+        [
+            Hash,
+            Array,
+            ProgramArray,
+            PerfEventArray,
+            PerCpuHash,
+            PerCpuArray,
+            StackTrace,
+            CgroupArray,
+            LruHash,
+            LruPerCpuHash,
+            LpmTrie,
+            ArrayOfMaps,
+            HashOfMaps,
+            DevMap,
+            SockMap,
+            CpuMap,
+            XskMap,
+            SockHash,
+            CgroupStorage,
+            ReuseportSockArray,
+            PerCpuCgroupStorage,
+            Queue,
+            Stack,
+            SkStorage,
+            DevMapHash,
+            StructOps,
+            RingBuf,
+            InodeStorage,
+            TaskStorage,
+            BloomFilter,
+        ]
+        .iter()
+        .copied()
+        .find(|t| t.bpf_map_type() as u32 == map_type)
+        .ok_or(MapError::InvalidMapType { map_type })
+    }
+}
+
 impl TryFrom<u32> for bpf_map_type {
     type Error = MapError;
 
@@ -415,9 +1078,49 @@ impl PerCpuKernelMem {
 /// let values = PerCpuValues::try_from(vec![42u32; nr_cpus()?])?;
 /// # Ok::<(), Error>(())
 /// ```
-#[derive(Debug)]
+///
+/// Lookups like [`PerCpuArray::get`](crate::maps::PerCpuArray::get) allocate
+/// new values on every call. When looking up values often, the same values can
+/// be reused with [`PerCpuArray::get_into`](crate::maps::PerCpuArray::get_into)
+/// and [`PerCpuHashMap::get_into`](crate::maps::PerCpuHashMap::get_into), which
+/// don't allocate:
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Map(#[from] aya::maps::MapError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::{PerCpuArray, PerCpuValues};
+/// use aya::util::nr_cpus;
+/// use std::convert::TryFrom;
+///
+/// let array = PerCpuArray::try_from(bpf.map("COUNTERS")?)?;
+/// let mut values = PerCpuValues::try_from(vec![0u64; nr_cpus()?])?;
+/// for index in 0..array.len() {
+///     array.get_into(&index, &mut values, 0)?;
+///     let total: u64 = values.iter().sum();
+/// }
+/// # Ok::<(), Error>(())
+/// ```
 pub struct PerCpuValues<T: Pod> {
     values: Box<[T]>,
+    // the values of all the CPUs as copied by the kernel, kept around so that
+    // looking up the same values again doesn't allocate
+    kernel_mem: Option<PerCpuKernelMem>,
+}
+
+impl<T: Pod + fmt::Debug> fmt::Debug for PerCpuValues<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PerCpuValues")
+            .field("values", &self.values)
+            .finish()
+    }
 }
 
 impl<T: Pod> TryFrom<Vec<T>> for PerCpuValues<T> {
@@ -433,6 +1136,7 @@ impl<T: Pod> TryFrom<Vec<T>> for PerCpuValues<T> {
         }
         Ok(PerCpuValues {
             values: values.into_boxed_slice(),
+            kernel_mem: None,
         })
     }
 }
@@ -445,6 +1149,16 @@ impl<T: Pod> PerCpuValues<T> {
         })
     }
 
+    pub(crate) fn kernel_mem_from_bytes(bytes: &[u8]) -> Option<PerCpuKernelMem> {
+        let value_size = (mem::size_of::<T>() + 7) & !7;
+        if bytes.len() != nr_cpus().ok()? * value_size {
+            return None;
+        }
+        Some(PerCpuKernelMem {
+            bytes: bytes.to_vec(),
+        })
+    }
+
     pub(crate) unsafe fn from_kernel_mem(mem: PerCpuKernelMem) -> PerCpuValues<T> {
         let mem_ptr = mem.bytes.as_ptr() as usize;
         let value_size = (mem::size_of::<T>() + 7) & !7;
@@ -457,6 +1171,31 @@ impl<T: Pod> PerCpuValues<T> {
 
         PerCpuValues {
             values: values.into_boxed_slice(),
+            kernel_mem: Some(mem),
+        }
+    }
+
+    /// Returns the memory the kernel copies the values to, allocating it on
+    /// first use.
+    pub(crate) fn kernel_mem_mut(&mut self) -> &mut PerCpuKernelMem {
+        let value_size = (mem::size_of::<T>() + 7) & !7;
+        let len = self.values.len() * value_size;
+        self.kernel_mem.get_or_insert_with(|| PerCpuKernelMem {
+            bytes: vec![0u8; len],
+        })
+    }
+
+    /// Updates the values from the memory returned by
+    /// [`PerCpuValues::kernel_mem_mut`], once the kernel has written to it.
+    pub(crate) unsafe fn read_kernel_mem(&mut self) {
+        let mem = match &self.kernel_mem {
+            Some(mem) => mem,
+            None => return,
+        };
+        let mem_ptr = mem.bytes.as_ptr() as usize;
+        let value_size = (mem::size_of::<T>() + 7) & !7;
+        for (i, value) in self.values.iter_mut().enumerate() {
+            *value = ptr::read_unaligned((mem_ptr + i * value_size) as *const _);
         }
     }
 
@@ -486,7 +1225,7 @@ mod tests {
 
     use crate::{
         bpf_map_def,
-        generated::{bpf_cmd, bpf_map_type::BPF_MAP_TYPE_HASH},
+        generated::{bpf_cmd, bpf_map_info, bpf_map_type::BPF_MAP_TYPE_HASH},
         sys::{override_syscall, Syscall},
     };
 
@@ -503,7 +1242,13 @@ mod tests {
                 ..Default::default()
             },
             section_index: 0,
+            section_offset: None,
             data: Vec::new(),
+            inner: None,
+            numa_node: None,
+            btf_key_type_id: None,
+            btf_value_type_id: None,
+            btf: None,
         }
     }
 
@@ -550,4 +1295,77 @@ mod tests {
         }
         assert_eq!(map.fd, None);
     }
+
+    #[test]
+    fn test_reuse_fd() {
+        override_syscall(|call| match call {
+            Syscall::Bpf {
+                cmd: bpf_cmd::BPF_OBJ_GET_INFO_BY_FD,
+                attr,
+            } => {
+                let info = unsafe { &mut *(attr.info.info as *mut bpf_map_info) };
+                info.type_ = BPF_MAP_TYPE_HASH as u32;
+                info.key_size = 4;
+                info.value_size = 4;
+                info.max_entries = 1024;
+                Ok(0)
+            }
+            _ => Err((-1, io::Error::from_raw_os_error(EFAULT))),
+        });
+
+        let file = std::fs::File::open("/dev/null").unwrap();
+        let mut map = new_map("foo");
+        let fd = map.reuse_fd(file.as_raw_fd()).unwrap();
+        assert_eq!(map.fd, Some(fd));
+        assert_ne!(fd, file.as_raw_fd());
+
+        let mut map = new_map("bar");
+        map.obj.def.max_entries = 1;
+        assert!(matches!(
+            map.reuse_fd(file.as_raw_fd()),
+            Err(MapError::IncompatibleMap { .. })
+        ));
+        assert_eq!(map.fd, None);
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let file = std::fs::File::open("/dev/null").unwrap();
+        let map = Map {
+            obj: new_obj_map("foo"),
+            fd: Some(file.into_raw_fd()),
+            pinned: false,
+        };
+
+        let clone = map.try_clone().unwrap();
+        assert_eq!(clone.name(), "foo");
+        assert!(clone.fd.is_some());
+        assert_ne!(clone.fd, map.fd);
+
+        let map = Map {
+            obj: new_obj_map("bar"),
+            fd: None,
+            pinned: false,
+        };
+        assert!(matches!(map.try_clone(), Err(MapError::NotCreated { .. })));
+    }
+
+    #[test]
+    fn test_per_cpu_values_reuse_kernel_mem() {
+        let nr_cpus = nr_cpus().unwrap();
+        let mut values = PerCpuValues::try_from(vec![0u32; nr_cpus]).unwrap();
+
+        // values are padded to 8 bytes
+        let mem = values.kernel_mem_mut();
+        assert_eq!(mem.bytes.len(), nr_cpus * 8);
+        for (cpu, chunk) in mem.bytes.chunks_mut(8).enumerate() {
+            chunk[..4].copy_from_slice(&(cpu as u32 + 1).to_ne_bytes());
+        }
+        let ptr = mem.as_mut_ptr();
+        unsafe { values.read_kernel_mem() };
+        assert_eq!(&values[..], &(1..=nr_cpus as u32).collect::<Vec<_>>()[..]);
+
+        // the memory is allocated once
+        assert_eq!(values.kernel_mem_mut().as_mut_ptr(), ptr);
+    }
 }
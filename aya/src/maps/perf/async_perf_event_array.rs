@@ -5,6 +5,16 @@ use std::{
     os::unix::prelude::{AsRawFd, RawFd},
 };
 
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+use futures::{ready, Stream};
+
 #[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
 use async_io::Async;
 
@@ -12,7 +22,7 @@ use async_io::Async;
 use tokio::io::unix::AsyncFd;
 
 use crate::maps::{
-    perf::{Events, PerfBufferError, PerfEventArray, PerfEventArrayBuffer},
+    perf::{Events, PerfBufferError, PerfBufferWakeup, PerfEventArray, PerfEventArrayBuffer},
     Map, MapError, MapRefMut,
 };
 
@@ -26,6 +36,10 @@ use crate::maps::{
 /// * call [`AsyncPerfEventArray::open`]
 /// * call [`AsyncPerfEventArrayBuffer::read_events`] to read the events
 ///
+/// [`AsyncPerfEventArrayBuffer`] also implements `futures::Stream`,
+/// yielding one event at a time, so buffers can be combined with the
+/// `StreamExt` combinators instead of being read in a loop.
+///
 /// # Minimum kernel version
 ///
 /// The minimum kernel version required to use this feature is 4.3.
@@ -86,12 +100,71 @@ use crate::maps::{
 /// # Ok(())
 /// # }
 /// ```
+///
+/// Reading the events of all the CPUs from a single task, using the buffers
+/// as streams:
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #    #[error(transparent)]
+/// #    IO(#[from] std::io::Error),
+/// #    #[error(transparent)]
+/// #    Map(#[from] aya::maps::MapError),
+/// #    #[error(transparent)]
+/// #    Bpf(#[from] aya::BpfError),
+/// #    #[error(transparent)]
+/// #    PerfBuf(#[from] aya::maps::perf::PerfBufferError),
+/// # }
+/// # #[cfg(feature = "async_tokio")]
+/// # async fn try_main() -> Result<(), Error> {
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::perf::AsyncPerfEventArray;
+/// use aya::util::online_cpus;
+/// use std::convert::TryFrom;
+/// use futures::stream::{self, StreamExt};
+///
+/// let mut perf_array = AsyncPerfEventArray::try_from(bpf.map_mut("PERF_ARRAY")?)?;
+/// let buffers = online_cpus()?
+///     .into_iter()
+///     .map(|cpu_id| perf_array.open(cpu_id, None))
+///     .collect::<Result<Vec<_>, _>>()?;
+///
+/// let mut events = stream::select_all(buffers);
+/// while let Some(event) = events.next().await {
+///     let buf = event?;
+///     // process buf
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[doc(alias = "BPF_MAP_TYPE_PERF_EVENT_ARRAY")]
 pub struct AsyncPerfEventArray<T: DerefMut<Target = Map>> {
     perf_map: PerfEventArray<T>,
 }
 
 impl<T: DerefMut<Target = Map>> AsyncPerfEventArray<T> {
+    /// Sets the number of pages of the buffers opened with a `page_count` of
+    /// `None`.
+    ///
+    /// See [`PerfEventArray::set_page_count`].
+    pub fn set_page_count(&mut self, page_count: usize) -> Result<(), PerfBufferError> {
+        self.perf_map.set_page_count(page_count)
+    }
+
+    /// Sets when the readers of the buffers opened from now on are woken up.
+    ///
+    /// See [`PerfEventArray::set_wakeup`].
+    pub fn set_wakeup(&mut self, wakeup: PerfBufferWakeup) {
+        self.perf_map.set_wakeup(wakeup)
+    }
+
+    /// Returns the total number of events lost by all the buffers opened
+    /// from this array.
+    pub fn lost_events(&self) -> u64 {
+        self.perf_map.lost_events()
+    }
+
     /// Opens the perf buffer at the given index.
     ///
     /// The returned buffer will receive all the events eBPF programs send at the given index.
@@ -127,8 +200,11 @@ impl<T: DerefMut<Target = Map>> AsyncPerfEventArray<T> {
 /// [`AsyncPerfEventArrayBuffer`] is a ring buffer that can receive events from eBPF programs that
 /// use `bpf_perf_event_output()`. It's returned by [`AsyncPerfEventArray::open`].
 ///
+/// Events can be read in batches with `read_events()`, or one at a time
+/// through the `futures::Stream` implementation.
+///
 /// See the [`AsyncPerfEventArray` documentation](AsyncPerfEventArray) for an overview of how to
-/// use perf buffers.
+/// use perf buffers, and an example reading the buffers as streams.
 pub struct AsyncPerfEventArrayBuffer<T: DerefMut<Target = Map>> {
     buf: PerfEventArrayBuffer<T>,
 
@@ -139,6 +215,14 @@ pub struct AsyncPerfEventArrayBuffer<T: DerefMut<Target = Map>> {
     async_fd: Async<RawFd>,
 }
 
+impl<T: DerefMut<Target = Map>> AsyncPerfEventArrayBuffer<T> {
+    /// Returns the total number of events lost by this buffer since it was
+    /// opened.
+    pub fn lost_events(&self) -> u64 {
+        self.buf.lost_events()
+    }
+}
+
 #[cfg(any(feature = "async_tokio", doc))]
 impl<T: DerefMut<Target = Map>> AsyncPerfEventArrayBuffer<T> {
     /// Reads events from the buffer.
@@ -198,6 +282,57 @@ impl<T: DerefMut<Target = Map>> AsyncPerfEventArrayBuffer<T> {
     }
 }
 
+#[cfg(feature = "async_tokio")]
+impl<T: DerefMut<Target = Map>> AsyncPerfEventArrayBuffer<T> {
+    fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            let mut guard = ready!(self.async_fd.poll_read_ready(cx))?;
+            if self.buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            guard.clear_ready();
+        }
+    }
+}
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+impl<T: DerefMut<Target = Map>> AsyncPerfEventArrayBuffer<T> {
+    fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            ready!(self.async_fd.poll_readable(cx))?;
+        }
+    }
+}
+
+/// Yields the events of the buffer one at a time, as they become available.
+///
+/// Each event is copied into its own [`BytesMut`]. Lost events are counted in
+/// [`AsyncPerfEventArrayBuffer::lost_events`].
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+impl<T: DerefMut<Target = Map> + Unpin> Stream for AsyncPerfEventArrayBuffer<T> {
+    type Item = Result<BytesMut, PerfBufferError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.buf.next_event() {
+                let data = BytesMut::from(&event[..]);
+                event.consume();
+                return Poll::Ready(Some(Ok(data)));
+            }
+            if let Err(e) = ready!(this.poll_readable(cx)) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+        }
+    }
+}
+
 impl TryFrom<MapRefMut> for AsyncPerfEventArray<MapRefMut> {
     type Error = MapError;
 
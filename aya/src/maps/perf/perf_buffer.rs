@@ -1,6 +1,8 @@
 use std::{
+    cmp,
     ffi::c_void,
     io, mem,
+    ops::Deref,
     os::unix::io::{AsRawFd, RawFd},
     ptr, slice,
     sync::atomic::{self, AtomicPtr, Ordering},
@@ -16,7 +18,7 @@ use crate::{
         perf_event_type::{PERF_RECORD_LOST, PERF_RECORD_SAMPLE},
     },
     sys::{perf_event_ioctl, perf_event_open_bpf},
-    PERF_EVENT_IOC_DISABLE, PERF_EVENT_IOC_ENABLE,
+    PERF_EVENT_IOC_DISABLE, PERF_EVENT_IOC_ENABLE, PERF_EVENT_IOC_PAUSE_OUTPUT,
 };
 
 /// Perf buffer error.
@@ -56,11 +58,54 @@ pub enum PerfBufferError {
     #[error("the buffer needs to be of at least {size} bytes")]
     MoreSpaceNeeded { size: usize },
 
+    /// The buffer was opened in overwrite mode and can only be read with
+    /// `read_snapshot()`.
+    #[error("the buffer is in overwrite mode, use read_snapshot() to read it")]
+    OverwriteMode,
+
+    /// `read_snapshot()` was called on a buffer that isn't in overwrite mode.
+    #[error("the buffer is not in overwrite mode")]
+    NotOverwriteMode,
+
+    /// Resuming the output of the buffer after `read_snapshot()` failed. The
+    /// output of the buffer stays paused.
+    #[error(
+        "PERF_EVENT_IOC_PAUSE_OUTPUT failed resuming the output: {io_error}{}",
+        .read_error.as_ref().map(|e| format!(", after the snapshot failed: {}", e)).unwrap_or_default()
+    )]
+    ResumeError {
+        /// The error resuming the output.
+        #[source]
+        io_error: io::Error,
+        /// The error reading the snapshot, if reading it failed too.
+        read_error: Option<Box<PerfBufferError>>,
+    },
+
     /// An IO error occurred.
     #[error(transparent)]
     IOError(#[from] io::Error),
 }
 
+/// Controls when the readers of a perf buffer are woken up.
+///
+/// Waking up readers less often reduces the number of context switches when
+/// eBPF programs produce many small events, at the cost of latency.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PerfBufferWakeup {
+    /// Wake up the readers every time the given number of events has been
+    /// written.
+    Events(u32),
+    /// Wake up the readers every time the given number of bytes has been
+    /// written.
+    Watermark(u32),
+}
+
+impl Default for PerfBufferWakeup {
+    fn default() -> Self {
+        PerfBufferWakeup::Events(1)
+    }
+}
+
 /// Return type of `read_events()`.
 #[derive(Debug, PartialEq)]
 pub struct Events {
@@ -75,6 +120,39 @@ pub(crate) struct PerfBuffer {
     size: usize,
     page_size: usize,
     fd: RawFd,
+    lost: u64,
+    overwrite: bool,
+    // holds the samples that wrap around the end of the buffer, which can't be
+    // borrowed as a contiguous slice
+    scratch: Vec<u8>,
+}
+
+/// An event borrowed from a perf buffer, returned by
+/// [`PerfEventArrayBuffer::next_event`](crate::maps::perf::PerfEventArrayBuffer::next_event).
+///
+/// The item derefs to the event data. The event stays in the buffer until
+/// [`PerfBufferItem::consume`] is called, so dropping the item without
+/// consuming it returns the same event again on the next read.
+pub struct PerfBufferItem<'a> {
+    data: &'a [u8],
+    header: *mut perf_event_mmap_page,
+    next_tail: u64,
+}
+
+impl PerfBufferItem<'_> {
+    /// Removes the event from the buffer, making room for new events.
+    pub fn consume(self) {
+        atomic::fence(Ordering::SeqCst);
+        unsafe { (*self.header).data_tail = self.next_tail };
+    }
+}
+
+impl Deref for PerfBufferItem<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
 }
 
 impl PerfBuffer {
@@ -82,25 +160,26 @@ impl PerfBuffer {
         cpu_id: u32,
         page_size: usize,
         page_count: usize,
+        wakeup: PerfBufferWakeup,
+        overwrite: bool,
     ) -> Result<PerfBuffer, PerfBufferError> {
         if !page_count.is_power_of_two() {
             return Err(PerfBufferError::InvalidPageCount { page_count });
         }
 
-        let fd = perf_event_open_bpf(cpu_id as i32)
+        let fd = perf_event_open_bpf(cpu_id as i32, wakeup, overwrite)
             .map_err(|(_, io_error)| PerfBufferError::OpenError { io_error })?
             as RawFd;
         let size = page_size * page_count;
-        let buf = unsafe {
-            mmap(
-                ptr::null_mut(),
-                size + page_size,
-                PROT_READ | PROT_WRITE,
-                MAP_SHARED,
-                fd,
-                0,
-            )
+        // mapping the buffer read-only tells the kernel that user space doesn't
+        // update data_tail, so it overwrites the oldest events instead of
+        // dropping the new ones
+        let prot = if overwrite {
+            PROT_READ
+        } else {
+            PROT_READ | PROT_WRITE
         };
+        let buf = unsafe { mmap(ptr::null_mut(), size + page_size, prot, MAP_SHARED, fd, 0) };
         if buf == MAP_FAILED {
             return Err(PerfBufferError::MMapError {
                 io_error: io::Error::last_os_error(),
@@ -112,6 +191,9 @@ impl PerfBuffer {
             fd,
             size,
             page_size,
+            lost: 0,
+            overwrite,
+            scratch: Vec::new(),
         };
 
         perf_event_ioctl(fd, PERF_EVENT_IOC_ENABLE, 0)
@@ -122,15 +204,23 @@ impl PerfBuffer {
 
     pub(crate) fn readable(&self) -> bool {
         let header = self.buf.load(Ordering::SeqCst);
-        let head = unsafe { (*header).data_head } as usize;
-        let tail = unsafe { (*header).data_tail } as usize;
+        let head = unsafe { (*header).data_head };
+        let tail = unsafe { (*header).data_tail };
         head != tail
     }
 
+    /// Returns the total number of events lost since the buffer was opened.
+    pub(crate) fn lost(&self) -> u64 {
+        self.lost
+    }
+
     pub(crate) fn read_events(
         &mut self,
         buffers: &mut [BytesMut],
     ) -> Result<Events, PerfBufferError> {
+        if self.overwrite {
+            return Err(PerfBufferError::OverwriteMode);
+        }
         if buffers.is_empty() {
             return Err(PerfBufferError::NoBuffers);
         }
@@ -140,27 +230,6 @@ impl PerfBuffer {
         let mut events = Events { read: 0, lost: 0 };
         let mut buf_n = 0;
 
-        let fill_buf = |start_off, base, mmap_size, out_buf: &mut [u8]| {
-            let len = out_buf.len();
-
-            let end = (start_off + len) % mmap_size;
-            let start = start_off % mmap_size;
-
-            if start < end {
-                out_buf.copy_from_slice(unsafe {
-                    slice::from_raw_parts((base + start) as *const u8, len)
-                });
-            } else {
-                let size = mmap_size - start;
-                unsafe {
-                    out_buf[..size]
-                        .copy_from_slice(slice::from_raw_parts((base + start) as *const u8, size));
-                    out_buf[size..]
-                        .copy_from_slice(slice::from_raw_parts(base as *const u8, len - size));
-                }
-            }
-        };
-
         let read_event = |event_start, event_type, base, buf: &mut BytesMut| {
             let sample_size = match event_type {
                 x if x == PERF_RECORD_SAMPLE as u32 || x == PERF_RECORD_LOST as u32 => {
@@ -207,8 +276,11 @@ impl PerfBuffer {
             }
         };
 
-        let head = unsafe { (*header).data_head } as usize;
-        let mut tail = unsafe { (*header).data_tail } as usize;
+        // the positions are 64 bits wide on all architectures, they must not be
+        // truncated or the kernel sees a bogus tail once 4GB have been read on
+        // 32-bit architectures
+        let head = unsafe { (*header).data_head };
+        let mut tail = unsafe { (*header).data_tail };
         while head != tail {
             if buf_n == buffers.len() {
                 break;
@@ -216,7 +288,7 @@ impl PerfBuffer {
 
             let buf = &mut buffers[buf_n];
 
-            let event_start = tail % self.size;
+            let event_start = (tail % self.size as u64) as usize;
             let event =
                 unsafe { ptr::read_unaligned((base + event_start) as *const perf_event_header) };
             let event_size = event.size as usize;
@@ -240,20 +312,219 @@ impl PerfBuffer {
                     // we got an error and we didn't process any events, propagate the error
                     // and give the caller a chance to increase buffers
                     atomic::fence(Ordering::SeqCst);
-                    unsafe { (*header).data_tail = tail as u64 };
+                    unsafe { (*header).data_tail = tail };
+                    self.lost += events.lost as u64;
                     return Err(e);
                 }
             }
-            tail += event_size;
+            tail += event_size as u64;
         }
 
         atomic::fence(Ordering::SeqCst);
-        unsafe { (*header).data_tail = tail as u64 };
+        unsafe { (*header).data_tail = tail };
+        self.lost += events.lost as u64;
+
+        Ok(events)
+    }
+
+    /// Returns the next sample without copying it, unless it wraps around the
+    /// end of the buffer. The lost events found before the sample are
+    /// consumed and added to `lost`.
+    pub(crate) fn next_event(&mut self, lost: &mut u64) -> Option<PerfBufferItem<'_>> {
+        if self.overwrite {
+            return None;
+        }
+        let header = self.buf.load(Ordering::SeqCst);
+        let base = header as usize + self.page_size;
+
+        let head = unsafe { (*header).data_head };
+        let start_tail = unsafe { (*header).data_tail };
+        let mut tail = start_tail;
+        let mut sample = None;
+        while head != tail {
+            let event_start = (tail % self.size as u64) as usize;
+            let event =
+                unsafe { ptr::read_unaligned((base + event_start) as *const perf_event_header) };
+            let next_tail = tail + event.size as u64;
+
+            match event.type_ {
+                x if x == PERF_RECORD_SAMPLE as u32 => {
+                    let mut size = [0u8; mem::size_of::<u32>()];
+                    fill_buf(
+                        event_start + mem::size_of::<perf_event_header>(),
+                        base,
+                        self.size,
+                        &mut size,
+                    );
+                    let sample_start =
+                        (event_start + mem::size_of::<perf_event_header>() + mem::size_of::<u32>())
+                            % self.size;
+                    sample = Some((sample_start, u32::from_ne_bytes(size) as usize, next_tail));
+                    break;
+                }
+                x if x == PERF_RECORD_LOST as u32 => {
+                    let mut count = [0u8; mem::size_of::<u64>()];
+                    fill_buf(
+                        event_start + mem::size_of::<perf_event_header>() + mem::size_of::<u64>(),
+                        base,
+                        self.size,
+                        &mut count,
+                    );
+                    let count = u64::from_ne_bytes(count);
+                    *lost += count;
+                    self.lost += count;
+                }
+                _ => { /* skip unknown event type */ }
+            }
+            tail = next_tail;
+        }
+
+        if tail != start_tail {
+            atomic::fence(Ordering::SeqCst);
+            unsafe { (*header).data_tail = tail };
+        }
+
+        let (sample_start, sample_size, next_tail) = sample?;
+        let data = if sample_start + sample_size <= self.size {
+            unsafe { slice::from_raw_parts((base + sample_start) as *const u8, sample_size) }
+        } else {
+            self.scratch.resize(sample_size, 0);
+            fill_buf(sample_start, base, self.size, &mut self.scratch);
+            &self.scratch
+        };
+
+        Some(PerfBufferItem {
+            data,
+            header,
+            next_tail,
+        })
+    }
+
+    /// Reads the events of an overwrite mode buffer, newest first.
+    ///
+    /// Output is paused while the buffer is read so that the kernel doesn't
+    /// overwrite the events being read, then resumed.
+    pub(crate) fn read_snapshot(
+        &mut self,
+        buffers: &mut [BytesMut],
+    ) -> Result<Events, PerfBufferError> {
+        if !self.overwrite {
+            return Err(PerfBufferError::NotOverwriteMode);
+        }
+        if buffers.is_empty() {
+            return Err(PerfBufferError::NoBuffers);
+        }
+
+        perf_event_ioctl(self.fd, PERF_EVENT_IOC_PAUSE_OUTPUT, 1)
+            .map_err(|(_, io_error)| io_error)?;
+        let events = self.read_backward(buffers);
+        // the output is resumed even if reading failed. The lost events of
+        // the snapshot aren't added to `lost`, the next snapshot counts them
+        // again until they're overwritten.
+        match (
+            events,
+            perf_event_ioctl(self.fd, PERF_EVENT_IOC_PAUSE_OUTPUT, 0),
+        ) {
+            (events, Ok(_)) => events,
+            (events, Err((_, io_error))) => Err(PerfBufferError::ResumeError {
+                io_error,
+                read_error: events.err().map(Box::new),
+            }),
+        }
+    }
+
+    fn read_backward(&mut self, buffers: &mut [BytesMut]) -> Result<Events, PerfBufferError> {
+        let header = self.buf.load(Ordering::SeqCst);
+        let base = header as usize + self.page_size;
+
+        // the head starts at 0 and moves backwards, so the newest event is at
+        // the head and the events after it are older. Until the buffer wraps,
+        // only the bytes between the head and 0 have been written.
+        let head = unsafe { ptr::read_volatile(&(*header).data_head) };
+        atomic::fence(Ordering::SeqCst);
+        let written = cmp::min(0u64.wrapping_sub(head), self.size as u64) as usize;
+        let start = (head % self.size as u64) as usize;
+
+        let mut events = Events { read: 0, lost: 0 };
+        let mut offset = 0;
+        while offset + mem::size_of::<perf_event_header>() <= written && events.read < buffers.len()
+        {
+            let event_start = (start + offset) % self.size;
+            let event =
+                unsafe { ptr::read_unaligned((base + event_start) as *const perf_event_header) };
+            let event_size = event.size as usize;
+            // stop at the oldest event, which may have been partially
+            // overwritten by the newest one
+            if event_size == 0 || offset + event_size > written {
+                break;
+            }
+
+            match event.type_ {
+                x if x == PERF_RECORD_SAMPLE as u32 => {
+                    let mut size = [0u8; mem::size_of::<u32>()];
+                    fill_buf(
+                        event_start + mem::size_of::<perf_event_header>(),
+                        base,
+                        self.size,
+                        &mut size,
+                    );
+                    let sample_size = u32::from_ne_bytes(size) as usize;
+                    let buf = &mut buffers[events.read];
+                    buf.clear();
+                    if sample_size > buf.capacity() {
+                        if events.read > 0 {
+                            break;
+                        }
+                        return Err(PerfBufferError::MoreSpaceNeeded { size: sample_size });
+                    }
+                    unsafe { buf.set_len(sample_size) };
+                    fill_buf(
+                        event_start + mem::size_of::<perf_event_header>() + mem::size_of::<u32>(),
+                        base,
+                        self.size,
+                        buf,
+                    );
+                    events.read += 1;
+                }
+                x if x == PERF_RECORD_LOST as u32 => {
+                    let mut count = [0u8; mem::size_of::<u64>()];
+                    fill_buf(
+                        event_start + mem::size_of::<perf_event_header>() + mem::size_of::<u64>(),
+                        base,
+                        self.size,
+                        &mut count,
+                    );
+                    events.lost += u64::from_ne_bytes(count) as usize;
+                }
+                _ => { /* skip unknown event type */ }
+            }
+            offset += event_size;
+        }
 
         Ok(events)
     }
 }
 
+// Copies `out_buf.len()` bytes at `start_off` of the ring buffer mapped at
+// `base`, wrapping around the end of the buffer if needed.
+fn fill_buf(start_off: usize, base: usize, mmap_size: usize, out_buf: &mut [u8]) {
+    let len = out_buf.len();
+
+    let end = (start_off + len) % mmap_size;
+    let start = start_off % mmap_size;
+
+    if start < end {
+        out_buf.copy_from_slice(unsafe { slice::from_raw_parts((base + start) as *const u8, len) });
+    } else {
+        let size = mmap_size - start;
+        unsafe {
+            out_buf[..size]
+                .copy_from_slice(slice::from_raw_parts((base + start) as *const u8, size));
+            out_buf[size..].copy_from_slice(slice::from_raw_parts(base as *const u8, len - size));
+        }
+    }
+}
+
 impl AsRawFd for PerfBuffer {
     fn as_raw_fd(&self) -> RawFd {
         self.fd
@@ -330,18 +601,25 @@ mod tests {
         TEST_MMAP_RET.with(|ret| *ret.borrow_mut() = buf as *const _ as *mut _);
     }
 
+    #[test]
+    fn test_send() {
+        // buffers are moved to the threads reading them
+        fn assert_send<T: Send>() {}
+        assert_send::<PerfBuffer>();
+    }
+
     #[test]
     fn test_invalid_page_count() {
         assert!(matches!(
-            PerfBuffer::open(1, PAGE_SIZE, 0),
+            PerfBuffer::open(1, PAGE_SIZE, 0, PerfBufferWakeup::default(), false),
             Err(PerfBufferError::InvalidPageCount { .. })
         ));
         assert!(matches!(
-            PerfBuffer::open(1, PAGE_SIZE, 3),
+            PerfBuffer::open(1, PAGE_SIZE, 3, PerfBufferWakeup::default(), false),
             Err(PerfBufferError::InvalidPageCount { .. })
         ));
         assert!(matches!(
-            PerfBuffer::open(1, PAGE_SIZE, 5),
+            PerfBuffer::open(1, PAGE_SIZE, 5, PerfBufferWakeup::default(), false),
             Err(PerfBufferError::InvalidPageCount { .. })
         ));
     }
@@ -354,7 +632,8 @@ mod tests {
         };
         fake_mmap(&mut mmapped_buf);
 
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
         assert!(matches!(
             buf.read_events(&mut []),
             Err(PerfBufferError::NoBuffers)
@@ -369,7 +648,8 @@ mod tests {
         };
         fake_mmap(&mut mmapped_buf);
 
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
         let out_buf = BytesMut::with_capacity(4);
         assert_eq!(
             buf.read_events(&mut [out_buf]).unwrap(),
@@ -396,10 +676,12 @@ mod tests {
         };
         write(&mut mmapped_buf, 0, evt);
 
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
         let out_buf = BytesMut::with_capacity(0);
         let events = buf.read_events(&mut [out_buf]).unwrap();
         assert_eq!(events.lost, 0xCAFEBABE);
+        assert_eq!(buf.lost(), 0xCAFEBABE);
     }
 
     #[repr(C)]
@@ -449,7 +731,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         write_sample(&mut mmapped_buf, 0, 0xCAFEBABEu32);
 
@@ -467,7 +750,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         let next = write_sample(&mut mmapped_buf, 0, 0xCAFEBABEu32);
         write_sample(&mut mmapped_buf, next, 0xBADCAFEu32);
@@ -490,7 +774,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         let next = write_sample(&mut mmapped_buf, 0, 0xCAFEBABEu32);
         write_sample(&mut mmapped_buf, next, 0xBADCAFEu32);
@@ -512,7 +797,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         let offset = PAGE_SIZE - mem::size_of::<PerfSample<u32>>();
         mmapped_buf.mmap_page.data_tail = offset as u64;
@@ -532,7 +818,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         let header = perf_event_header {
             type_: PERF_RECORD_SAMPLE as u32,
@@ -561,7 +848,8 @@ mod tests {
             data: [0; PAGE_SIZE * 2],
         };
         fake_mmap(&mut mmapped_buf);
-        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1).unwrap();
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
 
         let sample = PerfSample {
             s_hdr: Sample {
@@ -586,4 +874,204 @@ mod tests {
         assert_eq!(events, Events { lost: 0, read: 1 });
         assert_eq!(u64_from_buf(&out_bufs[0]), 0xBAADCAFECAFEBABE);
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_read_snapshot() {
+        let mut mmapped_buf = MMappedBuf {
+            data: [0; PAGE_SIZE * 2],
+        };
+        fake_mmap(&mut mmapped_buf);
+        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), true).unwrap();
+        assert!(matches!(
+            buf.read_events(&mut [BytesMut::with_capacity(4)]),
+            Err(PerfBufferError::OverwriteMode)
+        ));
+
+        // the kernel writes backwards from the end of the buffer
+        let sample_size = mem::size_of::<PerfSample<u32>>();
+        write_sample(&mut mmapped_buf, PAGE_SIZE - sample_size, 0xCAFEBABEu32);
+        write_sample(&mut mmapped_buf, PAGE_SIZE - 2 * sample_size, 0xBADCAFEu32);
+        mmapped_buf.mmap_page.data_head = 0u64.wrapping_sub(2 * sample_size as u64);
+
+        let mut out_bufs = (0..3)
+            .map(|_| BytesMut::with_capacity(4))
+            .collect::<Vec<_>>();
+        let events = buf.read_snapshot(&mut out_bufs).unwrap();
+        assert_eq!(events, Events { lost: 0, read: 2 });
+        assert_eq!(u32_from_buf(&out_bufs[0]), 0xBADCAFE);
+        assert_eq!(u32_from_buf(&out_bufs[1]), 0xCAFEBABE);
+
+        // snapshots don't consume events
+        let events = buf.read_snapshot(&mut out_bufs[..1]).unwrap();
+        assert_eq!(events, Events { lost: 0, read: 1 });
+        assert_eq!(u32_from_buf(&out_bufs[0]), 0xBADCAFE);
+        assert_eq!(unsafe { mmapped_buf.mmap_page.data_tail }, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_read_snapshot_resume_error() {
+        let mut mmapped_buf = MMappedBuf {
+            data: [0; PAGE_SIZE * 2],
+        };
+        fake_mmap(&mut mmapped_buf);
+        let mut buf = PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), true).unwrap();
+
+        let lost_size = mem::size_of::<LostSamples>();
+        let sample_size = mem::size_of::<PerfSample<u64>>();
+        write_sample(&mut mmapped_buf, PAGE_SIZE - sample_size, 0xCAFEBABEu64);
+        write(
+            &mut mmapped_buf,
+            PAGE_SIZE - sample_size - lost_size,
+            LostSamples {
+                header: perf_event_header {
+                    type_: PERF_RECORD_LOST as u32,
+                    misc: 0,
+                    size: lost_size as u16,
+                },
+                id: 1,
+                count: 3,
+            },
+        );
+        mmapped_buf.mmap_page.data_head = 0u64.wrapping_sub((sample_size + lost_size) as u64);
+
+        // the lost events are reported, but not added to the total since the
+        // next snapshot returns them again
+        let mut out_bufs = [BytesMut::with_capacity(8)];
+        for _ in 0..2 {
+            let events = buf.read_snapshot(&mut out_bufs).unwrap();
+            assert_eq!(events, Events { lost: 3, read: 1 });
+        }
+        assert_eq!(buf.lost(), 0);
+        assert_eq!(unsafe { mmapped_buf.mmap_page.data_tail }, 0);
+
+        // resuming is attempted when reading fails, and both errors are
+        // reported
+        override_syscall(|call| match call {
+            Syscall::PerfEventIoctl {
+                request: PERF_EVENT_IOC_PAUSE_OUTPUT,
+                arg: 0,
+                ..
+            } => Err((-1, io::Error::from_raw_os_error(libc::EIO))),
+            Syscall::PerfEventIoctl { .. } => Ok(0),
+            _ => panic!(),
+        });
+        match buf.read_snapshot(&mut [BytesMut::with_capacity(4)]) {
+            Err(PerfBufferError::ResumeError {
+                io_error,
+                read_error: Some(read_error),
+            }) => {
+                assert_eq!(io_error.raw_os_error(), Some(libc::EIO));
+                assert!(matches!(
+                    *read_error,
+                    PerfBufferError::MoreSpaceNeeded { size: 8 }
+                ));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            buf.read_snapshot(&mut out_bufs),
+            Err(PerfBufferError::ResumeError {
+                read_error: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_read_snapshot_not_overwrite() {
+        let mut mmapped_buf = MMappedBuf {
+            data: [0; PAGE_SIZE * 2],
+        };
+        fake_mmap(&mut mmapped_buf);
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
+        assert!(matches!(
+            buf.read_snapshot(&mut [BytesMut::with_capacity(4)]),
+            Err(PerfBufferError::NotOverwriteMode)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_next_event() {
+        let mut mmapped_buf = MMappedBuf {
+            data: [0; PAGE_SIZE * 2],
+        };
+        fake_mmap(&mut mmapped_buf);
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
+
+        let next = write(
+            &mut mmapped_buf,
+            0,
+            LostSamples {
+                header: perf_event_header {
+                    type_: PERF_RECORD_LOST as u32,
+                    misc: 0,
+                    size: mem::size_of::<LostSamples>() as u16,
+                },
+                id: 1,
+                count: 3,
+            },
+        );
+        let next = write_sample(&mut mmapped_buf, next, 0xCAFEBABEu32);
+        write_sample(&mut mmapped_buf, next, 0xBADCAFEu32);
+
+        let mut lost = 0;
+        {
+            let event = buf.next_event(&mut lost).unwrap();
+            assert_eq!(u32_from_buf(&event), 0xCAFEBABE);
+            assert_eq!(lost, 3);
+        }
+        // not consumed, so the same event is returned again
+        let event = buf.next_event(&mut lost).unwrap();
+        assert_eq!(u32_from_buf(&event), 0xCAFEBABE);
+        event.consume();
+
+        let event = buf.next_event(&mut lost).unwrap();
+        assert_eq!(u32_from_buf(&event), 0xBADCAFE);
+        event.consume();
+        assert!(buf.next_event(&mut lost).is_none());
+        assert_eq!(lost, 3);
+        assert_eq!(buf.lost(), 3);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_next_event_wrapping_value() {
+        let mut mmapped_buf = MMappedBuf {
+            data: [0; PAGE_SIZE * 2],
+        };
+        fake_mmap(&mut mmapped_buf);
+        let mut buf =
+            PerfBuffer::open(1, PAGE_SIZE, 1, PerfBufferWakeup::default(), false).unwrap();
+
+        let sample = PerfSample {
+            s_hdr: Sample {
+                header: perf_event_header {
+                    type_: PERF_RECORD_SAMPLE as u32,
+                    misc: 0,
+                    size: mem::size_of::<PerfSample<u64>>() as u16,
+                },
+                size: mem::size_of::<u64>() as u32,
+            },
+            value: 0xCAFEBABEu32,
+        };
+
+        let offset = PAGE_SIZE - mem::size_of::<PerfSample<u32>>();
+        mmapped_buf.mmap_page.data_tail = offset as u64;
+        write(&mut mmapped_buf, offset, sample);
+        write(&mut mmapped_buf, 0, 0xBAADCAFEu32);
+
+        let event = buf.next_event(&mut 0).unwrap();
+        assert_eq!(u64_from_buf(&event), 0xBAADCAFECAFEBABE);
+        event.consume();
+        assert_eq!(
+            unsafe { mmapped_buf.mmap_page.data_tail },
+            (offset + mem::size_of::<PerfSample<u64>>()) as u64
+        );
+    }
 }
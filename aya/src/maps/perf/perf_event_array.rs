@@ -3,9 +3,14 @@
 //! [`perf`]: https://perf.wiki.kernel.org/index.php/Main_Page.
 use std::{
     convert::TryFrom,
+    io, mem,
     ops::DerefMut,
-    os::unix::io::{AsRawFd, RawFd},
-    sync::Arc,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use bytes::BytesMut;
@@ -14,10 +19,12 @@ use libc::{sysconf, _SC_PAGESIZE};
 use crate::{
     generated::bpf_map_type::BPF_MAP_TYPE_PERF_EVENT_ARRAY,
     maps::{
-        perf::{Events, PerfBuffer, PerfBufferError},
+        perf::{Events, PerfBuffer, PerfBufferError, PerfBufferItem, PerfBufferWakeup},
         Map, MapError, MapRefMut,
     },
-    sys::bpf_map_update_elem,
+    programs::perf_event::PerfTypeId,
+    sys::{bpf_map_update_elem, perf_event_open_counter},
+    util::{online_cpus, poll_readable},
 };
 
 /// A ring buffer that can receive events from eBPF programs.
@@ -30,6 +37,7 @@ use crate::{
 pub struct PerfEventArrayBuffer<T: DerefMut<Target = Map>> {
     _map: Arc<T>,
     buf: PerfBuffer,
+    lost: Arc<AtomicU64>,
 }
 
 impl<T: DerefMut<Target = Map>> PerfEventArrayBuffer<T> {
@@ -38,6 +46,19 @@ impl<T: DerefMut<Target = Map>> PerfEventArrayBuffer<T> {
         self.buf.readable()
     }
 
+    /// Blocks until the buffer contains events or `timeout` expires. A
+    /// `timeout` of `None` waits forever.
+    ///
+    /// Returns true if the buffer is readable and false if the timeout expired.
+    /// The reader is woken up according to the [`PerfBufferWakeup`] policy
+    /// the buffer was opened with.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<bool, PerfBufferError> {
+        if self.readable() {
+            return Ok(true);
+        }
+        Ok(poll_readable(self.as_raw_fd(), timeout)? || self.readable())
+    }
+
     /// Reads events from the buffer.
     ///
     /// This method reads events into the provided slice of buffers, filling
@@ -54,8 +75,74 @@ impl<T: DerefMut<Target = Map>> PerfEventArrayBuffer<T> {
     ///
     /// [`PerfBufferError::MoreSpaceNeeded { size }`](PerfBufferError) is returned when the size of the events is
     /// bigger than the size of the out_bufs provided.
+    ///
+    /// [`PerfBufferError::OverwriteMode`] is returned when the buffer was opened in overwrite
+    /// mode, use [`PerfEventArrayBuffer::read_snapshot`] instead.
     pub fn read_events(&mut self, out_bufs: &mut [BytesMut]) -> Result<Events, PerfBufferError> {
-        self.buf.read_events(out_bufs)
+        // the lost events found before an error are consumed too, so they're
+        // counted from the total of the buffer rather than from the result
+        let lost = self.buf.lost();
+        let result = self.buf.read_events(out_bufs);
+        let lost = self.buf.lost() - lost;
+        if lost > 0 {
+            self.lost.fetch_add(lost, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Reads the events currently in an overwrite mode buffer, newest first.
+    ///
+    /// The output of the buffer is paused while the events are copied into
+    /// `out_bufs`, then resumed. The events are not removed from the buffer,
+    /// so the next snapshot returns them again until they're overwritten by
+    /// newer ones. Reading stops when there are no more events or all the
+    /// buffers have been filled.
+    ///
+    /// The lost events returned are the ones recorded in the snapshot, and
+    /// aren't added to [`PerfEventArrayBuffer::lost_events`].
+    ///
+    /// # Errors
+    ///
+    /// [`PerfBufferError::NotOverwriteMode`] is returned when the buffer wasn't opened in
+    /// overwrite mode, see [`PerfEventArray::set_overwrite`].
+    ///
+    /// [`PerfBufferError::NoBuffers`] and [`PerfBufferError::MoreSpaceNeeded { size }`](PerfBufferError)
+    /// are returned like for [`PerfEventArrayBuffer::read_events`].
+    ///
+    /// [`PerfBufferError::ResumeError`] is returned when the output of the
+    /// buffer can't be resumed, along with the error reading the snapshot if
+    /// any.
+    pub fn read_snapshot(&mut self, out_bufs: &mut [BytesMut]) -> Result<Events, PerfBufferError> {
+        self.buf.read_snapshot(out_bufs)
+    }
+
+    /// Returns the next event without copying it, or `None` if the buffer is
+    /// empty.
+    ///
+    /// The returned [`PerfBufferItem`] derefs to the event data, which is
+    /// borrowed from the buffer. Call [`PerfBufferItem::consume`] once done
+    /// with the event to remove it from the buffer: until then the kernel
+    /// can't reuse the space, and the next call returns the same event again.
+    /// Events that wrap around the end of the buffer are copied into an
+    /// internal buffer which is reused across calls.
+    ///
+    /// Lost events are counted in [`PerfEventArrayBuffer::lost_events`].
+    ///
+    /// Always returns `None` for buffers opened in overwrite mode, see
+    /// [`PerfEventArray::set_overwrite`].
+    pub fn next_event(&mut self) -> Option<PerfBufferItem<'_>> {
+        let mut lost = 0;
+        let event = self.buf.next_event(&mut lost);
+        if lost > 0 {
+            self.lost.fetch_add(lost, Ordering::Relaxed);
+        }
+        event
+    }
+
+    /// Returns the total number of events lost by this buffer since it was
+    /// opened.
+    pub fn lost_events(&self) -> u64 {
+        self.buf.lost()
     }
 }
 
@@ -65,6 +152,79 @@ impl<T: DerefMut<Target = Map>> AsRawFd for PerfEventArrayBuffer<T> {
     }
 }
 
+impl<T: DerefMut<Target = Map>> AsFd for PerfEventArrayBuffer<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the descriptor is closed when the buffer is dropped
+        unsafe { BorrowedFd::borrow_raw(self.as_raw_fd()) }
+    }
+}
+
+/// A perf counter inserted in a [`PerfEventArray`].
+///
+/// eBPF programs read the counter with `bpf_perf_event_read_value()`. It's
+/// returned by [`PerfEventArray::open_counter`].
+pub struct PerfCounter<T: DerefMut<Target = Map>> {
+    _map: Arc<T>,
+    fd: OwnedFd,
+    cpu: u32,
+}
+
+/// The value of a [`PerfCounter`], as returned by `bpf_perf_event_read_value()`
+/// in `struct bpf_perf_event_value`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PerfCounterValue {
+    /// The value of the counter.
+    pub counter: u64,
+    /// The time in nanoseconds the counter was enabled.
+    pub enabled: u64,
+    /// The time in nanoseconds the counter was actually counting. When more
+    /// counters are opened than the hardware supports, they're multiplexed and
+    /// `counter * enabled / running` estimates the full count.
+    pub running: u64,
+}
+
+impl<T: DerefMut<Target = Map>> PerfCounter<T> {
+    /// Returns the CPU the counter counts on, which is also its index in the
+    /// array.
+    pub fn cpu(&self) -> u32 {
+        self.cpu
+    }
+
+    /// Reads the counter from user space.
+    pub fn read(&self) -> Result<PerfCounterValue, io::Error> {
+        let mut value = [0u64; 3];
+        let size = mem::size_of_val(&value);
+        let ret = unsafe { libc::read(self.fd.as_raw_fd(), value.as_mut_ptr() as *mut _, size) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if ret as usize != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "short read of perf counter",
+            ));
+        }
+        let [counter, enabled, running] = value;
+        Ok(PerfCounterValue {
+            counter,
+            enabled,
+            running,
+        })
+    }
+}
+
+impl<T: DerefMut<Target = Map>> AsRawFd for PerfCounter<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl<T: DerefMut<Target = Map>> AsFd for PerfCounter<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
 /// A map that can be used to receive events from eBPF programs using the linux [`perf`] API.
 ///
 /// Each element of a [`PerfEventArray`] is a separate [`PerfEventArrayBuffer`] which can be used
@@ -142,12 +302,126 @@ impl<T: DerefMut<Target = Map>> AsRawFd for PerfEventArrayBuffer<T> {
 /// # Polling and avoiding lost events
 ///
 /// In the example above the implementation of `poll_buffers()` and `poll.poll_readable()` is not
-/// given. [`PerfEventArrayBuffer`] implements the [`AsRawFd`] trait, so you can implement polling
-/// using any crate that can poll file descriptors, like [epoll], [mio] etc.  
+/// given. [`PerfEventArrayBuffer`] implements the [`AsFd`] and [`AsRawFd`] traits, so you can
+/// implement polling using any crate that can poll file descriptors, like [epoll], [mio] etc.
+/// To wait on a single buffer, use [`PerfEventArrayBuffer::poll`].
 ///
 /// Perf buffers are internally implemented as ring buffers. If your eBPF programs produce large
 /// amounts of data, in order not to lose events you might want to process each
-/// [`PerfEventArrayBuffer`] on a different thread.
+/// [`PerfEventArrayBuffer`] on a different thread, or open larger buffers. The number of lost
+/// events is reported by [`PerfEventArrayBuffer::read_events`], and the totals are available with
+/// [`PerfEventArrayBuffer::lost_events`] and [`PerfEventArray::lost_events`].
+///
+/// # Reading from multiple threads
+///
+/// [`PerfEventArrayBuffer`] is [`Send`], so each buffer can be moved to its own
+/// thread:
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #    #[error(transparent)]
+/// #    IO(#[from] std::io::Error),
+/// #    #[error(transparent)]
+/// #    Map(#[from] aya::maps::MapError),
+/// #    #[error(transparent)]
+/// #    Bpf(#[from] aya::BpfError),
+/// #    #[error(transparent)]
+/// #    PerfBuf(#[from] aya::maps::perf::PerfBufferError),
+/// # }
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::PerfEventArray;
+/// use aya::util::online_cpus;
+/// use std::{convert::TryFrom, thread};
+/// use bytes::BytesMut;
+///
+/// let mut perf_array = PerfEventArray::try_from(bpf.map_mut("EVENTS")?)?;
+///
+/// let mut workers = Vec::new();
+/// for cpu_id in online_cpus()? {
+///     let mut buf = perf_array.open(cpu_id, None)?;
+///     workers.push(thread::spawn(move || -> Result<(), Error> {
+///         let mut out_bufs = [BytesMut::with_capacity(1024)];
+///         loop {
+///             // block until the buffer is readable
+///             buf.poll(None)?;
+///             let events = buf.read_events(&mut out_bufs)?;
+///             // process out_bufs[..events.read]
+///         }
+///     }));
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+///
+/// # Reading without copying
+///
+/// [`PerfEventArrayBuffer::read_events`] copies every event into the buffers it's given. For
+/// high rate event streams, [`PerfEventArrayBuffer::next_event`] gives access to the events in
+/// place instead:
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::PerfEventArray;
+/// use std::convert::TryFrom;
+///
+/// let mut perf_array = PerfEventArray::try_from(bpf.map_mut("EVENTS")?)?;
+/// let mut buf = perf_array.open(0, None)?;
+/// loop {
+///     buf.poll(None)?;
+///     while let Some(event) = buf.next_event() {
+///         println!("received {} bytes", event.len());
+///         event.consume();
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Keeping the last events
+///
+/// Flight recorder style tools that only need the events that led to an incident can open the
+/// buffers in overwrite mode with [`PerfEventArray::set_overwrite`]. The buffers then always
+/// contain the most recent events, which are read with [`PerfEventArrayBuffer::read_snapshot`]:
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::PerfEventArray;
+/// use std::convert::TryFrom;
+/// use bytes::BytesMut;
+///
+/// let mut perf_array = PerfEventArray::try_from(bpf.map_mut("EVENTS")?)?;
+/// perf_array.set_overwrite(true);
+/// let mut buf = perf_array.open(0, None)?;
+///
+/// // ... once something interesting happens, grab the last 16 events
+/// let mut out_bufs = (0..16).map(|_| BytesMut::with_capacity(1024)).collect::<Vec<_>>();
+/// let events = buf.read_snapshot(&mut out_bufs)?;
+/// for event in out_bufs[..events.read].iter().rev() {
+///     // process the events, oldest first
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Hardware counters
+///
+/// A perf array can also hold counters instead of buffers, which eBPF programs read with
+/// `bpf_perf_event_read_value(&COUNTERS, BPF_F_CURRENT_CPU, ...)`, for example to measure the
+/// cycles or the cache misses of a function between its entry and its exit.
+/// [`PerfEventArray::open_counters`] opens a counter on each CPU:
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::PerfEventArray;
+/// use aya::programs::perf_event::{perf_hw_id::PERF_COUNT_HW_CPU_CYCLES, PerfTypeId};
+/// use std::convert::TryFrom;
+///
+/// let mut counters = PerfEventArray::try_from(bpf.map_mut("CYCLES")?)?;
+/// let cycles = counters.open_counters(PerfTypeId::Hardware, PERF_COUNT_HW_CPU_CYCLES as u64)?;
+/// // the counters can be read from user space too
+/// for counter in &cycles {
+///     println!("cpu {}: {} cycles", counter.cpu(), counter.read()?.counter);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
 ///
 /// # Async
 ///
@@ -163,6 +437,10 @@ impl<T: DerefMut<Target = Map>> AsRawFd for PerfEventArrayBuffer<T> {
 pub struct PerfEventArray<T: DerefMut<Target = Map>> {
     map: Arc<T>,
     page_size: usize,
+    page_count: usize,
+    wakeup: PerfBufferWakeup,
+    overwrite: bool,
+    lost: Arc<AtomicU64>,
 }
 
 impl<T: DerefMut<Target = Map>> PerfEventArray<T> {
@@ -179,12 +457,56 @@ impl<T: DerefMut<Target = Map>> PerfEventArray<T> {
             map: Arc::new(map),
             // Safety: libc
             page_size: unsafe { sysconf(_SC_PAGESIZE) } as usize,
+            page_count: 2,
+            wakeup: PerfBufferWakeup::default(),
+            overwrite: false,
+            lost: Arc::new(AtomicU64::new(0)),
         })
     }
 
+    /// Sets the number of pages of the buffers opened with a `page_count` of
+    /// `None`.
+    ///
+    /// The page count must be a power of two. The default is 2.
+    pub fn set_page_count(&mut self, page_count: usize) -> Result<(), PerfBufferError> {
+        if !page_count.is_power_of_two() {
+            return Err(PerfBufferError::InvalidPageCount { page_count });
+        }
+        self.page_count = page_count;
+        Ok(())
+    }
+
+    /// Sets when the readers of the buffers opened from now on are woken up.
+    ///
+    /// By default readers are woken up for every event.
+    pub fn set_wakeup(&mut self, wakeup: PerfBufferWakeup) {
+        self.wakeup = wakeup;
+    }
+
+    /// Sets whether the buffers opened from now on are in overwrite mode.
+    ///
+    /// When an overwrite mode buffer is full, the kernel overwrites the
+    /// oldest events instead of dropping the new ones, so the buffer always
+    /// holds the most recent events. Overwrite mode buffers can only be read
+    /// with [`PerfEventArrayBuffer::read_snapshot`].
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 4.7.
+    pub fn set_overwrite(&mut self, overwrite: bool) {
+        self.overwrite = overwrite;
+    }
+
+    /// Returns the total number of events lost by all the buffers opened
+    /// from this array, as reported by their `read_events()` calls.
+    pub fn lost_events(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+
     /// Opens the perf buffer at the given index.
     ///
     /// The returned buffer will receive all the events eBPF programs send at the given index.
+    /// If `page_count` is `None`, the value set with [`PerfEventArray::set_page_count`] is used.
     pub fn open(
         &mut self,
         index: u32,
@@ -194,15 +516,62 @@ impl<T: DerefMut<Target = Map>> PerfEventArray<T> {
 
         // this cannot fail as new() checks that the fd is open
         let map_fd = self.map.fd_or_err().unwrap();
-        let buf = PerfBuffer::open(index, self.page_size, page_count.unwrap_or(2))?;
+        let buf = PerfBuffer::open(
+            index,
+            self.page_size,
+            page_count.unwrap_or(self.page_count),
+            self.wakeup,
+            self.overwrite,
+        )?;
         bpf_map_update_elem(map_fd, &index, &buf.as_raw_fd(), 0)
             .map_err(|(_, io_error)| io_error)?;
 
         Ok(PerfEventArrayBuffer {
             buf,
             _map: self.map.clone(),
+            lost: self.lost.clone(),
+        })
+    }
+
+    /// Opens a perf counter on `cpu` and inserts it at index `cpu`.
+    ///
+    /// The counter counts the events of all the processes running on `cpu`.
+    /// The possible values and encoding of the `config` argument depends on
+    /// the `perf_type`, see [`PerfEvent::attach`](crate::programs::PerfEvent::attach).
+    /// Hardware counters might not be available, for example in virtual
+    /// machines, in which case [`PerfBufferError::OpenError`] is returned.
+    pub fn open_counter(
+        &mut self,
+        cpu: u32,
+        perf_type: PerfTypeId,
+        config: u64,
+    ) -> Result<PerfCounter<T>, PerfBufferError> {
+        // this cannot fail as new() checks that the fd is open
+        let map_fd = self.map.fd_or_err().unwrap();
+        let fd = perf_event_open_counter(perf_type as u32, config, cpu as i32)
+            .map_err(|(_, io_error)| PerfBufferError::OpenError { io_error })?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        bpf_map_update_elem(map_fd, &cpu, &fd.as_raw_fd(), 0).map_err(|(_, io_error)| io_error)?;
+
+        Ok(PerfCounter {
+            _map: self.map.clone(),
+            fd,
+            cpu,
         })
     }
+
+    /// Opens a perf counter on each online CPU, see
+    /// [`PerfEventArray::open_counter`].
+    pub fn open_counters(
+        &mut self,
+        perf_type: PerfTypeId,
+        config: u64,
+    ) -> Result<Vec<PerfCounter<T>>, PerfBufferError> {
+        online_cpus()?
+            .into_iter()
+            .map(|cpu| self.open_counter(cpu, perf_type.clone(), config))
+            .collect()
+    }
 }
 
 impl TryFrom<MapRefMut> for PerfEventArray<MapRefMut> {
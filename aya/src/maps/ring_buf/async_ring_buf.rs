@@ -0,0 +1,247 @@
+use std::{convert::TryFrom, ops::DerefMut};
+
+#[cfg(any(feature = "async_tokio", feature = "async_std", doc))]
+use bytes::BytesMut;
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+use std::{
+    io,
+    os::unix::prelude::{AsRawFd, RawFd},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+use futures::{ready, Stream};
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+use async_io::Async;
+
+#[cfg(feature = "async_tokio")]
+use tokio::io::unix::AsyncFd;
+
+#[cfg(any(feature = "async_tokio", feature = "async_std", doc))]
+use crate::maps::ring_buf::RingBufError;
+use crate::maps::{ring_buf::RingBuf, Map, MapError, MapRefMut};
+
+/// A `Future` based ring buffer that can receive records from eBPF programs.
+///
+/// This is the async version of [`RingBuf`], which provides integration
+/// with [tokio](https://docs.rs/tokio) and [async-std](https:/docs.rs/async-std) and a nice
+/// `Future` based API, so that records can be awaited without a dedicated polling thread.
+///
+/// [`AsyncRingBuf`] also implements `futures::Stream`, yielding one
+/// record at a time, so that it can be used with the `StreamExt` combinators.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #    #[error(transparent)]
+/// #    Map(#[from] aya::maps::MapError),
+/// #    #[error(transparent)]
+/// #    Bpf(#[from] aya::BpfError),
+/// #    #[error(transparent)]
+/// #    RingBuf(#[from] aya::maps::ring_buf::RingBufError),
+/// # }
+/// # #[cfg(feature = "async_tokio")]
+/// # async fn try_main() -> Result<(), Error> {
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::ring_buf::AsyncRingBuf;
+/// use std::convert::TryFrom;
+/// use bytes::BytesMut;
+///
+/// let mut ring_buf = AsyncRingBuf::try_from(bpf.map_mut("EVENTS")?)?;
+/// let mut buffers = (0..10)
+///     .map(|_| BytesMut::with_capacity(1024))
+///     .collect::<Vec<_>>();
+///
+/// loop {
+///     // wait for records
+///     let read = ring_buf.read_events(&mut buffers).await?;
+///     for buf in buffers.iter_mut().take(read) {
+///         // process buf
+///     }
+/// }
+/// # }
+/// ```
+///
+/// Using the ring buffer as a stream:
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #    #[error(transparent)]
+/// #    Map(#[from] aya::maps::MapError),
+/// #    #[error(transparent)]
+/// #    Bpf(#[from] aya::BpfError),
+/// #    #[error(transparent)]
+/// #    RingBuf(#[from] aya::maps::ring_buf::RingBufError),
+/// # }
+/// # #[cfg(feature = "async_tokio")]
+/// # async fn try_main() -> Result<(), Error> {
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::ring_buf::AsyncRingBuf;
+/// use std::convert::TryFrom;
+/// use futures::StreamExt;
+///
+/// let ring_buf = AsyncRingBuf::try_from(bpf.map_mut("EVENTS")?)?;
+/// // process the records in batches of up to 64
+/// let mut batches = ring_buf.ready_chunks(64);
+/// while let Some(records) = batches.next().await {
+///     for record in records {
+///         let buf = record?;
+///         // process buf
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[doc(alias = "BPF_MAP_TYPE_RINGBUF")]
+pub struct AsyncRingBuf<T: DerefMut<Target = Map>> {
+    // only read by the runtime specific methods
+    #[cfg_attr(
+        not(any(feature = "async_tokio", feature = "async_std")),
+        allow(dead_code)
+    )]
+    ring_buf: RingBuf<T>,
+
+    #[cfg(feature = "async_tokio")]
+    async_fd: AsyncFd<RawFd>,
+
+    #[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+    async_fd: Async<RawFd>,
+}
+
+impl<T: DerefMut<Target = Map>> AsyncRingBuf<T> {
+    fn new(map: T) -> Result<AsyncRingBuf<T>, MapError> {
+        let ring_buf = RingBuf::new(map)?;
+        #[cfg(any(feature = "async_tokio", feature = "async_std"))]
+        let fd = ring_buf.as_raw_fd();
+        #[cfg(any(feature = "async_tokio", feature = "async_std"))]
+        let map_error = |io_error| MapError::SyscallError {
+            call: "epoll_ctl".to_owned(),
+            code: -1,
+            io_error,
+        };
+        Ok(AsyncRingBuf {
+            ring_buf,
+
+            #[cfg(feature = "async_tokio")]
+            async_fd: AsyncFd::new(fd).map_err(map_error)?,
+
+            #[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+            async_fd: Async::new(fd).map_err(map_error)?,
+        })
+    }
+}
+
+#[cfg(any(feature = "async_tokio", doc))]
+impl<T: DerefMut<Target = Map>> AsyncRingBuf<T> {
+    /// Reads records from the ring buffer.
+    ///
+    /// This method waits until records are available, then reads them into
+    /// the provided slice of buffers, filling each buffer in order stopping
+    /// when there are no more records to read or all the buffers have been
+    /// filled.
+    ///
+    /// Returns the number of records read.
+    pub async fn read_events(&mut self, buffers: &mut [BytesMut]) -> Result<usize, RingBufError> {
+        loop {
+            let mut guard = self.async_fd.readable_mut().await?;
+
+            match self.ring_buf.read_events(buffers) {
+                Ok(0) => {
+                    guard.clear_ready();
+                    continue;
+                }
+                ret => return ret,
+            }
+        }
+    }
+}
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+impl<T: DerefMut<Target = Map>> AsyncRingBuf<T> {
+    /// Reads records from the ring buffer.
+    ///
+    /// This method waits until records are available, then reads them into
+    /// the provided slice of buffers, filling each buffer in order stopping
+    /// when there are no more records to read or all the buffers have been
+    /// filled.
+    ///
+    /// Returns the number of records read.
+    pub async fn read_events(&mut self, buffers: &mut [BytesMut]) -> Result<usize, RingBufError> {
+        loop {
+            if !self.ring_buf.readable() {
+                self.async_fd.readable().await?;
+            }
+
+            match self.ring_buf.read_events(buffers) {
+                Ok(0) => continue,
+                ret => return ret,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async_tokio")]
+impl<T: DerefMut<Target = Map>> AsyncRingBuf<T> {
+    fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.ring_buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            let mut guard = ready!(self.async_fd.poll_read_ready(cx))?;
+            if self.ring_buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            guard.clear_ready();
+        }
+    }
+}
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+impl<T: DerefMut<Target = Map>> AsyncRingBuf<T> {
+    fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.ring_buf.readable() {
+                return Poll::Ready(Ok(()));
+            }
+            ready!(self.async_fd.poll_readable(cx))?;
+        }
+    }
+}
+
+/// Yields the records of the ring buffer one at a time, as they become
+/// available.
+///
+/// Each record is copied into its own [`BytesMut`].
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+impl<T: DerefMut<Target = Map> + Unpin> Stream for AsyncRingBuf<T> {
+    type Item = Result<BytesMut, RingBufError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(record) = this.ring_buf.next() {
+                return Poll::Ready(Some(Ok(BytesMut::from(&record[..]))));
+            }
+            if let Err(e) = ready!(this.poll_readable(cx)) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+        }
+    }
+}
+
+impl TryFrom<MapRefMut> for AsyncRingBuf<MapRefMut> {
+    type Error = MapError;
+
+    fn try_from(a: MapRefMut) -> Result<AsyncRingBuf<MapRefMut>, MapError> {
+        AsyncRingBuf::new(a)
+    }
+}
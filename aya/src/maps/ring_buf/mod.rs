@@ -0,0 +1,454 @@
+//! A ring buffer that can be used to receive events from eBPF programs.
+//!
+//! See [`RingBuf`] and [`AsyncRingBuf`].
+use std::{
+    convert::TryFrom,
+    ffi::c_void,
+    io,
+    ops::{Deref, DerefMut},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    ptr, slice,
+    sync::atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use libc::{c_int, sysconf, _SC_PAGESIZE, MAP_FAILED, MAP_SHARED, PROT_READ, PROT_WRITE};
+use thiserror::Error;
+
+use crate::{
+    generated::bpf_map_type::BPF_MAP_TYPE_RINGBUF,
+    maps::{Map, MapError, MapRefMut},
+    util::poll_readable,
+};
+
+#[cfg(any(feature = "async", doc))]
+mod async_ring_buf;
+#[cfg(any(feature = "async", doc))]
+pub use async_ring_buf::*;
+
+// see enum { BPF_RINGBUF_BUSY_BIT, ... } in include/uapi/linux/bpf.h
+const BPF_RINGBUF_BUSY_BIT: u32 = 1 << 31;
+const BPF_RINGBUF_DISCARD_BIT: u32 = 1 << 30;
+const BPF_RINGBUF_HDR_SZ: usize = 8;
+
+/// Ring buffer error.
+#[derive(Error, Debug)]
+pub enum RingBufError {
+    /// `read_events()` was called with no output buffers.
+    #[error("read_events() was called with no output buffers")]
+    NoBuffers,
+
+    /// `read_events()` was called with a buffer that is not large enough to
+    /// contain the next record in the ring buffer.
+    #[error("the buffer needs to be of at least {size} bytes")]
+    MoreSpaceNeeded { size: usize },
+
+    /// An IO error occurred.
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+/// A ring buffer that can receive records from eBPF programs.
+///
+/// Unlike [`PerfEventArray`](crate::maps::PerfEventArray), a ring buffer is
+/// shared by all the CPUs, and records are read in the order in which they
+/// were reserved. eBPF programs write to it with `bpf_ringbuf_output()` or
+/// `bpf_ringbuf_reserve()` and `bpf_ringbuf_submit()`.
+///
+/// [`RingBuf`] implements [`AsFd`] and [`AsRawFd`], so it can be polled with
+/// any crate that can poll file descriptors to be notified when new records
+/// are available, or waited on directly with [`RingBuf::poll`]. With [tokio]
+/// or [async-std], use [`AsyncRingBuf`] instead.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use aya::maps::ring_buf::RingBuf;
+/// use std::convert::TryFrom;
+///
+/// let mut ring_buf = RingBuf::try_from(bpf.map_mut("EVENTS")?)?;
+/// loop {
+///     // block until records are available
+///     ring_buf.poll(None)?;
+///     while let Some(record) = ring_buf.next() {
+///         // record derefs to &[u8] and is consumed when dropped
+///         println!("received {} bytes", record.len());
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [tokio]: https://docs.rs/tokio
+/// [async-std]: https://docs.rs/async-std
+#[doc(alias = "BPF_MAP_TYPE_RINGBUF")]
+pub struct RingBuf<T: DerefMut<Target = Map>> {
+    _map: T,
+    fd: RawFd,
+    page_size: usize,
+    data_size: usize,
+    // the consumer page, mapped read-write
+    consumer: AtomicPtr<c_void>,
+    // the producer page followed by the data pages, mapped read-only
+    producer: AtomicPtr<c_void>,
+}
+
+impl<T: DerefMut<Target = Map>> RingBuf<T> {
+    pub(crate) fn new(map: T) -> Result<RingBuf<T>, MapError> {
+        let map_type = map.obj.def.map_type;
+        if map_type != BPF_MAP_TYPE_RINGBUF as u32 {
+            return Err(MapError::InvalidMapType { map_type });
+        }
+        let fd = map.fd_or_err()?;
+        // Safety: libc
+        let page_size = unsafe { sysconf(_SC_PAGESIZE) } as usize;
+        let data_size = map.obj.def.max_entries as usize;
+
+        let consumer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                page_size,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if consumer == MAP_FAILED {
+            return Err(mmap_error());
+        }
+
+        // the data pages are mapped twice in a row, so that records that wrap
+        // around the end of the buffer can be read as a contiguous slice
+        let producer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                page_size + 2 * data_size,
+                PROT_READ,
+                MAP_SHARED,
+                fd,
+                page_size as libc::off_t,
+            )
+        };
+        if producer == MAP_FAILED {
+            let error = mmap_error();
+            unsafe { munmap(consumer, page_size) };
+            return Err(error);
+        }
+
+        Ok(RingBuf {
+            _map: map,
+            fd,
+            page_size,
+            data_size,
+            consumer: AtomicPtr::new(consumer),
+            producer: AtomicPtr::new(producer),
+        })
+    }
+
+    /// Returns true if the ring buffer contains records that haven't been
+    /// read.
+    pub fn readable(&self) -> bool {
+        self.consumer_pos().load(Ordering::Acquire) != self.producer_pos().load(Ordering::Acquire)
+    }
+
+    /// Blocks until the ring buffer contains records or `timeout` expires. A
+    /// `timeout` of `None` waits forever.
+    ///
+    /// Returns true if the ring buffer is readable and false if the timeout
+    /// expired.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<bool, RingBufError> {
+        if self.readable() {
+            return Ok(true);
+        }
+        Ok(poll_readable(self.fd, timeout)? || self.readable())
+    }
+
+    /// Returns the next record, or `None` if there are no records to read.
+    ///
+    /// The record is removed from the ring buffer when the returned
+    /// [`RingBufItem`] is dropped.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<RingBufItem<'_>> {
+        let (offset, len, next_pos) = self.peek()?;
+        let data = unsafe { slice::from_raw_parts((self.data() + offset) as *const u8, len) };
+        Some(RingBufItem {
+            data,
+            consumer_pos: self.consumer_pos(),
+            next_pos,
+        })
+    }
+
+    /// Reads records from the ring buffer.
+    ///
+    /// This method reads records into the provided slice of buffers, filling
+    /// each buffer in order stopping when there are no more records to read
+    /// or all the buffers have been filled.
+    ///
+    /// Returns the number of records read.
+    ///
+    /// # Errors
+    ///
+    /// [`RingBufError::NoBuffers`] is returned when `out_bufs` is empty.
+    ///
+    /// [`RingBufError::MoreSpaceNeeded { size }`](RingBufError) is returned when the next record
+    /// is bigger than the buffer it should be read into and no records have been read.
+    pub fn read_events(&mut self, out_bufs: &mut [BytesMut]) -> Result<usize, RingBufError> {
+        if out_bufs.is_empty() {
+            return Err(RingBufError::NoBuffers);
+        }
+
+        let mut read = 0;
+        for buf in out_bufs.iter_mut() {
+            let (offset, len, next_pos) = match self.peek() {
+                Some(record) => record,
+                None => break,
+            };
+            buf.clear();
+            if len > buf.capacity() {
+                if read > 0 {
+                    // return what we have, the next call will return the error
+                    // unless the caller increases the buffer size
+                    break;
+                }
+                return Err(RingBufError::MoreSpaceNeeded { size: len });
+            }
+            buf.extend_from_slice(unsafe {
+                slice::from_raw_parts((self.data() + offset) as *const u8, len)
+            });
+            self.consumer_pos().store(next_pos, Ordering::Release);
+            read += 1;
+        }
+
+        Ok(read)
+    }
+
+    // Returns the offset and length of the next record and the consumer
+    // position after it, skipping discarded records.
+    fn peek(&mut self) -> Option<(usize, usize, usize)> {
+        let mask = self.data_size - 1;
+        loop {
+            let consumer_pos = self.consumer_pos().load(Ordering::Acquire);
+            let producer_pos = self.producer_pos().load(Ordering::Acquire);
+            if consumer_pos == producer_pos {
+                return None;
+            }
+
+            let offset = consumer_pos & mask;
+            let header = unsafe { &*((self.data() + offset) as *const AtomicU32) };
+            let len = header.load(Ordering::Acquire);
+            if len & BPF_RINGBUF_BUSY_BIT != 0 {
+                // the record has been reserved but not committed yet
+                return None;
+            }
+
+            let size = (len & !(BPF_RINGBUF_BUSY_BIT | BPF_RINGBUF_DISCARD_BIT)) as usize;
+            let next_pos = consumer_pos + ((size + BPF_RINGBUF_HDR_SZ + 7) & !7);
+            if len & BPF_RINGBUF_DISCARD_BIT != 0 {
+                self.consumer_pos().store(next_pos, Ordering::Release);
+                continue;
+            }
+
+            return Some((offset + BPF_RINGBUF_HDR_SZ, size, next_pos));
+        }
+    }
+
+    fn consumer_pos(&self) -> &AtomicUsize {
+        unsafe { &*(self.consumer.load(Ordering::SeqCst) as *const AtomicUsize) }
+    }
+
+    fn producer_pos(&self) -> &AtomicUsize {
+        unsafe { &*(self.producer.load(Ordering::SeqCst) as *const AtomicUsize) }
+    }
+
+    fn data(&self) -> usize {
+        self.producer.load(Ordering::SeqCst) as usize + self.page_size
+    }
+}
+
+impl<T: DerefMut<Target = Map>> AsRawFd for RingBuf<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl<T: DerefMut<Target = Map>> AsFd for RingBuf<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the descriptor is owned by the map, which outlives the ring
+        // buffer
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl<T: DerefMut<Target = Map>> Drop for RingBuf<T> {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.consumer.load(Ordering::SeqCst), self.page_size);
+            munmap(
+                self.producer.load(Ordering::SeqCst),
+                self.page_size + 2 * self.data_size,
+            );
+        }
+    }
+}
+
+impl TryFrom<MapRefMut> for RingBuf<MapRefMut> {
+    type Error = MapError;
+
+    fn try_from(a: MapRefMut) -> Result<RingBuf<MapRefMut>, MapError> {
+        RingBuf::new(a)
+    }
+}
+
+/// A record read from a [`RingBuf`].
+///
+/// The record is removed from the ring buffer when the item is dropped.
+pub struct RingBufItem<'a> {
+    data: &'a [u8],
+    consumer_pos: &'a AtomicUsize,
+    next_pos: usize,
+}
+
+impl Deref for RingBufItem<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl Drop for RingBufItem<'_> {
+    fn drop(&mut self) {
+        self.consumer_pos.store(self.next_pos, Ordering::Release);
+    }
+}
+
+fn mmap_error() -> MapError {
+    MapError::SyscallError {
+        call: "mmap".to_owned(),
+        code: -1,
+        io_error: io::Error::last_os_error(),
+    }
+}
+
+#[cfg_attr(test, allow(unused_variables))]
+unsafe fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: i32,
+    offset: libc::off_t,
+) -> *mut c_void {
+    #[cfg(not(test))]
+    return libc::mmap(addr, len, prot, flags, fd, offset);
+
+    #[cfg(test)]
+    use crate::sys::TEST_MMAP_RET;
+
+    #[cfg(test)]
+    TEST_MMAP_RET.with(|ret| (*ret.borrow() as usize + offset as usize) as *mut c_void)
+}
+
+#[cfg_attr(test, allow(unused_variables))]
+unsafe fn munmap(addr: *mut c_void, len: usize) {
+    #[cfg(not(test))]
+    libc::munmap(addr, len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bpf_map_def, obj, sys::TEST_MMAP_RET};
+
+    const DATA_SIZE: usize = 4096;
+
+    fn new_map() -> Map {
+        Map {
+            obj: obj::Map {
+                name: "TEST".to_string(),
+                def: bpf_map_def {
+                    map_type: BPF_MAP_TYPE_RINGBUF as u32,
+                    max_entries: DATA_SIZE as u32,
+                    ..Default::default()
+                },
+                section_index: 0,
+                section_offset: None,
+                data: Vec::new(),
+                inner: None,
+                numa_node: None,
+                btf_key_type_id: None,
+                btf_value_type_id: None,
+                btf: None,
+            },
+            fd: Some(42),
+            pinned: false,
+        }
+    }
+
+    fn write_record(data: &mut [u8], offset: usize, header: u32, sample: &[u8]) -> usize {
+        data[offset..offset + 4].copy_from_slice(&header.to_ne_bytes());
+        let start = offset + BPF_RINGBUF_HDR_SZ;
+        data[start..start + sample.len()].copy_from_slice(sample);
+        offset + ((sample.len() + BPF_RINGBUF_HDR_SZ + 7) & !7)
+    }
+
+    #[test]
+    fn test_read_records() {
+        let page_size = unsafe { sysconf(_SC_PAGESIZE) } as usize;
+        let mut mem = vec![0u64; (2 * page_size + 2 * DATA_SIZE) / 8];
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(mem.as_mut_ptr() as *mut u8, mem.len() * 8) };
+        let data = &mut bytes[2 * page_size..];
+        let pos = write_record(data, 0, 4, &[1, 2, 3, 4]);
+        let pos = write_record(data, pos, 4 | BPF_RINGBUF_DISCARD_BIT, &[0xff; 4]);
+        let pos = write_record(data, pos, 8, &[5; 8]);
+        let end = write_record(data, pos, 4 | BPF_RINGBUF_BUSY_BIT, &[0xff; 4]);
+        bytes[page_size..page_size + 8].copy_from_slice(&(end as u64).to_ne_bytes());
+        TEST_MMAP_RET.with(|ret| *ret.borrow_mut() = mem.as_mut_ptr() as *mut c_void);
+
+        let mut map = new_map();
+        let mut ring_buf = RingBuf::new(&mut map).unwrap();
+        assert!(ring_buf.readable());
+
+        let record = ring_buf.next().unwrap();
+        assert_eq!(&*record, &[1, 2, 3, 4]);
+        drop(record);
+
+        let mut out_bufs = [BytesMut::with_capacity(8), BytesMut::with_capacity(8)];
+        assert_eq!(ring_buf.read_events(&mut out_bufs).unwrap(), 1);
+        assert_eq!(&out_bufs[0][..], &[5; 8]);
+
+        // the last record is still busy
+        assert!(ring_buf.next().is_none());
+        assert_eq!(ring_buf.consumer_pos().load(Ordering::SeqCst), pos);
+    }
+
+    #[test]
+    fn test_read_more_space_needed() {
+        let page_size = unsafe { sysconf(_SC_PAGESIZE) } as usize;
+        let mut mem = vec![0u64; (2 * page_size + 2 * DATA_SIZE) / 8];
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(mem.as_mut_ptr() as *mut u8, mem.len() * 8) };
+        let end = write_record(&mut bytes[2 * page_size..], 0, 8, &[5; 8]);
+        bytes[page_size..page_size + 8].copy_from_slice(&(end as u64).to_ne_bytes());
+        TEST_MMAP_RET.with(|ret| *ret.borrow_mut() = mem.as_mut_ptr() as *mut c_void);
+
+        let mut map = new_map();
+        let mut ring_buf = RingBuf::new(&mut map).unwrap();
+        assert!(matches!(
+            ring_buf.read_events(&mut []),
+            Err(RingBufError::NoBuffers)
+        ));
+        assert!(matches!(
+            ring_buf.read_events(&mut [BytesMut::with_capacity(4)]),
+            Err(RingBufError::MoreSpaceNeeded { size: 8 })
+        ));
+        assert!(ring_buf.readable());
+    }
+}
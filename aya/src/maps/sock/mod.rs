@@ -1,4 +1,5 @@
 //! Socket maps.
+mod reuseport_sock_array;
 mod sock_hash;
 mod sock_map;
 
@@ -6,6 +7,7 @@ use std::os::unix::io::RawFd;
 
 use crate::maps::MapError;
 
+pub use reuseport_sock_array::ReuseportSockArray;
 pub use sock_hash::SockHash;
 pub use sock_map::SockMap;
 pub trait SocketMap {
@@ -0,0 +1,144 @@
+//! An array of listening sockets used by [`SkReuseport`](crate::programs::SkReuseport) programs.
+
+use std::{
+    convert::TryFrom,
+    mem,
+    ops::{Deref, DerefMut},
+    os::unix::io::AsRawFd,
+};
+
+use crate::{
+    generated::bpf_map_type::BPF_MAP_TYPE_REUSEPORT_SOCKARRAY,
+    maps::{Map, MapError, MapKeys, MapRef, MapRefMut},
+    sys::{bpf_map_delete_elem, bpf_map_update_elem},
+};
+
+/// An array of sockets belonging to the same `SO_REUSEPORT` group.
+///
+/// [`SkReuseport`](crate::programs::SkReuseport) programs use a
+/// `ReuseportSockArray` to select the socket that receives an incoming packet
+/// or connection, with `bpf_sk_select_reuseport()`.
+///
+/// The value size of the map can be either 4 or 8 bytes. When it's 8 bytes,
+/// looking up an element from user space returns the socket cookie.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.19.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(Debug, thiserror::Error)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Map(#[from] aya::maps::MapError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use std::convert::TryFrom;
+/// use std::net::TcpListener;
+/// use aya::maps::ReuseportSockArray;
+///
+/// let mut sockets = ReuseportSockArray::try_from(bpf.map_mut("LISTENERS")?)?;
+/// let listener = TcpListener::bind("127.0.0.1:8080")?;
+/// sockets.set(0, &listener, 0)?;
+/// # Ok::<(), Error>(())
+/// ```
+#[doc(alias = "BPF_MAP_TYPE_REUSEPORT_SOCKARRAY")]
+pub struct ReuseportSockArray<T: Deref<Target = Map>> {
+    pub(crate) inner: T,
+}
+
+impl<T: Deref<Target = Map>> ReuseportSockArray<T> {
+    fn new(map: T) -> Result<ReuseportSockArray<T>, MapError> {
+        let map_type = map.obj.def.map_type;
+        if map_type != BPF_MAP_TYPE_REUSEPORT_SOCKARRAY as u32 {
+            return Err(MapError::InvalidMapType { map_type });
+        }
+        let expected = mem::size_of::<u32>();
+        let size = map.obj.def.key_size as usize;
+        if size != expected {
+            return Err(MapError::InvalidKeySize { size, expected });
+        }
+
+        let size = map.obj.def.value_size as usize;
+        if size != mem::size_of::<u32>() && size != mem::size_of::<u64>() {
+            return Err(MapError::InvalidValueSize {
+                size,
+                expected: mem::size_of::<u64>(),
+            });
+        }
+        let _fd = map.fd_or_err()?;
+
+        Ok(ReuseportSockArray { inner: map })
+    }
+
+    /// An iterator over the indices of the array that point to a socket. The
+    /// iterator item type is `Result<u32, MapError>`.
+    pub unsafe fn indices(&self) -> MapKeys<'_, u32> {
+        MapKeys::new(&self.inner)
+    }
+
+    fn check_bounds(&self, index: u32) -> Result<(), MapError> {
+        let max_entries = self.inner.obj.def.max_entries;
+        if index >= self.inner.obj.def.max_entries {
+            Err(MapError::OutOfBounds { index, max_entries })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Deref<Target = Map> + DerefMut<Target = Map>> ReuseportSockArray<T> {
+    /// Stores a socket into the map.
+    ///
+    /// The socket must be bound with `SO_REUSEPORT` set.
+    pub fn set<I: AsRawFd>(&mut self, index: u32, socket: &I, flags: u64) -> Result<(), MapError> {
+        let fd = self.inner.fd_or_err()?;
+        self.check_bounds(index)?;
+        let ret = if self.inner.obj.def.value_size as usize == mem::size_of::<u64>() {
+            bpf_map_update_elem(fd, &index, &(socket.as_raw_fd() as u64), flags)
+        } else {
+            bpf_map_update_elem(fd, &index, &(socket.as_raw_fd() as u32), flags)
+        };
+        ret.map_err(|(code, io_error)| MapError::SyscallError {
+            call: "bpf_map_update_elem".to_owned(),
+            code,
+            io_error,
+        })?;
+        Ok(())
+    }
+
+    /// Removes the socket stored at `index` from the map.
+    pub fn clear_index(&mut self, index: &u32) -> Result<(), MapError> {
+        let fd = self.inner.fd_or_err()?;
+        self.check_bounds(*index)?;
+        bpf_map_delete_elem(fd, index)
+            .map(|_| ())
+            .map_err(|(code, io_error)| MapError::SyscallError {
+                call: "bpf_map_delete_elem".to_owned(),
+                code,
+                io_error,
+            })
+    }
+}
+
+impl TryFrom<MapRef> for ReuseportSockArray<MapRef> {
+    type Error = MapError;
+
+    fn try_from(a: MapRef) -> Result<ReuseportSockArray<MapRef>, MapError> {
+        ReuseportSockArray::new(a)
+    }
+}
+
+impl TryFrom<MapRefMut> for ReuseportSockArray<MapRefMut> {
+    type Error = MapError;
+
+    fn try_from(a: MapRefMut) -> Result<ReuseportSockArray<MapRefMut>, MapError> {
+        ReuseportSockArray::new(a)
+    }
+}
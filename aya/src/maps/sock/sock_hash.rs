@@ -42,20 +42,19 @@ use crate::{
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::{TryFrom, TryInto};
+/// use std::convert::TryFrom;
 /// use std::io::Write;
 /// use std::net::TcpStream;
-/// use std::os::unix::io::AsRawFd;
 /// use aya::maps::SockHash;
 /// use aya::programs::SkMsg;
 ///
 /// let mut intercept_egress = SockHash::try_from(bpf.map_mut("INTERCEPT_EGRESS")?)?;
-/// let prog: &mut SkMsg = bpf.program_mut("intercept_egress_packet")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SkMsg>("intercept_egress_packet")?;
 /// prog.load()?;
 /// prog.attach(&intercept_egress)?;
 ///
 /// let mut client = TcpStream::connect("127.0.0.1:1234")?;
-/// intercept_egress.insert(1234, client.as_raw_fd(), 0)?;
+/// intercept_egress.insert(1234, &client, 0)?;
 ///
 /// // the write will be intercepted
 /// client.write_all(b"foo")?;
@@ -114,7 +113,11 @@ impl<T: Deref<Target = Map>, K: Pod> SockHash<T, K> {
 
 impl<T: DerefMut<Target = Map>, K: Pod> SockHash<T, K> {
     /// Inserts a socket under the given key.
-    pub fn insert<I: AsRawFd>(&mut self, key: K, value: I, flags: u64) -> Result<(), MapError> {
+    ///
+    /// `value` can be any socket, like a [`TcpStream`](std::net::TcpStream) or a
+    /// [`UnixStream`](std::os::unix::net::UnixStream), or a raw socket file
+    /// descriptor. The socket is removed from the map when it's closed.
+    pub fn insert<I: AsRawFd>(&mut self, key: K, value: &I, flags: u64) -> Result<(), MapError> {
         hash_map::insert(&mut self.inner, key, value.as_raw_fd(), flags)
     }
 
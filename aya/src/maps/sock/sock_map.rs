@@ -29,16 +29,31 @@ use crate::{
 /// # Examples
 ///
 /// ```no_run
+/// # #[derive(Debug, thiserror::Error)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Map(#[from] aya::maps::MapError),
+/// #     #[error(transparent)]
+/// #     Program(#[from] aya::programs::ProgramError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::{TryFrom, TryInto};
+/// use std::convert::TryFrom;
+/// use std::net::TcpStream;
 /// use aya::maps::SockMap;
 /// use aya::programs::SkSkb;
 ///
-/// let intercept_ingress = SockMap::try_from(bpf.map_mut("INTERCEPT_INGRESS")?)?;
-/// let prog: &mut SkSkb = bpf.program_mut("intercept_ingress_packet")?.try_into()?;
+/// let mut intercept_ingress = SockMap::try_from(bpf.map_mut("INTERCEPT_INGRESS")?)?;
+/// let prog = bpf.program_mut_typed::<SkSkb>("intercept_ingress_packet")?;
 /// prog.load()?;
 /// prog.attach(&intercept_ingress)?;
-/// # Ok::<(), aya::BpfError>(())
+///
+/// let client = TcpStream::connect("127.0.0.1:1234")?;
+/// intercept_ingress.insert(0, &client, 0)?;
+/// # Ok::<(), Error>(())
 /// ```
 #[doc(alias = "BPF_MAP_TYPE_SOCKMAP")]
 pub struct SockMap<T: Deref<Target = Map>> {
@@ -100,6 +115,29 @@ impl<T: Deref<Target = Map> + DerefMut<Target = Map>> SockMap<T> {
         Ok(())
     }
 
+    /// Stores a socket into the map at `index`.
+    ///
+    /// `socket` can be any socket, like a [`TcpStream`](std::net::TcpStream) or
+    /// a [`UnixStream`](std::os::unix::net::UnixStream), or a raw socket file
+    /// descriptor. The socket is removed from the map when it's closed.
+    ///
+    /// Same as [`SockMap::set`].
+    pub fn insert<I: AsRawFd>(
+        &mut self,
+        index: u32,
+        socket: &I,
+        flags: u64,
+    ) -> Result<(), MapError> {
+        self.set(index, socket, flags)
+    }
+
+    /// Removes the socket stored at `index` from the map.
+    ///
+    /// Same as [`SockMap::clear_index`].
+    pub fn remove(&mut self, index: &u32) -> Result<(), MapError> {
+        self.clear_index(index)
+    }
+
     /// Removes the socket stored at `index` from the map.
     pub fn clear_index(&mut self, index: &u32) -> Result<(), MapError> {
         let fd = self.inner.fd_or_err()?;
@@ -2,13 +2,20 @@
 //!
 //! See [`StackTraceMap`] for documentation and examples.
 use std::{
-    collections::BTreeMap, convert::TryFrom, fs, io, mem, ops::Deref, path::Path, str::FromStr,
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+    fs, io, mem,
+    ops::Deref,
+    os::unix::io::RawFd,
+    path::Path,
+    str::FromStr,
 };
 
 use crate::{
-    generated::bpf_map_type::BPF_MAP_TYPE_STACK_TRACE,
+    generated::{bpf_map_type::BPF_MAP_TYPE_STACK_TRACE, BPF_F_STACK_BUILD_ID},
     maps::{IterableMap, Map, MapError, MapIter, MapKeys, MapRef, MapRefMut},
     sys::bpf_map_lookup_elem_ptr,
+    util::KernelSymbols,
 };
 
 /// A hash map of kernel or user space stack traces.
@@ -64,11 +71,21 @@ use crate::{
 /// # Ok::<(), Error>(())
 /// ```
 ///
+/// # Build IDs
+///
+/// User space addresses are only meaningful while the process that captured
+/// them is alive. Maps created with [`MapFlags::STACK_BUILD_ID`] instead
+/// store the build ID of the object file of each frame and the offset of the
+/// frame within it, which can be symbolized at any later time, even on
+/// another machine. See [`StackFrame::build_id`] and [`Symbolizer`].
+///
+/// [`MapFlags::STACK_BUILD_ID`]: crate::maps::MapFlags::STACK_BUILD_ID
 #[derive(Debug)]
 #[doc(alias = "BPF_MAP_TYPE_STACK_TRACE")]
 pub struct StackTraceMap<T> {
     inner: T,
     max_stack_depth: usize,
+    build_id: bool,
 }
 
 impl<T: Deref<Target = Map>> StackTraceMap<T> {
@@ -93,8 +110,14 @@ impl<T: Deref<Target = Map>> StackTraceMap<T> {
                     io_error,
                 }
             })?;
+        let build_id = map.obj.def.map_flags & BPF_F_STACK_BUILD_ID != 0;
+        let frame_size = if build_id {
+            BUILD_ID_FRAME_SIZE
+        } else {
+            mem::size_of::<u64>()
+        };
         let size = map.obj.def.value_size as usize;
-        if size > max_stack_depth * mem::size_of::<u64>() {
+        if size > max_stack_depth * frame_size {
             return Err(MapError::InvalidValueSize { size, expected });
         }
         let _fd = map.fd_or_err()?;
@@ -102,6 +125,7 @@ impl<T: Deref<Target = Map>> StackTraceMap<T> {
         Ok(StackTraceMap {
             inner: map,
             max_stack_depth,
+            build_id,
         })
     }
 
@@ -114,23 +138,19 @@ impl<T: Deref<Target = Map>> StackTraceMap<T> {
     pub fn get(&self, stack_id: &u32, flags: u64) -> Result<StackTrace, MapError> {
         let fd = self.inner.fd_or_err()?;
 
-        let mut frames = vec![0; self.max_stack_depth];
-        bpf_map_lookup_elem_ptr(fd, stack_id, frames.as_mut_ptr(), flags)
-            .map_err(|(code, io_error)| MapError::SyscallError {
-                call: "bpf_map_lookup_elem".to_owned(),
-                code,
-                io_error,
-            })?
-            .ok_or(MapError::KeyNotFound)?;
-
-        let frames = frames
-            .drain(..)
-            .take_while(|ip| *ip != 0)
-            .map(|ip| StackFrame {
-                ip,
-                symbol_name: None,
-            })
-            .collect::<Vec<_>>();
+        let frames = if self.build_id {
+            let mut data = vec![0u8; self.inner.obj.def.value_size as usize];
+            lookup(fd, stack_id, data.as_mut_ptr(), flags)?;
+            parse_build_id_frames(&data)
+        } else {
+            let mut frames = vec![0; self.max_stack_depth];
+            lookup(fd, stack_id, frames.as_mut_ptr(), flags)?;
+            frames
+                .drain(..)
+                .take_while(|ip| *ip != 0)
+                .map(|ip| StackFrame::new(ip, None))
+                .collect::<Vec<_>>()
+        };
 
         Ok(StackTrace {
             id: *stack_id,
@@ -202,11 +222,31 @@ impl StackTrace {
     /// user-space traces you need to provide the symbols, for example loading
     /// them from debug info.
     pub fn resolve(&mut self, symbols: &BTreeMap<u64, String>) -> &StackTrace {
+        self.symbolize(symbols)
+    }
+
+    /// Resolves symbol names using the given kernel symbols.
+    ///
+    /// Unlike [`StackTrace::resolve`], symbols defined in modules are
+    /// resolved as `symbol [module]`.
+    pub fn resolve_kernel(&mut self, symbols: &KernelSymbols) -> &StackTrace {
+        self.symbolize(symbols)
+    }
+
+    /// Resolves the frames using `symbolizer`.
+    ///
+    /// Sets the symbol name, file and line of each frame to what the
+    /// symbolizer returns for it.
+    pub fn symbolize<S: Symbolizer + ?Sized>(&mut self, symbolizer: &S) -> &StackTrace {
         for frame in self.frames.iter_mut() {
-            frame.symbol_name = symbols
-                .range(..=frame.ip)
-                .next_back()
-                .map(|(_, s)| s.clone())
+            let info = symbolizer.symbolize(frame);
+            let (symbol_name, file, line) = match info {
+                Some(info) => (Some(info.name), info.file, info.line),
+                None => (None, None, None),
+            };
+            frame.symbol_name = symbol_name;
+            frame.file = file;
+            frame.line = line;
         }
 
         self
@@ -221,12 +261,157 @@ impl StackTrace {
 /// A stack frame.
 pub struct StackFrame {
     /// The instruction pointer of this frame.
+    ///
+    /// If [`StackFrame::build_id`] is set, this is the offset of the
+    /// instruction within the object file instead.
     pub ip: u64,
+    /// The build ID of the object file containing the frame.
+    ///
+    /// Only set for the maps created with
+    /// [`MapFlags::STACK_BUILD_ID`](crate::maps::MapFlags::STACK_BUILD_ID),
+    /// when the kernel could read the build ID. Shorter build IDs are padded
+    /// with zeros.
+    pub build_id: Option<[u8; BUILD_ID_SIZE]>,
     /// The symbol name corresponding to the start of this frame.
     ///
     /// Set to `Some()` if the frame address can be found in the symbols passed
-    /// to [`StackTrace::resolve`].
+    /// to [`StackTrace::resolve`], or by the [`Symbolizer`] passed to
+    /// [`StackTrace::symbolize`].
     pub symbol_name: Option<String>,
+    /// The source file of this frame, if known by the symbolizer.
+    pub file: Option<String>,
+    /// The source line of this frame, if known by the symbolizer.
+    pub line: Option<u32>,
+}
+
+impl StackFrame {
+    fn new(ip: u64, build_id: Option<[u8; BUILD_ID_SIZE]>) -> StackFrame {
+        StackFrame {
+            ip,
+            build_id,
+            symbol_name: None,
+            file: None,
+            line: None,
+        }
+    }
+}
+
+/// The symbol a stack frame resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolInfo {
+    /// The name of the function.
+    pub name: String,
+    /// The source file, if known.
+    pub file: Option<String>,
+    /// The source line, if known.
+    pub line: Option<u32>,
+}
+
+impl SymbolInfo {
+    /// Creates a symbol with only a name.
+    pub fn new(name: String) -> SymbolInfo {
+        SymbolInfo {
+            name,
+            file: None,
+            line: None,
+        }
+    }
+}
+
+/// Resolves stack frames to symbols.
+///
+/// Implement this trait to symbolize [`StackTrace`]s with
+/// [`StackTrace::symbolize`], for example on top of a library reading DWARF
+/// debug info. Frames captured by maps created with
+/// [`MapFlags::STACK_BUILD_ID`](crate::maps::MapFlags::STACK_BUILD_ID) carry a
+/// build ID and a file offset, which can be used to find the object file and
+/// its debug info long after the process exited.
+///
+/// It's implemented for symbol maps, as taken by [`StackTrace::resolve`], and
+/// for [`KernelSymbols`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{collections::HashMap, path::PathBuf};
+/// use aya::maps::stack_trace::{StackFrame, SymbolInfo, Symbolizer};
+///
+/// // resolves frames to the object files with the given build IDs
+/// struct Objects(HashMap<[u8; 20], PathBuf>);
+///
+/// impl Symbolizer for Objects {
+///     fn symbolize(&self, frame: &StackFrame) -> Option<SymbolInfo> {
+///         let path = self.0.get(frame.build_id.as_ref()?)?;
+///         // look up frame.ip in the symbols of the file
+///         Some(SymbolInfo::new(format!("{}+{:#x}", path.display(), frame.ip)))
+///     }
+/// }
+/// ```
+pub trait Symbolizer {
+    /// Returns the symbol `frame` resolves to, or `None` if it's unknown.
+    fn symbolize(&self, frame: &StackFrame) -> Option<SymbolInfo>;
+}
+
+impl Symbolizer for BTreeMap<u64, String> {
+    fn symbolize(&self, frame: &StackFrame) -> Option<SymbolInfo> {
+        self.range(..=frame.ip)
+            .next_back()
+            .map(|(_, name)| SymbolInfo::new(name.clone()))
+    }
+}
+
+impl Symbolizer for KernelSymbols {
+    fn symbolize(&self, frame: &StackFrame) -> Option<SymbolInfo> {
+        self.resolve(frame.ip).map(|(sym, _)| {
+            SymbolInfo::new(match &sym.module {
+                Some(module) => format!("{} [{}]", sym.name, module),
+                None => sym.name.clone(),
+            })
+        })
+    }
+}
+
+/// The size of the build IDs stored in stack traces.
+pub const BUILD_ID_SIZE: usize = 20;
+
+// struct bpf_stack_build_id: an i32 status, the build ID, then the offset or
+// the ip depending on the status
+const BUILD_ID_FRAME_SIZE: usize = 32;
+const BUILD_ID_OFFSET: usize = 4;
+const BUILD_ID_IP_OFFSET: usize = 24;
+// enum bpf_stack_build_id_status
+const BPF_STACK_BUILD_ID_VALID: i32 = 1;
+const BPF_STACK_BUILD_ID_IP: i32 = 2;
+
+fn parse_build_id_frames(data: &[u8]) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+    for frame in data.chunks_exact(BUILD_ID_FRAME_SIZE) {
+        let status = i32::from_ne_bytes(frame[..4].try_into().unwrap());
+        let ip = u64::from_ne_bytes(frame[BUILD_ID_IP_OFFSET..].try_into().unwrap());
+        let build_id = match status {
+            BPF_STACK_BUILD_ID_VALID => Some(
+                frame[BUILD_ID_OFFSET..BUILD_ID_OFFSET + BUILD_ID_SIZE]
+                    .try_into()
+                    .unwrap(),
+            ),
+            // the kernel couldn't read the build ID and stored the address
+            BPF_STACK_BUILD_ID_IP => None,
+            // BPF_STACK_BUILD_ID_EMPTY, the end of the trace
+            _ => break,
+        };
+        frames.push(StackFrame::new(ip, build_id));
+    }
+    frames
+}
+
+fn lookup<V>(fd: RawFd, stack_id: &u32, value: *mut V, flags: u64) -> Result<(), MapError> {
+    bpf_map_lookup_elem_ptr(fd, stack_id, value, flags)
+        .map_err(|(code, io_error)| MapError::SyscallError {
+            call: "bpf_map_lookup_elem".to_owned(),
+            code,
+            io_error,
+        })?
+        .ok_or(MapError::KeyNotFound)
 }
 
 fn sysctl<T: FromStr>(key: &str) -> Result<T, io::Error> {
@@ -235,3 +420,53 @@ fn sysctl<T: FromStr>(key: &str) -> Result<T, io::Error> {
         .parse::<T>()
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, val))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_id_frame(status: i32, build_id: u8, ip: u64) -> Vec<u8> {
+        let mut data = status.to_ne_bytes().to_vec();
+        data.extend_from_slice(&[build_id; BUILD_ID_SIZE]);
+        data.extend_from_slice(&ip.to_ne_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_build_id_frames() {
+        let mut data = build_id_frame(BPF_STACK_BUILD_ID_VALID, 0xab, 0x1234);
+        data.extend(build_id_frame(BPF_STACK_BUILD_ID_IP, 0, 0x7fff_0000_1000));
+        data.extend(build_id_frame(0, 0, 0));
+        data.extend(build_id_frame(BPF_STACK_BUILD_ID_VALID, 0xcd, 0x5678));
+
+        let frames = parse_build_id_frames(&data);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].ip, 0x1234);
+        assert_eq!(frames[0].build_id, Some([0xab; BUILD_ID_SIZE]));
+        assert_eq!(frames[1].ip, 0x7fff_0000_1000);
+        assert_eq!(frames[1].build_id, None);
+    }
+
+    #[test]
+    fn test_symbolize() {
+        let mut symbols = BTreeMap::new();
+        symbols.insert(0x1000, "foo".to_owned());
+        symbols.insert(0x2000, "bar".to_owned());
+
+        let mut trace = StackTrace {
+            id: 1,
+            frames: vec![
+                StackFrame::new(0x1010, None),
+                StackFrame::new(0x2000, None),
+                StackFrame::new(0x10, None),
+            ],
+        };
+        let names = trace
+            .resolve(&symbols)
+            .frames()
+            .iter()
+            .map(|frame| frame.symbol_name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("foo"), Some("bar"), None]);
+    }
+}
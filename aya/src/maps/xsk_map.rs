@@ -0,0 +1,136 @@
+//! An array of AF_XDP sockets.
+
+use std::{
+    convert::TryFrom,
+    mem,
+    ops::{Deref, DerefMut},
+    os::unix::io::{AsRawFd, RawFd},
+};
+
+use crate::{
+    generated::bpf_map_type::BPF_MAP_TYPE_XSKMAP,
+    maps::{Map, MapError, MapKeys, MapRef, MapRefMut},
+    sys::{bpf_map_delete_elem, bpf_map_update_elem},
+};
+
+/// An array of AF_XDP sockets.
+///
+/// XDP programs can use `bpf_redirect_map()` to redirect packets to the
+/// [`XskSocket`](crate::xdp::XskSocket)s stored in the map, usually indexed
+/// by the receive queue the packet arrived on (`ctx->rx_queue_index`).
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.18.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use std::convert::TryFrom;
+/// use aya::{
+///     maps::XskMap,
+///     xdp::{Umem, UmemConfig, XskConfig, XskSocket},
+/// };
+///
+/// let mut sockets = XskMap::try_from(bpf.map_mut("XSKS")?)?;
+/// let umem = Umem::new(4096, UmemConfig::default())?;
+/// let socket = XskSocket::new(&umem, "eth0", 0, XskConfig::default())?;
+/// sockets.set(0, &socket, 0)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[doc(alias = "BPF_MAP_TYPE_XSKMAP")]
+pub struct XskMap<T: Deref<Target = Map>> {
+    pub(crate) inner: T,
+}
+
+impl<T: Deref<Target = Map>> XskMap<T> {
+    fn new(map: T) -> Result<XskMap<T>, MapError> {
+        let map_type = map.obj.def.map_type;
+        if map_type != BPF_MAP_TYPE_XSKMAP as u32 {
+            return Err(MapError::InvalidMapType { map_type });
+        }
+        let expected = mem::size_of::<u32>();
+        let size = map.obj.def.key_size as usize;
+        if size != expected {
+            return Err(MapError::InvalidKeySize { size, expected });
+        }
+
+        let expected = mem::size_of::<RawFd>();
+        let size = map.obj.def.value_size as usize;
+        if size != expected {
+            return Err(MapError::InvalidValueSize { size, expected });
+        }
+        let _fd = map.fd_or_err()?;
+
+        Ok(XskMap { inner: map })
+    }
+
+    /// Returns the number of elements in the array.
+    pub fn len(&self) -> u32 {
+        self.inner.obj.def.max_entries
+    }
+
+    /// An iterator over the indices of the array that point to a socket. The
+    /// iterator item type is `Result<u32, MapError>`.
+    pub unsafe fn indices(&self) -> MapKeys<'_, u32> {
+        MapKeys::new(&self.inner)
+    }
+
+    fn check_bounds(&self, index: u32) -> Result<(), MapError> {
+        let max_entries = self.inner.obj.def.max_entries;
+        if index >= max_entries {
+            Err(MapError::OutOfBounds { index, max_entries })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Deref<Target = Map> + DerefMut<Target = Map>> XskMap<T> {
+    /// Stores an AF_XDP socket into the map at `index`.
+    ///
+    /// The socket must be bound, see [`XskSocket::new`](crate::xdp::XskSocket::new).
+    /// It is removed from the map when it's closed.
+    pub fn set<I: AsRawFd>(&mut self, index: u32, socket: &I, flags: u64) -> Result<(), MapError> {
+        let fd = self.inner.fd_or_err()?;
+        self.check_bounds(index)?;
+        bpf_map_update_elem(fd, &index, &socket.as_raw_fd(), flags).map_err(
+            |(code, io_error)| MapError::SyscallError {
+                call: "bpf_map_update_elem".to_owned(),
+                code,
+                io_error,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Removes the socket stored at `index` from the map.
+    pub fn clear_index(&mut self, index: &u32) -> Result<(), MapError> {
+        let fd = self.inner.fd_or_err()?;
+        self.check_bounds(*index)?;
+        bpf_map_delete_elem(fd, index)
+            .map(|_| ())
+            .map_err(|(code, io_error)| MapError::SyscallError {
+                call: "bpf_map_delete_elem".to_owned(),
+                code,
+                io_error,
+            })
+    }
+}
+
+impl TryFrom<MapRef> for XskMap<MapRef> {
+    type Error = MapError;
+
+    fn try_from(a: MapRef) -> Result<XskMap<MapRef>, MapError> {
+        XskMap::new(a)
+    }
+}
+
+impl TryFrom<MapRefMut> for XskMap<MapRefMut> {
+    type Error = MapError;
+
+    fn try_from(a: MapRefMut) -> Result<XskMap<MapRefMut>, MapError> {
+        XskMap::new(a)
+    }
+}
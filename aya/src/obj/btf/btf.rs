@@ -11,8 +11,15 @@ use object::Endianness;
 use thiserror::Error;
 
 use crate::{
-    generated::{btf_ext_header, btf_header},
-    obj::btf::{relocation::Relocation, BtfKind, BtfType},
+    generated::{
+        bpf_func_info, bpf_line_info, btf_enum, btf_ext_header, btf_header, btf_member, btf_type,
+    },
+    obj::btf::{
+        relocation::Relocation,
+        types::{type_info, type_vlen, BTF_FUNC_GLOBAL, BTF_FUNC_STATIC},
+        BtfKind, BtfType,
+    },
+    sys::{bpf_btf_get_data_by_fd, bpf_btf_get_fd_by_id, bpf_btf_get_info_by_fd},
 };
 
 pub(crate) const MAX_RESOLVE_DEPTH: u8 = 32;
@@ -71,6 +78,42 @@ pub enum BtfError {
 
     #[error("maximum depth reached resolving BTF type")]
     MaximumTypeDepthReached { type_id: u32 },
+
+    #[error("invalid size {size} for a value of BTF type `{type_id}`, expected {expected}")]
+    InvalidValueSize {
+        type_id: u32,
+        size: usize,
+        expected: usize,
+    },
+
+    #[error("the BPF_BTF_LOAD syscall failed. Verifier output: {verifier_log}")]
+    LoadError {
+        #[source]
+        io_error: io::Error,
+        verifier_log: String,
+    },
+
+    #[error("`{call}` failed")]
+    SyscallError {
+        call: String,
+        #[source]
+        io_error: io::Error,
+    },
+}
+
+/// The newer BTF kinds supported by the running kernel.
+///
+/// Kinds that are not supported are replaced by [`Btf::sanitize`] before the
+/// BTF is loaded in the kernel.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct BtfFeatures {
+    pub(crate) btf_func: bool,
+    pub(crate) btf_func_global: bool,
+    pub(crate) btf_datasec: bool,
+    pub(crate) btf_float: bool,
+    pub(crate) btf_decl_tag: bool,
+    pub(crate) btf_type_tag: bool,
+    pub(crate) btf_enum64: bool,
 }
 
 /// Bpf Type Format metadata.
@@ -86,15 +129,100 @@ pub struct Btf {
     header: btf_header,
     strings: Vec<u8>,
     types: Vec<BtfType>,
+    module: Option<String>,
     _endianness: Endianness,
 }
 
 impl Btf {
+    pub(crate) fn new() -> Btf {
+        Btf {
+            header: btf_header {
+                magic: 0xeb9f,
+                version: 0x01,
+                flags: 0x00,
+                hdr_len: mem::size_of::<btf_header>() as u32,
+                type_off: 0,
+                type_len: 0,
+                str_off: 0,
+                str_len: 1,
+            },
+            strings: vec![0],
+            types: vec![BtfType::Unknown],
+            module: None,
+            _endianness: Endianness::default(),
+        }
+    }
+
+    /// Adds `name` to the string section and returns its offset.
+    pub(crate) fn add_string(&mut self, name: &str) -> u32 {
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.header.str_len = self.strings.len() as u32;
+        offset
+    }
+
+    /// Adds `ty` and returns its type id.
+    pub(crate) fn add_type(&mut self, ty: BtfType) -> u32 {
+        self.types.push(ty);
+        (self.types.len() - 1) as u32
+    }
+
     /// Loads BTF metadata from `/sys/kernel/btf/vmlinux`.
     pub fn from_sys_fs() -> Result<Btf, BtfError> {
         Btf::parse_file("/sys/kernel/btf/vmlinux", Endianness::default())
     }
 
+    /// Loads the BTF metadata of the kernel module `module` from `/sys/kernel/btf/<module>`.
+    ///
+    /// Module BTF is split BTF: it only describes the types defined by the
+    /// module and refers to the kernel types for everything else. `base` must
+    /// be the BTF of the running kernel, as returned by [`Btf::from_sys_fs`].
+    ///
+    /// The returned value contains the types of both the kernel and the module,
+    /// and can be used to load programs that attach to module functions or
+    /// tracepoints.
+    pub fn from_sys_fs_module(module: &str, base: &Btf) -> Result<Btf, BtfError> {
+        let path = Path::new("/sys/kernel/btf").join(module);
+        let data = fs::read(&path).map_err(|error| BtfError::FileError { path, error })?;
+        let mut btf = Btf::parse_split(&data, Endianness::default(), base)?;
+        btf.module = Some(module.to_owned());
+        Ok(btf)
+    }
+
+    /// Loads the BTF object with the given id from the kernel.
+    ///
+    /// BTF objects are created when loading programs and maps that carry BTF
+    /// metadata, and for the kernel and its modules. The BTF of a kernel
+    /// module is merged with the BTF of the running kernel, like with
+    /// [`Btf::from_sys_fs_module`].
+    ///
+    /// Opening BTF objects by id requires `CAP_SYS_ADMIN`.
+    pub fn from_id(id: u32) -> Result<Btf, BtfError> {
+        let syscall_error = |call: &str| {
+            let call = call.to_owned();
+            move |io_error| BtfError::SyscallError { call, io_error }
+        };
+        let fd = bpf_btf_get_fd_by_id(id).map_err(syscall_error("bpf_btf_get_fd_by_id"))?;
+        let mut name = [0u8; 64];
+        let info = bpf_btf_get_info_by_fd(fd, &mut name);
+        let data = bpf_btf_get_data_by_fd(fd);
+        unsafe { libc::close(fd) };
+        let info = info.map_err(syscall_error("bpf_btf_get_info_by_fd"))?;
+        let data = data.map_err(syscall_error("bpf_btf_get_data_by_fd"))?;
+
+        let name = CStr::from_bytes_until_nul(&name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if info.kernel_btf != 0 && name != "vmlinux" {
+            let base = Btf::from_sys_fs()?;
+            let mut btf = Btf::parse_split(&data, Endianness::default(), &base)?;
+            btf.module = Some(name);
+            return Ok(btf);
+        }
+        Btf::parse(&data, Endianness::default())
+    }
+
     /// Loads BTF metadata from the given `path`.
     pub fn parse_file<P: AsRef<Path>>(path: P, endianness: Endianness) -> Result<Btf, BtfError> {
         let path = path.as_ref();
@@ -128,10 +256,195 @@ impl Btf {
             header,
             strings,
             types,
+            module: None,
             _endianness: endianness,
         })
     }
 
+    /// Parses split BTF `data` on top of `base`.
+    ///
+    /// Type ids and string offsets in split BTF continue where the ones of the
+    /// base BTF end, so the types and strings of both are concatenated.
+    pub(crate) fn parse_split(
+        data: &[u8],
+        endianness: Endianness,
+        base: &Btf,
+    ) -> Result<Btf, BtfError> {
+        let split = Btf::parse(data, endianness)?;
+
+        let mut strings = base.strings.clone();
+        strings.extend_from_slice(&split.strings);
+        let mut types = base.types.clone();
+        // skip the void type, which is only in the base
+        types.extend(split.types.into_iter().skip(1));
+
+        let mut header = split.header;
+        header.str_off = 0;
+        header.str_len = strings.len() as u32;
+
+        Ok(Btf {
+            header,
+            strings,
+            types,
+            module: base.module.clone(),
+            _endianness: endianness,
+        })
+    }
+
+    /// Encodes the BTF in the format expected by `BPF_BTF_LOAD`.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let types = self
+            .types
+            .iter()
+            .flat_map(|ty| ty.to_bytes())
+            .collect::<Vec<_>>();
+
+        let mut header = self.header;
+        header.hdr_len = mem::size_of::<btf_header>() as u32;
+        header.type_off = 0;
+        header.type_len = types.len() as u32;
+        header.str_off = header.type_len;
+        header.str_len = self.strings.len() as u32;
+
+        // Safety: btf_header is POD
+        let mut buf = unsafe {
+            std::slice::from_raw_parts(
+                &header as *const _ as *const u8,
+                mem::size_of::<btf_header>(),
+            )
+        }
+        .to_vec();
+        buf.extend(types);
+        buf.extend_from_slice(&self.strings);
+        buf
+    }
+
+    /// Returns a copy of the BTF where the kinds not supported by the kernel are
+    /// replaced with equivalent kinds older kernels understand.
+    ///
+    /// Type ids are preserved, so relocations and BTF ids computed against the
+    /// original BTF remain valid.
+    pub(crate) fn sanitize(&self, features: &BtfFeatures) -> Btf {
+        let mut btf = self.clone();
+        if !features.btf_datasec {
+            btf.sanitize_datasecs();
+        }
+
+        for ty in btf.types.iter_mut() {
+            match ty {
+                // a function prototype becomes an enum with one value per
+                // parameter
+                BtfType::FuncProto(t, params) if !features.btf_func => {
+                    let mut t = *t;
+                    t.info = type_info(BtfKind::Enum, params.len());
+                    t.__bindgen_anon_1.size = mem::size_of::<i32>() as u32;
+                    let enums = params
+                        .iter()
+                        .map(|p| btf_enum {
+                            name_off: p.name_off,
+                            val: 0,
+                        })
+                        .collect();
+                    *ty = BtfType::Enum(t, enums);
+                }
+                // a function becomes a typedef of its prototype
+                BtfType::Func(t) if !features.btf_func => {
+                    let mut t = *t;
+                    t.info = type_info(BtfKind::Typedef, 0);
+                    *ty = BtfType::Typedef(t);
+                }
+                // global functions become static
+                BtfType::Func(t)
+                    if !features.btf_func_global && type_vlen(t) as u32 == BTF_FUNC_GLOBAL =>
+                {
+                    t.info = type_info(BtfKind::Func, BTF_FUNC_STATIC as usize);
+                }
+                // a float becomes a struct of the same size with no members
+                BtfType::Float(t) if !features.btf_float => {
+                    let mut t = *t;
+                    t.info = type_info(BtfKind::Struct, 0);
+                    *ty = BtfType::Struct(t, Vec::new());
+                }
+                // a decl tag becomes an anonymous one byte int
+                BtfType::DeclTag(_, _) if !features.btf_decl_tag => {
+                    *ty = BtfType::new_int(0, 1, 0, 0);
+                }
+                // a type tag becomes a const modifier of the same type
+                BtfType::TypeTag(t) if !features.btf_type_tag => {
+                    let mut t = *t;
+                    t.name_off = 0;
+                    t.info = type_info(BtfKind::Const, 0);
+                    *ty = BtfType::Const(t);
+                }
+                // a 64 bit enum becomes an enum of the same size with the
+                // values truncated to 32 bits
+                BtfType::Enum64(t, enums) if !features.btf_enum64 => {
+                    let mut t: btf_type = *t;
+                    t.info = type_info(BtfKind::Enum, enums.len());
+                    let enums = enums
+                        .iter()
+                        .map(|e| btf_enum {
+                            name_off: e.name_off,
+                            val: e.val_lo32 as i32,
+                        })
+                        .collect();
+                    *ty = BtfType::Enum(t, enums);
+                }
+                _ => {}
+            }
+        }
+        btf
+    }
+
+    // replaces each datasec with a struct whose members are the variables of
+    // the section, and each variable with a one byte int
+    fn sanitize_datasecs(&mut self) {
+        let mut structs = Vec::new();
+        for (type_id, ty) in self.types.iter().enumerate() {
+            if let BtfType::DataSec(t, vars) = ty {
+                let members = vars
+                    .iter()
+                    .map(|var| btf_member {
+                        name_off: self
+                            .types
+                            .get(var.type_ as usize)
+                            .and_then(|ty| ty.name_offset())
+                            .unwrap_or(0),
+                        type_: var.type_,
+                        offset: var.offset * 8,
+                    })
+                    .collect::<Vec<_>>();
+                // datasec names like .data aren't valid identifiers
+                let name = self
+                    .string_at(t.name_off)
+                    .map(|name| name.replace('.', "_"))
+                    .unwrap_or_default();
+                let mut t = *t;
+                t.info = type_info(BtfKind::Struct, members.len());
+                structs.push((type_id, t, name, members));
+            }
+        }
+        for (type_id, mut t, name, members) in structs {
+            t.name_off = if name.is_empty() {
+                0
+            } else {
+                self.add_string(&name)
+            };
+            self.types[type_id] = BtfType::Struct(t, members);
+        }
+
+        for ty in self.types.iter_mut() {
+            if let BtfType::Var(t, _) = ty {
+                *ty = BtfType::new_int(t.name_off, 1, 0, 0);
+            }
+        }
+    }
+
+    /// Returns the name of the kernel module this BTF was loaded for, if any.
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
     fn read_type_info(
         header: &btf_header,
         data: &[u8],
@@ -201,7 +514,7 @@ impl Btf {
 
             use BtfType::*;
             match ty {
-                Volatile(ty) | Const(ty) | Restrict(ty) | Typedef(ty) => {
+                Volatile(ty) | Const(ty) | Restrict(ty) | Typedef(ty) | TypeTag(ty) => {
                     // Safety: union
                     type_id = unsafe { ty.__bindgen_anon_1.type_ };
                     continue;
@@ -251,6 +564,51 @@ impl Btf {
         })
     }
 
+    /// Returns the name, type id and offset of the variables in the DATASEC
+    /// named `section`.
+    pub(crate) fn datasec_vars(&self, section: &str) -> Result<Vec<(String, u32, u32)>, BtfError> {
+        let mut vars = Vec::new();
+        for ty in self.types() {
+            let secinfo = match ty {
+                BtfType::DataSec(_, secinfo) if self.type_name(ty)?.as_deref() == Some(section) => {
+                    secinfo
+                }
+                _ => continue,
+            };
+            for info in secinfo {
+                if let BtfType::Var(ty, _) = self.type_by_id(info.type_)? {
+                    // Safety: union
+                    let type_id = unsafe { ty.__bindgen_anon_1.type_ };
+                    vars.push((
+                        self.string_at(ty.name_off)?.to_string(),
+                        type_id,
+                        info.offset,
+                    ));
+                }
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// Returns true if `type_id` is a struct with a `struct bpf_spin_lock`
+    /// member, which the kernel requires to use `BPF_F_LOCK`.
+    pub(crate) fn has_spin_lock(&self, type_id: u32) -> Result<bool, BtfError> {
+        let members = match self.type_by_id(self.resolve_type(type_id)?)? {
+            BtfType::Struct(_, members) => members,
+            _ => return Ok(false),
+        };
+        for member in members {
+            let ty = self.type_by_id(self.resolve_type(member.type_)?)?;
+            if matches!(ty, BtfType::Struct(_, _))
+                && self.type_name(ty)?.as_deref() == Some("bpf_spin_lock")
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub(crate) fn type_size(&self, root_type_id: u32) -> Result<usize, BtfError> {
         let mut type_id = root_type_id;
         let mut n_elems = 1;
@@ -263,13 +621,19 @@ impl Btf {
                 | Struct(ty, _)
                 | Union(ty, _)
                 | Enum(ty, _)
+                | Enum64(ty, _)
                 | DataSec(ty, _)
                 | Float(ty) => {
                     // Safety: union
                     unsafe { ty.__bindgen_anon_1.size as usize }
                 }
                 Ptr(_) => mem::size_of::<*const c_void>(), // FIXME
-                Typedef(ty) | Volatile(ty) | Const(ty) | Restrict(ty) | Var(ty, _) => {
+                Typedef(ty)
+                | Volatile(ty)
+                | Const(ty)
+                | Restrict(ty)
+                | TypeTag(ty)
+                | Var(ty, _) => {
                     // Safety: union
                     type_id = unsafe { ty.__bindgen_anon_1.type_ };
                     continue;
@@ -279,7 +643,7 @@ impl Btf {
                     type_id = array.type_;
                     continue;
                 }
-                Unknown | Fwd(_) | Func(_) | FuncProto(_, _) => {
+                Unknown | Fwd(_) | Func(_) | FuncProto(_, _) | DeclTag(_, _) => {
                     return Err(BtfError::UnexpectedBtfType { type_id })
                 }
             };
@@ -303,9 +667,11 @@ pub struct BtfExt {
     data: Vec<u8>,
     _endianness: Endianness,
     relocations: Vec<(u32, Vec<Relocation>)>,
+    func_info: Vec<(u32, Vec<bpf_func_info>)>,
+    line_info: Vec<(u32, Vec<bpf_line_info>)>,
     header: btf_ext_header,
-    _func_info_rec_size: usize,
-    _line_info_rec_size: usize,
+    func_info_rec_size: usize,
+    line_info_rec_size: usize,
     core_relo_rec_size: usize,
 }
 
@@ -352,8 +718,10 @@ impl BtfExt {
         let mut ext = BtfExt {
             header,
             relocations: Vec::new(),
-            _func_info_rec_size: rec_size(func_info_off, func_info_len)?,
-            _line_info_rec_size: rec_size(line_info_off, line_info_len)?,
+            func_info: Vec::new(),
+            line_info: Vec::new(),
+            func_info_rec_size: rec_size(func_info_off, func_info_len)?,
+            line_info_rec_size: rec_size(line_info_off, line_info_len)?,
             core_relo_rec_size: rec_size(core_relo_off, core_relo_len)?,
             data: data.to_vec(),
             _endianness: endianness,
@@ -374,6 +742,19 @@ impl BtfExt {
                 .collect::<Result<Vec<_>, _>>()?,
         );
 
+        let func_info = read_records::<bpf_func_info>(
+            ext.func_info_data(),
+            ext.func_info_rec_size,
+            endianness,
+        )?;
+        ext.func_info = func_info;
+        let line_info = read_records::<bpf_line_info>(
+            ext.line_info_data(),
+            ext.line_info_rec_size,
+            endianness,
+        )?;
+        ext.line_info = line_info;
+
         Ok(ext)
     }
 
@@ -392,9 +773,61 @@ impl BtfExt {
         self.info_data(self.header.core_relo_off, self.header.core_relo_len)
     }
 
+    fn func_info_data(&self) -> &[u8] {
+        self.info_data(self.header.func_info_off, self.header.func_info_len)
+    }
+
+    fn line_info_data(&self) -> &[u8] {
+        self.info_data(self.header.line_info_off, self.header.line_info_len)
+    }
+
     pub(crate) fn relocations(&self) -> impl Iterator<Item = &(u32, Vec<Relocation>)> {
         self.relocations.iter()
     }
+
+    /// Returns the function info records of each section. The instruction
+    /// offsets are in bytes from the start of the section.
+    pub(crate) fn func_info(&self) -> impl Iterator<Item = &(u32, Vec<bpf_func_info>)> {
+        self.func_info.iter()
+    }
+
+    /// Returns the line info records of each section. The instruction offsets
+    /// are in bytes from the start of the section.
+    pub(crate) fn line_info(&self) -> impl Iterator<Item = &(u32, Vec<bpf_line_info>)> {
+        self.line_info.iter()
+    }
+}
+
+// reads the func_info or line_info records of each section. The records in
+// the object can be bigger than `T`, in which case the extra fields are
+// ignored.
+fn read_records<T: Copy>(
+    data: &[u8],
+    rec_size: usize,
+    endianness: Endianness,
+) -> Result<Vec<(u32, Vec<T>)>, BtfError> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    if rec_size < mem::size_of::<T>() {
+        return Err(BtfError::InvalidInfo {
+            offset: 0,
+            len: rec_size,
+            section_len: data.len(),
+        });
+    }
+    Ok(SecInfoIter::new(data, rec_size, endianness)
+        .map(|sec| {
+            let records = sec
+                .data
+                .chunks_exact(rec_size)
+                // Safety: the records are POD and the chunks are at least
+                // size_of::<T>() bytes
+                .map(|rec| unsafe { ptr::read_unaligned(rec.as_ptr() as *const T) })
+                .collect();
+            (sec.sec_name_off, records)
+        })
+        .collect())
 }
 
 pub(crate) struct SecInfoIter<'a> {
@@ -455,6 +888,229 @@ pub(crate) struct SecInfo<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::generated::{btf_member, btf_param, btf_var_secinfo};
+
+    fn btf_data(types: &[u8], strings: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0xeb9fu16.to_le_bytes());
+        data.extend_from_slice(&[1, 0]);
+        for v in &[24, 0, types.len(), types.len(), strings.len()] {
+            data.extend_from_slice(&(*v as u32).to_le_bytes());
+        }
+        data.extend_from_slice(types);
+        data.extend_from_slice(strings);
+        data
+    }
+
+    fn btf_type(name_off: u32, kind: u32, size_or_type: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&name_off.to_le_bytes());
+        data.extend_from_slice(&(kind << 24).to_le_bytes());
+        data.extend_from_slice(&size_or_type.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_split() {
+        // int, 32 bits
+        let mut types = btf_type(1, BtfKind::Int as u32, 4);
+        types.extend_from_slice(&32u32.to_le_bytes());
+        let base = Btf::parse(&btf_data(&types, b"\0int\0"), Endianness::Little).unwrap();
+
+        // typedef int foo_t
+        let types = btf_type(5, BtfKind::Typedef as u32, 1);
+        let btf =
+            Btf::parse_split(&btf_data(&types, b"foo_t\0"), Endianness::Little, &base).unwrap();
+
+        let id = btf.id_by_type_name_kind("foo_t", BtfKind::Typedef).unwrap();
+        assert_eq!(id, 2);
+        assert_eq!(btf.resolve_type(id).unwrap(), 1);
+        assert_eq!(btf.type_size(id).unwrap(), 4);
+        assert_eq!(
+            btf.id_by_type_name_kind("int", BtfKind::Int).unwrap(),
+            base.id_by_type_name_kind("int", BtfKind::Int).unwrap()
+        );
+        assert!(btf.string_at(11).is_err());
+    }
+
+    #[test]
+    fn test_has_spin_lock() {
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, 0, 0));
+        let member = |btf: &mut Btf, name: &str, type_, offset| btf_member {
+            name_off: btf.add_string(name),
+            type_,
+            offset,
+        };
+        let val = member(&mut btf, "val", int_type_id, 0);
+        let name_offset = btf.add_string("bpf_spin_lock");
+        let lock_type_id = btf.add_type(BtfType::new_struct(name_offset, vec![val], 4));
+
+        let counter = member(&mut btf, "counter", int_type_id, 0);
+        let lock = member(&mut btf, "lock", lock_type_id, 32);
+        let name_offset = btf.add_string("value");
+        let value_type_id = btf.add_type(BtfType::new_struct(name_offset, vec![counter, lock], 8));
+        let counter = member(&mut btf, "counter", int_type_id, 0);
+        let name_offset = btf.add_string("plain");
+        let plain_type_id = btf.add_type(BtfType::new_struct(name_offset, vec![counter], 4));
+
+        assert!(btf.has_spin_lock(value_type_id).unwrap());
+        assert!(!btf.has_spin_lock(plain_type_id).unwrap());
+        assert!(!btf.has_spin_lock(int_type_id).unwrap());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, 0, 0));
+        let name_offset = btf.add_string("double");
+        let float_type_id = btf.add_type(BtfType::new_float(name_offset, 8));
+        let name_offset = btf.add_string("tag");
+        let type_tag_id = btf.add_type(BtfType::new_type_tag(name_offset, int_type_id));
+        let decl_tag_id = btf.add_type(BtfType::new_decl_tag(name_offset, int_type_id, -1));
+
+        let raw = btf.to_bytes();
+        let parsed = Btf::parse(&raw, Endianness::default()).unwrap();
+        assert_eq!(parsed.to_bytes(), raw);
+
+        let features = BtfFeatures {
+            btf_float: true,
+            ..Default::default()
+        };
+        let sanitized = btf.sanitize(&features);
+        assert!(matches!(
+            sanitized.type_by_id(float_type_id).unwrap(),
+            BtfType::Float(_)
+        ));
+        assert!(matches!(
+            sanitized.type_by_id(type_tag_id).unwrap(),
+            BtfType::Const(_)
+        ));
+        assert!(matches!(
+            sanitized.type_by_id(decl_tag_id).unwrap(),
+            BtfType::Int(_, _)
+        ));
+        assert_eq!(sanitized.resolve_type(type_tag_id).unwrap(), int_type_id);
+
+        let sanitized = btf.sanitize(&BtfFeatures::default());
+        match sanitized.type_by_id(float_type_id).unwrap() {
+            BtfType::Struct(_, members) => assert!(members.is_empty()),
+            t => panic!("expected struct type, got {:#?}", t),
+        }
+        assert_eq!(sanitized.type_size(float_type_id).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_sanitize_datasec_func() {
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, 0, 0));
+        let name_offset = btf.add_string("counter");
+        let var_type_id = btf.add_type(BtfType::new_var(name_offset, int_type_id, 1));
+        let name_offset = btf.add_string(".data");
+        let datasec_type_id = btf.add_type(BtfType::new_datasec(
+            name_offset,
+            vec![btf_var_secinfo {
+                type_: var_type_id,
+                offset: 4,
+                size: 4,
+            }],
+            8,
+        ));
+        let name_offset = btf.add_string("arg");
+        let proto_type_id = btf.add_type(BtfType::new_func_proto(
+            vec![btf_param {
+                name_off: name_offset,
+                type_: int_type_id,
+            }],
+            int_type_id,
+        ));
+        let name_offset = btf.add_string("prog");
+        let func_type_id = btf.add_type(BtfType::new_func(
+            name_offset,
+            proto_type_id,
+            BTF_FUNC_GLOBAL,
+        ));
+
+        let features = BtfFeatures {
+            btf_func: true,
+            ..Default::default()
+        };
+        let sanitized = btf.sanitize(&features);
+        assert!(matches!(
+            sanitized.type_by_id(var_type_id).unwrap(),
+            BtfType::Int(_, _)
+        ));
+        match sanitized.type_by_id(datasec_type_id).unwrap() {
+            BtfType::Struct(t, members) => {
+                assert_eq!(sanitized.string_at(t.name_off).unwrap(), "_data");
+                assert_eq!(members.len(), 1);
+                assert_eq!(members[0].offset, 32);
+                assert_eq!(sanitized.string_at(members[0].name_off).unwrap(), "counter");
+            }
+            t => panic!("expected struct type, got {:#?}", t),
+        }
+        match sanitized.type_by_id(func_type_id).unwrap() {
+            BtfType::Func(t) => assert_eq!(type_vlen(t) as u32, BTF_FUNC_STATIC),
+            t => panic!("expected func type, got {:#?}", t),
+        }
+
+        let sanitized = btf.sanitize(&BtfFeatures::default());
+        assert!(matches!(
+            sanitized.type_by_id(proto_type_id).unwrap(),
+            BtfType::Enum(_, _)
+        ));
+        assert!(matches!(
+            sanitized.type_by_id(func_type_id).unwrap(),
+            BtfType::Typedef(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_btf_ext_func_line_info() {
+        let u32s = |vals: &[u32]| {
+            vals.iter()
+                .flat_map(|v| v.to_le_bytes().to_vec())
+                .collect::<Vec<_>>()
+        };
+        // rec_size, sec_name_off, num_info, then the records. The line info
+        // records carry an extra field that must be skipped.
+        let func_info = u32s(&[8, 1, 2, 0, 3, 16, 4]);
+        let line_info = u32s(&[20, 1, 1, 8, 5, 6, 7, 0xff]);
+        let mut data = u32s(&[0x0001eb9f, 32]);
+        data.extend(u32s(&[
+            0,
+            func_info.len() as u32,
+            func_info.len() as u32,
+            line_info.len() as u32,
+            0,
+            0,
+        ]));
+        data.extend(func_info);
+        data.extend(line_info);
+
+        let ext = BtfExt::parse(&data, Endianness::Little).unwrap();
+        let func_info = ext.func_info().collect::<Vec<_>>();
+        assert_eq!(func_info.len(), 1);
+        assert_eq!(func_info[0].0, 1);
+        assert_eq!(
+            func_info[0]
+                .1
+                .iter()
+                .map(|i| (i.insn_off, i.type_id))
+                .collect::<Vec<_>>(),
+            vec![(0, 3), (16, 4)]
+        );
+        let line_info = ext.line_info().collect::<Vec<_>>();
+        assert_eq!(line_info.len(), 1);
+        let rec = line_info[0].1[0];
+        assert_eq!(
+            (rec.insn_off, rec.file_name_off, rec.line_off, rec.line_col),
+            (8, 5, 6, 7)
+        );
+    }
 
     #[test]
     fn test_parse_header() {
@@ -0,0 +1,618 @@
+use std::collections::HashSet;
+
+use crate::obj::btf::{member_bit_field_size, Btf, BtfError, BtfType, MAX_RESOLVE_DEPTH};
+
+impl Btf {
+    /// Renders the types as C declarations, like `bpftool btf dump format c`.
+    ///
+    /// All the named structs, unions, enums and typedefs are rendered, along
+    /// with the anonymous enums that aren't used by any other type, which
+    /// usually define constants. Types are ordered so that they're defined
+    /// before being used by value. Functions, variables and data sections are
+    /// not rendered.
+    ///
+    /// The output is meant to be read when debugging CO-RE relocations or to
+    /// feed code generators. Unlike bpftool, padding and packing attributes
+    /// are not emitted, so it's not guaranteed to compile to the same layout.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::Btf;
+    ///
+    /// let btf = Btf::from_sys_fs()?;
+    /// println!("{}", btf.to_c_dump()?);
+    /// # Ok::<(), aya::BtfError>(())
+    /// ```
+    pub fn to_c_dump(&self) -> Result<String, BtfError> {
+        let mut referenced = HashSet::new();
+        for ty in self.types() {
+            referenced.extend(type_refs(ty));
+        }
+
+        let mut dumper = CDumper::new(self);
+        for (type_id, ty) in self.types().enumerate() {
+            let type_id = type_id as u32;
+            let is_anon = dumper.name(ty)?.is_empty();
+            let emit = match ty {
+                BtfType::Struct(_, _)
+                | BtfType::Union(_, _)
+                | BtfType::Typedef(_)
+                | BtfType::Fwd(_) => !is_anon,
+                BtfType::Enum(_, _) | BtfType::Enum64(_, _) => {
+                    !is_anon || !referenced.contains(&type_id)
+                }
+                _ => false,
+            };
+            if emit {
+                dumper.emit(type_id, 0)?;
+            }
+        }
+
+        Ok(dumper.out)
+    }
+
+    /// Renders the definition of the struct, union, enum or typedef named
+    /// `name` as C.
+    ///
+    /// Only the type itself is rendered, not the types it depends on. See
+    /// [`Btf::to_c_dump`].
+    pub fn to_c_type(&self, name: &str) -> Result<String, BtfError> {
+        let dumper = CDumper::new(self);
+        for (type_id, ty) in self.types().enumerate() {
+            match ty {
+                BtfType::Struct(_, _)
+                | BtfType::Union(_, _)
+                | BtfType::Enum(_, _)
+                | BtfType::Enum64(_, _)
+                | BtfType::Typedef(_)
+                | BtfType::Fwd(_) => {}
+                _ => continue,
+            }
+            if dumper.name(ty)? == name {
+                return dumper.definition(type_id as u32);
+            }
+        }
+
+        Err(BtfError::UnknownBtfTypeName {
+            type_name: name.to_owned(),
+        })
+    }
+
+    /// Renders the prototype of the function `type_id` as C, declaring it as
+    /// `name`.
+    pub(crate) fn func_decl(&self, type_id: u32, name: &str) -> Result<String, BtfError> {
+        let proto = match self.type_by_id(type_id)? {
+            // Safety: union
+            BtfType::Func(ty) => unsafe { ty.__bindgen_anon_1.type_ },
+            _ => return Err(BtfError::UnexpectedBtfType { type_id }),
+        };
+        CDumper::new(self).decl(proto, name, 0, 0)
+    }
+
+    /// Returns the arguments of the function prototype `type_id`, following
+    /// functions, typedefs and pointers that lead to it.
+    ///
+    /// The variadic marker of variadic functions is not returned.
+    pub(crate) fn func_args(&self, type_id: u32) -> Result<Vec<BtfFuncArg>, BtfError> {
+        let dumper = CDumper::new(self);
+        let mut type_id = type_id;
+        for _ in 0..MAX_RESOLVE_DEPTH {
+            let ty = self.type_by_id(type_id)?;
+            match ty {
+                BtfType::Func(ty) | BtfType::Typedef(ty) | BtfType::Ptr(ty) => {
+                    // Safety: union
+                    type_id = unsafe { ty.__bindgen_anon_1.type_ };
+                }
+                BtfType::FuncProto(_, params) => {
+                    return params
+                        .iter()
+                        .filter(|p| p.type_ != 0)
+                        .map(|p| {
+                            Ok(BtfFuncArg {
+                                name: self.string_at(p.name_off)?.into_owned(),
+                                type_name: dumper.decl(p.type_, "", 0, 0)?,
+                            })
+                        })
+                        .collect();
+                }
+                _ => return Err(BtfError::UnexpectedBtfType { type_id }),
+            }
+        }
+
+        Err(BtfError::MaximumTypeDepthReached { type_id })
+    }
+}
+
+/// An argument of a kernel function or tracepoint, as described by BTF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BtfFuncArg {
+    /// The name of the argument. Can be empty.
+    pub name: String,
+    /// The type of the argument rendered as C, eg `struct task_struct *`.
+    pub type_name: String,
+}
+
+// the ids of the types directly referenced by `ty`
+fn type_refs(ty: &BtfType) -> Vec<u32> {
+    use BtfType::*;
+    match ty {
+        Const(ty)
+        | Volatile(ty)
+        | Restrict(ty)
+        | Ptr(ty)
+        | Typedef(ty)
+        | TypeTag(ty)
+        | Func(ty)
+        | Var(ty, _) => {
+            // Safety: union
+            vec![unsafe { ty.__bindgen_anon_1.type_ }]
+        }
+        Array(_, array) => vec![array.type_],
+        Struct(_, members) | Union(_, members) => members.iter().map(|m| m.type_).collect(),
+        FuncProto(ty, params) => {
+            // Safety: union
+            let mut refs = vec![unsafe { ty.__bindgen_anon_1.type_ }];
+            refs.extend(params.iter().map(|p| p.type_));
+            refs
+        }
+        DataSec(_, vars) => vars.iter().map(|v| v.type_).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn kind_flag(ty: &BtfType) -> bool {
+    ty.info().map(|info| info >> 31 == 1).unwrap_or(false)
+}
+
+struct CDumper<'a> {
+    btf: &'a Btf,
+    emitted: HashSet<u32>,
+    out: String,
+}
+
+impl<'a> CDumper<'a> {
+    fn new(btf: &'a Btf) -> CDumper<'a> {
+        CDumper {
+            btf,
+            emitted: HashSet::new(),
+            out: String::new(),
+        }
+    }
+
+    fn name(&self, ty: &BtfType) -> Result<String, BtfError> {
+        Ok(self
+            .btf
+            .type_name(ty)?
+            .map(|name| name.into_owned())
+            .unwrap_or_default())
+    }
+
+    // emits the definition of `type_id` after the definitions it depends on
+    fn emit(&mut self, type_id: u32, depth: u8) -> Result<(), BtfError> {
+        if !self.emitted.insert(type_id) {
+            return Ok(());
+        }
+
+        let ty = self.btf.type_by_id(type_id)?;
+        match ty {
+            BtfType::Struct(_, members) | BtfType::Union(_, members) => {
+                for member in members {
+                    self.emit_deps(member.type_, true, depth + 1)?;
+                }
+            }
+            BtfType::Typedef(ty) => {
+                // Safety: union
+                let target = unsafe { ty.__bindgen_anon_1.type_ };
+                // typedefs of structs and unions don't need their definition
+                let target_ty = self.btf.type_by_id(target)?;
+                if !target_ty.is_composite() || self.name(target_ty)?.is_empty() {
+                    self.emit_deps(target, true, depth + 1)?;
+                }
+            }
+            _ => {}
+        }
+
+        let definition = self.definition(type_id)?;
+        self.out.push_str(&definition);
+        self.out.push_str("\n\n");
+
+        Ok(())
+    }
+
+    // emits the types that must be declared before `type_id` is used
+    fn emit_deps(&mut self, type_id: u32, by_value: bool, depth: u8) -> Result<(), BtfError> {
+        if depth > MAX_RESOLVE_DEPTH {
+            return Err(BtfError::MaximumTypeDepthReached { type_id });
+        }
+
+        let ty = self.btf.type_by_id(type_id)?;
+        let is_anon = self.name(ty)?.is_empty();
+        use BtfType::*;
+        match ty {
+            Const(ty) | Volatile(ty) | Restrict(ty) | TypeTag(ty) => {
+                // Safety: union
+                self.emit_deps(unsafe { ty.__bindgen_anon_1.type_ }, by_value, depth + 1)?
+            }
+            Ptr(ty) => {
+                // Safety: union
+                self.emit_deps(unsafe { ty.__bindgen_anon_1.type_ }, false, depth + 1)?
+            }
+            Array(_, array) => self.emit_deps(array.type_, by_value, depth + 1)?,
+            FuncProto(ty, params) => {
+                // Safety: union
+                self.emit_deps(unsafe { ty.__bindgen_anon_1.type_ }, false, depth + 1)?;
+                for param in params {
+                    self.emit_deps(param.type_, false, depth + 1)?;
+                }
+            }
+            Typedef(ty) => {
+                self.emit(type_id, depth + 1)?;
+                if by_value {
+                    // Safety: union
+                    self.emit_deps(unsafe { ty.__bindgen_anon_1.type_ }, true, depth + 1)?;
+                }
+            }
+            Struct(_, members) | Union(_, members) => {
+                if is_anon {
+                    // anonymous types are defined inline
+                    for member in members {
+                        self.emit_deps(member.type_, true, depth + 1)?;
+                    }
+                } else if by_value {
+                    self.emit(type_id, depth + 1)?;
+                }
+            }
+            Enum(_, _) | Enum64(_, _) if !is_anon => self.emit(type_id, depth + 1)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn definition(&self, type_id: u32) -> Result<String, BtfError> {
+        let ty = self.btf.type_by_id(type_id)?;
+        let definition = match ty {
+            BtfType::Struct(_, _)
+            | BtfType::Union(_, _)
+            | BtfType::Enum(_, _)
+            | BtfType::Enum64(_, _) => self.body(type_id, 0, 0)?,
+            BtfType::Typedef(target) => {
+                // Safety: union
+                let target = unsafe { target.__bindgen_anon_1.type_ };
+                format!("typedef {}", self.decl(target, &self.name(ty)?, 0, 0)?)
+            }
+            BtfType::Fwd(_) => self.type_ref(type_id)?,
+            _ => return Err(BtfError::UnexpectedBtfType { type_id }),
+        };
+
+        Ok(format!("{};", definition))
+    }
+
+    // the name used to refer to a named type, eg `struct foo`
+    fn type_ref(&self, type_id: u32) -> Result<String, BtfError> {
+        let ty = self.btf.type_by_id(type_id)?;
+        let name = self.name(ty)?;
+        let keyword = match ty {
+            BtfType::Struct(_, _) => "struct",
+            BtfType::Union(_, _) => "union",
+            BtfType::Enum(_, _) | BtfType::Enum64(_, _) => "enum",
+            BtfType::Fwd(_) if kind_flag(ty) => "union",
+            BtfType::Fwd(_) => "struct",
+            _ => return Ok(name),
+        };
+        if name.is_empty() {
+            Ok(keyword.to_owned())
+        } else {
+            Ok(format!("{} {}", keyword, name))
+        }
+    }
+
+    // the full definition of a struct, union or enum
+    fn body(&self, type_id: u32, indent: usize, depth: u8) -> Result<String, BtfError> {
+        let ty = self.btf.type_by_id(type_id)?;
+        let pad = "\t".repeat(indent + 1);
+        let mut body = format!("{} {{\n", self.type_ref(type_id)?);
+        match ty {
+            BtfType::Struct(btf_ty, members) | BtfType::Union(btf_ty, members) => {
+                for member in members {
+                    let name = self.btf.string_at(member.name_off)?;
+                    body.push_str(&pad);
+                    body.push_str(&self.decl(member.type_, &name, indent + 1, depth + 1)?);
+                    let bits = member_bit_field_size(btf_ty, member);
+                    if bits > 0 {
+                        body.push_str(&format!(": {}", bits));
+                    }
+                    body.push_str(";\n");
+                }
+            }
+            BtfType::Enum(_, values) => {
+                let signed = kind_flag(ty);
+                for value in values {
+                    let name = self.btf.string_at(value.name_off)?;
+                    let val = if signed {
+                        value.val as i64
+                    } else {
+                        value.val as u32 as i64
+                    };
+                    body.push_str(&format!("{}{} = {},\n", pad, name, val));
+                }
+            }
+            BtfType::Enum64(_, values) => {
+                let signed = kind_flag(ty);
+                for value in values {
+                    let name = self.btf.string_at(value.name_off)?;
+                    let val = (value.val_hi32 as u64) << 32 | value.val_lo32 as u64;
+                    if signed {
+                        body.push_str(&format!("{}{} = {},\n", pad, name, val as i64));
+                    } else {
+                        body.push_str(&format!("{}{} = {},\n", pad, name, val));
+                    }
+                }
+            }
+            _ => return Err(BtfError::UnexpectedBtfType { type_id }),
+        }
+        body.push_str(&"\t".repeat(indent));
+        body.push('}');
+
+        Ok(body)
+    }
+
+    // declares `declarator` as `type_id`, eg `int (*foo)[4]`
+    fn decl(
+        &self,
+        type_id: u32,
+        declarator: &str,
+        indent: usize,
+        depth: u8,
+    ) -> Result<String, BtfError> {
+        if depth > MAX_RESOLVE_DEPTH {
+            return Err(BtfError::MaximumTypeDepthReached { type_id });
+        }
+
+        let ty = self.btf.type_by_id(type_id)?;
+        let with_base = |base: String| {
+            if declarator.is_empty() {
+                base
+            } else {
+                format!("{} {}", base, declarator)
+            }
+        };
+        use BtfType::*;
+        Ok(match ty {
+            Unknown => with_base("void".to_owned()),
+            Int(_, _) | Float(_) | Typedef(_) | Fwd(_) => with_base(self.type_ref(type_id)?),
+            Struct(_, _) | Union(_, _) | Enum(_, _) | Enum64(_, _) => {
+                if self.name(ty)?.is_empty() {
+                    with_base(self.body(type_id, indent, depth + 1)?)
+                } else {
+                    with_base(self.type_ref(type_id)?)
+                }
+            }
+            Const(ty) | Volatile(ty) | Restrict(ty) => {
+                // Safety: union
+                let target = unsafe { ty.__bindgen_anon_1.type_ };
+                let qualifier = match ty_kind_name(self.btf.type_by_id(type_id)?) {
+                    Some(qualifier) => qualifier,
+                    None => return Err(BtfError::UnexpectedBtfType { type_id }),
+                };
+                if let Ptr(_) = self.btf.type_by_id(target)? {
+                    // the pointer itself is qualified, eg `char *const p`
+                    let declarator = if declarator.is_empty() {
+                        qualifier.to_owned()
+                    } else {
+                        format!("{} {}", qualifier, declarator)
+                    };
+                    self.decl(target, &declarator, indent, depth + 1)?
+                } else {
+                    format!(
+                        "{} {}",
+                        qualifier,
+                        self.decl(target, declarator, indent, depth + 1)?
+                    )
+                }
+            }
+            TypeTag(ty) => {
+                // Safety: union
+                let target = unsafe { ty.__bindgen_anon_1.type_ };
+                self.decl(target, declarator, indent, depth + 1)?
+            }
+            Ptr(ty) => {
+                // Safety: union
+                let target = unsafe { ty.__bindgen_anon_1.type_ };
+                let declarator = match self.btf.type_by_id(target)? {
+                    Array(_, _) | FuncProto(_, _) => format!("(*{})", declarator),
+                    _ => format!("*{}", declarator),
+                };
+                self.decl(target, &declarator, indent, depth + 1)?
+            }
+            Array(_, array) => {
+                let declarator = format!("{}[{}]", declarator, array.nelems);
+                self.decl(array.type_, &declarator, indent, depth + 1)?
+            }
+            FuncProto(ty, params) => {
+                let mut args = Vec::new();
+                for (i, param) in params.iter().enumerate() {
+                    if param.type_ == 0 && param.name_off == 0 && i == params.len() - 1 {
+                        args.push("...".to_owned());
+                        continue;
+                    }
+                    let name = self.btf.string_at(param.name_off)?;
+                    args.push(self.decl(param.type_, &name, indent, depth + 1)?);
+                }
+                if args.is_empty() {
+                    args.push("void".to_owned());
+                }
+                let declarator = format!("{}({})", declarator, args.join(", "));
+                // Safety: union
+                let ret = unsafe { ty.__bindgen_anon_1.type_ };
+                self.decl(ret, &declarator, indent, depth + 1)?
+            }
+            Func(_) | Var(_, _) | DataSec(_, _) | DeclTag(_, _) => {
+                return Err(BtfError::UnexpectedBtfType { type_id })
+            }
+        })
+    }
+}
+
+fn ty_kind_name(ty: &BtfType) -> Option<&'static str> {
+    match ty {
+        BtfType::Const(_) => Some("const"),
+        BtfType::Volatile(_) => Some("volatile"),
+        BtfType::Restrict(_) => Some("restrict"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generated::{btf_enum, btf_member, btf_param},
+        obj::btf::BTF_FUNC_GLOBAL,
+    };
+
+    fn test_btf() -> Btf {
+        let mut btf = Btf::new();
+        let member = |name_off, type_| btf_member {
+            name_off,
+            type_,
+            offset: 0,
+        };
+
+        // types are referenced before being defined, to check the ordering
+        let name = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name, 4, 1, 0));
+        let foo_ptr = btf.add_type(BtfType::new_ptr(3));
+        let (b, next, c, cb) = (
+            btf.add_string("b"),
+            btf.add_string("next"),
+            btf.add_string("c"),
+            btf.add_string("cb"),
+        );
+        let name = btf.add_string("foo");
+        let foo = btf.add_type(BtfType::new_struct(
+            name,
+            vec![
+                member(b, 5),
+                member(next, foo_ptr),
+                member(c, 6),
+                member(cb, 8),
+            ],
+            40,
+        ));
+        assert_eq!(foo, 3);
+
+        let a = btf.add_string("a");
+        let name = btf.add_string("bar");
+        let bar = btf.add_type(BtfType::new_struct(name, vec![member(a, int)], 4));
+        let name = btf.add_string("bar_t");
+        assert_eq!(btf.add_type(BtfType::new_typedef(name, bar)), 5);
+        assert_eq!(btf.add_type(BtfType::new_array(int, int, 4)), 6);
+
+        let proto = btf.add_type(BtfType::new_func_proto(
+            vec![btf_param {
+                name_off: 0,
+                type_: int,
+            }],
+            0,
+        ));
+        assert_eq!(btf.add_type(BtfType::new_ptr(proto)), 8);
+
+        let name = btf.add_string("color");
+        let (red, green) = (btf.add_string("RED"), btf.add_string("GREEN"));
+        btf.add_type(BtfType::new_enum(
+            name,
+            vec![
+                btf_enum {
+                    name_off: red,
+                    val: 0,
+                },
+                btf_enum {
+                    name_off: green,
+                    val: 1,
+                },
+            ],
+        ));
+
+        btf
+    }
+
+    #[test]
+    fn test_c_dump() {
+        let btf = test_btf();
+        assert_eq!(
+            btf.to_c_dump().unwrap(),
+            "typedef struct bar bar_t;
+
+struct bar {
+\tint a;
+};
+
+struct foo {
+\tbar_t b;
+\tstruct foo *next;
+\tint c[4];
+\tvoid (*cb)(int);
+};
+
+enum color {
+\tRED = 0,
+\tGREEN = 1,
+};
+
+"
+        );
+    }
+
+    #[test]
+    fn test_c_type() {
+        let btf = test_btf();
+        assert_eq!(btf.to_c_type("bar_t").unwrap(), "typedef struct bar bar_t;");
+        assert!(matches!(
+            btf.to_c_type("baz"),
+            Err(BtfError::UnknownBtfTypeName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_func_args() {
+        let mut btf = test_btf();
+        let (p, n) = (btf.add_string("p"), btf.add_string("n"));
+        let proto = btf.add_type(BtfType::new_func_proto(
+            vec![
+                btf_param {
+                    name_off: p,
+                    type_: 2,
+                },
+                btf_param {
+                    name_off: n,
+                    type_: 1,
+                },
+                btf_param {
+                    name_off: 0,
+                    type_: 0,
+                },
+            ],
+            1,
+        ));
+        let name = btf.add_string("baz");
+        let func = btf.add_type(BtfType::new_func(name, proto, BTF_FUNC_GLOBAL));
+        let args = vec![
+            BtfFuncArg {
+                name: "p".to_owned(),
+                type_name: "struct foo *".to_owned(),
+            },
+            BtfFuncArg {
+                name: "n".to_owned(),
+                type_name: "int".to_owned(),
+            },
+        ];
+        assert_eq!(btf.func_args(func).unwrap(), args);
+        assert_eq!(btf.func_args(proto).unwrap(), args);
+        assert!(matches!(
+            btf.func_args(1),
+            Err(BtfError::UnexpectedBtfType { type_id: 1 })
+        ));
+    }
+}
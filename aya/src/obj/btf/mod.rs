@@ -1,8 +1,12 @@
 #[allow(clippy::module_inception)]
 mod btf;
+mod dump;
 mod relocation;
 mod types;
+mod value;
 
 pub use btf::*;
+pub use dump::BtfFuncArg;
 pub use relocation::RelocationError;
 pub(crate) use types::*;
+pub use value::BtfValue;
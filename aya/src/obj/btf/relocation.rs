@@ -8,13 +8,13 @@ use thiserror::Error;
 
 use crate::{
     generated::{
-        bpf_core_relo, bpf_core_relo_kind::*, bpf_insn, BPF_ALU, BPF_ALU64, BPF_B, BPF_DW, BPF_H,
-        BPF_K, BPF_LD, BPF_LDX, BPF_ST, BPF_STX, BPF_W, BTF_INT_SIGNED,
+        bpf_core_relo, bpf_core_relo_kind::*, bpf_insn, BPF_ALU, BPF_ALU64, BPF_B, BPF_CALL,
+        BPF_DW, BPF_H, BPF_JMP, BPF_K, BPF_LD, BPF_LDX, BPF_ST, BPF_STX, BPF_W, BTF_INT_SIGNED,
     },
     obj::{
         btf::{
-            fields_are_compatible, member_bit_field_size, member_bit_offset, types_are_compatible,
-            BtfType, MAX_SPEC_LEN,
+            fields_are_compatible, kinds_match, member_bit_field_size, member_bit_offset,
+            types_are_compatible, BtfType, MAX_SPEC_LEN,
         },
         Btf, BtfError, Object, Program,
     },
@@ -251,7 +251,8 @@ fn relocate_btf_program<'target>(
             let conflicts = matches
                 .filter_map(|(cand_name, cand_spec, cand_comp_rel)| {
                     if cand_spec.bit_offset != target_spec.bit_offset
-                        || cand_comp_rel.target.value != target_comp_rel.target.value
+                        || cand_comp_rel.target.as_ref().map(|t| t.value)
+                            != target_comp_rel.target.as_ref().map(|t| t.value)
                     {
                         Some(cand_name)
                     } else {
@@ -292,7 +293,7 @@ fn find_candidates<'target>(
     let mut candidates = Vec::new();
     let local_name = flavorless_name(local_name);
     for (type_id, ty) in target_btf.types().enumerate() {
-        if local_ty.kind()? != ty.kind()? {
+        if !kinds_match(local_ty, ty)? {
             continue;
         }
         let name = &*target_btf.type_name(ty)?.unwrap();
@@ -345,25 +346,24 @@ fn match_candidate<'target>(
             let target_ty = candidate.btf.type_by_id(target_id)?;
             // the first accessor is guaranteed to have a name by construction
             let local_variant_name = local_spec.accessors[0].name.as_ref().unwrap();
-            match target_ty {
-                BtfType::Enum(_, members) => {
-                    for (index, member) in members.iter().enumerate() {
-                        let target_variant_name = candidate.btf.string_at(member.name_off)?;
-                        if flavorless_name(local_variant_name)
-                            == flavorless_name(&target_variant_name)
-                        {
-                            target_spec.parts.push(index);
-                            target_spec.accessors.push(Accessor {
-                                index,
-                                type_id: target_id,
-                                name: None,
-                            });
-                            return Ok(Some(target_spec));
-                        }
-                    }
+            let target_variants = match enum_variant_names(target_ty) {
+                Some(variants) => variants,
+                None => return Ok(None),
+            };
+            for (index, name_off) in target_variants.iter().enumerate() {
+                let target_variant_name = candidate.btf.string_at(*name_off)?;
+                if flavorless_name(local_variant_name) == flavorless_name(&target_variant_name) {
+                    target_spec.parts.push(index);
+                    target_spec.accessors.push(Accessor {
+                        index,
+                        type_id: target_id,
+                        name: None,
+                    });
+                    return Ok(Some(target_spec));
                 }
-                _ => return Ok(None),
             }
+
+            return Ok(None);
         }
         RelocationKind::FieldByteOffset
         | RelocationKind::FieldByteSize
@@ -561,50 +561,52 @@ impl<'a> AccessSpec<'a> {
                     bit_offset: 0,
                 }
             }
-            RelocationKind::EnumVariantExists | RelocationKind::EnumVariantValue => match ty {
-                BtfType::Enum(_, members) => {
-                    if parts.len() != 1 {
-                        return Err(RelocationError::InvalidAccessString {
-                            access_str: spec.to_string(),
+            RelocationKind::EnumVariantExists | RelocationKind::EnumVariantValue => {
+                match enum_variant_names(ty) {
+                    Some(variants) => {
+                        if parts.len() != 1 {
+                            return Err(RelocationError::InvalidAccessString {
+                                access_str: spec.to_string(),
+                            }
+                            .into());
                         }
-                        .into());
-                    }
-                    let index = parts[0];
-                    if index >= members.len() {
-                        return Err(RelocationError::InvalidAccessIndex {
-                            type_name: btf.err_type_name(ty),
-                            spec: spec.to_string(),
+                        let index = parts[0];
+                        if index >= variants.len() {
+                            return Err(RelocationError::InvalidAccessIndex {
+                                type_name: btf.err_type_name(ty),
+                                spec: spec.to_string(),
+                                index,
+                                max_index: variants.len(),
+                                error: "tried to access nonexistant enum variant".to_string(),
+                            }
+                            .into());
+                        }
+                        let accessors = vec![Accessor {
+                            type_id,
                             index,
-                            max_index: members.len(),
-                            error: "tried to access nonexistant enum variant".to_string(),
+                            name: Some(btf.string_at(variants[index])?.to_string()),
+                        }];
+
+                        AccessSpec {
+                            btf,
+                            root_type_id,
+                            relocation,
+                            parts,
+                            accessors,
+                            bit_offset: 0,
                         }
-                        .into());
-                    }
-                    let accessors = vec![Accessor {
-                        type_id,
-                        index,
-                        name: Some(btf.string_at(members[index].name_off)?.to_string()),
-                    }];
-
-                    AccessSpec {
-                        btf,
-                        root_type_id,
-                        relocation,
-                        parts,
-                        accessors,
-                        bit_offset: 0,
                     }
-                }
-                _ => {
-                    return Err(RelocationError::InvalidRelocationKindForType {
-                        relocation_number: relocation.number,
-                        relocation_kind: format!("{:?}", relocation.kind),
-                        type_kind: format!("{:?}", ty.kind()?.unwrap()),
-                        error: "enum relocation on non-enum type".to_string(),
+                    None => {
+                        return Err(RelocationError::InvalidRelocationKindForType {
+                            relocation_number: relocation.number,
+                            relocation_kind: format!("{:?}", relocation.kind),
+                            type_kind: format!("{:?}", ty.kind()?.unwrap()),
+                            error: "enum relocation on non-enum type".to_string(),
+                        }
+                        .into())
                     }
-                    .into())
                 }
-            },
+            }
 
             RelocationKind::FieldByteOffset
             | RelocationKind::FieldByteSize
@@ -617,7 +619,7 @@ impl<'a> AccessSpec<'a> {
                     index: parts[0],
                     name: None,
                 }];
-                let mut bit_offset = accessors[0].index as usize * btf.type_size(type_id)?;
+                let mut bit_offset = accessors[0].index as usize * btf.type_size(type_id)? * 8;
                 for index in parts.iter().skip(1).cloned() {
                     type_id = btf.resolve_type(type_id)?;
                     let ty = btf.type_by_id(type_id)?;
@@ -708,6 +710,16 @@ impl<'a> AccessSpec<'a> {
     }
 }
 
+/// Returns the name offsets of the variants of an enum type, or `None` if `ty`
+/// is not an enum.
+fn enum_variant_names(ty: &BtfType) -> Option<Vec<u32>> {
+    match ty {
+        BtfType::Enum(_, members) => Some(members.iter().map(|m| m.name_off).collect()),
+        BtfType::Enum64(_, members) => Some(members.iter().map(|m| m.name_off).collect()),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 struct Accessor {
     type_id: u32,
@@ -726,14 +738,43 @@ struct Candidate<'a> {
 #[derive(Debug)]
 struct ComputedRelocation {
     local: ComputedRelocationValue,
-    target: ComputedRelocationValue,
+    /// The value computed against the target BTF, or `None` if the relocation
+    /// can't be applied and the instruction must be poisoned.
+    target: Option<ComputedRelocationValue>,
 }
 
 #[derive(Debug)]
 struct ComputedRelocationValue {
-    value: u32,
+    value: u64,
     size: u32,
     type_id: Option<u32>,
+    /// Whether the original instruction is expected to encode the local value.
+    /// This is not the case for bitfields, whose layout might be computed
+    /// differently by the compiler.
+    validate: bool,
+}
+
+impl ComputedRelocationValue {
+    fn new(value: u64) -> ComputedRelocationValue {
+        ComputedRelocationValue {
+            value,
+            size: 0,
+            type_id: None,
+            validate: true,
+        }
+    }
+}
+
+// instructions that can't be relocated are turned into calls to this invalid
+// helper, so that the verifier only rejects the program if they're reachable
+const POISON_CALL_IMM: i32 = 0xbad2310;
+
+fn poison_insn(ins: &mut bpf_insn) {
+    ins.code = (BPF_JMP | BPF_CALL) as u8;
+    ins.set_dst_reg(0);
+    ins.set_src_reg(0);
+    ins.off = 0;
+    ins.imm = POISON_CALL_IMM;
 }
 
 impl ComputedRelocation {
@@ -746,15 +787,15 @@ impl ComputedRelocation {
         let ret = match rel.kind {
             FieldByteOffset | FieldByteSize | FieldExists | FieldSigned | FieldLShift64
             | FieldRShift64 => ComputedRelocation {
-                local: Self::compute_field_relocation(rel, Some(local_spec))?,
+                local: Self::compute_field_relocation(rel, Some(local_spec))?.unwrap(),
                 target: Self::compute_field_relocation(rel, target_spec)?,
             },
             TypeIdLocal | TypeIdTarget | TypeExists | TypeSize => ComputedRelocation {
-                local: Self::compute_type_relocation(rel, local_spec, target_spec)?,
+                local: Self::compute_type_relocation(rel, local_spec, Some(local_spec))?.unwrap(),
                 target: Self::compute_type_relocation(rel, local_spec, target_spec)?,
             },
             EnumVariantExists | EnumVariantValue => ComputedRelocation {
-                local: Self::compute_enum_relocation(rel, Some(local_spec))?,
+                local: Self::compute_enum_relocation(rel, Some(local_spec))?.unwrap(),
                 target: Self::compute_enum_relocation(rel, target_spec)?,
             },
         };
@@ -772,7 +813,7 @@ impl ComputedRelocation {
         let instructions = &mut program.function.instructions;
         let num_instructions = instructions.len();
         let ins_index = rel.ins_offset as usize / std::mem::size_of::<bpf_insn>();
-        let mut ins =
+        let ins =
             instructions
                 .get_mut(ins_index)
                 .ok_or(RelocationError::InvalidInstructionIndex {
@@ -783,7 +824,29 @@ impl ComputedRelocation {
 
         let class = (ins.code & 0x07) as u32;
 
-        let target_value = self.target.value;
+        let target = match &self.target {
+            Some(target) => target,
+            None => {
+                if class == BPF_LD {
+                    // poison the second half of the ldimm64 too, so that the verifier
+                    // doesn't complain about an unknown opcode
+                    if let Some(next_ins) = instructions.get_mut(ins_index + 1) {
+                        poison_insn(next_ins);
+                    }
+                }
+                poison_insn(&mut instructions[ins_index]);
+                return Ok(());
+            }
+        };
+        let target_value = target.value;
+        let invalid_local_value = |value: i64| RelocationError::InvalidInstruction {
+            relocation_number: rel.number,
+            index: ins_index,
+            error: format!(
+                "unexpected value `{}`, expected `{}`",
+                value, self.local.value
+            ),
+        };
 
         match class {
             BPF_ALU | BPF_ALU64 => {
@@ -797,10 +860,18 @@ impl ComputedRelocation {
                     .into());
                 }
 
+                if self.local.validate && ins.imm as u32 as u64 != self.local.value as u32 as u64 {
+                    return Err(invalid_local_value(ins.imm as i64).into());
+                }
+
                 ins.imm = target_value as i32;
             }
             BPF_LDX | BPF_ST | BPF_STX => {
-                if target_value > std::i16::MAX as u32 {
+                if self.local.validate && ins.off as i64 as u64 != self.local.value {
+                    return Err(invalid_local_value(ins.off as i64).into());
+                }
+
+                if target_value > std::i16::MAX as u64 {
                     return Err(RelocationError::InvalidInstruction {
                         relocation_number: rel.number,
                         index: ins_index,
@@ -809,11 +880,9 @@ impl ComputedRelocation {
                     .into());
                 }
 
-                ins.off = target_value as i16;
-
-                if self.local.size != self.target.size {
+                if self.local.size != target.size {
                     let local_ty = local_btf.type_by_id(self.local.type_id.unwrap())?;
-                    let target_ty = target_btf.type_by_id(self.target.type_id.unwrap())?;
+                    let target_ty = target_btf.type_by_id(target.type_id.unwrap())?;
                     let unsigned = |info: u32| ((info >> 24) & 0x0F) & BTF_INT_SIGNED == 0;
                     use BtfType::*;
                     match (local_ty, target_ty) {
@@ -821,22 +890,14 @@ impl ComputedRelocation {
                         (Int(_, local_info), Int(_, target_info))
                             if unsigned(*local_info) && unsigned(*target_info) => {}
                         _ => {
-                            return Err(RelocationError::InvalidInstruction {
-                                relocation_number: rel.number,
-                                index: ins_index,
-                                error: format!(
-                                    "original type {} has size {} but target type {} has size {}",
-                                    err_type_name(&local_btf.err_type_name(local_ty)),
-                                    self.local.size,
-                                    err_type_name(&target_btf.err_type_name(target_ty)),
-                                    self.target.size,
-                                ),
-                            }
-                            .into())
+                            // the memory access can't be resized safely, fail only if the
+                            // instruction is reachable
+                            poison_insn(ins);
+                            return Ok(());
                         }
                     }
 
-                    let size = match self.target.size {
+                    let size = match target.size {
                         8 => BPF_DW,
                         4 => BPF_W,
                         2 => BPF_H,
@@ -852,18 +913,25 @@ impl ComputedRelocation {
                     } as u8;
                     ins.code = ins.code & 0xE0 | size | ins.code & 0x07;
                 }
+
+                ins.off = target_value as i16;
             }
             BPF_LD => {
-                ins.imm = target_value as i32;
-                let mut next_ins = instructions.get_mut(ins_index + 1).ok_or(
+                let imm = ins.imm;
+                let next_ins = instructions.get_mut(ins_index + 1).ok_or(
                     RelocationError::InvalidInstructionIndex {
                         index: ins_index + 1,
                         num_instructions,
                         relocation_number: rel.number,
                     },
                 )?;
+                let value = (imm as u32 as u64) | ((next_ins.imm as u32 as u64) << 32);
+                if self.local.validate && value != self.local.value {
+                    return Err(invalid_local_value(value as i64).into());
+                }
 
-                next_ins.imm = 0;
+                next_ins.imm = (target_value >> 32) as i32;
+                instructions[ins_index].imm = target_value as i32;
             }
             class => {
                 return Err(RelocationError::InvalidInstruction {
@@ -881,60 +949,64 @@ impl ComputedRelocation {
     fn compute_enum_relocation(
         rel: &Relocation,
         spec: Option<&AccessSpec>,
-    ) -> Result<ComputedRelocationValue, ErrorWrapper> {
+    ) -> Result<Option<ComputedRelocationValue>, ErrorWrapper> {
         use RelocationKind::*;
-        let value = match rel.kind {
-            EnumVariantExists => spec.is_some() as u32,
-            EnumVariantValue => {
-                let spec = spec.unwrap();
+        let value = match (rel.kind, spec) {
+            (EnumVariantExists, spec) => spec.is_some() as u64,
+            (EnumVariantValue, Some(spec)) => {
                 let accessor = &spec.accessors[0];
                 match spec.btf.type_by_id(accessor.type_id)? {
-                    BtfType::Enum(_, variants) => variants[accessor.index].val as u32,
+                    // values are sign extended like libbpf does
+                    BtfType::Enum(_, variants) => variants[accessor.index].val as i64 as u64,
+                    BtfType::Enum64(_, variants) => {
+                        let variant = &variants[accessor.index];
+                        (variant.val_hi32 as u64) << 32 | variant.val_lo32 as u64
+                    }
                     _ => panic!("should not be reached"),
                 }
             }
+            // the variant doesn't exist in the target, poison the instruction
+            (EnumVariantValue, None) => return Ok(None),
             // this function is only called for enum relocations
             _ => panic!("should not be reached"),
         };
 
-        Ok(ComputedRelocationValue {
-            value,
-            size: 0,
-            type_id: None,
-        })
+        Ok(Some(ComputedRelocationValue::new(value)))
     }
 
     fn compute_field_relocation(
         rel: &Relocation,
         spec: Option<&AccessSpec>,
-    ) -> Result<ComputedRelocationValue, ErrorWrapper> {
+    ) -> Result<Option<ComputedRelocationValue>, ErrorWrapper> {
         use RelocationKind::*;
 
         if let FieldExists = rel.kind {
             // this is the bpf_preserve_field_info(member_access, FIELD_EXISTENCE) case. If we
             // managed to build a spec, it means the field exists.
-            return Ok(ComputedRelocationValue {
-                value: spec.is_some() as u32,
-                size: 0,
-                type_id: None,
-            });
+            return Ok(Some(ComputedRelocationValue::new(spec.is_some() as u64)));
         }
 
-        let spec = spec.unwrap();
+        let spec = match spec {
+            Some(spec) => spec,
+            // the field doesn't exist in the target, poison the instruction
+            None => return Ok(None),
+        };
         let accessor = spec.accessors.last().unwrap();
         if accessor.name.is_none() {
             // the last accessor is unnamed, meaning that this is an array access
             return match rel.kind {
-                FieldByteOffset => Ok(ComputedRelocationValue {
-                    value: (spec.bit_offset / 8) as u32,
+                FieldByteOffset => Ok(Some(ComputedRelocationValue {
+                    value: (spec.bit_offset / 8) as u64,
                     size: spec.btf.type_size(accessor.type_id)? as u32,
                     type_id: Some(accessor.type_id),
-                }),
-                FieldByteSize => Ok(ComputedRelocationValue {
-                    value: spec.btf.type_size(accessor.type_id)? as u32,
+                    validate: true,
+                })),
+                FieldByteSize => Ok(Some(ComputedRelocationValue {
+                    value: spec.btf.type_size(accessor.type_id)? as u64,
                     size: 0,
                     type_id: Some(accessor.type_id),
-                }),
+                    validate: true,
+                })),
                 rel_kind => {
                     let ty = spec.btf.type_by_id(accessor.type_id)?;
                     return Err(RelocationError::InvalidRelocationKindForType {
@@ -991,73 +1063,80 @@ impl ComputedRelocation {
             byte_off = spec.bit_offset as u32 / 8;
         }
 
+        // for bitfields the compiler might compute a different (but equally valid)
+        // load size and offset, so the original values can't be validated
         let mut value = ComputedRelocationValue {
             value: 0,
             size: 0,
             type_id: None,
+            validate: !is_bitfield,
         };
 
         #[allow(clippy::wildcard_in_or_patterns)]
         match rel.kind {
             FieldByteOffset => {
-                value.value = byte_off;
+                value.value = byte_off as u64;
                 if !is_bitfield {
                     value.size = byte_size;
                     value.type_id = Some(member_type_id);
                 }
             }
             FieldByteSize => {
-                value.value = byte_size;
+                value.value = byte_size as u64;
+            }
+            FieldSigned => {
+                value.value = match member_ty {
+                    BtfType::Enum(ty, _) | BtfType::Enum64(ty, _) => (ty.info >> 31) as u64,
+                    BtfType::Int(_, i) => (((i >> 24) & 0x0F) & BTF_INT_SIGNED) as u64,
+                    _ => 0,
+                };
+                // signedness is never ambiguous
+                value.validate = true;
             }
-            FieldSigned => match member_ty {
-                BtfType::Enum(_, _) => value.value = 1,
-                BtfType::Int(_, i) => value.value = ((i >> 24) & 0x0F) & BTF_INT_SIGNED,
-                _ => (),
-            },
             #[cfg(target_endian = "little")]
             FieldLShift64 => {
-                value.value = 64 - (bit_off + bit_size - byte_off * 8);
+                value.value = (64 - (bit_off + bit_size - byte_off * 8)) as u64;
             }
             #[cfg(target_endian = "big")]
             FieldLShift64 => {
-                value.value = (8 - byte_size) * 8 + (bit_off - byte_off * 8);
+                value.value = ((8 - byte_size) * 8 + (bit_off - byte_off * 8)) as u64;
             }
             FieldRShift64 => {
-                value.value = 64 - bit_size;
+                value.value = (64 - bit_size) as u64;
+                // the right shift only depends on the size of the bitfield
+                value.validate = true;
             }
             FieldExists // this is handled at the start of the function
             | _ => panic!("bug! this should not be reached"),
         }
 
-        Ok(value)
+        Ok(Some(value))
     }
 
     fn compute_type_relocation(
         rel: &Relocation,
         local_spec: &AccessSpec,
         target_spec: Option<&AccessSpec>,
-    ) -> Result<ComputedRelocationValue, ErrorWrapper> {
+    ) -> Result<Option<ComputedRelocationValue>, ErrorWrapper> {
         use RelocationKind::*;
-        let value = match rel.kind {
-            TypeIdLocal => local_spec.root_type_id,
-            _ => match target_spec {
-                Some(target_spec) => match rel.kind {
-                    TypeIdTarget => target_spec.root_type_id,
-                    TypeExists => 1,
-                    TypeSize => target_spec.btf.type_size(target_spec.root_type_id)? as u32,
-                    _ => panic!("bug! this should not be reached"),
-                },
-                // FIXME in the case of TypeIdTarget and TypeSize this should probably fail the
-                // relocation...
-                None => 0,
-            },
+        let value = match (rel.kind, target_spec) {
+            (TypeIdLocal, _) => local_spec.root_type_id as u64,
+            (TypeIdTarget, Some(target_spec)) => target_spec.root_type_id as u64,
+            (TypeExists, target_spec) => target_spec.is_some() as u64,
+            (TypeSize, Some(target_spec)) => {
+                target_spec.btf.type_size(target_spec.root_type_id)? as u64
+            }
+            // the type doesn't exist in the target, poison the instruction
+            (TypeIdTarget, None) | (TypeSize, None) => return Ok(None),
+            _ => panic!("bug! this should not be reached"),
         };
 
-        Ok(ComputedRelocationValue {
-            value,
-            size: 0,
-            type_id: None,
-        })
+        // type ids are not validated as the compiler and the kernel might number
+        // types differently
+        Ok(Some(ComputedRelocationValue {
+            validate: false,
+            ..ComputedRelocationValue::new(value)
+        }))
     }
 }
 
@@ -1071,3 +1150,130 @@ enum ErrorWrapper {
     #[error(transparent)]
     RelocationError(#[from] RelocationError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generated::{btf_member, btf_type},
+        obj::btf::{type_info, BtfKind},
+    };
+
+    // builds `struct foo` with the given (name, bitfield size, bit offset) members, all ints
+    fn btf_with_struct(size: u32, members: &[(&str, u32, u32)]) -> Btf {
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+        let members = members
+            .iter()
+            .map(|(name, bitfield_size, bit_offset)| btf_member {
+                name_off: btf.add_string(name),
+                type_: int_type_id,
+                offset: bitfield_size << 24 | bit_offset,
+            })
+            .collect::<Vec<_>>();
+
+        let mut ty = unsafe { mem::zeroed::<btf_type>() };
+        ty.name_off = btf.add_string("foo");
+        ty.info = type_info(BtfKind::Struct, members.len()) | 1 << 31;
+        ty.__bindgen_anon_1.size = size;
+        btf.add_type(BtfType::Struct(ty, members));
+        btf
+    }
+
+    fn compute(
+        kind: RelocationKind,
+        access_str: &str,
+        local_btf: &Btf,
+        target_btf: &Btf,
+    ) -> ComputedRelocation {
+        let rel = Relocation {
+            kind,
+            ins_offset: 0,
+            type_id: 2,
+            access_str_offset: 0,
+            number: 0,
+        };
+        let local_spec = AccessSpec::new(local_btf, 2, access_str, rel).unwrap();
+        let local_ty = local_btf.type_by_id(2).unwrap();
+        let candidates = find_candidates(local_ty, "foo", target_btf).unwrap();
+        assert_eq!(candidates.len(), 1);
+        let target_spec = match_candidate(&local_spec, &candidates[0]).unwrap();
+        ComputedRelocation::new(&rel, &local_spec, target_spec.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn test_bitfield_relocation() {
+        let local_btf = btf_with_struct(8, &[("a", 3, 0), ("b", 5, 3), ("c", 0, 32)]);
+        let target_btf =
+            btf_with_struct(12, &[("x", 0, 0), ("a", 3, 32), ("b", 5, 35), ("c", 0, 64)]);
+
+        let rel = compute(
+            RelocationKind::FieldByteOffset,
+            "0:1",
+            &local_btf,
+            &target_btf,
+        );
+        assert_eq!(rel.local.value, 0);
+        assert!(!rel.local.validate);
+        assert_eq!(rel.target.unwrap().value, 4);
+
+        let rel = compute(
+            RelocationKind::FieldByteSize,
+            "0:1",
+            &local_btf,
+            &target_btf,
+        );
+        assert_eq!(rel.target.unwrap().value, 4);
+
+        let rel = compute(
+            RelocationKind::FieldLShift64,
+            "0:1",
+            &local_btf,
+            &target_btf,
+        );
+        assert_eq!(rel.local.value, 56);
+        assert_eq!(rel.target.unwrap().value, 56);
+
+        let rel = compute(
+            RelocationKind::FieldRShift64,
+            "0:1",
+            &local_btf,
+            &target_btf,
+        );
+        assert!(rel.local.validate);
+        assert_eq!(rel.target.unwrap().value, 59);
+
+        let rel = compute(RelocationKind::FieldSigned, "0:1", &local_btf, &target_btf);
+        assert_eq!(rel.target.unwrap().value, 1);
+
+        let rel = compute(
+            RelocationKind::FieldByteOffset,
+            "0:2",
+            &local_btf,
+            &target_btf,
+        );
+        assert_eq!(rel.local.value, 4);
+        assert!(rel.local.validate);
+        assert_eq!(rel.target.unwrap().value, 8);
+    }
+
+    #[test]
+    fn test_missing_field_relocation() {
+        let local_btf = btf_with_struct(8, &[("a", 0, 0), ("d", 0, 32)]);
+        let target_btf = btf_with_struct(4, &[("a", 0, 0)]);
+
+        let rel = compute(RelocationKind::FieldExists, "0:1", &local_btf, &target_btf);
+        assert_eq!(rel.local.value, 1);
+        assert_eq!(rel.target.unwrap().value, 0);
+
+        // the instruction gets poisoned
+        let rel = compute(
+            RelocationKind::FieldByteOffset,
+            "0:1",
+            &local_btf,
+            &target_btf,
+        );
+        assert!(rel.target.is_none());
+    }
+}
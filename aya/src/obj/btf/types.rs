@@ -7,10 +7,11 @@ use object::Endianness;
 
 use crate::{
     generated::{
-        btf_array, btf_enum, btf_member, btf_param, btf_type, btf_type__bindgen_ty_1, btf_var,
-        btf_var_secinfo, BTF_KIND_ARRAY, BTF_KIND_CONST, BTF_KIND_DATASEC, BTF_KIND_ENUM,
-        BTF_KIND_FLOAT, BTF_KIND_FUNC, BTF_KIND_FUNC_PROTO, BTF_KIND_FWD, BTF_KIND_INT,
-        BTF_KIND_PTR, BTF_KIND_RESTRICT, BTF_KIND_STRUCT, BTF_KIND_TYPEDEF, BTF_KIND_UNION,
+        btf_array, btf_decl_tag, btf_enum, btf_enum64, btf_member, btf_param, btf_type,
+        btf_type__bindgen_ty_1, btf_var, btf_var_secinfo, BTF_KIND_ARRAY, BTF_KIND_CONST,
+        BTF_KIND_DATASEC, BTF_KIND_DECL_TAG, BTF_KIND_ENUM, BTF_KIND_ENUM64, BTF_KIND_FLOAT,
+        BTF_KIND_FUNC, BTF_KIND_FUNC_PROTO, BTF_KIND_FWD, BTF_KIND_INT, BTF_KIND_PTR,
+        BTF_KIND_RESTRICT, BTF_KIND_STRUCT, BTF_KIND_TYPEDEF, BTF_KIND_TYPE_TAG, BTF_KIND_UNION,
         BTF_KIND_UNKN, BTF_KIND_VAR, BTF_KIND_VOLATILE,
     },
     obj::btf::{Btf, BtfError, MAX_RESOLVE_DEPTH},
@@ -35,6 +36,9 @@ pub(crate) enum BtfType {
     FuncProto(btf_type, Vec<btf_param>),
     Var(btf_type, btf_var),
     DataSec(btf_type, Vec<btf_var_secinfo>),
+    DeclTag(btf_type, btf_decl_tag),
+    TypeTag(btf_type),
+    Enum64(btf_type, Vec<btf_enum64>),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -57,6 +61,9 @@ pub(crate) enum BtfKind {
     FuncProto = BTF_KIND_FUNC_PROTO,
     Var = BTF_KIND_VAR,
     DataSec = BTF_KIND_DATASEC,
+    DeclTag = BTF_KIND_DECL_TAG,
+    TypeTag = BTF_KIND_TYPE_TAG,
+    Enum64 = BTF_KIND_ENUM64,
 }
 
 impl TryFrom<u32> for BtfKind {
@@ -82,6 +89,9 @@ impl TryFrom<u32> for BtfKind {
             BTF_KIND_FUNC_PROTO => FuncProto,
             BTF_KIND_VAR => Var,
             BTF_KIND_DATASEC => DataSec,
+            BTF_KIND_DECL_TAG => DeclTag,
+            BTF_KIND_TYPE_TAG => TypeTag,
+            BTF_KIND_ENUM64 => Enum64,
             kind => return Err(BtfError::InvalidTypeKind { kind }),
         })
     }
@@ -146,9 +156,145 @@ impl BtfType {
             BtfKind::FuncProto => FuncProto(ty, unsafe { read_array(data, vlen)? }),
             BtfKind::Var => Var(ty, unsafe { read(data)? }),
             BtfKind::DataSec => DataSec(ty, unsafe { read_array(data, vlen)? }),
+            BtfKind::DeclTag => DeclTag(ty, unsafe { read(data)? }),
+            BtfKind::TypeTag => TypeTag(ty),
+            BtfKind::Enum64 => Enum64(ty, unsafe { read_array(data, vlen)? }),
         })
     }
 
+    pub(crate) fn new_int(name_off: u32, size: u32, encoding: u32, offset: u32) -> BtfType {
+        let info = (encoding << 24) | ((offset & 0xFF) << 16) | ((size * 8) & 0xFF);
+        BtfType::Int(new_btf_type(name_off, BtfKind::Int, 0, size), info)
+    }
+
+    pub(crate) fn new_float(name_off: u32, size: u32) -> BtfType {
+        BtfType::Float(new_btf_type(name_off, BtfKind::Float, 0, size))
+    }
+
+    pub(crate) fn new_ptr(type_: u32) -> BtfType {
+        BtfType::Ptr(new_btf_type(0, BtfKind::Ptr, 0, type_))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_array(type_: u32, index_type: u32, nelems: u32) -> BtfType {
+        BtfType::Array(
+            new_btf_type(0, BtfKind::Array, 0, 0),
+            btf_array {
+                type_,
+                index_type,
+                nelems,
+            },
+        )
+    }
+
+    pub(crate) fn new_struct(name_off: u32, members: Vec<btf_member>, size: u32) -> BtfType {
+        BtfType::Struct(
+            new_btf_type(name_off, BtfKind::Struct, members.len(), size),
+            members,
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_typedef(name_off: u32, type_: u32) -> BtfType {
+        BtfType::Typedef(new_btf_type(name_off, BtfKind::Typedef, 0, type_))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_enum(name_off: u32, values: Vec<btf_enum>) -> BtfType {
+        BtfType::Enum(
+            new_btf_type(name_off, BtfKind::Enum, values.len(), 4),
+            values,
+        )
+    }
+
+    pub(crate) fn new_func_proto(params: Vec<btf_param>, return_type: u32) -> BtfType {
+        BtfType::FuncProto(
+            new_btf_type(0, BtfKind::FuncProto, params.len(), return_type),
+            params,
+        )
+    }
+
+    pub(crate) fn new_func(name_off: u32, proto: u32, linkage: u32) -> BtfType {
+        // the linkage of functions is stored in vlen
+        BtfType::Func(new_btf_type(
+            name_off,
+            BtfKind::Func,
+            linkage as usize,
+            proto,
+        ))
+    }
+
+    pub(crate) fn new_datasec(
+        name_off: u32,
+        variables: Vec<btf_var_secinfo>,
+        size: u32,
+    ) -> BtfType {
+        BtfType::DataSec(
+            new_btf_type(name_off, BtfKind::DataSec, variables.len(), size),
+            variables,
+        )
+    }
+
+    pub(crate) fn new_var(name_off: u32, type_: u32, linkage: u32) -> BtfType {
+        BtfType::Var(
+            new_btf_type(name_off, BtfKind::Var, 0, type_),
+            btf_var { linkage },
+        )
+    }
+
+    pub(crate) fn new_decl_tag(name_off: u32, type_: u32, component_idx: i32) -> BtfType {
+        BtfType::DeclTag(
+            new_btf_type(name_off, BtfKind::DeclTag, 0, type_),
+            btf_decl_tag { component_idx },
+        )
+    }
+
+    pub(crate) fn new_type_tag(name_off: u32, type_: u32) -> BtfType {
+        BtfType::TypeTag(new_btf_type(name_off, BtfKind::TypeTag, 0, type_))
+    }
+
+    pub(crate) fn new_enum64(name_off: u32, size: u32, enums: Vec<btf_enum64>) -> BtfType {
+        BtfType::Enum64(
+            new_btf_type(name_off, BtfKind::Enum64, enums.len(), size),
+            enums,
+        )
+    }
+
+    /// Returns the binary representation of the type, as found in the `.BTF`
+    /// section.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        fn bytes_of<T>(val: &T) -> &[u8] {
+            // Safety: all the BTF types are POD
+            unsafe { std::slice::from_raw_parts(val as *const T as *const u8, mem::size_of::<T>()) }
+        }
+        fn bytes_of_slice<T>(vals: &[T]) -> Vec<u8> {
+            vals.iter().flat_map(|v| bytes_of(v).to_vec()).collect()
+        }
+
+        let ty = match self.btf_type() {
+            Some(ty) => ty,
+            None => return Vec::new(),
+        };
+        let mut buf = bytes_of(ty).to_vec();
+
+        use BtfType::*;
+        match self {
+            Unknown | Fwd(_) | Const(_) | Volatile(_) | Restrict(_) | Ptr(_) | Typedef(_)
+            | Func(_) | Float(_) | TypeTag(_) => {}
+            Int(_, int_info) => buf.extend_from_slice(bytes_of(int_info)),
+            Enum(_, enums) => buf.extend(bytes_of_slice(enums)),
+            Array(_, array) => buf.extend_from_slice(bytes_of(array)),
+            Struct(_, members) | Union(_, members) => buf.extend(bytes_of_slice(members)),
+            FuncProto(_, params) => buf.extend(bytes_of_slice(params)),
+            Var(_, var) => buf.extend_from_slice(bytes_of(var)),
+            DataSec(_, secinfo) => buf.extend(bytes_of_slice(secinfo)),
+            DeclTag(_, decl_tag) => buf.extend_from_slice(bytes_of(decl_tag)),
+            Enum64(_, enums) => buf.extend(bytes_of_slice(enums)),
+        }
+
+        buf
+    }
+
     pub(crate) fn type_info_size(&self) -> usize {
         let ty_size = mem::size_of::<btf_type>();
 
@@ -156,7 +302,7 @@ impl BtfType {
         match self {
             Unknown => ty_size,
             Fwd(_) | Const(_) | Volatile(_) | Restrict(_) | Ptr(_) | Typedef(_) | Func(_)
-            | Float(_) => ty_size,
+            | Float(_) | TypeTag(_) => ty_size,
             Int(_, _) => ty_size + mem::size_of::<u32>(),
             Enum(ty, _) => ty_size + type_vlen(ty) * mem::size_of::<btf_enum>(),
             Array(_, _) => ty_size + mem::size_of::<btf_array>(),
@@ -165,6 +311,8 @@ impl BtfType {
             FuncProto(ty, _) => ty_size + type_vlen(ty) * mem::size_of::<btf_param>(),
             Var(_, _) => ty_size + mem::size_of::<btf_var>(),
             DataSec(ty, _) => ty_size + type_vlen(ty) * mem::size_of::<btf_var_secinfo>(),
+            DeclTag(_, _) => ty_size + mem::size_of::<btf_decl_tag>(),
+            Enum64(ty, _) => ty_size + type_vlen(ty) * mem::size_of::<btf_enum64>(),
         }
     }
 
@@ -188,6 +336,9 @@ impl BtfType {
             FuncProto(ty, _) => ty,
             Var(ty, _) => ty,
             DataSec(ty, _) => ty,
+            DeclTag(ty, _) => ty,
+            TypeTag(ty) => ty,
+            Enum64(ty, _) => ty,
         })
     }
 
@@ -208,11 +359,40 @@ impl BtfType {
     }
 }
 
+/// Returns whether `a` and `b` have the same kind for the purpose of CO-RE
+/// relocations, which don't distinguish 32 and 64 bit enums.
+pub(crate) fn kinds_match(a: &BtfType, b: &BtfType) -> Result<bool, BtfError> {
+    let kind = |ty: &BtfType| {
+        ty.kind().map(|kind| match kind {
+            Some(BtfKind::Enum64) => Some(BtfKind::Enum),
+            kind => kind,
+        })
+    };
+    Ok(kind(a)? == kind(b)?)
+}
+
+// see enum btf_func_linkage in include/uapi/linux/btf.h
+pub(crate) const BTF_FUNC_STATIC: u32 = 0;
+pub(crate) const BTF_FUNC_GLOBAL: u32 = 1;
+
+pub(crate) fn type_info(kind: BtfKind, vlen: usize) -> u32 {
+    ((kind as u32) << 24) | (vlen as u32 & 0xFFFF)
+}
+
+fn new_btf_type(name_off: u32, kind: BtfKind, vlen: usize, size_or_type: u32) -> btf_type {
+    let mut ty = unsafe { mem::zeroed::<btf_type>() };
+    ty.name_off = name_off;
+    ty.info = type_info(kind, vlen);
+    // Safety: union, size and type_ have the same layout
+    ty.__bindgen_anon_1.size = size_or_type;
+    ty
+}
+
 fn type_kind(ty: &btf_type) -> Result<BtfKind, BtfError> {
     ((ty.info >> 24) & 0x1F).try_into()
 }
 
-fn type_vlen(ty: &btf_type) -> usize {
+pub(crate) fn type_vlen(ty: &btf_type) -> usize {
     (ty.info & 0xFFFF) as usize
 }
 
@@ -245,7 +425,7 @@ pub(crate) fn types_are_compatible(
     let local_ty = local_btf.type_by_id(local_id)?;
     let target_ty = target_btf.type_by_id(target_id)?;
 
-    if local_ty.kind()? != target_ty.kind()? {
+    if !kinds_match(local_ty, target_ty)? {
         return Ok(false);
     }
 
@@ -255,18 +435,24 @@ pub(crate) fn types_are_compatible(
         let local_ty = local_btf.type_by_id(local_id)?;
         let target_ty = target_btf.type_by_id(target_id)?;
 
-        if local_ty.kind()? != target_ty.kind()? {
+        if !kinds_match(local_ty, target_ty)? {
             return Ok(false);
         }
 
         use BtfType::*;
         match local_ty {
-            Unknown | Struct(_, _) | Union(_, _) | Enum(_, _) | Fwd(_) | Float(_) => {
-                return Ok(true)
-            }
-            Int(_, local_off) => {
-                if let Int(_, target_off) = target_ty {
-                    return Ok(*local_off == 0 && *target_off == 0);
+            Unknown
+            | Struct(_, _)
+            | Union(_, _)
+            | Enum(_, _)
+            | Enum64(_, _)
+            | Fwd(_)
+            | Float(_) => return Ok(true),
+            Int(_, local_info) => {
+                if let Int(_, target_info) = target_ty {
+                    // the bit offset is stored in bits 16-23 of the int info
+                    let offset = |info: u32| (info >> 16) & 0xff;
+                    return Ok(offset(*local_info) == 0 && offset(*target_info) == 0);
                 }
             }
             Ptr(l_ty) => {
@@ -334,13 +520,13 @@ pub(crate) fn fields_are_compatible(
             return Ok(true);
         }
 
-        if local_ty.kind()? != target_ty.kind()? {
+        if !kinds_match(local_ty, target_ty)? {
             return Ok(false);
         }
 
         use BtfType::*;
         match local_ty {
-            Fwd(_) | Enum(_, _) => {
+            Fwd(_) | Enum(_, _) | Enum64(_, _) => {
                 let flavorless_name =
                     |name: &str| name.split_once("___").map_or(name, |x| x.0).to_string();
 
@@ -619,4 +805,55 @@ mod tests {
             Err(_) => panic!("unexpected error"),
         }
     }
+
+    #[test]
+    fn test_read_btf_type_decl_tag() {
+        let endianness = Endianness::default();
+        let data: &[u8] = &[
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x02, 0x00, 0x00, 0x00, 0xff, 0xff,
+            0xff, 0xff,
+        ];
+        match unsafe { BtfType::read(data, endianness) } {
+            Ok(ty @ BtfType::DeclTag(_, btf_decl_tag { component_idx: -1 })) => {
+                assert_eq!(ty.type_info_size(), data.len());
+                assert_eq!(ty.to_bytes(), data);
+            }
+            Ok(t) => panic!("expected decl_tag type, got {:#?}", t),
+            Err(_) => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn test_read_btf_type_type_tag() {
+        let endianness = Endianness::default();
+        let data: &[u8] = &[
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x01, 0x00, 0x00, 0x00,
+        ];
+        match unsafe { BtfType::read(data, endianness) } {
+            Ok(ty @ BtfType::TypeTag(_)) => {
+                assert_eq!(ty.type_info_size(), data.len());
+                assert_eq!(ty.to_bytes(), data);
+            }
+            Ok(t) => panic!("expected type_tag type, got {:#?}", t),
+            Err(_) => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn test_read_btf_type_enum64() {
+        let endianness = Endianness::default();
+        let data: &[u8] = &[
+            0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x13, 0x08, 0x00, 0x00, 0x00, 0x05, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        ];
+        match unsafe { BtfType::read(data, endianness) } {
+            Ok(BtfType::Enum64(_, enums)) => {
+                assert_eq!(enums.len(), 1);
+                assert_eq!(enums[0].val_lo32, 2);
+                assert_eq!(enums[0].val_hi32, 1);
+            }
+            Ok(t) => panic!("expected enum64 type, got {:#?}", t),
+            Err(_) => panic!("unexpected error"),
+        }
+    }
 }
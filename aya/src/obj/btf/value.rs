@@ -0,0 +1,466 @@
+use std::{convert::TryInto, fmt};
+
+use crate::obj::btf::{
+    member_bit_field_size, member_bit_offset, Btf, BtfError, BtfType, MAX_RESOLVE_DEPTH,
+};
+
+// see BTF_INT_SIGNED, BTF_INT_CHAR and BTF_INT_BOOL in include/uapi/linux/btf.h
+const BTF_INT_SIGNED: u32 = 1 << 0;
+const BTF_INT_BOOL: u32 = 1 << 2;
+
+/// A value decoded using its BTF type information.
+///
+/// Values are decoded from the raw bytes of map keys and values with
+/// [`Map::decode_key`](crate::maps::Map::decode_key) and
+/// [`Map::decode_value`](crate::maps::Map::decode_value). The [`Display`](fmt::Display)
+/// implementation renders values like `bpftool map dump`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BtfValue {
+    /// A signed integer or character.
+    Int(i64),
+    /// An unsigned integer or character.
+    Uint(u64),
+    /// A boolean.
+    Bool(bool),
+    /// A floating point number.
+    Float(f64),
+    /// A pointer, decoded as an address.
+    Ptr(u64),
+    /// An enum value, along with the name of the matching variant if any.
+    Enum {
+        /// The name of the variant.
+        name: Option<String>,
+        /// The numeric value.
+        value: i64,
+    },
+    /// An array.
+    Array(Vec<BtfValue>),
+    /// A struct or union, along with the name of each field. The fields of a
+    /// union all decode the same bytes. Data sections, like the values of
+    /// global data maps, decode as a struct of their variables.
+    Struct(Vec<(String, BtfValue)>),
+    /// A value that can't be decoded, like a 128 bit integer.
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for BtfValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BtfValue::Int(v) => write!(f, "{}", v),
+            BtfValue::Uint(v) => write!(f, "{}", v),
+            BtfValue::Bool(v) => write!(f, "{}", v),
+            BtfValue::Float(v) => write!(f, "{}", v),
+            BtfValue::Ptr(v) => write!(f, "{:#x}", v),
+            BtfValue::Enum {
+                name: Some(name), ..
+            } => write!(f, "{}", name),
+            BtfValue::Enum { name: None, value } => write!(f, "{}", value),
+            BtfValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            BtfValue::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    if name.is_empty() {
+                        write!(f, " {}", value)?;
+                    } else {
+                        write!(f, " {} = {}", name, value)?;
+                    }
+                }
+                write!(f, " }}")
+            }
+            BtfValue::Bytes(bytes) => {
+                write!(f, "0x")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Btf {
+    /// Decodes `data` as a value of type `type_id`.
+    ///
+    /// The data is expected to be in the byte order of the host, as found in
+    /// maps.
+    pub(crate) fn decode_value(&self, type_id: u32, data: &[u8]) -> Result<BtfValue, BtfError> {
+        let size = self.type_size(type_id)?;
+        if data.len() != size {
+            return Err(BtfError::InvalidValueSize {
+                type_id,
+                size: data.len(),
+                expected: size,
+            });
+        }
+
+        self.decode(type_id, data, 0)
+    }
+
+    fn decode(&self, type_id: u32, data: &[u8], depth: u8) -> Result<BtfValue, BtfError> {
+        if depth > MAX_RESOLVE_DEPTH {
+            return Err(BtfError::MaximumTypeDepthReached { type_id });
+        }
+
+        let type_id = self.resolve_type(type_id)?;
+        let ty = self.type_by_id(type_id)?;
+        let size = self.type_size(type_id)?;
+        let data = data.get(..size).ok_or(BtfError::InvalidValueSize {
+            type_id,
+            size: data.len(),
+            expected: size,
+        })?;
+
+        use BtfType::*;
+        Ok(match ty {
+            Int(_, info) => {
+                let encoding = info >> 24;
+                let bits_offset = (info >> 16) & 0xff;
+                let bits = info & 0xff;
+                match read_uint(data) {
+                    Some(value) => {
+                        let value = extract_bits(value, bits_offset, bits);
+                        if encoding & BTF_INT_BOOL != 0 {
+                            BtfValue::Bool(value != 0)
+                        } else if encoding & BTF_INT_SIGNED != 0 {
+                            BtfValue::Int(sign_extend(value, bits))
+                        } else {
+                            BtfValue::Uint(value)
+                        }
+                    }
+                    None => BtfValue::Bytes(data.to_vec()),
+                }
+            }
+            Float(_) => match data.len() {
+                4 => BtfValue::Float(f32::from_ne_bytes(data.try_into().unwrap()) as f64),
+                8 => BtfValue::Float(f64::from_ne_bytes(data.try_into().unwrap())),
+                _ => BtfValue::Bytes(data.to_vec()),
+            },
+            Ptr(_) => BtfValue::Ptr(read_uint(data).unwrap_or_default()),
+            Enum(btf_ty, values) => {
+                let raw = read_uint(data).unwrap_or_default();
+                let value = if btf_ty.info >> 31 == 1 {
+                    sign_extend(raw, data.len() as u32 * 8)
+                } else {
+                    raw as i64
+                };
+                let name = values
+                    .iter()
+                    .find(|v| v.val as i64 == value || v.val as u32 as i64 == value)
+                    .map(|v| self.string_at(v.name_off).map(String::from))
+                    .transpose()?;
+                BtfValue::Enum { name, value }
+            }
+            Enum64(_, values) => {
+                let value = read_uint(data).unwrap_or_default();
+                let name = values
+                    .iter()
+                    .find(|v| (v.val_hi32 as u64) << 32 | v.val_lo32 as u64 == value)
+                    .map(|v| self.string_at(v.name_off).map(String::from))
+                    .transpose()?;
+                BtfValue::Enum {
+                    name,
+                    value: value as i64,
+                }
+            }
+            Array(_, array) => {
+                let elem_size = self.type_size(array.type_)?;
+                let mut values = Vec::with_capacity(array.nelems as usize);
+                for i in 0..array.nelems as usize {
+                    let elem = &data[i * elem_size..];
+                    values.push(self.decode(array.type_, elem, depth + 1)?);
+                }
+                BtfValue::Array(values)
+            }
+            Struct(btf_ty, members) | Union(btf_ty, members) => {
+                let mut fields = Vec::with_capacity(members.len());
+                for member in members {
+                    let name = self.string_at(member.name_off)?.into_owned();
+                    let bit_offset = member_bit_offset(btf_ty.info, member);
+                    let bit_size = member_bit_field_size(btf_ty, member);
+                    let value = if bit_size > 0 {
+                        self.decode_bitfield(member.type_, data, bit_offset, bit_size)?
+                    } else {
+                        let field = data.get(bit_offset / 8..).unwrap_or_default();
+                        self.decode(member.type_, field, depth + 1)?
+                    };
+                    fields.push((name, value));
+                }
+                BtfValue::Struct(fields)
+            }
+            DataSec(_, vars) => {
+                let mut fields = Vec::with_capacity(vars.len());
+                for var in vars {
+                    let (name, type_id) = match self.type_by_id(var.type_)? {
+                        // Safety: union
+                        Var(ty, _) => (self.string_at(ty.name_off)?.into_owned(), unsafe {
+                            ty.__bindgen_anon_1.type_
+                        }),
+                        _ => return Err(BtfError::UnexpectedBtfType { type_id: var.type_ }),
+                    };
+                    let field = data.get(var.offset as usize..).unwrap_or_default();
+                    fields.push((name, self.decode(type_id, field, depth + 1)?));
+                }
+                BtfValue::Struct(fields)
+            }
+            Unknown
+            | Fwd(_)
+            | Func(_)
+            | FuncProto(_, _)
+            | Var(_, _)
+            | DeclTag(_, _)
+            | Const(_)
+            | Volatile(_)
+            | Restrict(_)
+            | Typedef(_)
+            | TypeTag(_) => return Err(BtfError::UnexpectedBtfType { type_id }),
+        })
+    }
+
+    fn decode_bitfield(
+        &self,
+        type_id: u32,
+        data: &[u8],
+        bit_offset: usize,
+        bit_size: usize,
+    ) -> Result<BtfValue, BtfError> {
+        // bitfields are laid out starting from the least significant bit on
+        // little endian hosts, and from the most significant one on big endian
+        // hosts
+        let start = bit_offset / 8;
+        let end = (bit_offset + bit_size).div_ceil(8);
+        let bytes = data.get(start..end).ok_or(BtfError::InvalidValueSize {
+            type_id,
+            size: data.len(),
+            expected: end,
+        })?;
+        let raw = read_uint(bytes).ok_or(BtfError::UnexpectedBtfType { type_id })?;
+        let shift = if cfg!(target_endian = "little") {
+            bit_offset % 8
+        } else {
+            bytes.len() * 8 - bit_offset % 8 - bit_size
+        };
+        let value = extract_bits(raw, shift as u32, bit_size as u32);
+
+        let signed = match self.type_by_id(self.resolve_type(type_id)?)? {
+            BtfType::Int(_, info) => info >> 24 & BTF_INT_SIGNED != 0,
+            BtfType::Enum(ty, _) => ty.info >> 31 == 1,
+            _ => false,
+        };
+        Ok(if signed {
+            BtfValue::Int(sign_extend(value, bit_size as u32))
+        } else {
+            BtfValue::Uint(value)
+        })
+    }
+}
+
+// reads an integer of up to 8 bytes in host byte order
+fn read_uint(data: &[u8]) -> Option<u64> {
+    if data.len() > 8 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    if cfg!(target_endian = "little") {
+        bytes[..data.len()].copy_from_slice(data);
+    } else {
+        bytes[8 - data.len()..].copy_from_slice(data);
+    }
+    Some(u64::from_ne_bytes(bytes))
+}
+
+fn extract_bits(value: u64, offset: u32, bits: u32) -> u64 {
+    let value = value >> offset;
+    if bits == 0 || bits >= 64 {
+        value
+    } else {
+        value & ((1 << bits) - 1)
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    if bits == 0 || bits >= 64 {
+        value as i64
+    } else {
+        let shift = 64 - bits;
+        ((value << shift) as i64) >> shift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::{btf_enum, btf_member, btf_var_secinfo};
+
+    #[test]
+    fn test_decode_struct() {
+        let mut btf = Btf::new();
+        let name = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name, 4, BTF_INT_SIGNED, 0));
+        let name = btf.add_string("unsigned char");
+        let uchar = btf.add_type(BtfType::new_int(name, 1, 0, 0));
+        let array = btf.add_type(BtfType::new_array(uchar, int, 2));
+        let (red, green) = (btf.add_string("RED"), btf.add_string("GREEN"));
+        let name = btf.add_string("color");
+        let color = btf.add_type(BtfType::new_enum(
+            name,
+            vec![
+                btf_enum {
+                    name_off: red,
+                    val: 0,
+                },
+                btf_enum {
+                    name_off: green,
+                    val: 1,
+                },
+            ],
+        ));
+        let (a, b, c) = (
+            btf.add_string("a"),
+            btf.add_string("b"),
+            btf.add_string("c"),
+        );
+        let name = btf.add_string("value");
+        let value = btf.add_type(BtfType::new_struct(
+            name,
+            vec![
+                btf_member {
+                    name_off: a,
+                    type_: int,
+                    offset: 0,
+                },
+                btf_member {
+                    name_off: b,
+                    type_: array,
+                    offset: 32,
+                },
+                btf_member {
+                    name_off: c,
+                    type_: color,
+                    offset: 64,
+                },
+            ],
+            12,
+        ));
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(-3i32).to_ne_bytes());
+        data.extend_from_slice(&[1, 2, 0, 0]);
+        data.extend_from_slice(&1u32.to_ne_bytes());
+        let decoded = btf.decode_value(value, &data).unwrap();
+        assert_eq!(
+            decoded,
+            BtfValue::Struct(vec![
+                ("a".to_owned(), BtfValue::Int(-3)),
+                (
+                    "b".to_owned(),
+                    BtfValue::Array(vec![BtfValue::Uint(1), BtfValue::Uint(2)])
+                ),
+                (
+                    "c".to_owned(),
+                    BtfValue::Enum {
+                        name: Some("GREEN".to_owned()),
+                        value: 1
+                    }
+                ),
+            ])
+        );
+        assert_eq!(decoded.to_string(), "{ a = -3, b = [1, 2], c = GREEN }");
+
+        assert!(matches!(
+            btf.decode_value(value, &data[..4]),
+            Err(BtfError::InvalidValueSize {
+                size: 4,
+                expected: 12,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_datasec() {
+        let mut btf = Btf::new();
+        let name = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name, 4, BTF_INT_SIGNED, 0));
+        let name = btf.add_string("bpf_metadata_version");
+        let version = btf.add_type(BtfType::new_var(name, int, 1));
+        let name = btf.add_string("limit");
+        let limit = btf.add_type(BtfType::new_var(name, int, 1));
+        let name = btf.add_string(".rodata");
+        let rodata = btf.add_type(BtfType::new_datasec(
+            name,
+            vec![
+                btf_var_secinfo {
+                    type_: version,
+                    offset: 0,
+                    size: 4,
+                },
+                btf_var_secinfo {
+                    type_: limit,
+                    offset: 4,
+                    size: 4,
+                },
+            ],
+            8,
+        ));
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&3i32.to_ne_bytes());
+        data.extend_from_slice(&(-1i32).to_ne_bytes());
+        assert_eq!(
+            btf.decode_value(rodata, &data).unwrap(),
+            BtfValue::Struct(vec![
+                ("bpf_metadata_version".to_owned(), BtfValue::Int(3)),
+                ("limit".to_owned(), BtfValue::Int(-1)),
+            ])
+        );
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_decode_bitfield() {
+        let mut btf = Btf::new();
+        let name = btf.add_string("unsigned int");
+        let uint = btf.add_type(BtfType::new_int(name, 4, 0, 0));
+        let (a, b) = (btf.add_string("a"), btf.add_string("b"));
+        let name = btf.add_string("flags");
+        let mut flags = BtfType::new_struct(
+            name,
+            vec![
+                // kind_flag set: the bitfield size is in the upper 8 bits
+                btf_member {
+                    name_off: a,
+                    type_: uint,
+                    offset: 3 << 24,
+                },
+                btf_member {
+                    name_off: b,
+                    type_: uint,
+                    offset: 5 << 24 | 3,
+                },
+            ],
+            4,
+        );
+        if let BtfType::Struct(ty, _) = &mut flags {
+            ty.info |= 1 << 31;
+        }
+        let flags = btf.add_type(flags);
+
+        let data = (22u32 << 3 | 5).to_ne_bytes();
+        assert_eq!(
+            btf.decode_value(flags, &data).unwrap().to_string(),
+            "{ a = 5, b = 22 }"
+        );
+    }
+}
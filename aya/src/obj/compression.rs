@@ -0,0 +1,132 @@
+use std::{borrow::Cow, io};
+
+#[cfg(feature = "zstd")]
+use std::io::Read;
+
+use thiserror::Error;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Error returned when decompressing an object fails.
+#[derive(Debug, Error)]
+#[error("error decompressing {format} object")]
+pub struct DecompressionError {
+    /// The compression format of the object, `"gzip"` or `"zstd"`.
+    pub format: &'static str,
+    /// The error returned by the decoder, or an error of kind
+    /// [`io::ErrorKind::Unsupported`] if aya was built without the feature
+    /// for the format.
+    #[source]
+    pub io_error: io::Error,
+}
+
+/// Decompresses `data` if it starts with the gzip or zstd magic bytes,
+/// otherwise returns it as is.
+pub(crate) fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>, DecompressionError> {
+    if data.starts_with(GZIP_MAGIC) {
+        gunzip(data)
+            .map(Cow::Owned)
+            .map_err(|io_error| DecompressionError {
+                format: "gzip",
+                io_error,
+            })
+    } else if data.starts_with(ZSTD_MAGIC) {
+        unzstd(data)
+            .map(Cow::Owned)
+            .map_err(|io_error| DecompressionError {
+                format: "zstd",
+                io_error,
+            })
+    } else {
+        Ok(Cow::Borrowed(data))
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    crate::util::gunzip(data)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    Err(unsupported("gzip"))
+}
+
+#[cfg(feature = "zstd")]
+fn unzstd(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut out = Vec::new();
+    zstd::stream::read::Decoder::new(data)?.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unzstd(_data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    Err(unsupported("zstd"))
+}
+
+#[cfg(not(all(feature = "gzip", feature = "zstd")))]
+fn unsupported(feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("aya was built without the `{}` feature", feature),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+    #[test]
+    fn test_uncompressed() {
+        assert!(matches!(
+            decompress(ELF_MAGIC),
+            Ok(Cow::Borrowed(ELF_MAGIC))
+        ));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x77, 0xf5, 0x71,
+            0x03, 0x00, 0x51, 0xc4, 0x3a, 0xa7, 0x04, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(&*decompress(&data).unwrap(), ELF_MAGIC);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_bad_trailer() {
+        let mut data = vec![
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x77, 0xf5, 0x71,
+            0x03, 0x00, 0x51, 0xc4, 0x3a, 0xa7, 0x04, 0x00, 0x00, 0x00,
+        ];
+        // wrong CRC32
+        data[16] ^= 0xff;
+        assert!(decompress(&data).is_err());
+        data[16] ^= 0xff;
+        // declared size smaller than the data
+        data[20] = 2;
+        assert!(decompress(&data).is_err());
+        // truncated
+        assert!(decompress(&data[..12]).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let data = zstd::encode_all(ELF_MAGIC, 0).unwrap();
+        assert_eq!(&*decompress(&data).unwrap(), ELF_MAGIC);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_unsupported() {
+        let err = decompress(&[0x1f, 0x8b, 0x08]).unwrap_err();
+        assert_eq!(err.format, "gzip");
+        assert_eq!(err.io_error.kind(), io::ErrorKind::Unsupported);
+    }
+}
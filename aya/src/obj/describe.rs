@@ -0,0 +1,261 @@
+use std::{convert::TryFrom, fs, path::Path};
+
+use crate::{
+    maps::MapType,
+    obj::{decompress, Object, ProgramSection, INS_SIZE},
+    programs::ProgramType,
+    BpfError, PinningType,
+};
+
+/// A description of the content of an eBPF object file.
+///
+/// Describing an object parses it the same way [`Bpf::load`](crate::Bpf::load)
+/// does, but doesn't create maps or load programs, so it doesn't require any
+/// privileges. This can be used to lint objects in CI or by tools listing the
+/// programs and maps of an object, like `bpftool`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::ObjectDescription;
+///
+/// let object = ObjectDescription::parse_file("file.o")?;
+/// for program in &object.programs {
+///     println!(
+///         "{} ({:?}) in {}: {} instructions",
+///         program.name, program.program_type, program.section, program.instructions
+///     );
+/// }
+/// for map in &object.maps {
+///     println!(
+///         "{} {:?} key {} value {} max_entries {} flags {:#x}",
+///         map.name, map.map_type, map.key_size, map.value_size, map.max_entries, map.map_flags
+///     );
+/// }
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ObjectDescription {
+    /// The license of the object.
+    pub license: String,
+    /// The kernel version the object was built for, if any.
+    pub kernel_version: Option<u32>,
+    /// Whether the object contains BTF type information.
+    pub has_btf: bool,
+    /// Whether the object contains BTF extended information, used for CO-RE
+    /// relocations and to annotate the instructions with their source lines.
+    pub has_btf_ext: bool,
+    /// The sections of the object.
+    pub sections: Vec<SectionDescription>,
+    /// The programs, sorted by name.
+    pub programs: Vec<ProgramDescription>,
+    /// The maps, including the maps of the global data sections, sorted by name.
+    pub maps: Vec<MapDescription>,
+    /// The `__kconfig` and `__ksym` externs, resolved when the object is loaded.
+    pub externs: Vec<String>,
+}
+
+/// A section of an eBPF object.
+#[derive(Debug, Clone)]
+pub struct SectionDescription {
+    /// The index of the section in the ELF file.
+    pub index: usize,
+    /// The name of the section.
+    pub name: String,
+    /// The size of the section in bytes.
+    pub size: u64,
+    /// The number of relocations of the section.
+    pub relocations: usize,
+}
+
+/// A program of an eBPF object.
+#[derive(Debug, Clone)]
+pub struct ProgramDescription {
+    /// The name of the program.
+    pub name: String,
+    /// The section the program is defined in.
+    pub section: String,
+    /// The type the program is loaded as.
+    pub program_type: ProgramType,
+    /// Whether the program is sleepable, ie it's defined in a `.s` section
+    /// like `lsm.s/`.
+    pub sleepable: bool,
+    /// The number of instructions of the program, not including the functions
+    /// it calls.
+    pub instructions: usize,
+    /// The relocations of the instructions of the program.
+    pub relocations: Vec<RelocationDescription>,
+}
+
+/// A relocation of an instruction.
+#[derive(Debug, Clone)]
+pub struct RelocationDescription {
+    /// The index of the relocated instruction in its program.
+    pub instruction: usize,
+    /// The name of the symbol the instruction refers to, like a map or a
+    /// function.
+    pub symbol: Option<String>,
+}
+
+/// A map of an eBPF object.
+#[derive(Debug, Clone)]
+pub struct MapDescription {
+    /// The name of the map.
+    pub name: String,
+    /// The section the map is defined in.
+    pub section: String,
+    /// The type of the map, or `None` if the type isn't known to aya.
+    pub map_type: Option<MapType>,
+    /// The size of the keys in bytes.
+    pub key_size: u32,
+    /// The size of the values in bytes.
+    pub value_size: u32,
+    /// The maximum number of entries.
+    pub max_entries: u32,
+    /// The `BPF_F_*` flags the map is created with.
+    pub map_flags: u32,
+    /// Whether the map is pinned by name.
+    pub pinned: bool,
+    /// Whether the types of the keys and values are described by BTF.
+    pub has_btf_types: bool,
+    /// The size of the initial data of global data maps.
+    pub data_size: usize,
+}
+
+impl ObjectDescription {
+    /// Describes the eBPF object contained in `data`.
+    pub fn parse(data: &[u8]) -> Result<ObjectDescription, BpfError> {
+        Ok(Object::parse(&decompress(data)?)?.describe())
+    }
+
+    /// Describes the eBPF object file at `path`.
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<ObjectDescription, BpfError> {
+        let path = path.as_ref();
+        ObjectDescription::parse(&fs::read(path).map_err(|error| BpfError::FileError {
+            path: path.to_owned(),
+            error,
+        })?)
+    }
+}
+
+impl Object {
+    pub(crate) fn describe(&self) -> ObjectDescription {
+        let section_name = |index: usize| {
+            self.sections
+                .iter()
+                .find(|s| s.index == index)
+                .map(|s| s.name.clone())
+                .unwrap_or_default()
+        };
+
+        let mut programs = self
+            .programs
+            .iter()
+            .map(|(name, program)| {
+                let function = &program.function;
+                let start = function.section_offset as u64;
+                let end = start + (function.instructions.len() * INS_SIZE) as u64;
+                let mut relocations = self
+                    .relocations
+                    .get(&function.section_index)
+                    .into_iter()
+                    .flat_map(|relocations| relocations.values())
+                    .filter(|rel| (start..end).contains(&rel.offset))
+                    .map(|rel| RelocationDescription {
+                        instruction: ((rel.offset - start) as usize) / INS_SIZE,
+                        symbol: self
+                            .symbols_by_index
+                            .get(&rel.symbol_index)
+                            .and_then(|sym| sym.name.clone()),
+                    })
+                    .collect::<Vec<_>>();
+                relocations.sort_by_key(|rel| rel.instruction);
+
+                ProgramDescription {
+                    name: name.clone(),
+                    section: section_name(function.section_index.0),
+                    program_type: program.section.program_type(),
+                    sleepable: program.section.sleepable(),
+                    instructions: function.instructions.len(),
+                    relocations,
+                }
+            })
+            .collect::<Vec<_>>();
+        programs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut maps = self
+            .maps
+            .values()
+            .map(|map| MapDescription {
+                name: map.name.clone(),
+                section: section_name(map.section_index),
+                map_type: MapType::try_from(map.def.map_type).ok(),
+                key_size: map.def.key_size,
+                value_size: map.def.value_size,
+                max_entries: map.def.max_entries,
+                map_flags: map.def.map_flags,
+                pinned: map.def.pinning == PinningType::ByName,
+                has_btf_types: map.btf_value_type_id.is_some(),
+                data_size: map.data.len(),
+            })
+            .collect::<Vec<_>>();
+        maps.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let externs = self
+            .kconfig
+            .iter()
+            .map(|e| e.name.clone())
+            .chain(self.ksyms.iter().map(|e| e.name.clone()))
+            .collect();
+
+        ObjectDescription {
+            license: self.license.to_string_lossy().into_owned(),
+            kernel_version: match self.kernel_version {
+                super::KernelVersion::Version(v) => Some(v),
+                super::KernelVersion::Any => None,
+            },
+            has_btf: self.btf.is_some(),
+            has_btf_ext: self.btf_ext.is_some(),
+            sections: self.sections.clone(),
+            programs,
+            maps,
+            externs,
+        }
+    }
+}
+
+impl ProgramSection {
+    pub(crate) fn program_type(&self) -> ProgramType {
+        use ProgramSection::*;
+        match self {
+            KRetProbe { .. }
+            | KProbe { .. }
+            | KRetProbeMulti { .. }
+            | KProbeMulti { .. }
+            | UProbe { .. }
+            | URetProbe { .. }
+            | UProbeMulti { .. }
+            | URetProbeMulti { .. }
+            | Usdt { .. } => ProgramType::KProbe,
+            TracePoint { .. } => ProgramType::TracePoint,
+            SocketFilter { .. } => ProgramType::SocketFilter,
+            Xdp { .. } => ProgramType::Xdp,
+            SkMsg { .. } => ProgramType::SkMsg,
+            SkSkbStreamParser { .. } | SkSkbStreamVerdict { .. } => ProgramType::SkSkb,
+            SockOps { .. } => ProgramType::SockOps,
+            SchedClassifier { .. } => ProgramType::SchedClassifier,
+            CgroupSkbIngress { .. } | CgroupSkbEgress { .. } => ProgramType::CgroupSkb,
+            LircMode2 { .. } => ProgramType::LircMode2,
+            PerfEvent { .. } => ProgramType::PerfEvent,
+            RawTracePoint { .. } => ProgramType::RawTracePoint,
+            Lsm { .. } | LsmCgroup { .. } => ProgramType::Lsm,
+            BtfTracePoint { .. } | FEntry { .. } | FExit { .. } | Iter { .. } => {
+                ProgramType::Tracing
+            }
+            FlowDissector { .. } => ProgramType::FlowDissector,
+            SkReuseport { .. } | SkReuseportMigrate { .. } => ProgramType::SkReuseport,
+            Netfilter { .. } => ProgramType::Netfilter,
+            Extension { .. } => ProgramType::Extension,
+        }
+    }
+}
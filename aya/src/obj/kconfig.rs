@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use object::Endianness;
+use thiserror::Error;
+
+use crate::{
+    bpf_map_def,
+    generated::{bpf_map_type::BPF_MAP_TYPE_ARRAY, BTF_INT_BOOL, BTF_INT_CHAR, BTF_INT_SIGNED},
+    obj::{
+        btf::{Btf, BtfError, BtfType},
+        Map, Object, Symbol,
+    },
+    BpfError,
+};
+
+pub(crate) const KCONFIG_SECTION: &str = ".kconfig";
+pub(crate) const LINUX_KERNEL_VERSION: &str = "LINUX_KERNEL_VERSION";
+
+/// The error type returned when resolving `.kconfig` extern variables.
+#[derive(Debug, Error)]
+pub enum KConfigError {
+    /// The extern has a type that can't be filled from the kernel config.
+    #[error("kconfig extern `{name}` has an unsupported type")]
+    UnsupportedType { name: String },
+
+    /// The extern is not weak and no value was found for it.
+    #[error("no value found for kconfig extern `{name}`")]
+    MissingValue { name: String },
+
+    /// The value found for the extern can't be converted to its type.
+    #[error("invalid value `{value}` for kconfig extern `{name}`")]
+    InvalidValue { name: String, value: String },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum ExternKind {
+    Bool,
+    Char,
+    Tristate,
+    Int { size: usize, signed: bool },
+    CharArray { len: usize },
+}
+
+impl ExternKind {
+    fn size(&self) -> usize {
+        match self {
+            ExternKind::Bool | ExternKind::Char => 1,
+            ExternKind::Tristate => 4,
+            ExternKind::Int { size, .. } => *size,
+            ExternKind::CharArray { len } => *len,
+        }
+    }
+
+    fn align(&self) -> usize {
+        match self {
+            ExternKind::CharArray { .. } => 1,
+            _ => self.size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KConfigExtern {
+    pub(crate) name: String,
+    pub(crate) kind: ExternKind,
+    pub(crate) is_weak: bool,
+    pub(crate) offset: usize,
+}
+
+impl Object {
+    /// Collects the variables declared as `extern ... __kconfig`.
+    ///
+    /// Clang puts them in the `.kconfig` BTF DATASEC, while the ELF symbols
+    /// are left undefined.
+    pub(crate) fn parse_kconfig_externs(&mut self) -> Result<(), BpfError> {
+        let btf = match &self.btf {
+            Some(btf) => btf,
+            None => return Ok(()),
+        };
+
+        let mut externs = Vec::new();
+        for (name, type_id, _) in btf.datasec_vars(KCONFIG_SECTION)? {
+            let kind = extern_kind(btf, type_id)?
+                .ok_or_else(|| KConfigError::UnsupportedType { name: name.clone() })?;
+            let is_weak = find_extern_symbol(&self.symbols_by_index, &name)
+                .map(|sym| sym.is_weak)
+                .unwrap_or(false);
+            externs.push(KConfigExtern {
+                name,
+                kind,
+                is_weak,
+                offset: 0,
+            });
+        }
+
+        // lay the externs out by decreasing alignment so that they're packed
+        externs.sort_by(|a, b| {
+            b.kind
+                .align()
+                .cmp(&a.kind.align())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        let mut offset = 0;
+        for ext in externs.iter_mut() {
+            offset = align_up(offset, ext.kind.align());
+            ext.offset = offset;
+            offset += ext.kind.size();
+        }
+
+        self.kconfig = externs;
+        Ok(())
+    }
+
+    /// Returns `true` if the object references kconfig values that must be
+    /// read from the kernel config.
+    pub(crate) fn needs_kernel_config(&self) -> bool {
+        self.kconfig
+            .iter()
+            .any(|ext| ext.name != LINUX_KERNEL_VERSION)
+    }
+
+    /// Fills the `.kconfig` externs and creates the map that holds them.
+    ///
+    /// `config` is the content of the kernel config file, and
+    /// `kernel_version` the version of the running kernel encoded as in
+    /// `KERNEL_VERSION(a, b, c)`.
+    pub(crate) fn resolve_kconfig(
+        &mut self,
+        config: Option<&str>,
+        kernel_version: Option<u32>,
+    ) -> Result<(), KConfigError> {
+        let size = match self.kconfig.last() {
+            Some(ext) => ext.offset + ext.kind.size(),
+            None => return Ok(()),
+        };
+
+        let values = config.map(parse_kernel_config).unwrap_or_default();
+        let mut data = vec![0u8; size];
+        for ext in &self.kconfig {
+            let buf = &mut data[ext.offset..ext.offset + ext.kind.size()];
+            if ext.name == LINUX_KERNEL_VERSION {
+                match (ext.kind, kernel_version) {
+                    (ExternKind::Int { size: 4, .. }, Some(version)) => {
+                        write_int(buf, version as u64, self.endianness)
+                    }
+                    (ExternKind::Int { size: 4, .. }, None) if ext.is_weak => {}
+                    (ExternKind::Int { size: 4, .. }, None) => {
+                        return Err(KConfigError::MissingValue {
+                            name: ext.name.clone(),
+                        })
+                    }
+                    _ => {
+                        return Err(KConfigError::UnsupportedType {
+                            name: ext.name.clone(),
+                        })
+                    }
+                }
+                continue;
+            }
+
+            match values.get(ext.name.as_str()) {
+                Some(value) => set_value(buf, ext, value, self.endianness)?,
+                // missing weak externs are left zeroed
+                None if ext.is_weak => {}
+                None => {
+                    return Err(KConfigError::MissingValue {
+                        name: ext.name.clone(),
+                    })
+                }
+            }
+        }
+
+        self.maps.insert(
+            KCONFIG_SECTION.to_owned(),
+            Map {
+                name: KCONFIG_SECTION.to_owned(),
+                def: bpf_map_def {
+                    map_type: BPF_MAP_TYPE_ARRAY as u32,
+                    key_size: std::mem::size_of::<u32>() as u32,
+                    value_size: size as u32,
+                    max_entries: 1,
+                    ..Default::default()
+                },
+                // .kconfig isn't an ELF section, and index 0 is never the
+                // section of a defined symbol
+                section_index: 0,
+                section_offset: None,
+                data,
+                inner: None,
+                numa_node: None,
+                btf_key_type_id: None,
+                btf_value_type_id: None,
+                btf: None,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+pub(crate) fn find_extern_symbol<'a>(
+    symbols: &'a HashMap<usize, Symbol>,
+    name: &str,
+) -> Option<&'a Symbol> {
+    symbols
+        .values()
+        .find(|sym| sym.section_index.is_none() && sym.name.as_deref() == Some(name))
+}
+
+fn extern_kind(btf: &Btf, type_id: u32) -> Result<Option<ExternKind>, BtfError> {
+    let resolved = btf.type_by_id(btf.resolve_type(type_id)?)?;
+    let kind = match resolved {
+        BtfType::Int(ty, info) => {
+            // Safety: union
+            let size = unsafe { ty.__bindgen_anon_1.size } as usize;
+            let encoding = (info >> 24) & 0x0f;
+            match size {
+                1 if encoding & BTF_INT_BOOL != 0 => ExternKind::Bool,
+                1 if encoding & BTF_INT_CHAR != 0 => ExternKind::Char,
+                1 | 2 | 4 | 8 => ExternKind::Int {
+                    size,
+                    signed: encoding & BTF_INT_SIGNED != 0,
+                },
+                _ => return Ok(None),
+            }
+        }
+        BtfType::Enum(_, _) if btf.type_name(resolved)?.as_deref() == Some("libbpf_tristate") => {
+            ExternKind::Tristate
+        }
+        BtfType::Array(_, array) => match btf.type_by_id(btf.resolve_type(array.type_)?)? {
+            // Safety: union
+            BtfType::Int(ty, _) if unsafe { ty.__bindgen_anon_1.size } == 1 => {
+                ExternKind::CharArray {
+                    len: array.nelems as usize,
+                }
+            }
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(kind))
+}
+
+/// Parses the `CONFIG_NAME=value` lines of a kernel config file.
+fn parse_kernel_config(config: &str) -> HashMap<&str, &str> {
+    config
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("CONFIG_"))
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+fn set_value(
+    buf: &mut [u8],
+    ext: &KConfigExtern,
+    value: &str,
+    endianness: Endianness,
+) -> Result<(), KConfigError> {
+    let invalid = || KConfigError::InvalidValue {
+        name: ext.name.clone(),
+        value: value.to_owned(),
+    };
+
+    match (ext.kind, value) {
+        (ExternKind::Bool, "y") => buf[0] = 1,
+        (ExternKind::Bool, "n") => buf[0] = 0,
+        (ExternKind::Tristate, "y") => write_int(buf, 1, endianness),
+        (ExternKind::Tristate, "n") => write_int(buf, 0, endianness),
+        (ExternKind::Tristate, "m") => write_int(buf, 2, endianness),
+        (ExternKind::Char, "y") | (ExternKind::Char, "n") | (ExternKind::Char, "m") => {
+            buf[0] = value.as_bytes()[0]
+        }
+        (ExternKind::CharArray { len }, _) => {
+            let s = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .ok_or_else(invalid)?;
+            // truncate long strings, always leaving room for the NUL
+            let n = s.len().min(len.saturating_sub(1));
+            buf[..n].copy_from_slice(&s.as_bytes()[..n]);
+        }
+        (ExternKind::Int { size, signed }, _) => {
+            let value = parse_number(value).ok_or_else(invalid)?;
+            let bits = size as u32 * 8;
+            let (min, max) = if signed {
+                (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+            } else {
+                (0, (1i128 << bits) - 1)
+            };
+            if value < min || value > max {
+                return Err(invalid());
+            }
+            write_int(buf, value as u64, endianness);
+        }
+        _ => return Err(invalid()),
+    }
+
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Option<i128> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let n = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    }
+    .ok()? as i128;
+
+    Some(if negative { -n } else { n })
+}
+
+fn write_int(buf: &mut [u8], value: u64, endianness: Endianness) {
+    let size = buf.len();
+    match endianness {
+        Endianness::Little => buf.copy_from_slice(&value.to_le_bytes()[..size]),
+        Endianness::Big => buf.copy_from_slice(&value.to_be_bytes()[8 - size..]),
+    }
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    (offset + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ext(name: &str, kind: ExternKind) -> KConfigExtern {
+        KConfigExtern {
+            name: name.to_owned(),
+            kind,
+            is_weak: false,
+            offset: 0,
+        }
+    }
+
+    fn set(kind: ExternKind, value: &str) -> Result<Vec<u8>, KConfigError> {
+        let mut buf = vec![0u8; kind.size()];
+        set_value(
+            &mut buf,
+            &ext("CONFIG_TEST", kind),
+            value,
+            Endianness::Little,
+        )?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_parse_kernel_config() {
+        let config = parse_kernel_config(
+            "#\n\
+             # Automatically generated file; DO NOT EDIT.\n\
+             #\n\
+             CONFIG_HZ=250\n\
+             # CONFIG_FOO is not set\n\
+             CONFIG_BPF=y\n\
+             CONFIG_DEFAULT_HOSTNAME=\"(none)\"\n",
+        );
+        assert_eq!(config.len(), 3);
+        assert_eq!(config["CONFIG_HZ"], "250");
+        assert_eq!(config["CONFIG_BPF"], "y");
+        assert_eq!(config["CONFIG_DEFAULT_HOSTNAME"], "\"(none)\"");
+        assert!(!config.contains_key("CONFIG_FOO"));
+    }
+
+    #[test]
+    fn test_set_value() {
+        assert_eq!(set(ExternKind::Bool, "y").unwrap(), [1]);
+        assert_eq!(set(ExternKind::Bool, "n").unwrap(), [0]);
+        assert!(set(ExternKind::Bool, "m").is_err());
+        assert_eq!(set(ExternKind::Tristate, "m").unwrap(), [2, 0, 0, 0]);
+        assert_eq!(set(ExternKind::Char, "m").unwrap(), [b'm']);
+
+        let int = ExternKind::Int {
+            size: 4,
+            signed: false,
+        };
+        assert_eq!(set(int, "250").unwrap(), [250, 0, 0, 0]);
+        assert_eq!(set(int, "0x100").unwrap(), [0, 1, 0, 0]);
+        assert_eq!(set(int, "010").unwrap(), [8, 0, 0, 0]);
+        assert!(set(int, "-1").is_err());
+        assert!(set(int, "0x100000000").is_err());
+        assert!(set(int, "y").is_err());
+        let signed = ExternKind::Int {
+            size: 2,
+            signed: true,
+        };
+        assert_eq!(set(signed, "-2").unwrap(), [0xfe, 0xff]);
+        assert!(set(signed, "32768").is_err());
+
+        let array = ExternKind::CharArray { len: 4 };
+        assert_eq!(set(array, "\"ab\"").unwrap(), b"ab\0\0");
+        assert_eq!(set(array, "\"abcdef\"").unwrap(), b"abc\0");
+        assert!(set(array, "abc").is_err());
+    }
+
+    #[test]
+    fn test_write_int_big_endian() {
+        let mut buf = [0u8; 2];
+        write_int(&mut buf, 0x1234, Endianness::Big);
+        assert_eq!(buf, [0x12, 0x34]);
+    }
+}
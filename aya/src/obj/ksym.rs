@@ -0,0 +1,263 @@
+use thiserror::Error;
+
+use crate::{
+    obj::{
+        btf::{types_are_compatible, Btf, BtfKind, BtfType},
+        find_extern_symbol, Object,
+    },
+    util::KernelSymbols,
+    BpfError,
+};
+
+pub(crate) const KSYMS_SECTION: &str = ".ksyms";
+
+/// The error type returned when resolving `.ksyms` extern variables.
+#[derive(Debug, Error)]
+pub enum KSymError {
+    /// The kernel symbol doesn't exist and the extern is not weak.
+    #[error("kernel symbol `{name}` not found")]
+    NotFound { name: String },
+
+    /// The type of the extern doesn't match the type of the kernel variable.
+    #[error("the type of `{name}` is incompatible with the kernel type")]
+    IncompatibleType { name: String },
+
+    /// The kernel BTF is needed to resolve typed externs.
+    #[error("kernel BTF is required to resolve `{name}`")]
+    MissingBtf { name: String },
+
+    /// The typed kernel variable is defined in a module. Only the variables
+    /// of the vmlinux BTF are supported.
+    #[error("kernel symbol `{name}` is defined in module `{module}`, which is not supported")]
+    ModuleSymbol { name: String, module: String },
+
+    /// The kernel BTF type of the symbol is not a variable.
+    #[error("the kernel BTF type of `{name}` is not a variable")]
+    InvalidType { name: String },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum KSymValue {
+    /// The address of a typeless ksym, or zero for missing weak ksyms.
+    Address(u64),
+    /// The id of a typed ksym in the kernel BTF.
+    BtfId(u32),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KSymExtern {
+    pub(crate) name: String,
+    // the BTF type of typed ksyms, `None` for `extern const void sym __ksym`
+    pub(crate) type_id: Option<u32>,
+    pub(crate) is_weak: bool,
+    pub(crate) value: Option<KSymValue>,
+}
+
+impl Object {
+    /// Collects the variables declared as `extern ... __ksym`.
+    pub(crate) fn parse_ksym_externs(&mut self) -> Result<(), BpfError> {
+        let btf = match &self.btf {
+            Some(btf) => btf,
+            None => return Ok(()),
+        };
+
+        for (name, type_id, _) in btf.datasec_vars(KSYMS_SECTION)? {
+            let type_id = btf.resolve_type(type_id)?;
+            let is_weak = find_extern_symbol(&self.symbols_by_index, &name)
+                .map(|sym| sym.is_weak)
+                .unwrap_or(false);
+            self.ksyms.push(KSymExtern {
+                name,
+                type_id: match btf.type_by_id(type_id)? {
+                    BtfType::Unknown => None,
+                    _ => Some(type_id),
+                },
+                is_weak,
+                value: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the object references typeless ksyms, which are
+    /// resolved with `/proc/kallsyms`.
+    pub(crate) fn needs_kernel_symbols(&self) -> bool {
+        self.ksyms.iter().any(|ext| ext.type_id.is_none())
+    }
+
+    /// Resolves the `.ksyms` externs.
+    ///
+    /// Typeless ksyms are resolved to their address in `ksyms`, typed ksyms to
+    /// the id of the matching variable in the kernel `btf`. `ksyms` is also
+    /// used, if given, to report the typed ksyms defined in modules.
+    pub(crate) fn resolve_ksyms(
+        &mut self,
+        ksyms: Option<&KernelSymbols>,
+        btf: Option<&Btf>,
+    ) -> Result<(), BpfError> {
+        for ext in self.ksyms.iter_mut() {
+            let value = match ext.type_id {
+                None => ksyms
+                    .and_then(|ksyms| ksyms.address_of_in_module(&ext.name, None))
+                    .map(KSymValue::Address),
+                Some(local_id) => {
+                    let target_btf = btf.ok_or_else(|| KSymError::MissingBtf {
+                        name: ext.name.clone(),
+                    })?;
+                    match target_btf.id_by_type_name_kind(&ext.name, BtfKind::Var) {
+                        Ok(target_id) => {
+                            // Safety: union
+                            let target_type_id = match target_btf.type_by_id(target_id)? {
+                                BtfType::Var(ty, _) => unsafe { ty.__bindgen_anon_1.type_ },
+                                _ => {
+                                    return Err(KSymError::InvalidType {
+                                        name: ext.name.clone(),
+                                    }
+                                    .into())
+                                }
+                            };
+                            // the local BTF exists, otherwise there would be no ksyms
+                            let local_btf = self.btf.as_ref().unwrap();
+                            if !types_are_compatible(
+                                local_btf,
+                                local_id,
+                                target_btf,
+                                target_btf.resolve_type(target_type_id)?,
+                            )? {
+                                return Err(KSymError::IncompatibleType {
+                                    name: ext.name.clone(),
+                                }
+                                .into());
+                            }
+                            Some(KSymValue::BtfId(target_id))
+                        }
+                        // the ld_imm64 of typed ksyms refers to the vmlinux
+                        // BTF, module variables would need the fd of the
+                        // module BTF
+                        Err(_) => match ksyms.and_then(|ksyms| {
+                            ksyms
+                                .symbols_named(&ext.name)
+                                .find_map(|sym| sym.module.clone())
+                        }) {
+                            Some(module) => {
+                                return Err(KSymError::ModuleSymbol {
+                                    name: ext.name.clone(),
+                                    module,
+                                }
+                                .into())
+                            }
+                            None => None,
+                        },
+                    }
+                }
+            };
+
+            ext.value = match value {
+                Some(value) => Some(value),
+                // missing weak ksyms resolve to NULL
+                None if ext.is_weak => Some(KSymValue::Address(0)),
+                None => {
+                    return Err(KSymError::NotFound {
+                        name: ext.name.clone(),
+                    }
+                    .into())
+                }
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+    use object::Endianness;
+    use std::{ffi::CString, io::BufReader};
+
+    use super::*;
+    use crate::obj::KernelVersion;
+
+    fn btf_with_var(name: &str, ptr: bool) -> (Btf, u32) {
+        let mut btf = Btf::new();
+        let name_off = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name_off, 4, 0, 0));
+        let ty = if ptr {
+            btf.add_type(BtfType::new_ptr(int))
+        } else {
+            int
+        };
+        let name_off = btf.add_string(name);
+        btf.add_type(BtfType::new_var(name_off, ty, 1));
+        (btf, int)
+    }
+
+    fn object_with_ksym(name: &str, is_weak: bool) -> Object {
+        let (btf, int) = btf_with_var(name, false);
+        let mut obj = Object::new(
+            Endianness::Little,
+            CString::new("GPL").unwrap(),
+            KernelVersion::Any,
+        );
+        obj.btf = Some(btf);
+        obj.ksyms.push(KSymExtern {
+            name: name.to_owned(),
+            type_id: Some(int),
+            is_weak,
+            value: None,
+        });
+        obj
+    }
+
+    #[test]
+    fn test_resolve_typed_ksym() {
+        let (target_btf, _) = btf_with_var("counter", false);
+        let mut obj = object_with_ksym("counter", false);
+        obj.resolve_ksyms(None, Some(&target_btf)).unwrap();
+        assert_eq!(obj.ksyms[0].value, Some(KSymValue::BtfId(2)));
+    }
+
+    #[test]
+    fn test_resolve_missing_ksym() {
+        let (target_btf, _) = btf_with_var("counter", false);
+
+        let mut obj = object_with_ksym("other", true);
+        obj.resolve_ksyms(None, Some(&target_btf)).unwrap();
+        assert_eq!(obj.ksyms[0].value, Some(KSymValue::Address(0)));
+
+        let mut obj = object_with_ksym("other", false);
+        assert_matches!(
+            obj.resolve_ksyms(None, Some(&target_btf)),
+            Err(BpfError::KSymError(KSymError::NotFound { name })) if name == "other"
+        );
+    }
+
+    #[test]
+    fn test_resolve_incompatible_ksym() {
+        let (target_btf, _) = btf_with_var("counter", true);
+        let mut obj = object_with_ksym("counter", false);
+        assert_matches!(
+            obj.resolve_ksyms(None, Some(&target_btf)),
+            Err(BpfError::KSymError(KSymError::IncompatibleType { .. }))
+        );
+        assert_matches!(
+            obj.resolve_ksyms(None, None),
+            Err(BpfError::KSymError(KSymError::MissingBtf { .. }))
+        );
+    }
+
+    #[test]
+    fn test_resolve_module_ksym() {
+        let (target_btf, _) = btf_with_var("counter", false);
+        let data = "ffffffffc0001000 d nf_conntrack_count\t[nf_conntrack]\n".as_bytes();
+        let ksyms = KernelSymbols::parse(&mut BufReader::new(data)).unwrap();
+
+        let mut obj = object_with_ksym("nf_conntrack_count", true);
+        assert_matches!(
+            obj.resolve_ksyms(Some(&ksyms), Some(&target_btf)),
+            Err(BpfError::KSymError(KSymError::ModuleSymbol { name, module }))
+                if name == "nf_conntrack_count" && module == "nf_conntrack"
+        );
+    }
+}
@@ -1,4 +1,8 @@
 pub(crate) mod btf;
+mod compression;
+mod describe;
+mod kconfig;
+mod ksym;
 mod relocation;
 
 use object::{
@@ -6,25 +10,38 @@ use object::{
     Endianness, ObjectSymbol, ObjectSymbolTable, RelocationTarget, SectionIndex, SymbolKind,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
     ffi::{CStr, CString},
     mem, ptr,
     str::FromStr,
+    sync::Arc,
 };
 use thiserror::Error;
 
+pub use compression::DecompressionError;
+pub(crate) use compression::*;
+pub use describe::*;
+pub use kconfig::KConfigError;
+pub(crate) use kconfig::*;
+pub use ksym::KSymError;
+pub(crate) use ksym::*;
 use relocation::*;
 
 use crate::{
     bpf_map_def,
-    generated::{bpf_insn, bpf_map_type::BPF_MAP_TYPE_ARRAY},
-    obj::btf::{Btf, BtfError, BtfExt},
-    BpfError,
+    generated::{bpf_func_info, bpf_insn, bpf_line_info, bpf_map_type::BPF_MAP_TYPE_ARRAY},
+    obj::btf::{Btf, BtfError, BtfExt, BtfType},
+    programs::{XdpActions, XdpRunConfig},
+    BpfError, PinningType,
 };
 use std::slice::from_raw_parts_mut;
 
 const KERNEL_VERSION_ANY: u32 = 0xFFFF_FFFE;
+/// The section of the BTF-defined maps.
+const MAPS_SECTION: &str = ".maps";
+/// The section of the run configs declared with libxdp's `XDP_RUN_CONFIG()`.
+const XDP_RUN_CONFIG_SECTION: &str = ".xdp_run_config";
 /// The first five __u32 of `bpf_map_def` must be defined.
 const MINIMUM_MAP_SIZE: usize = mem::size_of::<u32>() * 5;
 
@@ -37,9 +54,14 @@ pub struct Object {
     pub btf_ext: Option<BtfExt>,
     pub(crate) maps: HashMap<String, Map>,
     pub(crate) programs: HashMap<String, Program>,
-    pub(crate) functions: HashMap<u64, Function>,
+    pub(crate) functions: HashMap<(SectionIndex, u64), Function>,
     pub(crate) relocations: HashMap<SectionIndex, HashMap<u64, Relocation>>,
     pub(crate) symbols_by_index: HashMap<usize, Symbol>,
+    pub(crate) kconfig: Vec<KConfigExtern>,
+    pub(crate) ksyms: Vec<KSymExtern>,
+    pub(crate) sections: Vec<SectionDescription>,
+    // the section kinds registered with `BpfLoader::section_alias`
+    section_aliases: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,7 +69,20 @@ pub struct Map {
     pub(crate) name: String,
     pub(crate) def: bpf_map_def,
     pub(crate) section_index: usize,
+    // the offset of the definition in the section, for the BTF-defined maps
+    // which all live in the `.maps` section
+    pub(crate) section_offset: Option<u64>,
     pub(crate) data: Vec<u8>,
+    // the definition of the inner maps of a map of maps, used to create the
+    // template map the kernel requires
+    pub(crate) inner: Option<Box<Map>>,
+    // the NUMA node the map is allocated on
+    pub(crate) numa_node: Option<u32>,
+    // the BTF types of the keys and values of the BTF-defined maps, and the
+    // BTF they refer to once the object is loaded
+    pub(crate) btf_key_type_id: Option<u32>,
+    pub(crate) btf_value_type_id: Option<u32>,
+    pub(crate) btf: Option<Arc<Btf>>,
 }
 
 #[derive(Debug, Clone)]
@@ -65,17 +100,25 @@ pub(crate) struct Function {
     pub(crate) section_index: SectionIndex,
     pub(crate) section_offset: usize,
     pub(crate) instructions: Vec<bpf_insn>,
+    // the instruction offsets are relative to the start of the function
+    pub(crate) func_info: Vec<bpf_func_info>,
+    pub(crate) line_info: Vec<bpf_line_info>,
 }
 
 #[derive(Debug, Clone)]
 pub enum ProgramSection {
     KRetProbe { name: String },
     KProbe { name: String },
-    UProbe { name: String },
-    URetProbe { name: String },
+    KRetProbeMulti { name: String },
+    KProbeMulti { name: String },
+    UProbe { name: String, sleepable: bool },
+    URetProbe { name: String, sleepable: bool },
+    UProbeMulti { name: String },
+    URetProbeMulti { name: String },
+    Usdt { name: String },
     TracePoint { name: String },
     SocketFilter { name: String },
-    Xdp { name: String },
+    Xdp { name: String, frags: bool },
     SkMsg { name: String },
     SkSkbStreamParser { name: String },
     SkSkbStreamVerdict { name: String },
@@ -86,8 +129,17 @@ pub enum ProgramSection {
     LircMode2 { name: String },
     PerfEvent { name: String },
     RawTracePoint { name: String },
-    Lsm { name: String },
+    Lsm { name: String, sleepable: bool },
+    LsmCgroup { name: String },
     BtfTracePoint { name: String },
+    FlowDissector { name: String },
+    SkReuseport { name: String },
+    SkReuseportMigrate { name: String },
+    Netfilter { name: String },
+    Extension { name: String },
+    FEntry { name: String, sleepable: bool },
+    FExit { name: String, sleepable: bool },
+    Iter { name: String },
 }
 
 impl ProgramSection {
@@ -95,11 +147,16 @@ impl ProgramSection {
         match self {
             ProgramSection::KRetProbe { name } => name,
             ProgramSection::KProbe { name } => name,
-            ProgramSection::UProbe { name } => name,
-            ProgramSection::URetProbe { name } => name,
+            ProgramSection::KRetProbeMulti { name } => name,
+            ProgramSection::KProbeMulti { name } => name,
+            ProgramSection::UProbe { name, .. } => name,
+            ProgramSection::URetProbe { name, .. } => name,
+            ProgramSection::UProbeMulti { name } => name,
+            ProgramSection::URetProbeMulti { name } => name,
+            ProgramSection::Usdt { name } => name,
             ProgramSection::TracePoint { name } => name,
             ProgramSection::SocketFilter { name } => name,
-            ProgramSection::Xdp { name } => name,
+            ProgramSection::Xdp { name, .. } => name,
             ProgramSection::SkMsg { name } => name,
             ProgramSection::SkSkbStreamParser { name } => name,
             ProgramSection::SkSkbStreamVerdict { name } => name,
@@ -110,8 +167,79 @@ impl ProgramSection {
             ProgramSection::LircMode2 { name } => name,
             ProgramSection::PerfEvent { name } => name,
             ProgramSection::RawTracePoint { name } => name,
-            ProgramSection::Lsm { name } => name,
+            ProgramSection::Lsm { name, .. } => name,
+            ProgramSection::LsmCgroup { name } => name,
             ProgramSection::BtfTracePoint { name } => name,
+            ProgramSection::FlowDissector { name } => name,
+            ProgramSection::SkReuseport { name } => name,
+            ProgramSection::SkReuseportMigrate { name } => name,
+            ProgramSection::Netfilter { name } => name,
+            ProgramSection::Extension { name } => name,
+            ProgramSection::FEntry { name, .. } => name,
+            ProgramSection::FExit { name, .. } => name,
+            ProgramSection::Iter { name } => name,
+        }
+    }
+
+    /// Returns true if the program is loaded with `BPF_F_SLEEPABLE`, which
+    /// allows it to call helpers that may sleep like `bpf_copy_from_user`.
+    pub(crate) fn sleepable(&self) -> bool {
+        matches!(
+            self,
+            ProgramSection::UProbe {
+                sleepable: true,
+                ..
+            } | ProgramSection::URetProbe {
+                sleepable: true,
+                ..
+            } | ProgramSection::Lsm {
+                sleepable: true,
+                ..
+            } | ProgramSection::FEntry {
+                sleepable: true,
+                ..
+            } | ProgramSection::FExit {
+                sleepable: true,
+                ..
+            }
+        )
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            ProgramSection::KRetProbe { name } => name,
+            ProgramSection::KProbe { name } => name,
+            ProgramSection::KRetProbeMulti { name } => name,
+            ProgramSection::KProbeMulti { name } => name,
+            ProgramSection::UProbe { name, .. } => name,
+            ProgramSection::URetProbe { name, .. } => name,
+            ProgramSection::UProbeMulti { name } => name,
+            ProgramSection::URetProbeMulti { name } => name,
+            ProgramSection::Usdt { name } => name,
+            ProgramSection::TracePoint { name } => name,
+            ProgramSection::SocketFilter { name } => name,
+            ProgramSection::Xdp { name, .. } => name,
+            ProgramSection::SkMsg { name } => name,
+            ProgramSection::SkSkbStreamParser { name } => name,
+            ProgramSection::SkSkbStreamVerdict { name } => name,
+            ProgramSection::SockOps { name } => name,
+            ProgramSection::SchedClassifier { name } => name,
+            ProgramSection::CgroupSkbIngress { name } => name,
+            ProgramSection::CgroupSkbEgress { name } => name,
+            ProgramSection::LircMode2 { name } => name,
+            ProgramSection::PerfEvent { name } => name,
+            ProgramSection::RawTracePoint { name } => name,
+            ProgramSection::Lsm { name, .. } => name,
+            ProgramSection::LsmCgroup { name } => name,
+            ProgramSection::BtfTracePoint { name } => name,
+            ProgramSection::FlowDissector { name } => name,
+            ProgramSection::SkReuseport { name } => name,
+            ProgramSection::SkReuseportMigrate { name } => name,
+            ProgramSection::Netfilter { name } => name,
+            ProgramSection::Extension { name } => name,
+            ProgramSection::FEntry { name, .. } => name,
+            ProgramSection::FExit { name, .. } => name,
+            ProgramSection::Iter { name } => name,
         }
     }
 }
@@ -134,9 +262,31 @@ impl FromStr for ProgramSection {
         Ok(match kind {
             "kprobe" => KProbe { name },
             "kretprobe" => KRetProbe { name },
-            "uprobe" => UProbe { name },
-            "uretprobe" => URetProbe { name },
-            "xdp" => Xdp { name },
+            "kprobe.multi" => KProbeMulti { name },
+            "kretprobe.multi" => KRetProbeMulti { name },
+            "uprobe" => UProbe {
+                name,
+                sleepable: false,
+            },
+            "uprobe.s" => UProbe {
+                name,
+                sleepable: true,
+            },
+            "uretprobe" => URetProbe {
+                name,
+                sleepable: false,
+            },
+            "uretprobe.s" => URetProbe {
+                name,
+                sleepable: true,
+            },
+            "uprobe.multi" => UProbeMulti { name },
+            "uretprobe.multi" => URetProbeMulti { name },
+            "usdt" => Usdt { name },
+            "xdp" => Xdp { name, frags: false },
+            "xdp.frags" => Xdp { name, frags: true },
+            // the default section of `ip link set dev eth0 xdp obj prog.o`
+            "prog" => Xdp { name, frags: false },
             "tp_btf" => BtfTracePoint { name },
             _ if kind.starts_with("tracepoint") || kind.starts_with("tp") => {
                 // tracepoint sections are named `tracepoint/category/event_name`,
@@ -157,14 +307,51 @@ impl FromStr for ProgramSection {
             },
             "sk_skb/stream_parser" => SkSkbStreamParser { name },
             "sk_skb/stream_verdict" => SkSkbStreamVerdict { name },
+            "sk_reuseport" => match &*name {
+                "migrate" => SkReuseportMigrate { name },
+                _ => SkReuseport { name },
+            },
+            "sk_reuseport/migrate" => SkReuseportMigrate { name },
             "sockops" => SockOps { name },
-            "classifier" => SchedClassifier { name },
+            // `classifier` and `action` are the sections used by iproute2 for
+            // `tc filter` and `tc action` programs, `tc` the one used by
+            // libbpf. Actions are loaded as classifiers, which run them in
+            // direct-action mode.
+            "classifier" | "action" | "tc" => SchedClassifier { name },
             "cgroup_skb/ingress" => CgroupSkbIngress { name },
             "cgroup_skb/egress" => CgroupSkbEgress { name },
             "lirc_mode2" => LircMode2 { name },
             "perf_event" => PerfEvent { name },
             "raw_tp" | "raw_tracepoint" => RawTracePoint { name },
-            "lsm" => Lsm { name },
+            "lsm" => Lsm {
+                name,
+                sleepable: false,
+            },
+            "lsm.s" => Lsm {
+                name,
+                sleepable: true,
+            },
+            "lsm_cgroup" => LsmCgroup { name },
+            "flow_dissector" => FlowDissector { name },
+            "netfilter" => Netfilter { name },
+            "freplace" => Extension { name },
+            "fentry" => FEntry {
+                name,
+                sleepable: false,
+            },
+            "fentry.s" => FEntry {
+                name,
+                sleepable: true,
+            },
+            "fexit" => FExit {
+                name,
+                sleepable: false,
+            },
+            "fexit.s" => FExit {
+                name,
+                sleepable: true,
+            },
+            "iter" => Iter { name },
             _ => {
                 return Err(ParseError::InvalidProgramSection {
                     section: section.to_owned(),
@@ -176,6 +363,13 @@ impl FromStr for ProgramSection {
 
 impl Object {
     pub(crate) fn parse(data: &[u8]) -> Result<Object, BpfError> {
+        Object::parse_with_aliases(data, &HashMap::new())
+    }
+
+    pub(crate) fn parse_with_aliases(
+        data: &[u8],
+        section_aliases: &HashMap<String, String>,
+    ) -> Result<Object, BpfError> {
         let obj = object::read::File::parse(data).map_err(ParseError::ElfError)?;
         let endianness = obj.endianness();
 
@@ -192,6 +386,7 @@ impl Object {
         };
 
         let mut bpf_obj = Object::new(endianness, license, kernel_version);
+        bpf_obj.section_aliases = section_aliases.clone();
 
         if let Some(symbol_table) = obj.symbol_table() {
             for symbol in symbol_table.symbols() {
@@ -203,6 +398,8 @@ impl Object {
                     size: symbol.size(),
                     is_definition: symbol.is_definition(),
                     is_text: symbol.kind() == SymbolKind::Text,
+                    is_global: symbol.is_global(),
+                    is_weak: symbol.is_weak(),
                 };
                 bpf_obj
                     .symbols_by_index
@@ -210,10 +407,24 @@ impl Object {
             }
         }
 
+        let mut section_indices = HashMap::new();
         for s in obj.sections() {
-            bpf_obj.parse_section(Section::try_from(&s)?)?;
+            let section = Section::try_from(&s)?;
+            section_indices.insert(section.name.to_owned(), section.index);
+            bpf_obj.parse_section(section)?;
+        }
+
+        // the BTF-defined maps are described by the .BTF section, which can
+        // come after .maps
+        if let Some(index) = section_indices.get(MAPS_SECTION) {
+            bpf_obj.parse_btf_maps(*index)?;
         }
 
+        bpf_obj.parse_func_line_info(&section_indices)?;
+
+        bpf_obj.parse_kconfig_externs()?;
+        bpf_obj.parse_ksym_externs()?;
+
         Ok(bpf_obj)
     }
 
@@ -229,6 +440,10 @@ impl Object {
             functions: HashMap::new(),
             relocations: HashMap::new(),
             symbols_by_index: HashMap::new(),
+            kconfig: Vec::new(),
+            ksyms: Vec::new(),
+            sections: Vec::new(),
+            section_aliases: HashMap::new(),
         }
     }
 
@@ -243,9 +458,73 @@ impl Object {
         Ok(())
     }
 
+    // assigns the .BTF.ext func_info and line_info records to the functions
+    // they describe
+    fn parse_func_line_info(
+        &mut self,
+        section_indices: &HashMap<String, SectionIndex>,
+    ) -> Result<(), BtfError> {
+        let Object {
+            btf,
+            btf_ext,
+            programs,
+            functions,
+            ..
+        } = self;
+        let (btf, btf_ext) = match (btf, btf_ext) {
+            (Some(btf), Some(btf_ext)) => (btf, btf_ext),
+            _ => return Ok(()),
+        };
+
+        let mut func_info = HashMap::new();
+        for (sec_name_off, records) in btf_ext.func_info() {
+            if let Some(index) = section_indices.get(&*btf.string_at(*sec_name_off)?) {
+                func_info.insert(*index, records);
+            }
+        }
+        let mut line_info = HashMap::new();
+        for (sec_name_off, records) in btf_ext.line_info() {
+            if let Some(index) = section_indices.get(&*btf.string_at(*sec_name_off)?) {
+                line_info.insert(*index, records);
+            }
+        }
+
+        for function in programs
+            .values_mut()
+            .map(|program| &mut program.function)
+            .chain(functions.values_mut())
+        {
+            let start = function.section_offset as u32;
+            let range = start..start + (function.instructions.len() * INS_SIZE) as u32;
+            if let Some(records) = func_info.get(&function.section_index) {
+                function.func_info = records
+                    .iter()
+                    .filter(|rec| range.contains(&rec.insn_off))
+                    .map(|rec| bpf_func_info {
+                        insn_off: (rec.insn_off - start) / INS_SIZE as u32,
+                        ..*rec
+                    })
+                    .collect();
+            }
+            if let Some(records) = line_info.get(&function.section_index) {
+                function.line_info = records
+                    .iter()
+                    .filter(|rec| range.contains(&rec.insn_off))
+                    .map(|rec| bpf_line_info {
+                        insn_off: (rec.insn_off - start) / INS_SIZE as u32,
+                        ..*rec
+                    })
+                    .collect();
+            }
+        }
+
+        Ok(())
+    }
+
     fn parse_program(&self, section: &Section) -> Result<Program, ParseError> {
-        let prog_sec = ProgramSection::from_str(section.name)?;
+        let prog_sec = self.program_section(section.name)?;
         let name = prog_sec.name().to_owned();
+        let instructions = copy_instructions(section.name, &name, 0, section.data)?;
         Ok(Program {
             license: self.license.clone(),
             kernel_version: self.kernel_version,
@@ -255,73 +534,218 @@ impl Object {
                 address: section.address,
                 section_index: section.index,
                 section_offset: 0,
-                instructions: copy_instructions(section.data)?,
+                instructions,
+                func_info: Vec::new(),
+                line_info: Vec::new(),
             },
         })
     }
 
+    /// Returns the run configs declared with libxdp's `XDP_RUN_CONFIG()`
+    /// macro, by function name. Malformed declarations are ignored.
+    pub(crate) fn xdp_run_configs(&self) -> HashMap<String, XdpRunConfig> {
+        let btf = match &self.btf {
+            Some(btf) => btf,
+            None => return HashMap::new(),
+        };
+        let vars = btf.datasec_vars(XDP_RUN_CONFIG_SECTION).unwrap_or_default();
+        vars.into_iter()
+            .filter_map(|(name, type_id, _)| {
+                // the variables are named after the function with a `_` prefix
+                let function = name.strip_prefix('_')?.to_owned();
+                let config = parse_xdp_run_config(btf, type_id).ok()?;
+                Some((function, config))
+            })
+            .collect()
+    }
+
+    fn program_section(&self, section: &str) -> Result<ProgramSection, ParseError> {
+        match resolve_section_alias(&self.section_aliases, section) {
+            Some(resolved) => {
+                ProgramSection::from_str(&resolved).map_err(|_| ParseError::InvalidProgramSection {
+                    section: section.to_owned(),
+                })
+            }
+            None => ProgramSection::from_str(section),
+        }
+    }
+
+    fn parse_btf_maps(&mut self, section_index: SectionIndex) -> Result<(), BpfError> {
+        let btf = self.btf.as_ref().ok_or_else(|| ParseError::MissingBtf {
+            section: MAPS_SECTION.to_owned(),
+        })?;
+        for (name, type_id, offset) in btf.datasec_vars(MAPS_SECTION)? {
+            let mut map = parse_btf_map(btf, &name, type_id)?;
+            map.section_index = section_index.0;
+            map.section_offset = Some(offset as u64);
+            self.maps.insert(name, map);
+        }
+
+        Ok(())
+    }
+
+    // parses a program section. Sections that define more than one function
+    // can contain static subprograms, and several programs if more than one
+    // of the functions is global.
+    fn parse_program_section(&mut self, section: &Section) -> Result<(), ParseError> {
+        let n_functions = self
+            .symbols_by_index
+            .values()
+            .filter(|sym| is_function_of(sym, section))
+            .count();
+        if n_functions <= 1 {
+            let program = self.parse_program(section)?;
+            self.programs
+                .insert(program.section.name().to_owned(), program);
+            return Ok(());
+        }
+
+        let prog_sec = self.program_section(section.name)?;
+        let functions = self.parse_functions(section)?;
+        let n_programs = functions.iter().filter(|(_, is_global)| *is_global).count();
+        for (function, is_global) in functions {
+            if is_global {
+                let mut section = prog_sec.clone();
+                // a single program keeps the name of its section, otherwise
+                // the programs are named after their functions
+                if n_programs > 1 {
+                    *section.name_mut() = function.name.clone();
+                }
+                self.programs.insert(
+                    section.name().to_owned(),
+                    Program {
+                        license: self.license.clone(),
+                        kernel_version: self.kernel_version,
+                        section,
+                        function: function.clone(),
+                    },
+                );
+            }
+            self.functions
+                .insert((function.section_index, function.address), function);
+        }
+
+        Ok(())
+    }
+
     fn parse_text_section(&mut self, mut section: Section) -> Result<(), ParseError> {
+        for (function, _) in self.parse_functions(&section)? {
+            self.functions
+                .insert((function.section_index, function.address), function);
+        }
+
+        if !section.relocations.is_empty() {
+            self.relocations.insert(
+                section.index,
+                section
+                    .relocations
+                    .drain(..)
+                    .map(|rel| (rel.offset, rel))
+                    .collect(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the indices of the sections holding `bpf_metadata_*`
+    /// variables.
+    ///
+    /// The programs never read the metadata, so its maps are bound to them
+    /// explicitly to show up in their info.
+    pub(crate) fn metadata_sections(&self) -> HashSet<usize> {
+        self.symbols_by_index
+            .values()
+            .filter(|sym| {
+                !sym.is_text
+                    && sym
+                        .name
+                        .as_deref()
+                        .is_some_and(|name| name.starts_with("bpf_metadata_"))
+            })
+            .filter_map(|sym| sym.section_index.map(|index| index.0))
+            .collect()
+    }
+
+    // splits a section into the functions defined by the symbol table. Returns
+    // the functions along with whether they are global.
+    fn parse_functions(&self, section: &Section) -> Result<Vec<(Function, bool)>, ParseError> {
         let mut symbols_by_address = HashMap::new();
 
         for sym in self.symbols_by_index.values() {
-            if sym.is_definition && sym.is_text && sym.section_index == Some(section.index) {
-                if symbols_by_address.contains_key(&sym.address) {
+            if is_function_of(sym, section) {
+                if let Some(other) = symbols_by_address.insert(sym.address, sym) {
                     return Err(ParseError::SymbolTableConflict {
-                        section_index: section.index.0,
+                        section: section.name.to_owned(),
                         address: sym.address,
+                        symbol: symbol_name(sym),
+                        other: symbol_name(other),
                     });
                 }
-                symbols_by_address.insert(sym.address, sym);
             }
         }
 
+        let mut functions = Vec::new();
         let mut offset = 0;
         while offset < section.data.len() {
             let address = section.address + offset as u64;
-            let sym = symbols_by_address
-                .get(&address)
-                .ok_or(ParseError::UnknownSymbol {
-                    section_index: section.index.0,
-                    address,
-                })?;
+            let sym =
+                symbols_by_address
+                    .get(&address)
+                    .ok_or_else(|| ParseError::UnknownSymbol {
+                        section: section.name.to_owned(),
+                        address,
+                    })?;
+            let invalid = |reason: &str| ParseError::InvalidSymbol {
+                index: sym.index,
+                name: sym.name.clone(),
+                section: section.name.to_owned(),
+                reason: reason.to_owned(),
+            };
             if sym.size == 0 {
-                return Err(ParseError::InvalidSymbol {
-                    index: sym.index,
-                    name: sym.name.clone(),
-                });
+                return Err(invalid("the function has a size of 0"));
+            }
+            let name = sym
+                .name
+                .clone()
+                .ok_or_else(|| invalid("the function has no name"))?;
+            let end = offset + sym.size as usize;
+            if end > section.data.len() {
+                return Err(invalid("the function extends past the end of the section"));
             }
 
-            self.functions.insert(
-                sym.address,
+            functions.push((
                 Function {
                     address,
-                    name: sym.name.clone().unwrap(),
-                    section_index: section.index,
-                    section_offset: offset,
                     instructions: copy_instructions(
-                        &section.data[offset..offset + sym.size as usize],
+                        section.name,
+                        &name,
+                        offset,
+                        &section.data[offset..end],
                     )?,
+                    name,
+                    section_index: section.index,
+                    section_offset: offset,
+                    func_info: Vec::new(),
+                    line_info: Vec::new(),
                 },
-            );
+                sym.is_global,
+            ));
 
             offset += sym.size as usize;
         }
 
-        if !section.relocations.is_empty() {
-            self.relocations.insert(
-                section.index,
-                section
-                    .relocations
-                    .drain(..)
-                    .map(|rel| (rel.offset, rel))
-                    .collect(),
-            );
-        }
-
-        Ok(())
+        Ok(functions)
     }
 
     fn parse_section(&mut self, mut section: Section) -> Result<(), BpfError> {
+        self.sections.push(SectionDescription {
+            index: section.index.0,
+            name: section.name.to_owned(),
+            size: section.size,
+            relocations: section.relocations.len(),
+        });
+
         let mut parts = section.name.rsplitn(2, '/').collect::<Vec<_>>();
         parts.reverse();
 
@@ -335,7 +759,7 @@ impl Object {
         }
 
         match section.name {
-            name if name == ".bss" || name.starts_with(".data") || name.starts_with(".rodata") => {
+            name if is_data_section(name) => {
                 self.maps
                     .insert(name.to_string(), parse_map(&section, name)?);
             }
@@ -347,10 +771,10 @@ impl Object {
                 self.maps
                     .insert(name.to_string(), parse_map(&section, name)?);
             }
-            name if is_program_section(name) => {
-                let program = self.parse_program(&section)?;
-                self.programs
-                    .insert(program.section.name().to_owned(), program);
+            name if resolve_section_alias(&self.section_aliases, name).is_some()
+                || is_program_section(name) =>
+            {
+                self.parse_program_section(&section)?;
                 if !section.relocations.is_empty() {
                     self.relocations.insert(
                         section.index,
@@ -384,33 +808,56 @@ pub enum ParseError {
     #[error("invalid kernel version `{data:?}`")]
     InvalidKernelVersion { data: Vec<u8> },
 
-    #[error("error parsing section with index {index}")]
+    #[error("error parsing section `{}` with index {index}", .name.as_deref().unwrap_or("[unknown]"))]
     SectionError {
         index: usize,
+        name: Option<String>,
         #[source]
         source: object::read::Error,
     },
 
-    #[error("unsupported relocation target")]
-    UnsupportedRelocationTarget,
+    #[error("unsupported target of the relocation at offset {offset:#x} in section `{section}`")]
+    UnsupportedRelocationTarget { section: String, offset: u64 },
 
     #[error("invalid program section `{section}`")]
     InvalidProgramSection { section: String },
 
-    #[error("invalid program code")]
-    InvalidProgramCode,
+    #[error("invalid code of function `{function}` in section `{section}` at offset {offset:#x}: the size {size} is not a multiple of the instruction size")]
+    InvalidProgramCode {
+        section: String,
+        function: String,
+        offset: usize,
+        size: usize,
+    },
+
+    #[error("error parsing map `{name}` in section `{section}`: {reason}")]
+    InvalidMapDefinition {
+        name: String,
+        section: String,
+        reason: String,
+    },
 
-    #[error("error parsing map `{name}`")]
-    InvalidMapDefinition { name: String },
+    #[error("symbol `{symbol}` in section `{section}` has the same address {address:#X} as symbol `{other}`")]
+    SymbolTableConflict {
+        section: String,
+        address: u64,
+        symbol: String,
+        other: String,
+    },
 
-    #[error("two or more symbols in section `{section_index}` have the same address {address:#X}")]
-    SymbolTableConflict { section_index: usize, address: u64 },
+    #[error("unknown symbol in section `{section}` at address {address:#X}")]
+    UnknownSymbol { section: String, address: u64 },
 
-    #[error("unknown symbol in section `{section_index}` at address {address:#X}")]
-    UnknownSymbol { section_index: usize, address: u64 },
+    #[error("invalid symbol `{}` with index {index} in section `{section}`: {reason}", .name.as_deref().unwrap_or("[unknown]"))]
+    InvalidSymbol {
+        index: usize,
+        name: Option<String>,
+        section: String,
+        reason: String,
+    },
 
-    #[error("invalid symbol, index `{index}` name: {}", .name.as_ref().unwrap_or(&"[unknown]".into()))]
-    InvalidSymbol { index: usize, name: Option<String> },
+    #[error("section `{section}` requires BTF info")]
+    MissingBtf { section: String },
 }
 
 #[derive(Debug)]
@@ -428,16 +875,22 @@ impl<'data, 'file, 'a> TryFrom<&'a ObjSection<'data, 'file>> for Section<'a> {
 
     fn try_from(section: &'a ObjSection) -> Result<Section<'a>, ParseError> {
         let index = section.index();
-        let map_err = |source| ParseError::SectionError {
+        let name = section.name().map_err(|source| ParseError::SectionError {
             index: index.0,
+            name: None,
             source,
-        };
+        })?;
+        let data = section.data().map_err(|source| ParseError::SectionError {
+            index: index.0,
+            name: Some(name.to_owned()),
+            source,
+        })?;
 
         Ok(Section {
             index,
             address: section.address(),
-            name: section.name().map_err(map_err)?,
-            data: section.data().map_err(map_err)?,
+            name,
+            data,
             size: section.size(),
             relocations: section
                 .relocations()
@@ -445,7 +898,12 @@ impl<'data, 'file, 'a> TryFrom<&'a ObjSection<'data, 'file>> for Section<'a> {
                     Ok(Relocation {
                         symbol_index: match r.target() {
                             RelocationTarget::Symbol(index) => index.0,
-                            _ => return Err(ParseError::UnsupportedRelocationTarget),
+                            _ => {
+                                return Err(ParseError::UnsupportedRelocationTarget {
+                                    section: name.to_owned(),
+                                    offset,
+                                })
+                            }
                         },
                         offset,
                     })
@@ -511,8 +969,7 @@ impl From<KernelVersion> for u32 {
 }
 
 fn parse_map(section: &Section, name: &str) -> Result<Map, ParseError> {
-    let (def, data) = if name == ".bss" || name.starts_with(".data") || name.starts_with(".rodata")
-    {
+    let (def, data) = if is_data_section(name) {
         let def = bpf_map_def {
             map_type: BPF_MAP_TYPE_ARRAY as u32,
             key_size: mem::size_of::<u32>() as u32,
@@ -523,23 +980,170 @@ fn parse_map(section: &Section, name: &str) -> Result<Map, ParseError> {
             map_flags: 0, /* FIXME: set rodata readonly */
             ..Default::default()
         };
-        (def, section.data.to_vec())
+        // .bss sections have no data in the file, their map is zero filled
+        let mut data = section.data.to_vec();
+        data.resize(section.size as usize, 0);
+        (def, data)
     } else {
-        (parse_map_def(name, section.data)?, Vec::new())
+        (parse_map_def(section.name, name, section.data)?, Vec::new())
     };
 
     Ok(Map {
         section_index: section.index.0,
+        section_offset: None,
         name: name.to_string(),
         def,
         data,
+        inner: None,
+        numa_node: None,
+        btf_key_type_id: None,
+        btf_value_type_id: None,
+        btf: None,
+    })
+}
+
+// parses a map defined with the libbpf conventions, where the members of the
+// definition struct encode the attributes of the map in their types:
+//
+// * `__uint(name, val)`, ie `int (*name)[val]`, for integer attributes
+// * `__type(name, val)`, ie `typeof(val) *name`, for the key and value types
+// * `__array(name, val)`, ie `typeof(val) *name[]`, for the inner maps of a
+//   map of maps
+fn parse_btf_map(btf: &Btf, name: &str, type_id: u32) -> Result<Map, BpfError> {
+    let invalid = |attr: &str| ParseError::InvalidMapDefinition {
+        name: name.to_owned(),
+        section: MAPS_SECTION.to_owned(),
+        reason: format!("invalid `{}` attribute", attr),
+    };
+    let members = match btf.type_by_id(btf.resolve_type(type_id)?)? {
+        BtfType::Struct(_, members) => members,
+        _ => {
+            return Err(ParseError::InvalidMapDefinition {
+                name: name.to_owned(),
+                section: MAPS_SECTION.to_owned(),
+                reason: "the definition is not a struct".to_owned(),
+            }
+            .into())
+        }
+    };
+
+    let mut def = bpf_map_def::default();
+    let mut inner = None;
+    let mut btf_key_type_id = None;
+    let mut btf_value_type_id = None;
+    let mut numa_node = None;
+    for member in members {
+        let attr = btf.string_at(member.name_off)?;
+        let invalid = || invalid(&attr);
+        match &*attr {
+            "type" => def.map_type = btf_map_uint(btf, member.type_).ok_or_else(invalid)?,
+            "key_size" => def.key_size = btf_map_uint(btf, member.type_).ok_or_else(invalid)?,
+            "value_size" => def.value_size = btf_map_uint(btf, member.type_).ok_or_else(invalid)?,
+            "max_entries" => {
+                def.max_entries = btf_map_uint(btf, member.type_).ok_or_else(invalid)?
+            }
+            "map_flags" => def.map_flags = btf_map_uint(btf, member.type_).ok_or_else(invalid)?,
+            "pinning" => {
+                def.pinning = match btf_map_uint(btf, member.type_).ok_or_else(invalid)? {
+                    0 => PinningType::None,
+                    1 => PinningType::ByName,
+                    _ => return Err(invalid().into()),
+                }
+            }
+            "key" => {
+                def.key_size = btf_map_type_size(btf, member.type_)? as u32;
+                btf_key_type_id = btf_map_pointee(btf, member.type_);
+            }
+            "value" => {
+                def.value_size = btf_map_type_size(btf, member.type_)? as u32;
+                btf_value_type_id = btf_map_pointee(btf, member.type_);
+            }
+            "values" => {
+                // an array of pointers to the definition of the inner map, or
+                // to the programs of a program array
+                let array = match btf.type_by_id(btf.resolve_type(member.type_)?)? {
+                    BtfType::Array(_, array) => array,
+                    _ => return Err(invalid().into()),
+                };
+                let pointee = match btf.type_by_id(btf.resolve_type(array.type_)?)? {
+                    // Safety: union
+                    BtfType::Ptr(ty) => btf.resolve_type(unsafe { ty.__bindgen_anon_1.type_ })?,
+                    _ => return Err(invalid().into()),
+                };
+                if let BtfType::Struct(_, _) = btf.type_by_id(pointee)? {
+                    inner = Some(Box::new(parse_btf_map(
+                        btf,
+                        &format!("{}.inner", name),
+                        pointee,
+                    )?));
+                }
+                // the values are fds
+                def.value_size = mem::size_of::<u32>() as u32;
+            }
+            "numa_node" => numa_node = Some(btf_map_uint(btf, member.type_).ok_or_else(invalid)?),
+            // map_extra...
+            _ => {}
+        }
+    }
+
+    Ok(Map {
+        section_index: 0,
+        section_offset: None,
+        name: name.to_owned(),
+        def,
+        data: Vec::new(),
+        inner,
+        numa_node,
+        btf_key_type_id,
+        btf_value_type_id,
+        btf: None,
     })
 }
 
-fn parse_map_def(name: &str, data: &[u8]) -> Result<bpf_map_def, ParseError> {
+// the value of a `__uint()` attribute is the number of elements of the array
+// pointed to by the member
+fn btf_map_uint(btf: &Btf, type_id: u32) -> Option<u32> {
+    let ptr = btf.type_by_id(btf.resolve_type(type_id).ok()?).ok()?;
+    let pointee = match ptr {
+        // Safety: union
+        BtfType::Ptr(ty) => unsafe { ty.__bindgen_anon_1.type_ },
+        _ => return None,
+    };
+    match btf.type_by_id(btf.resolve_type(pointee).ok()?).ok()? {
+        BtfType::Array(_, array) => Some(array.nelems),
+        _ => None,
+    }
+}
+
+// the size of a `__type()` attribute is the size of the type pointed to by the
+// member
+fn btf_map_type_size(btf: &Btf, type_id: u32) -> Result<usize, BtfError> {
+    match btf.type_by_id(btf.resolve_type(type_id)?)? {
+        // Safety: union
+        BtfType::Ptr(ty) => btf.type_size(unsafe { ty.__bindgen_anon_1.type_ }),
+        _ => Err(BtfError::UnexpectedBtfType { type_id }),
+    }
+}
+
+// the type of a `__type()` attribute
+fn btf_map_pointee(btf: &Btf, type_id: u32) -> Option<u32> {
+    match btf.type_by_id(btf.resolve_type(type_id).ok()?).ok()? {
+        // Safety: union
+        BtfType::Ptr(ty) => Some(unsafe { ty.__bindgen_anon_1.type_ }),
+        _ => None,
+    }
+}
+
+fn parse_map_def(section: &str, name: &str, data: &[u8]) -> Result<bpf_map_def, ParseError> {
     if data.len() < MINIMUM_MAP_SIZE {
         return Err(ParseError::InvalidMapDefinition {
             name: name.to_owned(),
+            section: section.to_owned(),
+            reason: format!(
+                "the definition is {} bytes, expected at least {}",
+                data.len(),
+                MINIMUM_MAP_SIZE
+            ),
         });
     }
 
@@ -556,9 +1160,19 @@ fn parse_map_def(name: &str, data: &[u8]) -> Result<bpf_map_def, ParseError> {
     }
 }
 
-fn copy_instructions(data: &[u8]) -> Result<Vec<bpf_insn>, ParseError> {
+fn copy_instructions(
+    section: &str,
+    function: &str,
+    offset: usize,
+    data: &[u8],
+) -> Result<Vec<bpf_insn>, ParseError> {
     if data.len() % mem::size_of::<bpf_insn>() > 0 {
-        return Err(ParseError::InvalidProgramCode);
+        return Err(ParseError::InvalidProgramCode {
+            section: section.to_owned(),
+            function: function.to_owned(),
+            offset,
+            size: data.len(),
+        });
     }
     let num_instructions = data.len() / mem::size_of::<bpf_insn>();
     let instructions = (0..num_instructions)
@@ -572,8 +1186,90 @@ fn copy_instructions(data: &[u8]) -> Result<Vec<bpf_insn>, ParseError> {
     Ok(instructions)
 }
 
+/// Returns true if `name` is a global data section, which is backed by its own
+/// array map. Besides `.data`, `.rodata` and `.bss`, clang emits custom
+/// sections like `.data.settings` for variables declared with
+/// `SEC(".data.settings")`.
+pub(crate) fn is_data_section(name: &str) -> bool {
+    name.starts_with(".data") || name.starts_with(".rodata") || is_bss_section(name)
+}
+
+/// Returns true if `name` is a `.bss` section, whose data is zero initialized.
+pub(crate) fn is_bss_section(name: &str) -> bool {
+    name == ".bss" || name.starts_with(".bss.")
+}
+
+fn is_function_of(sym: &Symbol, section: &Section) -> bool {
+    sym.is_definition && sym.is_text && sym.section_index == Some(section.index)
+}
+
+fn symbol_name(sym: &Symbol) -> String {
+    sym.name
+        .clone()
+        .unwrap_or_else(|| format!("[unknown #{}]", sym.index))
+}
+
+// The members of the run configs are declared with `__uint(name, value)`,
+// which encodes the value in the size of an array: `int (*name)[value]`.
+fn parse_xdp_run_config(btf: &Btf, type_id: u32) -> Result<XdpRunConfig, BtfError> {
+    let mut config = XdpRunConfig::default();
+    let members = match btf.type_by_id(btf.resolve_type(type_id)?)? {
+        BtfType::Struct(_, members) => members,
+        _ => return Err(BtfError::UnexpectedBtfType { type_id }),
+    };
+    for member in members {
+        let value = match btf.type_by_id(member.type_)? {
+            // Safety: union
+            BtfType::Ptr(ty) => match btf.type_by_id(unsafe { ty.__bindgen_anon_1.type_ })? {
+                BtfType::Array(_, array) => array.nelems,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let action = match &*btf.string_at(member.name_off)? {
+            "priority" => {
+                config.priority = value;
+                continue;
+            }
+            "XDP_ABORTED" => XdpActions::ABORTED,
+            "XDP_DROP" => XdpActions::DROP,
+            "XDP_PASS" => XdpActions::PASS,
+            "XDP_TX" => XdpActions::TX,
+            "XDP_REDIRECT" => XdpActions::REDIRECT,
+            _ => continue,
+        };
+        config.chain_call_actions.set(action, value != 0);
+    }
+
+    Ok(config)
+}
+
+// Rewrites `section` to the section kind it's an alias of, eg `my_xdp/foo` to
+// `xdp/foo` for the alias `my_xdp` of `xdp`. A section named after the alias
+// itself keeps its name as the name of the program. The longest matching
+// alias wins, and aliases take precedence over the built-in section names.
+fn resolve_section_alias(aliases: &HashMap<String, String>, section: &str) -> Option<String> {
+    aliases
+        .iter()
+        .filter(|(alias, _)| {
+            section == alias.as_str()
+                || section
+                    .strip_prefix(alias.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|(alias, _)| alias.len())
+        .map(|(alias, kind)| {
+            if section == alias.as_str() {
+                format!("{}/{}", kind, section)
+            } else {
+                format!("{}{}", kind, &section[alias.len()..])
+            }
+        })
+}
+
 fn is_program_section(name: &str) -> bool {
     for prefix in &[
+        "action",
         "classifier",
         "cgroup/skb",
         "cgroup_skb/egress",
@@ -585,17 +1281,28 @@ fn is_program_section(name: &str) -> bool {
         "sk_msg",
         "sk_skb/stream_parser",
         "sk_skb/stream_verdict",
+        "sk_reuseport",
         "socket_filter",
         "sockops",
         "tp",
         "tracepoint",
         "uprobe",
         "uretprobe",
+        "usdt",
         "xdp",
         "raw_tp",
         "raw_tracepoint",
         "lsm",
+        "lsm_cgroup",
         "tp_btf",
+        "flow_dissector",
+        "netfilter",
+        "freplace",
+        "fentry",
+        "fexit",
+        "iter",
+        "prog",
+        "tc",
     ] {
         if name.starts_with(prefix) {
             return true;
@@ -612,7 +1319,7 @@ mod tests {
     use std::slice;
 
     use super::*;
-    use crate::PinningType;
+    use crate::{generated::btf_member, maps::MapType, programs::ProgramType, PinningType};
 
     fn fake_section<'a>(name: &'a str, data: &'a [u8]) -> Section<'a> {
         Section {
@@ -702,7 +1409,7 @@ mod tests {
     #[test]
     fn test_parse_map_def_error() {
         assert!(matches!(
-            parse_map_def("foo", &[]),
+            parse_map_def("maps/foo", "foo", &[]),
             Err(ParseError::InvalidMapDefinition { .. })
         ));
     }
@@ -720,7 +1427,7 @@ mod tests {
         };
 
         assert_eq!(
-            parse_map_def("foo", &bytes_of(&def)[..MINIMUM_MAP_SIZE]).unwrap(),
+            parse_map_def("maps/foo", "foo", &bytes_of(&def)[..MINIMUM_MAP_SIZE]).unwrap(),
             def
         );
     }
@@ -737,7 +1444,10 @@ mod tests {
             pinning: PinningType::ByName,
         };
 
-        assert_eq!(parse_map_def("foo", bytes_of(&def)).unwrap(), def);
+        assert_eq!(
+            parse_map_def("maps/foo", "foo", bytes_of(&def)).unwrap(),
+            def
+        );
     }
 
     #[test]
@@ -754,14 +1464,15 @@ mod tests {
         let mut buf = [0u8; 128];
         unsafe { ptr::write_unaligned(buf.as_mut_ptr() as *mut _, def) };
 
-        assert_eq!(parse_map_def("foo", &buf).unwrap(), def);
+        assert_eq!(parse_map_def("maps/foo", "foo", &buf).unwrap(), def);
     }
 
     #[test]
     fn test_parse_map_error() {
         assert!(matches!(
             parse_map(&fake_section("maps/foo", &[]), "foo"),
-            Err(ParseError::InvalidMapDefinition { .. })
+            Err(ParseError::InvalidMapDefinition { name, section, .. })
+                if name == "foo" && section == "maps/foo"
         ));
     }
 
@@ -795,7 +1506,8 @@ mod tests {
                     id: 0,
                     pinning: PinningType::None,
                 },
-                data
+                data,
+                ..
             }) if name == "foo" && data.is_empty()
         ))
     }
@@ -823,7 +1535,8 @@ mod tests {
                     id: 0,
                     pinning: PinningType::None,
                 },
-                data
+                data,
+                ..
             }) if name == ".bss" && data == map_data && value_size == map_data.len() as u32
         ))
     }
@@ -842,7 +1555,8 @@ mod tests {
 
         assert_matches!(
             obj.parse_program(&fake_section("kprobe/foo", &42u32.to_ne_bytes(),),),
-            Err(ParseError::InvalidProgramCode)
+            Err(ParseError::InvalidProgramCode { section, function, offset: 0, size: 4 })
+                if section == "kprobe/foo" && function == "foo"
         );
     }
 
@@ -861,7 +1575,8 @@ mod tests {
                     address: 0,
                     section_index: SectionIndex(0),
                     section_offset: 0,
-                    instructions
+                    instructions,
+                    ..
                 }
             }) if license.to_string_lossy() == "GPL" && name == "foo" && instructions.len() == 1
         );
@@ -920,117 +1635,356 @@ mod tests {
             Ok(())
         );
         assert!(obj.maps.get(".data.boo").is_some());
+
+        let mut section = fake_section(".bss.boo", &[]);
+        section.size = 8;
+        assert_matches!(obj.parse_section(section), Ok(()));
+        let map = obj.maps.get(".bss.boo").unwrap();
+        assert_eq!(map.def.value_size, 8);
+        assert_eq!(map.data, vec![0; 8]);
     }
 
     #[test]
-    fn test_parse_section_kprobe() {
+    fn test_describe() {
         let mut obj = fake_obj();
-
-        assert_matches!(
-            obj.parse_section(fake_section("kprobe/foo", bytes_of(&fake_ins()))),
-            Ok(())
-        );
-        assert_matches!(
-            obj.programs.get("foo"),
-            Some(Program {
-                section: ProgramSection::KProbe { .. },
-                ..
-            })
+        let insns = [fake_ins(), fake_ins()];
+        let mut section = fake_section("kprobe/foo", bytes_of(&insns));
+        section.relocations.push(Relocation {
+            offset: INS_SIZE as u64,
+            symbol_index: 1,
+        });
+        obj.symbols_by_index.insert(
+            1,
+            Symbol {
+                index: 1,
+                section_index: None,
+                name: Some("bar".to_string()),
+                address: 0,
+                size: 0,
+                is_definition: false,
+                is_text: false,
+                is_global: false,
+                is_weak: false,
+            },
         );
+        obj.parse_section(section).unwrap();
+        obj.parse_section(fake_section(
+            "maps/bar",
+            bytes_of(&bpf_map_def {
+                map_type: BPF_MAP_TYPE_ARRAY as u32,
+                key_size: 4,
+                value_size: 8,
+                max_entries: 16,
+                pinning: PinningType::ByName,
+                ..Default::default()
+            }),
+        ))
+        .unwrap();
+
+        let desc = obj.describe();
+        assert_eq!(desc.license, "GPL");
+        assert_eq!(desc.kernel_version, None);
+        assert!(!desc.has_btf);
+        assert_eq!(desc.sections.len(), 2);
+        assert_eq!(desc.sections[0].name, "kprobe/foo");
+        assert_eq!(desc.sections[0].relocations, 1);
+
+        assert_eq!(desc.programs.len(), 1);
+        let program = &desc.programs[0];
+        assert_eq!(program.name, "foo");
+        assert_eq!(program.section, "kprobe/foo");
+        assert_eq!(program.program_type, ProgramType::KProbe);
+        assert_eq!(program.instructions, 2);
+        assert_eq!(program.relocations.len(), 1);
+        assert_eq!(program.relocations[0].instruction, 1);
+        assert_eq!(program.relocations[0].symbol.as_deref(), Some("bar"));
+
+        assert_eq!(desc.maps.len(), 1);
+        let map = &desc.maps[0];
+        assert_eq!(map.name, "bar");
+        assert_eq!(map.map_type, Some(MapType::Array));
+        assert_eq!((map.key_size, map.value_size, map.max_entries), (4, 8, 16));
+        assert!(map.pinned);
     }
 
     #[test]
-    fn test_parse_section_uprobe() {
+    fn test_parse_section_kprobe() {
         let mut obj = fake_obj();
 
         assert_matches!(
-            obj.parse_section(fake_section("uprobe/foo", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("kprobe/foo", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::UProbe { .. },
+                section: ProgramSection::KProbe { .. },
                 ..
             })
         );
     }
 
     #[test]
-    fn test_parse_section_trace_point() {
+    fn test_parse_section_kprobe_multi() {
         let mut obj = fake_obj();
 
         assert_matches!(
-            obj.parse_section(fake_section("tracepoint/foo", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("kprobe.multi/foo", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::TracePoint { .. },
+                section: ProgramSection::KProbeMulti { .. },
                 ..
             })
         );
 
         assert_matches!(
-            obj.parse_section(fake_section("tp/foo/bar", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("kretprobe.multi/bar", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
-            obj.programs.get("foo/bar"),
+            obj.programs.get("bar"),
             Some(Program {
-                section: ProgramSection::TracePoint { .. },
+                section: ProgramSection::KRetProbeMulti { .. },
                 ..
             })
         );
     }
 
     #[test]
-    fn test_parse_section_socket_filter() {
+    fn test_parse_section_uprobe() {
         let mut obj = fake_obj();
 
         assert_matches!(
-            obj.parse_section(fake_section("socket_filter/foo", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("uprobe/foo", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::SocketFilter { .. },
+                section: ProgramSection::UProbe { .. },
                 ..
             })
         );
     }
 
     #[test]
-    fn test_parse_section_xdp() {
+    fn test_parse_section_usdt() {
         let mut obj = fake_obj();
 
         assert_matches!(
-            obj.parse_section(fake_section("xdp/foo", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("usdt/foo", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::Xdp { .. },
+                section: ProgramSection::Usdt { .. },
                 ..
             })
         );
     }
 
     #[test]
-    fn test_parse_section_raw_tp() {
+    fn test_parse_section_trace_point() {
         let mut obj = fake_obj();
 
         assert_matches!(
-            obj.parse_section(fake_section("raw_tp/foo", bytes_of(&fake_ins()))),
+            obj.parse_section(fake_section("tracepoint/foo", bytes_of(&fake_ins()))),
             Ok(())
         );
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::RawTracePoint { .. },
+                section: ProgramSection::TracePoint { .. },
+                ..
+            })
+        );
+
+        assert_matches!(
+            obj.parse_section(fake_section("tp/foo/bar", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo/bar"),
+            Some(Program {
+                section: ProgramSection::TracePoint { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_iproute2() {
+        let mut obj = fake_obj();
+
+        for section in &["classifier", "action/drop", "tc", "prog"] {
+            assert_matches!(
+                obj.parse_section(fake_section(section, bytes_of(&fake_ins()))),
+                Ok(())
+            );
+        }
+        for name in &["classifier", "drop", "tc"] {
+            assert_matches!(
+                obj.programs.get(*name),
+                Some(Program {
+                    section: ProgramSection::SchedClassifier { .. },
+                    ..
+                })
+            );
+        }
+        assert_matches!(
+            obj.programs.get("prog"),
+            Some(Program {
+                section: ProgramSection::Xdp { frags: false, .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_xdp_run_config() {
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, 0, 0));
+        // __uint(name, value) is int (*name)[value]
+        let mut uint = |value| {
+            let array_type_id = btf.add_type(BtfType::new_array(int_type_id, int_type_id, value));
+            btf.add_type(BtfType::new_ptr(array_type_id))
+        };
+        let (priority, drop, pass) = (uint(10), uint(1), uint(0));
+        let members = [
+            ("priority", priority),
+            ("XDP_DROP", drop),
+            ("XDP_PASS", pass),
+        ]
+        .iter()
+        .map(|(name, type_)| btf_member {
+            name_off: btf.add_string(name),
+            type_: *type_,
+            offset: 0,
+        })
+        .collect();
+        let struct_type_id = btf.add_type(BtfType::new_struct(0, members, 24));
+
+        assert_eq!(
+            parse_xdp_run_config(&btf, struct_type_id).unwrap(),
+            XdpRunConfig {
+                priority: 10,
+                chain_call_actions: XdpActions::DROP,
+            }
+        );
+        assert!(parse_xdp_run_config(&btf, int_type_id).is_err());
+    }
+
+    #[test]
+    fn test_parse_section_alias() {
+        let mut obj = fake_obj();
+        obj.section_aliases
+            .insert("my_xdp".to_owned(), "xdp".to_owned());
+        obj.section_aliases
+            .insert("prog".to_owned(), "classifier".to_owned());
+        obj.section_aliases
+            .insert("bogus".to_owned(), "not_a_kind".to_owned());
+
+        for section in &["my_xdp", "my_xdp/foo", "prog/bar"] {
+            assert_matches!(
+                obj.parse_section(fake_section(section, bytes_of(&fake_ins()))),
+                Ok(())
+            );
+        }
+        for name in &["my_xdp", "foo"] {
+            assert_matches!(
+                obj.programs.get(*name),
+                Some(Program {
+                    section: ProgramSection::Xdp { .. },
+                    ..
+                })
+            );
+        }
+        assert_matches!(
+            obj.programs.get("bar"),
+            Some(Program {
+                section: ProgramSection::SchedClassifier { .. },
+                ..
+            })
+        );
+        assert_matches!(
+            obj.parse_section(fake_section("bogus/baz", bytes_of(&fake_ins()))),
+            Err(BpfError::ParseError(ParseError::InvalidProgramSection { section })) if section == "bogus/baz"
+        );
+        // only whole components match
+        assert_eq!(
+            resolve_section_alias(&obj.section_aliases, "my_xdpfoo"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_section_socket_filter() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("socket_filter/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::SocketFilter { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_xdp() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("xdp/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::Xdp { frags: false, .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_xdp_frags() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("xdp.frags/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::Xdp { frags: true, .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_raw_tp() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("raw_tp/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::RawTracePoint { .. },
                 ..
             })
         );
@@ -1059,7 +2013,52 @@ mod tests {
         assert_matches!(
             obj.programs.get("foo"),
             Some(Program {
-                section: ProgramSection::Lsm { .. },
+                section: ProgramSection::Lsm {
+                    sleepable: false,
+                    ..
+                },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_sleepable() {
+        let mut obj = fake_obj();
+
+        for (section, name) in [
+            ("lsm.s/file_open", "file_open"),
+            ("fentry.s/do_unlinkat", "do_unlinkat"),
+            ("fexit.s/do_mkdirat", "do_mkdirat"),
+            ("uprobe.s/malloc", "malloc"),
+            ("uretprobe.s/free", "free"),
+        ] {
+            assert_matches!(
+                obj.parse_section(fake_section(section, bytes_of(&fake_ins()))),
+                Ok(())
+            );
+            assert!(obj.programs[name].section.sleepable(), "{}", section);
+        }
+
+        assert_matches!(
+            obj.parse_section(fake_section("fentry/do_rmdir", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert!(!obj.programs["do_rmdir"].section.sleepable());
+    }
+
+    #[test]
+    fn test_parse_section_lsm_cgroup() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("lsm_cgroup/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::LsmCgroup { .. },
                 ..
             })
         );
@@ -1082,6 +2081,134 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_section_flow_dissector() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("flow_dissector/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::FlowDissector { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_sk_reuseport() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("sk_reuseport/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::SkReuseport { .. },
+                ..
+            })
+        );
+
+        assert_matches!(
+            obj.parse_section(fake_section(
+                "sk_reuseport/migrate/bar",
+                bytes_of(&fake_ins())
+            )),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("bar"),
+            Some(Program {
+                section: ProgramSection::SkReuseportMigrate { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_netfilter() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("netfilter/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::Netfilter { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_fentry_fexit() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("fentry/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.parse_section(fake_section("fexit/bar", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::FEntry { .. },
+                ..
+            })
+        );
+        assert_matches!(
+            obj.programs.get("bar"),
+            Some(Program {
+                section: ProgramSection::FExit { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_iter() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("iter/task", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("task"),
+            Some(Program {
+                section: ProgramSection::Iter { .. },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_section_extension() {
+        let mut obj = fake_obj();
+
+        assert_matches!(
+            obj.parse_section(fake_section("freplace/foo", bytes_of(&fake_ins()))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::Extension { .. },
+                ..
+            })
+        );
+    }
+
     #[test]
     fn test_parse_section_skskb_unnamed() {
         let mut obj = fake_obj();
@@ -1118,4 +2245,193 @@ mod tests {
             })
         );
     }
+
+    fn fake_function_sym(
+        obj: &mut Object,
+        index: usize,
+        address: u64,
+        name: &str,
+        is_global: bool,
+    ) {
+        obj.symbols_by_index.insert(
+            index,
+            Symbol {
+                index,
+                section_index: Some(SectionIndex(0)),
+                name: Some(name.to_owned()),
+                address,
+                size: mem::size_of::<bpf_insn>() as u64,
+                is_definition: true,
+                is_text: true,
+                is_global,
+                is_weak: false,
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_section_program_with_subprogram() {
+        let mut obj = fake_obj();
+        fake_function_sym(&mut obj, 1, 0, "prog", true);
+        fake_function_sym(&mut obj, 2, 8, "helper", false);
+
+        let insns = [fake_ins(), fake_ins()];
+        assert_matches!(
+            obj.parse_section(fake_section("kprobe/foo", bytes_of(&insns))),
+            Ok(())
+        );
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::KProbe { .. },
+                function: Function {
+                    name,
+                    instructions,
+                    ..
+                },
+                ..
+            }) if name == "prog" && instructions.len() == 1
+        );
+        assert_matches!(
+            obj.functions.get(&(SectionIndex(0), 8)),
+            Some(Function {
+                name,
+                section_offset: 8,
+                ..
+            }) if name == "helper"
+        );
+    }
+
+    #[test]
+    fn test_parse_section_multiple_programs() {
+        let mut obj = fake_obj();
+        fake_function_sym(&mut obj, 1, 0, "foo", true);
+        fake_function_sym(&mut obj, 2, 8, "bar", true);
+        fake_function_sym(&mut obj, 3, 16, "helper", false);
+
+        let insns = [fake_ins(), fake_ins(), fake_ins()];
+        assert_matches!(
+            obj.parse_section(fake_section("xdp", bytes_of(&insns))),
+            Ok(())
+        );
+        assert_eq!(obj.programs.len(), 2);
+        assert_matches!(
+            obj.programs.get("foo"),
+            Some(Program {
+                section: ProgramSection::Xdp { .. },
+                function: Function {
+                    section_offset: 0,
+                    ..
+                },
+                ..
+            })
+        );
+        assert_matches!(
+            obj.programs.get("bar"),
+            Some(Program {
+                section: ProgramSection::Xdp { .. },
+                function: Function {
+                    section_offset: 8,
+                    ..
+                },
+                ..
+            })
+        );
+        assert_eq!(obj.functions.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_btf_map_of_maps() {
+        use crate::generated::{btf_member, btf_var_secinfo, BTF_INT_SIGNED};
+
+        let mut btf = Btf::new();
+        let name_offset = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+        let int_ptr = btf.add_type(BtfType::new_ptr(int));
+        // __uint(name, value)
+        let uint = |btf: &mut Btf, value| {
+            let array = btf.add_type(BtfType::new_array(int, int, value));
+            btf.add_type(BtfType::new_ptr(array))
+        };
+        let array_type = uint(&mut btf, BPF_MAP_TYPE_ARRAY as u32);
+        let hash_of_maps_type = uint(&mut btf, 13);
+        let one = uint(&mut btf, 1);
+        let eight = uint(&mut btf, 8);
+        let member = |btf: &mut Btf, name: &str, type_| btf_member {
+            name_off: btf.add_string(name),
+            type_,
+            offset: 0,
+        };
+
+        let members = vec![
+            member(&mut btf, "type", array_type),
+            member(&mut btf, "max_entries", one),
+            member(&mut btf, "key", int_ptr),
+            member(&mut btf, "value", int_ptr),
+        ];
+        let inner = btf.add_type(BtfType::new_struct(0, members, 32));
+        let inner_ptr = btf.add_type(BtfType::new_ptr(inner));
+        let values = btf.add_type(BtfType::new_array(inner_ptr, int, 0));
+        let members = vec![
+            member(&mut btf, "type", hash_of_maps_type),
+            member(&mut btf, "max_entries", eight),
+            member(&mut btf, "key", int_ptr),
+            member(&mut btf, "values", values),
+            member(&mut btf, "numa_node", one),
+        ];
+        let outer = btf.add_type(BtfType::new_struct(0, members, 32));
+        let name_offset = btf.add_string("outer");
+        let var = btf.add_type(BtfType::new_var(name_offset, outer, 1));
+        let name_offset = btf.add_string(".maps");
+        btf.add_type(BtfType::new_datasec(
+            name_offset,
+            vec![btf_var_secinfo {
+                type_: var,
+                offset: 16,
+                size: 32,
+            }],
+            48,
+        ));
+
+        let mut obj = fake_obj();
+        obj.btf = Some(btf);
+        obj.parse_btf_maps(SectionIndex(3)).unwrap();
+
+        let map = &obj.maps["outer"];
+        assert_eq!(map.section_index, 3);
+        assert_eq!(map.section_offset, Some(16));
+        assert_eq!(map.def.map_type, 13);
+        assert_eq!(map.def.max_entries, 8);
+        assert_eq!(map.def.key_size, 4);
+        assert_eq!(map.def.value_size, 4);
+        assert_eq!(map.numa_node, Some(1));
+        let inner = map.inner.as_ref().unwrap();
+        assert_eq!(inner.numa_node, None);
+        assert_eq!(inner.def.map_type, BPF_MAP_TYPE_ARRAY as u32);
+        assert_eq!(inner.def.max_entries, 1);
+        assert_eq!(inner.def.key_size, 4);
+        assert_eq!(inner.def.value_size, 4);
+    }
+
+    #[test]
+    fn test_metadata_sections() {
+        let mut obj = fake_obj();
+        fake_function_sym(&mut obj, 1, 0, "bpf_metadata_prog", true);
+        let data_sym = |index, section_index, name: &str| Symbol {
+            index,
+            section_index: Some(SectionIndex(section_index)),
+            name: Some(name.to_owned()),
+            address: 0,
+            size: 4,
+            is_definition: true,
+            is_text: false,
+            is_global: true,
+            is_weak: false,
+        };
+        obj.symbols_by_index
+            .insert(2, data_sym(2, 3, "bpf_metadata_version"));
+        obj.symbols_by_index.insert(3, data_sym(3, 4, "config"));
+
+        assert_eq!(obj.metadata_sections(), [3].iter().copied().collect());
+    }
 }
@@ -5,34 +5,59 @@ use thiserror::Error;
 
 use crate::{
     generated::{
-        bpf_insn, BPF_CALL, BPF_JMP, BPF_K, BPF_PSEUDO_CALL, BPF_PSEUDO_MAP_FD,
-        BPF_PSEUDO_MAP_VALUE,
+        bpf_func_info, bpf_insn, bpf_line_info, BPF_CALL, BPF_JMP, BPF_K, BPF_PSEUDO_BTF_ID,
+        BPF_PSEUDO_CALL, BPF_PSEUDO_MAP_FD, BPF_PSEUDO_MAP_VALUE,
     },
     maps::Map,
-    obj::{Function, Object, Program},
+    obj::{Function, KSymValue, Object, Program, SectionDescription, KCONFIG_SECTION},
     BpfError,
 };
 
-const INS_SIZE: usize = mem::size_of::<bpf_insn>();
+pub(crate) const INS_SIZE: usize = mem::size_of::<bpf_insn>();
 
 #[derive(Debug, Error)]
 enum RelocationError {
-    #[error("unknown symbol, index `{index}`")]
-    UnknownSymbol { index: usize },
+    #[error("unknown symbol `{}` #{index}, referenced in section `{section}` at offset {offset:#x}",
+            .symbol_name.as_deref().unwrap_or(""))]
+    UnknownSymbol {
+        index: usize,
+        symbol_name: Option<String>,
+        section: String,
+        offset: u64,
+    },
 
-    #[error("section `{section_index}` not found, referenced by symbol `{}` #{symbol_index}",
+    #[error("section `{section_index}` not found, referenced by symbol `{}` #{symbol_index} in section `{section}` at offset {offset:#x}",
             .symbol_name.clone().unwrap_or_else(|| "".to_string()))]
     SectionNotFound {
         section_index: usize,
         symbol_index: usize,
         symbol_name: Option<String>,
+        section: String,
+        offset: u64,
     },
 
-    #[error("function {address:#x} not found while relocating `{caller_name}`")]
-    UnknownFunction { address: u64, caller_name: String },
+    #[error("function `{}` {address:#x} in section `{callee_section}` not found while relocating `{caller_name}` in section `{section}` at offset {offset:#x}",
+            .symbol_name.as_deref().unwrap_or(""))]
+    UnknownFunction {
+        address: u64,
+        section_index: usize,
+        callee_section: String,
+        symbol_name: Option<String>,
+        caller_name: String,
+        section: String,
+        offset: u64,
+    },
 
-    #[error("the map `{name}` at section `{section_index}` has not been created")]
-    MapNotCreated { section_index: usize, name: String },
+    #[error("the map `{name}` at section `{map_section}`, referenced by symbol `{}` in section `{section}` at offset {offset:#x}, has not been created",
+            .symbol_name.as_deref().unwrap_or(""))]
+    MapNotCreated {
+        section_index: usize,
+        map_section: String,
+        name: String,
+        symbol_name: Option<String>,
+        section: String,
+        offset: u64,
+    },
 
     #[error("invalid offset `{offset}` applying relocation #{relocation_number}")]
     InvalidRelocationOffset {
@@ -41,6 +66,12 @@ enum RelocationError {
     },
 }
 
+// the values of the extern variables referenced by the programs
+struct Externs<'a> {
+    kconfig: Option<(&'a Map, HashMap<&'a str, usize>)>,
+    ksyms: HashMap<&'a str, KSymValue>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub(crate) struct Relocation {
     // byte offset of the instruction to be relocated
@@ -58,14 +89,38 @@ pub(crate) struct Symbol {
     pub(crate) size: u64,
     pub(crate) is_definition: bool,
     pub(crate) is_text: bool,
+    pub(crate) is_global: bool,
+    pub(crate) is_weak: bool,
 }
 
 impl Object {
     pub fn relocate_maps(&mut self, maps: &[Map]) -> Result<(), BpfError> {
-        let maps_by_section = maps
-            .iter()
-            .map(|map| (map.obj.section_index, map))
-            .collect::<HashMap<_, _>>();
+        let mut maps_by_section = HashMap::new();
+        let mut maps_by_symbol = HashMap::new();
+        for map in maps.iter().filter(|map| map.obj.name != KCONFIG_SECTION) {
+            match map.obj.section_offset {
+                Some(offset) => maps_by_symbol.insert((map.obj.section_index, offset), map),
+                None => maps_by_section.insert(map.obj.section_index, map),
+            };
+        }
+        let kconfig_externs = &self.kconfig;
+        let externs = Externs {
+            kconfig: maps
+                .iter()
+                .find(|map| map.obj.name == KCONFIG_SECTION)
+                .map(|map| {
+                    let offsets = kconfig_externs
+                        .iter()
+                        .map(|ext| (ext.name.as_str(), ext.offset))
+                        .collect::<HashMap<_, _>>();
+                    (map, offsets)
+                }),
+            ksyms: self
+                .ksyms
+                .iter()
+                .filter_map(|ext| ext.value.map(|value| (ext.name.as_str(), value)))
+                .collect(),
+        };
 
         let functions = self
             .programs
@@ -79,7 +134,10 @@ impl Object {
                     function,
                     relocations.values(),
                     &maps_by_section,
+                    &maps_by_symbol,
+                    &externs,
                     &self.symbols_by_index,
+                    &self.sections,
                 )
                 .map_err(|error| BpfError::RelocationError {
                     function: function.name.clone(),
@@ -93,8 +151,12 @@ impl Object {
 
     pub fn relocate_calls(&mut self) -> Result<(), BpfError> {
         for (name, program) in self.programs.iter_mut() {
-            let linker =
-                FunctionLinker::new(&self.functions, &self.relocations, &self.symbols_by_index);
+            let linker = FunctionLinker::new(
+                &self.functions,
+                &self.relocations,
+                &self.symbols_by_index,
+                &self.sections,
+            );
             linker
                 .link(program)
                 .map_err(|error| BpfError::RelocationError {
@@ -105,14 +167,38 @@ impl Object {
 
         Ok(())
     }
+
+    // links the calls of a program that was removed from the object
+    pub(crate) fn relocate_program_calls(
+        &self,
+        name: &str,
+        program: &mut Program,
+    ) -> Result<(), BpfError> {
+        FunctionLinker::new(
+            &self.functions,
+            &self.relocations,
+            &self.symbols_by_index,
+            &self.sections,
+        )
+        .link(program)
+        .map_err(|error| BpfError::RelocationError {
+            function: name.to_owned(),
+            error: Box::new(error),
+        })
+    }
 }
 
 fn relocate_maps<'a, I: Iterator<Item = &'a Relocation>>(
     fun: &mut Function,
     relocations: I,
     maps_by_section: &HashMap<usize, &Map>,
+    maps_by_symbol: &HashMap<(usize, u64), &Map>,
+    externs: &Externs,
     symbol_table: &HashMap<usize, Symbol>,
+    sections: &[SectionDescription],
 ) -> Result<(), RelocationError> {
+    let section_index = fun.section_index.0;
+    let section = || section_name(sections, section_index);
     let section_offset = fun.section_offset;
     let instructions = &mut fun.instructions;
     let function_size = instructions.len() * INS_SIZE;
@@ -140,35 +226,67 @@ fn relocate_maps<'a, I: Iterator<Item = &'a Relocation>>(
         }
 
         // a map relocation points to the ELF section that contains the map
-        let sym = symbol_table
-            .get(&rel.symbol_index)
-            .ok_or(RelocationError::UnknownSymbol {
-                index: rel.symbol_index,
-            })?;
-
-        let section_index = match sym.section_index {
-            Some(index) => index,
-            // this is not a map relocation
-            None => continue,
-        };
+        let sym =
+            symbol_table
+                .get(&rel.symbol_index)
+                .ok_or_else(|| RelocationError::UnknownSymbol {
+                    index: rel.symbol_index,
+                    symbol_name: None,
+                    section: section(),
+                    offset: rel.offset,
+                })?;
 
-        let map =
-            maps_by_section
-                .get(&section_index.0)
-                .ok_or(RelocationError::SectionNotFound {
-                    symbol_index: rel.symbol_index,
-                    symbol_name: sym.name.clone(),
-                    section_index: section_index.0,
+        let (map, address) = match sym.section_index {
+            // BTF-defined maps share their section, the symbol is the map
+            Some(section_index) if maps_by_symbol.contains_key(&(section_index.0, sym.address)) => {
+                (maps_by_symbol[&(section_index.0, sym.address)], 0)
+            }
+            Some(section_index) => {
+                let map = maps_by_section.get(&section_index.0).ok_or_else(|| {
+                    RelocationError::SectionNotFound {
+                        symbol_index: rel.symbol_index,
+                        symbol_name: sym.name.clone(),
+                        section_index: section_index.0,
+                        section: section(),
+                        offset: rel.offset,
+                    }
                 })?;
+                (*map, sym.address)
+            }
+            // undefined symbols are externs
+            None => {
+                let name = match &sym.name {
+                    Some(name) => name.as_str(),
+                    None => continue,
+                };
+                if let Some(value) = externs.ksyms.get(name) {
+                    relocate_ksym(&mut instructions[ins_index..ins_index + 2], *value);
+                    continue;
+                }
+                // kconfig values are stored in the .kconfig map
+                match &externs.kconfig {
+                    Some((map, offsets)) => match offsets.get(name) {
+                        Some(offset) => (*map, *offset as u64),
+                        None => continue,
+                    },
+                    // this is not a map relocation
+                    None => continue,
+                }
+            }
+        };
 
         let map_fd = map.fd.ok_or_else(|| RelocationError::MapNotCreated {
             name: map.obj.name.clone(),
-            section_index: section_index.0,
+            section_index: map.obj.section_index,
+            map_section: section_name(sections, map.obj.section_index),
+            symbol_name: sym.name.clone(),
+            section: section(),
+            offset: rel.offset,
         })?;
 
         if !map.obj.data.is_empty() {
             instructions[ins_index].set_src_reg(BPF_PSEUDO_MAP_VALUE as u8);
-            instructions[ins_index + 1].imm = instructions[ins_index].imm + sym.address as i32;
+            instructions[ins_index + 1].imm = instructions[ins_index].imm + address as i32;
         } else {
             instructions[ins_index].set_src_reg(BPF_PSEUDO_MAP_FD as u8);
         }
@@ -178,24 +296,43 @@ fn relocate_maps<'a, I: Iterator<Item = &'a Relocation>>(
     Ok(())
 }
 
+fn relocate_ksym(ld_imm64: &mut [bpf_insn], value: KSymValue) {
+    match value {
+        KSymValue::Address(address) => {
+            ld_imm64[0].set_src_reg(0);
+            ld_imm64[0].imm = address as i32;
+            ld_imm64[1].imm = (address >> 32) as i32;
+        }
+        KSymValue::BtfId(btf_id) => {
+            ld_imm64[0].set_src_reg(BPF_PSEUDO_BTF_ID as u8);
+            ld_imm64[0].imm = btf_id as i32;
+            // the fd of the BTF object, 0 for vmlinux
+            ld_imm64[1].imm = 0;
+        }
+    }
+}
+
 struct FunctionLinker<'a> {
-    functions: &'a HashMap<u64, Function>,
-    linked_functions: HashMap<u64, usize>,
+    functions: &'a HashMap<(SectionIndex, u64), Function>,
+    linked_functions: HashMap<(SectionIndex, u64), usize>,
     relocations: &'a HashMap<SectionIndex, HashMap<u64, Relocation>>,
     symbol_table: &'a HashMap<usize, Symbol>,
+    sections: &'a [SectionDescription],
 }
 
 impl<'a> FunctionLinker<'a> {
     fn new(
-        functions: &'a HashMap<u64, Function>,
+        functions: &'a HashMap<(SectionIndex, u64), Function>,
         relocations: &'a HashMap<SectionIndex, HashMap<u64, Relocation>>,
         symbol_table: &'a HashMap<usize, Symbol>,
+        sections: &'a [SectionDescription],
     ) -> FunctionLinker<'a> {
         FunctionLinker {
             functions,
             linked_functions: HashMap::new(),
             relocations,
             symbol_table,
+            sections,
         }
     }
 
@@ -205,6 +342,21 @@ impl<'a> FunctionLinker<'a> {
         // it will trigger linking in all the callees.
         self.relocate(&mut fun, &program.function)?;
 
+        // the kernel rejects func_info and line_info that don't describe every
+        // function, drop them if a function doesn't have any
+        let has_info = |f: &Function| !f.func_info.is_empty();
+        let has_lines = |f: &Function| f.line_info.first().map(|l| l.insn_off) == Some(0);
+        let linked = self
+            .linked_functions
+            .keys()
+            .filter_map(|key| self.functions.get(key));
+        if !has_info(&program.function) || linked.clone().any(|f| !has_info(f)) {
+            fun.func_info.clear();
+        }
+        if !has_lines(&program.function) || linked.clone().any(|f| !has_lines(f)) {
+            fun.line_info.clear();
+        }
+
         // this now includes the program function plus all the other functions called during
         // execution
         program.function = fun;
@@ -217,7 +369,8 @@ impl<'a> FunctionLinker<'a> {
         program: &mut Function,
         fun: &Function,
     ) -> Result<usize, RelocationError> {
-        if let Some(fun_ins_index) = self.linked_functions.get(&fun.address) {
+        let key = (fun.section_index, fun.address);
+        if let Some(fun_ins_index) = self.linked_functions.get(&key) {
             return Ok(*fun_ins_index);
         };
 
@@ -225,7 +378,19 @@ impl<'a> FunctionLinker<'a> {
         // at `start_ins`. We'll use `start_ins` to do pc-relative calls.
         let start_ins = program.instructions.len();
         program.instructions.extend(&fun.instructions);
-        self.linked_functions.insert(fun.address, start_ins);
+        program
+            .func_info
+            .extend(fun.func_info.iter().map(|info| bpf_func_info {
+                insn_off: info.insn_off + start_ins as u32,
+                ..*info
+            }));
+        program
+            .line_info
+            .extend(fun.line_info.iter().map(|info| bpf_line_info {
+                insn_off: info.insn_off + start_ins as u32,
+                ..*info
+            }));
+        self.linked_functions.insert(key, start_ins);
 
         // relocate `fun`, recursively linking in all the callees
         self.relocate(program, fun)?;
@@ -236,15 +401,17 @@ impl<'a> FunctionLinker<'a> {
     fn relocate(&mut self, program: &mut Function, fun: &Function) -> Result<(), RelocationError> {
         let relocations = self.relocations.get(&fun.section_index);
         let rel_info = |offset| relocations.and_then(|rels| rels.get(&offset));
-        let rel_target_address = |rel: &Relocation, symbol_table: &HashMap<usize, Symbol>| {
-            let sym =
-                symbol_table
-                    .get(&rel.symbol_index)
-                    .ok_or(RelocationError::UnknownSymbol {
-                        index: rel.symbol_index,
-                    })?;
-
-            Ok(sym.address)
+        let sections = self.sections;
+        let section = || section_name(sections, fun.section_index.0);
+        let rel_target_symbol = |rel: &Relocation, symbol_table: &'a HashMap<usize, Symbol>| {
+            symbol_table
+                .get(&rel.symbol_index)
+                .ok_or_else(|| RelocationError::UnknownSymbol {
+                    index: rel.symbol_index,
+                    symbol_name: None,
+                    section: section(),
+                    offset: rel.offset,
+                })
         };
 
         let n_instructions = fun.instructions.len();
@@ -257,31 +424,53 @@ impl<'a> FunctionLinker<'a> {
                 continue;
             }
 
-            let callee_address = if let Some(rel) =
-                rel_info((fun.section_offset + (ins_index - start_ins) * INS_SIZE) as u64)
-            {
-                // We have a relocation entry for the instruction at `ins_index`, the address of
-                // the callee is the address of the relocation's target symbol.
-                rel_target_address(rel, self.symbol_table)?
+            let ins_size = INS_SIZE as i64;
+            let imm = program.instructions[ins_index].imm as i64;
+            let offset = (fun.section_offset + (ins_index - start_ins) * INS_SIZE) as u64;
+            let mut symbol_name = None;
+            let (callee_section, callee_address) = if let Some(rel) = rel_info(offset) {
+                // We have a relocation entry for the instruction at `ins_index`, the callee is in
+                // the section of the relocation's target symbol. Calls to global functions target
+                // the function's symbol and have imm = -1, calls to static functions target the
+                // section symbol and the imm holds the offset of the callee in the section.
+                let sym = rel_target_symbol(rel, self.symbol_table)?;
+                symbol_name = sym.name.clone();
+                let section_index =
+                    sym.section_index
+                        .ok_or_else(|| RelocationError::UnknownSymbol {
+                            index: rel.symbol_index,
+                            symbol_name: sym.name.clone(),
+                            section: section(),
+                            offset,
+                        })?;
+                (
+                    section_index,
+                    (sym.address as i64 + (imm + 1) * ins_size) as u64,
+                )
             } else {
                 // The caller and the callee are in the same ELF section and this is a pc-relative
                 // call. Resolve the pc-relative imm to an absolute address.
-                let ins_size = INS_SIZE as i64;
-                (fun.section_offset as i64
-                    + ((ins_index - start_ins) as i64) * ins_size
-                    + (program.instructions[ins_index].imm + 1) as i64 * ins_size)
-                    as u64
+                (
+                    fun.section_index,
+                    (fun.address as i64 + ((ins_index - start_ins) as i64 + imm + 1) * ins_size)
+                        as u64,
+                )
             };
 
             // lookup and link the callee if it hasn't been linked already. `callee_ins_index` will
             // contain the instruction index of the callee inside the program.
-            let callee =
-                self.functions
-                    .get(&callee_address)
-                    .ok_or(RelocationError::UnknownFunction {
-                        address: callee_address,
-                        caller_name: fun.name.clone(),
-                    })?;
+            let callee = self
+                .functions
+                .get(&(callee_section, callee_address))
+                .ok_or_else(|| RelocationError::UnknownFunction {
+                    address: callee_address,
+                    section_index: callee_section.0,
+                    callee_section: section_name(self.sections, callee_section.0),
+                    symbol_name,
+                    caller_name: fun.name.clone(),
+                    section: section(),
+                    offset,
+                })?;
             let callee_ins_index = self.link_function(program, callee)?;
 
             let mut ins = &mut program.instructions[ins_index];
@@ -296,6 +485,16 @@ impl<'a> FunctionLinker<'a> {
     }
 }
 
+// the name of the section `index` for the error messages, or its index if the
+// section is unknown
+fn section_name(sections: &[SectionDescription], index: usize) -> String {
+    sections
+        .iter()
+        .find(|section| section.index == index)
+        .map(|section| section.name.clone())
+        .unwrap_or_else(|| index.to_string())
+}
+
 fn is_call(ins: &bpf_insn) -> bool {
     let klass = (ins.code & 0x07) as u32;
     let op = (ins.code & 0xF0) as u32;
@@ -308,3 +507,270 @@ fn is_call(ins: &bpf_insn) -> bool {
         && ins.dst_reg() == 0
         && ins.off == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use object::Endianness;
+
+    use super::*;
+    use crate::{
+        generated::{BPF_DW, BPF_EXIT, BPF_IMM, BPF_LD},
+        obj::{parse_map, KernelVersion, ProgramSection, Section},
+    };
+
+    fn ins(code: u32, src_reg: u32, imm: i32) -> bpf_insn {
+        bpf_insn {
+            code: code as u8,
+            _bitfield_align_1: [],
+            _bitfield_1: bpf_insn::new_bitfield_1(0, src_reg as u8),
+            off: 0,
+            imm,
+        }
+    }
+
+    fn function(
+        name: &str,
+        section_index: usize,
+        address: u64,
+        instructions: Vec<bpf_insn>,
+    ) -> Function {
+        Function {
+            address,
+            name: name.to_owned(),
+            section_index: SectionIndex(section_index),
+            section_offset: address as usize,
+            instructions,
+            func_info: Vec::new(),
+            line_info: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_relocate_static_call_to_other_section() {
+        let mut obj = Object::new(
+            Endianness::Little,
+            CString::new("GPL").unwrap(),
+            KernelVersion::Any,
+        );
+        let call = ins(BPF_JMP | BPF_CALL, BPF_PSEUDO_CALL, 0);
+        let exit = ins(BPF_JMP | BPF_EXIT, 0, 0);
+
+        // the program calls the second function of .text, through a
+        // relocation against the .text section symbol
+        obj.programs.insert(
+            "foo".to_owned(),
+            Program {
+                license: CString::new("GPL").unwrap(),
+                kernel_version: KernelVersion::Any,
+                section: ProgramSection::KProbe {
+                    name: "foo".to_owned(),
+                },
+                function: function("foo", 1, 0, vec![call, exit]),
+            },
+        );
+        for (address, name) in [(0, "first"), (8, "second")] {
+            obj.functions.insert(
+                (SectionIndex(2), address),
+                function(name, 2, address, vec![exit]),
+            );
+        }
+        obj.symbols_by_index.insert(
+            1,
+            Symbol {
+                index: 1,
+                section_index: Some(SectionIndex(2)),
+                name: Some(".text".to_owned()),
+                address: 0,
+                size: 0,
+                is_definition: true,
+                is_text: false,
+                is_global: false,
+                is_weak: false,
+            },
+        );
+        obj.relocations.insert(
+            SectionIndex(1),
+            vec![(
+                0,
+                Relocation {
+                    offset: 0,
+                    symbol_index: 1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        obj.relocate_calls().unwrap();
+
+        let instructions = &obj.programs["foo"].function.instructions;
+        assert_eq!(instructions.len(), 3);
+        // the callee is appended after the program, right after the exit
+        assert_eq!(instructions[0].imm, 1);
+    }
+
+    #[test]
+    fn test_relocate_unknown_function() {
+        let mut obj = Object::new(
+            Endianness::Little,
+            CString::new("GPL").unwrap(),
+            KernelVersion::Any,
+        );
+        let call = ins(BPF_JMP | BPF_CALL, BPF_PSEUDO_CALL, 1);
+        let exit = ins(BPF_JMP | BPF_EXIT, 0, 0);
+
+        // the program calls a function at .text+16, which doesn't exist
+        obj.programs.insert(
+            "foo".to_owned(),
+            Program {
+                license: CString::new("GPL").unwrap(),
+                kernel_version: KernelVersion::Any,
+                section: ProgramSection::KProbe {
+                    name: "foo".to_owned(),
+                },
+                function: function("foo", 1, 0, vec![exit, call, exit]),
+            },
+        );
+        for (index, name) in [(1, "kprobe/foo"), (2, ".text")] {
+            obj.sections.push(SectionDescription {
+                index,
+                name: name.to_owned(),
+                size: 0,
+                relocations: 0,
+            });
+        }
+        obj.symbols_by_index.insert(
+            1,
+            Symbol {
+                index: 1,
+                section_index: Some(SectionIndex(2)),
+                name: Some(".text".to_owned()),
+                address: 0,
+                size: 0,
+                is_definition: true,
+                is_text: false,
+                is_global: false,
+                is_weak: false,
+            },
+        );
+        obj.relocations.insert(
+            SectionIndex(1),
+            vec![(
+                8,
+                Relocation {
+                    offset: 8,
+                    symbol_index: 1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        match obj.relocate_calls() {
+            Err(BpfError::RelocationError { function, error }) => {
+                assert_eq!(function, "foo");
+                assert_eq!(
+                    error.to_string(),
+                    "function `.text` 0x10 in section `.text` not found while relocating `foo` \
+                     in section `kprobe/foo` at offset 0x8"
+                );
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_relocate_custom_data_sections() {
+        let mut obj = Object::new(
+            Endianness::Little,
+            CString::new("GPL").unwrap(),
+            KernelVersion::Any,
+        );
+        let ld = ins(BPF_LD | BPF_IMM | BPF_DW, 0, 0);
+        let exit = ins(BPF_JMP | BPF_EXIT, 0, 0);
+
+        // the program loads the address of a variable in .data.settings, then
+        // of one in .bss.counters
+        obj.programs.insert(
+            "foo".to_owned(),
+            Program {
+                license: CString::new("GPL").unwrap(),
+                kernel_version: KernelVersion::Any,
+                section: ProgramSection::KProbe {
+                    name: "foo".to_owned(),
+                },
+                function: function("foo", 1, 0, vec![ld, ld, ld, ld, exit]),
+            },
+        );
+        let mut maps = Vec::new();
+        for (index, name, fd) in [(2, ".data.settings", 10), (3, ".bss.counters", 11)] {
+            let section = Section {
+                index: SectionIndex(index),
+                address: 0,
+                name,
+                data: if name == ".bss.counters" {
+                    &[]
+                } else {
+                    &[1; 16]
+                },
+                size: 16,
+                relocations: Vec::new(),
+            };
+            let mut map = parse_map(&section, name).unwrap();
+            map.section_index = index;
+            maps.push(crate::maps::Map {
+                obj: map,
+                fd: Some(fd),
+                pinned: false,
+            });
+            obj.symbols_by_index.insert(
+                index,
+                Symbol {
+                    index,
+                    section_index: Some(SectionIndex(index)),
+                    name: Some(format!("var{}", index)),
+                    address: 4 * index as u64,
+                    size: 4,
+                    is_definition: true,
+                    is_text: false,
+                    is_global: true,
+                    is_weak: false,
+                },
+            );
+        }
+        obj.relocations.insert(
+            SectionIndex(1),
+            vec![(0, 2), (16, 3)]
+                .into_iter()
+                .map(|(offset, symbol_index)| {
+                    (
+                        offset,
+                        Relocation {
+                            offset,
+                            symbol_index,
+                        },
+                    )
+                })
+                .collect(),
+        );
+
+        let ret = obj.relocate_maps(&maps);
+        // the maps don't own real descriptors
+        for map in &mut maps {
+            map.fd = None;
+        }
+        ret.unwrap();
+
+        let instructions = &obj.programs["foo"].function.instructions;
+        for (ins_index, fd, address) in [(0, 10, 8), (2, 11, 12)] {
+            assert_eq!(
+                instructions[ins_index].src_reg() as u32,
+                BPF_PSEUDO_MAP_VALUE
+            );
+            assert_eq!(instructions[ins_index].imm, fd);
+            assert_eq!(instructions[ins_index + 1].imm, address);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Cgroup v2 hierarchy helpers.
+use libc::{pid_t, O_DIRECTORY};
+use std::{
+    fs::{self, File, OpenOptions},
+    io, mem,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+const PROC_MOUNTS: &str = "/proc/self/mounts";
+const DEFAULT_CGROUP2_PATH: &str = "/sys/fs/cgroup";
+// see include/uapi/linux/magic.h
+const CGROUP2_SUPER_MAGIC: i64 = 0x6367_7270;
+
+/// The error type returned when resolving or opening cgroups.
+#[derive(Debug, Error)]
+pub enum CgroupError {
+    /// Opening the cgroup directory failed.
+    #[error("error opening cgroup `{}`", path.display())]
+    OpenError {
+        /// The path of the cgroup.
+        path: PathBuf,
+        #[source]
+        /// The original [`io::Error`].
+        io_error: io::Error,
+    },
+
+    /// Reading the cgroup membership of a process failed.
+    #[error("error reading the cgroup of process {pid}")]
+    ProcessCgroupError {
+        /// The process id.
+        pid: pid_t,
+        #[source]
+        /// The original [`io::Error`].
+        io_error: io::Error,
+    },
+
+    /// The path isn't a directory of the cgroup2 filesystem.
+    #[error("`{}` is not a cgroup v2 directory", path.display())]
+    NotCgroup2 {
+        /// The path of the directory.
+        path: PathBuf,
+    },
+
+    /// The process doesn't belong to a cgroup v2 hierarchy.
+    #[error("process {pid} doesn't belong to a cgroup v2 hierarchy")]
+    NoUnifiedCgroup {
+        /// The process id.
+        pid: pid_t,
+    },
+
+    /// Listing the descendants of a cgroup failed.
+    #[error("error listing the descendants of cgroup `{}`", path.display())]
+    ReadDirError {
+        /// The path of the cgroup directory that couldn't be read.
+        path: PathBuf,
+        #[source]
+        /// The original [`io::Error`].
+        io_error: io::Error,
+    },
+}
+
+/// An open cgroup v2 directory.
+///
+/// Cgroup programs such as [`CgroupSkb`](crate::programs::CgroupSkb) and
+/// [`SockOps`](crate::programs::SockOps) are attached to a cgroup file
+/// descriptor. [`Cgroup`] resolves and opens cgroups by path or by process,
+/// and can be passed directly to the `attach()` methods of those programs.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     Cgroup(#[from] aya::programs::CgroupError),
+/// #     #[error(transparent)]
+/// #     Program(#[from] aya::programs::ProgramError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use aya::programs::{Cgroup, CgroupAttachFlags, CgroupSkb, CgroupSkbAttachType};
+///
+/// // the cgroup the current process belongs to
+/// let cgroup = Cgroup::of_process(std::process::id() as i32)?;
+/// let egress = bpf.program_mut_typed::<CgroupSkb>("egress_filter")?;
+/// egress.load()?;
+/// egress.attach(cgroup, CgroupSkbAttachType::Egress, CgroupAttachFlags::ALLOW_MULTI)?;
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+    file: File,
+}
+
+impl Cgroup {
+    /// Opens the cgroup at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CgroupError::NotCgroup2`] if `path` is not a directory of the
+    /// cgroup2 filesystem, since programs can't be attached to cgroup v1
+    /// hierarchies.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Cgroup, CgroupError> {
+        let path = path.as_ref();
+        let open_error = |io_error| CgroupError::OpenError {
+            path: path.to_owned(),
+            io_error,
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(O_DIRECTORY)
+            .open(path)
+            .map_err(open_error)?;
+
+        let mut stat = unsafe { mem::zeroed::<libc::statfs>() };
+        if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } < 0 {
+            return Err(open_error(io::Error::last_os_error()));
+        }
+        if stat.f_type as i64 != CGROUP2_SUPER_MAGIC {
+            return Err(CgroupError::NotCgroup2 {
+                path: path.to_owned(),
+            });
+        }
+
+        Ok(Cgroup {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    /// Opens the root cgroup of the cgroup2 filesystem.
+    pub fn root() -> Result<Cgroup, CgroupError> {
+        Cgroup::open(Cgroup::mount_point())
+    }
+
+    /// Opens the cgroup v2 the process `pid` belongs to.
+    ///
+    /// See [`Cgroup::path_of_process`].
+    pub fn of_process(pid: pid_t) -> Result<Cgroup, CgroupError> {
+        Cgroup::open(Cgroup::path_of_process(pid)?)
+    }
+
+    /// Returns the path of the cgroup v2 the process `pid` belongs to.
+    ///
+    /// The cgroup is resolved from `/proc/<pid>/cgroup`, relative to the
+    /// mount point of the cgroup2 filesystem.
+    pub fn path_of_process(pid: pid_t) -> Result<PathBuf, CgroupError> {
+        let data = fs::read_to_string(format!("/proc/{}/cgroup", pid))
+            .map_err(|io_error| CgroupError::ProcessCgroupError { pid, io_error })?;
+        let relative = parse_unified_cgroup(&data).ok_or(CgroupError::NoUnifiedCgroup { pid })?;
+
+        let mut path = Cgroup::mount_point();
+        let relative = relative.trim_start_matches('/');
+        // joining an empty path would add a trailing slash
+        if !relative.is_empty() {
+            path.push(relative);
+        }
+        Ok(path)
+    }
+
+    /// Returns the mount point of the cgroup2 filesystem.
+    ///
+    /// The mount point is looked up in `/proc/self/mounts`, falling back to
+    /// `/sys/fs/cgroup` if the cgroup2 filesystem isn't listed.
+    pub fn mount_point() -> PathBuf {
+        fs::read_to_string(PROC_MOUNTS)
+            .ok()
+            .and_then(|mounts| parse_cgroup2_mount(&mounts))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CGROUP2_PATH))
+    }
+
+    /// Returns the path of the cgroup.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the paths of all the cgroups below this one in the hierarchy.
+    ///
+    /// Parents are listed before their children.
+    pub fn descendants(&self) -> Result<Vec<PathBuf>, CgroupError> {
+        let mut descendants = Vec::new();
+        let mut pending = vec![self.path.clone()];
+        while let Some(dir) = pending.pop() {
+            let read_dir_error = |io_error| CgroupError::ReadDirError {
+                path: dir.clone(),
+                io_error,
+            };
+            for entry in fs::read_dir(&dir).map_err(read_dir_error)? {
+                let entry = entry.map_err(read_dir_error)?;
+                if entry.file_type().map_err(read_dir_error)?.is_dir() {
+                    let path = entry.path();
+                    descendants.push(path.clone());
+                    pending.push(path);
+                }
+            }
+        }
+
+        Ok(descendants)
+    }
+}
+
+impl AsRawFd for Cgroup {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+fn parse_cgroup2_mount(mounts: &str) -> Option<PathBuf> {
+    mounts.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let mount_point = parts.nth(1)?;
+        match parts.next()? {
+            "cgroup2" => Some(PathBuf::from(mount_point)),
+            _ => None,
+        }
+    })
+}
+
+// the unified hierarchy is the `0::<path>` entry
+fn parse_unified_cgroup(data: &str) -> Option<&str> {
+    data.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unified_cgroup() {
+        let data =
+            "12:pids:/user.slice\n1:name=systemd:/user.slice\n0::/user.slice/session-1.scope\n";
+        assert_eq!(
+            parse_unified_cgroup(data),
+            Some("/user.slice/session-1.scope")
+        );
+        assert_eq!(parse_unified_cgroup("1:name=systemd:/\n"), None);
+    }
+
+    #[test]
+    fn test_parse_cgroup2_mount() {
+        let mounts = "proc /proc proc rw,nosuid 0 0\n\
+                      cgroup /sys/fs/cgroup/cpu cgroup rw,cpu 0 0\n\
+                      cgroup2 /sys/fs/cgroup/unified cgroup2 rw,nosuid 0 0\n";
+        assert_eq!(
+            parse_cgroup2_mount(mounts),
+            Some(PathBuf::from("/sys/fs/cgroup/unified"))
+        );
+        assert_eq!(parse_cgroup2_mount("proc /proc proc rw 0 0\n"), None);
+    }
+}
@@ -1,11 +1,17 @@
-use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::{
+    os::unix::prelude::{AsRawFd, RawFd},
+    path::Path,
+};
 
 use crate::{
     generated::{
-        bpf_attach_type::{BPF_CGROUP_INET_EGRESS, BPF_CGROUP_INET_INGRESS},
+        bpf_attach_type::{self, BPF_CGROUP_INET_EGRESS, BPF_CGROUP_INET_INGRESS},
         bpf_prog_type::BPF_PROG_TYPE_CGROUP_SKB,
     },
-    programs::{load_program, LinkRef, ProgAttachLink, ProgramData, ProgramError},
+    programs::{
+        load_program, query_cgroup, Cgroup, CgroupAttachFlags, CgroupPrograms, LinkRef,
+        ProgAttachLink, ProgramData, ProgramError,
+    },
     sys::{bpf_link_create, bpf_prog_attach, kernel_version},
 };
 
@@ -39,13 +45,12 @@ use super::FdLink;
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
 /// use std::fs::File;
-/// use std::convert::TryInto;
-/// use aya::programs::{CgroupSkb, CgroupSkbAttachType};
+/// use aya::programs::{CgroupAttachFlags, CgroupSkb, CgroupSkbAttachType};
 ///
 /// let file = File::open("/sys/fs/cgroup/unified")?;
-/// let egress: &mut CgroupSkb = bpf.program_mut("egress_filter")?.try_into()?;
+/// let egress = bpf.program_mut_typed::<CgroupSkb>("egress_filter")?;
 /// egress.load()?;
-/// egress.attach(file, CgroupSkbAttachType::Egress)?;
+/// egress.attach(file, CgroupSkbAttachType::Egress, CgroupAttachFlags::ALLOW_MULTI)?;
 /// # Ok::<(), Error>(())
 /// ```
 #[derive(Debug)]
@@ -79,20 +84,23 @@ impl CgroupSkb {
     }
 
     /// Attaches the program to the given cgroup.
+    ///
+    /// Pass [`CgroupAttachFlags::ALLOW_MULTI`] to let the program coexist with
+    /// the programs attached to the same cgroup by other processes. On kernels
+    /// 5.7 and newer, attaching with no flags or with `ALLOW_MULTI` creates a
+    /// `bpf_link`, which always behaves like `ALLOW_MULTI`.
     pub fn attach<T: AsRawFd>(
         &mut self,
         cgroup: T,
         attach_type: CgroupSkbAttachType,
+        flags: CgroupAttachFlags,
     ) -> Result<LinkRef, ProgramError> {
         let prog_fd = self.data.fd_or_err()?;
         let cgroup_fd = cgroup.as_raw_fd();
 
-        let attach_type = match attach_type {
-            CgroupSkbAttachType::Ingress => BPF_CGROUP_INET_INGRESS,
-            CgroupSkbAttachType::Egress => BPF_CGROUP_INET_EGRESS,
-        };
+        let attach_type = attach_type.bpf_attach_type();
         let k_ver = kernel_version().unwrap();
-        if k_ver >= (5, 7, 0) {
+        if k_ver >= (5, 7, 0) && (flags.is_empty() || flags == CgroupAttachFlags::ALLOW_MULTI) {
             let link_fd =
                 bpf_link_create(prog_fd, cgroup_fd, attach_type, 0).map_err(|(_, io_error)| {
                     ProgramError::SyscallError {
@@ -102,18 +110,59 @@ impl CgroupSkb {
                 })? as RawFd;
             Ok(self.data.link(FdLink { fd: Some(link_fd) }))
         } else {
-            bpf_prog_attach(prog_fd, cgroup_fd, attach_type).map_err(|(_, io_error)| {
-                ProgramError::SyscallError {
+            bpf_prog_attach(prog_fd, cgroup_fd, attach_type, flags.bits()).map_err(
+                |(_, io_error)| ProgramError::SyscallError {
                     call: "bpf_prog_attach".to_owned(),
                     io_error,
-                }
-            })?;
+                },
+            )?;
 
             Ok(self
                 .data
                 .link(ProgAttachLink::new(prog_fd, cgroup_fd, attach_type)))
         }
     }
+
+    /// Attaches the program to the cgroup at `path`.
+    ///
+    /// See [`CgroupSkb::attach`] for the meaning of the other arguments.
+    pub fn attach_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        attach_type: CgroupSkbAttachType,
+        flags: CgroupAttachFlags,
+    ) -> Result<LinkRef, ProgramError> {
+        let cgroup = Cgroup::open(path)?;
+        self.attach(cgroup, attach_type, flags)
+    }
+
+    /// Returns the programs attached to the given cgroup for `attach_type`.
+    ///
+    /// Only the programs attached directly to the cgroup are returned, not
+    /// the ones inherited from its ancestors. Querying the attach flags first
+    /// tells whether [`CgroupAttachFlags::ALLOW_MULTI`] is needed to attach
+    /// next to the existing programs.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::programs::{Cgroup, CgroupAttachFlags, CgroupSkb, CgroupSkbAttachType};
+    ///
+    /// let cgroup = Cgroup::open("/sys/fs/cgroup/unified")?;
+    /// let programs = CgroupSkb::query(cgroup, CgroupSkbAttachType::Egress)?;
+    /// if !programs.program_ids.is_empty()
+    ///     && !programs.attach_flags.contains(CgroupAttachFlags::ALLOW_MULTI)
+    /// {
+    ///     println!("egress is owned by programs {:?}", programs.program_ids);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn query<T: AsRawFd>(
+        cgroup: T,
+        attach_type: CgroupSkbAttachType,
+    ) -> Result<CgroupPrograms, ProgramError> {
+        query_cgroup(cgroup, attach_type.bpf_attach_type())
+    }
 }
 
 /// Defines where to attach a [`CgroupSkb`] program.
@@ -124,3 +173,12 @@ pub enum CgroupSkbAttachType {
     /// Attach to egress.
     Egress,
 }
+
+impl CgroupSkbAttachType {
+    fn bpf_attach_type(self) -> bpf_attach_type {
+        match self {
+            CgroupSkbAttachType::Ingress => BPF_CGROUP_INET_INGRESS,
+            CgroupSkbAttachType::Egress => BPF_CGROUP_INET_EGRESS,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Extension programs.
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+
+use libc::close;
+use object::Endianness;
+use thiserror::Error;
+
+use crate::{
+    generated::{bpf_prog_info, bpf_prog_type::BPF_PROG_TYPE_EXT},
+    obj::btf::{Btf, BtfError, BtfKind},
+    programs::{load_program, FdLink, LinkRef, ProgramData, ProgramError, ProgramFd},
+    sys::{
+        bpf_btf_get_data_by_fd, bpf_btf_get_fd_by_id, bpf_prog_get_info_by_fd,
+        bpf_raw_tracepoint_open,
+    },
+};
+
+/// A program that replaces a global function of another eBPF program, also
+/// known as `freplace`.
+///
+/// Extension programs are loaded against a target: a loaded program and the
+/// name of one of its global functions. Once attached, calls to the function
+/// run the extension instead. The target can be set with
+/// [`BpfLoader::extension_target`](crate::BpfLoader::extension_target) or
+/// [`Extension::set_target`] before loading the extension.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.6.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::{BpfLoader, programs::{Extension, ProgramFd, Xdp, XdpFlags}};
+///
+/// let mut dispatcher = aya::Bpf::load_file("dispatcher.o")?;
+/// let xdp = dispatcher.program_mut_typed::<Xdp>("dispatcher")?;
+/// xdp.load()?;
+/// xdp.attach("eth0", XdpFlags::default())?;
+/// let dispatcher_fd = xdp.fd().unwrap();
+///
+/// let mut bpf = BpfLoader::new()
+///     // replace the `slot0` function of the dispatcher
+///     .extension_target("filter", dispatcher_fd, "slot0")
+///     .load_file("filter.o")?;
+/// let program = bpf.program_mut_typed::<Extension>("filter")?;
+/// program.load()?;
+/// program.attach()?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_PROG_TYPE_EXT")]
+pub struct Extension {
+    pub(crate) data: ProgramData,
+    pub(crate) target: Option<(OwnedFd, String)>,
+}
+
+/// Error type returned when loading [`Extension`] programs.
+#[derive(Debug, Error)]
+pub enum ExtensionError {
+    /// The target program wasn't loaded with BTF information, so its
+    /// functions can't be replaced.
+    #[error("the target program has no BTF information")]
+    NoBtf,
+
+    /// An error occurred while working with the BTF of the target program.
+    #[error(transparent)]
+    Btf(#[from] BtfError),
+}
+
+impl Extension {
+    /// Sets the function replaced by the extension: `func_name` in the
+    /// loaded program `program`.
+    ///
+    /// The descriptor of `program` is duplicated, so `program` doesn't need to
+    /// stay open until the extension is loaded.
+    pub fn set_target<P: ProgramFd>(
+        &mut self,
+        program: &P,
+        func_name: &str,
+    ) -> Result<(), ProgramError> {
+        self.target = Some((program.try_clone_fd()?, func_name.to_owned()));
+        Ok(())
+    }
+
+    /// Loads the program inside the kernel.
+    ///
+    /// The BTF id of the target function is resolved from the BTF of the
+    /// target program.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    ///
+    /// # Errors
+    ///
+    /// [`ProgramError::NoAttachTarget`] is returned if no target was set.
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        let (target_fd, func_name) = match &self.target {
+            Some((fd, func_name)) => (fd.as_raw_fd(), func_name.as_str()),
+            None => {
+                return Err(ProgramError::NoAttachTarget {
+                    name: self.data.name.clone(),
+                })
+            }
+        };
+        let btf = program_btf(target_fd)?;
+        let btf_id = btf
+            .id_by_type_name_kind(func_name, BtfKind::Func)
+            .map_err(ExtensionError::from)?;
+
+        self.data.attach_btf_id = Some(btf_id);
+        self.data.attach_prog_fd = Some(target_fd);
+        load_program(BPF_PROG_TYPE_EXT, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the extension, replacing the target function.
+    pub fn attach(&mut self) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+
+        // the target is specified at program load time
+        let pfd = bpf_raw_tracepoint_open(None, prog_fd).map_err(|(_code, io_error)| {
+            ProgramError::SyscallError {
+                call: "bpf_raw_tracepoint_open".to_owned(),
+                io_error,
+            }
+        })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(pfd) }))
+    }
+}
+
+/// Returns the BTF the program `prog_fd` was loaded with.
+fn program_btf(prog_fd: RawFd) -> Result<Btf, ProgramError> {
+    let syscall_error = |call: &str, io_error| ProgramError::SyscallError {
+        call: call.to_owned(),
+        io_error,
+    };
+
+    let mut info = unsafe { std::mem::zeroed::<bpf_prog_info>() };
+    bpf_prog_get_info_by_fd(prog_fd, &mut info)
+        .map_err(|e| syscall_error("bpf_prog_get_info_by_fd", e))?;
+    if info.btf_id == 0 {
+        return Err(ExtensionError::NoBtf.into());
+    }
+
+    let btf_fd =
+        bpf_btf_get_fd_by_id(info.btf_id).map_err(|e| syscall_error("bpf_btf_get_fd_by_id", e))?;
+    let data = bpf_btf_get_data_by_fd(btf_fd);
+    unsafe { close(btf_fd) };
+    let data = data.map_err(|e| syscall_error("bpf_btf_get_data_by_fd", e))?;
+
+    Btf::parse(&data, Endianness::default()).map_err(|e| ExtensionError::from(e).into())
+}
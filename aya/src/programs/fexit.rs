@@ -0,0 +1,94 @@
+//! Fexit programs.
+use std::os::unix::io::RawFd;
+
+use crate::{
+    generated::{bpf_attach_type::BPF_TRACE_FEXIT, bpf_prog_type::BPF_PROG_TYPE_TRACING},
+    obj::btf::{Btf, BtfError, BtfFuncArg, BtfKind},
+    programs::{load_btf_program, resolve_btf_type, FdLink, LinkRef, ProgramData, ProgramError},
+    sys::bpf_raw_tracepoint_open,
+};
+
+/// A program that can be attached to the exit point of (almost) any kernel
+/// function.
+///
+/// [`FExit`] programs are similar to [kretprobes](crate::programs::KProbe),
+/// but they have practically zero overhead and can access both the arguments
+/// and the return value of the function. Fexit programs can be also attached
+/// to other eBPF programs.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.5.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{programs::FExit, Btf};
+///
+/// let btf = Btf::from_sys_fs()?;
+/// let program = bpf.program_mut_typed::<FExit>("filename_lookup")?;
+/// program.load("filename_lookup", &btf)?;
+/// program.attach()?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "fexit")]
+#[doc(alias = "BPF_TRACE_FEXIT")]
+pub struct FExit {
+    pub(crate) data: ProgramData,
+}
+
+impl FExit {
+    /// Loads the program inside the kernel.
+    ///
+    /// Loads the program so it's executed when the kernel function `fn_name`
+    /// returns. If `btf` is the BTF of the running kernel and the function
+    /// is defined by a kernel module, the program is loaded against the BTF of
+    /// the module.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self, fn_name: &str, btf: &Btf) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_TRACE_FEXIT);
+        let (type_id, module_btf) = resolve_btf_type(btf, fn_name, BtfKind::Func)?;
+        self.data.attach_btf_id = Some(type_id);
+        load_btf_program(
+            BPF_PROG_TYPE_TRACING,
+            &mut self.data,
+            module_btf.as_ref().unwrap_or(btf),
+        )
+    }
+
+    /// Returns the arguments of the kernel function `fn_name`, as described
+    /// by `btf`.
+    ///
+    /// The program also receives the return value of the function, after the
+    /// arguments.
+    ///
+    /// Like [`FExit::load`], functions defined in kernel modules are looked up
+    /// in the BTF of the loaded modules.
+    pub fn args(fn_name: &str, btf: &Btf) -> Result<Vec<BtfFuncArg>, BtfError> {
+        let (type_id, module_btf) = resolve_btf_type(btf, fn_name, BtfKind::Func)?;
+        module_btf.as_ref().unwrap_or(btf).func_args(type_id)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program.
+    pub fn attach(&mut self) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+
+        // the target function is specified at program load time
+        let pfd = bpf_raw_tracepoint_open(None, prog_fd).map_err(|(_code, io_error)| {
+            ProgramError::SyscallError {
+                call: "bpf_raw_tracepoint_open".to_owned(),
+                io_error,
+            }
+        })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(pfd) }))
+    }
+}
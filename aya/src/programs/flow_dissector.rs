@@ -0,0 +1,217 @@
+//! Flow dissector programs.
+use std::{
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::io::{AsRawFd, RawFd},
+    slice,
+};
+
+use crate::{
+    generated::{
+        bpf_attach_type::BPF_FLOW_DISSECTOR, bpf_flow_keys,
+        bpf_prog_type::BPF_PROG_TYPE_FLOW_DISSECTOR,
+    },
+    programs::{
+        load_program, test_run, FdLink, LinkRef, ProgAttachLink, ProgramData, ProgramError,
+        TestRunOptions, TestRunResult,
+    },
+    sys::{bpf_link_create, bpf_prog_attach},
+    util::KernelVersion,
+};
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+
+/// A program used to dissect the packets of a network namespace.
+///
+/// [`FlowDissector`] programs replace the kernel's built-in flow dissector,
+/// which extracts the fields used to hash packets into flows, like the
+/// addresses, ports and protocols. They are attached to a network namespace.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.20.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Program(#[from] aya::programs::ProgramError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use std::fs::File;
+/// use aya::programs::FlowDissector;
+///
+/// let netns = File::open("/proc/self/ns/net")?;
+/// let program = bpf.program_mut_typed::<FlowDissector>("dissect")?;
+/// program.load()?;
+/// program.attach(netns)?;
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_PROG_TYPE_FLOW_DISSECTOR")]
+pub struct FlowDissector {
+    pub(crate) data: ProgramData,
+}
+
+impl FlowDissector {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_FLOW_DISSECTOR);
+        load_program(BPF_PROG_TYPE_FLOW_DISSECTOR, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program to the given network namespace.
+    ///
+    /// `netns` is usually an open `/proc/<pid>/ns/net` file. On kernels 5.7
+    /// and newer the program is attached with a `bpf_link`.
+    pub fn attach<T: AsRawFd>(&mut self, netns: T) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let netns_fd = netns.as_raw_fd();
+
+        let k_ver = KernelVersion::current().map_err(|io_error| ProgramError::SyscallError {
+            call: "uname".to_owned(),
+            io_error,
+        })?;
+        if k_ver >= KernelVersion::new(5, 7, 0) {
+            let link_fd = bpf_link_create(prog_fd, netns_fd, BPF_FLOW_DISSECTOR, 0).map_err(
+                |(_, io_error)| ProgramError::SyscallError {
+                    call: "bpf_link_create".to_owned(),
+                    io_error,
+                },
+            )? as RawFd;
+            Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+        } else {
+            bpf_prog_attach(prog_fd, netns_fd, BPF_FLOW_DISSECTOR, 0).map_err(
+                |(_, io_error)| ProgramError::SyscallError {
+                    call: "bpf_prog_attach".to_owned(),
+                    io_error,
+                },
+            )?;
+
+            Ok(self
+                .data
+                .link(ProgAttachLink::new(prog_fd, netns_fd, BPF_FLOW_DISSECTOR)))
+        }
+    }
+
+    /// Runs the program in the kernel with `packet` as input, without
+    /// attaching it.
+    ///
+    /// `packet` must start with an ethernet header. Returns the result of the
+    /// run along with the flow keys filled in by the program.
+    pub fn test_run(
+        &self,
+        packet: &[u8],
+        options: &TestRunOptions,
+    ) -> Result<(TestRunResult, FlowKeys), ProgramError> {
+        let mut keys = unsafe { mem::zeroed::<bpf_flow_keys>() };
+        // Safety: bpf_flow_keys is a plain C struct
+        let keys_out = unsafe {
+            slice::from_raw_parts_mut(
+                &mut keys as *mut bpf_flow_keys as *mut u8,
+                mem::size_of::<bpf_flow_keys>(),
+            )
+        };
+        let result = test_run(&self.data, packet, keys_out, options)?;
+
+        Ok((result, FlowKeys::from(&keys)))
+    }
+}
+
+/// The flow keys extracted by a [`FlowDissector`] program.
+///
+/// Multi-byte fields are converted to host byte order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FlowKeys {
+    /// The offset of the network header.
+    pub nhoff: u16,
+    /// The offset of the transport header.
+    pub thoff: u16,
+    /// The ethertype of the addresses, eg `0x0800` for IPv4.
+    pub addr_proto: u16,
+    /// Whether the packet is a fragment.
+    pub is_frag: bool,
+    /// Whether the packet is the first fragment.
+    pub is_first_frag: bool,
+    /// Whether the packet is encapsulated.
+    pub is_encap: bool,
+    /// The IP protocol of the transport header.
+    pub ip_proto: u8,
+    /// The ethertype of the network header.
+    pub n_proto: u16,
+    /// The source port.
+    pub sport: u16,
+    /// The destination port.
+    pub dport: u16,
+    /// The source address, if `addr_proto` is IPv4 or IPv6.
+    pub src: Option<IpAddr>,
+    /// The destination address, if `addr_proto` is IPv4 or IPv6.
+    pub dst: Option<IpAddr>,
+    /// The `BPF_FLOW_DISSECTOR_F_*` flags.
+    pub flags: u32,
+    /// The IPv6 flow label.
+    pub flow_label: u32,
+}
+
+impl From<&bpf_flow_keys> for FlowKeys {
+    fn from(keys: &bpf_flow_keys) -> FlowKeys {
+        // Safety: union, the variant is selected by addr_proto
+        let (src, dst) = match keys.addr_proto {
+            ETH_P_IP => unsafe {
+                let addrs = &keys.__bindgen_anon_1.__bindgen_anon_1;
+                (
+                    Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(addrs.ipv4_src)))),
+                    Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(addrs.ipv4_dst)))),
+                )
+            },
+            ETH_P_IPV6 => unsafe {
+                let addrs = &keys.__bindgen_anon_1.__bindgen_anon_2;
+                (
+                    Some(IpAddr::V6(ipv6_addr(&addrs.ipv6_src))),
+                    Some(IpAddr::V6(ipv6_addr(&addrs.ipv6_dst))),
+                )
+            },
+            _ => (None, None),
+        };
+
+        FlowKeys {
+            nhoff: keys.nhoff,
+            thoff: keys.thoff,
+            addr_proto: keys.addr_proto,
+            is_frag: keys.is_frag != 0,
+            is_first_frag: keys.is_first_frag != 0,
+            is_encap: keys.is_encap != 0,
+            ip_proto: keys.ip_proto,
+            n_proto: u16::from_be(keys.n_proto),
+            sport: u16::from_be(keys.sport),
+            dport: u16::from_be(keys.dport),
+            src,
+            dst,
+            flags: keys.flags,
+            flow_label: u32::from_be(keys.flow_label),
+        }
+    }
+}
+
+// the address words are in network byte order
+fn ipv6_addr(words: &[u32; 4]) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    for (chunk, word) in octets.chunks_mut(4).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    Ipv6Addr::from(octets)
+}
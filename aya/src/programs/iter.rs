@@ -0,0 +1,340 @@
+//! Iterator programs.
+use std::{
+    fs::File,
+    io::{self, Read},
+    mem,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    ptr,
+};
+
+use thiserror::Error;
+
+use crate::{
+    generated::{
+        bpf_attach_type::BPF_TRACE_ITER, bpf_cgroup_iter_order, bpf_iter_link_info,
+        bpf_prog_type::BPF_PROG_TYPE_TRACING,
+    },
+    obj::{
+        btf::{Btf, BtfError, BtfKind},
+        ProgramSection,
+    },
+    programs::{
+        load_btf_program, resolve_btf_type, FdLink, Link, LinkRef, Program, ProgramData,
+        ProgramError,
+    },
+    sys::{bpf_iter_create, bpf_link_create_iter},
+    Bpf, Pod,
+};
+
+/// The order in which a cgroup iterator walks the cgroup hierarchy.
+#[derive(Copy, Clone, Debug)]
+pub enum CgroupIterOrder {
+    /// Only the cgroup the iterator is attached to.
+    SelfOnly,
+    /// The cgroup and its descendants, parents before children.
+    DescendantsPre,
+    /// The cgroup and its descendants, children before parents.
+    DescendantsPost,
+    /// The cgroup and its ancestors, up to the root.
+    AncestorsUp,
+}
+
+impl CgroupIterOrder {
+    fn order(self) -> bpf_cgroup_iter_order {
+        use bpf_cgroup_iter_order::*;
+        match self {
+            CgroupIterOrder::SelfOnly => BPF_CGROUP_ITER_SELF_ONLY,
+            CgroupIterOrder::DescendantsPre => BPF_CGROUP_ITER_DESCENDANTS_PRE,
+            CgroupIterOrder::DescendantsPost => BPF_CGROUP_ITER_DESCENDANTS_POST,
+            CgroupIterOrder::AncestorsUp => BPF_CGROUP_ITER_ANCESTORS_UP,
+        }
+    }
+}
+
+/// A program that iterates over kernel objects.
+///
+/// [`Iter`] programs are run once for each object of the iterator target,
+/// like `task` or `cgroup`, and write their output with helpers like
+/// `bpf_seq_printf`. Once the program is attached, each [`Iter::open`] call
+/// starts a new iteration and returns a reader over its output.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8. Cgroup
+/// iterators require 6.1.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{programs::{CgroupIterOrder, Iter}, Btf};
+/// use std::{fs::File, io::Read, os::unix::io::AsRawFd};
+///
+/// let btf = Btf::from_sys_fs()?;
+/// let cgroup = File::open("/sys/fs/cgroup/unified")?;
+/// let program = bpf.program_mut_typed::<Iter>("dump_cgroups")?;
+/// program.load("cgroup", &btf)?;
+/// let link = program.attach_cgroup(cgroup.as_raw_fd(), CgroupIterOrder::DescendantsPre)?;
+///
+/// let mut output = String::new();
+/// program.open(&link)?.read_to_string(&mut output)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "iter")]
+#[doc(alias = "BPF_TRACE_ITER")]
+pub struct Iter {
+    pub(crate) data: ProgramData,
+}
+
+impl Iter {
+    /// Loads the program inside the kernel.
+    ///
+    /// Loads the program for the iterator target `target`, for example
+    /// `task`, `bpf_map` or `cgroup`. The target is resolved from the
+    /// `bpf_iter_<target>` function in `btf`.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self, target: &str, btf: &Btf) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_TRACE_ITER);
+        let (type_id, module_btf) =
+            resolve_btf_type(btf, &format!("bpf_iter_{}", target), BtfKind::Func)?;
+        self.data.attach_btf_id = Some(type_id);
+        load_btf_program(
+            BPF_PROG_TYPE_TRACING,
+            &mut self.data,
+            module_btf.as_ref().unwrap_or(btf),
+        )
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program.
+    ///
+    /// Iterators that take parameters, like cgroup iterators, must be
+    /// attached with the dedicated `attach_*` methods.
+    pub fn attach(&mut self) -> Result<LinkRef, ProgramError> {
+        self.attach_with_info(None)
+    }
+
+    /// Attaches a cgroup iterator to the cgroup `cgroup_fd`.
+    ///
+    /// The program is run for the cgroups selected by `order`, starting from
+    /// `cgroup_fd`.
+    pub fn attach_cgroup(
+        &mut self,
+        cgroup_fd: RawFd,
+        order: CgroupIterOrder,
+    ) -> Result<LinkRef, ProgramError> {
+        let mut info = unsafe { std::mem::zeroed::<bpf_iter_link_info>() };
+        info.cgroup.order = order.order();
+        info.cgroup.cgroup_fd = cgroup_fd as u32;
+        self.attach_with_info(Some(&info))
+    }
+
+    fn attach_with_info(
+        &mut self,
+        info: Option<&bpf_iter_link_info>,
+    ) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+
+        let link_fd = bpf_link_create_iter(prog_fd, info).map_err(|(_code, io_error)| {
+            ProgramError::SyscallError {
+                call: "bpf_link_create".to_owned(),
+                io_error,
+            }
+        })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+
+    /// Starts a new iteration of the iterator attached with `link`.
+    ///
+    /// The returned reader yields the output of the program, and reaches the
+    /// end of file once all the objects have been visited.
+    pub fn open(&self, link: &LinkRef) -> Result<IterReader, ProgramError> {
+        open_iter(link)
+    }
+}
+
+fn open_iter(link: &LinkRef) -> Result<IterReader, ProgramError> {
+    let link_fd = link.fd().ok_or(ProgramError::AlreadyDetached)?;
+    let fd = bpf_iter_create(link_fd).map_err(|(_code, io_error)| ProgramError::SyscallError {
+        call: "bpf_iter_create".to_owned(),
+        io_error,
+    })? as RawFd;
+
+    Ok(IterReader {
+        file: unsafe { File::from_raw_fd(fd) },
+    })
+}
+
+/// A reader over the output of an iteration, returned by [`Iter::open`].
+#[derive(Debug)]
+pub struct IterReader {
+    file: File,
+}
+
+impl Read for IterReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl AsRawFd for IterReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Error type returned by [`TaskIter`].
+#[derive(Debug, Error)]
+pub enum TaskIterError {
+    /// The object doesn't contain an `iter/task` program.
+    #[error("no `iter/task` program found")]
+    NoProgram,
+
+    /// The object contains more than one `iter/task` program.
+    #[error("more than one `iter/task` program found")]
+    MultiplePrograms,
+
+    /// The output of the iterator isn't a whole number of records.
+    #[error("the iterator output is not a multiple of the record size {record_size}")]
+    TruncatedRecord {
+        /// The size of the records.
+        record_size: usize,
+    },
+
+    /// The BTF of the running kernel couldn't be loaded.
+    #[error(transparent)]
+    BtfError(#[from] BtfError),
+
+    /// Loading, attaching or opening the iterator failed.
+    #[error(transparent)]
+    ProgramError(#[from] ProgramError),
+
+    /// Reading the output of the iterator failed.
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+/// A task iterator, visiting every task of the system.
+///
+/// [`TaskIter`] pairs the `iter/task` program of an object with the decoding
+/// of the records it writes, usually with `bpf_seq_write`. Each record must
+/// be a `T` written as is, so the program and user space can share the
+/// definition of the record.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+/// use aya::programs::TaskIter;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct TaskInfo {
+///     pid: u32,
+///     ppid: u32,
+///     comm: [u8; 16],
+/// }
+/// unsafe impl aya::Pod for TaskInfo {}
+///
+/// let mut tasks = TaskIter::new(&mut bpf)?;
+/// for (pid, comm) in tasks.read_records(|task: &TaskInfo| {
+///     let len = task.comm.iter().position(|b| *b == 0).unwrap_or(task.comm.len());
+///     (task.pid, String::from_utf8_lossy(&task.comm[..len]).into_owned())
+/// })? {
+///     println!("{:>7} {}", pid, comm);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct TaskIter {
+    link: LinkRef,
+}
+
+impl TaskIter {
+    /// Attaches the `iter/task` program of `bpf`, loading it against the BTF
+    /// of the running kernel first if it isn't loaded.
+    pub fn new(bpf: &mut Bpf) -> Result<TaskIter, TaskIterError> {
+        let mut programs = bpf.programs_mut().filter_map(|program| match program {
+            Program::Iter(p) if is_task_iter(p) => Some(p),
+            _ => None,
+        });
+        let program = programs.next().ok_or(TaskIterError::NoProgram)?;
+        if programs.next().is_some() {
+            return Err(TaskIterError::MultiplePrograms);
+        }
+
+        if program.data.fd.is_none() {
+            program.load("task", &Btf::from_sys_fs()?)?;
+        }
+        Ok(TaskIter {
+            link: program.attach()?,
+        })
+    }
+
+    /// Runs the iterator and decodes each record with `decode`.
+    ///
+    /// The output of the iterator is split in records of `size_of::<T>()`
+    /// bytes, which are passed to `decode` in order.
+    pub fn read_records<T: Pod, R>(
+        &mut self,
+        decode: impl FnMut(&T) -> R,
+    ) -> Result<Vec<R>, TaskIterError> {
+        let mut data = Vec::new();
+        open_iter(&self.link)?.read_to_end(&mut data)?;
+        decode_records(&data, decode)
+    }
+}
+
+fn is_task_iter(program: &Iter) -> bool {
+    matches!(&program.data.obj.section, ProgramSection::Iter { name } if name == "task")
+}
+
+fn decode_records<T: Pod, R>(
+    data: &[u8],
+    mut decode: impl FnMut(&T) -> R,
+) -> Result<Vec<R>, TaskIterError> {
+    let record_size = mem::size_of::<T>();
+    let chunks = data.chunks_exact(record_size.max(1));
+    if record_size == 0 || !chunks.remainder().is_empty() {
+        return Err(TaskIterError::TruncatedRecord { record_size });
+    }
+
+    Ok(chunks
+        .map(|chunk| {
+            let record = unsafe { ptr::read_unaligned(chunk.as_ptr() as *const T) };
+            decode(&record)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_records() {
+        let data = [1u32, 2, 3]
+            .iter()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decode_records(&data, |v: &u32| v * 2).unwrap(),
+            vec![2, 4, 6]
+        );
+        assert!(matches!(
+            decode_records(&data[..5], |v: &u32| *v),
+            Err(TaskIterError::TruncatedRecord { record_size: 4 })
+        ));
+    }
+}
@@ -1,5 +1,8 @@
 //! Kernel space probes.
-use std::io;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs, io,
+};
 use thiserror::Error;
 
 use crate::{
@@ -7,8 +10,9 @@ use crate::{
     programs::{
         load_program,
         probe::{attach, ProbeKind},
-        LinkRef, ProgramData, ProgramError,
+        Link, LinkRef, ProgramData, ProgramError,
     },
+    util::{tracefs_path, KernelSymbols},
 };
 
 /// A kernel probe.
@@ -28,9 +32,8 @@ use crate::{
 /// ```no_run
 /// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 /// use aya::{Bpf, programs::KProbe};
-/// use std::convert::TryInto;
 ///
-/// let program: &mut KProbe = bpf.program_mut("intercept_wakeups")?.try_into()?;
+/// let program = bpf.program_mut_typed::<KProbe>("intercept_wakeups")?;
 /// program.load()?;
 /// program.attach("try_to_wake_up", 0)?;
 /// # Ok::<(), aya::BpfError>(())
@@ -71,10 +74,152 @@ impl KProbe {
     /// Conversely if the program is a `kretprobe`, it is attached to the return address of the
     /// target function.
     pub fn attach(&mut self, fn_name: &str, offset: u64) -> Result<LinkRef, ProgramError> {
-        attach(&mut self.data, self.kind, fn_name, offset, None)
+        attach(&mut self.data, self.kind, fn_name, offset, None, None, None)
+    }
+
+    /// Attaches a `kretprobe` program, setting the maximum number of concurrent
+    /// instances of the probed function that can be tracked.
+    ///
+    /// By default the kernel limits the number of function instances for which
+    /// the return can be traced at the same time, and events are missed when
+    /// the limit is exceeded. Use `maxactive` to raise the limit for functions
+    /// that are called concurrently a lot or that sleep.
+    ///
+    /// The probe is always created via debugfs, since that's the only
+    /// interface that allows setting `maxactive`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KProbeError::MaxActiveNotSupported`] if the program is not a
+    /// `kretprobe`.
+    pub fn attach_with_maxactive(
+        &mut self,
+        fn_name: &str,
+        offset: u64,
+        maxactive: u32,
+    ) -> Result<LinkRef, ProgramError> {
+        if !matches!(self.kind, ProbeKind::KRetProbe) {
+            return Err(KProbeError::MaxActiveNotSupported.into());
+        }
+        attach(
+            &mut self.data,
+            self.kind,
+            fn_name,
+            offset,
+            None,
+            Some(maxactive),
+            None,
+        )
+    }
+
+    /// Attaches the program to all the kernel functions matching `pattern`.
+    ///
+    /// `pattern` is a glob where `*` matches any sequence of characters and
+    /// `?` matches any single character, eg `tcp_*`. The functions are looked
+    /// up in `/proc/kallsyms` and restricted to the ones listed in the
+    /// `available_filter_functions` file of tracefs, which are the ones that
+    /// can be probed. The functions whose name contains a `.`, like the
+    /// `.isra` and `.cold` variants generated by the compiler, are skipped.
+    ///
+    /// One link is created for each function. If attaching to any of the
+    /// functions fails, the links created so far are detached. To probe many
+    /// functions, [`KProbeMulti::attach_pattern`](crate::programs::KProbeMulti::attach_pattern)
+    /// is considerably faster since it creates a single link.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KProbeError::NoMatchingFunctions`] if no function matches
+    /// `pattern`, and [`KProbeError::FileError`] if `/proc/kallsyms` or
+    /// `available_filter_functions` can't be read, for instance because
+    /// tracefs isn't mounted.
+    pub fn attach_pattern(&mut self, pattern: &str) -> Result<Vec<LinkRef>, ProgramError> {
+        let fn_names = matching_functions(pattern)?;
+        let mut links = Vec::with_capacity(fn_names.len());
+        for fn_name in &fn_names {
+            match self.attach(fn_name, 0) {
+                Ok(link) => links.push(link),
+                Err(e) => {
+                    for mut link in links {
+                        let _ = link.detach();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(links)
     }
 }
 
+/// Returns the names of the kernel functions matching the glob `pattern`.
+pub(crate) fn matching_functions(pattern: &str) -> Result<Vec<String>, KProbeError> {
+    let ksyms = KernelSymbols::load().map_err(|io_error| KProbeError::FileError {
+        filename: "/proc/kallsyms".to_owned(),
+        io_error,
+    })?;
+    // without the filter, functions that can't be probed would match and make
+    // attaching fail
+    let filter_error = |filename: String, io_error| KProbeError::FileError { filename, io_error };
+    let filter_path = tracefs_path()
+        .map(|path| path.join("available_filter_functions"))
+        .map_err(|io_error| filter_error("available_filter_functions".to_owned(), io_error))?;
+    let filter_data = fs::read_to_string(&filter_path)
+        .map_err(|io_error| filter_error(filter_path.to_string_lossy().into_owned(), io_error))?;
+    let filter = parse_filter_functions(&filter_data);
+
+    let fn_names = ksyms
+        .iter()
+        .filter(|sym| matches!(sym.kind, 't' | 'T'))
+        .map(|sym| sym.name.as_str())
+        // compiler generated clones, like `.isra.0`, can't be probed by name
+        .filter(|name| !name.contains('.'))
+        .filter(|name| glob_match(pattern, name))
+        .filter(|name| filter.contains(name))
+        .collect::<BTreeSet<_>>();
+    if fn_names.is_empty() {
+        return Err(KProbeError::NoMatchingFunctions {
+            pattern: pattern.to_owned(),
+        });
+    }
+
+    Ok(fn_names.into_iter().map(str::to_owned).collect())
+}
+
+// the lines of available_filter_functions are either `name` or `name [module]`
+fn parse_filter_functions(data: &str) -> HashSet<&str> {
+    data.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect()
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // the position after the last `*` and the position in `name` it matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
 /// The type returned when attaching a [`KProbe`] fails.
 #[derive(Debug, Error)]
 pub enum KProbeError {
@@ -84,4 +229,48 @@ pub enum KProbeError {
         #[source]
         io_error: io::Error,
     },
+
+    #[error("maxactive can only be set for kretprobes")]
+    MaxActiveNotSupported,
+
+    /// No kernel function matches the pattern.
+    #[error("no kernel function matches `{pattern}`")]
+    NoMatchingFunctions { pattern: String },
+
+    /// The name of the kernel function contains a nul byte.
+    #[error("invalid kernel function name `{name}`")]
+    InvalidFunctionName { name: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("tcp_*", "tcp_sendmsg"));
+        assert!(glob_match("tcp_*", "tcp_"));
+        assert!(!glob_match("tcp_*", "udp_sendmsg"));
+        assert!(glob_match("*_sendmsg", "tcp_sendmsg"));
+        assert!(glob_match("*send*", "tcp_sendmsg"));
+        assert!(glob_match("tcp_?endmsg", "tcp_sendmsg"));
+        assert!(!glob_match("tcp_?", "tcp_sendmsg"));
+        assert!(glob_match("vfs_read", "vfs_read"));
+        assert!(!glob_match("vfs_read", "vfs_readv"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_parse_filter_functions() {
+        let filter = parse_filter_functions("vfs_read\nvfs_write\nbtrfs_sync_file [btrfs]\n");
+        assert_eq!(
+            filter,
+            ["vfs_read", "vfs_write", "btrfs_sync_file"]
+                .iter()
+                .copied()
+                .collect()
+        );
+    }
 }
@@ -0,0 +1,157 @@
+//! Kernel space multi probes.
+use std::{
+    ffi::{CStr, CString},
+    os::unix::io::RawFd,
+};
+
+use crate::{
+    generated::{bpf_attach_type::BPF_TRACE_KPROBE_MULTI, bpf_prog_type::BPF_PROG_TYPE_KPROBE},
+    programs::{
+        kprobe::matching_functions, load_program, FdLink, KProbeError, LinkRef, ProbeKind,
+        ProgramData, ProgramError,
+    },
+    sys::bpf_link_create_kprobe_multi,
+};
+
+// see enum { BPF_F_KPROBE_MULTI_RETURN } in include/uapi/linux/bpf.h
+const BPF_F_KPROBE_MULTI_RETURN: u32 = 1 << 0;
+
+/// A kernel probe attached to many functions at once.
+///
+/// [`KProbeMulti`] programs behave like [`KProbe`](crate::programs::KProbe)
+/// programs, but a single attach call can instrument any number of kernel
+/// functions. All the probes are created with a single `bpf_link`, which is
+/// considerably faster than attaching one perf event per function and only
+/// uses one file descriptor.
+///
+/// Multi probes are declared in sections named `kprobe.multi/name` and
+/// `kretprobe.multi/name`.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.18.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{Bpf, programs::KProbeMulti};
+///
+/// let program = bpf.program_mut_typed::<KProbeMulti>("trace_vfs")?;
+/// program.load()?;
+/// program.attach(&["vfs_read", "vfs_write", "vfs_open"])?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_TRACE_KPROBE_MULTI")]
+pub struct KProbeMulti {
+    pub(crate) data: ProgramData,
+    pub(crate) kind: ProbeKind,
+}
+
+impl KProbeMulti {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_TRACE_KPROBE_MULTI);
+        load_program(BPF_PROG_TYPE_KPROBE, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Returns `KProbe` if the program is a `kprobe.multi`, or `KRetProbe` if
+    /// the program is a `kretprobe.multi`.
+    pub fn kind(&self) -> ProbeKind {
+        self.kind
+    }
+
+    /// Attaches the program to the given kernel functions.
+    ///
+    /// All the functions are probed through a single link. Detaching the
+    /// returned link removes all the probes.
+    pub fn attach(&mut self, fn_names: &[&str]) -> Result<LinkRef, ProgramError> {
+        let fn_names = c_names(fn_names)?;
+        let syms = fn_names
+            .iter()
+            .map(|name| name.as_c_str())
+            .collect::<Vec<_>>();
+        self.attach_either(&syms, &[])
+    }
+
+    /// Attaches the program to all the kernel functions matching `pattern`.
+    ///
+    /// `pattern` is a glob where `*` matches any sequence of characters and
+    /// `?` matches any single character, eg `tcp_*`. See
+    /// [`KProbe::attach_pattern`](crate::programs::KProbe::attach_pattern) for
+    /// how the functions are looked up. All the functions are probed through
+    /// a single link.
+    pub fn attach_pattern(&mut self, pattern: &str) -> Result<LinkRef, ProgramError> {
+        let fn_names = c_names(&matching_functions(pattern)?)?;
+        let syms = fn_names
+            .iter()
+            .map(|name| name.as_c_str())
+            .collect::<Vec<_>>();
+        self.attach_either(&syms, &[])
+    }
+
+    /// Attaches the program to the given kernel addresses.
+    ///
+    /// This is useful to probe functions that have been resolved with
+    /// [`kernel_symbols`](crate::util::kernel_symbols), or functions that
+    /// don't have a unique name.
+    pub fn attach_addrs(&mut self, addrs: &[u64]) -> Result<LinkRef, ProgramError> {
+        self.attach_either(&[], addrs)
+    }
+
+    fn attach_either(&mut self, syms: &[&CStr], addrs: &[u64]) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let flags = match self.kind {
+            ProbeKind::KRetProbe => BPF_F_KPROBE_MULTI_RETURN,
+            _ => 0,
+        };
+        let link_fd =
+            bpf_link_create_kprobe_multi(prog_fd, syms, addrs, flags).map_err(|(_, io_error)| {
+                ProgramError::SyscallError {
+                    call: "bpf_link_create".to_owned(),
+                    io_error,
+                }
+            })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+}
+
+fn c_names<S: AsRef<str>>(names: &[S]) -> Result<Vec<CString>, KProbeError> {
+    names
+        .iter()
+        .map(|name| {
+            CString::new(name.as_ref()).map_err(|_| KProbeError::InvalidFunctionName {
+                name: name.as_ref().to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_names() {
+        assert_eq!(
+            c_names(&["vfs_read", "vfs_write"]).unwrap(),
+            vec![
+                CString::new("vfs_read").unwrap(),
+                CString::new("vfs_write").unwrap()
+            ]
+        );
+        assert!(matches!(
+            c_names(&["vfs_read", "vfs\0write"]),
+            Err(KProbeError::InvalidFunctionName { name }) if name == "vfs\0write"
+        ));
+    }
+}
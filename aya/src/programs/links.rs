@@ -0,0 +1,413 @@
+//! Enumerating the links attached on the system.
+//!
+//! [`loaded_links`] lists every `bpf_link` known to the kernel, including the
+//! ones created by other processes, which is useful to audit what is attached
+//! on a host. Attachments that aren't owned by a link, like programs attached
+//! with `BPF_PROG_ATTACH` or XDP programs attached via netlink, aren't listed.
+//!
+//! [`LinkHandle::from_id`] opens one of the listed links, for example to detach
+//! it.
+use std::{
+    ffi::CStr,
+    io, mem,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+};
+
+use libc::{close, ENOENT};
+
+use crate::{
+    generated::{bpf_link_info, bpf_link_type},
+    programs::ProgramError,
+    sys::{bpf_link_detach, bpf_link_get_fd_by_id, bpf_link_get_info_by_fd, bpf_link_get_next_id},
+};
+
+// the longest tracepoint names are well below this
+const TP_NAME_LEN: usize = 256;
+
+/// The type of a link, as known to the kernel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LinkType {
+    /// A raw or BTF tracepoint, `BPF_LINK_TYPE_RAW_TRACEPOINT`.
+    RawTracePoint,
+    /// A fentry, fexit, LSM or extension program, `BPF_LINK_TYPE_TRACING`.
+    Tracing,
+    /// A program attached to a cgroup, `BPF_LINK_TYPE_CGROUP`.
+    Cgroup,
+    /// A BPF iterator, `BPF_LINK_TYPE_ITER`.
+    Iter,
+    /// A program attached to a network namespace, `BPF_LINK_TYPE_NETNS`.
+    Netns,
+    /// An XDP program, `BPF_LINK_TYPE_XDP`.
+    Xdp,
+    /// A program attached to a perf event, like a kprobe or a tracepoint,
+    /// `BPF_LINK_TYPE_PERF_EVENT`.
+    PerfEvent,
+    /// A kprobe attached to many functions, `BPF_LINK_TYPE_KPROBE_MULTI`.
+    KProbeMulti,
+    /// A struct_ops map, `BPF_LINK_TYPE_STRUCT_OPS`.
+    StructOps,
+    /// A netfilter program, `BPF_LINK_TYPE_NETFILTER`.
+    Netfilter,
+    /// A TCX program, `BPF_LINK_TYPE_TCX`.
+    Tcx,
+    /// An uprobe attached to many locations, `BPF_LINK_TYPE_UPROBE_MULTI`.
+    UProbeMulti,
+    /// A link type not known to aya.
+    Unknown(u32),
+}
+
+impl LinkType {
+    fn from_raw(link_type: u32) -> LinkType {
+        use bpf_link_type::*;
+        match link_type {
+            x if x == BPF_LINK_TYPE_RAW_TRACEPOINT as u32 => LinkType::RawTracePoint,
+            x if x == BPF_LINK_TYPE_TRACING as u32 => LinkType::Tracing,
+            x if x == BPF_LINK_TYPE_CGROUP as u32 => LinkType::Cgroup,
+            x if x == BPF_LINK_TYPE_ITER as u32 => LinkType::Iter,
+            x if x == BPF_LINK_TYPE_NETNS as u32 => LinkType::Netns,
+            x if x == BPF_LINK_TYPE_XDP as u32 => LinkType::Xdp,
+            x if x == BPF_LINK_TYPE_PERF_EVENT as u32 => LinkType::PerfEvent,
+            x if x == BPF_LINK_TYPE_KPROBE_MULTI as u32 => LinkType::KProbeMulti,
+            x if x == BPF_LINK_TYPE_STRUCT_OPS as u32 => LinkType::StructOps,
+            x if x == BPF_LINK_TYPE_NETFILTER as u32 => LinkType::Netfilter,
+            x if x == BPF_LINK_TYPE_TCX as u32 => LinkType::Tcx,
+            x if x == BPF_LINK_TYPE_UPROBE_MULTI as u32 => LinkType::UProbeMulti,
+            x => LinkType::Unknown(x),
+        }
+    }
+}
+
+/// The details of a link that depend on its type.
+///
+/// Attach types are the raw `bpf_attach_type` values of the kernel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkDetails {
+    /// A raw or BTF tracepoint.
+    RawTracePoint {
+        /// The name of the tracepoint.
+        name: String,
+    },
+    /// A fentry, fexit, LSM or extension program.
+    Tracing {
+        attach_type: u32,
+        /// The id of the program the link is attached to for fentry, fexit
+        /// and extension programs attached to another program, 0 otherwise.
+        target_obj_id: u32,
+        /// The BTF id of the function the link is attached to.
+        target_btf_id: u32,
+    },
+    /// A program attached to a cgroup.
+    Cgroup { cgroup_id: u64, attach_type: u32 },
+    /// A program attached to a network namespace.
+    Netns { netns_ino: u32, attach_type: u32 },
+    /// An XDP program.
+    Xdp {
+        /// The index of the interface, 0 if it has been removed.
+        ifindex: u32,
+    },
+    /// A netfilter program.
+    Netfilter {
+        pf: u32,
+        hooknum: u32,
+        priority: i32,
+        flags: u32,
+    },
+    /// A TCX program.
+    Tcx {
+        /// The index of the interface, 0 if it has been removed.
+        ifindex: u32,
+        attach_type: u32,
+    },
+    /// A link type without details or whose details aren't decoded.
+    None,
+}
+
+/// Provides information about a link, like its id and the program it attaches.
+#[derive(Clone, Debug)]
+pub struct LinkInfo {
+    id: u32,
+    link_type: LinkType,
+    prog_id: u32,
+    details: LinkDetails,
+}
+
+impl LinkInfo {
+    /// Returns the information of the link `fd`.
+    pub(crate) fn from_fd(fd: RawFd) -> Result<LinkInfo, io::Error> {
+        let mut info = unsafe { mem::zeroed::<bpf_link_info>() };
+        bpf_link_get_info_by_fd(fd, &mut info)?;
+
+        // the name buffer shares the union with the buffers of the other link
+        // types, like the addresses of kprobe multi links, so it's only set
+        // once the link is known to be a raw tracepoint
+        let mut tp_name = Vec::new();
+        if info.type_ == bpf_link_type::BPF_LINK_TYPE_RAW_TRACEPOINT as u32 {
+            tp_name = vec![0u8; TP_NAME_LEN];
+            info = unsafe { mem::zeroed::<bpf_link_info>() };
+            info.__bindgen_anon_1.raw_tracepoint.tp_name = tp_name.as_mut_ptr() as u64;
+            info.__bindgen_anon_1.raw_tracepoint.tp_name_len = tp_name.len() as u32;
+            bpf_link_get_info_by_fd(fd, &mut info)?;
+        }
+
+        Ok(LinkInfo::from_raw(&info, &tp_name))
+    }
+
+    fn from_raw(info: &bpf_link_info, tp_name: &[u8]) -> LinkInfo {
+        let link_type = LinkType::from_raw(info.type_);
+        let u = &info.__bindgen_anon_1;
+        let details = unsafe {
+            match link_type {
+                LinkType::RawTracePoint => LinkDetails::RawTracePoint {
+                    name: CStr::from_bytes_until_nul(tp_name)
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                },
+                LinkType::Tracing => LinkDetails::Tracing {
+                    attach_type: u.tracing.attach_type,
+                    target_obj_id: u.tracing.target_obj_id,
+                    target_btf_id: u.tracing.target_btf_id,
+                },
+                LinkType::Cgroup => LinkDetails::Cgroup {
+                    cgroup_id: u.cgroup.cgroup_id,
+                    attach_type: u.cgroup.attach_type,
+                },
+                LinkType::Netns => LinkDetails::Netns {
+                    netns_ino: u.netns.netns_ino,
+                    attach_type: u.netns.attach_type,
+                },
+                LinkType::Xdp => LinkDetails::Xdp {
+                    ifindex: u.xdp.ifindex,
+                },
+                LinkType::Netfilter => LinkDetails::Netfilter {
+                    pf: u.netfilter.pf,
+                    hooknum: u.netfilter.hooknum,
+                    priority: u.netfilter.priority,
+                    flags: u.netfilter.flags,
+                },
+                LinkType::Tcx => LinkDetails::Tcx {
+                    ifindex: u.tcx.ifindex,
+                    attach_type: u.tcx.attach_type,
+                },
+                _ => LinkDetails::None,
+            }
+        };
+
+        LinkInfo {
+            id: info.id,
+            link_type,
+            prog_id: info.prog_id,
+            details,
+        }
+    }
+
+    /// The id of the link. Each link has a unique id.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The type of the link.
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    /// The id of the program attached by the link.
+    pub fn prog_id(&self) -> u32 {
+        self.prog_id
+    }
+
+    /// The details of the link that depend on its type.
+    pub fn details(&self) -> &LinkDetails {
+        &self.details
+    }
+}
+
+/// Returns an iterator over the links attached on the system.
+///
+/// Listing the links requires `CAP_SYS_ADMIN`. Links detached while iterating
+/// are skipped.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::programs::links::{loaded_links, LinkDetails};
+///
+/// for link in loaded_links() {
+///     let link = link?;
+///     if let LinkDetails::Xdp { ifindex } = link.details() {
+///         println!("link {} attaches program {} to interface {}", link.id(), link.prog_id(), ifindex);
+///     }
+/// }
+/// # Ok::<(), aya::programs::ProgramError>(())
+/// ```
+pub fn loaded_links() -> LoadedLinks {
+    LoadedLinks { next_id: Some(0) }
+}
+
+/// The iterator returned by [`loaded_links`].
+#[derive(Debug)]
+pub struct LoadedLinks {
+    next_id: Option<u32>,
+}
+
+impl Iterator for LoadedLinks {
+    type Item = Result<LinkInfo, ProgramError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let syscall_error = |call: &str, io_error| ProgramError::SyscallError {
+            call: call.to_owned(),
+            io_error,
+        };
+
+        loop {
+            let id = match bpf_link_get_next_id(self.next_id?) {
+                Ok(Some(id)) => id,
+                Ok(None) => {
+                    self.next_id = None;
+                    return None;
+                }
+                Err(e) => {
+                    self.next_id = None;
+                    return Some(Err(syscall_error("bpf_link_get_next_id", e)));
+                }
+            };
+            self.next_id = Some(id);
+
+            let fd = match bpf_link_get_fd_by_id(id) {
+                Ok(fd) => fd,
+                // detached since we got its id
+                Err(e) if e.raw_os_error() == Some(ENOENT) => continue,
+                Err(e) => return Some(Err(syscall_error("bpf_link_get_fd_by_id", e))),
+            };
+            let info = LinkInfo::from_fd(fd);
+            unsafe { close(fd) };
+
+            return Some(info.map_err(|e| syscall_error("bpf_link_get_info_by_fd", e)));
+        }
+    }
+}
+
+/// A handle to an existing link, opened by id.
+///
+/// [`LinkHandle`] gives access to links created by other processes, for
+/// example to detach the programs attached by an agent that crashed while its
+/// links were pinned or held by another process. The ids of the links can be
+/// found with [`loaded_links`].
+///
+/// Dropping a [`LinkHandle`] closes its file descriptor but leaves the program
+/// attached. Call [`LinkHandle::detach`] to detach it.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.8, detaching
+/// requires 5.9.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::programs::links::{loaded_links, LinkHandle, LinkType};
+///
+/// // detach all the XDP programs attached with a link
+/// for link in loaded_links() {
+///     let link = link?;
+///     if link.link_type() == LinkType::Xdp {
+///         LinkHandle::from_id(link.id())?.detach()?;
+///     }
+/// }
+/// # Ok::<(), aya::programs::ProgramError>(())
+/// ```
+#[derive(Debug)]
+pub struct LinkHandle {
+    fd: RawFd,
+}
+
+impl LinkHandle {
+    /// Opens the link with the given id.
+    ///
+    /// Opening links by id requires `CAP_SYS_ADMIN`.
+    pub fn from_id(id: u32) -> Result<LinkHandle, ProgramError> {
+        let fd = bpf_link_get_fd_by_id(id).map_err(|io_error| ProgramError::SyscallError {
+            call: "bpf_link_get_fd_by_id".to_owned(),
+            io_error,
+        })?;
+
+        Ok(LinkHandle { fd })
+    }
+
+    /// Returns information about the link.
+    pub fn info(&self) -> Result<LinkInfo, ProgramError> {
+        LinkInfo::from_fd(self.fd).map_err(|io_error| ProgramError::SyscallError {
+            call: "bpf_link_get_info_by_fd".to_owned(),
+            io_error,
+        })
+    }
+
+    /// Detaches the program of the link.
+    ///
+    /// The link stays alive until all its references, like pins and file
+    /// descriptors held by other processes, are gone, but its program is no
+    /// longer run.
+    pub fn detach(self) -> Result<(), ProgramError> {
+        bpf_link_detach(self.fd).map_err(|(_, io_error)| ProgramError::SyscallError {
+            call: "BPF_LINK_DETACH".to_owned(),
+            io_error,
+        })?;
+
+        Ok(())
+    }
+}
+
+impl AsRawFd for LinkHandle {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for LinkHandle {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the descriptor is closed when the handle is dropped
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for LinkHandle {
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_info_from_raw() {
+        let mut info = unsafe { mem::zeroed::<bpf_link_info>() };
+        info.type_ = bpf_link_type::BPF_LINK_TYPE_XDP as u32;
+        info.id = 3;
+        info.prog_id = 42;
+        info.__bindgen_anon_1.xdp.ifindex = 2;
+        let link = LinkInfo::from_raw(&info, &[]);
+        assert_eq!(link.id(), 3);
+        assert_eq!(link.prog_id(), 42);
+        assert_eq!(link.link_type(), LinkType::Xdp);
+        assert_eq!(link.details(), &LinkDetails::Xdp { ifindex: 2 });
+
+        info.type_ = bpf_link_type::BPF_LINK_TYPE_RAW_TRACEPOINT as u32;
+        let link = LinkInfo::from_raw(&info, b"sched_switch\0\0\0");
+        assert_eq!(
+            link.details(),
+            &LinkDetails::RawTracePoint {
+                name: "sched_switch".to_owned()
+            }
+        );
+
+        info.type_ = 1000;
+        let link = LinkInfo::from_raw(&info, &[]);
+        assert_eq!(link.link_type(), LinkType::Unknown(1000));
+        assert_eq!(link.details(), &LinkDetails::None);
+    }
+}
@@ -35,12 +35,11 @@ use libc::{close, dup};
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
 /// use std::fs::File;
-/// use std::convert::TryInto;
 /// use aya::programs::LircMode2;
 ///
 /// let file = File::open("/dev/lirc0")?;
 /// let mut bpf = aya::Bpf::load_file("imon_rsc.o")?;
-/// let decoder: &mut LircMode2 = bpf.program_mut("imon_rsc")?.try_into().unwrap();
+/// let decoder = bpf.program_mut_typed::<LircMode2>("imon_rsc")?;
 /// decoder.load()?;
 /// decoder.attach(file)?;
 /// # Ok::<(), Error>(())
@@ -69,7 +68,7 @@ impl LircMode2 {
         let prog_fd = self.data.fd_or_err()?;
         let lircdev_fd = lircdev.as_raw_fd();
 
-        bpf_prog_attach(prog_fd, lircdev_fd, BPF_LIRC_MODE2).map_err(|(_, io_error)| {
+        bpf_prog_attach(prog_fd, lircdev_fd, BPF_LIRC_MODE2, 0).map_err(|(_, io_error)| {
             ProgramError::SyscallError {
                 call: "bpf_prog_attach".to_owned(),
                 io_error,
@@ -80,27 +79,53 @@ impl LircMode2 {
     }
 
     /// Queries the lirc device for attached programs.
+    ///
+    /// Returns a link for each program attached to the device, including the
+    /// programs attached by other processes. Detaching a link detaches the
+    /// program from the device, which can be used to replace stale
+    /// attachments.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let mut bpf = aya::Bpf::load(&[])?;
+    /// use aya::programs::{LircMode2, Link};
+    /// use std::fs::File;
+    ///
+    /// let file = File::open("/dev/lirc0")?;
+    /// for mut link in LircMode2::query(file)? {
+    ///     println!("detaching program {}", link.info()?.id());
+    ///     link.detach()?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
     pub fn query<T: AsRawFd>(target_fd: T) -> Result<Vec<LircLink>, ProgramError> {
-        let prog_ids = query(target_fd.as_raw_fd(), BPF_LIRC_MODE2, 0, &mut None)?;
-
-        let mut prog_fds = Vec::with_capacity(prog_ids.len());
+        let prog_ids = LircMode2::query_ids(target_fd.as_raw_fd())?;
 
+        let mut links = Vec::with_capacity(prog_ids.len());
         for id in prog_ids {
+            // if this fails the fds opened so far are closed when links is
+            // dropped
             let fd = bpf_prog_get_fd_by_id(id).map_err(|io_error| ProgramError::SyscallError {
                 call: "bpf_prog_get_fd_by_id".to_owned(),
                 io_error,
             })?;
 
-            prog_fds.push(fd as RawFd);
+            links.push(LircLink {
+                prog_fd: Some(fd as RawFd),
+                target_fd: Some(unsafe { dup(target_fd.as_raw_fd()) }),
+                owns_prog_fd: true,
+            });
         }
 
-        Ok(prog_fds
-            .into_iter()
-            .map(|prog_fd| LircLink {
-                prog_fd: Some(prog_fd),
-                target_fd: Some(unsafe { dup(target_fd.as_raw_fd()) }),
-            })
-            .collect())
+        Ok(links)
+    }
+
+    /// Returns the ids of the programs attached to the lirc device.
+    ///
+    /// Unlike [`LircMode2::query`], this doesn't open the programs.
+    pub fn query_ids<T: AsRawFd>(target_fd: T) -> Result<Vec<u32>, ProgramError> {
+        query(target_fd.as_raw_fd(), BPF_LIRC_MODE2, 0, &mut None)
     }
 }
 
@@ -108,6 +133,8 @@ impl LircMode2 {
 pub struct LircLink {
     prog_fd: Option<RawFd>,
     target_fd: Option<RawFd>,
+    // links returned by LircMode2::query() own the program fd
+    owns_prog_fd: bool,
 }
 
 impl LircLink {
@@ -115,6 +142,13 @@ impl LircLink {
         LircLink {
             prog_fd: Some(prog_fd),
             target_fd: Some(unsafe { dup(target_fd) }),
+            owns_prog_fd: false,
+        }
+    }
+
+    fn close_prog_fd(&self, prog_fd: RawFd) {
+        if self.owns_prog_fd {
+            unsafe { close(prog_fd) };
         }
     }
 
@@ -139,11 +173,22 @@ impl Link for LircLink {
             let target_fd = self.target_fd.take().unwrap();
             let _ = bpf_prog_detach(prog_fd, target_fd, BPF_LIRC_MODE2);
             unsafe { close(target_fd) };
+            self.close_prog_fd(prog_fd);
             Ok(())
         } else {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        if let Some(prog_fd) = self.prog_fd.take() {
+            unsafe { close(self.target_fd.take().unwrap()) };
+            self.close_prog_fd(prog_fd);
+            Ok(None)
+        } else {
+            Err(ProgramError::AlreadyDetached)
+        }
+    }
 }
 
 impl Drop for LircLink {
@@ -151,5 +196,8 @@ impl Drop for LircLink {
         if let Some(target_fd) = self.target_fd.take() {
             unsafe { close(target_fd) };
         }
+        if let Some(prog_fd) = self.prog_fd.take() {
+            self.close_prog_fd(prog_fd);
+        }
     }
 }
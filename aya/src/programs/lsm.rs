@@ -5,7 +5,7 @@ use thiserror::Error;
 
 use crate::{
     generated::{bpf_attach_type::BPF_LSM_MAC, bpf_prog_type::BPF_PROG_TYPE_LSM},
-    obj::btf::{Btf, BtfError, BtfKind},
+    obj::btf::{Btf, BtfError, BtfKind, BtfType},
     programs::{load_program, FdLink, LinkRef, ProgramData, ProgramError},
     sys::bpf_raw_tracepoint_open,
 };
@@ -40,16 +40,27 @@ use crate::{
 /// # }
 /// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 /// use aya::{Bpf, programs::Lsm, BtfError, Btf};
-/// use std::convert::TryInto;
 ///
 /// let btf = Btf::from_sys_fs()?;
-/// let program: &mut Lsm = bpf.program_mut("lsm_prog")?.try_into()?;
+/// let program = bpf.program_mut_typed::<Lsm>("lsm_prog")?;
 /// program.load("security_bprm_exec", &btf)?;
 /// program.attach()?;
 /// # Ok::<(), LsmError>(())
 /// ```
 ///
 /// [1]: https://elixir.bootlin.com/linux/latest/source/include/linux/lsm_hook_defs.h
+// the kernel defines a bpf_lsm_<hook> function for each LSM hook
+const LSM_HOOK_PREFIX: &str = "bpf_lsm_";
+
+// the functions of kernel/bpf/bpf_lsm.c that share the prefix of the hooks
+const NON_HOOK_FUNCS: &[&str] = &[
+    "bpf_lsm_find_cgroup_shim",
+    "bpf_lsm_get_retval_range",
+    "bpf_lsm_is_sleepable_hook",
+    "bpf_lsm_is_trusted",
+    "bpf_lsm_verify_prog",
+];
+
 #[derive(Debug)]
 #[doc(alias = "BPF_PROG_TYPE_LSM")]
 pub struct Lsm {
@@ -66,6 +77,16 @@ pub enum LsmLoadError {
     Program(#[from] ProgramError),
 }
 
+/// An LSM hook that [`Lsm`] programs can be attached to, returned by
+/// [`Lsm::available_hooks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsmHook {
+    /// The name of the hook, as passed to [`Lsm::load`].
+    pub name: String,
+    /// The prototype of the hook as C, eg `int file_open(struct file *file)`.
+    pub prototype: String,
+}
+
 impl Lsm {
     /// Loads the program inside the kernel.
     ///
@@ -77,7 +98,7 @@ impl Lsm {
     ///   be attached to
     pub fn load(&mut self, lsm_hook_name: &str, btf: &Btf) -> Result<(), LsmLoadError> {
         self.data.expected_attach_type = Some(BPF_LSM_MAC);
-        let type_name = format!("bpf_lsm_{}", lsm_hook_name);
+        let type_name = format!("{}{}", LSM_HOOK_PREFIX, lsm_hook_name);
         self.data.attach_btf_id =
             Some(btf.id_by_type_name_kind(type_name.as_str(), BtfKind::Func)?);
         load_program(BPF_PROG_TYPE_LSM, &mut self.data).map_err(LsmLoadError::from)
@@ -88,6 +109,49 @@ impl Lsm {
         self.data.name.to_string()
     }
 
+    /// Returns the LSM hooks the kernel described by `btf` provides, sorted
+    /// by name.
+    ///
+    /// This can be used to validate hook names before calling
+    /// [`Lsm::load`]. The hooks are listed whether or not the BPF LSM is
+    /// enabled at boot.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::{programs::Lsm, Btf};
+    ///
+    /// let btf = Btf::from_sys_fs()?;
+    /// for hook in Lsm::available_hooks(&btf)? {
+    ///     println!("{}: {}", hook.name, hook.prototype);
+    /// }
+    /// # Ok::<(), aya::BtfError>(())
+    /// ```
+    pub fn available_hooks(btf: &Btf) -> Result<Vec<LsmHook>, BtfError> {
+        let mut hooks = Vec::new();
+        for (type_id, ty) in btf.types().enumerate() {
+            if !matches!(ty, BtfType::Func(_)) {
+                continue;
+            }
+            let func_name = match btf.type_name(ty)? {
+                Some(name) => name,
+                None => continue,
+            };
+            if NON_HOOK_FUNCS.contains(&&*func_name) {
+                continue;
+            }
+            if let Some(name) = func_name.strip_prefix(LSM_HOOK_PREFIX) {
+                hooks.push(LsmHook {
+                    name: name.to_owned(),
+                    prototype: btf.func_decl(type_id as u32, name)?,
+                });
+            }
+        }
+        hooks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(hooks)
+    }
+
     /// Attaches the program.
     pub fn attach(&mut self) -> Result<LinkRef, ProgramError> {
         attach_btf_id(&mut self.data)
@@ -109,3 +173,53 @@ pub(crate) fn attach_btf_id(program_data: &mut ProgramData) -> Result<LinkRef, P
 
     Ok(program_data.link(FdLink { fd: Some(pfd) }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generated::{btf_param, BTF_INT_SIGNED},
+        obj::btf::BTF_FUNC_GLOBAL,
+    };
+
+    #[test]
+    fn test_available_hooks() {
+        let mut btf = Btf::new();
+        let name = btf.add_string("int");
+        let int = btf.add_type(BtfType::new_int(name, 4, BTF_INT_SIGNED, 0));
+        let name = btf.add_string("file");
+        let file = btf.add_type(BtfType::new_struct(name, Vec::new(), 0));
+        let file_ptr = btf.add_type(BtfType::new_ptr(file));
+        let name = btf.add_string("file");
+        let proto = btf.add_type(BtfType::new_func_proto(
+            vec![btf_param {
+                name_off: name,
+                type_: file_ptr,
+            }],
+            int,
+        ));
+        for func in [
+            "bpf_lsm_file_open",
+            "bpf_lsm_verify_prog",
+            "bpf_lsm_bpf",
+            "security_file_open",
+        ] {
+            let name = btf.add_string(func);
+            btf.add_type(BtfType::new_func(name, proto, BTF_FUNC_GLOBAL));
+        }
+
+        assert_eq!(
+            Lsm::available_hooks(&btf).unwrap(),
+            vec![
+                LsmHook {
+                    name: "bpf".to_owned(),
+                    prototype: "int bpf(struct file *file)".to_owned(),
+                },
+                LsmHook {
+                    name: "file_open".to_owned(),
+                    prototype: "int file_open(struct file *file)".to_owned(),
+                },
+            ]
+        );
+    }
+}
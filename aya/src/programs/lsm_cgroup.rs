@@ -0,0 +1,104 @@
+//! Cgroup-scoped LSM probes.
+use std::{
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+};
+
+use crate::{
+    generated::{bpf_attach_type::BPF_LSM_CGROUP, bpf_prog_type::BPF_PROG_TYPE_LSM},
+    obj::btf::{Btf, BtfKind},
+    programs::{load_program, Cgroup, FdLink, LinkRef, LsmLoadError, ProgramData, ProgramError},
+    sys::bpf_link_create,
+};
+
+/// A program that attaches to Linux LSM hooks for the tasks of a given cgroup.
+///
+/// [`LsmCgroup`] programs are like [`Lsm`](crate::programs::Lsm) programs,
+/// but instead of running for every invocation of the [security hook][1],
+/// they only run when the hook is invoked by a task belonging to the cgroup
+/// the program is attached to, or to one of its descendants. This can be
+/// used to enforce a different security policy per container.
+///
+/// LSM cgroup probes require a kernel compiled with `CONFIG_BPF_LSM=y` and
+/// `CONFIG_DEBUG_INFO_BTF=y`. Unlike global LSM probes, they don't need the
+/// BPF LSM to be enabled in the kernel's boot parameters.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 6.0.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum LsmError {
+/// #     #[error(transparent)]
+/// #     LsmLoad(#[from] aya::programs::LsmLoadError),
+/// #     #[error(transparent)]
+/// #     BtfError(#[from] aya::BtfError),
+/// #     #[error(transparent)]
+/// #     Program(#[from] aya::programs::ProgramError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError),
+/// # }
+/// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{Bpf, programs::LsmCgroup, BtfError, Btf};
+///
+/// let btf = Btf::from_sys_fs()?;
+/// let program = bpf.program_mut_typed::<LsmCgroup>("lsm_prog")?;
+/// program.load("socket_bind", &btf)?;
+/// program.attach_path("/sys/fs/cgroup/unified/container")?;
+/// # Ok::<(), LsmError>(())
+/// ```
+///
+/// [1]: https://elixir.bootlin.com/linux/latest/source/include/linux/lsm_hook_defs.h
+#[derive(Debug)]
+#[doc(alias = "BPF_LSM_CGROUP")]
+pub struct LsmCgroup {
+    pub(crate) data: ProgramData,
+}
+
+impl LsmCgroup {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    ///
+    /// # Arguments
+    ///
+    /// * `lsm_hook_name` - full name of the LSM hook that the program should
+    ///   be attached to
+    pub fn load(&mut self, lsm_hook_name: &str, btf: &Btf) -> Result<(), LsmLoadError> {
+        self.data.expected_attach_type = Some(BPF_LSM_CGROUP);
+        let type_name = format!("bpf_lsm_{}", lsm_hook_name);
+        self.data.attach_btf_id =
+            Some(btf.id_by_type_name_kind(type_name.as_str(), BtfKind::Func)?);
+        load_program(BPF_PROG_TYPE_LSM, &mut self.data).map_err(LsmLoadError::from)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program to the given cgroup.
+    pub fn attach<T: AsRawFd>(&mut self, cgroup: T) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let cgroup_fd = cgroup.as_raw_fd();
+
+        let link_fd =
+            bpf_link_create(prog_fd, cgroup_fd, BPF_LSM_CGROUP, 0).map_err(|(_, io_error)| {
+                ProgramError::SyscallError {
+                    call: "bpf_link_create".to_owned(),
+                    io_error,
+                }
+            })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+
+    /// Attaches the program to the cgroup at `path`.
+    pub fn attach_path<P: AsRef<Path>>(&mut self, path: P) -> Result<LinkRef, ProgramError> {
+        let cgroup = Cgroup::open(path)?;
+        self.attach(cgroup)
+    }
+}
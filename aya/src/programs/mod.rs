@@ -15,11 +15,10 @@
 //!
 //! ```no_run
 //! use aya::{Bpf, programs::KProbe};
-//! use std::convert::TryInto;
 //!
 //! let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 //! // intercept_wakeups is the name of the program we want to load
-//! let program: &mut KProbe = bpf.program_mut("intercept_wakeups")?.try_into()?;
+//! let program = bpf.program_mut_typed::<KProbe>("intercept_wakeups")?;
 //! program.load()?;
 //! // intercept_wakeups will be called every time try_to_wake_up() is called
 //! // inside the kernel
@@ -36,15 +35,27 @@
 //! [`Bpf::program`]: crate::Bpf::program
 //! [`Bpf::program_mut`]: crate::Bpf::program_mut
 //! [`maps`]: crate::maps
+mod cgroup;
 mod cgroup_skb;
+mod extension;
+mod fentry;
+mod fexit;
+mod flow_dissector;
+mod iter;
 mod kprobe;
+mod kprobe_multi;
+pub mod links;
 mod lirc_mode2;
 mod lsm;
+mod lsm_cgroup;
+mod netfilter;
+mod netns;
 mod perf_attach;
 pub mod perf_event;
 mod probe;
 mod raw_trace_point;
 mod sk_msg;
+mod sk_reuseport;
 mod sk_skb;
 mod sock_ops;
 mod socket_filter;
@@ -52,44 +63,86 @@ pub mod tc;
 mod tp_btf;
 mod trace_point;
 mod uprobe;
+mod uprobe_multi;
+pub mod usdt;
 mod xdp;
+mod xdp_dispatcher;
 
-use libc::{close, dup, ENOSPC};
+use libc::{close, dup, E2BIG, EINVAL, ENOENT, ENOSPC};
 use std::{
     cell::RefCell,
     cmp,
     convert::TryFrom,
     ffi::{CStr, CString},
-    io,
-    os::unix::io::{AsRawFd, RawFd},
-    path::Path,
+    fs, io, mem,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+    ptr,
     rc::Rc,
+    time::Duration,
 };
 use thiserror::Error;
 
+pub use cgroup::{Cgroup, CgroupError};
 pub use cgroup_skb::{CgroupSkb, CgroupSkbAttachType};
+pub use extension::{Extension, ExtensionError};
+pub use fentry::FEntry;
+pub use fexit::FExit;
+pub use flow_dissector::{FlowDissector, FlowKeys};
+pub use iter::{CgroupIterOrder, Iter, IterReader, TaskIter, TaskIterError};
 pub use kprobe::{KProbe, KProbeError};
+pub use kprobe_multi::KProbeMulti;
 pub use lirc_mode2::LircMode2;
-pub use lsm::{Lsm, LsmLoadError};
+pub use lsm::{Lsm, LsmHook, LsmLoadError};
+pub use lsm_cgroup::LsmCgroup;
+pub use netfilter::{Netfilter, NetfilterFlags, NetfilterHook, NetfilterProtocolFamily};
+pub use netns::NetNs;
 use perf_attach::*;
-pub use perf_event::{PerfEvent, PerfEventScope, PerfTypeId, SamplePolicy};
+pub use perf_event::{
+    BreakpointLength, BreakpointType, PerfEvent, PerfEventConfig, PerfEventScope, PerfTypeId,
+    SamplePolicy,
+};
 pub use probe::ProbeKind;
 pub use raw_trace_point::RawTracePoint;
 pub use sk_msg::SkMsg;
+pub use sk_reuseport::{SkReuseport, SkReuseportError, SkReuseportKind};
 pub use sk_skb::{SkSkb, SkSkbKind};
 pub use sock_ops::SockOps;
 pub use socket_filter::{SocketFilter, SocketFilterError};
-pub use tc::{SchedClassifier, TcAttachType, TcError};
+pub use tc::{SchedClassifier, TcAttachType, TcError, TcFlags, TcxAnchor, TcxOrder};
 pub use tp_btf::{BtfTracePoint, BtfTracePointError};
 pub use trace_point::{TracePoint, TracePointError};
 pub use uprobe::{UProbe, UProbeError};
-pub use xdp::{Xdp, XdpError, XdpFlags};
+pub use uprobe_multi::UProbeMulti;
+pub use usdt::{Usdt, UsdtError};
+pub use xdp::{Xdp, XdpAttachment, XdpError, XdpFeatures, XdpFlags};
+pub use xdp_dispatcher::{
+    XdpActions, XdpDispatcher, XdpDispatcherError, XdpDispatcherProgram, XdpRunConfig,
+    DEFAULT_RUN_PRIORITY, MAX_DISPATCHER_PROGRAMS,
+};
 
 use crate::{
-    generated::{bpf_attach_type, bpf_prog_info, bpf_prog_type},
-    maps::MapError,
-    obj::{self, Function, KernelVersion},
-    sys::{bpf_load_program, bpf_pin_object, bpf_prog_detach, bpf_prog_query, BpfLoadProgramAttrs},
+    bpf::new_program,
+    features::sanitize_helper_calls,
+    generated::{
+        bpf_attach_type, bpf_insn, bpf_map_type::BPF_MAP_TYPE_ARRAY, bpf_prog_info, bpf_prog_type,
+        BPF_F_ALLOW_MULTI, BPF_F_ALLOW_OVERRIDE,
+    },
+    maps::{Map, MapError},
+    obj::{
+        self,
+        btf::{BtfError, BtfKind, BtfType, BtfValue},
+        Function, ProgramSection,
+    },
+    sys::{
+        bpf_btf_get_fd_by_id, bpf_btf_get_info_by_fd, bpf_btf_get_next_id, bpf_get_object,
+        bpf_link_detach, bpf_link_update, bpf_load_program, bpf_map_lookup_elem_ptr,
+        bpf_obj_get_info_by_fd, bpf_pin_object, bpf_prog_bind_map, bpf_prog_detach,
+        bpf_prog_get_fd_by_id, bpf_prog_get_info_by_fd, bpf_prog_query, bpf_prog_test_run,
+        BpfLoadProgramAttrs,
+    },
+    util::{dup_fd, is_memlock_error, KernelVersion},
+    Btf,
 };
 
 /// Error type returned when working with programs.
@@ -115,6 +168,10 @@ pub enum ProgramError {
     #[error("the program is not attached")]
     NotAttached,
 
+    /// The link doesn't support being forgotten, see [`Link::forget`].
+    #[error("the link can't be forgotten")]
+    ForgetNotSupported,
+
     /// Loading the program failed.
     #[error("the BPF_PROG_LOAD syscall failed. Verifier output: {verifier_log}")]
     LoadError {
@@ -135,6 +192,30 @@ pub enum ProgramError {
         io_error: io::Error,
     },
 
+    /// The program could not be loaded because `RLIMIT_MEMLOCK` is too low.
+    #[error("the BPF_PROG_LOAD syscall failed: the RLIMIT_MEMLOCK limit is too low, raise it with setrlimit() or BpfLoader::raise_memlock_rlimit()")]
+    MemlockLimitExceeded {
+        /// The [`io::Error`] returned by the `BPF_PROG_LOAD` syscall.
+        #[source]
+        io_error: io::Error,
+    },
+
+    /// The program type requires a newer kernel.
+    ///
+    /// This is returned in place of [`ProgramError::LoadError`] when the
+    /// kernel rejected the program without a verifier log, as it does for the
+    /// program types it doesn't know.
+    #[error("the program requires kernel {required} or newer, the running kernel is {found}")]
+    KernelVersionTooOld {
+        /// The minimum kernel version required by the program type.
+        required: KernelVersion,
+        /// The version of the running kernel.
+        found: KernelVersion,
+        /// The [`io::Error`] returned by the `BPF_PROG_LOAD` syscall.
+        #[source]
+        io_error: io::Error,
+    },
+
     /// The network interface does not exist.
     #[error("unknown network interface {name}")]
     UnknownInterface { name: String },
@@ -143,6 +224,23 @@ pub enum ProgramError {
     #[error("unexpected program type")]
     UnexpectedProgramType,
 
+    /// The program returned by [`Bpf::program_typed`](crate::Bpf::program_typed) or
+    /// [`Bpf::program_mut_typed`](crate::Bpf::program_mut_typed) is not of the requested type.
+    #[error("program `{name}` is of type {found}, not {expected}")]
+    ProgramTypeMismatch {
+        name: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    /// The attach target of the program can't be determined.
+    #[error("program `{name}` has no attach target")]
+    NoAttachTarget { name: String },
+
+    /// The kernel has no BTF object for the given module.
+    #[error("no BTF object found for kernel module `{module}`")]
+    ModuleBtfNotFound { module: String },
+
     #[error("invalid pin path `{error}`")]
     InvalidPinPath { error: String },
 
@@ -150,6 +248,10 @@ pub enum ProgramError {
     #[error(transparent)]
     MapError(#[from] MapError),
 
+    /// An error occurred while resolving or opening a [`Cgroup`].
+    #[error(transparent)]
+    CgroupError(#[from] CgroupError),
+
     /// An error occurred while working with a [`KProbe`].
     #[error(transparent)]
     KProbeError(#[from] KProbeError),
@@ -158,6 +260,10 @@ pub enum ProgramError {
     #[error(transparent)]
     UProbeError(#[from] UProbeError),
 
+    /// An error occurred while working with an [`Usdt`] program.
+    #[error(transparent)]
+    UsdtError(#[from] UsdtError),
+
     /// An error occurred while working with a [`TracePoint`].
     #[error(transparent)]
     TracePointError(#[from] TracePointError),
@@ -166,6 +272,10 @@ pub enum ProgramError {
     #[error(transparent)]
     SocketFilterError(#[from] SocketFilterError),
 
+    /// An error occurred while working with a [`SkReuseport`] program.
+    #[error(transparent)]
+    SkReuseportError(#[from] SkReuseportError),
+
     /// An error occurred while working with an [`Xdp`] program.
     #[error(transparent)]
     XdpError(#[from] XdpError),
@@ -177,17 +287,48 @@ pub enum ProgramError {
     /// An error occurred while working with a BTF raw tracepoint program.
     #[error(transparent)]
     BtfTracePointError(#[from] BtfTracePointError),
+
+    /// An error occurred while working with BTF.
+    #[error(transparent)]
+    BtfError(#[from] BtfError),
+
+    /// An error occurred while working with an [`Extension`] program.
+    #[error(transparent)]
+    ExtensionError(#[from] ExtensionError),
 }
 
 pub trait ProgramFd {
     fn fd(&self) -> Option<RawFd>;
+
+    /// Duplicates the program file descriptor.
+    ///
+    /// The returned descriptor refers to the same program and keeps it loaded
+    /// until it's closed, so it can be passed to other crates or processes.
+    fn try_clone_fd(&self) -> Result<OwnedFd, ProgramError> {
+        let fd = self.fd().ok_or(ProgramError::NotLoaded)?;
+        dup_fd(fd).map_err(|io_error| ProgramError::SyscallError {
+            call: "fcntl".to_owned(),
+            io_error,
+        })
+    }
+}
+
+/// Raw program file descriptors, for example obtained from a pinned program
+/// or from another process.
+impl ProgramFd for RawFd {
+    fn fd(&self) -> Option<RawFd> {
+        Some(*self)
+    }
 }
 
 /// eBPF program type.
 #[derive(Debug)]
 pub enum Program {
     KProbe(KProbe),
+    KProbeMulti(KProbeMulti),
     UProbe(UProbe),
+    UProbeMulti(UProbeMulti),
+    Usdt(Usdt),
     TracePoint(TracePoint),
     SocketFilter(SocketFilter),
     Xdp(Xdp),
@@ -200,7 +341,15 @@ pub enum Program {
     PerfEvent(PerfEvent),
     RawTracePoint(RawTracePoint),
     Lsm(Lsm),
+    LsmCgroup(LsmCgroup),
     BtfTracePoint(BtfTracePoint),
+    FlowDissector(FlowDissector),
+    SkReuseport(SkReuseport),
+    Netfilter(Netfilter),
+    Extension(Extension),
+    FEntry(FEntry),
+    FExit(FExit),
+    Iter(Iter),
 }
 
 impl Program {
@@ -215,7 +364,43 @@ impl Program {
     /// If the program is already loaded, [`ProgramError::AlreadyLoaded`] is
     /// returned.
     pub fn load(&mut self) -> Result<(), ProgramError> {
-        load_program(self.prog_type(), self.data_mut())
+        match self {
+            // the BTF id of the target is resolved at load time
+            Program::Extension(p) => p.load(),
+            _ => load_program(self.prog_type(), self.data_mut()),
+        }
+    }
+
+    /// Returns the name of the program type, eg `"Xdp"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Program::KProbe(_) => "KProbe",
+            Program::KProbeMulti(_) => "KProbeMulti",
+            Program::UProbe(_) => "UProbe",
+            Program::UProbeMulti(_) => "UProbeMulti",
+            Program::Usdt(_) => "Usdt",
+            Program::TracePoint(_) => "TracePoint",
+            Program::SocketFilter(_) => "SocketFilter",
+            Program::Xdp(_) => "Xdp",
+            Program::SkMsg(_) => "SkMsg",
+            Program::SkSkb(_) => "SkSkb",
+            Program::SockOps(_) => "SockOps",
+            Program::SchedClassifier(_) => "SchedClassifier",
+            Program::CgroupSkb(_) => "CgroupSkb",
+            Program::LircMode2(_) => "LircMode2",
+            Program::PerfEvent(_) => "PerfEvent",
+            Program::RawTracePoint(_) => "RawTracePoint",
+            Program::Lsm(_) => "Lsm",
+            Program::LsmCgroup(_) => "LsmCgroup",
+            Program::BtfTracePoint(_) => "BtfTracePoint",
+            Program::FlowDissector(_) => "FlowDissector",
+            Program::SkReuseport(_) => "SkReuseport",
+            Program::Netfilter(_) => "Netfilter",
+            Program::Extension(_) => "Extension",
+            Program::FEntry(_) => "FEntry",
+            Program::FExit(_) => "FExit",
+            Program::Iter(_) => "Iter",
+        }
     }
 
     /// Returns the low level program type.
@@ -223,7 +408,10 @@ impl Program {
         use crate::generated::bpf_prog_type::*;
         match self {
             Program::KProbe(_) => BPF_PROG_TYPE_KPROBE,
+            Program::KProbeMulti(_) => BPF_PROG_TYPE_KPROBE,
             Program::UProbe(_) => BPF_PROG_TYPE_KPROBE,
+            Program::UProbeMulti(_) => BPF_PROG_TYPE_KPROBE,
+            Program::Usdt(_) => BPF_PROG_TYPE_KPROBE,
             Program::TracePoint(_) => BPF_PROG_TYPE_TRACEPOINT,
             Program::SocketFilter(_) => BPF_PROG_TYPE_SOCKET_FILTER,
             Program::Xdp(_) => BPF_PROG_TYPE_XDP,
@@ -236,7 +424,15 @@ impl Program {
             Program::PerfEvent(_) => BPF_PROG_TYPE_PERF_EVENT,
             Program::RawTracePoint(_) => BPF_PROG_TYPE_RAW_TRACEPOINT,
             Program::Lsm(_) => BPF_PROG_TYPE_LSM,
+            Program::LsmCgroup(_) => BPF_PROG_TYPE_LSM,
             Program::BtfTracePoint(_) => BPF_PROG_TYPE_TRACING,
+            Program::FlowDissector(_) => BPF_PROG_TYPE_FLOW_DISSECTOR,
+            Program::SkReuseport(_) => BPF_PROG_TYPE_SK_REUSEPORT,
+            Program::Netfilter(_) => BPF_PROG_TYPE_NETFILTER,
+            Program::Extension(_) => BPF_PROG_TYPE_EXT,
+            Program::FEntry(_) => BPF_PROG_TYPE_TRACING,
+            Program::FExit(_) => BPF_PROG_TYPE_TRACING,
+            Program::Iter(_) => BPF_PROG_TYPE_TRACING,
         }
     }
 
@@ -245,15 +441,170 @@ impl Program {
         &self.data().name
     }
 
+    /// Opens the loaded program with the given id.
+    ///
+    /// This gives access to programs loaded by other processes, for example
+    /// the ones found with `bpftool prog list`, without them having to be
+    /// pinned. The variant of the returned program is inferred from its
+    /// program type. When several variants share a type, the most generic one
+    /// is used: [`KProbe`] for `BPF_PROG_TYPE_KPROBE` programs, [`FEntry`] for
+    /// `BPF_PROG_TYPE_TRACING` programs (which can attach fexit and `tp_btf`
+    /// programs too), [`Lsm`] for `BPF_PROG_TYPE_LSM` programs and so on.
+    ///
+    /// Opening programs by id requires `CAP_SYS_ADMIN`.
+    ///
+    /// # Errors
+    ///
+    /// [`ProgramError::UnexpectedProgramType`] is returned for program types
+    /// that have no matching [`Program`] variant.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::programs::{Program, Xdp, XdpFlags};
+    /// use std::convert::TryInto;
+    ///
+    /// let mut program = Program::from_id(42)?;
+    /// let xdp: &mut Xdp = (&mut program).try_into()?;
+    /// xdp.attach("eth0", XdpFlags::default())?;
+    /// # Ok::<(), aya::programs::ProgramError>(())
+    /// ```
+    pub fn from_id(id: u32) -> Result<Program, ProgramError> {
+        let fd = bpf_prog_get_fd_by_id(id).map_err(|io_error| ProgramError::SyscallError {
+            call: "bpf_prog_get_fd_by_id".to_owned(),
+            io_error,
+        })?;
+        // the descriptor is closed when the OwnedFd is dropped if the
+        // program type isn't supported
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let info = bpf_obj_get_info_by_fd(fd.as_raw_fd()).map_err(|io_error| {
+            ProgramError::SyscallError {
+                call: "bpf_obj_get_info_by_fd".to_owned(),
+                io_error,
+            }
+        })?;
+        let info = ProgramInfo(info);
+        let kernel_name = info.name_as_str().unwrap_or_default().to_owned();
+        let name = kernel_name.clone();
+
+        use crate::generated::bpf_prog_type::*;
+        let section = match info.0.type_ {
+            x if x == BPF_PROG_TYPE_SOCKET_FILTER as u32 => ProgramSection::SocketFilter { name },
+            x if x == BPF_PROG_TYPE_KPROBE as u32 => ProgramSection::KProbe { name },
+            x if x == BPF_PROG_TYPE_SCHED_CLS as u32 => ProgramSection::SchedClassifier { name },
+            x if x == BPF_PROG_TYPE_TRACEPOINT as u32 => ProgramSection::TracePoint { name },
+            x if x == BPF_PROG_TYPE_XDP as u32 => ProgramSection::Xdp { name, frags: false },
+            x if x == BPF_PROG_TYPE_PERF_EVENT as u32 => ProgramSection::PerfEvent { name },
+            x if x == BPF_PROG_TYPE_CGROUP_SKB as u32 => ProgramSection::CgroupSkbIngress { name },
+            x if x == BPF_PROG_TYPE_SOCK_OPS as u32 => ProgramSection::SockOps { name },
+            x if x == BPF_PROG_TYPE_SK_SKB as u32 => ProgramSection::SkSkbStreamVerdict { name },
+            x if x == BPF_PROG_TYPE_SK_MSG as u32 => ProgramSection::SkMsg { name },
+            x if x == BPF_PROG_TYPE_RAW_TRACEPOINT as u32 => ProgramSection::RawTracePoint { name },
+            x if x == BPF_PROG_TYPE_LIRC_MODE2 as u32 => ProgramSection::LircMode2 { name },
+            x if x == BPF_PROG_TYPE_SK_REUSEPORT as u32 => ProgramSection::SkReuseport { name },
+            x if x == BPF_PROG_TYPE_FLOW_DISSECTOR as u32 => ProgramSection::FlowDissector { name },
+            x if x == BPF_PROG_TYPE_TRACING as u32 => ProgramSection::FEntry {
+                name,
+                sleepable: false,
+            },
+            x if x == BPF_PROG_TYPE_LSM as u32 => ProgramSection::Lsm {
+                name,
+                sleepable: false,
+            },
+            x if x == BPF_PROG_TYPE_EXT as u32 => ProgramSection::Extension { name },
+            x if x == BPF_PROG_TYPE_NETFILTER as u32 => ProgramSection::Netfilter { name },
+            _ => return Err(ProgramError::UnexpectedProgramType),
+        };
+
+        let obj = obj::Program {
+            license: CString::default(),
+            kernel_version: obj::KernelVersion::Any,
+            section,
+            function: Function {
+                address: 0,
+                name: kernel_name.clone(),
+                section_index: object::SectionIndex(0),
+                section_offset: 0,
+                instructions: Vec::new(),
+                func_info: Vec::new(),
+                line_info: Vec::new(),
+            },
+        };
+        let mut program = new_program(kernel_name.clone(), obj, None, None);
+        let data = program.data_mut();
+        data.kernel_name = Some(kernel_name);
+        data.fd = Some(fd.into_raw_fd());
+        Ok(program)
+    }
+
+    /// Returns information about the loaded program.
+    pub fn info(&self) -> Result<ProgramInfo, ProgramError> {
+        let fd = self.data().fd_or_err()?;
+        bpf_obj_get_info_by_fd(fd)
+            .map(ProgramInfo)
+            .map_err(|io_error| ProgramError::SyscallError {
+                call: "bpf_obj_get_info_by_fd".to_owned(),
+                io_error,
+            })
+    }
+
+    /// Returns the instructions of the loaded program, as rewritten by the
+    /// verifier and as JITed to native code.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let mut bpf = aya::Bpf::load(&[])?;
+    /// let program = bpf.program_mut("xdp_prog")?;
+    /// program.load()?;
+    ///
+    /// let insns = program.instructions()?;
+    /// for insn in insns.xlated_insns() {
+    ///     println!("{:?}", insn);
+    /// }
+    /// for (addr, code) in insns.jited_functions() {
+    ///     println!("{:#x}: {} bytes", addr, code.len());
+    /// }
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn instructions(&self) -> Result<ProgramInstructions, ProgramError> {
+        program_instructions(self.data().fd_or_err()?)
+    }
+
+    /// Borrows the program file descriptor.
+    ///
+    /// If the program is not loaded, [`ProgramError::NotLoaded`] is returned.
+    pub fn borrow_fd(&self) -> Result<BorrowedFd<'_>, ProgramError> {
+        let fd = self.data().fd_or_err()?;
+        // Safety: the descriptor is owned by the program and stays open until
+        // it's unloaded, which requires a mutable borrow
+        Ok(unsafe { BorrowedFd::borrow_raw(fd) })
+    }
+
     /// Pin the program to the provided path
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ProgramError> {
         self.data_mut().pin(path)
     }
 
-    fn data(&self) -> &ProgramData {
+    /// Unloads the program from the kernel.
+    ///
+    /// All the links created by attaching the program are detached, then the
+    /// program file descriptor is closed. The kernel frees the program once
+    /// nothing else references it, for example a pin. The program can be
+    /// loaded again with [`Program::load`].
+    ///
+    /// If the program is not loaded, [`ProgramError::NotLoaded`] is returned.
+    pub fn unload(&mut self) -> Result<(), ProgramError> {
+        self.data_mut().unload()
+    }
+
+    pub(crate) fn data(&self) -> &ProgramData {
         match self {
             Program::KProbe(p) => &p.data,
+            Program::KProbeMulti(p) => &p.data,
             Program::UProbe(p) => &p.data,
+            Program::UProbeMulti(p) => &p.data,
+            Program::Usdt(p) => &p.data,
             Program::TracePoint(p) => &p.data,
             Program::SocketFilter(p) => &p.data,
             Program::Xdp(p) => &p.data,
@@ -266,14 +617,25 @@ impl Program {
             Program::PerfEvent(p) => &p.data,
             Program::RawTracePoint(p) => &p.data,
             Program::Lsm(p) => &p.data,
+            Program::LsmCgroup(p) => &p.data,
             Program::BtfTracePoint(p) => &p.data,
+            Program::FlowDissector(p) => &p.data,
+            Program::SkReuseport(p) => &p.data,
+            Program::Netfilter(p) => &p.data,
+            Program::Extension(p) => &p.data,
+            Program::FEntry(p) => &p.data,
+            Program::FExit(p) => &p.data,
+            Program::Iter(p) => &p.data,
         }
     }
 
-    fn data_mut(&mut self) -> &mut ProgramData {
+    pub(crate) fn data_mut(&mut self) -> &mut ProgramData {
         match self {
             Program::KProbe(p) => &mut p.data,
+            Program::KProbeMulti(p) => &mut p.data,
             Program::UProbe(p) => &mut p.data,
+            Program::UProbeMulti(p) => &mut p.data,
+            Program::Usdt(p) => &mut p.data,
             Program::TracePoint(p) => &mut p.data,
             Program::SocketFilter(p) => &mut p.data,
             Program::Xdp(p) => &mut p.data,
@@ -286,7 +648,95 @@ impl Program {
             Program::PerfEvent(p) => &mut p.data,
             Program::RawTracePoint(p) => &mut p.data,
             Program::Lsm(p) => &mut p.data,
+            Program::LsmCgroup(p) => &mut p.data,
             Program::BtfTracePoint(p) => &mut p.data,
+            Program::FlowDissector(p) => &mut p.data,
+            Program::SkReuseport(p) => &mut p.data,
+            Program::Netfilter(p) => &mut p.data,
+            Program::Extension(p) => &mut p.data,
+            Program::FEntry(p) => &mut p.data,
+            Program::FExit(p) => &mut p.data,
+            Program::Iter(p) => &mut p.data,
+        }
+    }
+}
+
+/// The type of an eBPF program, as known to the kernel.
+///
+/// Several [`Program`] variants can share the same program type, for example
+/// [`KProbe`] and [`UProbe`] are both [`ProgramType::KProbe`] programs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProgramType {
+    SocketFilter,
+    KProbe,
+    SchedClassifier,
+    SchedAction,
+    TracePoint,
+    Xdp,
+    PerfEvent,
+    CgroupSkb,
+    CgroupSock,
+    LwtInput,
+    LwtOutput,
+    LwtXmit,
+    SockOps,
+    SkSkb,
+    CgroupDevice,
+    SkMsg,
+    RawTracePoint,
+    CgroupSockAddr,
+    LwtSeg6local,
+    LircMode2,
+    SkReuseport,
+    FlowDissector,
+    CgroupSysctl,
+    RawTracePointWritable,
+    CgroupSockopt,
+    Tracing,
+    StructOps,
+    Extension,
+    Lsm,
+    SkLookup,
+    Syscall,
+    Netfilter,
+}
+
+impl ProgramType {
+    pub(crate) fn bpf_prog_type(self) -> bpf_prog_type {
+        use crate::generated::bpf_prog_type::*;
+        match self {
+            ProgramType::SocketFilter => BPF_PROG_TYPE_SOCKET_FILTER,
+            ProgramType::KProbe => BPF_PROG_TYPE_KPROBE,
+            ProgramType::SchedClassifier => BPF_PROG_TYPE_SCHED_CLS,
+            ProgramType::SchedAction => BPF_PROG_TYPE_SCHED_ACT,
+            ProgramType::TracePoint => BPF_PROG_TYPE_TRACEPOINT,
+            ProgramType::Xdp => BPF_PROG_TYPE_XDP,
+            ProgramType::PerfEvent => BPF_PROG_TYPE_PERF_EVENT,
+            ProgramType::CgroupSkb => BPF_PROG_TYPE_CGROUP_SKB,
+            ProgramType::CgroupSock => BPF_PROG_TYPE_CGROUP_SOCK,
+            ProgramType::LwtInput => BPF_PROG_TYPE_LWT_IN,
+            ProgramType::LwtOutput => BPF_PROG_TYPE_LWT_OUT,
+            ProgramType::LwtXmit => BPF_PROG_TYPE_LWT_XMIT,
+            ProgramType::SockOps => BPF_PROG_TYPE_SOCK_OPS,
+            ProgramType::SkSkb => BPF_PROG_TYPE_SK_SKB,
+            ProgramType::CgroupDevice => BPF_PROG_TYPE_CGROUP_DEVICE,
+            ProgramType::SkMsg => BPF_PROG_TYPE_SK_MSG,
+            ProgramType::RawTracePoint => BPF_PROG_TYPE_RAW_TRACEPOINT,
+            ProgramType::CgroupSockAddr => BPF_PROG_TYPE_CGROUP_SOCK_ADDR,
+            ProgramType::LwtSeg6local => BPF_PROG_TYPE_LWT_SEG6LOCAL,
+            ProgramType::LircMode2 => BPF_PROG_TYPE_LIRC_MODE2,
+            ProgramType::SkReuseport => BPF_PROG_TYPE_SK_REUSEPORT,
+            ProgramType::FlowDissector => BPF_PROG_TYPE_FLOW_DISSECTOR,
+            ProgramType::CgroupSysctl => BPF_PROG_TYPE_CGROUP_SYSCTL,
+            ProgramType::RawTracePointWritable => BPF_PROG_TYPE_RAW_TRACEPOINT_WRITABLE,
+            ProgramType::CgroupSockopt => BPF_PROG_TYPE_CGROUP_SOCKOPT,
+            ProgramType::Tracing => BPF_PROG_TYPE_TRACING,
+            ProgramType::StructOps => BPF_PROG_TYPE_STRUCT_OPS,
+            ProgramType::Extension => BPF_PROG_TYPE_EXT,
+            ProgramType::Lsm => BPF_PROG_TYPE_LSM,
+            ProgramType::SkLookup => BPF_PROG_TYPE_SK_LOOKUP,
+            ProgramType::Syscall => BPF_PROG_TYPE_SYSCALL,
+            ProgramType::Netfilter => BPF_PROG_TYPE_NETFILTER,
         }
     }
 }
@@ -294,12 +744,22 @@ impl Program {
 #[derive(Debug)]
 pub(crate) struct ProgramData {
     pub(crate) name: String,
+    // the name of the program in the kernel, programs are unnamed by default
+    pub(crate) kernel_name: Option<String>,
+    // maps bound to the program once it's loaded, which it doesn't reference
+    pub(crate) bound_maps: Vec<RawFd>,
     pub(crate) obj: obj::Program,
     pub(crate) fd: Option<RawFd>,
     pub(crate) links: Vec<Rc<RefCell<dyn Link>>>,
     pub(crate) expected_attach_type: Option<bpf_attach_type>,
+    pub(crate) flags: u32,
     pub(crate) attach_btf_obj_fd: Option<u32>,
     pub(crate) attach_btf_id: Option<u32>,
+    pub(crate) attach_prog_fd: Option<RawFd>,
+    pub(crate) btf_fd: Option<RawFd>,
+    pub(crate) token_fd: Option<RawFd>,
+    // the interface the program is offloaded to
+    pub(crate) ifindex: Option<u32>,
 }
 
 impl ProgramData {
@@ -313,6 +773,16 @@ impl ProgramData {
         LinkRef::new(link)
     }
 
+    pub(crate) fn unload(&mut self) -> Result<(), ProgramError> {
+        let fd = self.fd.take().ok_or(ProgramError::NotLoaded)?;
+        for link in self.links.drain(..) {
+            // links detached by the user return AlreadyDetached
+            let _ = link.borrow_mut().detach();
+        }
+        unsafe { close(fd) };
+        Ok(())
+    }
+
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ProgramError> {
         let fd = self.fd_or_err()?;
         let path_string =
@@ -339,7 +809,7 @@ pub(crate) struct VerifierLog {
 }
 
 impl VerifierLog {
-    fn new() -> VerifierLog {
+    pub(crate) fn new() -> VerifierLog {
         VerifierLog { buf: Vec::new() }
     }
 
@@ -347,7 +817,7 @@ impl VerifierLog {
         &mut self.buf
     }
 
-    fn grow(&mut self) {
+    pub(crate) fn grow(&mut self) {
         let len = cmp::max(
             MIN_LOG_BUF_SIZE,
             cmp::min(MAX_LOG_BUF_SIZE, self.buf.capacity() * 10),
@@ -362,7 +832,7 @@ impl VerifierLog {
         }
     }
 
-    fn truncate(&mut self) {
+    pub(crate) fn truncate(&mut self) {
         if self.buf.is_empty() {
             return;
         }
@@ -391,38 +861,72 @@ fn load_program(prog_type: bpf_prog_type, data: &mut ProgramData) -> Result<(),
         return Err(ProgramError::AlreadyLoaded);
     }
     let crate::obj::Program {
-        function: Function { instructions, .. },
+        function:
+            Function {
+                instructions,
+                func_info,
+                line_info,
+                ..
+            },
         license,
         kernel_version,
         ..
     } = obj;
 
     let target_kernel_version = match *kernel_version {
-        KernelVersion::Any => {
-            let (major, minor, patch) = crate::sys::kernel_version().unwrap();
-            (major << 16) + (minor << 8) + patch
-        }
+        obj::KernelVersion::Any => KernelVersion::current()
+            .map(|version| version.code())
+            .unwrap_or(0),
         _ => (*kernel_version).into(),
     };
 
+    let features = crate::features();
+    sanitize_helper_calls(
+        instructions,
+        features.probe_read_kernel(),
+        features.ringbuf(),
+    );
+
+    // func_info refers to FUNC types, which are sanitized away when the kernel
+    // doesn't support them
+    let (func_info, line_info) = if features.btf_func() {
+        (&func_info[..], &line_info[..])
+    } else {
+        (&[][..], &[][..])
+    };
+
     let mut log_buf = VerifierLog::new();
     let mut retries = 0;
     let mut ret;
     loop {
         let attr = BpfLoadProgramAttrs {
+            name: data.kernel_name.as_deref(),
             ty: prog_type,
             insns: instructions,
             license,
             kernel_version: target_kernel_version,
             expected_attach_type: data.expected_attach_type,
+            flags: data.flags,
             attach_btf_obj_fd: data.attach_btf_obj_fd,
             attach_btf_id: data.attach_btf_id,
+            attach_prog_fd: data.attach_prog_fd,
+            prog_btf_fd: data.btf_fd,
+            token_fd: data.token_fd,
+            prog_ifindex: data.ifindex,
+            func_info,
+            line_info,
             log: &mut log_buf,
         };
         ret = bpf_load_program(attr);
         match &ret {
             Ok(prog_fd) => {
-                *fd = Some(*prog_fd as RawFd);
+                let prog_fd = *prog_fd as RawFd;
+                // like libbpf, binding is best effort: it requires kernel 5.10
+                // and the maps are only needed for introspection
+                for map_fd in &data.bound_maps {
+                    let _ = bpf_prog_bind_map(prog_fd, *map_fd);
+                }
+                *fd = Some(prog_fd);
                 return Ok(());
             }
             Err((_, io_error)) if retries == 0 || io_error.raw_os_error() == Some(ENOSPC) => {
@@ -437,7 +941,31 @@ fn load_program(prog_type: bpf_prog_type, data: &mut ProgramData) -> Result<(),
     }
 
     if let Err((_, io_error)) = ret {
+        if is_memlock_error(&io_error) {
+            return Err(ProgramError::MemlockLimitExceeded { io_error });
+        }
         log_buf.truncate();
+        // distributions backport features to older kernels, so the version is
+        // only checked to explain why loading failed, when the verifier
+        // didn't say why
+        let no_log = match log_buf.as_c_str() {
+            Some(log) => log.to_bytes().is_empty(),
+            None => true,
+        };
+        if no_log && matches!(io_error.raw_os_error(), Some(EINVAL) | Some(E2BIG)) {
+            if let (Some(required), Ok(found)) = (
+                min_kernel_version(prog_type, data.expected_attach_type),
+                KernelVersion::current(),
+            ) {
+                if found < required {
+                    return Err(ProgramError::KernelVersionTooOld {
+                        required,
+                        found,
+                        io_error,
+                    });
+                }
+            }
+        }
         return Err(ProgramError::LoadError {
             io_error,
             verifier_log: log_buf
@@ -450,6 +978,155 @@ fn load_program(prog_type: bpf_prog_type, data: &mut ProgramData) -> Result<(),
     Ok(())
 }
 
+// the kernel versions in which program (and some attach) types were introduced
+fn min_kernel_version(
+    prog_type: bpf_prog_type,
+    expected_attach_type: Option<bpf_attach_type>,
+) -> Option<KernelVersion> {
+    use crate::generated::{bpf_attach_type::*, bpf_prog_type::*};
+    let (major, minor) = match (prog_type, expected_attach_type) {
+        (BPF_PROG_TYPE_KPROBE, Some(BPF_TRACE_UPROBE_MULTI)) => (6, 6),
+        (BPF_PROG_TYPE_KPROBE, Some(BPF_TRACE_KPROBE_MULTI)) => (5, 18),
+        (BPF_PROG_TYPE_SOCKET_FILTER, _) => (4, 0),
+        (BPF_PROG_TYPE_KPROBE, _) | (BPF_PROG_TYPE_SCHED_CLS, _) | (BPF_PROG_TYPE_SCHED_ACT, _) => {
+            (4, 1)
+        }
+        (BPF_PROG_TYPE_TRACEPOINT, _) => (4, 7),
+        (BPF_PROG_TYPE_XDP, _) => (4, 8),
+        (BPF_PROG_TYPE_PERF_EVENT, _) => (4, 9),
+        (BPF_PROG_TYPE_CGROUP_SKB, _) | (BPF_PROG_TYPE_CGROUP_SOCK, _) => (4, 10),
+        (BPF_PROG_TYPE_LWT_IN, _) | (BPF_PROG_TYPE_LWT_OUT, _) | (BPF_PROG_TYPE_LWT_XMIT, _) => {
+            (4, 10)
+        }
+        (BPF_PROG_TYPE_SOCK_OPS, _) => (4, 13),
+        (BPF_PROG_TYPE_SK_SKB, _) => (4, 14),
+        (BPF_PROG_TYPE_CGROUP_DEVICE, _) => (4, 15),
+        (BPF_PROG_TYPE_SK_MSG, _)
+        | (BPF_PROG_TYPE_RAW_TRACEPOINT, _)
+        | (BPF_PROG_TYPE_CGROUP_SOCK_ADDR, _) => (4, 17),
+        (BPF_PROG_TYPE_LIRC_MODE2, _) | (BPF_PROG_TYPE_LWT_SEG6LOCAL, _) => (4, 18),
+        (BPF_PROG_TYPE_SK_REUSEPORT, _) => (4, 19),
+        (BPF_PROG_TYPE_FLOW_DISSECTOR, _) => (4, 20),
+        (BPF_PROG_TYPE_CGROUP_SYSCTL, _) | (BPF_PROG_TYPE_RAW_TRACEPOINT_WRITABLE, _) => (5, 2),
+        (BPF_PROG_TYPE_CGROUP_SOCKOPT, _) => (5, 3),
+        (BPF_PROG_TYPE_TRACING, _) => (5, 5),
+        (BPF_PROG_TYPE_STRUCT_OPS, _) | (BPF_PROG_TYPE_EXT, _) => (5, 6),
+        (BPF_PROG_TYPE_LSM, _) => (5, 7),
+        (BPF_PROG_TYPE_SK_LOOKUP, _) => (5, 9),
+        (BPF_PROG_TYPE_SYSCALL, _) => (5, 14),
+        (BPF_PROG_TYPE_NETFILTER, _) => (6, 4),
+        _ => return None,
+    };
+    Some(KernelVersion::new(major, minor, 0))
+}
+
+/// Loads a program whose `attach_btf_id` refers to a type of `btf`.
+///
+/// If `btf` is the split BTF of a kernel module, the BTF object of the module is
+/// passed to the kernel along with the type id.
+fn load_btf_program(
+    prog_type: bpf_prog_type,
+    data: &mut ProgramData,
+    btf: &Btf,
+) -> Result<(), ProgramError> {
+    let btf_fd = match btf.module() {
+        Some(module) => Some(module_btf_fd(module)?),
+        None => None,
+    };
+    data.attach_btf_obj_fd = btf_fd.map(|fd| fd as u32);
+    let ret = load_program(prog_type, data);
+    if let Some(fd) = btf_fd {
+        // the program holds a reference to the BTF object once loaded
+        unsafe { close(fd) };
+    }
+    data.attach_btf_obj_fd = None;
+
+    ret
+}
+
+/// Returns the id of the type `name` of kind `kind`.
+///
+/// If `btf` is the BTF of the running kernel and doesn't define the type, the
+/// type is looked up in the BTF of the loaded kernel modules, and the BTF of
+/// the module that defines it is returned along with the id.
+fn resolve_btf_type(btf: &Btf, name: &str, kind: BtfKind) -> Result<(u32, Option<Btf>), BtfError> {
+    let error = match btf.id_by_type_name_kind(name, kind) {
+        Ok(type_id) => return Ok((type_id, None)),
+        Err(e) if btf.module().is_some() => return Err(e),
+        Err(e) => e,
+    };
+
+    let entries = match fs::read_dir("/sys/kernel/btf") {
+        Ok(entries) => entries,
+        Err(_) => return Err(error),
+    };
+    let mut needle = Vec::with_capacity(name.len() + 2);
+    needle.push(0);
+    needle.extend_from_slice(name.as_bytes());
+    needle.push(0);
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let module = match file_name.to_str() {
+            Some(module) if module != "vmlinux" => module,
+            _ => continue,
+        };
+        // parsing split BTF is expensive, skip the modules that don't have the
+        // name in their string table
+        match fs::read(entry.path()) {
+            Ok(data) if data.windows(needle.len()).any(|w| w == needle) => {}
+            _ => continue,
+        }
+        let module_btf = match Btf::from_sys_fs_module(module, btf) {
+            Ok(module_btf) => module_btf,
+            Err(_) => continue,
+        };
+        if let Ok(type_id) = module_btf.id_by_type_name_kind(name, kind) {
+            return Ok((type_id, Some(module_btf)));
+        }
+    }
+
+    Err(error)
+}
+
+/// Returns a file descriptor to the BTF object of the kernel module `module`.
+fn module_btf_fd(module: &str) -> Result<RawFd, ProgramError> {
+    let syscall_error = |call: &str, io_error| ProgramError::SyscallError {
+        call: call.to_owned(),
+        io_error,
+    };
+
+    let mut btf_id = 0;
+    while let Some(next_id) =
+        bpf_btf_get_next_id(btf_id).map_err(|e| syscall_error("bpf_btf_get_next_id", e))?
+    {
+        btf_id = next_id;
+        let fd = match bpf_btf_get_fd_by_id(btf_id) {
+            Ok(fd) => fd,
+            // the object went away since we got its id
+            Err(e) if e.raw_os_error() == Some(ENOENT) => continue,
+            Err(e) => return Err(syscall_error("bpf_btf_get_fd_by_id", e)),
+        };
+
+        let mut name = [0u8; 64];
+        let info = match bpf_btf_get_info_by_fd(fd, &mut name) {
+            Ok(info) => info,
+            Err(e) => {
+                unsafe { close(fd) };
+                return Err(syscall_error("bpf_obj_get_info_by_fd", e));
+            }
+        };
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        if info.kernel_btf != 0 && &name[..len] == module.as_bytes() {
+            return Ok(fd);
+        }
+        unsafe { close(fd) };
+    }
+
+    Err(ProgramError::ModuleBtfNotFound {
+        module: module.to_owned(),
+    })
+}
+
 pub(crate) fn query<T: AsRawFd>(
     target_fd: T,
     attach_type: bpf_attach_type,
@@ -488,9 +1165,198 @@ pub(crate) fn query<T: AsRawFd>(
     }
 }
 
+/// Options used to run a program with `BPF_PROG_TEST_RUN`.
+///
+/// The kernel runs the program `repeat` times in a loop and reports the
+/// average duration of a run. To benchmark a program, the loop can be repeated
+/// for several `rounds`, and the minimum, average and maximum durations across
+/// the rounds are returned in [`TestRunResult`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// # let packet = [0u8; 64];
+/// use aya::programs::{TestRunOptions, Xdp};
+///
+/// let program = bpf.program_mut_typed::<Xdp>("filter")?;
+/// program.load()?;
+/// let result = program.test_run(
+///     &packet,
+///     &mut [],
+///     TestRunOptions::new().repeat(1_000_000).rounds(10).cpu(0),
+/// )?;
+/// println!(
+///     "min {:?} avg {:?} max {:?}",
+///     result.min_duration, result.duration, result.max_duration
+/// );
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestRunOptions {
+    repeat: u32,
+    rounds: u32,
+    cpu: Option<usize>,
+}
+
+impl TestRunOptions {
+    /// Creates options for a single run.
+    pub fn new() -> TestRunOptions {
+        TestRunOptions {
+            repeat: 1,
+            rounds: 1,
+            cpu: None,
+        }
+    }
+
+    /// Sets how many times the kernel runs the program in a loop.
+    pub fn repeat(&mut self, repeat: u32) -> &mut TestRunOptions {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Sets how many times the loop is run. The durations returned in
+    /// [`TestRunResult`] are aggregated across the rounds.
+    pub fn rounds(&mut self, rounds: u32) -> &mut TestRunOptions {
+        self.rounds = rounds.max(1);
+        self
+    }
+
+    /// Runs the program on the given CPU.
+    ///
+    /// The calling thread is pinned to `cpu` for the duration of the run, then
+    /// its CPU affinity is restored.
+    pub fn cpu(&mut self, cpu: usize) -> &mut TestRunOptions {
+        self.cpu = Some(cpu);
+        self
+    }
+}
+
+impl Default for TestRunOptions {
+    fn default() -> Self {
+        TestRunOptions::new()
+    }
+}
+
+/// The result of running a program with `BPF_PROG_TEST_RUN`.
+#[derive(Debug, Copy, Clone)]
+pub struct TestRunResult {
+    /// The value returned by the program in the last run.
+    pub retval: u32,
+    /// The size of the output written by the kernel in the last run.
+    pub data_size_out: usize,
+    /// The average duration of a run.
+    pub duration: Duration,
+    /// The average duration of a run in the fastest round.
+    pub min_duration: Duration,
+    /// The average duration of a run in the slowest round.
+    pub max_duration: Duration,
+}
+
+// restores the CPU affinity of the calling thread when dropped
+struct CpuAffinityGuard(libc::cpu_set_t);
+
+impl CpuAffinityGuard {
+    fn pin(cpu: usize) -> Result<CpuAffinityGuard, ProgramError> {
+        let affinity_error = |call: &str| ProgramError::SyscallError {
+            call: call.to_owned(),
+            io_error: io::Error::last_os_error(),
+        };
+        let size = mem::size_of::<libc::cpu_set_t>();
+        unsafe {
+            let mut saved = mem::zeroed::<libc::cpu_set_t>();
+            if libc::sched_getaffinity(0, size, &mut saved) < 0 {
+                return Err(affinity_error("sched_getaffinity"));
+            }
+            let mut set = mem::zeroed::<libc::cpu_set_t>();
+            libc::CPU_SET(cpu, &mut set);
+            if libc::sched_setaffinity(0, size, &set) < 0 {
+                return Err(affinity_error("sched_setaffinity"));
+            }
+            Ok(CpuAffinityGuard(saved))
+        }
+    }
+}
+
+impl Drop for CpuAffinityGuard {
+    fn drop(&mut self) {
+        unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &self.0) };
+    }
+}
+
+/// Runs the program in the kernel with `data_in` as input.
+pub(crate) fn test_run(
+    data: &ProgramData,
+    data_in: &[u8],
+    data_out: &mut [u8],
+    options: &TestRunOptions,
+) -> Result<TestRunResult, ProgramError> {
+    let prog_fd = data.fd_or_err()?;
+    let _guard = options.cpu.map(CpuAffinityGuard::pin).transpose()?;
+
+    let mut retval = 0;
+    let mut data_size_out = 0;
+    let mut total = 0u64;
+    let mut min = u32::MAX;
+    let mut max = 0;
+    for _ in 0..options.rounds {
+        let mut duration = 0;
+        bpf_prog_test_run(
+            prog_fd,
+            data_in,
+            data_out,
+            options.repeat,
+            &mut retval,
+            &mut data_size_out,
+            &mut duration,
+        )
+        .map_err(|(_, io_error)| ProgramError::SyscallError {
+            call: "bpf_prog_test_run".to_owned(),
+            io_error,
+        })?;
+        total += duration as u64;
+        min = min.min(duration);
+        max = max.max(duration);
+    }
+
+    Ok(TestRunResult {
+        retval,
+        data_size_out: data_size_out as usize,
+        duration: Duration::from_nanos(total / options.rounds as u64),
+        min_duration: Duration::from_nanos(min as u64),
+        max_duration: Duration::from_nanos(max as u64),
+    })
+}
+
 /// Detach an attached program.
 pub trait Link: std::fmt::Debug {
     fn detach(&mut self) -> Result<(), ProgramError>;
+
+    /// Forgets the link without detaching the program.
+    ///
+    /// After calling this method the link will no longer detach the program
+    /// when dropped. Attachments that are not owned by a file descriptor, like
+    /// XDP and TC programs attached via netlink or programs attached with
+    /// `BPF_PROG_ATTACH`, stay in place after the process exits.
+    ///
+    /// Attachments owned by a file descriptor (`bpf_link`s and perf events)
+    /// are kept alive as long as the descriptor stays open. The descriptor is
+    /// returned so that it can be pinned or passed to another process;
+    /// ownership of it is transferred to the caller.
+    ///
+    /// The default implementation returns [`ProgramError::ForgetNotSupported`],
+    /// links defined outside of aya must implement it to be forgotten.
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        Err(ProgramError::ForgetNotSupported)
+    }
+
+    /// Returns the file descriptor owning the attachment, if any.
+    ///
+    /// Returns `None` for attachments not owned by a file descriptor and for
+    /// links that have been detached or forgotten.
+    fn fd(&self) -> Option<RawFd> {
+        None
+    }
 }
 
 /// The return type of `program.attach(...)`.
@@ -512,6 +1378,14 @@ impl Link for LinkRef {
     fn detach(&mut self) -> Result<(), ProgramError> {
         self.inner.borrow_mut().detach()
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.inner.borrow_mut().forget()
+    }
+
+    fn fd(&self) -> Option<RawFd> {
+        self.inner.borrow().fd()
+    }
 }
 
 #[derive(Debug)]
@@ -528,6 +1402,17 @@ impl Link for FdLink {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.fd
+            .take()
+            .map(Some)
+            .ok_or(ProgramError::AlreadyDetached)
+    }
+
+    fn fd(&self) -> Option<RawFd> {
+        self.fd
+    }
 }
 
 impl Drop for FdLink {
@@ -536,6 +1421,152 @@ impl Drop for FdLink {
     }
 }
 
+/// A `bpf_link` pinned in the BPF filesystem.
+///
+/// Pinned links keep their program attached after the process that created
+/// them exits. A [`PinnedLink`] is created by pinning the file descriptor
+/// returned by [`Link::forget`], or by opening a link pinned previously, for
+/// example by an earlier instance of the same daemon. The program of the link
+/// can then be replaced atomically with [`PinnedLink::update`], without any
+/// window where no program is attached.
+///
+/// Dropping a [`PinnedLink`] closes its file descriptor but leaves the
+/// program attached. Call [`PinnedLink::detach`] to detach it.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.7.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use aya::programs::{PinnedLink, Xdp};
+///
+/// let program = bpf.program_mut_typed::<Xdp>("xdp_v2")?;
+/// program.load()?;
+///
+/// let mut link = PinnedLink::from_pin("/sys/fs/bpf/xdp_link")?;
+/// link.update(program)?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+pub struct PinnedLink {
+    fd: RawFd,
+    path: PathBuf,
+}
+
+impl PinnedLink {
+    /// Pins the link file descriptor `link_fd` at `path`.
+    ///
+    /// `link_fd` is usually the descriptor returned by [`Link::forget`].
+    /// Ownership of it is transferred to the returned [`PinnedLink`].
+    pub fn pin<P: AsRef<Path>>(link_fd: RawFd, path: P) -> Result<PinnedLink, ProgramError> {
+        let path = path.as_ref();
+        let path_string = pin_path(path)?;
+        bpf_pin_object(link_fd, &path_string).map_err(|(_, io_error)| {
+            ProgramError::SyscallError {
+                call: "BPF_OBJ_PIN".to_owned(),
+                io_error,
+            }
+        })?;
+
+        Ok(PinnedLink {
+            fd: link_fd,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Opens the link pinned at `path`.
+    pub fn from_pin<P: AsRef<Path>>(path: P) -> Result<PinnedLink, ProgramError> {
+        let path = path.as_ref();
+        let path_string = pin_path(path)?;
+        let fd =
+            bpf_get_object(&path_string).map_err(|(_, io_error)| ProgramError::SyscallError {
+                call: "BPF_OBJ_GET".to_owned(),
+                io_error,
+            })? as RawFd;
+
+        Ok(PinnedLink {
+            fd,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Returns the path the link is pinned at.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates a new handle to the same link, with its own file descriptor.
+    pub fn try_clone(&self) -> Result<PinnedLink, ProgramError> {
+        let fd = dup_fd(self.fd).map_err(|io_error| ProgramError::SyscallError {
+            call: "fcntl".to_owned(),
+            io_error,
+        })?;
+
+        Ok(PinnedLink {
+            fd: fd.into_raw_fd(),
+            path: self.path.clone(),
+        })
+    }
+
+    /// Atomically replaces the program of the link with `program`.
+    ///
+    /// `program` must be loaded, and must be of the same type and have the
+    /// same expected attach type as the program it replaces.
+    pub fn update<P: ProgramFd>(&mut self, program: &P) -> Result<(), ProgramError> {
+        let prog_fd = program.fd().ok_or(ProgramError::NotLoaded)?;
+        bpf_link_update(self.fd, prog_fd).map_err(|(_, io_error)| ProgramError::SyscallError {
+            call: "BPF_LINK_UPDATE".to_owned(),
+            io_error,
+        })?;
+
+        Ok(())
+    }
+
+    /// Detaches the program and removes the pin.
+    ///
+    /// Detaching requires kernel 5.9 or newer.
+    pub fn detach(self) -> Result<(), ProgramError> {
+        bpf_link_detach(self.fd).map_err(|(_, io_error)| ProgramError::SyscallError {
+            call: "BPF_LINK_DETACH".to_owned(),
+            io_error,
+        })?;
+        fs::remove_file(&self.path).map_err(|io_error| ProgramError::SyscallError {
+            call: "unlink".to_owned(),
+            io_error,
+        })?;
+
+        Ok(())
+    }
+}
+
+impl AsRawFd for PinnedLink {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for PinnedLink {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the descriptor is closed when the link is dropped
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for PinnedLink {
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+fn pin_path(path: &Path) -> Result<CString, ProgramError> {
+    CString::new(path.to_string_lossy().into_owned()).map_err(|e| ProgramError::InvalidPinPath {
+        error: e.to_string(),
+    })
+}
+
 #[derive(Debug)]
 struct ProgAttachLink {
     prog_fd: Option<RawFd>,
@@ -561,13 +1592,78 @@ impl Link for ProgAttachLink {
     fn detach(&mut self) -> Result<(), ProgramError> {
         if let Some(prog_fd) = self.prog_fd.take() {
             let target_fd = self.target_fd.take().unwrap();
-            let _ = bpf_prog_detach(prog_fd, target_fd, self.attach_type);
+            let ret = prog_detach(prog_fd, target_fd, self.attach_type);
             unsafe { close(target_fd) };
-            Ok(())
+            ret
         } else {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        if self.prog_fd.take().is_some() {
+            unsafe { close(self.target_fd.take().unwrap()) };
+            Ok(None)
+        } else {
+            Err(ProgramError::AlreadyDetached)
+        }
+    }
+}
+
+bitflags! {
+    /// Flags passed to the `attach()` method of the programs attached to cgroups.
+    ///
+    /// By default a cgroup can only have one program of each attach type, and
+    /// the programs attached to the cgroups below it in the hierarchy are
+    /// ignored. These flags allow multiple programs to coexist.
+    #[derive(Default)]
+    pub struct CgroupAttachFlags: u32 {
+        /// Allow the programs attached to descendant cgroups to override this
+        /// program.
+        const ALLOW_OVERRIDE = BPF_F_ALLOW_OVERRIDE;
+        /// Allow multiple programs to be attached to the cgroup. The programs
+        /// attached to this cgroup and to its ancestors are all executed.
+        const ALLOW_MULTI = BPF_F_ALLOW_MULTI;
+    }
+}
+
+/// The programs attached to a cgroup, returned by the `query()` methods of the
+/// programs attached to cgroups, like [`CgroupSkb::query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupPrograms {
+    /// The flags the programs were attached with. Programs attached without
+    /// flags and `bpf_link`s can't be told apart, and both report no flags.
+    pub attach_flags: CgroupAttachFlags,
+    /// The ids of the programs, in the order in which they run.
+    pub program_ids: Vec<u32>,
+}
+
+/// Lists the programs attached to `cgroup` for `attach_type`.
+pub(crate) fn query_cgroup<T: AsRawFd>(
+    cgroup: T,
+    attach_type: bpf_attach_type,
+) -> Result<CgroupPrograms, ProgramError> {
+    let mut attach_flags = Some(0);
+    let program_ids = query(cgroup, attach_type, 0, &mut attach_flags)?;
+    Ok(CgroupPrograms {
+        attach_flags: CgroupAttachFlags::from_bits_truncate(attach_flags.unwrap_or_default()),
+        program_ids,
+    })
+}
+
+/// Detaches a program attached with `BPF_PROG_ATTACH`.
+pub(crate) fn prog_detach(
+    prog_fd: RawFd,
+    target_fd: RawFd,
+    attach_type: bpf_attach_type,
+) -> Result<(), ProgramError> {
+    bpf_prog_detach(prog_fd, target_fd, attach_type).map_err(|(_, io_error)| {
+        ProgramError::SyscallError {
+            call: "bpf_prog_detach".to_owned(),
+            io_error,
+        }
+    })?;
+    Ok(())
 }
 
 impl Drop for ProgAttachLink {
@@ -596,7 +1692,53 @@ macro_rules! impl_program_fd {
 
 impl_program_fd!(
     KProbe,
+    KProbeMulti,
+    UProbe,
+    UProbeMulti,
+    Usdt,
+    TracePoint,
+    SocketFilter,
+    Xdp,
+    SkMsg,
+    SkSkb,
+    SchedClassifier,
+    CgroupSkb,
+    LircMode2,
+    PerfEvent,
+    Lsm,
+    LsmCgroup,
+    RawTracePoint,
+    BtfTracePoint,
+    FlowDissector,
+    SkReuseport,
+    Netfilter,
+    Extension,
+    FEntry,
+    FExit,
+    Iter,
+);
+
+macro_rules! impl_program_unload {
+    ($($struct_name:ident),+ $(,)?) => {
+        $(
+            impl $struct_name {
+                /// Unloads the program from the kernel.
+                ///
+                /// See also [`Program::unload`].
+                pub fn unload(&mut self) -> Result<(), ProgramError> {
+                    self.data.unload()
+                }
+            }
+        )+
+    }
+}
+
+impl_program_unload!(
+    KProbe,
+    KProbeMulti,
     UProbe,
+    UProbeMulti,
+    Usdt,
     TracePoint,
     SocketFilter,
     Xdp,
@@ -607,13 +1749,55 @@ impl_program_fd!(
     LircMode2,
     PerfEvent,
     Lsm,
+    LsmCgroup,
     RawTracePoint,
     BtfTracePoint,
+    FlowDissector,
+    SkReuseport,
+    Netfilter,
+    Extension,
+    FEntry,
+    FExit,
+    Iter,
 );
 
+/// A program type that can be extracted from a [`Program`].
+///
+/// This trait is implemented by all the program types and is used by
+/// [`Bpf::program_typed`](crate::Bpf::program_typed) and
+/// [`Bpf::program_mut_typed`](crate::Bpf::program_mut_typed).
+pub trait TypedProgram: Sized {
+    /// The name of the program type, as returned by [`Program::type_name`].
+    const TYPE_NAME: &'static str;
+
+    /// Returns the program if it's of this type.
+    fn from_program(program: &Program) -> Option<&Self>;
+
+    /// Returns the program if it's of this type.
+    fn from_program_mut(program: &mut Program) -> Option<&mut Self>;
+}
+
 macro_rules! impl_try_from_program {
     ($($ty:ident),+ $(,)?) => {
         $(
+            impl TypedProgram for $ty {
+                const TYPE_NAME: &'static str = stringify!($ty);
+
+                fn from_program(program: &Program) -> Option<&$ty> {
+                    match program {
+                        Program::$ty(p) => Some(p),
+                        _ => None,
+                    }
+                }
+
+                fn from_program_mut(program: &mut Program) -> Option<&mut $ty> {
+                    match program {
+                        Program::$ty(p) => Some(p),
+                        _ => None,
+                    }
+                }
+            }
+
             impl<'a> TryFrom<&'a Program> for &'a $ty {
                 type Error = ProgramError;
 
@@ -641,7 +1825,10 @@ macro_rules! impl_try_from_program {
 
 impl_try_from_program!(
     KProbe,
+    KProbeMulti,
     UProbe,
+    UProbeMulti,
+    Usdt,
     TracePoint,
     SocketFilter,
     Xdp,
@@ -653,8 +1840,16 @@ impl_try_from_program!(
     LircMode2,
     PerfEvent,
     Lsm,
+    LsmCgroup,
     RawTracePoint,
     BtfTracePoint,
+    FlowDissector,
+    SkReuseport,
+    Netfilter,
+    Extension,
+    FEntry,
+    FExit,
+    Iter,
 );
 
 /// Provides information about a loaded program, like name, id and statistics
@@ -685,4 +1880,207 @@ impl ProgramInfo {
     pub fn id(&self) -> u32 {
         self.0.id
     }
+
+    /// The size in bytes of the instructions after they were rewritten by the
+    /// verifier.
+    pub fn xlated_len(&self) -> u32 {
+        self.0.xlated_prog_len
+    }
+
+    /// The size in bytes of the native code the program was compiled to, 0
+    /// if the program is not JITed.
+    pub fn jited_len(&self) -> u32 {
+        self.0.jited_prog_len
+    }
+
+    /// The number of instructions processed by the verifier, 0 on kernels
+    /// older than 5.16.
+    pub fn verified_insns(&self) -> u32 {
+        self.0.verified_insns
+    }
+
+    /// Returns the metadata of the program, like `bpftool prog show`.
+    ///
+    /// Metadata is declared as global constants whose names start with
+    /// `bpf_metadata_`, for example `const char bpf_metadata_version[] =
+    /// "1.2";`. The variables are returned without the prefix, decoded using
+    /// their BTF types. Programs loaded by aya and libbpf keep the map holding
+    /// their metadata bound to them, even if they never read it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let bpf = aya::Bpf::load(&[])?;
+    /// let program = bpf.program("xdp_prog")?;
+    /// for (name, value) in program.info()?.metadata()? {
+    ///     println!("{} = {}", name, value);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn metadata(&self) -> Result<Vec<(String, BtfValue)>, ProgramError> {
+        let fd =
+            bpf_prog_get_fd_by_id(self.id()).map_err(|io_error| ProgramError::SyscallError {
+                call: "bpf_prog_get_fd_by_id".to_owned(),
+                io_error,
+            })?;
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut map_ids = vec![0u32; self.0.nr_map_ids as usize];
+        let mut info = unsafe { mem::zeroed::<bpf_prog_info>() };
+        info.nr_map_ids = map_ids.len() as u32;
+        info.map_ids = map_ids.as_mut_ptr() as u64;
+        bpf_prog_get_info_by_fd(fd.as_raw_fd(), &mut info).map_err(|io_error| {
+            ProgramError::SyscallError {
+                call: "bpf_obj_get_info_by_fd".to_owned(),
+                io_error,
+            }
+        })?;
+        map_ids.truncate(info.nr_map_ids as usize);
+
+        let mut metadata = Vec::new();
+        for map_id in map_ids {
+            let map = Map::from_id(map_id)?;
+            let def = &map.obj.def;
+            if def.map_type != BPF_MAP_TYPE_ARRAY as u32 || def.max_entries != 1 {
+                continue;
+            }
+            // global data maps have their data section as value type
+            let (btf, type_id) = match (&map.obj.btf, map.obj.btf_value_type_id) {
+                (Some(btf), Some(type_id)) => (btf, type_id),
+                _ => continue,
+            };
+            if !matches!(btf.type_by_id(type_id), Ok(BtfType::DataSec(_, _))) {
+                continue;
+            }
+
+            let mut value = vec![0u8; def.value_size as usize];
+            let found = bpf_map_lookup_elem_ptr(map.fd_or_err()?, &0u32, value.as_mut_ptr(), 0)
+                .map_err(|(code, io_error)| MapError::SyscallError {
+                    call: "bpf_map_lookup_elem".to_owned(),
+                    code,
+                    io_error,
+                })?;
+            if found.is_none() {
+                continue;
+            }
+            if let BtfValue::Struct(vars) = btf.decode_value(type_id, &value)? {
+                metadata.extend(vars.into_iter().filter_map(|(name, value)| {
+                    let name = name.strip_prefix("bpf_metadata_")?.to_owned();
+                    Some((name, value))
+                }));
+            }
+        }
+
+        Ok(metadata)
+    }
+}
+
+/// The instructions of a loaded program, as returned by
+/// [`Program::instructions`].
+///
+/// Reading the instructions requires `CAP_SYS_ADMIN`. The kernel doesn't return
+/// the native code of programs JITed with constant blinding (see the
+/// `net.core.bpf_jit_harden` sysctl), nor the addresses of the functions if
+/// `kernel.kptr_restrict` hides them, in which case the fields are empty.
+#[derive(Debug, Clone)]
+pub struct ProgramInstructions {
+    /// The eBPF bytecode after it was rewritten by the verifier.
+    pub xlated: Vec<u8>,
+    /// The native code the program was JITed to.
+    pub jited: Vec<u8>,
+    /// The kernel addresses of the JITed functions, the main program first.
+    pub jited_ksyms: Vec<u64>,
+    /// The sizes in bytes of the JITed functions, in the same order as
+    /// `jited_ksyms`.
+    pub jited_func_lens: Vec<u32>,
+}
+
+impl ProgramInstructions {
+    /// Decodes the eBPF bytecode.
+    ///
+    /// Instructions loading a 64 bit immediate take two slots, the second one
+    /// holds the upper 32 bits of the value in its `imm` field.
+    pub fn xlated_insns(&self) -> Vec<Instruction> {
+        self.xlated
+            .chunks_exact(mem::size_of::<bpf_insn>())
+            .map(|chunk| {
+                let insn = unsafe { ptr::read_unaligned(chunk.as_ptr() as *const bpf_insn) };
+                Instruction {
+                    code: insn.code,
+                    dst_reg: insn.dst_reg(),
+                    src_reg: insn.src_reg(),
+                    off: insn.off,
+                    imm: insn.imm,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the native code of each JITed function along with its kernel
+    /// address, the main program first.
+    pub fn jited_functions(&self) -> Vec<(u64, &[u8])> {
+        let mut offset = 0;
+        self.jited_ksyms
+            .iter()
+            .zip(self.jited_func_lens.iter())
+            .filter_map(|(addr, len)| {
+                let code = self.jited.get(offset..offset + *len as usize)?;
+                offset += *len as usize;
+                Some((*addr, code))
+            })
+            .collect()
+    }
+}
+
+/// A decoded eBPF instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// The opcode.
+    pub code: u8,
+    /// The destination register.
+    pub dst_reg: u8,
+    /// The source register.
+    pub src_reg: u8,
+    /// The signed offset.
+    pub off: i16,
+    /// The signed immediate constant.
+    pub imm: i32,
+}
+
+fn program_instructions(fd: RawFd) -> Result<ProgramInstructions, ProgramError> {
+    let syscall_error = |io_error| ProgramError::SyscallError {
+        call: "bpf_obj_get_info_by_fd".to_owned(),
+        io_error,
+    };
+    // get the sizes first, then the buffers
+    let sizes = bpf_obj_get_info_by_fd(fd).map_err(syscall_error)?;
+
+    let mut xlated = vec![0u8; sizes.xlated_prog_len as usize];
+    let mut jited = vec![0u8; sizes.jited_prog_len as usize];
+    let mut jited_ksyms = vec![0u64; sizes.nr_jited_ksyms as usize];
+    let mut jited_func_lens = vec![0u32; sizes.nr_jited_func_lens as usize];
+
+    let mut info = unsafe { mem::zeroed::<bpf_prog_info>() };
+    info.xlated_prog_len = xlated.len() as u32;
+    info.xlated_prog_insns = xlated.as_mut_ptr() as u64;
+    info.jited_prog_len = jited.len() as u32;
+    info.jited_prog_insns = jited.as_mut_ptr() as u64;
+    info.nr_jited_ksyms = jited_ksyms.len() as u32;
+    info.jited_ksyms = jited_ksyms.as_mut_ptr() as u64;
+    info.nr_jited_func_lens = jited_func_lens.len() as u32;
+    info.jited_func_lens = jited_func_lens.as_mut_ptr() as u64;
+    bpf_prog_get_info_by_fd(fd, &mut info).map_err(syscall_error)?;
+
+    // the kernel sets the lengths to 0 when it hides the data
+    xlated.truncate(info.xlated_prog_len as usize);
+    jited.truncate(info.jited_prog_len as usize);
+    jited_ksyms.truncate(info.nr_jited_ksyms as usize);
+    jited_func_lens.truncate(info.nr_jited_func_lens as usize);
+
+    Ok(ProgramInstructions {
+        xlated,
+        jited,
+        jited_ksyms,
+        jited_func_lens,
+    })
 }
@@ -0,0 +1,126 @@
+//! Netfilter programs.
+use std::os::unix::io::RawFd;
+
+use crate::{
+    generated::{bpf_attach_type::BPF_NETFILTER, bpf_prog_type::BPF_PROG_TYPE_NETFILTER},
+    programs::{load_program, FdLink, LinkRef, ProgramData, ProgramError},
+    sys::bpf_link_create_netfilter,
+};
+
+// see enum { NFPROTO_* } in include/uapi/linux/netfilter.h
+const NFPROTO_IPV4: u32 = 2;
+const NFPROTO_IPV6: u32 = 10;
+
+// see enum { BPF_F_NETFILTER_IP_DEFRAG } in include/uapi/linux/bpf.h
+const BPF_F_NETFILTER_IP_DEFRAG: u32 = 1 << 0;
+
+bitflags! {
+    /// Flags passed to [`Netfilter::attach`].
+    pub struct NetfilterFlags: u32 {
+        /// Reassembles IP fragments before running the program. Requires
+        /// kernel 6.6 or newer.
+        const IP_DEFRAG = BPF_F_NETFILTER_IP_DEFRAG;
+    }
+}
+
+/// The protocol family of the packets a [`Netfilter`] program runs on.
+#[derive(Copy, Clone, Debug)]
+pub enum NetfilterProtocolFamily {
+    /// IPv4 packets.
+    Ipv4,
+    /// IPv6 packets.
+    Ipv6,
+}
+
+impl NetfilterProtocolFamily {
+    fn pf(self) -> u32 {
+        match self {
+            NetfilterProtocolFamily::Ipv4 => NFPROTO_IPV4,
+            NetfilterProtocolFamily::Ipv6 => NFPROTO_IPV6,
+        }
+    }
+}
+
+/// The netfilter hook a [`Netfilter`] program is attached to.
+#[derive(Copy, Clone, Debug)]
+pub enum NetfilterHook {
+    /// `NF_INET_PRE_ROUTING`, before the routing decision.
+    PreRouting = 0,
+    /// `NF_INET_LOCAL_IN`, for packets delivered to the host.
+    LocalIn = 1,
+    /// `NF_INET_FORWARD`, for packets forwarded by the host.
+    Forward = 2,
+    /// `NF_INET_LOCAL_OUT`, for packets sent by the host.
+    LocalOut = 3,
+    /// `NF_INET_POST_ROUTING`, after the routing decision.
+    PostRouting = 4,
+}
+
+/// A program attached to a netfilter hook.
+///
+/// [`Netfilter`] programs run at the same places as iptables or nftables
+/// rules, and decide whether packets are accepted or dropped by returning
+/// `NF_ACCEPT` or `NF_DROP`.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 6.4.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use aya::programs::{Netfilter, NetfilterFlags, NetfilterHook, NetfilterProtocolFamily};
+///
+/// let program = bpf.program_mut_typed::<Netfilter>("filter")?;
+/// program.load()?;
+/// program.attach(
+///     NetfilterProtocolFamily::Ipv4,
+///     NetfilterHook::PreRouting,
+///     -128,
+///     NetfilterFlags::empty(),
+/// )?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_PROG_TYPE_NETFILTER")]
+pub struct Netfilter {
+    pub(crate) data: ProgramData,
+}
+
+impl Netfilter {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_NETFILTER);
+        load_program(BPF_PROG_TYPE_NETFILTER, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program to the given netfilter hook.
+    ///
+    /// Hooks run in ascending `priority` order. The priorities `i32::MIN`
+    /// and `i32::MAX` are reserved by the kernel.
+    pub fn attach(
+        &mut self,
+        family: NetfilterProtocolFamily,
+        hook: NetfilterHook,
+        priority: i32,
+        flags: NetfilterFlags,
+    ) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let link_fd =
+            bpf_link_create_netfilter(prog_fd, family.pf(), hook as u32, priority, flags.bits())
+                .map_err(|(_, io_error)| ProgramError::SyscallError {
+                    call: "bpf_link_create".to_owned(),
+                    io_error,
+                })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+}
@@ -0,0 +1,150 @@
+//! Network namespaces.
+use std::{
+    cell::RefCell,
+    fs::File,
+    io,
+    os::unix::io::{AsRawFd, OwnedFd, RawFd},
+    path::Path,
+    sync::Arc,
+};
+
+use libc::{pid_t, CLONE_NEWNET};
+
+thread_local! {
+    // the namespace entered by NetNs::run on this thread, if any
+    static CURRENT: RefCell<Option<NetNs>> = const { RefCell::new(None) };
+}
+
+/// A network namespace.
+///
+/// Network interfaces only exist in one network namespace, and netlink
+/// operations act on the namespace of the calling thread. [`NetNs::run`]
+/// temporarily moves the calling thread into another namespace, which allows
+/// an agent running on the host to attach [`Xdp`](crate::programs::Xdp) and
+/// [`SchedClassifier`](crate::programs::SchedClassifier) programs to the
+/// interfaces of containers, like the container end of veth pairs.
+///
+/// Links created inside [`NetNs::run`] remember the namespace, so that
+/// detaching them later acts on the right interface.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// use aya::programs::{tc, NetNs, SchedClassifier, TcAttachType};
+///
+/// let program = bpf.program_mut_typed::<SchedClassifier>("redirect")?;
+/// program.load()?;
+///
+/// let netns = NetNs::from_name("container")?;
+/// netns.run(|| {
+///     // the error is ignored since the qdisc might already exist
+///     let _ = tc::qdisc_add_clsact("eth0");
+///     program.attach("eth0", TcAttachType::Ingress)
+/// })??;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct NetNs {
+    fd: Arc<OwnedFd>,
+}
+
+impl NetNs {
+    /// Opens the network namespace bound to `path`, eg `/proc/<pid>/ns/net`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<NetNs, io::Error> {
+        Ok(NetNs::from(OwnedFd::from(File::open(path)?)))
+    }
+
+    /// Opens the network namespace `name` created by `ip netns add`.
+    pub fn from_name(name: &str) -> Result<NetNs, io::Error> {
+        NetNs::from_path(Path::new("/var/run/netns").join(name))
+    }
+
+    /// Opens the network namespace of the process `pid`.
+    pub fn from_pid(pid: pid_t) -> Result<NetNs, io::Error> {
+        NetNs::from_path(format!("/proc/{}/ns/net", pid))
+    }
+
+    /// Runs `f` with the calling thread moved into the namespace.
+    ///
+    /// The thread is moved back to its original namespace once `f` returns,
+    /// or if `f` panics. Returns an error if entering the namespace fails, in
+    /// which case `f` isn't called, or if the thread can't be moved back to
+    /// its original namespace, in which case the value returned by `f` is
+    /// dropped.
+    pub fn run<T, F: FnOnce() -> T>(&self, f: F) -> Result<T, io::Error> {
+        let original = File::open("/proc/thread-self/ns/net")?;
+        setns(self.as_raw_fd())?;
+
+        let mut guard = RestoreGuard {
+            original,
+            previous: Some(CURRENT.with(|current| current.replace(Some(self.clone())))),
+        };
+        let ret = f();
+        guard.restore()?;
+        Ok(ret)
+    }
+
+    /// Returns the namespace entered with [`NetNs::run`] on the calling
+    /// thread, if any.
+    pub(crate) fn current() -> Option<NetNs> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+}
+
+impl From<OwnedFd> for NetNs {
+    fn from(fd: OwnedFd) -> NetNs {
+        NetNs { fd: Arc::new(fd) }
+    }
+}
+
+impl AsRawFd for NetNs {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+// moves the thread back to its original namespace when dropped, so that it's
+// restored when the function run in the namespace panics
+struct RestoreGuard {
+    original: File,
+    // the namespace entered by an outer NetNs::run, None once restored
+    previous: Option<Option<NetNs>>,
+}
+
+impl RestoreGuard {
+    fn restore(&mut self) -> Result<(), io::Error> {
+        match self.previous.take() {
+            Some(previous) => {
+                CURRENT.with(|current| *current.borrow_mut() = previous);
+                setns(self.original.as_raw_fd())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RestoreGuard {
+    fn drop(&mut self) {
+        // nothing can be done about the error while unwinding
+        let _ = self.restore();
+    }
+}
+
+/// Runs `f` in `netns` if given, or in the namespace of the calling thread.
+pub(crate) fn run_in<T>(
+    netns: Option<&NetNs>,
+    f: impl FnOnce() -> Result<T, io::Error>,
+) -> Result<T, io::Error> {
+    match netns {
+        Some(netns) => netns.run(f)?,
+        None => f(),
+    }
+}
+
+fn setns(fd: RawFd) -> Result<(), io::Error> {
+    if unsafe { libc::setns(fd, CLONE_NEWNET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -33,6 +33,13 @@ impl Link for PerfLink {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        let fd = self.perf_fd.take().ok_or(ProgramError::AlreadyDetached)?;
+        self.probe_kind = None;
+        self.event_alias = None;
+        Ok(Some(fd))
+    }
 }
 
 impl Drop for PerfLink {
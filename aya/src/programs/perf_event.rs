@@ -1,5 +1,8 @@
 //! Perf event programs.
-use crate::{generated::bpf_prog_type::BPF_PROG_TYPE_PERF_EVENT, sys::perf_event_open};
+use crate::{
+    generated::bpf_prog_type::BPF_PROG_TYPE_PERF_EVENT,
+    sys::{perf_event_open, perf_event_open_breakpoint},
+};
 
 use crate::generated::perf_type_id::{
     PERF_TYPE_BREAKPOINT, PERF_TYPE_HARDWARE, PERF_TYPE_HW_CACHE, PERF_TYPE_RAW,
@@ -39,6 +42,142 @@ pub enum PerfEventScope {
     AllProcessesOneCpu { cpu: u32 },
 }
 
+/// The kind of memory access that triggers a hardware breakpoint.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BreakpointType {
+    /// Triggers when the memory is read.
+    Read = 1,
+    /// Triggers when the memory is written.
+    Write = 2,
+    /// Triggers when the memory is read or written.
+    ReadWrite = 3,
+    /// Triggers when the instruction at the address is executed.
+    Execute = 4,
+}
+
+/// The length of the memory watched by a hardware breakpoint.
+#[repr(u64)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BreakpointLength {
+    Len1 = 1,
+    Len2 = 2,
+    Len4 = 4,
+    Len8 = 8,
+}
+
+/// The configuration of a perf event, used with [`PerfEvent::attach_with_config`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::programs::perf_event::{
+///     perf_hw_id::PERF_COUNT_HW_CPU_CYCLES, PerfEventConfig, PerfEventScope, SamplePolicy,
+/// };
+///
+/// let mut config = PerfEventConfig::hardware(PERF_COUNT_HW_CPU_CYCLES);
+/// config
+///     .scope(PerfEventScope::OneProcessAnyCpu { pid: 1234 })
+///     .sample_policy(SamplePolicy::Frequency(99))
+///     .inherit(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PerfEventConfig {
+    perf_type: PerfTypeId,
+    config: u64,
+    scope: PerfEventScope,
+    sample_policy: SamplePolicy,
+    inherit: bool,
+    breakpoint: Option<(u64, BreakpointLength, BreakpointType)>,
+}
+
+impl PerfEventConfig {
+    /// Creates a new configuration for the given event type and config.
+    ///
+    /// The possible values and encoding of the `config` argument depends on the
+    /// `perf_type`. By default the event is scoped to the calling process on
+    /// any CPU, and samples every event.
+    pub fn new(perf_type: PerfTypeId, config: u64) -> PerfEventConfig {
+        PerfEventConfig {
+            perf_type,
+            config,
+            scope: PerfEventScope::CallingProcessAnyCpu,
+            sample_policy: SamplePolicy::Period(1),
+            inherit: false,
+            breakpoint: None,
+        }
+    }
+
+    /// Creates a configuration for a generalized hardware event.
+    pub fn hardware(id: perf_hw_id) -> PerfEventConfig {
+        PerfEventConfig::new(PerfTypeId::Hardware, id as u64)
+    }
+
+    /// Creates a configuration for a software event.
+    pub fn software(id: perf_sw_ids) -> PerfEventConfig {
+        PerfEventConfig::new(PerfTypeId::Software, id as u64)
+    }
+
+    /// Creates a configuration for a tracepoint event, given the tracepoint
+    /// id as found in `/sys/kernel/debug/tracing/events/*/*/id`.
+    pub fn tracepoint(id: u64) -> PerfEventConfig {
+        PerfEventConfig::new(PerfTypeId::TracePoint, id)
+    }
+
+    /// Creates a configuration for a hardware cache event.
+    pub fn hw_cache(
+        id: perf_hw_cache_id,
+        op: perf_hw_cache_op_id,
+        result: perf_hw_cache_op_result_id,
+    ) -> PerfEventConfig {
+        PerfEventConfig::new(
+            PerfTypeId::HwCache,
+            id as u64 | (op as u64) << 8 | (result as u64) << 16,
+        )
+    }
+
+    /// Creates a configuration for a raw, implementation specific, event.
+    pub fn raw(config: u64) -> PerfEventConfig {
+        PerfEventConfig::new(PerfTypeId::Raw, config)
+    }
+
+    /// Creates a configuration for a hardware breakpoint on `address`.
+    ///
+    /// The event triggers whenever `length` bytes at `address` are accessed
+    /// as specified by `ty`. `address` can be a kernel or a user space
+    /// address, in which case the event should be scoped to a process. For
+    /// [`BreakpointType::Execute`] breakpoints, the kernel requires `length`
+    /// to be the size of a `long`.
+    pub fn breakpoint(
+        address: u64,
+        length: BreakpointLength,
+        ty: BreakpointType,
+    ) -> PerfEventConfig {
+        let mut config = PerfEventConfig::new(PerfTypeId::Breakpoint, 0);
+        config.breakpoint = Some((address, length, ty));
+        config
+    }
+
+    /// Sets the processes and CPUs the event is scoped to.
+    pub fn scope(&mut self, scope: PerfEventScope) -> &mut PerfEventConfig {
+        self.scope = scope;
+        self
+    }
+
+    /// Sets whether the program is run every n events or at a given frequency.
+    pub fn sample_policy(&mut self, sample_policy: SamplePolicy) -> &mut PerfEventConfig {
+        self.sample_policy = sample_policy;
+        self
+    }
+
+    /// Sets whether child tasks created after the event is attached are
+    /// counted too.
+    pub fn inherit(&mut self, inherit: bool) -> &mut PerfEventConfig {
+        self.inherit = inherit;
+        self
+    }
+}
+
 /// A program that can be attached at a perf event.
 ///
 /// # Minimum kernel version
@@ -60,13 +199,12 @@ pub enum PerfEventScope {
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::TryInto;
 /// use aya::util::online_cpus;
 /// use aya::programs::perf_event::{
 ///     perf_sw_ids::PERF_COUNT_SW_CPU_CLOCK, PerfEvent, PerfEventScope, PerfTypeId, SamplePolicy,
 /// };
 ///
-/// let prog: &mut PerfEvent = bpf.program_mut("observe_cpu_clock")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<PerfEvent>("observe_cpu_clock")?;
 /// prog.load()?;
 ///
 /// for cpu in online_cpus()? {
@@ -105,6 +243,28 @@ impl PerfEvent {
         scope: PerfEventScope,
         sample_policy: SamplePolicy,
     ) -> Result<LinkRef, ProgramError> {
+        self.attach_with_config(
+            PerfEventConfig::new(perf_type, config)
+                .scope(scope)
+                .sample_policy(sample_policy),
+        )
+    }
+
+    /// Attaches to the perf event described by `config`.
+    ///
+    /// See [`PerfEventConfig`] for the available options.
+    pub fn attach_with_config(
+        &mut self,
+        config: &PerfEventConfig,
+    ) -> Result<LinkRef, ProgramError> {
+        let PerfEventConfig {
+            perf_type,
+            config,
+            scope,
+            sample_policy,
+            inherit,
+            breakpoint,
+        } = config.clone();
         let (sample_period, sample_frequency) = match sample_policy {
             SamplePolicy::Period(period) => (period, None),
             SamplePolicy::Frequency(frequency) => (0, Some(frequency)),
@@ -116,16 +276,29 @@ impl PerfEvent {
             PerfEventScope::OneProcessOneCpu { cpu, pid } => (pid as i32, cpu as i32),
             PerfEventScope::AllProcessesOneCpu { cpu } => (-1, cpu as i32),
         };
-        let fd = perf_event_open(
-            perf_type as u32,
-            config,
-            pid,
-            cpu,
-            sample_period,
-            sample_frequency,
-            false,
-            0,
-        )
+        let fd = match breakpoint {
+            Some((address, length, ty)) => perf_event_open_breakpoint(
+                ty as u32,
+                address,
+                length as u64,
+                pid,
+                cpu,
+                sample_period,
+                sample_frequency,
+                inherit,
+            ),
+            None => perf_event_open(
+                perf_type as u32,
+                config,
+                pid,
+                cpu,
+                sample_period,
+                sample_frequency,
+                false,
+                inherit,
+                0,
+            ),
+        }
         .map_err(|(_code, io_error)| ProgramError::SyscallError {
             call: "perf_event_open".to_owned(),
             io_error,
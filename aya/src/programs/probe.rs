@@ -1,7 +1,8 @@
-use libc::pid_t;
+use libc::{close, pid_t};
 use std::{
     fs::{self, OpenOptions},
     io::{self, Write},
+    path::Path,
     process,
 };
 
@@ -12,6 +13,7 @@ use crate::{
         ProgramError,
     },
     sys::{kernel_version, perf_event_open_probe, perf_event_open_trace_point},
+    util::tracefs_path,
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -41,17 +43,27 @@ pub(crate) fn attach(
     fn_name: &str,
     offset: u64,
     pid: Option<pid_t>,
+    maxactive: Option<u32>,
+    ref_ctr_offset: Option<u64>,
 ) -> Result<LinkRef, ProgramError> {
     // https://github.com/torvalds/linux/commit/e12f03d7031a977356e3d7b75a68c2185ff8d155
-    // Use debugfs to create probe
+    // Use tracefs to create probe. The perf PMU doesn't support setting
+    // maxactive, so we have to go through tracefs in that case too. Some
+    // kernels, like older Android ones, don't have the PMU either.
     let k_ver = kernel_version().unwrap();
-    if k_ver < (4, 17, 0) {
-        let (fd, event_alias) = create_as_trace_point(kind, fn_name, offset, pid)?;
-
-        return perf_attach_debugfs(program_data, fd, kind, event_alias);
+    if k_ver < (4, 17, 0) || maxactive.is_some() || !is_pmu_available(kind) {
+        let (fd, event_alias) =
+            create_as_trace_point(kind, fn_name, offset, pid, maxactive, ref_ctr_offset)?;
+
+        return perf_attach_debugfs(program_data, fd, kind, event_alias.clone()).inspect_err(
+            |_| {
+                unsafe { close(fd) };
+                let _ = delete_probe_event(kind, &event_alias);
+            },
+        );
     };
 
-    let fd = create_as_probe(kind, fn_name, offset, pid)?;
+    let fd = create_as_probe(kind, fn_name, offset, pid, ref_ctr_offset)?;
 
     perf_attach(program_data, fd)
 }
@@ -74,6 +86,7 @@ fn create_as_probe(
     fn_name: &str,
     offset: u64,
     pid: Option<pid_t>,
+    ref_ctr_offset: Option<u64>,
 ) -> Result<i32, ProgramError> {
     use ProbeKind::*;
 
@@ -96,7 +109,7 @@ fn create_as_probe(
         _ => None,
     };
 
-    let fd = perf_event_open_probe(perf_ty, ret_bit, fn_name, offset, pid).map_err(
+    let fd = perf_event_open_probe(perf_ty, ret_bit, fn_name, offset, pid, ref_ctr_offset).map_err(
         |(_code, io_error)| ProgramError::SyscallError {
             call: "perf_event_open".to_owned(),
             io_error,
@@ -111,55 +124,102 @@ fn create_as_trace_point(
     name: &str,
     offset: u64,
     pid: Option<pid_t>,
+    maxactive: Option<u32>,
+    ref_ctr_offset: Option<u64>,
 ) -> Result<(i32, String), ProgramError> {
     use ProbeKind::*;
 
     let event_alias = match kind {
-        KProbe | KRetProbe => create_probe_event(kind, name, offset)
+        KProbe | KRetProbe => create_probe_event(kind, name, offset, maxactive, None)
             .map_err(|(filename, io_error)| KProbeError::FileError { filename, io_error })?,
-        UProbe | URetProbe => create_probe_event(kind, name, offset)
+        UProbe | URetProbe => create_probe_event(kind, name, offset, None, ref_ctr_offset)
             .map_err(|(filename, io_error)| UProbeError::FileError { filename, io_error })?,
     };
 
     let category = format!("{}s", kind.pmu());
-    let tpid = read_sys_fs_trace_point_id(&category, &event_alias)?;
-    let fd = perf_event_open_trace_point(tpid, pid).map_err(|(_code, io_error)| {
-        ProgramError::SyscallError {
-            call: "perf_event_open".to_owned(),
-            io_error,
-        }
-    })? as i32;
+    let fd = read_sys_fs_trace_point_id(&category, &event_alias)
+        .map_err(ProgramError::from)
+        .and_then(|tpid| {
+            perf_event_open_trace_point(tpid, pid).map_err(|(_code, io_error)| {
+                ProgramError::SyscallError {
+                    call: "perf_event_open".to_owned(),
+                    io_error,
+                }
+            })
+        })
+        .inspect_err(|_| {
+            // don't leave the event behind
+            let _ = delete_probe_event(kind, &event_alias);
+        })? as i32;
 
     Ok((fd, event_alias))
 }
 
+fn is_pmu_available(kind: ProbeKind) -> bool {
+    Path::new("/sys/bus/event_source/devices")
+        .join(kind.pmu())
+        .join("type")
+        .exists()
+}
+
+fn events_file_name(kind: ProbeKind) -> Result<String, (String, io::Error)> {
+    let file_name = format!("{}_events", kind.pmu());
+    tracefs_path()
+        .map(|path| path.join(&file_name).to_string_lossy().into_owned())
+        .map_err(|e| (file_name, e))
+}
+
+// event names can only contain alphanumeric characters and underscores, while
+// uprobes are named after the path of the binary
+fn event_alias(kind: ProbeKind, fn_name: &str, offset: u64) -> String {
+    let probe_type_prefix = match kind {
+        ProbeKind::KProbe | ProbeKind::UProbe => 'p',
+        ProbeKind::KRetProbe | ProbeKind::URetProbe => 'r',
+    };
+    let fn_name = fn_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!(
+        "aya_{}_{}_{}_{:#x}",
+        process::id(),
+        probe_type_prefix,
+        fn_name,
+        offset
+    )
+}
+
 fn create_probe_event(
     kind: ProbeKind,
     fn_name: &str,
     offset: u64,
+    maxactive: Option<u32>,
+    ref_ctr_offset: Option<u64>,
 ) -> Result<String, (String, io::Error)> {
     use ProbeKind::*;
 
-    let events_file_name = format!("/sys/kernel/debug/tracing/{}_events", kind.pmu());
+    let events_file_name = events_file_name(kind)?;
     let probe_type_prefix = match kind {
         KProbe | UProbe => 'p',
         KRetProbe | URetProbe => 'r',
     };
-    let event_alias = format!(
-        "aya_{}_{}_{}_{:#x}",
-        process::id(),
-        probe_type_prefix,
-        fn_name,
-        offset
-    );
+    let maxactive = match maxactive {
+        Some(maxactive) => maxactive.to_string(),
+        None => "".to_string(),
+    };
+    let event_alias = event_alias(kind, fn_name, offset);
     let offset_suffix = match kind {
         KProbe => format!("+{}", offset),
-        UProbe => format!(":{:#x}", offset),
+        UProbe | URetProbe => match ref_ctr_offset {
+            Some(ref_ctr_offset) => format!(":{:#x}({:#x})", offset, ref_ctr_offset),
+            None => format!(":{:#x}", offset),
+        },
         _ => "".to_string(),
     };
     let probe = format!(
-        "{}:{}s/{} {}{}\n",
+        "{}{}:{}s/{} {}{}\n",
         probe_type_prefix,
+        maxactive,
         kind.pmu(),
         event_alias,
         fn_name,
@@ -179,7 +239,7 @@ fn create_probe_event(
 }
 
 fn delete_probe_event(kind: ProbeKind, event_alias: &str) -> Result<(), (String, io::Error)> {
-    let events_file_name = format!("/sys/kernel/debug/tracing/{}_events", kind.pmu());
+    let events_file_name = events_file_name(kind)?;
 
     let events =
         fs::read_to_string(&events_file_name).map_err(|e| (events_file_name.clone(), e))?;
@@ -231,3 +291,21 @@ fn read_sys_fs_perf_ret_probe(pmu: &str) -> Result<u32, (String, io::Error)> {
         .parse::<u32>()
         .map_err(|e| (file, io::Error::new(io::ErrorKind::Other, e)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_alias() {
+        let pid = process::id();
+        assert_eq!(
+            event_alias(ProbeKind::KRetProbe, "tcp_v4_connect.isra.0", 0),
+            format!("aya_{}_r_tcp_v4_connect_isra_0_0x0", pid)
+        );
+        assert_eq!(
+            event_alias(ProbeKind::UProbe, "/usr/lib/libc.so.6", 0x1234),
+            format!("aya_{}_p__usr_lib_libc_so_6_0x1234", pid)
+        );
+    }
+}
@@ -24,9 +24,8 @@ use crate::{
 /// ```no_run
 /// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 /// use aya::{Bpf, programs::RawTracePoint};
-/// use std::convert::TryInto;
 ///
-/// let program: &mut RawTracePoint = bpf.program_mut("sys_enter")?.try_into()?;
+/// let program = bpf.program_mut_typed::<RawTracePoint>("sys_enter")?;
 /// program.load()?;
 /// program.attach("sys_enter")?;
 /// # Ok::<(), aya::BpfError>(())
@@ -1,7 +1,7 @@
 use crate::{
     generated::{bpf_attach_type::BPF_SK_MSG_VERDICT, bpf_prog_type::BPF_PROG_TYPE_SK_MSG},
     maps::sock::SocketMap,
-    programs::{load_program, LinkRef, ProgAttachLink, ProgramData, ProgramError},
+    programs::{load_program, prog_detach, LinkRef, ProgAttachLink, ProgramData, ProgramError},
     sys::bpf_prog_attach,
 };
 
@@ -30,20 +30,19 @@ use crate::{
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::{TryFrom, TryInto};
+/// use std::convert::TryFrom;
 /// use std::io::Write;
 /// use std::net::TcpStream;
-/// use std::os::unix::io::AsRawFd;
 /// use aya::maps::SockHash;
 /// use aya::programs::SkMsg;
 ///
 /// let mut intercept_egress = SockHash::try_from(bpf.map_mut("INTERCEPT_EGRESS")?)?;
-/// let prog: &mut SkMsg = bpf.program_mut("intercept_egress_packet")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SkMsg>("intercept_egress_packet")?;
 /// prog.load()?;
 /// prog.attach(&intercept_egress)?;
 ///
 /// let mut client = TcpStream::connect("127.0.0.1:1234")?;
-/// intercept_egress.insert(1234, client.as_raw_fd(), 0)?;
+/// intercept_egress.insert(1234, &client, 0)?;
 ///
 /// // the write will be intercepted
 /// client.write_all(b"foo")?;
@@ -77,7 +76,7 @@ impl SkMsg {
         let prog_fd = self.data.fd_or_err()?;
         let map_fd = map.fd_or_err()?;
 
-        bpf_prog_attach(prog_fd, map_fd, BPF_SK_MSG_VERDICT).map_err(|(_, io_error)| {
+        bpf_prog_attach(prog_fd, map_fd, BPF_SK_MSG_VERDICT, 0).map_err(|(_, io_error)| {
             ProgramError::SyscallError {
                 call: "bpf_prog_attach".to_owned(),
                 io_error,
@@ -87,4 +86,16 @@ impl SkMsg {
             .data
             .link(ProgAttachLink::new(prog_fd, map_fd, BPF_SK_MSG_VERDICT)))
     }
+
+    /// Detaches the program from the given sockmap.
+    ///
+    /// The map is left untouched, so sockets already in the map stay there.
+    /// Dropping or detaching the [`LinkRef`] returned by
+    /// [`attach`](Self::attach) detaches the program as well.
+    pub fn detach(&mut self, map: &dyn SocketMap) -> Result<(), ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let map_fd = map.fd_or_err()?;
+
+        prog_detach(prog_fd, map_fd, BPF_SK_MSG_VERDICT)
+    }
 }
@@ -0,0 +1,179 @@
+use libc::{c_int, setsockopt, SOL_SOCKET};
+use std::{
+    io, mem,
+    os::unix::prelude::{AsRawFd, RawFd},
+};
+use thiserror::Error;
+
+use crate::{
+    generated::{
+        bpf_attach_type::{BPF_SK_REUSEPORT_SELECT, BPF_SK_REUSEPORT_SELECT_OR_MIGRATE},
+        bpf_prog_type::BPF_PROG_TYPE_SK_REUSEPORT,
+        SO_ATTACH_REUSEPORT_EBPF, SO_DETACH_REUSEPORT_BPF,
+    },
+    programs::{load_program, Link, LinkRef, ProgramData, ProgramError},
+};
+
+/// The type returned when attaching a [`SkReuseport`] program fails.
+#[derive(Debug, Error)]
+pub enum SkReuseportError {
+    /// Setting the `SO_ATTACH_REUSEPORT_EBPF` socket option failed.
+    #[error("setsockopt SO_ATTACH_REUSEPORT_EBPF failed")]
+    SoAttachReuseportEbpfError {
+        #[source]
+        io_error: io::Error,
+    },
+}
+
+/// The kind of [`SkReuseport`] program.
+#[derive(Copy, Clone, Debug)]
+pub enum SkReuseportKind {
+    /// Selects a socket for new packets and connections.
+    Select,
+    /// Also selects the socket that takes over the requests of a closed
+    /// listener.
+    SelectOrMigrate,
+}
+
+/// A program used to select the socket of a `SO_REUSEPORT` group.
+///
+/// When several sockets are bound to the same address with `SO_REUSEPORT`,
+/// the kernel hashes incoming packets and connections to one of them.
+/// [`SkReuseport`] programs replace that selection, usually picking a socket
+/// from a [`ReuseportSockArray`](crate::maps::ReuseportSockArray). The program
+/// is attached to one of the sockets of the group, and applies to the whole
+/// group.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.19.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(Debug, thiserror::Error)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Program(#[from] aya::programs::ProgramError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let mut bpf = aya::Bpf::load(&[])?;
+/// # let listener = std::net::TcpListener::bind("127.0.0.1:8080")?;
+/// use std::os::unix::io::AsRawFd;
+/// use aya::programs::SkReuseport;
+///
+/// let prog = bpf.program_mut_typed::<SkReuseport>("select_listener")?;
+/// prog.load()?;
+/// prog.attach(listener.as_raw_fd())?;
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_PROG_TYPE_SK_REUSEPORT")]
+pub struct SkReuseport {
+    pub(crate) data: ProgramData,
+    pub(crate) kind: SkReuseportKind,
+}
+
+impl SkReuseport {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(match self.kind {
+            SkReuseportKind::Select => BPF_SK_REUSEPORT_SELECT,
+            SkReuseportKind::SelectOrMigrate => BPF_SK_REUSEPORT_SELECT_OR_MIGRATE,
+        });
+        load_program(BPF_PROG_TYPE_SK_REUSEPORT, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Returns the kind of the program, set by its ELF section name:
+    /// `sk_reuseport` or `sk_reuseport/migrate`.
+    pub fn kind(&self) -> SkReuseportKind {
+        self.kind
+    }
+
+    /// Attaches the program to the reuseport group of the given socket.
+    ///
+    /// The socket must have `SO_REUSEPORT` set.
+    pub fn attach<T: AsRawFd>(&mut self, socket: T) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let socket = socket.as_raw_fd();
+
+        let ret = unsafe {
+            setsockopt(
+                socket,
+                SOL_SOCKET,
+                SO_ATTACH_REUSEPORT_EBPF as i32,
+                &prog_fd as *const _ as *const _,
+                mem::size_of::<RawFd>() as u32,
+            )
+        };
+        if ret < 0 {
+            return Err(SkReuseportError::SoAttachReuseportEbpfError {
+                io_error: io::Error::last_os_error(),
+            }
+            .into());
+        }
+
+        Ok(self.data.link(SkReuseportLink {
+            socket,
+            prog_fd: Some(prog_fd),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct SkReuseportLink {
+    socket: RawFd,
+    prog_fd: Option<RawFd>,
+}
+
+impl Link for SkReuseportLink {
+    fn detach(&mut self) -> Result<(), ProgramError> {
+        if self.prog_fd.take().is_some() {
+            // SO_DETACH_REUSEPORT_BPF requires 5.3, on older kernels the
+            // call fails and the program stays attached until the group is
+            // closed
+            let value: c_int = 0;
+            let ret = unsafe {
+                setsockopt(
+                    self.socket,
+                    SOL_SOCKET,
+                    SO_DETACH_REUSEPORT_BPF as i32,
+                    &value as *const _ as *const _,
+                    mem::size_of::<c_int>() as u32,
+                )
+            };
+            if ret < 0 {
+                return Err(ProgramError::SyscallError {
+                    call: "setsockopt".to_owned(),
+                    io_error: io::Error::last_os_error(),
+                });
+            }
+            Ok(())
+        } else {
+            Err(ProgramError::AlreadyDetached)
+        }
+    }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.prog_fd
+            .take()
+            .map(|_| None)
+            .ok_or(ProgramError::AlreadyDetached)
+    }
+}
+
+impl Drop for SkReuseportLink {
+    fn drop(&mut self) {
+        let _ = self.detach();
+    }
+}
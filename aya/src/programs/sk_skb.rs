@@ -1,10 +1,10 @@
 use crate::{
     generated::{
-        bpf_attach_type::{BPF_SK_SKB_STREAM_PARSER, BPF_SK_SKB_STREAM_VERDICT},
+        bpf_attach_type::{self, BPF_SK_SKB_STREAM_PARSER, BPF_SK_SKB_STREAM_VERDICT},
         bpf_prog_type::BPF_PROG_TYPE_SK_SKB,
     },
     maps::sock::SocketMap,
-    programs::{load_program, LinkRef, ProgAttachLink, ProgramData, ProgramError},
+    programs::{load_program, prog_detach, LinkRef, ProgAttachLink, ProgramData, ProgramError},
     sys::bpf_prog_attach,
 };
 
@@ -29,12 +29,12 @@ pub enum SkSkbKind {
 ///
 /// ```no_run
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::{TryFrom, TryInto};
+/// use std::convert::TryFrom;
 /// use aya::maps::SockMap;
 /// use aya::programs::SkSkb;
 ///
 /// let intercept_ingress = SockMap::try_from(bpf.map_mut("INTERCEPT_INGRESS")?)?;
-/// let prog: &mut SkSkb = bpf.program_mut("intercept_ingress_packet")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SkSkb>("intercept_ingress_packet")?;
 /// prog.load()?;
 /// prog.attach(&intercept_ingress)?;
 /// # Ok::<(), aya::BpfError>(())
@@ -68,11 +68,8 @@ impl SkSkb {
         let prog_fd = self.data.fd_or_err()?;
         let map_fd = map.fd_or_err()?;
 
-        let attach_type = match self.kind {
-            SkSkbKind::StreamParser => BPF_SK_SKB_STREAM_PARSER,
-            SkSkbKind::StreamVerdict => BPF_SK_SKB_STREAM_VERDICT,
-        };
-        bpf_prog_attach(prog_fd, map_fd, attach_type).map_err(|(_, io_error)| {
+        let attach_type = self.attach_type();
+        bpf_prog_attach(prog_fd, map_fd, attach_type, 0).map_err(|(_, io_error)| {
             ProgramError::SyscallError {
                 call: "bpf_prog_attach".to_owned(),
                 io_error,
@@ -82,4 +79,23 @@ impl SkSkb {
             .data
             .link(ProgAttachLink::new(prog_fd, map_fd, attach_type)))
     }
+
+    /// Detaches the program from the given socket map.
+    ///
+    /// The map is left untouched, so sockets already in the map stay there.
+    /// Dropping or detaching the [`LinkRef`] returned by
+    /// [`attach`](Self::attach) detaches the program as well.
+    pub fn detach(&mut self, map: &dyn SocketMap) -> Result<(), ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let map_fd = map.fd_or_err()?;
+
+        prog_detach(prog_fd, map_fd, self.attach_type())
+    }
+
+    fn attach_type(&self) -> bpf_attach_type {
+        match self.kind {
+            SkSkbKind::StreamParser => BPF_SK_SKB_STREAM_PARSER,
+            SkSkbKind::StreamVerdict => BPF_SK_SKB_STREAM_VERDICT,
+        }
+    }
 }
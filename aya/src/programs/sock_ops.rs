@@ -1,8 +1,11 @@
-use std::os::unix::io::AsRawFd;
+use std::{os::unix::io::AsRawFd, path::Path};
 
 use crate::{
     generated::{bpf_attach_type::BPF_CGROUP_SOCK_OPS, bpf_prog_type::BPF_PROG_TYPE_SOCK_OPS},
-    programs::{load_program, LinkRef, ProgAttachLink, ProgramData, ProgramError},
+    programs::{
+        load_program, query_cgroup, Cgroup, CgroupAttachFlags, CgroupPrograms, LinkRef,
+        ProgAttachLink, ProgramData, ProgramError,
+    },
     sys::bpf_prog_attach,
 };
 
@@ -32,13 +35,12 @@ use crate::{
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
 /// use std::fs::File;
-/// use std::convert::TryInto;
-/// use aya::programs::SockOps;
+/// use aya::programs::{CgroupAttachFlags, SockOps};
 ///
 /// let file = File::open("/sys/fs/cgroup/unified")?;
-/// let prog: &mut SockOps = bpf.program_mut("intercept_active_sockets")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SockOps>("intercept_active_sockets")?;
 /// prog.load()?;
-/// prog.attach(file)?;
+/// prog.attach(file, CgroupAttachFlags::empty())?;
 /// # Ok::<(), Error>(())
 #[derive(Debug)]
 #[doc(alias = "BPF_PROG_TYPE_SOCK_OPS")]
@@ -60,18 +62,44 @@ impl SockOps {
     }
 
     /// Attaches the program to the given cgroup.
-    pub fn attach<T: AsRawFd>(&mut self, cgroup: T) -> Result<LinkRef, ProgramError> {
+    ///
+    /// See [`CgroupAttachFlags`] for how `flags` control the coexistence with
+    /// other programs attached to the cgroup hierarchy.
+    pub fn attach<T: AsRawFd>(
+        &mut self,
+        cgroup: T,
+        flags: CgroupAttachFlags,
+    ) -> Result<LinkRef, ProgramError> {
         let prog_fd = self.data.fd_or_err()?;
         let cgroup_fd = cgroup.as_raw_fd();
 
-        bpf_prog_attach(prog_fd, cgroup_fd, BPF_CGROUP_SOCK_OPS).map_err(|(_, io_error)| {
-            ProgramError::SyscallError {
+        bpf_prog_attach(prog_fd, cgroup_fd, BPF_CGROUP_SOCK_OPS, flags.bits()).map_err(
+            |(_, io_error)| ProgramError::SyscallError {
                 call: "bpf_prog_attach".to_owned(),
                 io_error,
-            }
-        })?;
+            },
+        )?;
         Ok(self
             .data
             .link(ProgAttachLink::new(prog_fd, cgroup_fd, BPF_CGROUP_SOCK_OPS)))
     }
+
+    /// Attaches the program to the cgroup at `path`.
+    ///
+    /// See [`SockOps::attach`] for the meaning of `flags`.
+    pub fn attach_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        flags: CgroupAttachFlags,
+    ) -> Result<LinkRef, ProgramError> {
+        let cgroup = Cgroup::open(path)?;
+        self.attach(cgroup, flags)
+    }
+
+    /// Returns the programs attached to the given cgroup.
+    ///
+    /// See [`CgroupSkb::query`](crate::programs::CgroupSkb::query).
+    pub fn query<T: AsRawFd>(cgroup: T) -> Result<CgroupPrograms, ProgramError> {
+        query_cgroup(cgroup, BPF_CGROUP_SOCK_OPS)
+    }
 }
@@ -7,7 +7,10 @@ use thiserror::Error;
 
 use crate::{
     generated::{bpf_prog_type::BPF_PROG_TYPE_SOCKET_FILTER, SO_ATTACH_BPF, SO_DETACH_BPF},
-    programs::{load_program, Link, LinkRef, ProgramData, ProgramError},
+    programs::{
+        load_program, test_run, Link, LinkRef, ProgramData, ProgramError, TestRunOptions,
+        TestRunResult,
+    },
 };
 
 /// The type returned when attaching a [`SocketFilter`] fails.
@@ -45,13 +48,12 @@ pub enum SocketFilterError {
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::TryInto;
 /// use std::net::TcpStream;
 /// use std::os::unix::io::AsRawFd;
 /// use aya::programs::SocketFilter;
 ///
 /// let mut client = TcpStream::connect("127.0.0.1:1234")?;
-/// let prog: &mut SocketFilter = bpf.program_mut("filter_packets")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SocketFilter>("filter_packets")?;
 /// prog.load()?;
 /// prog.attach(client.as_raw_fd())?;
 /// # Ok::<(), Error>(())
@@ -96,6 +98,20 @@ impl SocketFilter {
             prog_fd: Some(prog_fd),
         }))
     }
+
+    /// Runs the program in the kernel with `packet` as input, without
+    /// attaching it.
+    ///
+    /// `packet` must start with an ethernet header. The value returned by
+    /// the program, the number of bytes of the packet to keep, is in
+    /// [`TestRunResult::retval`].
+    pub fn test_run(
+        &self,
+        packet: &[u8],
+        options: &TestRunOptions,
+    ) -> Result<TestRunResult, ProgramError> {
+        test_run(&self.data, packet, &mut [], options)
+    }
 }
 
 #[derive(Debug)]
@@ -121,6 +137,13 @@ impl Link for SocketFilterLink {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.prog_fd
+            .take()
+            .map(|_| None)
+            .ok_or(ProgramError::AlreadyDetached)
+    }
 }
 
 impl Drop for SocketFilterLink {
@@ -5,12 +5,20 @@ use std::{ffi::CString, io, os::unix::io::RawFd};
 
 use crate::{
     generated::{
-        bpf_prog_type::BPF_PROG_TYPE_SCHED_CLS, TC_H_CLSACT, TC_H_MIN_EGRESS, TC_H_MIN_INGRESS,
+        bpf_attach_type::{BPF_TCX_EGRESS, BPF_TCX_INGRESS},
+        bpf_prog_type::BPF_PROG_TYPE_SCHED_CLS,
+        BPF_F_AFTER, BPF_F_BEFORE, BPF_F_ID, BPF_F_LINK, TCA_BPF_FLAG_ACT_DIRECT,
+        TCA_CLS_FLAGS_SKIP_HW, TCA_CLS_FLAGS_SKIP_SW, TC_H_CLSACT, TC_H_MIN_EGRESS,
+        TC_H_MIN_INGRESS,
+    },
+    programs::{
+        load_program,
+        netns::{run_in, NetNs},
+        test_run, FdLink, Link, LinkRef, ProgramData, ProgramError, TestRunOptions, TestRunResult,
     },
-    programs::{load_program, Link, LinkRef, ProgramData, ProgramError},
     sys::{
-        netlink_find_filter_with_name, netlink_qdisc_add_clsact, netlink_qdisc_attach,
-        netlink_qdisc_detach,
+        bpf_link_create_tcx, netlink_find_filter_with_name, netlink_qdisc_add_clsact,
+        netlink_qdisc_attach, netlink_qdisc_detach,
     },
     util::{ifindex_from_ifname, tc_handler_make},
 };
@@ -26,6 +34,95 @@ pub enum TcAttachType {
     Custom(u32),
 }
 
+bitflags! {
+    /// Flags passed to [`SchedClassifier::attach_with_flags`].
+    pub struct TcFlags: u32 {
+        /// Direct-action mode: the return code of the program is the action
+        /// applied to the packet, like `TC_ACT_SHOT`, instead of a
+        /// classification result for a separate tc action. Most classifiers
+        /// are written for this mode.
+        const DIRECT_ACTION = 1 << 0;
+        /// Only run the program offloaded to the NIC, failing if it can't be
+        /// offloaded.
+        const SKIP_SW = 1 << 1;
+        /// Never offload the program to the NIC.
+        const SKIP_HW = 1 << 2;
+    }
+}
+
+impl Default for TcFlags {
+    /// Returns [`TcFlags::DIRECT_ACTION`], the mode [`SchedClassifier::attach`]
+    /// uses.
+    fn default() -> TcFlags {
+        TcFlags::DIRECT_ACTION
+    }
+}
+
+impl TcFlags {
+    // returns the TCA_BPF_FLAGS and TCA_BPF_FLAGS_GEN attributes
+    fn netlink_flags(self) -> (u32, u32) {
+        let mut flags = 0;
+        if self.contains(TcFlags::DIRECT_ACTION) {
+            flags |= TCA_BPF_FLAG_ACT_DIRECT;
+        }
+        let mut gen_flags = 0;
+        if self.contains(TcFlags::SKIP_SW) {
+            gen_flags |= TCA_CLS_FLAGS_SKIP_SW;
+        }
+        if self.contains(TcFlags::SKIP_HW) {
+            gen_flags |= TCA_CLS_FLAGS_SKIP_HW;
+        }
+        (flags, gen_flags)
+    }
+}
+
+/// The position of a program attached with [`SchedClassifier::attach_tcx`],
+/// relative to the other tcx programs of the interface.
+#[derive(Debug, Clone, Copy)]
+pub enum TcxOrder {
+    /// Run before all the other programs.
+    First,
+    /// Run after all the other programs.
+    Last,
+    /// Run before the given program or link.
+    Before(TcxAnchor),
+    /// Run after the given program or link.
+    After(TcxAnchor),
+}
+
+/// A program or link other programs are attached relative to.
+///
+/// See [`TcxOrder`].
+#[derive(Debug, Clone, Copy)]
+pub enum TcxAnchor {
+    /// A program, by file descriptor.
+    ProgramFd(RawFd),
+    /// A program, by id.
+    ProgramId(u32),
+    /// A link, by file descriptor.
+    LinkFd(RawFd),
+    /// A link, by id.
+    LinkId(u32),
+}
+
+impl TcxOrder {
+    // returns the link_create flags and the relative fd or id
+    fn flags(&self) -> (u32, u32) {
+        let (flags, anchor) = match self {
+            TcxOrder::First => return (BPF_F_BEFORE, 0),
+            TcxOrder::Last => return (BPF_F_AFTER, 0),
+            TcxOrder::Before(anchor) => (BPF_F_BEFORE, anchor),
+            TcxOrder::After(anchor) => (BPF_F_AFTER, anchor),
+        };
+        match *anchor {
+            TcxAnchor::ProgramFd(fd) => (flags, fd as u32),
+            TcxAnchor::ProgramId(id) => (flags | BPF_F_ID, id),
+            TcxAnchor::LinkFd(fd) => (flags | BPF_F_LINK, fd as u32),
+            TcxAnchor::LinkId(id) => (flags | BPF_F_LINK | BPF_F_ID, id),
+        }
+    }
+}
+
 /// A network traffic control classifier.
 ///
 /// [`SchedClassifier`] programs can be used to inspect, filter or redirect
@@ -52,14 +149,13 @@ pub enum TcAttachType {
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::TryInto;
 /// use aya::programs::{tc, SchedClassifier, TcAttachType};
 ///
 /// // the clsact qdisc needs to be added before SchedClassifier programs can be
 /// // attached
 /// tc::qdisc_add_clsact("eth0")?;
 ///
-/// let prog: &mut SchedClassifier = bpf.program_mut("redirect_ingress")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<SchedClassifier>("redirect_ingress")?;
 /// prog.load()?;
 /// prog.attach("eth0", TcAttachType::Ingress)?;
 ///
@@ -80,6 +176,9 @@ pub enum TcError {
     },
     #[error("the clsact qdisc is already attached")]
     AlreadyAttached,
+    /// tcx programs can only be attached to ingress or egress.
+    #[error("tcx programs can't be attached to a custom parent")]
+    InvalidTcxAttachType,
 }
 
 #[derive(Debug)]
@@ -88,6 +187,7 @@ struct TcLink {
     attach_type: TcAttachType,
     prog_fd: Option<RawFd>,
     priority: u32,
+    netns: Option<NetNs>,
 }
 
 impl TcAttachType {
@@ -115,6 +215,9 @@ impl SchedClassifier {
 
     /// Attaches the program to the given `interface`.
     ///
+    /// To attach to an interface of another network namespace, call this
+    /// method from [`NetNs::run`](crate::programs::NetNs::run).
+    ///
     /// # Errors
     ///
     /// [`TcError::NetlinkError`] is returned if attaching fails. A common cause
@@ -125,22 +228,101 @@ impl SchedClassifier {
         &mut self,
         interface: &str,
         attach_type: TcAttachType,
+    ) -> Result<LinkRef, ProgramError> {
+        self.attach_with_flags(interface, attach_type, TcFlags::default())
+    }
+
+    /// Attaches the program to the given `interface` with the given `flags`.
+    ///
+    /// [`SchedClassifier::attach`] always attaches in direct-action mode, this
+    /// method allows attaching without it, or controlling hardware offload
+    /// with [`TcFlags::SKIP_SW`] and [`TcFlags::SKIP_HW`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SchedClassifier::attach`]. Attaching with [`TcFlags::SKIP_SW`]
+    /// fails if the NIC can't run the program.
+    pub fn attach_with_flags(
+        &mut self,
+        interface: &str,
+        attach_type: TcAttachType,
+        flags: TcFlags,
     ) -> Result<LinkRef, ProgramError> {
         let prog_fd = self.data.fd_or_err()?;
         let if_index = ifindex_from_ifname(interface)
             .map_err(|io_error| TcError::NetlinkError { io_error })?;
         let name = CString::new(self.name()).unwrap();
-        let priority =
-            unsafe { netlink_qdisc_attach(if_index as i32, &attach_type, prog_fd, &name) }
-                .map_err(|io_error| TcError::NetlinkError { io_error })?;
+        let (flags, gen_flags) = flags.netlink_flags();
+        let priority = unsafe {
+            netlink_qdisc_attach(
+                if_index as i32,
+                &attach_type,
+                prog_fd,
+                &name,
+                flags,
+                gen_flags,
+            )
+        }
+        .map_err(|io_error| TcError::NetlinkError { io_error })?;
 
         Ok(self.data.link(TcLink {
             if_index: if_index as i32,
             attach_type,
             prog_fd: Some(prog_fd),
             priority,
+            netns: NetNs::current(),
         }))
     }
+
+    /// Attaches the program to the given `interface` with a tcx link.
+    ///
+    /// Unlike [`SchedClassifier::attach`], tcx doesn't need the `clsact`
+    /// qdisc and orders the programs of an interface explicitly with
+    /// `order`, so that several applications can attach programs to the same
+    /// interface without clashing on priorities. The program is detached when
+    /// the link is dropped.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 6.6.
+    pub fn attach_tcx(
+        &mut self,
+        interface: &str,
+        attach_type: TcAttachType,
+        order: TcxOrder,
+    ) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        let if_index = ifindex_from_ifname(interface)
+            .map_err(|io_error| TcError::NetlinkError { io_error })?;
+        let attach_type = match attach_type {
+            TcAttachType::Ingress => BPF_TCX_INGRESS,
+            TcAttachType::Egress => BPF_TCX_EGRESS,
+            TcAttachType::Custom(_) => return Err(TcError::InvalidTcxAttachType.into()),
+        };
+        let (flags, relative) = order.flags();
+        let link_fd = bpf_link_create_tcx(prog_fd, if_index, attach_type, flags, relative).map_err(
+            |(_, io_error)| ProgramError::SyscallError {
+                call: "bpf_link_create".to_owned(),
+                io_error,
+            },
+        )? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+
+    /// Runs the program in the kernel with `packet` as input, without
+    /// attaching it.
+    ///
+    /// The packet modified by the program is written to `packet_out`, see
+    /// [`TestRunResult::data_size_out`] for its size.
+    pub fn test_run(
+        &self,
+        packet: &[u8],
+        packet_out: &mut [u8],
+        options: &TestRunOptions,
+    ) -> Result<TestRunResult, ProgramError> {
+        test_run(&self.data, packet, packet_out, options)
+    }
 }
 
 impl Drop for TcLink {
@@ -152,13 +334,24 @@ impl Drop for TcLink {
 impl Link for TcLink {
     fn detach(&mut self) -> Result<(), ProgramError> {
         if self.prog_fd.take().is_some() {
-            unsafe { netlink_qdisc_detach(self.if_index, &self.attach_type, self.priority) }
-                .map_err(|io_error| TcError::NetlinkError { io_error })?;
+            let (if_index, attach_type, priority) =
+                (self.if_index, self.attach_type, self.priority);
+            run_in(self.netns.as_ref(), || unsafe {
+                netlink_qdisc_detach(if_index, &attach_type, priority)
+            })
+            .map_err(|io_error| TcError::NetlinkError { io_error })?;
             Ok(())
         } else {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.prog_fd
+            .take()
+            .map(|_| None)
+            .ok_or(ProgramError::AlreadyDetached)
+    }
 }
 
 /// Add the `clasct` qdisc to the given interface.
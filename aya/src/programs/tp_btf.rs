@@ -5,8 +5,8 @@ use thiserror::Error;
 
 use crate::{
     generated::{bpf_attach_type::BPF_TRACE_RAW_TP, bpf_prog_type::BPF_PROG_TYPE_TRACING},
-    obj::btf::{Btf, BtfError, BtfKind},
-    programs::{load_program, FdLink, LinkRef, ProgramData, ProgramError},
+    obj::btf::{Btf, BtfError, BtfFuncArg, BtfKind},
+    programs::{load_btf_program, resolve_btf_type, FdLink, LinkRef, ProgramData, ProgramError},
     sys::bpf_raw_tracepoint_open,
 };
 
@@ -37,10 +37,9 @@ use crate::{
 /// # }
 /// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 /// use aya::{Bpf, programs::BtfTracePoint, BtfError, Btf};
-/// use std::convert::TryInto;
 ///
 /// let btf = Btf::from_sys_fs()?;
-/// let program: &mut BtfTracePoint = bpf.program_mut("sched_process_fork")?.try_into()?;
+/// let program = bpf.program_mut_typed::<BtfTracePoint>("sched_process_fork")?;
 /// program.load("sched_process_fork", &btf)?;
 /// program.attach()?;
 /// # Ok::<(), Error>(())
@@ -71,14 +70,51 @@ impl BtfTracePoint {
     ///
     /// * `tracepoint` - full name of the tracepoint that we should attach to
     /// * `btf` - btf information for the target system
+    ///
+    /// Tracepoints defined in kernel modules are looked up in the BTF of the
+    /// loaded modules when `btf` is the BTF of the running kernel. The BTF of
+    /// the module, as returned by [`Btf::from_sys_fs_module`], can also be
+    /// passed directly.
     pub fn load(&mut self, tracepoint: &str, btf: &Btf) -> Result<(), ProgramError> {
         self.data.expected_attach_type = Some(BPF_TRACE_RAW_TP);
         let type_name = format!("btf_trace_{}", tracepoint);
-        self.data.attach_btf_id = Some(
-            btf.id_by_type_name_kind(type_name.as_str(), BtfKind::Typedef)
-                .map_err(BtfTracePointError::from)?,
-        );
-        load_program(BPF_PROG_TYPE_TRACING, &mut self.data)
+        let (type_id, module_btf) = resolve_btf_type(btf, &type_name, BtfKind::Typedef)
+            .map_err(BtfTracePointError::from)?;
+        self.data.attach_btf_id = Some(type_id);
+        load_btf_program(
+            BPF_PROG_TYPE_TRACING,
+            &mut self.data,
+            module_btf.as_ref().unwrap_or(btf),
+        )
+    }
+
+    /// Returns the arguments the program receives when attached to
+    /// `tracepoint`, as described by `btf`.
+    ///
+    /// Like [`BtfTracePoint::load`], tracepoints defined in kernel modules are
+    /// looked up in the BTF of the loaded modules.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::{programs::BtfTracePoint, Btf};
+    ///
+    /// let btf = Btf::from_sys_fs()?;
+    /// for arg in BtfTracePoint::args("sched_process_fork", &btf)? {
+    ///     println!("{} {}", arg.type_name, arg.name);
+    /// }
+    /// # Ok::<(), aya::programs::BtfTracePointError>(())
+    /// ```
+    pub fn args(tracepoint: &str, btf: &Btf) -> Result<Vec<BtfFuncArg>, BtfTracePointError> {
+        let type_name = format!("btf_trace_{}", tracepoint);
+        let (type_id, module_btf) = resolve_btf_type(btf, &type_name, BtfKind::Typedef)?;
+        let mut args = module_btf.as_ref().unwrap_or(btf).func_args(type_id)?;
+        // the first argument of the trace function is the tracepoint's private
+        // data, which isn't passed to programs
+        if !args.is_empty() {
+            args.remove(0);
+        }
+        Ok(args)
     }
 
     /// Returns the name of the program.
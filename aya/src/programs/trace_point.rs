@@ -1,7 +1,10 @@
 use std::{fs, io};
 use thiserror::Error;
 
-use crate::{generated::bpf_prog_type::BPF_PROG_TYPE_TRACEPOINT, sys::perf_event_open_trace_point};
+use crate::{
+    generated::bpf_prog_type::BPF_PROG_TYPE_TRACEPOINT, sys::perf_event_open_trace_point,
+    util::tracefs_path,
+};
 
 use super::{load_program, perf_attach, LinkRef, ProgramData, ProgramError};
 
@@ -41,10 +44,9 @@ pub enum TracePointError {
 /// #     Bpf(#[from] aya::BpfError)
 /// # }
 /// # let mut bpf = aya::Bpf::load(&[])?;
-/// use std::convert::TryInto;
 /// use aya::programs::TracePoint;
 ///
-/// let prog: &mut TracePoint = bpf.program_mut("trace_context_switch")?.try_into()?;
+/// let prog = bpf.program_mut_typed::<TracePoint>("trace_context_switch")?;
 /// prog.load()?;
 /// prog.attach("sched", "sched_switch")?;
 /// # Ok::<(), Error>(())
@@ -84,7 +86,14 @@ pub(crate) fn read_sys_fs_trace_point_id(
     category: &str,
     name: &str,
 ) -> Result<u32, TracePointError> {
-    let file = format!("/sys/kernel/debug/tracing/events/{}/{}/id", category, name);
+    let file = tracefs_path()
+        .map(|path| path.join("events").join(category).join(name).join("id"))
+        .map_err(|io_error| TracePointError::FileError {
+            filename: format!("events/{}/{}/id", category, name),
+            io_error,
+        })?
+        .to_string_lossy()
+        .into_owned();
 
     let id = fs::read_to_string(&file).map_err(|io_error| TracePointError::FileError {
         filename: file.clone(),
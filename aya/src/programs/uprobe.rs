@@ -2,13 +2,14 @@
 use libc::pid_t;
 use object::{Object, ObjectSymbol};
 use std::{
+    env,
     error::Error,
-    ffi::CStr,
+    ffi::{CStr, OsStr, OsString},
     fs,
     io::{self, BufRead, Cursor, Read},
     mem,
-    os::raw::c_char,
-    path::{Path, PathBuf},
+    os::{raw::c_char, unix::ffi::OsStrExt},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 use thiserror::Error;
@@ -20,6 +21,7 @@ use crate::{
         probe::{attach, ProbeKind},
         LinkRef, ProgramData, ProgramError,
     },
+    util::crc32,
 };
 
 const LD_SO_CACHE_FILE: &str = "/etc/ld.so.cache";
@@ -67,12 +69,20 @@ impl UProbe {
     ///
     /// Attaches the uprobe to the function `fn_name` defined in the `target`.
     /// If `offset` is non-zero, it is added to the address of the target
-    /// function. If `pid` is not `None`, the program executes only when the target
-    /// function is executed by the given `pid`.
+    /// function. If `fn_name` is `None`, `offset` is used as is, which allows
+    /// probing arbitrary locations, for example in stripped binaries. If `pid`
+    /// is not `None`, the program executes only when the target function is
+    /// executed by the given `pid`.
     ///
     /// The `target` argument can be an absolute path to a binary or library, or
     /// a library name (eg: `"libc"`).
     ///
+    /// `fn_name` is looked up in the dynamic and regular symbol tables of the
+    /// target. If the target is stripped, its symbols are looked up in its
+    /// separate debug file, found by build-id under `/usr/lib/debug/.build-id`
+    /// or in the debuginfod client cache, or by `.gnu_debuglink` next to the
+    /// target, in its `.debug` directory or under `/usr/lib/debug`.
+    ///
     /// If the program is an `uprobe`, it is attached to the *start* address of the target
     /// function.  Instead if the program is a `kretprobe`, it is attached to the return address of
     /// the target function.
@@ -84,36 +94,82 @@ impl UProbe {
         target: T,
         pid: Option<pid_t>,
     ) -> Result<LinkRef, ProgramError> {
-        let target = target.as_ref();
-        let target_str = &*target.as_os_str().to_string_lossy();
+        self.attach_inner(fn_name, offset, target.as_ref(), pid, None, None)
+    }
 
-        let mut path = if let Some(pid) = pid {
-            find_lib_in_proc_maps(pid, target_str).map_err(|io_error| UProbeError::FileError {
-                filename: format!("/proc/{}/maps", pid),
-                io_error,
-            })?
-        } else {
-            None
-        };
+    /// Attaches the program, incrementing a reference counter while attached.
+    ///
+    /// Works like [`UProbe::attach`], but additionally makes the kernel
+    /// increment the 16 bit counter at `ref_ctr_offset` in the `target` while
+    /// the probe is attached. This is how USDT semaphores are activated, so
+    /// that the traced application only pays for computing probe arguments
+    /// when somebody is listening.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 4.20.
+    pub fn attach_with_ref_ctr_offset<T: AsRef<Path>>(
+        &mut self,
+        fn_name: Option<&str>,
+        offset: u64,
+        target: T,
+        pid: Option<pid_t>,
+        ref_ctr_offset: u64,
+    ) -> Result<LinkRef, ProgramError> {
+        self.attach_inner(
+            fn_name,
+            offset,
+            target.as_ref(),
+            pid,
+            Some(ref_ctr_offset),
+            None,
+        )
+    }
 
-        if path.is_none() {
-            path = if target.is_absolute() {
-                Some(target_str)
-            } else {
-                let cache =
-                    LD_SO_CACHE
-                        .as_ref()
-                        .map_err(|error| UProbeError::InvalidLdSoCache {
-                            io_error: error.clone(),
-                        })?;
-                cache.resolve(target_str)
-            }
-            .map(String::from)
-        };
+    /// Attaches the program to a target in the mount namespace of `ns_pid`.
+    ///
+    /// Works like [`UProbe::attach`], but `target` is resolved as seen by the
+    /// process `ns_pid`, through `/proc/<ns_pid>/root`. Library names are
+    /// looked up in the `ld.so.cache` of that namespace. This allows
+    /// instrumenting the binaries of a container from the host, by passing
+    /// the pid of any process running in the container.
+    ///
+    /// If `pid` is not `None`, it must be a process of the same mount
+    /// namespace.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let mut bpf = aya::Bpf::load(&[])?;
+    /// use aya::programs::UProbe;
+    ///
+    /// let container_pid = 4242;
+    /// let program = bpf.program_mut_typed::<UProbe>("trace_malloc")?;
+    /// program.load()?;
+    /// program.attach_in_mount_ns(Some("malloc"), 0, "libc", None, container_pid)?;
+    /// # Ok::<(), aya::BpfError>(())
+    /// ```
+    pub fn attach_in_mount_ns<T: AsRef<Path>>(
+        &mut self,
+        fn_name: Option<&str>,
+        offset: u64,
+        target: T,
+        pid: Option<pid_t>,
+        ns_pid: pid_t,
+    ) -> Result<LinkRef, ProgramError> {
+        self.attach_inner(fn_name, offset, target.as_ref(), pid, None, Some(ns_pid))
+    }
 
-        let path = path.ok_or(UProbeError::InvalidTarget {
-            path: target.to_owned(),
-        })?;
+    fn attach_inner(
+        &mut self,
+        fn_name: Option<&str>,
+        offset: u64,
+        target: &Path,
+        pid: Option<pid_t>,
+        ref_ctr_offset: Option<u64>,
+        ns_pid: Option<pid_t>,
+    ) -> Result<LinkRef, ProgramError> {
+        let path = resolve_target(target, pid, ns_pid)?;
 
         let sym_offset = if let Some(fn_name) = fn_name {
             resolve_symbol(&path, fn_name).map_err(|error| UProbeError::SymbolError {
@@ -124,7 +180,15 @@ impl UProbe {
             0
         };
 
-        attach(&mut self.data, self.kind, &path, sym_offset + offset, pid)
+        attach(
+            &mut self.data,
+            self.kind,
+            &path,
+            sym_offset + offset,
+            pid,
+            None,
+            ref_ctr_offset,
+        )
     }
 }
 
@@ -150,6 +214,10 @@ pub enum UProbeError {
         error: Box<dyn Error + Send + Sync>,
     },
 
+    /// The number of cookies doesn't match the number of probes.
+    #[error("expected {expected} cookies, got {got}")]
+    InvalidCookies { expected: usize, got: usize },
+
     /// There was an error accessing `filename`.
     #[error("`{filename}`")]
     FileError {
@@ -159,6 +227,124 @@ pub enum UProbeError {
     },
 }
 
+/// Resolves the path of the binary or library `target`, looking into the
+/// memory mappings of `pid` first if given, then into `ld.so.cache`.
+///
+/// If `ns_pid` is given, `target` is resolved in the mount namespace of
+/// `ns_pid` and the returned path goes through `/proc/<ns_pid>/root`.
+pub(crate) fn resolve_target(
+    target: &Path,
+    pid: Option<pid_t>,
+    ns_pid: Option<pid_t>,
+) -> Result<String, UProbeError> {
+    let target_str = &*target.as_os_str().to_string_lossy();
+
+    let mut path = if let Some(pid) = pid {
+        find_lib_in_proc_maps(pid, target_str).map_err(|io_error| UProbeError::FileError {
+            filename: format!("/proc/{}/maps", pid),
+            io_error,
+        })?
+    } else {
+        None
+    };
+
+    if path.is_none() {
+        path = if target.is_absolute() {
+            Some(target_str.to_owned())
+        } else if let Some(ns_pid) = ns_pid {
+            let filename = ns_path(ns_pid, LD_SO_CACHE_FILE)?;
+            let cache = LdSoCache::load(&filename)
+                .map_err(|io_error| UProbeError::FileError { filename, io_error })?;
+            cache.resolve(target_str).map(String::from)
+        } else {
+            let cache = LD_SO_CACHE
+                .as_ref()
+                .map_err(|error| UProbeError::InvalidLdSoCache {
+                    io_error: error.clone(),
+                })?;
+            cache.resolve(target_str).map(String::from)
+        }
+    };
+
+    let path = path.ok_or(UProbeError::InvalidTarget {
+        path: target.to_owned(),
+    })?;
+    match ns_pid {
+        Some(ns_pid) => ns_path(ns_pid, &path),
+        None => Ok(path),
+    }
+}
+
+// returns the path to `path` in the mount namespace of `ns_pid`
+fn ns_path(ns_pid: pid_t, path: &str) -> Result<String, UProbeError> {
+    let root = PathBuf::from(format!("/proc/{}/root", ns_pid));
+    resolve_in_root(&root, Path::new(path))
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|io_error| UProbeError::FileError {
+            filename: root
+                .join(path.trim_start_matches('/'))
+                .to_string_lossy()
+                .into_owned(),
+            io_error,
+        })
+}
+
+// resolves `path` relative to `root` like openat2(RESOLVE_IN_ROOT) does:
+// absolute symlinks and `..` are resolved against `root` instead of the root
+// of the current mount namespace, so they can't escape it
+fn resolve_in_root(root: &Path, path: &Path) -> Result<PathBuf, io::Error> {
+    const MAX_SYMLINKS: usize = 40;
+
+    // the components left to resolve, in reverse order. A normal component
+    // can't be `..`, so it marks the parent directory
+    fn components(path: &Path) -> impl DoubleEndedIterator<Item = OsString> + '_ {
+        path.components().filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_owned()),
+            Component::ParentDir => Some(OsString::from("..")),
+            _ => None,
+        })
+    }
+    let mut pending = components(path).rev().collect::<Vec<_>>();
+    let mut resolved = PathBuf::new();
+    let mut symlinks = 0;
+    while let Some(component) = pending.pop() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+        resolved.push(&component);
+        let full_path = root.join(&resolved);
+        match fs::symlink_metadata(&full_path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(io::Error::from_raw_os_error(libc::ELOOP));
+                }
+                let target = fs::read_link(&full_path)?;
+                resolved.pop();
+                if target.is_absolute() {
+                    resolved = PathBuf::new();
+                }
+                pending.extend(components(&target).rev());
+            }
+            Ok(_) => {}
+            // nothing left to resolve, the caller reports the missing file
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                while let Some(component) = pending.pop() {
+                    if component == ".." {
+                        resolved.pop();
+                    } else {
+                        resolved.push(component);
+                    }
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(root.join(resolved))
+}
+
 fn proc_maps_libs(pid: pid_t) -> Result<Vec<(String, String)>, io::Error> {
     let maps_file = format!("/proc/{}/maps", pid);
     let data = fs::read_to_string(maps_file)?;
@@ -283,7 +469,7 @@ impl LdSoCache {
 }
 
 #[derive(Error, Debug)]
-enum ResolveSymbolError {
+pub(crate) enum ResolveSymbolError {
     #[error(transparent)]
     Io(#[from] io::Error),
 
@@ -294,13 +480,176 @@ enum ResolveSymbolError {
     Unknown(String),
 }
 
-fn resolve_symbol(path: &str, symbol: &str) -> Result<u64, ResolveSymbolError> {
+pub(crate) fn resolve_symbol(path: &str, symbol: &str) -> Result<u64, ResolveSymbolError> {
     let data = fs::read(path)?;
     let obj = object::read::File::parse(&*data)?;
 
+    if let Some(address) = find_symbol(&obj, symbol) {
+        return Ok(address);
+    }
+
+    // distributions strip their binaries and ship the symbols in separate
+    // debug files
+    if let Some(debug_data) = find_debug_file(&obj, Path::new(path)) {
+        let debug_obj = object::read::File::parse(&*debug_data)?;
+        if let Some(address) = find_symbol(&debug_obj, symbol) {
+            return Ok(address);
+        }
+    }
+
+    Err(ResolveSymbolError::Unknown(symbol.to_string()))
+}
+
+fn find_symbol(obj: &object::read::File<'_>, symbol: &str) -> Option<u64> {
     obj.dynamic_symbols()
         .chain(obj.symbols())
         .find(|sym| sym.name().map(|name| name == symbol).unwrap_or(false))
         .map(|s| s.address())
-        .ok_or_else(|| ResolveSymbolError::Unknown(symbol.to_string()))
+}
+
+/// Returns the contents of the separate debug file of `obj`, loaded from
+/// `path`, looking it up by build-id first and by debuglink then.
+fn find_debug_file(obj: &object::read::File<'_>, path: &Path) -> Option<Vec<u8>> {
+    if let Ok(Some(build_id)) = obj.build_id() {
+        let cache_dir = debuginfod_cache_dir();
+        for candidate in build_id_paths(build_id, cache_dir.as_deref()) {
+            if let Ok(data) = fs::read(candidate) {
+                return Some(data);
+            }
+        }
+    }
+
+    if let Ok(Some((name, crc))) = obj.gnu_debuglink() {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        for candidate in debuglink_paths(&path, OsStr::from_bytes(name)) {
+            match fs::read(candidate) {
+                Ok(data) if crc32(&data) == crc => return Some(data),
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+// the locations where gdb and the debuginfod client look for the debug file of
+// a binary with the given build-id
+fn build_id_paths(build_id: &[u8], debuginfod_cache_dir: Option<&Path>) -> Vec<PathBuf> {
+    let hex = build_id
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    if hex.len() < 3 {
+        return Vec::new();
+    }
+
+    let mut paths = vec![PathBuf::from(format!(
+        "/usr/lib/debug/.build-id/{}/{}.debug",
+        &hex[..2],
+        &hex[2..]
+    ))];
+    if let Some(dir) = debuginfod_cache_dir {
+        paths.push(dir.join(&hex).join("debuginfo"));
+    }
+    paths
+}
+
+fn debuginfod_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("DEBUGINFOD_CACHE_PATH") {
+        return Some(dir.into());
+    }
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_dir.join("debuginfod_client"))
+}
+
+// the locations where gdb looks for the debuglink `name` of the binary `path`
+fn debuglink_paths(path: &Path, name: &OsStr) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    vec![
+        dir.join(name),
+        dir.join(".debug").join(name),
+        Path::new("/usr/lib/debug")
+            .join(dir.strip_prefix("/").unwrap_or(dir))
+            .join(name),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_in_root() {
+        let root = env::temp_dir().join(format!("aya-test-resolve-in-root-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        fs::write(root.join("lib/libfoo.so.1"), b"").unwrap();
+        // absolute symlinks and `..` resolve inside the root
+        symlink("/lib/libfoo.so.1", root.join("usr/lib/libfoo.so")).unwrap();
+        symlink("../../../../../lib", root.join("usr/lib/up")).unwrap();
+        symlink("/usr/lib/libfoo.so", root.join("usr/lib/libbar.so")).unwrap();
+        symlink("loop", root.join("usr/lib/loop")).unwrap();
+
+        let resolve = |path: &str| resolve_in_root(&root, Path::new(path));
+        assert_eq!(
+            resolve("/usr/lib/libfoo.so").unwrap(),
+            root.join("lib/libfoo.so.1")
+        );
+        assert_eq!(
+            resolve("/usr/lib/libbar.so").unwrap(),
+            root.join("lib/libfoo.so.1")
+        );
+        assert_eq!(
+            resolve("/usr/lib/up/libfoo.so.1").unwrap(),
+            root.join("lib/libfoo.so.1")
+        );
+        assert_eq!(
+            resolve("/../../usr/lib/missing.so").unwrap(),
+            root.join("usr/lib/missing.so")
+        );
+        assert!(resolve("/usr/lib/loop").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_build_id_paths() {
+        assert_eq!(
+            build_id_paths(&[0xab, 0xcd, 0x01], Some(Path::new("/cache"))),
+            vec![
+                PathBuf::from("/usr/lib/debug/.build-id/ab/cd01.debug"),
+                PathBuf::from("/cache/abcd01/debuginfo"),
+            ]
+        );
+        assert!(build_id_paths(&[0xab], None).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_target_in_mount_ns() {
+        assert_eq!(
+            resolve_target(Path::new("/usr/bin/bash"), None, Some(42)).unwrap(),
+            "/proc/42/root/usr/bin/bash"
+        );
+        assert_eq!(
+            resolve_target(Path::new("/usr/bin/bash"), None, None).unwrap(),
+            "/usr/bin/bash"
+        );
+    }
+
+    #[test]
+    fn test_debuglink_paths() {
+        assert_eq!(
+            debuglink_paths(Path::new("/usr/bin/bash"), OsStr::new("bash.debug")),
+            vec![
+                PathBuf::from("/usr/bin/bash.debug"),
+                PathBuf::from("/usr/bin/.debug/bash.debug"),
+                PathBuf::from("/usr/lib/debug/usr/bin/bash.debug"),
+            ]
+        );
+    }
 }
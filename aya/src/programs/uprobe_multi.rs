@@ -0,0 +1,146 @@
+//! User space multi probes.
+use libc::pid_t;
+use std::{ffi::CString, os::unix::io::RawFd, path::Path};
+
+use crate::{
+    generated::{bpf_attach_type::BPF_TRACE_UPROBE_MULTI, bpf_prog_type::BPF_PROG_TYPE_KPROBE},
+    programs::{
+        load_program,
+        uprobe::{resolve_symbol, resolve_target},
+        FdLink, LinkRef, ProbeKind, ProgramData, ProgramError, UProbeError,
+    },
+    sys::bpf_link_create_uprobe_multi,
+};
+
+// see enum { BPF_F_UPROBE_MULTI_RETURN } in include/uapi/linux/bpf.h
+const BPF_F_UPROBE_MULTI_RETURN: u32 = 1 << 0;
+
+/// An user space probe attached to many locations at once.
+///
+/// [`UProbeMulti`] programs behave like [`UProbe`](crate::programs::UProbe)
+/// programs, but a single attach call can instrument any number of locations
+/// in a binary or library, using a single `bpf_link`. Each location can be
+/// given a cookie, which the program can read with `bpf_get_attach_cookie()`
+/// to tell which location triggered it.
+///
+/// Multi probes are declared in sections named `uprobe.multi/name` and
+/// `uretprobe.multi/name`.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 6.6.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{Bpf, programs::UProbeMulti};
+///
+/// let program = bpf.program_mut_typed::<UProbeMulti>("trace_malloc")?;
+/// program.load()?;
+/// program.attach("libc", &["malloc", "calloc", "realloc"], Some(&[0, 1, 2]), None)?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_TRACE_UPROBE_MULTI")]
+pub struct UProbeMulti {
+    pub(crate) data: ProgramData,
+    pub(crate) kind: ProbeKind,
+}
+
+impl UProbeMulti {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        self.data.expected_attach_type = Some(BPF_TRACE_UPROBE_MULTI);
+        load_program(BPF_PROG_TYPE_KPROBE, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Returns `UProbe` if the program is a `uprobe.multi`, or `URetProbe` if
+    /// the program is a `uretprobe.multi`.
+    pub fn kind(&self) -> ProbeKind {
+        self.kind
+    }
+
+    /// Attaches the program to the functions `fn_names` defined in `target`.
+    ///
+    /// If `cookies` is not `None`, it must contain one cookie per function. If
+    /// `pid` is not `None`, the program executes only when the target
+    /// functions are executed by the given `pid`.
+    ///
+    /// The `target` argument can be an absolute path to a binary or library, or
+    /// a library name (eg: `"libc"`).
+    pub fn attach<T: AsRef<Path>>(
+        &mut self,
+        target: T,
+        fn_names: &[&str],
+        cookies: Option<&[u64]>,
+        pid: Option<pid_t>,
+    ) -> Result<LinkRef, ProgramError> {
+        let path = resolve_target(target.as_ref(), pid, None)?;
+        let offsets = fn_names
+            .iter()
+            .map(|fn_name| {
+                resolve_symbol(&path, fn_name).map_err(|error| UProbeError::SymbolError {
+                    symbol: fn_name.to_string(),
+                    error: Box::new(error),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.attach_inner(&path, &offsets, cookies, pid)
+    }
+
+    /// Attaches the program to the given file `offsets` in `target`.
+    ///
+    /// See [`UProbeMulti::attach`] for the meaning of the other arguments.
+    pub fn attach_offsets<T: AsRef<Path>>(
+        &mut self,
+        target: T,
+        offsets: &[u64],
+        cookies: Option<&[u64]>,
+        pid: Option<pid_t>,
+    ) -> Result<LinkRef, ProgramError> {
+        let path = resolve_target(target.as_ref(), pid, None)?;
+        self.attach_inner(&path, offsets, cookies, pid)
+    }
+
+    fn attach_inner(
+        &mut self,
+        path: &str,
+        offsets: &[u64],
+        cookies: Option<&[u64]>,
+        pid: Option<pid_t>,
+    ) -> Result<LinkRef, ProgramError> {
+        let prog_fd = self.data.fd_or_err()?;
+        if let Some(cookies) = cookies {
+            if cookies.len() != offsets.len() {
+                return Err(UProbeError::InvalidCookies {
+                    expected: offsets.len(),
+                    got: cookies.len(),
+                }
+                .into());
+            }
+        }
+
+        let flags = match self.kind {
+            ProbeKind::URetProbe => BPF_F_UPROBE_MULTI_RETURN,
+            _ => 0,
+        };
+        let c_path =
+            CString::new(path).map_err(|_| UProbeError::InvalidTarget { path: path.into() })?;
+        let link_fd = bpf_link_create_uprobe_multi(prog_fd, &c_path, offsets, cookies, pid, flags)
+            .map_err(|(_, io_error)| ProgramError::SyscallError {
+            call: "bpf_link_create".to_owned(),
+            io_error,
+        })? as RawFd;
+
+        Ok(self.data.link(FdLink { fd: Some(link_fd) }))
+    }
+}
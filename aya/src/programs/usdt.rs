@@ -0,0 +1,598 @@
+//! User statically-defined tracing.
+use libc::pid_t;
+use object::{Object, ObjectSection, ObjectSegment};
+use std::{
+    fs, io,
+    os::unix::io::RawFd,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    generated::bpf_prog_type::BPF_PROG_TYPE_KPROBE,
+    programs::{
+        load_program,
+        probe::{attach, ProbeKind},
+        uprobe::resolve_target,
+        Link, LinkRef, ProgramData, ProgramError,
+    },
+};
+
+const STAPSDT_NOTE_SECTION: &str = ".note.stapsdt";
+const STAPSDT_BASE_SECTION: &str = ".stapsdt.base";
+const STAPSDT_NOTE_NAME: &[u8] = b"stapsdt";
+const STAPSDT_NOTE_TYPE: u32 = 3;
+
+/// A USDT probe.
+///
+/// USDT (User Statically-Defined Tracing) probes are tracing points compiled
+/// into user space applications and libraries, for example with the
+/// `DTRACE_PROBE` macros from `sys/sdt.h`. Many language runtimes and
+/// databases (Python, Node.js, PostgreSQL, ...) ship with USDT probes.
+///
+/// [`Usdt`] programs are attached as uprobes to every location of the probe
+/// found in the target binary. If the probe is guarded by a semaphore, the
+/// semaphore is incremented while the program is attached.
+///
+/// The argument specifications of a probe can be retrieved with
+/// [`usdt_probes`], so that they can be passed to the program, for example
+/// through a map.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.20.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
+/// use aya::{Bpf, programs::Usdt};
+///
+/// let program = bpf.program_mut_typed::<Usdt>("function_entry")?;
+/// program.load()?;
+/// program.attach("/usr/bin/python3", "python", "function__entry", None)?;
+/// # Ok::<(), aya::BpfError>(())
+/// ```
+#[derive(Debug)]
+#[doc(alias = "BPF_PROG_TYPE_KPROBE")]
+pub struct Usdt {
+    pub(crate) data: ProgramData,
+}
+
+impl Usdt {
+    /// Loads the program inside the kernel.
+    ///
+    /// See also [`Program::load`](crate::programs::Program::load).
+    pub fn load(&mut self) -> Result<(), ProgramError> {
+        load_program(BPF_PROG_TYPE_KPROBE, &mut self.data)
+    }
+
+    /// Returns the name of the program.
+    pub fn name(&self) -> String {
+        self.data.name.to_string()
+    }
+
+    /// Attaches the program.
+    ///
+    /// Attaches the program to all the locations of the USDT probe `name` of
+    /// `provider` defined in `target`. If `pid` is not `None`, the program
+    /// executes only when the probe is hit by the given `pid`.
+    ///
+    /// The `target` argument can be an absolute path to a binary or library, or
+    /// a library name (eg: `"libc"`).
+    ///
+    /// If attaching to any of the locations fails, the locations attached so
+    /// far are detached.
+    pub fn attach<T: AsRef<Path>>(
+        &mut self,
+        target: T,
+        provider: &str,
+        name: &str,
+        pid: Option<pid_t>,
+    ) -> Result<LinkRef, ProgramError> {
+        let path = resolve_target(target.as_ref(), pid, None)?;
+        let probes = usdt_probes(&path)?
+            .into_iter()
+            .filter(|probe| probe.provider == provider && probe.name == name)
+            .collect::<Vec<_>>();
+        if probes.is_empty() {
+            return Err(UsdtError::ProbeNotFound {
+                provider: provider.to_owned(),
+                name: name.to_owned(),
+            }
+            .into());
+        }
+
+        let mut links = Vec::with_capacity(probes.len());
+        for probe in probes {
+            match attach(
+                &mut self.data,
+                ProbeKind::UProbe,
+                &path,
+                probe.offset,
+                pid,
+                None,
+                probe.semaphore_offset,
+            ) {
+                Ok(link) => links.push(link),
+                Err(e) => {
+                    for mut link in links {
+                        let _ = link.detach();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(self.data.link(UsdtLink { links }))
+    }
+}
+
+#[derive(Debug)]
+struct UsdtLink {
+    links: Vec<LinkRef>,
+}
+
+impl Link for UsdtLink {
+    fn detach(&mut self) -> Result<(), ProgramError> {
+        if self.links.is_empty() {
+            return Err(ProgramError::AlreadyDetached);
+        }
+        // all the probes are detached even if some fail, the first error is
+        // returned
+        self.links
+            .drain(..)
+            .map(|mut link| link.detach())
+            .fold(Ok(()), Result::and)
+    }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        if self.links.is_empty() {
+            return Err(ProgramError::AlreadyDetached);
+        }
+        self.links
+            .drain(..)
+            .map(|mut link| link.forget().map(|_| ()))
+            .fold(Ok(()), Result::and)?;
+        Ok(None)
+    }
+}
+
+/// A USDT probe location, as found in the `.note.stapsdt` section of a binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtProbe {
+    /// The provider of the probe.
+    pub provider: String,
+    /// The name of the probe.
+    pub name: String,
+    /// The file offset of the probe location.
+    pub offset: u64,
+    /// The file offset of the semaphore guarding the probe, if any.
+    pub semaphore_offset: Option<u64>,
+    /// The arguments of the probe.
+    pub args: Vec<UsdtArgument>,
+}
+
+/// The specification of a USDT probe argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtArgument {
+    /// The size of the argument in bytes.
+    pub size: u8,
+    /// Whether the argument is signed.
+    pub signed: bool,
+    /// Where the value of the argument can be found.
+    pub location: UsdtArgumentLocation,
+}
+
+/// Where the value of a USDT probe argument can be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsdtArgumentLocation {
+    /// The argument is a constant.
+    Constant(i64),
+    /// The argument is stored in the given register.
+    Register(String),
+    /// The argument is stored in memory at `offset` from the address
+    /// contained in `register`.
+    Memory { register: String, offset: i64 },
+}
+
+/// The type returned when working with USDT probes fails.
+#[derive(Debug, Error)]
+pub enum UsdtError {
+    /// There was an error accessing `filename`.
+    #[error("`{}`", filename.display())]
+    FileError {
+        filename: PathBuf,
+        #[source]
+        io_error: io::Error,
+    },
+
+    /// The target binary could not be parsed.
+    #[error("error parsing ELF")]
+    Object(#[from] object::Error),
+
+    /// The `.note.stapsdt` section is malformed.
+    #[error("invalid USDT note: {0}")]
+    InvalidNote(String),
+
+    /// A probe argument specification could not be parsed.
+    #[error("invalid USDT argument `{arg}`")]
+    InvalidArgument { arg: String },
+
+    /// The probe could not be found in the target.
+    #[error("USDT probe `{provider}:{name}` not found")]
+    ProbeNotFound { provider: String, name: String },
+}
+
+/// Returns the USDT probes defined in the binary or library at `path`.
+pub fn usdt_probes<T: AsRef<Path>>(path: T) -> Result<Vec<UsdtProbe>, UsdtError> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|io_error| UsdtError::FileError {
+        filename: path.to_owned(),
+        io_error,
+    })?;
+    let obj = object::read::File::parse(&*data)?;
+
+    let notes = match obj.section_by_name(STAPSDT_NOTE_SECTION) {
+        Some(section) => section.data()?,
+        None => return Ok(Vec::new()),
+    };
+    let base_addr = obj
+        .section_by_name(STAPSDT_BASE_SECTION)
+        .map(|section| section.address());
+
+    let segments = obj
+        .segments()
+        .map(|segment| (segment.address(), segment.file_range()))
+        .collect::<Vec<_>>();
+    let file_offset = |addr: u64| {
+        segments
+            .iter()
+            .find(|(seg_addr, (_, size))| addr >= *seg_addr && addr < seg_addr + size)
+            .map(|(seg_addr, (offset, _))| addr - seg_addr + offset)
+            .ok_or_else(|| UsdtError::InvalidNote(format!("address {:#x} is not mapped", addr)))
+    };
+
+    let reader = NoteReader {
+        little_endian: obj.is_little_endian(),
+        is_64: obj.is_64(),
+    };
+    let mut probes = Vec::new();
+    for note in reader.notes(notes)? {
+        if note.name != STAPSDT_NOTE_NAME || note.ty != STAPSDT_NOTE_TYPE {
+            continue;
+        }
+        let mut probe = reader.parse_stapsdt(note.desc)?;
+
+        // if the binary was prelinked, the addresses in the note need to be
+        // adjusted by the difference between the actual and the recorded
+        // address of .stapsdt.base
+        if let (Some(base_addr), Some(note_base)) = (base_addr, probe.base) {
+            probe.pc = probe.pc.wrapping_add(base_addr).wrapping_sub(note_base);
+            if probe.semaphore != 0 {
+                probe.semaphore = probe
+                    .semaphore
+                    .wrapping_add(base_addr)
+                    .wrapping_sub(note_base);
+            }
+        }
+
+        probes.push(UsdtProbe {
+            provider: probe.provider,
+            name: probe.name,
+            offset: file_offset(probe.pc)?,
+            semaphore_offset: if probe.semaphore != 0 {
+                Some(file_offset(probe.semaphore)?)
+            } else {
+                None
+            },
+            args: parse_args(&probe.args)?,
+        });
+    }
+
+    Ok(probes)
+}
+
+struct Note<'a> {
+    name: &'a [u8],
+    ty: u32,
+    desc: &'a [u8],
+}
+
+struct StapsdtNote {
+    pc: u64,
+    base: Option<u64>,
+    semaphore: u64,
+    provider: String,
+    name: String,
+    args: String,
+}
+
+struct NoteReader {
+    little_endian: bool,
+    is_64: bool,
+}
+
+impl NoteReader {
+    fn read_u32(&self, data: &[u8], offset: usize) -> Result<u32, UsdtError> {
+        let bytes = data
+            .get(offset..offset + 4)
+            .ok_or_else(|| UsdtError::InvalidNote("unexpected end of data".to_owned()))?;
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(bytes);
+        Ok(if self.little_endian {
+            u32::from_le_bytes(buf)
+        } else {
+            u32::from_be_bytes(buf)
+        })
+    }
+
+    fn read_addr(&self, data: &[u8], offset: usize) -> Result<u64, UsdtError> {
+        if !self.is_64 {
+            return self.read_u32(data, offset).map(u64::from);
+        }
+        let bytes = data
+            .get(offset..offset + 8)
+            .ok_or_else(|| UsdtError::InvalidNote("unexpected end of data".to_owned()))?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(bytes);
+        Ok(if self.little_endian {
+            u64::from_le_bytes(buf)
+        } else {
+            u64::from_be_bytes(buf)
+        })
+    }
+
+    fn notes<'a>(&self, data: &'a [u8]) -> Result<Vec<Note<'a>>, UsdtError> {
+        let align = |v: usize| (v + 3) & !3;
+        let mut notes = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let namesz = self.read_u32(data, offset)? as usize;
+            let descsz = self.read_u32(data, offset + 4)? as usize;
+            let ty = self.read_u32(data, offset + 8)?;
+            let name_start = offset + 12;
+            let desc_start = name_start + align(namesz);
+            let name = data
+                .get(name_start..name_start + namesz)
+                .ok_or_else(|| UsdtError::InvalidNote("invalid name size".to_owned()))?;
+            let desc = data
+                .get(desc_start..desc_start + descsz)
+                .ok_or_else(|| UsdtError::InvalidNote("invalid desc size".to_owned()))?;
+            notes.push(Note {
+                // strip the NULL terminator
+                name: name.strip_suffix(&[0]).unwrap_or(name),
+                ty,
+                desc,
+            });
+            offset = desc_start + align(descsz);
+        }
+
+        Ok(notes)
+    }
+
+    fn parse_stapsdt(&self, desc: &[u8]) -> Result<StapsdtNote, UsdtError> {
+        let addr_size = if self.is_64 { 8 } else { 4 };
+        let pc = self.read_addr(desc, 0)?;
+        let base = self.read_addr(desc, addr_size)?;
+        let semaphore = self.read_addr(desc, addr_size * 2)?;
+
+        let mut strings = desc[addr_size * 3..]
+            .split(|b| *b == 0)
+            .map(|s| String::from_utf8_lossy(s).into_owned());
+        let mut next = || {
+            strings
+                .next()
+                .ok_or_else(|| UsdtError::InvalidNote("missing probe strings".to_owned()))
+        };
+
+        Ok(StapsdtNote {
+            pc,
+            base: if base != 0 { Some(base) } else { None },
+            semaphore,
+            provider: next()?,
+            name: next()?,
+            args: next()?,
+        })
+    }
+}
+
+fn parse_args(args: &str) -> Result<Vec<UsdtArgument>, UsdtError> {
+    let mut ret = Vec::new();
+    let mut rest = args.trim();
+    while !rest.is_empty() {
+        // aarch64 memory operands contain spaces, eg `-4@[sp, 12]`
+        let end = match rest.find('[') {
+            Some(bracket) if rest.find(' ').map(|space| bracket < space).unwrap_or(false) => {
+                rest.find(']').map(|pos| pos + 1).unwrap_or(rest.len())
+            }
+            _ => rest.find(' ').unwrap_or(rest.len()),
+        };
+        ret.push(parse_arg(&rest[..end])?);
+        rest = rest[end..].trim_start();
+    }
+
+    Ok(ret)
+}
+
+fn parse_arg(arg: &str) -> Result<UsdtArgument, UsdtError> {
+    let invalid = || UsdtError::InvalidArgument {
+        arg: arg.to_owned(),
+    };
+    let parse_int = |s: &str| -> Result<i64, UsdtError> {
+        let (neg, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let v = match s.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => s.parse::<i64>(),
+        }
+        .map_err(|_| invalid())?;
+        Ok(if neg { -v } else { v })
+    };
+
+    let (size, spec) = arg.split_once('@').ok_or_else(invalid)?;
+    let size = parse_int(size)?;
+    let signed = size < 0;
+    let size = size.unsigned_abs();
+    if !matches!(size, 1 | 2 | 4 | 8) {
+        return Err(invalid());
+    }
+
+    let location = if let Some(imm) = spec.strip_prefix('$') {
+        // x86 immediate, eg `4@$5`
+        UsdtArgumentLocation::Constant(parse_int(imm)?)
+    } else if let Some(reg) = spec.strip_prefix('%') {
+        // x86 register, eg `8@%rax`
+        UsdtArgumentLocation::Register(reg.to_owned())
+    } else if let Some(mem) = spec.strip_suffix(')') {
+        // x86 memory, eg `-4@-4(%rbp)`. Indexed addressing isn't supported.
+        let (offset, reg) = mem.split_once("(%").ok_or_else(invalid)?;
+        if reg.contains(',') {
+            return Err(invalid());
+        }
+        UsdtArgumentLocation::Memory {
+            register: reg.to_owned(),
+            offset: if offset.is_empty() {
+                0
+            } else {
+                parse_int(offset)?
+            },
+        }
+    } else if let Some(mem) = spec.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        // aarch64 memory, eg `-4@[sp, 12]` or `8@[x0]`
+        let mut parts = mem.split(',').map(str::trim);
+        let register = parts.next().filter(|r| !r.is_empty()).ok_or_else(invalid)?;
+        let offset = match parts.next() {
+            Some(offset) => parse_int(offset)?,
+            None => 0,
+        };
+        UsdtArgumentLocation::Memory {
+            register: register.to_owned(),
+            offset,
+        }
+    } else if spec.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+        // aarch64 immediate, eg `4@5`
+        UsdtArgumentLocation::Constant(parse_int(spec)?)
+    } else if !spec.is_empty() {
+        // aarch64 register, eg `8@x0`
+        UsdtArgumentLocation::Register(spec.to_owned())
+    } else {
+        return Err(invalid());
+    };
+
+    Ok(UsdtArgument {
+        size: size as u8,
+        signed,
+        location,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args_x86_64() {
+        assert_eq!(
+            parse_args("-4@-4(%rbp) 8@%rax 4@$5 2@(%rdi)").unwrap(),
+            vec![
+                UsdtArgument {
+                    size: 4,
+                    signed: true,
+                    location: UsdtArgumentLocation::Memory {
+                        register: "rbp".to_owned(),
+                        offset: -4
+                    }
+                },
+                UsdtArgument {
+                    size: 8,
+                    signed: false,
+                    location: UsdtArgumentLocation::Register("rax".to_owned())
+                },
+                UsdtArgument {
+                    size: 4,
+                    signed: false,
+                    location: UsdtArgumentLocation::Constant(5)
+                },
+                UsdtArgument {
+                    size: 2,
+                    signed: false,
+                    location: UsdtArgumentLocation::Memory {
+                        register: "rdi".to_owned(),
+                        offset: 0
+                    }
+                },
+            ]
+        );
+        assert!(parse_args("8@-8(%rdx,%rcx,1)").is_err());
+        assert!(parse_args("3@%rax").is_err());
+    }
+
+    #[test]
+    fn test_parse_args_aarch64() {
+        assert_eq!(
+            parse_args("8@x0 -4@[sp, 12] 4@5 8@[x1]").unwrap(),
+            vec![
+                UsdtArgument {
+                    size: 8,
+                    signed: false,
+                    location: UsdtArgumentLocation::Register("x0".to_owned())
+                },
+                UsdtArgument {
+                    size: 4,
+                    signed: true,
+                    location: UsdtArgumentLocation::Memory {
+                        register: "sp".to_owned(),
+                        offset: 12
+                    }
+                },
+                UsdtArgument {
+                    size: 4,
+                    signed: false,
+                    location: UsdtArgumentLocation::Constant(5)
+                },
+                UsdtArgument {
+                    size: 8,
+                    signed: false,
+                    location: UsdtArgumentLocation::Memory {
+                        register: "x1".to_owned(),
+                        offset: 0
+                    }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_stapsdt_note() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        let desc_len = 3 * 8 + "python\0function__entry\08@%rdi\0".len();
+        data.extend_from_slice(&(desc_len as u32).to_le_bytes());
+        data.extend_from_slice(&STAPSDT_NOTE_TYPE.to_le_bytes());
+        data.extend_from_slice(b"stapsdt\0");
+        data.extend_from_slice(&0x1000u64.to_le_bytes());
+        data.extend_from_slice(&0x2000u64.to_le_bytes());
+        data.extend_from_slice(&0x3000u64.to_le_bytes());
+        data.extend_from_slice(b"python\0function__entry\08@%rdi\0");
+
+        let reader = NoteReader {
+            little_endian: true,
+            is_64: true,
+        };
+        let notes = reader.notes(&data).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].name, STAPSDT_NOTE_NAME);
+        assert_eq!(notes[0].ty, STAPSDT_NOTE_TYPE);
+
+        let note = reader.parse_stapsdt(notes[0].desc).unwrap();
+        assert_eq!(note.pc, 0x1000);
+        assert_eq!(note.base, Some(0x2000));
+        assert_eq!(note.semaphore, 0x3000);
+        assert_eq!(note.provider, "python");
+        assert_eq!(note.name, "function__entry");
+        assert_eq!(note.args, "8@%rdi");
+    }
+}
@@ -5,11 +5,20 @@ use thiserror::Error;
 
 use crate::{
     generated::{
-        bpf_attach_type::BPF_XDP, bpf_prog_type::BPF_PROG_TYPE_XDP, XDP_FLAGS_DRV_MODE,
-        XDP_FLAGS_HW_MODE, XDP_FLAGS_REPLACE, XDP_FLAGS_SKB_MODE, XDP_FLAGS_UPDATE_IF_NOEXIST,
+        bpf_attach_type::BPF_XDP, bpf_prog_type::BPF_PROG_TYPE_XDP, netdev_xdp_act,
+        BPF_F_XDP_HAS_FRAGS, XDP_FLAGS_DRV_MODE, XDP_FLAGS_HW_MODE, XDP_FLAGS_REPLACE,
+        XDP_FLAGS_SKB_MODE, XDP_FLAGS_UPDATE_IF_NOEXIST,
+    },
+    programs::{
+        load_program,
+        netns::{run_in, NetNs},
+        test_run, FdLink, Link, LinkRef, ProgramData, ProgramError, TestRunOptions, TestRunResult,
+        XdpRunConfig,
+    },
+    sys::{
+        bpf_link_create, kernel_version, netlink_set_xdp_fd, netlink_xdp_features,
+        netlink_xdp_query,
     },
-    programs::{load_program, FdLink, Link, LinkRef, ProgramData, ProgramError},
-    sys::{bpf_link_create, kernel_version, netlink_set_xdp_fd},
 };
 
 /// The type returned when attaching an [`Xdp`] program fails on kernels `< 5.9`.
@@ -39,6 +48,41 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// The XDP features supported by a network driver, returned by
+    /// [`Xdp::features()`].
+    #[derive(Default)]
+    pub struct XdpFeatures: u64 {
+        /// The driver can run XDP programs, supporting the `XDP_ABORTED`,
+        /// `XDP_DROP`, `XDP_PASS` and `XDP_TX` actions. Drivers without this
+        /// feature can only run programs in [`XdpFlags::SKB_MODE`].
+        const BASIC = netdev_xdp_act::NETDEV_XDP_ACT_BASIC as u64;
+        /// The driver supports the `XDP_REDIRECT` action.
+        const REDIRECT = netdev_xdp_act::NETDEV_XDP_ACT_REDIRECT as u64;
+        /// The device can be the target of `XDP_REDIRECT`.
+        const NDO_XMIT = netdev_xdp_act::NETDEV_XDP_ACT_NDO_XMIT as u64;
+        /// The driver supports AF_XDP sockets in zero-copy mode.
+        const XSK_ZEROCOPY = netdev_xdp_act::NETDEV_XDP_ACT_XSK_ZEROCOPY as u64;
+        /// The device can run programs in [`XdpFlags::HW_MODE`].
+        const HW_OFFLOAD = netdev_xdp_act::NETDEV_XDP_ACT_HW_OFFLOAD as u64;
+        /// The driver supports multi-buffer packets.
+        const RX_SG = netdev_xdp_act::NETDEV_XDP_ACT_RX_SG as u64;
+        /// The device can be the target of `XDP_REDIRECT` for multi-buffer
+        /// packets.
+        const NDO_XMIT_SG = netdev_xdp_act::NETDEV_XDP_ACT_NDO_XMIT_SG as u64;
+    }
+}
+
+/// An XDP program attached to an interface, returned by [`Xdp::query()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct XdpAttachment {
+    /// The id of the program.
+    pub program_id: u32,
+    /// The mode the program is attached in: [`XdpFlags::SKB_MODE`],
+    /// [`XdpFlags::DRV_MODE`] or [`XdpFlags::HW_MODE`].
+    pub mode: XdpFlags,
+}
+
 /// An XDP program.
 ///
 /// eXpress Data Path (XDP) programs can be attached to the very early stages of network
@@ -50,14 +94,35 @@ bitflags! {
 ///
 /// The minimum kernel version required to use this feature is 4.8.
 ///
+/// # Multi-buffer packets
+///
+/// Drivers configured with a large MTU, and interfaces receiving GRO packets,
+/// can split packets across several buffers. Such drivers only accept XDP
+/// programs that declare they can handle those fragments. Programs defined in
+/// `xdp.frags` sections are loaded with this capability, and
+/// [`Xdp::set_frags`] can be used to toggle it before loading. Multi-buffer
+/// support requires kernel 5.18 or newer.
+///
+/// # Hardware offload
+///
+/// Some smart NICs can run XDP programs themselves. Offloaded programs must
+/// be bound to their device when they're loaded, with
+/// [`Xdp::set_offload_interface`], and attached with [`XdpFlags::HW_MODE`].
+/// Use [`Xdp::features`] to find out which modes a driver supports.
+///
+/// # Sharing an interface
+///
+/// An interface runs a single XDP program. To run several programs on the
+/// same interface, attach them to an
+/// [`XdpDispatcher`](crate::programs::XdpDispatcher) instead.
+///
 /// # Examples
 ///
 /// ```no_run
 /// # let mut bpf = Bpf::load_file("ebpf_programs.o")?;
 /// use aya::{Bpf, programs::{Xdp, XdpFlags}};
-/// use std::convert::TryInto;
 ///
-/// let program: &mut Xdp = bpf.program_mut("intercept_packets")?.try_into()?;
+/// let program = bpf.program_mut_typed::<Xdp>("intercept_packets")?;
 /// program.attach("eth0", XdpFlags::default())?;
 /// # Ok::<(), aya::BpfError>(())
 /// ```
@@ -65,6 +130,8 @@ bitflags! {
 #[doc(alias = "BPF_PROG_TYPE_XDP")]
 pub struct Xdp {
     pub(crate) data: ProgramData,
+    pub(crate) frags: bool,
+    pub(crate) run_config: XdpRunConfig,
 }
 
 impl Xdp {
@@ -72,9 +139,147 @@ impl Xdp {
     ///
     /// See also [`Program::load`](crate::programs::Program::load).
     pub fn load(&mut self) -> Result<(), ProgramError> {
+        if self.frags {
+            self.data.flags |= BPF_F_XDP_HAS_FRAGS;
+        } else {
+            self.data.flags &= !BPF_F_XDP_HAS_FRAGS;
+        }
         load_program(BPF_PROG_TYPE_XDP, &mut self.data)
     }
 
+    /// Returns whether the program is loaded with multi-buffer support.
+    pub fn frags(&self) -> bool {
+        self.frags
+    }
+
+    /// Sets whether the program is loaded with multi-buffer support.
+    ///
+    /// This defaults to `true` for programs defined in `xdp.frags` sections,
+    /// and must be called before [`Xdp::load`].
+    pub fn set_frags(&mut self, frags: bool) {
+        self.frags = frags;
+    }
+
+    /// Sets the interface the program is offloaded to.
+    ///
+    /// Offloaded programs run on the network device instead of the host, and
+    /// can only be attached to `interface`, with [`XdpFlags::HW_MODE`]. This
+    /// must be called before [`Xdp::load`], and `None` loads the program for
+    /// the host again.
+    ///
+    /// The maps used by an offloaded program must be offloaded to the same
+    /// device, which is done with
+    /// [`BpfLoader::map_ifindex`](crate::BpfLoader::map_ifindex) when loading
+    /// the object.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let mut bpf = aya::Bpf::load(&[])?;
+    /// use aya::programs::{Xdp, XdpFeatures, XdpFlags};
+    ///
+    /// let program = bpf.program_mut_typed::<Xdp>("intercept_packets")?;
+    /// let features = Xdp::features("eth0")?;
+    /// let flags = if features.contains(XdpFeatures::HW_OFFLOAD) {
+    ///     program.set_offload_interface(Some("eth0"))?;
+    ///     XdpFlags::HW_MODE
+    /// } else if features.contains(XdpFeatures::BASIC) {
+    ///     XdpFlags::DRV_MODE
+    /// } else {
+    ///     XdpFlags::SKB_MODE
+    /// };
+    /// program.load()?;
+    /// program.attach("eth0", flags)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_offload_interface(&mut self, interface: Option<&str>) -> Result<(), ProgramError> {
+        self.data.ifindex = interface.map(if_index).transpose()?;
+        Ok(())
+    }
+
+    /// Returns the XDP features supported by the driver of `interface`.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 6.3.
+    ///
+    /// # Errors
+    ///
+    /// If the given `interface` does not exist
+    /// [`ProgramError::UnknownInterface`] is returned, and if querying the
+    /// features fails [`XdpError::NetlinkError`] is returned.
+    pub fn features(interface: &str) -> Result<XdpFeatures, ProgramError> {
+        let if_index = if_index(interface)?;
+        let features = unsafe { netlink_xdp_features(if_index as i32) }
+            .map_err(|io_error| XdpError::NetlinkError { io_error })?;
+        Ok(XdpFeatures::from_bits_truncate(features))
+    }
+
+    /// Returns the XDP programs attached to `interface`.
+    ///
+    /// An interface can run one program in each mode, so up to three programs
+    /// are returned, and none when no program is attached. This can be used to
+    /// detect programs attached by other applications before replacing them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use aya::programs::{Program, Xdp};
+    ///
+    /// for attachment in Xdp::query("eth0")? {
+    ///     let program = Program::from_id(attachment.program_id)?;
+    ///     println!("{} is attached in {:?}", program.name(), attachment.mode);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the given `interface` does not exist
+    /// [`ProgramError::UnknownInterface`] is returned, and if the query
+    /// fails [`XdpError::NetlinkError`] is returned.
+    pub fn query(interface: &str) -> Result<Vec<XdpAttachment>, ProgramError> {
+        let if_index = if_index(interface)?;
+        let programs = unsafe { netlink_xdp_query(if_index as i32) }
+            .map_err(|io_error| XdpError::NetlinkError { io_error })?;
+        Ok(programs
+            .into_iter()
+            .map(|(program_id, mode)| XdpAttachment {
+                program_id,
+                mode: XdpFlags::from_bits_truncate(mode),
+            })
+            .collect())
+    }
+
+    /// Returns how the program is run when it's attached to an
+    /// [`XdpDispatcher`](crate::programs::XdpDispatcher).
+    ///
+    /// This defaults to the config declared with libxdp's `XDP_RUN_CONFIG()`
+    /// macro, or to [`XdpRunConfig::default`].
+    pub fn run_config(&self) -> XdpRunConfig {
+        self.run_config
+    }
+
+    /// Sets how the program is run when it's attached to an
+    /// [`XdpDispatcher`](crate::programs::XdpDispatcher).
+    pub fn set_run_config(&mut self, config: XdpRunConfig) {
+        self.run_config = config;
+    }
+
+    /// Runs the program in the kernel with `packet` as input, without
+    /// attaching it.
+    ///
+    /// The packet modified by the program is written to `packet_out`, see
+    /// [`TestRunResult::data_size_out`] for its size.
+    pub fn test_run(
+        &self,
+        packet: &[u8],
+        packet_out: &mut [u8],
+        options: &TestRunOptions,
+    ) -> Result<TestRunResult, ProgramError> {
+        test_run(&self.data, packet, packet_out, options)
+    }
+
     /// Returns the name of the program.
     pub fn name(&self) -> String {
         self.data.name.to_string()
@@ -82,6 +287,9 @@ impl Xdp {
 
     /// Attaches the program to the given `interface`.
     ///
+    /// To attach to an interface of another network namespace, call this
+    /// method from [`NetNs::run`](crate::programs::NetNs::run).
+    ///
     /// # Errors
     ///
     /// If the given `interface` does not exist
@@ -93,14 +301,7 @@ impl Xdp {
     /// kernels.
     pub fn attach(&mut self, interface: &str, flags: XdpFlags) -> Result<LinkRef, ProgramError> {
         let prog_fd = self.data.fd_or_err()?;
-
-        let c_interface = CString::new(interface).unwrap();
-        let if_index = unsafe { if_nametoindex(c_interface.as_ptr()) } as RawFd;
-        if if_index == 0 {
-            return Err(ProgramError::UnknownInterface {
-                name: interface.to_string(),
-            });
-        }
+        let if_index = if_index(interface)? as RawFd;
 
         let k_ver = kernel_version().unwrap();
         if k_ver >= (5, 9, 0) {
@@ -121,16 +322,29 @@ impl Xdp {
                 if_index,
                 prog_fd: Some(prog_fd),
                 flags,
+                netns: NetNs::current(),
             })))
         }
     }
 }
 
+fn if_index(interface: &str) -> Result<u32, ProgramError> {
+    let c_interface = CString::new(interface).unwrap();
+    let if_index = unsafe { if_nametoindex(c_interface.as_ptr()) };
+    if if_index == 0 {
+        return Err(ProgramError::UnknownInterface {
+            name: interface.to_string(),
+        });
+    }
+    Ok(if_index)
+}
+
 #[derive(Debug)]
 struct NlLink {
     if_index: i32,
     prog_fd: Option<RawFd>,
     flags: XdpFlags,
+    netns: Option<NetNs>,
 }
 
 impl Link for NlLink {
@@ -142,12 +356,22 @@ impl Link for NlLink {
             } else {
                 self.flags.bits
             };
-            let _ = unsafe { netlink_set_xdp_fd(self.if_index, -1, Some(fd), flags) };
+            let if_index = self.if_index;
+            let _ = run_in(self.netns.as_ref(), || unsafe {
+                netlink_set_xdp_fd(if_index, -1, Some(fd), flags)
+            });
             Ok(())
         } else {
             Err(ProgramError::AlreadyDetached)
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        self.prog_fd
+            .take()
+            .map(|_| None)
+            .ok_or(ProgramError::AlreadyDetached)
+    }
 }
 
 impl Drop for NlLink {
@@ -169,4 +393,11 @@ impl Link for XdpLink {
             XdpLink::NlLink(link) => link.detach(),
         }
     }
+
+    fn forget(&mut self) -> Result<Option<RawFd>, ProgramError> {
+        match self {
+            XdpLink::FdLink(link) => link.forget(),
+            XdpLink::NlLink(link) => link.forget(),
+        }
+    }
 }
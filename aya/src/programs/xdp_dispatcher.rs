@@ -0,0 +1,968 @@
+//! Running several XDP programs on the same interface.
+use libc::{flock, if_nametoindex, ENOENT, LOCK_EX};
+use std::{
+    ffi::CString,
+    fs::{self, File},
+    io, mem,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    bpf::load_btf,
+    bpf_map_def,
+    generated::{
+        bpf_func_info, bpf_insn,
+        bpf_map_type::BPF_MAP_TYPE_ARRAY,
+        bpf_prog_info,
+        bpf_prog_type::{BPF_PROG_TYPE_EXT, BPF_PROG_TYPE_XDP},
+        btf_param, BPF_ALU, BPF_ALU64, BPF_AND, BPF_CALL, BPF_EXIT, BPF_F_RDONLY_PROG,
+        BPF_F_XDP_HAS_FRAGS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LSH, BPF_MOV, BPF_PSEUDO_CALL, BPF_X,
+        BTF_INT_SIGNED, XDP_FLAGS_REPLACE,
+    },
+    obj::btf::{Btf, BtfError, BtfType, BTF_FUNC_GLOBAL},
+    programs::{load_program, ProgramError, ProgramFd, VerifierLog, Xdp, XdpError, XdpFlags},
+    sys::{
+        bpf_create_map, bpf_get_object, bpf_link_create_freplace, bpf_load_program, bpf_map_freeze,
+        bpf_map_get_fd_by_id, bpf_map_get_info_by_fd, bpf_map_lookup_elem, bpf_map_update_elem,
+        bpf_obj_get_info_by_fd, bpf_pin_object, bpf_prog_bind_map, bpf_prog_get_fd_by_id,
+        bpf_prog_get_info_by_fd, netlink_set_xdp_fd, netlink_xdp_query, BpfLoadProgramAttrs,
+    },
+    PinningType, Pod,
+};
+
+// The conventions below are the ones of libxdp, so that the programs attached
+// by aya and by libxdp based tools like xdp-loader can share an interface.
+
+/// The directory of the pinned dispatchers, also locked while they're updated.
+const DISPATCHER_DIR: &str = "/sys/fs/bpf/xdp";
+const DISPATCHER_NAME: &str = "xdp_dispatcher";
+const DISPATCHER_MAP_NAME: &str = "xdp_disp.rodata";
+const DISPATCHER_MAGIC: u8 = 236;
+const DISPATCHER_VERSION: u8 = 2;
+// the value returned by the slots that aren't replaced
+const DISPATCHER_RETVAL: u32 = 31;
+// the type ids of the dispatcher BTF, see dispatcher_btf()
+const DISPATCHER_FUNC_TYPE_ID: u32 = 5;
+const FIRST_SLOT_TYPE_ID: u32 = 6;
+
+/// The maximum number of programs an [`XdpDispatcher`] can run.
+pub const MAX_DISPATCHER_PROGRAMS: usize = 10;
+
+/// The default priority of the programs attached to an [`XdpDispatcher`].
+pub const DEFAULT_RUN_PRIORITY: u32 = 50;
+
+bitflags! {
+    /// XDP actions, as returned by XDP programs.
+    pub struct XdpActions: u32 {
+        /// `XDP_ABORTED`.
+        const ABORTED = 1 << 0;
+        /// `XDP_DROP`.
+        const DROP = 1 << 1;
+        /// `XDP_PASS`.
+        const PASS = 1 << 2;
+        /// `XDP_TX`.
+        const TX = 1 << 3;
+        /// `XDP_REDIRECT`.
+        const REDIRECT = 1 << 4;
+    }
+}
+
+/// How a program attached to an [`XdpDispatcher`] is run.
+///
+/// Objects built for libxdp declare the run config of their programs with the
+/// `XDP_RUN_CONFIG()` macro, which is used as the default config of the
+/// programs. See [`Xdp::set_run_config`] to change it before attaching.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct XdpRunConfig {
+    /// The priority of the program. Programs with a lower priority run first,
+    /// programs with the same priority in the order they were attached.
+    pub priority: u32,
+    /// The actions after which the next program runs. When the program
+    /// returns another action, the packet isn't passed to the next programs.
+    pub chain_call_actions: XdpActions,
+}
+
+impl Default for XdpRunConfig {
+    fn default() -> XdpRunConfig {
+        XdpRunConfig {
+            priority: DEFAULT_RUN_PRIORITY,
+            chain_call_actions: XdpActions::PASS,
+        }
+    }
+}
+
+/// Error type returned by [`XdpDispatcher`].
+#[derive(Debug, Error)]
+pub enum XdpDispatcherError {
+    /// The dispatcher already runs [`MAX_DISPATCHER_PROGRAMS`] programs.
+    #[error("the dispatcher already runs {MAX_DISPATCHER_PROGRAMS} programs")]
+    TooManyPrograms,
+
+    /// The program isn't attached to the dispatcher.
+    #[error("program {id} is not attached to the dispatcher")]
+    UnknownProgram {
+        /// The id of the program.
+        id: u32,
+    },
+
+    /// The dispatcher attached to the interface was created by a version of
+    /// libxdp that isn't supported.
+    #[error("the dispatcher {id} has an unsupported format")]
+    UnsupportedDispatcher {
+        /// The id of the dispatcher program.
+        id: u32,
+    },
+
+    /// Loading or attaching a program failed.
+    #[error(transparent)]
+    ProgramError(#[from] ProgramError),
+
+    /// Creating the BTF of the dispatcher failed.
+    #[error(transparent)]
+    BtfError(#[from] BtfError),
+
+    /// Accessing the pins of the dispatcher failed.
+    #[error("error accessing `{}`", path.display())]
+    IOError {
+        /// The path being accessed.
+        path: PathBuf,
+        /// The error.
+        #[source]
+        io_error: io::Error,
+    },
+}
+
+/// A program attached to an [`XdpDispatcher`].
+#[derive(Debug)]
+pub struct XdpDispatcherProgram {
+    fd: OwnedFd,
+    id: u32,
+    name: String,
+    config: XdpRunConfig,
+    frags: bool,
+}
+
+impl XdpDispatcherProgram {
+    /// Returns the id of the program.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the kernel name of the program.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the run config of the program.
+    pub fn run_config(&self) -> XdpRunConfig {
+        self.config
+    }
+
+    /// Returns whether the program supports multi-buffer packets.
+    pub fn frags(&self) -> bool {
+        self.frags
+    }
+}
+
+#[derive(Debug)]
+struct AttachedDispatcher {
+    fd: OwnedFd,
+    path: PathBuf,
+}
+
+/// Runs several XDP programs on the same interface.
+///
+/// An interface runs a single XDP program. The dispatcher is a generated XDP
+/// program calling the attached programs in turn, sorted by priority, which
+/// replace its functions as [`Extension`](crate::programs::Extension)s. When
+/// a program returns one of its chain call actions, the next program runs,
+/// otherwise its action is the action of the dispatcher. The dispatcher is
+/// compatible with libxdp: programs attached by aya and by libxdp based tools,
+/// like `xdp-loader`, can share an interface.
+///
+/// Attaching or detaching a program creates a new dispatcher, which atomically
+/// replaces the previous one. The dispatcher and its programs are pinned
+/// under `/sys/fs/bpf/xdp`, so they stay attached when the process exits
+/// until they're detached with [`XdpDispatcher::detach`]. An
+/// [`XdpDispatcher`] holds the lock libxdp uses to serialize the changes
+/// while it's alive.
+///
+/// The programs must be loaded with BTF, and aren't loaded before they're
+/// attached: [`XdpDispatcher::attach`] loads them as extensions of the
+/// dispatcher.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 5.10.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let mut bpf = aya::Bpf::load_file("ebpf_programs.o")?;
+/// use aya::programs::{Xdp, XdpActions, XdpDispatcher, XdpFlags, XdpRunConfig};
+///
+/// let mut dispatcher = XdpDispatcher::open("eth0", XdpFlags::default())?;
+/// let program = bpf.program_mut_typed::<Xdp>("firewall")?;
+/// program.set_run_config(XdpRunConfig {
+///     priority: 10,
+///     chain_call_actions: XdpActions::PASS,
+/// });
+/// let id = dispatcher.attach(program)?;
+///
+/// for program in dispatcher.programs() {
+///     println!("{} {} {:?}", program.id(), program.name(), program.run_config());
+/// }
+///
+/// dispatcher.detach(id)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct XdpDispatcher {
+    if_index: u32,
+    flags: XdpFlags,
+    // held for the lifetime of the dispatcher
+    _lock: File,
+    dispatcher: Option<AttachedDispatcher>,
+    programs: Vec<XdpDispatcherProgram>,
+}
+
+impl XdpDispatcher {
+    /// Opens the dispatcher of `interface`.
+    ///
+    /// The programs already attached to the dispatcher of the interface, by
+    /// aya or libxdp, are kept when the dispatcher is updated. `flags` is the
+    /// mode the dispatcher is attached in, and must match the mode of the
+    /// dispatcher already attached to the interface. This blocks until the
+    /// other processes updating a dispatcher are done.
+    pub fn open(interface: &str, flags: XdpFlags) -> Result<XdpDispatcher, XdpDispatcherError> {
+        let c_interface = CString::new(interface).unwrap();
+        let if_index = unsafe { if_nametoindex(c_interface.as_ptr()) };
+        if if_index == 0 {
+            return Err(ProgramError::UnknownInterface {
+                name: interface.to_string(),
+            }
+            .into());
+        }
+
+        let dir = Path::new(DISPATCHER_DIR);
+        fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+        let lock = File::open(dir).map_err(|e| io_error(dir, e))?;
+        if unsafe { flock(lock.as_raw_fd(), LOCK_EX) } != 0 {
+            return Err(io_error(dir, io::Error::last_os_error()));
+        }
+
+        let mut dispatcher = XdpDispatcher {
+            if_index,
+            flags,
+            _lock: lock,
+            dispatcher: None,
+            programs: Vec::new(),
+        };
+        dispatcher.open_attached()?;
+        Ok(dispatcher)
+    }
+
+    /// Returns the programs attached to the dispatcher, in the order they run.
+    pub fn programs(&self) -> &[XdpDispatcherProgram] {
+        &self.programs
+    }
+
+    /// Loads `program` and attaches it to the dispatcher, with the config
+    /// returned by [`Xdp::run_config`].
+    ///
+    /// Returns the id of the program, used to detach it.
+    ///
+    /// # Errors
+    ///
+    /// [`ProgramError::AlreadyLoaded`] is returned if `program` is already
+    /// loaded.
+    pub fn attach(&mut self, program: &mut Xdp) -> Result<u32, XdpDispatcherError> {
+        if self.programs.len() == MAX_DISPATCHER_PROGRAMS {
+            return Err(XdpDispatcherError::TooManyPrograms);
+        }
+        if program.data.fd.is_some() {
+            return Err(ProgramError::AlreadyLoaded.into());
+        }
+
+        let config = program.run_config;
+        let index = slot_index(
+            self.programs.iter().map(|p| p.config.priority),
+            config.priority,
+        );
+        let mut slots = self.slots();
+        slots.insert(index, (config, program.frags));
+        let dispatcher = load_dispatcher(&slots)?;
+
+        program.data.attach_prog_fd = Some(dispatcher.as_raw_fd());
+        program.data.attach_btf_id = Some(FIRST_SLOT_TYPE_ID + index as u32);
+        if program.frags {
+            program.data.flags |= BPF_F_XDP_HAS_FRAGS;
+        }
+        let loaded = load_program(BPF_PROG_TYPE_EXT, &mut program.data);
+        // the dispatcher is closed when this returns
+        program.data.attach_prog_fd = None;
+        loaded?;
+
+        let fd = program.try_clone_fd()?;
+        let info = prog_info(fd.as_raw_fd())?;
+        self.programs.insert(
+            index,
+            XdpDispatcherProgram {
+                fd,
+                id: info.id,
+                name: prog_name(&info),
+                config,
+                frags: program.frags,
+            },
+        );
+        let attached = match self.attach_dispatcher(Some(dispatcher)) {
+            Ok(attached) => attached,
+            Err(e) => {
+                self.programs.remove(index);
+                return Err(e);
+            }
+        };
+        self.release(attached)?;
+        Ok(info.id)
+    }
+
+    /// Detaches the program `id` from the dispatcher.
+    ///
+    /// The dispatcher is detached from the interface once it doesn't run any
+    /// program.
+    pub fn detach(&mut self, id: u32) -> Result<(), XdpDispatcherError> {
+        let index = self
+            .programs
+            .iter()
+            .position(|p| p.id == id)
+            .ok_or(XdpDispatcherError::UnknownProgram { id })?;
+        let program = self.programs.remove(index);
+
+        let attached = if self.programs.is_empty() {
+            self.attach_dispatcher(None)
+        } else {
+            load_dispatcher(&self.slots()).and_then(|fd| self.attach_dispatcher(Some(fd)))
+        };
+        let attached = match attached {
+            Ok(attached) => attached,
+            Err(e) => {
+                self.programs.insert(index, program);
+                return Err(e);
+            }
+        };
+        self.release(attached)
+    }
+
+    fn slots(&self) -> Vec<(XdpRunConfig, bool)> {
+        self.programs.iter().map(|p| (p.config, p.frags)).collect()
+    }
+
+    // reads the dispatcher attached to the interface, if any
+    fn open_attached(&mut self) -> Result<(), XdpDispatcherError> {
+        let attached_id = self.attached_prog_id()?;
+        let dir = Path::new(DISPATCHER_DIR);
+        let prefix = format!("dispatch-{}-", self.if_index);
+        for entry in fs::read_dir(dir).map_err(|e| io_error(dir, e))? {
+            let path = entry.map_err(|e| io_error(dir, e))?.path();
+            let id = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|id| id.parse::<u32>().ok())
+            {
+                Some(id) => id,
+                None => continue,
+            };
+            // like libxdp, only the pins of the program attached to the
+            // interface are used
+            if Some(id) != attached_id {
+                if let Err(e) = bpf_prog_get_fd_by_id(id) {
+                    // the dispatcher was detached by a process that exited
+                    // before removing its pins
+                    if e.raw_os_error() == Some(ENOENT) {
+                        remove_pins(&path)?;
+                    }
+                }
+                continue;
+            }
+            let fd = bpf_prog_get_fd_by_id(id)
+                .map_err(|io_error| syscall_error("bpf_prog_get_fd_by_id", io_error))?;
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+            let config = read_config(id, fd.as_raw_fd())?;
+            let mut programs = Vec::new();
+            for i in 0..config.num_progs_enabled as usize {
+                let prog_path = path.join(format!("prog{}-prog", i));
+                let c_path = CString::new(prog_path.to_string_lossy().as_bytes()).unwrap();
+                let prog_fd =
+                    bpf_get_object(&c_path).map_err(|(_, e)| io_error(&prog_path, e))? as RawFd;
+                let prog_fd = unsafe { OwnedFd::from_raw_fd(prog_fd) };
+                let info = prog_info(prog_fd.as_raw_fd())?;
+                programs.push(XdpDispatcherProgram {
+                    fd: prog_fd,
+                    id: info.id,
+                    name: prog_name(&info),
+                    config: XdpRunConfig {
+                        priority: config.run_prios[i],
+                        chain_call_actions: XdpActions::from_bits_truncate(
+                            config.chain_call_actions[i],
+                        ),
+                    },
+                    frags: config.program_flags[i] & BPF_F_XDP_HAS_FRAGS != 0,
+                });
+            }
+
+            self.dispatcher = Some(AttachedDispatcher { fd, path });
+            self.programs = programs;
+            break;
+        }
+
+        Ok(())
+    }
+
+    // returns the id of the program netlink reports as attached to the
+    // interface in the mode of the dispatcher
+    fn attached_prog_id(&self) -> Result<Option<u32>, XdpDispatcherError> {
+        let programs = unsafe { netlink_xdp_query(self.if_index as i32) }
+            .map_err(|io_error| ProgramError::from(XdpError::NetlinkError { io_error }))?;
+        let mode = self.flags & (XdpFlags::SKB_MODE | XdpFlags::DRV_MODE | XdpFlags::HW_MODE);
+        Ok(programs
+            .into_iter()
+            .find(|(_, attached_mode)| mode.is_empty() || mode.bits() == *attached_mode)
+            .map(|(id, _)| id))
+    }
+
+    // attaches `dispatcher` running the programs in place of the current
+    // dispatcher, or detaches the current dispatcher if `dispatcher` is None.
+    //
+    // The programs and their links are pinned before the dispatcher is
+    // attached, and the pins are removed if attaching it fails, so that the
+    // interface and the pins are left as they were on error.
+    fn attach_dispatcher(
+        &self,
+        dispatcher: Option<OwnedFd>,
+    ) -> Result<Option<AttachedDispatcher>, XdpDispatcherError> {
+        let old_fd = self.dispatcher.as_ref().map(|d| d.fd.as_raw_fd());
+        let mut flags = self.flags.bits();
+        if old_fd.is_some() {
+            flags |= XDP_FLAGS_REPLACE;
+        }
+
+        let dispatcher = match dispatcher {
+            Some(dispatcher) => dispatcher,
+            None => {
+                if let Some(old_fd) = old_fd {
+                    unsafe { netlink_set_xdp_fd(self.if_index as i32, -1, Some(old_fd), flags) }
+                        .map_err(|io_error| {
+                            ProgramError::from(XdpError::NetlinkError { io_error })
+                        })?;
+                }
+                return Ok(None);
+            }
+        };
+
+        let mut links = Vec::new();
+        for (i, program) in self.programs.iter().enumerate() {
+            let link_fd = bpf_link_create_freplace(
+                program.fd.as_raw_fd(),
+                dispatcher.as_raw_fd(),
+                FIRST_SLOT_TYPE_ID + i as u32,
+            )
+            .map_err(|(_, io_error)| ProgramError::SyscallError {
+                call: "bpf_link_create".to_owned(),
+                io_error,
+            })? as RawFd;
+            links.push(unsafe { OwnedFd::from_raw_fd(link_fd) });
+        }
+
+        let id = prog_info(dispatcher.as_raw_fd())?.id;
+        let path = Path::new(DISPATCHER_DIR).join(format!("dispatch-{}-{}", self.if_index, id));
+        fs::create_dir(&path).map_err(|e| io_error(&path, e))?;
+        let attached = self
+            .programs
+            .iter()
+            .zip(&links)
+            .enumerate()
+            .try_for_each(|(i, (program, link))| {
+                pin(link.as_raw_fd(), &path.join(format!("prog{}-link", i)))?;
+                pin(
+                    program.fd.as_raw_fd(),
+                    &path.join(format!("prog{}-prog", i)),
+                )
+            })
+            .and_then(|_| {
+                unsafe {
+                    netlink_set_xdp_fd(self.if_index as i32, dispatcher.as_raw_fd(), old_fd, flags)
+                }
+                .map_err(|io_error| ProgramError::from(XdpError::NetlinkError { io_error }).into())
+            });
+        if let Err(e) = attached {
+            // unpinning the links releases the new dispatcher, the error
+            // returned is the one that caused the unwinding
+            let _ = remove_pins(&path);
+            return Err(e);
+        }
+
+        Ok(Some(AttachedDispatcher {
+            fd: dispatcher,
+            path,
+        }))
+    }
+
+    // makes `attached` the current dispatcher, and releases the previous one
+    fn release(&mut self, attached: Option<AttachedDispatcher>) -> Result<(), XdpDispatcherError> {
+        // unpinning the links of the previous dispatcher releases it
+        match mem::replace(&mut self.dispatcher, attached) {
+            Some(old) => remove_pins(&old.path),
+            None => Ok(()),
+        }
+    }
+}
+
+// the index of the slot of a program with `priority`, after the programs with
+// the same priority
+fn slot_index(priorities: impl Iterator<Item = u32>, priority: u32) -> usize {
+    let mut index = 0;
+    for p in priorities {
+        if p > priority {
+            break;
+        }
+        index += 1;
+    }
+    index
+}
+
+/// The configuration of a dispatcher, stored in its `.rodata` map.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct DispatcherConfig {
+    magic: u8,
+    dispatcher_version: u8,
+    num_progs_enabled: u8,
+    is_xdp_frags: u8,
+    chain_call_actions: [u32; MAX_DISPATCHER_PROGRAMS],
+    run_prios: [u32; MAX_DISPATCHER_PROGRAMS],
+    program_flags: [u32; MAX_DISPATCHER_PROGRAMS],
+}
+
+unsafe impl Pod for DispatcherConfig {}
+
+impl DispatcherConfig {
+    fn new(slots: &[(XdpRunConfig, bool)]) -> DispatcherConfig {
+        let mut config = DispatcherConfig {
+            magic: DISPATCHER_MAGIC,
+            dispatcher_version: DISPATCHER_VERSION,
+            num_progs_enabled: slots.len() as u8,
+            // the dispatcher only supports multi-buffer packets if all its
+            // programs do
+            is_xdp_frags: slots.iter().all(|(_, frags)| *frags) as u8,
+            chain_call_actions: [0; MAX_DISPATCHER_PROGRAMS],
+            run_prios: [0; MAX_DISPATCHER_PROGRAMS],
+            program_flags: [0; MAX_DISPATCHER_PROGRAMS],
+        };
+        for (i, (run_config, frags)) in slots.iter().enumerate() {
+            // the slots that aren't replaced always chain call
+            config.chain_call_actions[i] =
+                run_config.chain_call_actions.bits() | 1 << DISPATCHER_RETVAL;
+            config.run_prios[i] = run_config.priority;
+            config.program_flags[i] = if *frags { BPF_F_XDP_HAS_FRAGS } else { 0 };
+        }
+        config
+    }
+
+    // whether the config was written by a dispatcher version we can update
+    fn is_supported(&self) -> bool {
+        self.magic == DISPATCHER_MAGIC
+            && self.dispatcher_version == DISPATCHER_VERSION
+            && self.num_progs_enabled as usize <= MAX_DISPATCHER_PROGRAMS
+    }
+}
+
+fn read_config(id: u32, dispatcher_fd: RawFd) -> Result<DispatcherConfig, XdpDispatcherError> {
+    let unsupported = XdpDispatcherError::UnsupportedDispatcher { id };
+
+    let mut map_ids = [0u32; 1];
+    let mut info = unsafe { mem::zeroed::<bpf_prog_info>() };
+    info.nr_map_ids = map_ids.len() as u32;
+    info.map_ids = map_ids.as_mut_ptr() as u64;
+    bpf_prog_get_info_by_fd(dispatcher_fd, &mut info)
+        .map_err(|e| syscall_error("bpf_prog_get_info_by_fd", e))?;
+    if info.nr_map_ids != 1 {
+        return Err(unsupported);
+    }
+
+    let map_fd =
+        bpf_map_get_fd_by_id(map_ids[0]).map_err(|e| syscall_error("bpf_map_get_fd_by_id", e))?;
+    let map_fd = unsafe { OwnedFd::from_raw_fd(map_fd) };
+    let map_info = bpf_map_get_info_by_fd(map_fd.as_raw_fd())
+        .map_err(|e| syscall_error("bpf_map_get_info_by_fd", e))?;
+    if map_info.value_size as usize != mem::size_of::<DispatcherConfig>() {
+        return Err(unsupported);
+    }
+
+    match bpf_map_lookup_elem::<u32, DispatcherConfig>(map_fd.as_raw_fd(), &0, 0) {
+        Ok(Some(config)) if config.is_supported() => Ok(config),
+        Ok(_) => Err(unsupported),
+        Err((_, io_error)) => Err(syscall_error("bpf_map_lookup_elem", io_error).into()),
+    }
+}
+
+// loads a dispatcher running the programs of `slots`
+fn load_dispatcher(slots: &[(XdpRunConfig, bool)]) -> Result<OwnedFd, XdpDispatcherError> {
+    let config = DispatcherConfig::new(slots);
+
+    let def = bpf_map_def {
+        map_type: BPF_MAP_TYPE_ARRAY as u32,
+        key_size: mem::size_of::<u32>() as u32,
+        value_size: mem::size_of::<DispatcherConfig>() as u32,
+        max_entries: 1,
+        map_flags: BPF_F_RDONLY_PROG,
+        id: 0,
+        pinning: PinningType::None,
+    };
+    let name = CString::new(DISPATCHER_MAP_NAME).unwrap();
+    let map_fd = bpf_create_map(&name, &def, None, None, None, None, None)
+        .map_err(|(_, io_error)| syscall_error("bpf_map_create", io_error))?
+        as RawFd;
+    let map_fd = unsafe { OwnedFd::from_raw_fd(map_fd) };
+    bpf_map_update_elem(map_fd.as_raw_fd(), &0u32, &config, 0)
+        .map_err(|(_, io_error)| syscall_error("bpf_map_update_elem", io_error))?;
+    bpf_map_freeze(map_fd.as_raw_fd())
+        .map_err(|(_, io_error)| syscall_error("bpf_map_freeze", io_error))?;
+
+    let btf_fd = load_btf(&dispatcher_btf(), None)?;
+    let btf_fd = unsafe { OwnedFd::from_raw_fd(btf_fd) };
+
+    let insns = dispatcher_instructions(&config);
+    let func_info = (0..=MAX_DISPATCHER_PROGRAMS)
+        .map(|i| bpf_func_info {
+            insn_off: if i == 0 {
+                0
+            } else {
+                slot_offset(config.num_progs_enabled as usize, i - 1) as u32
+            },
+            type_id: DISPATCHER_FUNC_TYPE_ID + i as u32,
+        })
+        .collect::<Vec<_>>();
+    let license = CString::new("GPL").unwrap();
+    let load = |log: &mut VerifierLog| {
+        bpf_load_program(BpfLoadProgramAttrs {
+            name: Some(DISPATCHER_NAME),
+            ty: BPF_PROG_TYPE_XDP,
+            insns: &insns,
+            license: &license,
+            kernel_version: 0,
+            expected_attach_type: None,
+            flags: if config.is_xdp_frags != 0 {
+                BPF_F_XDP_HAS_FRAGS
+            } else {
+                0
+            },
+            attach_btf_obj_fd: None,
+            attach_btf_id: None,
+            attach_prog_fd: None,
+            prog_btf_fd: Some(btf_fd.as_raw_fd()),
+            token_fd: None,
+            prog_ifindex: None,
+            func_info: &func_info,
+            line_info: &[],
+            log,
+        })
+    };
+    let mut log = VerifierLog::new();
+    let prog_fd = match load(&mut log) {
+        Ok(fd) => fd,
+        // retry with a log buffer to report why the verifier rejected it
+        Err(_) => {
+            log.grow();
+            load(&mut log).map_err(|(_, io_error)| {
+                log.truncate();
+                ProgramError::LoadError {
+                    io_error,
+                    verifier_log: log
+                        .as_c_str()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "[none]".to_owned()),
+                }
+            })?
+        }
+    } as RawFd;
+    let prog_fd = unsafe { OwnedFd::from_raw_fd(prog_fd) };
+
+    // libxdp reads the config of the dispatchers from their only map
+    bpf_prog_bind_map(prog_fd.as_raw_fd(), map_fd.as_raw_fd())
+        .map_err(|(_, io_error)| syscall_error("bpf_prog_bind_map", io_error))?;
+
+    Ok(prog_fd)
+}
+
+// the BTF of the dispatcher, where all the functions have the prototype
+// `int (struct xdp_md *ctx)`, which is required to replace them
+fn dispatcher_btf() -> Btf {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    // the members aren't needed to match the context type
+    let name_offset = btf.add_string("xdp_md");
+    let ctx_type_id = btf.add_type(BtfType::new_struct(name_offset, Vec::new(), 24));
+    let ptr_type_id = btf.add_type(BtfType::new_ptr(ctx_type_id));
+    let name_offset = btf.add_string("ctx");
+    let proto_type_id = btf.add_type(BtfType::new_func_proto(
+        vec![btf_param {
+            name_off: name_offset,
+            type_: ptr_type_id,
+        }],
+        int_type_id,
+    ));
+
+    let names = std::iter::once(DISPATCHER_NAME.to_owned())
+        .chain((0..MAX_DISPATCHER_PROGRAMS).map(|i| format!("prog{}", i)));
+    for name in names {
+        let name_offset = btf.add_string(&name);
+        btf.add_type(BtfType::new_func(
+            name_offset,
+            proto_type_id,
+            BTF_FUNC_GLOBAL,
+        ));
+    }
+
+    btf
+}
+
+// the number of instructions of the main function calling each slot
+fn main_len(n_slots: usize) -> usize {
+    1 + 6 * n_slots + 2
+}
+
+fn slot_offset(n_slots: usize, slot: usize) -> usize {
+    main_len(n_slots) + 2 * slot
+}
+
+// The instructions of the dispatcher, equivalent to:
+//
+// int xdp_dispatcher(struct xdp_md *ctx)
+// {
+//         int ret = prog0(ctx);
+//         if (!((1U << ret) & conf.chain_call_actions[0]))
+//                 return ret;
+//         ...
+//         return XDP_PASS;
+// }
+//
+// followed by the slot functions prog0 to prog9, which return
+// DISPATCHER_RETVAL until they're replaced.
+fn dispatcher_instructions(config: &DispatcherConfig) -> Vec<bpf_insn> {
+    let n_slots = config.num_progs_enabled as usize;
+    let exit_index = main_len(n_slots) - 1;
+
+    // r6 = ctx
+    let mut insns = vec![insn(BPF_ALU64 | BPF_MOV | BPF_X, 6, 1, 0, 0)];
+    for slot in 0..n_slots {
+        let call_index = insns.len() + 1;
+        let jump_index = call_index + 4;
+        insns.extend_from_slice(&[
+            // r1 = ctx
+            insn(BPF_ALU64 | BPF_MOV | BPF_X, 1, 6, 0, 0),
+            // r0 = prog<slot>(ctx)
+            insn(
+                BPF_JMP | BPF_CALL,
+                0,
+                BPF_PSEUDO_CALL as u8,
+                0,
+                (slot_offset(n_slots, slot) - (call_index + 1)) as i32,
+            ),
+            // w2 = (1 << w0) & chain_call_actions
+            insn(BPF_ALU | BPF_MOV | BPF_K, 2, 0, 0, 1),
+            insn(BPF_ALU | BPF_LSH | BPF_X, 2, 0, 0, 0),
+            insn(
+                BPF_ALU | BPF_AND | BPF_K,
+                2,
+                0,
+                0,
+                config.chain_call_actions[slot] as i32,
+            ),
+            // return r0 if the action doesn't chain call
+            insn(
+                BPF_JMP | BPF_JEQ | BPF_K,
+                2,
+                0,
+                (exit_index - (jump_index + 1)) as i16,
+                0,
+            ),
+        ]);
+    }
+    insns.extend_from_slice(&[
+        // XDP_PASS
+        insn(BPF_ALU64 | BPF_MOV | BPF_K, 0, 0, 0, 2),
+        insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0),
+    ]);
+
+    for _ in 0..MAX_DISPATCHER_PROGRAMS {
+        insns.extend_from_slice(&[
+            insn(
+                BPF_ALU64 | BPF_MOV | BPF_K,
+                0,
+                0,
+                0,
+                DISPATCHER_RETVAL as i32,
+            ),
+            insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0),
+        ]);
+    }
+
+    insns
+}
+
+fn insn(code: u32, dst_reg: u8, src_reg: u8, off: i16, imm: i32) -> bpf_insn {
+    let mut insn = unsafe { mem::zeroed::<bpf_insn>() };
+    insn.code = code as u8;
+    insn.set_dst_reg(dst_reg);
+    insn.set_src_reg(src_reg);
+    insn.off = off;
+    insn.imm = imm;
+    insn
+}
+
+fn prog_info(fd: RawFd) -> Result<bpf_prog_info, XdpDispatcherError> {
+    bpf_obj_get_info_by_fd(fd).map_err(|e| syscall_error("bpf_obj_get_info_by_fd", e).into())
+}
+
+fn prog_name(info: &bpf_prog_info) -> String {
+    info.name
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8 as char)
+        .collect()
+}
+
+fn pin(fd: RawFd, path: &Path) -> Result<(), XdpDispatcherError> {
+    let c_path = CString::new(path.to_string_lossy().as_bytes()).unwrap();
+    bpf_pin_object(fd, &c_path).map_err(|(_, e)| io_error(path, e))?;
+    Ok(())
+}
+
+fn remove_pins(path: &Path) -> Result<(), XdpDispatcherError> {
+    fs::remove_dir_all(path).map_err(|e| io_error(path, e))
+}
+
+fn io_error(path: &Path, io_error: io::Error) -> XdpDispatcherError {
+    XdpDispatcherError::IOError {
+        path: path.to_owned(),
+        io_error,
+    }
+}
+
+fn syscall_error(call: &str, io_error: io::Error) -> ProgramError {
+    ProgramError::SyscallError {
+        call: call.to_owned(),
+        io_error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispatcher_config() {
+        let config = DispatcherConfig::new(&[
+            (XdpRunConfig::default(), true),
+            (
+                XdpRunConfig {
+                    priority: 60,
+                    chain_call_actions: XdpActions::PASS | XdpActions::DROP,
+                },
+                false,
+            ),
+        ]);
+        assert_eq!(mem::size_of::<DispatcherConfig>(), 124);
+        assert_eq!(config.num_progs_enabled, 2);
+        assert_eq!(config.is_xdp_frags, 0);
+        assert_eq!(config.chain_call_actions[0], 1 << 2 | 1 << 31);
+        assert_eq!(config.chain_call_actions[1], 1 << 1 | 1 << 2 | 1 << 31);
+        assert_eq!(config.run_prios[..2], [50, 60]);
+        assert_eq!(config.program_flags[..2], [BPF_F_XDP_HAS_FRAGS, 0]);
+    }
+
+    #[test]
+    fn test_slot_index() {
+        let priorities = [10, 50, 50, 60];
+        assert_eq!(slot_index(priorities.iter().copied(), 5), 0);
+        assert_eq!(slot_index(priorities.iter().copied(), 10), 1);
+        // after the programs with the same priority
+        assert_eq!(slot_index(priorities.iter().copied(), 50), 3);
+        assert_eq!(slot_index(priorities.iter().copied(), 55), 3);
+        assert_eq!(slot_index(priorities.iter().copied(), 100), 4);
+        assert_eq!(slot_index(std::iter::empty(), 50), 0);
+    }
+
+    #[test]
+    fn test_dispatcher_config_rodata() {
+        let config = DispatcherConfig::new(&[(XdpRunConfig::default(), true); 3]);
+        assert!(config.is_supported());
+
+        // the layout of the .rodata of the libxdp dispatcher
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &config as *const _ as *const u8,
+                mem::size_of::<DispatcherConfig>(),
+            )
+        };
+        assert_eq!(bytes[..4], [236, 2, 3, 1]);
+        let word = |offset: usize| {
+            u32::from_ne_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let (actions, prios, flags) = (4, 4 + 4 * 10, 4 + 8 * 10);
+        for i in 0..3 {
+            assert_eq!(word(actions + 4 * i), 1 << 2 | 1 << 31);
+            assert_eq!(word(prios + 4 * i), DEFAULT_RUN_PRIORITY);
+            assert_eq!(word(flags + 4 * i), BPF_F_XDP_HAS_FRAGS);
+        }
+        assert_eq!(word(actions + 4 * 3), 0);
+
+        let mut other = config;
+        other.magic = 0;
+        assert!(!other.is_supported());
+        let mut other = config;
+        other.dispatcher_version = 1;
+        assert!(!other.is_supported());
+        let mut other = config;
+        other.num_progs_enabled = MAX_DISPATCHER_PROGRAMS as u8 + 1;
+        assert!(!other.is_supported());
+    }
+
+    #[test]
+    fn test_dispatcher_instructions() {
+        let slots = [(XdpRunConfig::default(), false); 2];
+        let config = DispatcherConfig::new(&slots);
+        let insns = dispatcher_instructions(&config);
+        assert_eq!(insns.len(), main_len(2) + 2 * MAX_DISPATCHER_PROGRAMS);
+
+        let exit_index = main_len(2) - 1;
+        for slot in 0..2 {
+            let call_index = 2 + 6 * slot;
+            let call = &insns[call_index];
+            assert_eq!(call.code, (BPF_JMP | BPF_CALL) as u8);
+            assert_eq!(call.src_reg(), BPF_PSEUDO_CALL as u8);
+            assert_eq!(
+                call_index as i32 + 1 + call.imm,
+                slot_offset(2, slot) as i32
+            );
+            let and = &insns[call_index + 3];
+            assert_eq!(and.code, (BPF_ALU | BPF_AND | BPF_K) as u8);
+            assert_eq!(and.imm as u32, config.chain_call_actions[slot]);
+            let jump = &insns[call_index + 4];
+            assert_eq!(jump.code, (BPF_JMP | BPF_JEQ | BPF_K) as u8);
+            assert_eq!(call_index + 5 + jump.off as usize, exit_index);
+        }
+        assert_eq!(insns[exit_index].code, (BPF_JMP | BPF_EXIT) as u8);
+        assert_eq!(insns[slot_offset(2, 0)].imm, DISPATCHER_RETVAL as i32);
+    }
+}
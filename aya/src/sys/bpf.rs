@@ -7,12 +7,18 @@ use std::{
     slice,
 };
 
-use libc::{c_long, ENOENT};
+use libc::{c_long, c_ulong, pid_t, ENOENT};
 
 use crate::{
     bpf_map_def,
-    generated::{bpf_attach_type, bpf_attr, bpf_cmd, bpf_insn, bpf_prog_info, bpf_prog_type},
+    generated::{
+        bpf_attach_type, bpf_attr, bpf_btf_info, bpf_cmd, bpf_func_info, bpf_insn,
+        bpf_iter_link_info, bpf_line_info, bpf_link_info, bpf_map_info, bpf_prog_info,
+        bpf_prog_type, btf_enum64, btf_param, btf_var_secinfo, BPF_F_NUMA_NODE, BPF_F_TOKEN_FD,
+        BTF_INT_SIGNED,
+    },
     maps::PerCpuValues,
+    obj::btf::{Btf, BtfType, BTF_FUNC_GLOBAL, BTF_FUNC_STATIC},
     programs::VerifierLog,
     sys::{kernel_version, SysResult},
     Pod, BPF_OBJ_NAME_LEN,
@@ -20,7 +26,23 @@ use crate::{
 
 use super::{syscall, Syscall};
 
-pub(crate) fn bpf_create_map(name: &CStr, def: &bpf_map_def) -> SysResult {
+/// The BTF types of the keys and values of a map.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct MapBtf {
+    pub(crate) fd: RawFd,
+    pub(crate) key_type_id: u32,
+    pub(crate) value_type_id: u32,
+}
+
+pub(crate) fn bpf_create_map(
+    name: &CStr,
+    def: &bpf_map_def,
+    numa_node: Option<u32>,
+    ifindex: Option<u32>,
+    inner_map_fd: Option<RawFd>,
+    token_fd: Option<RawFd>,
+    btf: Option<MapBtf>,
+) -> SysResult {
     let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
 
     let u = unsafe { &mut attr.__bindgen_anon_1 };
@@ -29,6 +51,25 @@ pub(crate) fn bpf_create_map(name: &CStr, def: &bpf_map_def) -> SysResult {
     u.value_size = def.value_size;
     u.max_entries = def.max_entries;
     u.map_flags = def.map_flags;
+    if let Some(numa_node) = numa_node {
+        u.map_flags |= BPF_F_NUMA_NODE;
+        u.numa_node = numa_node;
+    }
+    if let Some(ifindex) = ifindex {
+        u.map_ifindex = ifindex;
+    }
+    if let Some(inner_map_fd) = inner_map_fd {
+        u.inner_map_fd = inner_map_fd as u32;
+    }
+    if let Some(token_fd) = token_fd {
+        u.map_flags |= BPF_F_TOKEN_FD;
+        u.map_token_fd = token_fd;
+    }
+    if let Some(btf) = btf {
+        u.btf_fd = btf.fd as u32;
+        u.btf_key_type_id = btf.key_type_id;
+        u.btf_value_type_id = btf.value_type_id;
+    }
 
     // https://github.com/torvalds/linux/commit/ad5b177bd73f5107d97c36f56395c4281fb6f089
     // The map name was added as a parameter in kernel 4.15+ so we skip adding it on
@@ -60,13 +101,21 @@ pub(crate) fn bpf_get_object(path: &CStr) -> SysResult {
 }
 
 pub(crate) struct BpfLoadProgramAttrs<'a> {
+    pub(crate) name: Option<&'a str>,
     pub(crate) ty: bpf_prog_type,
     pub(crate) insns: &'a [bpf_insn],
     pub(crate) license: &'a CStr,
     pub(crate) kernel_version: u32,
     pub(crate) expected_attach_type: Option<bpf_attach_type>,
+    pub(crate) flags: u32,
     pub(crate) attach_btf_obj_fd: Option<u32>,
     pub(crate) attach_btf_id: Option<u32>,
+    pub(crate) attach_prog_fd: Option<RawFd>,
+    pub(crate) prog_btf_fd: Option<RawFd>,
+    pub(crate) token_fd: Option<RawFd>,
+    pub(crate) prog_ifindex: Option<u32>,
+    pub(crate) func_info: &'a [bpf_func_info],
+    pub(crate) line_info: &'a [bpf_line_info],
     pub(crate) log: &'a mut VerifierLog,
 }
 
@@ -82,6 +131,24 @@ pub(crate) fn bpf_load_program(aya_attr: BpfLoadProgramAttrs) -> SysResult {
     u.insn_cnt = aya_attr.insns.len() as u32;
     u.license = aya_attr.license.as_ptr() as u64;
     u.kern_version = aya_attr.kernel_version;
+    u.prog_flags = aya_attr.flags;
+    // like map names, program names were added in kernel 4.15
+    if let Some(name) = aya_attr.name {
+        if kernel_version().unwrap() >= (4, 15, 0) {
+            // u.prog_name is 16 bytes max and must be NULL terminated
+            let name_len = cmp::min(name.len(), BPF_OBJ_NAME_LEN - 1);
+            for (dst, src) in u.prog_name.iter_mut().zip(&name.as_bytes()[..name_len]) {
+                *dst = *src as _;
+            }
+        }
+    }
+    if let Some(token_fd) = aya_attr.token_fd {
+        u.prog_flags |= BPF_F_TOKEN_FD;
+        u.prog_token_fd = token_fd;
+    }
+    if let Some(v) = aya_attr.prog_ifindex {
+        u.prog_ifindex = v;
+    }
     let log_buf = aya_attr.log.buf();
     if log_buf.capacity() > 0 {
         u.log_level = 7;
@@ -94,6 +161,22 @@ pub(crate) fn bpf_load_program(aya_attr: BpfLoadProgramAttrs) -> SysResult {
     if let Some(v) = aya_attr.attach_btf_id {
         u.attach_btf_id = v;
     }
+    if let Some(v) = aya_attr.attach_prog_fd {
+        u.__bindgen_anon_1.attach_prog_fd = v as u32;
+    }
+    if let Some(v) = aya_attr.prog_btf_fd {
+        u.prog_btf_fd = v as u32;
+        if !aya_attr.func_info.is_empty() {
+            u.func_info_rec_size = mem::size_of::<bpf_func_info>() as u32;
+            u.func_info = aya_attr.func_info.as_ptr() as u64;
+            u.func_info_cnt = aya_attr.func_info.len() as u32;
+        }
+        if !aya_attr.line_info.is_empty() {
+            u.line_info_rec_size = mem::size_of::<bpf_line_info>() as u32;
+            u.line_info = aya_attr.line_info.as_ptr() as u64;
+            u.line_info_cnt = aya_attr.line_info.len() as u32;
+        }
+    }
 
     sys_bpf(bpf_cmd::BPF_PROG_LOAD, &attr)
 }
@@ -151,6 +234,24 @@ pub(crate) fn bpf_map_lookup_elem_per_cpu<K: Pod, V: Pod>(
     }
 }
 
+/// Looks up the per-CPU values of `key` into `values`, reusing their memory.
+pub(crate) fn bpf_map_lookup_elem_per_cpu_into<K: Pod, V: Pod>(
+    fd: RawFd,
+    key: &K,
+    values: &mut PerCpuValues<V>,
+    flags: u64,
+) -> Result<Option<()>, (c_long, io::Error)> {
+    let mem = values.kernel_mem_mut();
+    match bpf_map_lookup_elem_ptr(fd, key, mem.as_mut_ptr(), flags) {
+        Ok(_) => {
+            unsafe { values.read_kernel_mem() };
+            Ok(Some(()))
+        }
+        Err((_, io_error)) if io_error.raw_os_error() == Some(ENOENT) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 pub(crate) fn bpf_map_lookup_elem_ptr<K: Pod, V>(
     fd: RawFd,
     key: &K,
@@ -253,6 +354,36 @@ pub(crate) fn bpf_map_get_next_key<K>(
     }
 }
 
+// since kernel 5.6
+pub(crate) fn bpf_map_lookup_batch(
+    fd: RawFd,
+    in_batch: Option<&[u8]>,
+    out_batch: &mut [u8],
+    keys: &mut [u8],
+    values: &mut [u8],
+    count: &mut u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    let u = unsafe { &mut attr.batch };
+    u.map_fd = fd as u32;
+    if let Some(in_batch) = in_batch {
+        u.in_batch = in_batch.as_ptr() as u64;
+    }
+    u.out_batch = out_batch.as_mut_ptr() as u64;
+    u.keys = keys.as_mut_ptr() as u64;
+    u.values = values.as_mut_ptr() as u64;
+    u.count = *count;
+
+    let ret = sys_bpf(bpf_cmd::BPF_MAP_LOOKUP_BATCH, &attr);
+
+    // the kernel updates count even when it fails with ENOENT at the end of
+    // the map
+    *count = unsafe { attr.batch.count };
+
+    ret
+}
+
 // since kernel 5.7
 pub(crate) fn bpf_link_create(
     prog_fd: RawFd,
@@ -270,16 +401,181 @@ pub(crate) fn bpf_link_create(
     sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
 }
 
+// since kernel 5.10, the target of extension programs can be different from
+// the one they were loaded against
+pub(crate) fn bpf_link_create_freplace(
+    prog_fd: RawFd,
+    target_fd: RawFd,
+    target_btf_id: u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.__bindgen_anon_1.target_fd = target_fd as u32;
+    attr.link_create.__bindgen_anon_2.target_btf_id = target_btf_id;
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
+// since kernel 5.7
+pub(crate) fn bpf_link_update(link_fd: RawFd, new_prog_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_update.link_fd = link_fd as u32;
+    attr.link_update.new_prog_fd = new_prog_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_LINK_UPDATE, &attr)
+}
+
+// since kernel 5.9
+pub(crate) fn bpf_link_detach(link_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_detach.link_fd = link_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_LINK_DETACH, &attr)
+}
+
+// since kernel 5.8
+pub(crate) fn bpf_link_create_iter(
+    prog_fd: RawFd,
+    link_info: Option<&bpf_iter_link_info>,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.attach_type = bpf_attach_type::BPF_TRACE_ITER as u32;
+    if let Some(link_info) = link_info {
+        let u = unsafe { &mut attr.link_create.__bindgen_anon_2.__bindgen_anon_1 };
+        u.iter_info = link_info as *const _ as u64;
+        u.iter_info_len = mem::size_of::<bpf_iter_link_info>() as u32;
+    }
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
+// since kernel 5.8
+pub(crate) fn bpf_iter_create(link_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.iter_create.link_fd = link_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_ITER_CREATE, &attr)
+}
+
+// since kernel 5.18
+pub(crate) fn bpf_link_create_kprobe_multi(
+    prog_fd: RawFd,
+    syms: &[&CStr],
+    addrs: &[u64],
+    flags: u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    let syms = syms.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+    let addrs = to_ulongs(addrs);
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.attach_type = bpf_attach_type::BPF_TRACE_KPROBE_MULTI as u32;
+    let u = unsafe { &mut attr.link_create.__bindgen_anon_2.kprobe_multi };
+    u.flags = flags;
+    if !syms.is_empty() {
+        u.cnt = syms.len() as u32;
+        u.syms = syms.as_ptr() as u64;
+    } else {
+        u.cnt = addrs.len() as u32;
+        u.addrs = addrs.as_ptr() as u64;
+    }
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
+// since kernel 6.4
+pub(crate) fn bpf_link_create_netfilter(
+    prog_fd: RawFd,
+    pf: u32,
+    hooknum: u32,
+    priority: i32,
+    flags: u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.attach_type = bpf_attach_type::BPF_NETFILTER as u32;
+    let u = unsafe { &mut attr.link_create.__bindgen_anon_2.netfilter };
+    u.pf = pf;
+    u.hooknum = hooknum;
+    u.priority = priority;
+    u.flags = flags;
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
+// since kernel 6.6
+pub(crate) fn bpf_link_create_tcx(
+    prog_fd: RawFd,
+    if_index: u32,
+    attach_type: bpf_attach_type,
+    flags: u32,
+    relative_fd_or_id: u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.__bindgen_anon_1.target_ifindex = if_index;
+    attr.link_create.attach_type = attach_type as u32;
+    attr.link_create.flags = flags;
+    // relative_fd and relative_id share the same field
+    attr.link_create
+        .__bindgen_anon_2
+        .tcx
+        .__bindgen_anon_1
+        .relative_fd = relative_fd_or_id;
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
+// since kernel 6.6
+pub(crate) fn bpf_link_create_uprobe_multi(
+    prog_fd: RawFd,
+    path: &CStr,
+    offsets: &[u64],
+    cookies: Option<&[u64]>,
+    pid: Option<pid_t>,
+    flags: u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.link_create.prog_fd = prog_fd as u32;
+    attr.link_create.attach_type = bpf_attach_type::BPF_TRACE_UPROBE_MULTI as u32;
+    let u = unsafe { &mut attr.link_create.__bindgen_anon_2.uprobe_multi };
+    let offsets = to_ulongs(offsets);
+    u.path = path.as_ptr() as u64;
+    u.offsets = offsets.as_ptr() as u64;
+    u.cnt = offsets.len() as u32;
+    if let Some(cookies) = cookies {
+        u.cookies = cookies.as_ptr() as u64;
+    }
+    if let Some(pid) = pid {
+        u.pid = pid as u32;
+    }
+    u.flags = flags;
+
+    sys_bpf(bpf_cmd::BPF_LINK_CREATE, &attr)
+}
+
 pub(crate) fn bpf_prog_attach(
     prog_fd: RawFd,
     target_fd: RawFd,
     attach_type: bpf_attach_type,
+    flags: u32,
 ) -> SysResult {
     let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
 
     attr.__bindgen_anon_5.attach_bpf_fd = prog_fd as u32;
     attr.__bindgen_anon_5.target_fd = target_fd as u32;
     attr.__bindgen_anon_5.attach_type = attach_type as u32;
+    attr.__bindgen_anon_5.attach_flags = flags;
 
     sys_bpf(bpf_cmd::BPF_PROG_ATTACH, &attr)
 }
@@ -325,6 +621,35 @@ pub(crate) fn bpf_prog_query(
     ret
 }
 
+pub(crate) fn bpf_prog_test_run(
+    prog_fd: RawFd,
+    data_in: &[u8],
+    data_out: &mut [u8],
+    repeat: u32,
+    retval: &mut u32,
+    data_size_out: &mut u32,
+    duration: &mut u32,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.test.prog_fd = prog_fd as u32;
+    attr.test.data_in = data_in.as_ptr() as u64;
+    attr.test.data_size_in = data_in.len() as u32;
+    attr.test.data_out = data_out.as_mut_ptr() as u64;
+    attr.test.data_size_out = data_out.len() as u32;
+    attr.test.repeat = repeat;
+
+    let ret = sys_bpf(bpf_cmd::BPF_PROG_TEST_RUN, &attr);
+
+    unsafe {
+        *retval = attr.test.retval;
+        *data_size_out = attr.test.data_size_out;
+        *duration = attr.test.duration;
+    }
+
+    ret
+}
+
 pub(crate) fn bpf_prog_get_fd_by_id(prog_id: u32) -> Result<RawFd, io::Error> {
     let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
 
@@ -351,6 +676,161 @@ pub(crate) fn bpf_obj_get_info_by_fd(prog_fd: RawFd) -> Result<bpf_prog_info, io
     }
 }
 
+// fills the buffers pointed to by `info`, the lengths are updated by the kernel
+pub(crate) fn bpf_prog_get_info_by_fd(
+    prog_fd: RawFd,
+    info: &mut bpf_prog_info,
+) -> Result<(), io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.info.bpf_fd = prog_fd as u32;
+    attr.info.info = info as *mut _ as u64;
+    attr.info.info_len = mem::size_of::<bpf_prog_info>() as u32;
+
+    match sys_bpf(bpf_cmd::BPF_OBJ_GET_INFO_BY_FD, &attr) {
+        Ok(_) => Ok(()),
+        Err((_, err)) => Err(err),
+    }
+}
+
+pub(crate) fn bpf_map_get_fd_by_id(map_id: u32) -> Result<RawFd, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_6.__bindgen_anon_1.map_id = map_id;
+
+    match sys_bpf(bpf_cmd::BPF_MAP_GET_FD_BY_ID, &attr) {
+        Ok(v) => Ok(v as RawFd),
+        Err((_, err)) => Err(err),
+    }
+}
+
+// since kernel 5.2
+pub(crate) fn bpf_map_freeze(map_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_2.map_fd = map_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_MAP_FREEZE, &attr)
+}
+
+pub(crate) fn bpf_map_get_info_by_fd(map_fd: RawFd) -> Result<bpf_map_info, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+    // info gets entirely populated by the kernel
+    let info = unsafe { MaybeUninit::zeroed().assume_init() };
+
+    attr.info.bpf_fd = map_fd as u32;
+    attr.info.info = &info as *const _ as u64;
+    attr.info.info_len = mem::size_of::<bpf_map_info>() as u32;
+
+    match sys_bpf(bpf_cmd::BPF_OBJ_GET_INFO_BY_FD, &attr) {
+        Ok(_) => Ok(info),
+        Err((_, err)) => Err(err),
+    }
+}
+
+pub(crate) fn bpf_btf_get_next_id(start_id: u32) -> Result<Option<u32>, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_6.__bindgen_anon_1.start_id = start_id;
+
+    match sys_bpf(bpf_cmd::BPF_BTF_GET_NEXT_ID, &attr) {
+        Ok(_) => Ok(Some(unsafe { attr.__bindgen_anon_6.next_id })),
+        Err((_, err)) if err.raw_os_error() == Some(ENOENT) => Ok(None),
+        Err((_, err)) => Err(err),
+    }
+}
+
+pub(crate) fn bpf_link_get_next_id(start_id: u32) -> Result<Option<u32>, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_6.__bindgen_anon_1.start_id = start_id;
+
+    match sys_bpf(bpf_cmd::BPF_LINK_GET_NEXT_ID, &attr) {
+        Ok(_) => Ok(Some(unsafe { attr.__bindgen_anon_6.next_id })),
+        Err((_, err)) if err.raw_os_error() == Some(ENOENT) => Ok(None),
+        Err((_, err)) => Err(err),
+    }
+}
+
+pub(crate) fn bpf_link_get_fd_by_id(link_id: u32) -> Result<RawFd, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_6.__bindgen_anon_1.link_id = link_id;
+
+    match sys_bpf(bpf_cmd::BPF_LINK_GET_FD_BY_ID, &attr) {
+        Ok(v) => Ok(v as RawFd),
+        Err((_, err)) => Err(err),
+    }
+}
+
+// fills the buffers pointed to by `info`, like the name of raw tracepoints
+pub(crate) fn bpf_link_get_info_by_fd(
+    link_fd: RawFd,
+    info: &mut bpf_link_info,
+) -> Result<(), io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.info.bpf_fd = link_fd as u32;
+    attr.info.info = info as *mut _ as u64;
+    attr.info.info_len = mem::size_of::<bpf_link_info>() as u32;
+
+    match sys_bpf(bpf_cmd::BPF_OBJ_GET_INFO_BY_FD, &attr) {
+        Ok(_) => Ok(()),
+        Err((_, err)) => Err(err),
+    }
+}
+
+pub(crate) fn bpf_btf_get_fd_by_id(btf_id: u32) -> Result<RawFd, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.__bindgen_anon_6.__bindgen_anon_1.btf_id = btf_id;
+
+    match sys_bpf(bpf_cmd::BPF_BTF_GET_FD_BY_ID, &attr) {
+        Ok(v) => Ok(v as RawFd),
+        Err((_, err)) => Err(err),
+    }
+}
+
+/// Returns the info of the BTF object `btf_fd`, copying its name into `name`.
+pub(crate) fn bpf_btf_get_info_by_fd(
+    btf_fd: RawFd,
+    name: &mut [u8],
+) -> Result<bpf_btf_info, io::Error> {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+    let mut info = unsafe { mem::zeroed::<bpf_btf_info>() };
+    info.name = name.as_mut_ptr() as u64;
+    info.name_len = name.len() as u32;
+
+    attr.info.bpf_fd = btf_fd as u32;
+    attr.info.info = &info as *const _ as u64;
+    attr.info.info_len = mem::size_of::<bpf_btf_info>() as u32;
+
+    match sys_bpf(bpf_cmd::BPF_OBJ_GET_INFO_BY_FD, &attr) {
+        Ok(_) => Ok(info),
+        Err((_, err)) => Err(err),
+    }
+}
+
+/// Returns the raw data of the BTF object `btf_fd`.
+pub(crate) fn bpf_btf_get_data_by_fd(btf_fd: RawFd) -> Result<Vec<u8>, io::Error> {
+    let info = bpf_btf_get_info_by_fd(btf_fd, &mut [])?;
+    let mut data = vec![0u8; info.btf_size as usize];
+
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+    let mut info = unsafe { mem::zeroed::<bpf_btf_info>() };
+    info.btf = data.as_mut_ptr() as u64;
+    info.btf_size = data.len() as u32;
+
+    attr.info.bpf_fd = btf_fd as u32;
+    attr.info.info = &info as *const _ as u64;
+    attr.info.info_len = mem::size_of::<bpf_btf_info>() as u32;
+
+    match sys_bpf(bpf_cmd::BPF_OBJ_GET_INFO_BY_FD, &attr) {
+        Ok(_) => Ok(data),
+        Err((_, err)) => Err(err),
+    }
+}
+
 pub(crate) fn bpf_raw_tracepoint_open(name: Option<&CStr>, prog_fd: RawFd) -> SysResult {
     let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
 
@@ -363,6 +843,178 @@ pub(crate) fn bpf_raw_tracepoint_open(name: Option<&CStr>, prog_fd: RawFd) -> Sy
     sys_bpf(bpf_cmd::BPF_RAW_TRACEPOINT_OPEN, &attr)
 }
 
+pub(crate) fn bpf_prog_bind_map(prog_fd: RawFd, map_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    attr.prog_bind_map.prog_fd = prog_fd as u32;
+    attr.prog_bind_map.map_fd = map_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_PROG_BIND_MAP, &attr)
+}
+
+pub(crate) fn bpf_load_btf(
+    raw_btf: &[u8],
+    log: &mut VerifierLog,
+    token_fd: Option<RawFd>,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    let u = unsafe { &mut attr.__bindgen_anon_7 };
+    u.btf = raw_btf.as_ptr() as u64;
+    u.btf_size = raw_btf.len() as u32;
+    if let Some(token_fd) = token_fd {
+        u.btf_flags = BPF_F_TOKEN_FD;
+        u.btf_token_fd = token_fd;
+    }
+    let log_buf = log.buf();
+    if log_buf.capacity() > 0 {
+        u.btf_log_level = 1;
+        u.btf_log_buf = log_buf.as_mut_ptr() as u64;
+        u.btf_log_size = log_buf.capacity() as u32;
+    }
+
+    sys_bpf(bpf_cmd::BPF_BTF_LOAD, &attr)
+}
+
+/// Creates a BPF token from a bpffs mounted with delegation options.
+pub(crate) fn bpf_token_create(bpffs_fd: RawFd) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<bpf_attr>() };
+
+    let u = unsafe { &mut attr.token_create };
+    u.bpffs_fd = bpffs_fd as u32;
+
+    sys_bpf(bpf_cmd::BPF_TOKEN_CREATE, &attr)
+}
+
+fn is_btf_loadable(btf: &Btf) -> bool {
+    match bpf_load_btf(&btf.to_bytes(), &mut VerifierLog::new(), None) {
+        Ok(fd) => {
+            unsafe { libc::close(fd as RawFd) };
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub(crate) fn is_btf_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_func_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let name_offset = btf.add_string("a");
+    let func_proto_type_id = btf.add_type(BtfType::new_func_proto(
+        vec![btf_param {
+            name_off: name_offset,
+            type_: int_type_id,
+        }],
+        int_type_id,
+    ));
+    let name_offset = btf.add_string("inc");
+    btf.add_type(BtfType::new_func(
+        name_offset,
+        func_proto_type_id,
+        BTF_FUNC_STATIC,
+    ));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_func_global_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let func_proto_type_id = btf.add_type(BtfType::new_func_proto(Vec::new(), int_type_id));
+    let name_offset = btf.add_string("inc");
+    btf.add_type(BtfType::new_func(
+        name_offset,
+        func_proto_type_id,
+        BTF_FUNC_GLOBAL,
+    ));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_datasec_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let name_offset = btf.add_string("foo");
+    let var_type_id = btf.add_type(BtfType::new_var(name_offset, int_type_id, 0));
+    let name_offset = btf.add_string(".data");
+    btf.add_type(BtfType::new_datasec(
+        name_offset,
+        vec![btf_var_secinfo {
+            type_: var_type_id,
+            offset: 0,
+            size: 4,
+        }],
+        4,
+    ));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_float_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("float");
+    btf.add_type(BtfType::new_float(name_offset, 4));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_decl_tag_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let name_offset = btf.add_string("foo");
+    let var_type_id = btf.add_type(BtfType::new_var(name_offset, int_type_id, 0));
+    let name_offset = btf.add_string("decl_tag");
+    btf.add_type(BtfType::new_decl_tag(name_offset, var_type_id, -1));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_type_tag_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("int");
+    let int_type_id = btf.add_type(BtfType::new_int(name_offset, 4, BTF_INT_SIGNED, 0));
+    let name_offset = btf.add_string("type_tag");
+    let type_tag_id = btf.add_type(BtfType::new_type_tag(name_offset, int_type_id));
+    btf.add_type(BtfType::new_ptr(type_tag_id));
+
+    is_btf_loadable(&btf)
+}
+
+pub(crate) fn is_btf_enum64_supported() -> bool {
+    let mut btf = Btf::new();
+    let name_offset = btf.add_string("enum64");
+    let value_offset = btf.add_string("a");
+    btf.add_type(BtfType::new_enum64(
+        name_offset,
+        8,
+        vec![btf_enum64 {
+            name_off: value_offset,
+            val_lo32: 0,
+            val_hi32: 1,
+        }],
+    ));
+
+    is_btf_loadable(&btf)
+}
+
 fn sys_bpf(cmd: bpf_cmd, attr: &bpf_attr) -> SysResult {
     syscall(Syscall::Bpf { cmd, attr })
 }
+
+// the kernel reads some arrays, like kprobe addresses and uprobe offsets, as
+// `unsigned long` which is 32 bits wide on 32-bit architectures
+fn to_ulongs(values: &[u64]) -> Vec<c_ulong> {
+    values.iter().map(|v| *v as c_ulong).collect()
+}
@@ -7,7 +7,7 @@ mod fake;
 
 use std::io;
 #[cfg(not(test))]
-use std::{convert::TryInto, ffi::CString, mem};
+use std::{convert::TryInto, ffi::CStr, mem};
 
 #[cfg(not(test))]
 use libc::utsname;
@@ -84,27 +84,22 @@ pub(crate) fn kernel_version() -> Result<(u32, u32, u32), ()> {
 
 #[cfg(not(test))]
 pub(crate) fn kernel_version() -> Result<(u32, u32, u32), ()> {
-    unsafe {
+    let (release, version) = unsafe {
         let mut v = mem::zeroed::<utsname>();
         if libc::uname(&mut v as *mut _) != 0 {
             return Err(());
         }
+        (
+            CStr::from_ptr(v.release.as_ptr())
+                .to_string_lossy()
+                .into_owned(),
+            CStr::from_ptr(v.version.as_ptr())
+                .to_string_lossy()
+                .into_owned(),
+        )
+    };
+    // Ubuntu reports the upstream version the kernel is based on here
+    let signature = std::fs::read_to_string("/proc/version_signature").ok();
 
-        let mut major = 0u32;
-        let mut minor = 0u32;
-        let mut patch = 0u32;
-        let format = CString::new("%u.%u.%u").unwrap();
-        if libc::sscanf(
-            v.release.as_ptr(),
-            format.as_ptr(),
-            &mut major as *mut u32,
-            &mut minor as *mut _,
-            &mut patch as *mut _,
-        ) != 3
-        {
-            return Err(());
-        }
-
-        Ok((major, minor, patch))
-    }
+    crate::util::parse_kernel_version(&release, &version, signature.as_deref()).ok_or(())
 }
@@ -1,19 +1,27 @@
-use std::{collections::HashMap, ffi::CStr, io, mem, os::unix::io::RawFd, ptr, slice};
+use std::{
+    collections::HashMap, convert::TryInto, ffi::CStr, io, mem, os::unix::io::RawFd, ptr, slice,
+};
 use thiserror::Error;
 
 use libc::{
-    close, getsockname, nlattr, nlmsgerr, nlmsghdr, recv, send, setsockopt, sockaddr_nl, socket,
-    AF_NETLINK, AF_UNSPEC, ETH_P_ALL, IFLA_XDP, NETLINK_EXT_ACK, NETLINK_ROUTE, NLA_ALIGNTO,
-    NLA_F_NESTED, NLA_TYPE_MASK, NLMSG_DONE, NLMSG_ERROR, NLM_F_ACK, NLM_F_CREATE, NLM_F_DUMP,
-    NLM_F_ECHO, NLM_F_EXCL, NLM_F_MULTI, NLM_F_REQUEST, RTM_DELTFILTER, RTM_GETTFILTER,
-    RTM_NEWQDISC, RTM_NEWTFILTER, RTM_SETLINK, SOCK_RAW, SOL_NETLINK,
+    close, genlmsghdr, getsockname, nlattr, nlmsgerr, nlmsghdr, recv, send, setsockopt,
+    sockaddr_nl, socket, AF_NETLINK, AF_UNSPEC, CTRL_ATTR_FAMILY_ID, CTRL_ATTR_FAMILY_NAME,
+    CTRL_CMD_GETFAMILY, ETH_P_ALL, GENL_ID_CTRL, IFLA_XDP, NETLINK_EXT_ACK, NETLINK_GENERIC,
+    NETLINK_ROUTE, NLA_ALIGNTO, NLA_F_NESTED, NLA_TYPE_MASK, NLMSG_DONE, NLMSG_ERROR, NLM_F_ACK,
+    NLM_F_CREATE, NLM_F_DUMP, NLM_F_ECHO, NLM_F_EXCL, NLM_F_MULTI, NLM_F_REQUEST, RTM_DELTFILTER,
+    RTM_GETLINK, RTM_GETTFILTER, RTM_NEWLINK, RTM_NEWQDISC, RTM_NEWTFILTER, RTM_SETLINK, SOCK_RAW,
+    SOL_NETLINK,
 };
 
 use crate::{
     generated::{
-        ifinfomsg, tcmsg, IFLA_XDP_EXPECTED_FD, IFLA_XDP_FD, IFLA_XDP_FLAGS, NLMSG_ALIGNTO,
-        TCA_BPF_FD, TCA_BPF_FLAGS, TCA_BPF_FLAG_ACT_DIRECT, TCA_BPF_NAME, TCA_KIND, TCA_OPTIONS,
-        TC_H_CLSACT, TC_H_INGRESS, TC_H_MAJ_MASK, TC_H_UNSPEC, XDP_FLAGS_REPLACE,
+        ifinfomsg, tcmsg, IFLA_XDP_ATTACHED, IFLA_XDP_DRV_PROG_ID, IFLA_XDP_EXPECTED_FD,
+        IFLA_XDP_FD, IFLA_XDP_FLAGS, IFLA_XDP_HW_PROG_ID, IFLA_XDP_PROG_ID, IFLA_XDP_SKB_PROG_ID,
+        NETDEV_A_DEV_IFINDEX, NETDEV_A_DEV_XDP_FEATURES, NETDEV_CMD_DEV_GET, NETDEV_FAMILY_NAME,
+        NETDEV_FAMILY_VERSION, NLMSG_ALIGNTO, TCA_BPF_FD, TCA_BPF_FLAGS, TCA_BPF_FLAGS_GEN,
+        TCA_BPF_NAME, TCA_KIND, TCA_OPTIONS, TC_H_CLSACT, TC_H_INGRESS, TC_H_MAJ_MASK, TC_H_UNSPEC,
+        XDP_ATTACHED_DRV, XDP_ATTACHED_HW, XDP_ATTACHED_SKB, XDP_FLAGS_DRV_MODE, XDP_FLAGS_HW_MODE,
+        XDP_FLAGS_REPLACE, XDP_FLAGS_SKB_MODE,
     },
     programs::TcAttachType,
     util::tc_handler_make,
@@ -68,6 +76,73 @@ pub(crate) unsafe fn netlink_set_xdp_fd(
     Ok(())
 }
 
+// Returns the ids of the XDP programs attached to `if_index`, along with the
+// XDP_FLAGS_*_MODE flag of the mode each one is attached in
+pub(crate) unsafe fn netlink_xdp_query(if_index: i32) -> Result<Vec<(u32, u32)>, io::Error> {
+    let sock = NetlinkSocket::open()?;
+
+    // Safety: Request is POD so this is safe
+    let mut req = mem::zeroed::<Request>();
+
+    let nlmsg_len = mem::size_of::<nlmsghdr>() + mem::size_of::<ifinfomsg>();
+    req.header = nlmsghdr {
+        nlmsg_len: nlmsg_len as u32,
+        nlmsg_flags: NLM_F_REQUEST as u16,
+        nlmsg_type: RTM_GETLINK,
+        nlmsg_pid: 0,
+        nlmsg_seq: 1,
+    };
+    req.if_info.ifi_family = AF_UNSPEC as u8;
+    req.if_info.ifi_index = if_index;
+
+    sock.send(&bytes_of(&req)[..req.header.nlmsg_len as usize])?;
+
+    for msg in sock.recv()? {
+        if msg.header.nlmsg_type != RTM_NEWLINK || msg.data.len() < mem::size_of::<ifinfomsg>() {
+            continue;
+        }
+        let attrs = parse_attrs(&msg.data[mem::size_of::<ifinfomsg>()..])?;
+        if let Some(xdp) = attrs.get(&IFLA_XDP) {
+            return parse_xdp_attrs(xdp.data);
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+fn parse_xdp_attrs(data: &[u8]) -> Result<Vec<(u32, u32)>, io::Error> {
+    let attrs = parse_attrs(data)?;
+    let mut programs = Vec::new();
+    for (attr, mode) in [
+        (IFLA_XDP_SKB_PROG_ID, XDP_FLAGS_SKB_MODE),
+        (IFLA_XDP_DRV_PROG_ID, XDP_FLAGS_DRV_MODE),
+        (IFLA_XDP_HW_PROG_ID, XDP_FLAGS_HW_MODE),
+    ] {
+        if let Some(attr) = attrs.get(&(attr as u16)) {
+            programs.push((attr_u32(attr)?, mode));
+        }
+    }
+
+    // kernels older than 4.19 only report the id of the program and the mode
+    // it's attached in
+    if programs.is_empty() {
+        let mode = match attrs
+            .get(&(IFLA_XDP_ATTACHED as u16))
+            .and_then(|attr| attr.data.first())
+        {
+            Some(mode) if *mode as u32 == XDP_ATTACHED_SKB as u32 => XDP_FLAGS_SKB_MODE,
+            Some(mode) if *mode as u32 == XDP_ATTACHED_DRV as u32 => XDP_FLAGS_DRV_MODE,
+            Some(mode) if *mode as u32 == XDP_ATTACHED_HW as u32 => XDP_FLAGS_HW_MODE,
+            _ => return Ok(programs),
+        };
+        if let Some(attr) = attrs.get(&(IFLA_XDP_PROG_ID as u16)) {
+            programs.push((attr_u32(attr)?, mode));
+        }
+    }
+
+    Ok(programs)
+}
+
 pub(crate) unsafe fn netlink_qdisc_add_clsact(if_index: i32) -> Result<(), io::Error> {
     let sock = NetlinkSocket::open()?;
 
@@ -103,6 +178,8 @@ pub(crate) unsafe fn netlink_qdisc_attach(
     attach_type: &TcAttachType,
     prog_fd: RawFd,
     prog_name: &CStr,
+    flags: u32,
+    gen_flags: u32,
 ) -> Result<u32, io::Error> {
     let sock = NetlinkSocket::open()?;
     let priority = 0;
@@ -127,12 +204,15 @@ pub(crate) unsafe fn netlink_qdisc_attach(
     // add TCA_KIND
     let kind_len = write_attr_bytes(attrs_buf, 0, TCA_KIND as u16, b"bpf\0")?;
 
-    // add TCA_OPTIONS which includes TCA_BPF_FD, TCA_BPF_NAME and TCA_BPF_FLAGS
+    // add TCA_OPTIONS which includes TCA_BPF_FD, TCA_BPF_NAME, TCA_BPF_FLAGS
+    // and TCA_BPF_FLAGS_GEN
     let mut options = NestedAttrs::new(&mut attrs_buf[kind_len..], TCA_OPTIONS as u16);
     options.write_attr(TCA_BPF_FD as u16, prog_fd)?;
     options.write_attr_bytes(TCA_BPF_NAME as u16, prog_name.to_bytes_with_nul())?;
-    let flags: u32 = TCA_BPF_FLAG_ACT_DIRECT;
     options.write_attr(TCA_BPF_FLAGS as u16, flags)?;
+    if gen_flags != 0 {
+        options.write_attr(TCA_BPF_FLAGS_GEN as u16, gen_flags)?;
+    }
     let options_len = options.finish()?;
 
     req.header.nlmsg_len += align_to(kind_len + options_len as usize, NLA_ALIGNTO as usize) as u32;
@@ -240,6 +320,110 @@ pub(crate) unsafe fn netlink_find_filter_with_name(
     Ok(prios)
 }
 
+// Returns the XDP features of the device `if_index`, see enum netdev_xdp_act in
+// include/uapi/linux/netdev.h
+pub(crate) unsafe fn netlink_xdp_features(if_index: i32) -> Result<u64, io::Error> {
+    let sock = NetlinkSocket::open_protocol(NETLINK_GENERIC)?;
+    let family_id = genl_family_id(&sock, NETDEV_FAMILY_NAME)?;
+
+    let mut req = mem::zeroed::<GenlRequest>();
+    let nlmsg_len = mem::size_of::<nlmsghdr>() + mem::size_of::<genlmsghdr>();
+    req.header = nlmsghdr {
+        nlmsg_len: nlmsg_len as u32,
+        nlmsg_type: family_id,
+        nlmsg_flags: NLM_F_REQUEST as u16,
+        nlmsg_pid: 0,
+        nlmsg_seq: 1,
+    };
+    req.genl_info.cmd = NETDEV_CMD_DEV_GET as u8;
+    req.genl_info.version = NETDEV_FAMILY_VERSION as u8;
+
+    let attrs_buf = request_attributes(&mut req, nlmsg_len);
+    let attr_len = write_attr(attrs_buf, 0, NETDEV_A_DEV_IFINDEX as u16, if_index as u32)?;
+    req.header.nlmsg_len += align_to(attr_len, NLA_ALIGNTO as usize) as u32;
+
+    sock.send(&bytes_of(&req)[..req.header.nlmsg_len as usize])?;
+
+    let mut features = 0;
+    for msg in sock.recv()? {
+        if msg.header.nlmsg_type != family_id {
+            continue;
+        }
+        let attrs = parse_genl_attrs(&msg.data)?;
+        if let Some(attr) = attrs.get(&(NETDEV_A_DEV_XDP_FEATURES as u16)) {
+            features = attr_u64(attr)?;
+        }
+    }
+
+    Ok(features)
+}
+
+// resolves the id of the generic netlink family `name`
+unsafe fn genl_family_id(sock: &NetlinkSocket, name: &[u8]) -> Result<u16, io::Error> {
+    let mut req = mem::zeroed::<GenlRequest>();
+    let nlmsg_len = mem::size_of::<nlmsghdr>() + mem::size_of::<genlmsghdr>();
+    req.header = nlmsghdr {
+        nlmsg_len: nlmsg_len as u32,
+        nlmsg_type: GENL_ID_CTRL as u16,
+        nlmsg_flags: NLM_F_REQUEST as u16,
+        nlmsg_pid: 0,
+        nlmsg_seq: 1,
+    };
+    req.genl_info.cmd = CTRL_CMD_GETFAMILY as u8;
+    req.genl_info.version = 1;
+
+    let attrs_buf = request_attributes(&mut req, nlmsg_len);
+    let attr_len = write_attr_bytes(attrs_buf, 0, CTRL_ATTR_FAMILY_NAME as u16, name)?;
+    req.header.nlmsg_len += align_to(attr_len, NLA_ALIGNTO as usize) as u32;
+
+    sock.send(&bytes_of(&req)[..req.header.nlmsg_len as usize])?;
+
+    for msg in sock.recv()? {
+        if msg.header.nlmsg_type != GENL_ID_CTRL as u16 {
+            continue;
+        }
+        let attrs = parse_genl_attrs(&msg.data)?;
+        if let Some(attr) = attrs.get(&(CTRL_ATTR_FAMILY_ID as u16)) {
+            return Ok(attr_u32(attr)? as u16);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "generic netlink family not found",
+    ))
+}
+
+fn parse_genl_attrs(data: &[u8]) -> Result<HashMap<u16, NlAttr>, io::Error> {
+    let offset = mem::size_of::<genlmsghdr>();
+    if data.len() < offset {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "buffer smaller than genlmsghdr",
+        ));
+    }
+    Ok(parse_attrs(&data[offset..])?)
+}
+
+fn attr_u64(attr: &NlAttr) -> Result<u64, io::Error> {
+    attr.data
+        .get(..mem::size_of::<u64>())
+        .map(|data| u64::from_ne_bytes(data.try_into().unwrap()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "invalid u64 attribute"))
+}
+
+// reads u32 attributes and the u16 ones, which are padded to 4 bytes
+fn attr_u32(attr: &NlAttr) -> Result<u32, io::Error> {
+    match attr.data.len() {
+        2 | 3 => Ok(u16::from_ne_bytes(attr.data[..2].try_into().unwrap()) as u32),
+        n if n >= 4 => Ok(u32::from_ne_bytes(attr.data[..4].try_into().unwrap())),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "invalid u32 attribute",
+        )),
+    }
+}
+
 #[repr(C)]
 struct Request {
     header: nlmsghdr,
@@ -254,6 +438,13 @@ struct TcRequest {
     attrs: [u8; 64],
 }
 
+#[repr(C)]
+struct GenlRequest {
+    header: nlmsghdr,
+    genl_info: genlmsghdr,
+    attrs: [u8; 64],
+}
+
 struct NetlinkSocket {
     sock: RawFd,
     _nl_pid: u32,
@@ -261,8 +452,12 @@ struct NetlinkSocket {
 
 impl NetlinkSocket {
     fn open() -> Result<NetlinkSocket, io::Error> {
+        NetlinkSocket::open_protocol(NETLINK_ROUTE)
+    }
+
+    fn open_protocol(protocol: i32) -> Result<NetlinkSocket, io::Error> {
         // Safety: libc wrapper
-        let sock = unsafe { socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE) };
+        let sock = unsafe { socket(AF_NETLINK, SOCK_RAW, protocol) };
         if sock < 0 {
             return Err(io::Error::last_os_error());
         }
@@ -721,4 +916,47 @@ mod tests {
         let name = CStr::from_bytes_with_nul(inner.data).unwrap();
         assert_eq!(name.to_string_lossy(), "foo");
     }
+
+    #[test]
+    fn test_parse_xdp_attrs() {
+        let mut buf = [0; 64];
+        let mut offset = 0;
+        offset += write_attr(&mut buf, offset, IFLA_XDP_SKB_PROG_ID as u16, 42u32).unwrap();
+        offset += write_attr(&mut buf, offset, IFLA_XDP_DRV_PROG_ID as u16, 24u32).unwrap();
+        offset += write_attr(&mut buf, offset, IFLA_XDP_ATTACHED as u16, 4u8).unwrap();
+        assert_eq!(
+            parse_xdp_attrs(&buf[..offset]).unwrap(),
+            vec![(42, XDP_FLAGS_SKB_MODE), (24, XDP_FLAGS_DRV_MODE)]
+        );
+
+        // older kernels only report the mode and a single id
+        let mut buf = [0; 64];
+        let mut offset = 0;
+        offset += write_attr(&mut buf, offset, IFLA_XDP_ATTACHED as u16, 3u8).unwrap();
+        offset += write_attr(&mut buf, offset, IFLA_XDP_PROG_ID as u16, 42u32).unwrap();
+        assert_eq!(
+            parse_xdp_attrs(&buf[..offset]).unwrap(),
+            vec![(42, XDP_FLAGS_HW_MODE)]
+        );
+
+        let mut buf = [0; 64];
+        let offset = write_attr(&mut buf, 0, IFLA_XDP_ATTACHED as u16, 0u8).unwrap();
+        assert!(parse_xdp_attrs(&buf[..offset]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_genl_attrs() {
+        let mut buf = [0; 64];
+        let mut offset = mem::size_of::<genlmsghdr>();
+        offset += write_attr(&mut buf, offset, CTRL_ATTR_FAMILY_ID as u16, 42u16).unwrap();
+        offset += write_attr(&mut buf, offset, NETDEV_A_DEV_XDP_FEATURES as u16, 0x13u64).unwrap();
+
+        let attrs = parse_genl_attrs(&buf[..offset]).unwrap();
+        let family_id = &attrs[&(CTRL_ATTR_FAMILY_ID as u16)];
+        assert_eq!(attr_u32(family_id).unwrap(), 42);
+        let features = &attrs[&(NETDEV_A_DEV_XDP_FEATURES as u16)];
+        assert_eq!(attr_u64(features).unwrap(), 0x13);
+
+        assert!(parse_genl_attrs(&buf[..2]).is_err());
+    }
 }
@@ -2,12 +2,16 @@ use std::{ffi::CString, mem};
 
 use libc::{c_int, pid_t};
 
-use crate::generated::{
-    perf_event_attr,
-    perf_event_sample_format::PERF_SAMPLE_RAW,
-    perf_sw_ids::PERF_COUNT_SW_BPF_OUTPUT,
-    perf_type_id::{PERF_TYPE_SOFTWARE, PERF_TYPE_TRACEPOINT},
-    PERF_FLAG_FD_CLOEXEC,
+use crate::{
+    generated::{
+        perf_event_attr,
+        perf_event_read_format::{PERF_FORMAT_TOTAL_TIME_ENABLED, PERF_FORMAT_TOTAL_TIME_RUNNING},
+        perf_event_sample_format::PERF_SAMPLE_RAW,
+        perf_sw_ids::PERF_COUNT_SW_BPF_OUTPUT,
+        perf_type_id::{PERF_TYPE_BREAKPOINT, PERF_TYPE_SOFTWARE, PERF_TYPE_TRACEPOINT},
+        PERF_FLAG_FD_CLOEXEC,
+    },
+    maps::perf::PerfBufferWakeup,
 };
 
 use super::{syscall, SysResult, Syscall};
@@ -21,6 +25,7 @@ pub(crate) fn perf_event_open(
     sample_period: u64,
     sample_frequency: Option<u64>,
     wakeup: bool,
+    inherit: bool,
     flags: u32,
 ) -> SysResult {
     let mut attr = unsafe { mem::zeroed::<perf_event_attr>() };
@@ -29,36 +34,111 @@ pub(crate) fn perf_event_open(
     attr.size = mem::size_of::<perf_event_attr>() as u32;
     attr.type_ = perf_type;
     attr.sample_type = PERF_SAMPLE_RAW as u64;
-    // attr.inherits = if pid > 0 { 1 } else { 0 };
+    attr.set_inherit(if inherit { 1 } else { 0 });
     attr.__bindgen_anon_2.wakeup_events = if wakeup { 1 } else { 0 };
+    set_sample_policy(&mut attr, sample_period, sample_frequency);
+
+    syscall(Syscall::PerfEventOpen {
+        attr,
+        pid,
+        cpu,
+        group: -1,
+        flags,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn perf_event_open_breakpoint(
+    bp_type: u32,
+    bp_addr: u64,
+    bp_len: u64,
+    pid: pid_t,
+    cpu: c_int,
+    sample_period: u64,
+    sample_frequency: Option<u64>,
+    inherit: bool,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<perf_event_attr>() };
+
+    attr.size = mem::size_of::<perf_event_attr>() as u32;
+    attr.type_ = PERF_TYPE_BREAKPOINT as u32;
+    attr.bp_type = bp_type;
+    attr.__bindgen_anon_3.bp_addr = bp_addr;
+    attr.__bindgen_anon_4.bp_len = bp_len;
+    attr.set_inherit(if inherit { 1 } else { 0 });
+    set_sample_policy(&mut attr, sample_period, sample_frequency);
+
+    syscall(Syscall::PerfEventOpen {
+        attr,
+        pid,
+        cpu,
+        group: -1,
+        flags: 0,
+    })
+}
 
+fn set_sample_policy(
+    attr: &mut perf_event_attr,
+    sample_period: u64,
+    sample_frequency: Option<u64>,
+) {
     if let Some(frequency) = sample_frequency {
         attr.set_freq(1);
         attr.__bindgen_anon_1.sample_freq = frequency;
     } else {
         attr.__bindgen_anon_1.sample_period = sample_period;
     }
+}
+
+pub(crate) fn perf_event_open_bpf(
+    cpu: c_int,
+    wakeup: PerfBufferWakeup,
+    overwrite: bool,
+) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<perf_event_attr>() };
+
+    attr.config = PERF_COUNT_SW_BPF_OUTPUT as u64;
+    attr.size = mem::size_of::<perf_event_attr>() as u32;
+    attr.type_ = PERF_TYPE_SOFTWARE as u32;
+    attr.sample_type = PERF_SAMPLE_RAW as u64;
+    match wakeup {
+        PerfBufferWakeup::Events(events) => attr.__bindgen_anon_2.wakeup_events = events,
+        PerfBufferWakeup::Watermark(bytes) => {
+            attr.set_watermark(1);
+            attr.__bindgen_anon_2.wakeup_watermark = bytes;
+        }
+    }
+    // in overwrite mode the kernel writes the buffer backwards, so that the
+    // newest events can be found from the head when the buffer is full
+    attr.set_write_backward(if overwrite { 1 } else { 0 });
+    set_sample_policy(&mut attr, 1, None);
 
     syscall(Syscall::PerfEventOpen {
         attr,
-        pid,
+        pid: -1,
         cpu,
         group: -1,
-        flags,
+        flags: PERF_FLAG_FD_CLOEXEC,
     })
 }
 
-pub(crate) fn perf_event_open_bpf(cpu: c_int) -> SysResult {
-    perf_event_open(
-        PERF_TYPE_SOFTWARE as u32,
-        PERF_COUNT_SW_BPF_OUTPUT as u64,
-        -1,
+pub(crate) fn perf_event_open_counter(perf_type: u32, config: u64, cpu: c_int) -> SysResult {
+    let mut attr = unsafe { mem::zeroed::<perf_event_attr>() };
+
+    attr.config = config;
+    attr.size = mem::size_of::<perf_event_attr>() as u32;
+    attr.type_ = perf_type;
+    // same layout as struct bpf_perf_event_value
+    attr.read_format =
+        PERF_FORMAT_TOTAL_TIME_ENABLED as u64 | PERF_FORMAT_TOTAL_TIME_RUNNING as u64;
+
+    syscall(Syscall::PerfEventOpen {
+        attr,
+        pid: -1,
         cpu,
-        1,
-        None,
-        true,
-        PERF_FLAG_FD_CLOEXEC,
-    )
+        group: -1,
+        flags: PERF_FLAG_FD_CLOEXEC,
+    })
 }
 
 pub(crate) fn perf_event_open_probe(
@@ -67,6 +147,7 @@ pub(crate) fn perf_event_open_probe(
     name: &str,
     offset: u64,
     pid: Option<pid_t>,
+    ref_ctr_offset: Option<u64>,
 ) -> SysResult {
     let mut attr = unsafe { mem::zeroed::<perf_event_attr>() };
 
@@ -74,6 +155,11 @@ pub(crate) fn perf_event_open_probe(
         attr.config = 1 << ret_bit;
     }
 
+    // see PERF_UPROBE_REF_CTR_OFFSET_SHIFT in kernel/trace/trace_event_perf.c
+    if let Some(ref_ctr_offset) = ref_ctr_offset {
+        attr.config |= ref_ctr_offset << 32;
+    }
+
     let c_name = CString::new(name).unwrap();
 
     attr.size = mem::size_of::<perf_event_attr>() as u32;
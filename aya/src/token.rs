@@ -0,0 +1,116 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::sys::bpf_token_create;
+
+/// The default mount point of the BPF filesystem.
+pub const DEFAULT_BPFFS_PATH: &str = "/sys/fs/bpf";
+
+/// The error type returned by [`BpfToken::from_bpffs`].
+#[derive(Debug, Error)]
+pub enum BpfTokenError {
+    /// Opening the BPF filesystem failed.
+    #[error("error opening bpffs `{}`", path.display())]
+    OpenError {
+        /// The path of the BPF filesystem.
+        path: PathBuf,
+        #[source]
+        /// The original [`io::Error`].
+        io_error: io::Error,
+    },
+
+    /// A syscall failed.
+    #[error("`{call}` failed")]
+    SyscallError {
+        /// The name of the syscall which failed.
+        call: String,
+        #[source]
+        /// The [`io::Error`] returned by the syscall.
+        io_error: io::Error,
+    },
+}
+
+/// A BPF token, used to create maps and load programs without `CAP_BPF`.
+///
+/// Tokens are requested from a BPF filesystem mounted with the `delegate_*`
+/// options, usually by a privileged parent that sets up the mount for a
+/// container running in a user namespace. The token grants the commands,
+/// map types, program types and attach types allowed by the mount.
+///
+/// Pass the token to [`BpfLoader::token`](crate::BpfLoader::token) to use
+/// it when loading an object. The token is closed when dropped.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 6.9.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(thiserror::Error, Debug)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     Token(#[from] aya::BpfTokenError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// use std::os::unix::io::AsRawFd;
+/// use aya::{BpfLoader, BpfToken};
+///
+/// let token = BpfToken::from_bpffs("/sys/fs/bpf")?;
+/// let bpf = BpfLoader::new()
+///     .token(token.as_raw_fd())
+///     .load_file("file.o")?;
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+pub struct BpfToken {
+    fd: RawFd,
+}
+
+impl BpfToken {
+    /// Requests a token from the BPF filesystem mounted at `path`.
+    ///
+    /// See also [`DEFAULT_BPFFS_PATH`].
+    pub fn from_bpffs<P: AsRef<Path>>(path: P) -> Result<BpfToken, BpfTokenError> {
+        let path = path.as_ref();
+        let bpffs = File::open(path).map_err(|io_error| BpfTokenError::OpenError {
+            path: path.to_owned(),
+            io_error,
+        })?;
+
+        let fd = bpf_token_create(bpffs.as_raw_fd()).map_err(|(_, io_error)| {
+            BpfTokenError::SyscallError {
+                call: "bpf_token_create".to_owned(),
+                io_error,
+            }
+        })? as RawFd;
+
+        Ok(BpfToken { fd })
+    }
+}
+
+impl AsRawFd for BpfToken {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for BpfToken {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the descriptor is closed when the token is dropped
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for BpfToken {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
@@ -0,0 +1,298 @@
+//! Reading the kernel trace pipe.
+//!
+//! eBPF programs can print debug messages with `bpf_printk()`, also known as
+//! `bpf_trace_printk()`. The messages are written to the `trace_pipe` file of
+//! tracefs, which [`TracePipe`] reads and parses.
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    time::Duration,
+};
+
+#[cfg(feature = "async")]
+use std::{
+    fs::OpenOptions,
+    io::Read,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, RawFd},
+    },
+};
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+use async_io::Async;
+
+#[cfg(feature = "async_tokio")]
+use tokio::io::unix::AsyncFd;
+
+use crate::util::tracefs_path;
+
+/// A line read from the trace pipe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceLine {
+    /// The name of the task that was running when the line was printed.
+    pub task: String,
+    /// The pid of the task.
+    pub pid: u32,
+    /// The CPU the line was printed on.
+    pub cpu: u32,
+    /// The time the line was printed at, since the system booted.
+    pub timestamp: Duration,
+    /// The message, without the `bpf_trace_printk: ` prefix.
+    pub message: String,
+}
+
+impl TraceLine {
+    /// Parses a line of the trace pipe, returning `None` if it's not a trace
+    /// event, for example a `CPU:1 [LOST 3 EVENTS]` notice.
+    ///
+    /// Lines look like
+    /// `  curl-1234  [003] d..31 5647.258714: bpf_trace_printk: message`, the
+    /// flags after the CPU being absent on older kernels.
+    pub fn parse(line: &str) -> Option<TraceLine> {
+        let line = line.trim_end_matches('\n');
+
+        // task names can contain spaces and dashes, so look for the first
+        // `-<pid> [<cpu>]` sequence
+        let (task_pid, cpu, rest) = line.match_indices('[').find_map(|(pos, _)| {
+            let (cpu, rest) = line[pos + 1..].split_once(']')?;
+            let cpu = cpu.parse::<u32>().ok()?;
+            let task_pid = line[..pos].trim();
+            let (_, pid) = task_pid.rsplit_once('-')?;
+            pid.parse::<u32>().ok()?;
+            Some((task_pid, cpu, rest))
+        })?;
+        let (task, pid) = task_pid.rsplit_once('-')?;
+        let pid = pid.parse().ok()?;
+
+        let rest = rest.trim_start();
+        let (timestamp, message) = match rest.split_once(": ") {
+            Some((head, message)) => (head, message),
+            None => return None,
+        };
+        // skip the flags
+        let timestamp = timestamp.rsplit(' ').next()?;
+        let (secs, frac) = timestamp.split_once('.')?;
+        if frac.is_empty() || frac.len() > 9 {
+            return None;
+        }
+        // usually microseconds, but the precision depends on the trace clock
+        let nanos = frac.parse::<u32>().ok()? * 10u32.pow(9 - frac.len() as u32);
+        let timestamp = Duration::new(secs.parse().ok()?, nanos);
+        let message = message
+            .strip_prefix("bpf_trace_printk: ")
+            .unwrap_or(message);
+
+        Some(TraceLine {
+            task: task.trim().to_owned(),
+            pid,
+            cpu,
+            timestamp,
+            message: message.to_owned(),
+        })
+    }
+}
+
+/// A reader of the kernel trace pipe.
+///
+/// Reading the trace pipe consumes the lines, so only one reader should be
+/// active at a time. The iterator blocks until lines are available, and skips
+/// the lines that aren't trace events.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::trace_pipe::TracePipe;
+///
+/// for line in TracePipe::open()? {
+///     let line = line?;
+///     println!("{} ({}) on cpu {}: {}", line.task, line.pid, line.cpu, line.message);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TracePipe {
+    reader: BufReader<File>,
+}
+
+impl TracePipe {
+    /// Opens the `trace_pipe` file of tracefs.
+    pub fn open() -> Result<TracePipe, io::Error> {
+        let file = File::open(tracefs_path()?.join("trace_pipe"))?;
+        Ok(TracePipe {
+            reader: BufReader::new(file),
+        })
+    }
+
+    /// Reads the next trace event, blocking until one is available.
+    pub fn read_line(&mut self) -> Result<TraceLine, io::Error> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if let Some(line) = TraceLine::parse(&line) {
+                return Ok(line);
+            }
+        }
+    }
+}
+
+impl Iterator for TracePipe {
+    type Item = Result<TraceLine, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_line())
+    }
+}
+
+/// An async reader of the kernel trace pipe.
+///
+/// This is the async version of [`TracePipe`], for use with
+/// [tokio](https://docs.rs/tokio) or [async-std](https://docs.rs/async-std).
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "async_tokio")]
+/// # async fn try_main() -> Result<(), std::io::Error> {
+/// use aya::trace_pipe::AsyncTracePipe;
+///
+/// let mut trace_pipe = AsyncTracePipe::open()?;
+/// loop {
+///     let line = trace_pipe.read_line().await?;
+///     println!("{}: {}", line.task, line.message);
+/// }
+/// # }
+/// ```
+#[cfg(any(feature = "async", doc))]
+pub struct AsyncTracePipe {
+    file: File,
+    buf: Vec<u8>,
+
+    #[cfg(feature = "async_tokio")]
+    async_fd: AsyncFd<RawFd>,
+
+    #[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+    async_fd: Async<RawFd>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncTracePipe {
+    /// Opens the `trace_pipe` file of tracefs.
+    pub fn open() -> Result<AsyncTracePipe, io::Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(tracefs_path()?.join("trace_pipe"))?;
+        let fd = file.as_raw_fd();
+        Ok(AsyncTracePipe {
+            file,
+            buf: Vec::new(),
+
+            #[cfg(feature = "async_tokio")]
+            async_fd: AsyncFd::new(fd)?,
+
+            #[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+            async_fd: Async::new(fd)?,
+        })
+    }
+
+    // returns the next complete trace event in the buffer, if any
+    fn next_buffered(&mut self) -> Option<TraceLine> {
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line = self.buf.drain(..=pos).collect::<Vec<_>>();
+            if let Some(line) = TraceLine::parse(&String::from_utf8_lossy(&line)) {
+                return Some(line);
+            }
+        }
+        None
+    }
+}
+
+// reads what's available without blocking, returning false if nothing was
+#[cfg(feature = "async")]
+fn fill_buf(file: &mut File, buf: &mut Vec<u8>) -> Result<bool, io::Error> {
+    let mut chunk = [0u8; 4096];
+    match file.read(&mut chunk) {
+        Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+        Ok(n) => {
+            buf.extend_from_slice(&chunk[..n]);
+            Ok(true)
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(any(feature = "async_tokio", doc))]
+impl AsyncTracePipe {
+    /// Reads the next trace event, waiting until one is available.
+    pub async fn read_line(&mut self) -> Result<TraceLine, io::Error> {
+        loop {
+            if let Some(line) = self.next_buffered() {
+                return Ok(line);
+            }
+            let mut guard = self.async_fd.readable().await?;
+            if !fill_buf(&mut self.file, &mut self.buf)? {
+                guard.clear_ready();
+            }
+        }
+    }
+}
+
+#[cfg(all(not(feature = "async_tokio"), feature = "async_std"))]
+impl AsyncTracePipe {
+    /// Reads the next trace event, waiting until one is available.
+    pub async fn read_line(&mut self) -> Result<TraceLine, io::Error> {
+        loop {
+            if let Some(line) = self.next_buffered() {
+                return Ok(line);
+            }
+            if !fill_buf(&mut self.file, &mut self.buf)? {
+                self.async_fd.readable().await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let line = TraceLine::parse(
+            "           <...>-2381    [003] d..31 11457.294615: bpf_trace_printk: hello world\n",
+        )
+        .unwrap();
+        assert_eq!(
+            line,
+            TraceLine {
+                task: "<...>".to_owned(),
+                pid: 2381,
+                cpu: 3,
+                timestamp: Duration::from_micros(11_457_294_615),
+                message: "hello world".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_line_no_flags() {
+        let line =
+            TraceLine::parse(" kworker/u8:1-my-task-42 [000] 5.000001: bpf_trace_printk: a: b")
+                .unwrap();
+        assert_eq!(line.task, "kworker/u8:1-my-task");
+        assert_eq!(line.pid, 42);
+        assert_eq!(line.cpu, 0);
+        assert_eq!(line.timestamp, Duration::from_micros(5_000_001));
+        assert_eq!(line.message, "a: b");
+    }
+
+    #[test]
+    fn test_parse_lost_events() {
+        assert_eq!(TraceLine::parse("CPU:2 [LOST 4 EVENTS]"), None);
+    }
+}
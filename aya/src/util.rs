@@ -1,20 +1,38 @@
 //! Utility functions.
 use std::{
-    collections::BTreeMap,
+    cmp,
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
     ffi::CString,
+    fmt,
     fs::{self, File},
-    io::{self, BufReader},
+    io::{self, BufReader, Read},
+    mem,
+    os::unix::{
+        io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        net::UnixStream,
+    },
+    path::{Path, PathBuf},
+    ptr,
     str::FromStr,
+    time::{Duration, Instant},
 };
 
-use crate::generated::{TC_H_MAJ_MASK, TC_H_MIN_MASK};
+use crate::{
+    generated::{TC_H_MAJ_MASK, TC_H_MIN_MASK},
+    BPF_OBJ_NAME_LEN,
+};
 
-use libc::if_nametoindex;
+use flate2::read::GzDecoder;
+use libc::{c_int, fcntl, if_nametoindex, poll, pollfd, EINTR, F_DUPFD_CLOEXEC, POLLIN};
 
 use io::BufRead;
 
 const ONLINE_CPUS: &str = "/sys/devices/system/cpu/online";
 pub(crate) const POSSIBLE_CPUS: &str = "/sys/devices/system/cpu/possible";
+const TRACEFS_PATHS: &[&str] = &["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+const KERNEL_RELEASE: &str = "/proc/sys/kernel/osrelease";
+const PROC_CONFIG_GZ: &str = "/proc/config.gz";
 
 /// Returns the numeric IDs of the CPUs currently online.
 pub fn online_cpus() -> Result<Vec<u32>, io::Error> {
@@ -72,23 +90,369 @@ fn parse_cpu_ranges(data: &str) -> Result<Vec<u32>, ()> {
 ///
 /// The symbols can be passed to [`StackTrace::resolve`](crate::maps::stack_trace::StackTrace::resolve).
 pub fn kernel_symbols() -> Result<BTreeMap<u64, String>, io::Error> {
-    let mut reader = BufReader::new(File::open("/proc/kallsyms")?);
-    parse_kernel_symbols(&mut reader)
+    Ok(KernelSymbols::load()?.into_map())
+}
+
+/// A kernel symbol, as listed in `/proc/kallsyms`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelSymbol {
+    /// The address of the symbol.
+    pub address: u64,
+    /// The name of the symbol.
+    pub name: String,
+    /// The type of the symbol, eg `T` for global text symbols.
+    pub kind: char,
+    /// The module defining the symbol, or `None` if the symbol is part of the
+    /// core kernel image.
+    pub module: Option<String>,
+}
+
+/// Kernel symbols resolver.
+///
+/// Parses `/proc/kallsyms` once and allows looking up symbols by address and
+/// by name. Reading addresses from `/proc/kallsyms` requires `CAP_SYSLOG`,
+/// otherwise all the addresses are zero: the symbols are still listed, but
+/// [`KernelSymbols::resolve`] and [`KernelSymbols::address_of`] return `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::util::KernelSymbols;
+///
+/// let ksyms = KernelSymbols::load()?;
+/// if let Some(addr) = ksyms.address_of("try_to_wake_up") {
+///     let (sym, offset) = ksyms.resolve(addr + 4).unwrap();
+///     assert_eq!((sym.name.as_str(), offset), ("try_to_wake_up", 4));
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct KernelSymbols {
+    // sorted by address, symbols at the same address are kept in the order
+    // of /proc/kallsyms
+    symbols: Vec<KernelSymbol>,
+    // indices into `symbols`
+    by_name: HashMap<String, Vec<usize>>,
+    // whether the addresses are hidden by kptr_restrict
+    restricted: bool,
+}
+
+impl KernelSymbols {
+    /// Loads the kernel symbols from `/proc/kallsyms`.
+    pub fn load() -> Result<KernelSymbols, io::Error> {
+        let mut reader = BufReader::new(File::open("/proc/kallsyms")?);
+        KernelSymbols::parse(&mut reader)
+    }
+
+    pub(crate) fn parse(reader: &mut dyn BufRead) -> Result<KernelSymbols, io::Error> {
+        let mut symbols = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, line.clone());
+            let mut parts = line.split_whitespace();
+            let address = parts
+                .next()
+                .and_then(|addr| u64::from_str_radix(addr, 16).ok())
+                .ok_or_else(invalid)?;
+            let kind = parts
+                .next()
+                .and_then(|kind| kind.chars().next())
+                .ok_or_else(invalid)?;
+            let name = parts.next().ok_or_else(invalid)?.to_owned();
+            let module = parts.next().map(|module| {
+                module
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_owned()
+            });
+
+            symbols.push(KernelSymbol {
+                address,
+                name,
+                kind,
+                module,
+            });
+        }
+
+        symbols.sort_by_key(|sym| sym.address);
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, sym) in symbols.iter().enumerate() {
+            by_name.entry(sym.name.clone()).or_default().push(i);
+        }
+        let restricted = !symbols.is_empty() && symbols.iter().all(|sym| sym.address == 0);
+
+        Ok(KernelSymbols {
+            symbols,
+            by_name,
+            restricted,
+        })
+    }
+
+    // the symbol map taken by `StackTrace::resolve`, the first alias wins
+    fn into_map(self) -> BTreeMap<u64, String> {
+        let mut map = BTreeMap::new();
+        for sym in self.symbols {
+            map.entry(sym.address).or_insert(sym.name);
+        }
+        map
+    }
+
+    /// Returns the symbol containing `address` and the offset of `address`
+    /// from the start of the symbol.
+    ///
+    /// If several symbols start at the same address, the first one listed in
+    /// `/proc/kallsyms` is returned.
+    pub fn resolve(&self, address: u64) -> Option<(&KernelSymbol, u64)> {
+        if self.restricted {
+            return None;
+        }
+        let end = self.symbols.partition_point(|sym| sym.address <= address);
+        let start = self.symbols.get(end.checked_sub(1)?)?.address;
+        let first = self.symbols[..end].partition_point(|sym| sym.address < start);
+        let sym = &self.symbols[first];
+        Some((sym, address - sym.address))
+    }
+
+    /// Returns the address of the symbol called `name`.
+    ///
+    /// If several symbols have the same name, the address of the first one
+    /// found is returned. Use [`KernelSymbols::address_of_in_module`] to
+    /// disambiguate.
+    pub fn address_of(&self, name: &str) -> Option<u64> {
+        self.address_of_in(name, |_| true)
+    }
+
+    /// Returns the address of the symbol called `name` defined in `module`,
+    /// or in the core kernel image if `module` is `None`.
+    pub fn address_of_in_module(&self, name: &str, module: Option<&str>) -> Option<u64> {
+        self.address_of_in(name, |sym| sym.module.as_deref() == module)
+    }
+
+    fn address_of_in(&self, name: &str, filter: impl Fn(&KernelSymbol) -> bool) -> Option<u64> {
+        if self.restricted {
+            return None;
+        }
+        self.symbols_named(name)
+            .find(|sym| filter(sym))
+            .map(|sym| sym.address)
+    }
+
+    /// Returns all the symbols called `name`.
+    ///
+    /// The symbols are returned even if their addresses are hidden.
+    pub fn symbols_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a KernelSymbol> {
+        self.by_name
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(move |i| &self.symbols[*i])
+    }
+
+    /// Returns an iterator over all the symbols, ordered by address.
+    pub fn iter(&self) -> impl Iterator<Item = &KernelSymbol> {
+        self.symbols.iter()
+    }
+}
+
+/// Returns the path where tracefs is mounted.
+///
+/// Newer kernels mount tracefs at `/sys/kernel/tracing`, while older ones only
+/// expose it under debugfs at `/sys/kernel/debug/tracing`.
+pub(crate) fn tracefs_path() -> Result<PathBuf, io::Error> {
+    TRACEFS_PATHS
+        .iter()
+        .map(Path::new)
+        .find(|path| path.join("available_events").exists())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "tracefs not found"))
+}
+
+/// Returns the tracepoints available for attaching as `(category, name)`
+/// pairs.
+///
+/// The tracepoints are read from the `available_events` file in tracefs. The
+/// returned pairs can be passed to
+/// [`TracePoint::attach`](crate::programs::TracePoint::attach).
+pub fn available_tracepoints() -> Result<Vec<(String, String)>, io::Error> {
+    let data = fs::read_to_string(tracefs_path()?.join("available_events"))?;
+    Ok(parse_available_events(&data))
+}
+
+fn parse_available_events(data: &str) -> Vec<(String, String)> {
+    data.lines()
+        .filter_map(|line| {
+            let (category, name) = line.trim().split_once(':')?;
+            Some((category.to_owned(), name.to_owned()))
+        })
+        .collect()
+}
+
+/// A kernel version.
+///
+/// Versions compare in release order, so they can be checked against the
+/// minimum version required by a feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use aya::util::KernelVersion;
+///
+/// if KernelVersion::current()? >= KernelVersion::new(5, 8, 0) {
+///     // use ring buffers
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KernelVersion {
+    major: u8,
+    minor: u8,
+    patch: u16,
+}
+
+impl KernelVersion {
+    /// Creates a new kernel version.
+    pub fn new(major: u8, minor: u8, patch: u16) -> KernelVersion {
+        KernelVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Returns the version of the running kernel.
+    ///
+    /// On Ubuntu and Debian the upstream version the kernel is based on is
+    /// returned, rather than the version in `uname -r` which includes the
+    /// distribution's ABI number.
+    pub fn current() -> Result<KernelVersion, io::Error> {
+        let (major, minor, patch) = crate::sys::kernel_version().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "unable to determine the kernel version",
+            )
+        })?;
+        Ok(KernelVersion::new(major as u8, minor as u8, patch as u16))
+    }
+
+    /// Returns the major version.
+    pub fn major(&self) -> u8 {
+        self.major
+    }
+
+    /// Returns the minor version.
+    pub fn minor(&self) -> u8 {
+        self.minor
+    }
+
+    /// Returns the patch version.
+    pub fn patch(&self) -> u16 {
+        self.patch
+    }
+
+    /// Returns the version encoded as `KERNEL_VERSION(major, minor, patch)`.
+    ///
+    /// Like the kernel's `LINUX_VERSION_CODE`, the patch version saturates at
+    /// 255.
+    pub fn code(&self) -> u32 {
+        ((self.major as u32) << 16) + ((self.minor as u32) << 8) + self.patch.min(255) as u32
+    }
 }
 
-fn parse_kernel_symbols(reader: &mut dyn BufRead) -> Result<BTreeMap<u64, String>, io::Error> {
-    let mut syms = BTreeMap::new();
+impl fmt::Display for KernelVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
 
-    for line in reader.lines() {
-        let line = line?;
-        let parts = line.splitn(4, ' ').collect::<Vec<_>>();
-        let addr = u64::from_str_radix(parts[0], 16)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, line.clone()))?;
-        let name = parts[2].to_owned();
-        syms.insert(addr, name);
+/// Parses the kernel version from the output of `uname -r` and `uname -v`,
+/// and from `/proc/version_signature` when present.
+pub(crate) fn parse_kernel_version(
+    release: &str,
+    version: &str,
+    signature: Option<&str>,
+) -> Option<(u32, u32, u32)> {
+    // Ubuntu: "Ubuntu 5.4.0-42.46-generic 5.4.44", the last field is the
+    // upstream version
+    if let Some(upstream) = signature.and_then(|s| s.split_whitespace().last()) {
+        if let Some(v) = parse_version_triple(upstream) {
+            return Some(v);
+        }
     }
 
-    Ok(syms)
+    // Debian: "#1 SMP Debian 4.19.208-1 (2021-09-29)", uname -r only contains
+    // the ABI version, eg 4.19.0-18
+    if let Some(pos) = version.find("Debian ") {
+        if let Some(v) = version[pos + "Debian ".len()..]
+            .split_whitespace()
+            .next()
+            .and_then(parse_version_triple)
+        {
+            return Some(v);
+        }
+    }
+
+    parse_version_triple(release)
+}
+
+// parses the leading "major.minor[.patch]" of `s`, ignoring any suffix like
+// "-rc1" or "-305.el8.x86_64"
+fn parse_version_triple(s: &str) -> Option<(u32, u32, u32)> {
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let mut parts = s[..end].split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch = match parts.next() {
+        Some(patch) => patch.parse().ok()?,
+        None => 0,
+    };
+    Some((major, minor, patch))
+}
+
+/// Raises the `RLIMIT_MEMLOCK` limit of the current process to infinity.
+///
+/// Before Linux 5.11, the memory used by eBPF maps and programs is charged
+/// against the locked memory limit, which is usually too low to load anything
+/// but trivial programs. Raising the limit requires `CAP_SYS_RESOURCE`.
+///
+/// See also [`BpfLoader::raise_memlock_rlimit`](crate::BpfLoader::raise_memlock_rlimit).
+pub fn raise_memlock_rlimit() -> Result<(), io::Error> {
+    let rlim = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns whether memory used by eBPF objects is charged against
+/// `RLIMIT_MEMLOCK` instead of the memory cgroup.
+pub(crate) fn memlock_rlimit_applies() -> bool {
+    // the accounting changed in 5.11. If the version is unknown, assume the
+    // limit applies.
+    KernelVersion::current()
+        .map(|version| version < KernelVersion::new(5, 11, 0))
+        .unwrap_or(true)
+}
+
+/// Returns whether `io_error` was likely caused by the `RLIMIT_MEMLOCK` limit
+/// being too low.
+pub(crate) fn is_memlock_error(io_error: &io::Error) -> bool {
+    if io_error.raw_os_error() != Some(libc::EPERM) || !memlock_rlimit_applies() {
+        return false;
+    }
+    let mut rlim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut rlim) } != 0 {
+        return false;
+    }
+    rlim.rlim_cur != libc::RLIM_INFINITY
 }
 
 pub(crate) fn ifindex_from_ifname(if_name: &str) -> Result<u32, io::Error> {
@@ -102,10 +466,285 @@ pub(crate) fn ifindex_from_ifname(if_name: &str) -> Result<u32, io::Error> {
     Ok(if_index)
 }
 
+/// Duplicates `fd`, setting `FD_CLOEXEC` on the new descriptor.
+pub(crate) fn dup_fd(fd: RawFd) -> Result<OwnedFd, io::Error> {
+    let ret = unsafe { fcntl(fd, F_DUPFD_CLOEXEC, 3) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Safety: fcntl returned a new descriptor that nothing else owns
+    Ok(unsafe { OwnedFd::from_raw_fd(ret) })
+}
+
+/// Waits until `fd` is readable or `timeout` expires, retrying if the wait is
+/// interrupted by a signal. A `timeout` of `None` waits forever.
+///
+/// Returns `true` if `fd` is readable and `false` if the timeout expired.
+pub(crate) fn poll_readable(fd: RawFd, timeout: Option<Duration>) -> Result<bool, io::Error> {
+    poll_readable_any(&[fd], timeout)
+}
+
+/// Like [`poll_readable`], but waits until any of `fds` is readable.
+pub(crate) fn poll_readable_any(
+    fds: &[RawFd],
+    timeout: Option<Duration>,
+) -> Result<bool, io::Error> {
+    let mut pfds = fds
+        .iter()
+        .map(|fd| pollfd {
+            fd: *fd,
+            events: POLLIN,
+            revents: 0,
+        })
+        .collect::<Vec<_>>();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let timeout_ms = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                // round up so that we never return before the timeout expires
+                let ms = remaining.as_nanos().div_ceil(1_000_000);
+                cmp::min(ms, c_int::MAX as u128) as c_int
+            }
+            None => -1,
+        };
+        // Safety: libc wrapper
+        let ret = unsafe { poll(pfds.as_mut_ptr(), pfds.len() as _, timeout_ms) };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(EINTR) {
+                continue;
+            }
+            return Err(e);
+        }
+        return Ok(ret > 0);
+    }
+}
+
+// see SCM_MAX_FD in include/net/scm.h
+const SCM_MAX_FD: usize = 253;
+
+/// Sends file descriptors over a Unix socket.
+///
+/// The descriptors are sent with `SCM_RIGHTS`, and can be received by the
+/// process at the other end of the socket with [`recv_fds`]. This allows a
+/// privileged process to load programs and create maps, then hand them over to
+/// unprivileged processes, which can use them without any capability. See
+/// [`Map::try_clone_fd`](crate::maps::Map::try_clone_fd) and
+/// [`ProgramFd::try_clone_fd`](crate::programs::ProgramFd::try_clone_fd).
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[derive(Debug, thiserror::Error)]
+/// # enum Error {
+/// #     #[error(transparent)]
+/// #     IO(#[from] std::io::Error),
+/// #     #[error(transparent)]
+/// #     Map(#[from] aya::maps::MapError),
+/// #     #[error(transparent)]
+/// #     Bpf(#[from] aya::BpfError)
+/// # }
+/// # let bpf = aya::Bpf::load(&[])?;
+/// use std::os::unix::net::UnixStream;
+/// use aya::util::send_fds;
+///
+/// let counters = bpf.map("COUNTERS")?;
+/// let socket = UnixStream::connect("/run/consumer.sock")?;
+/// send_fds(&socket, &[counters.borrow_fd()?])?;
+/// # Ok::<(), Error>(())
+/// ```
+pub fn send_fds(socket: &UnixStream, fds: &[BorrowedFd<'_>]) -> Result<(), io::Error> {
+    if fds.is_empty() || fds.len() > SCM_MAX_FD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("can't send {} file descriptors", fds.len()),
+        ));
+    }
+    let raw_fds = fds.iter().map(|fd| fd.as_raw_fd()).collect::<Vec<_>>();
+    let fds_len = mem::size_of_val(raw_fds.as_slice());
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len as u32) } as usize];
+
+    // at least one byte of data must be sent along with the descriptors
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = control.len() as _;
+
+    // Safety: the control buffer is large enough for one message with fds_len
+    // bytes of data
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as u32) as _;
+        ptr::copy_nonoverlapping(
+            raw_fds.as_ptr() as *const u8,
+            libc::CMSG_DATA(cmsg),
+            fds_len,
+        );
+    }
+
+    if unsafe { libc::sendmsg(socket.as_raw_fd(), &msg, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Receives file descriptors sent with [`send_fds`].
+///
+/// Up to `max_fds` descriptors are received. If more were sent, the extra
+/// descriptors are closed and an error is returned. The received descriptors
+/// can be turned back into maps with
+/// [`Map::from_fd`](crate::maps::Map::from_fd), and program descriptors can be
+/// used anywhere a [`ProgramFd`](crate::programs::ProgramFd) is expected.
+pub fn recv_fds(socket: &UnixStream, max_fds: usize) -> Result<Vec<OwnedFd>, io::Error> {
+    let max_fds = cmp::min(max_fds, SCM_MAX_FD);
+    let fds_len = max_fds * mem::size_of::<RawFd>();
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len as u32) } as usize];
+
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = control.len() as _;
+
+    let ret = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if ret == 0 {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    let mut fds = Vec::new();
+    // Safety: the kernel filled the control buffer with well formed messages
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                for i in 0..len / mem::size_of::<RawFd>() {
+                    fds.push(OwnedFd::from_raw_fd(ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    // the control buffer is padded, so it can fit more than max_fds
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 || fds.len() > max_fds {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("received more than {} file descriptors", max_fds),
+        ));
+    }
+
+    Ok(fds)
+}
+
+// the kernel only accepts alphanumeric characters, `_` and `.` in the names
+// of maps and programs
+fn is_obj_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Checks that `name` can be used as the name of a map or program, returning
+/// why it can't otherwise.
+pub(crate) fn validate_obj_name(name: &str) -> Result<(), &'static str> {
+    if name.len() >= BPF_OBJ_NAME_LEN {
+        return Err("names are limited to 15 characters");
+    }
+    if !name.chars().all(is_obj_name_char) {
+        return Err("names can only contain alphanumeric characters, `_` and `.`");
+    }
+    Ok(())
+}
+
+/// Makes `name` a valid map or program name, replacing the invalid characters
+/// with `_` and truncating it to 15 characters.
+pub(crate) fn sanitize_obj_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if is_obj_name_char(c) { c } else { '_' })
+        .take(BPF_OBJ_NAME_LEN - 1)
+        .collect()
+}
+
 pub(crate) fn tc_handler_make(major: u32, minor: u32) -> u32 {
     (major & TC_H_MAJ_MASK) | (minor & TC_H_MIN_MASK)
 }
 
+/// Returns the build configuration of the running kernel.
+///
+/// The configuration is read from `/boot/config-$(uname -r)` if it exists, and
+/// from `/proc/config.gz` otherwise.
+pub(crate) fn kernel_config() -> Result<String, io::Error> {
+    let release = fs::read_to_string(KERNEL_RELEASE)?;
+    let boot_config = format!("/boot/config-{}", release.trim());
+    if Path::new(&boot_config).exists() {
+        return fs::read_to_string(boot_config);
+    }
+
+    let data = gunzip(&fs::read(PROC_CONFIG_GZ)?)?;
+    String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Decompresses gzip data.
+///
+/// This is only meant to decompress `/proc/config.gz` and compressed objects.
+/// The decoder verifies the CRC-32 and the size in the trailer, and the output
+/// is capped to the declared size so that a forged stream can't make us
+/// allocate more than that.
+pub(crate) fn gunzip(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let size = match data.len().checked_sub(4) {
+        Some(pos) => u32::from_le_bytes(data[pos..].try_into().unwrap()) as u64,
+        None => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+    };
+    let mut out = Vec::new();
+    GzDecoder::new(data).take(size + 1).read_to_end(&mut out)?;
+    if out.len() as u64 > size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "gzip data larger than its declared size",
+        ));
+    }
+    Ok(out)
+}
+
+/// Computes the CRC-32 used by gzip and `.gnu_debuglink`.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+        *entry = crc;
+    }
+
+    !data.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
 /// Include bytes from a file for use in a subsequent [`crate::Bpf::load`].
 ///
 /// This macro differs from the standard `include_bytes!` macro since it also ensures that
@@ -145,8 +784,62 @@ macro_rules! include_bytes_aligned {
 
 #[cfg(test)]
 mod tests {
+    use std::os::unix::io::AsFd;
+
     use super::*;
 
+    #[test]
+    fn test_obj_name() {
+        assert!(validate_obj_name("my_map.v2").is_ok());
+        assert!(validate_obj_name("sixteen_chars_xx").is_err());
+        assert!(validate_obj_name("net/connect").is_err());
+        assert_eq!(sanitize_obj_name("net/connect"), "net_connect");
+        assert_eq!(
+            sanitize_obj_name("instance1_trace_connect"),
+            "instance1_trace"
+        );
+        assert_eq!(sanitize_obj_name("é_map"), "__map");
+    }
+
+    #[test]
+    fn test_send_recv_fds() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let file = File::open("/dev/null").unwrap();
+        let file2 = File::open("/dev/null").unwrap();
+
+        send_fds(&sender, &[file.as_fd(), file2.as_fd()]).unwrap();
+        let fds = recv_fds(&receiver, 2).unwrap();
+        assert_eq!(fds.len(), 2);
+        assert_ne!(fds[0].as_raw_fd(), file.as_raw_fd());
+        assert_ne!(fds[1].as_raw_fd(), file2.as_raw_fd());
+    }
+
+    #[test]
+    fn test_recv_fds_truncated() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let file = File::open("/dev/null").unwrap();
+
+        send_fds(&sender, &[file.as_fd(), file.as_fd()]).unwrap();
+        assert!(recv_fds(&receiver, 1).is_err());
+    }
+
+    #[test]
+    fn test_send_no_fds() {
+        let (sender, _receiver) = UnixStream::pair().unwrap();
+        assert!(send_fds(&sender, &[]).is_err());
+    }
+
+    #[test]
+    fn test_poll_readable() {
+        use std::io::Write;
+
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        assert!(!poll_readable(receiver.as_raw_fd(), Some(Duration::from_millis(1))).unwrap());
+        sender.write_all(b"x").unwrap();
+        assert!(poll_readable(receiver.as_raw_fd(), Some(Duration::from_secs(0))).unwrap());
+        assert!(poll_readable(receiver.as_raw_fd(), None).unwrap());
+    }
+
     #[test]
     fn test_parse_online_cpus() {
         assert_eq!(parse_cpu_ranges("0").unwrap(), vec![0]);
@@ -174,7 +867,9 @@ mod tests {
         let data = "0000000000002000 A irq_stack_backing_store\n\
                           0000000000006000 A cpu_tss_rw [foo bar]\n"
             .as_bytes();
-        let syms = parse_kernel_symbols(&mut BufReader::new(data)).unwrap();
+        let syms = KernelSymbols::parse(&mut BufReader::new(data))
+            .unwrap()
+            .into_map();
         assert_eq!(syms.keys().collect::<Vec<_>>(), vec![&0x2000, &0x6000]);
         assert_eq!(
             syms.get(&0x2000u64).unwrap().as_str(),
@@ -182,4 +877,248 @@ mod tests {
         );
         assert_eq!(syms.get(&0x6000u64).unwrap().as_str(), "cpu_tss_rw");
     }
+
+    #[test]
+    fn test_kernel_symbols() {
+        let data = "ffffffff81000000 T _stext\n\
+                    ffffffff81001000 T try_to_wake_up\n\
+                    ffffffffc0001000 t init_module\t[foo]\n\
+                    ffffffffc0002000 t init_module\t[bar]\n"
+            .as_bytes();
+        let ksyms = KernelSymbols::parse(&mut BufReader::new(data)).unwrap();
+
+        let (sym, offset) = ksyms.resolve(0xffffffff81001010).unwrap();
+        assert_eq!(sym.name, "try_to_wake_up");
+        assert_eq!(sym.kind, 'T');
+        assert_eq!(sym.module, None);
+        assert_eq!(offset, 0x10);
+        assert!(ksyms.resolve(0x1000).is_none());
+
+        assert_eq!(ksyms.address_of("try_to_wake_up"), Some(0xffffffff81001000));
+        assert_eq!(ksyms.address_of("nope"), None);
+        assert_eq!(
+            ksyms.address_of_in_module("init_module", Some("bar")),
+            Some(0xffffffffc0002000)
+        );
+        assert_eq!(ksyms.address_of_in_module("init_module", None), None);
+        assert_eq!(ksyms.symbols_named("init_module").count(), 2);
+    }
+
+    #[test]
+    fn test_kernel_symbols_aliases() {
+        let data = "ffffffff81002000 T __x64_sys_getpid
+                    ffffffff81001000 T do_exit
+                    ffffffff81002000 T __ia32_sys_getpid
+"
+        .as_bytes();
+        let ksyms = KernelSymbols::parse(&mut BufReader::new(data)).unwrap();
+
+        let (sym, offset) = ksyms.resolve(0xffffffff81002008).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("__x64_sys_getpid", 8));
+        assert_eq!(ksyms.resolve(0xffffffff81001fff).unwrap().0.name, "do_exit");
+        assert_eq!(
+            ksyms.address_of("__ia32_sys_getpid"),
+            Some(0xffffffff81002000)
+        );
+        assert_eq!(
+            ksyms
+                .iter()
+                .map(|sym| sym.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["do_exit", "__x64_sys_getpid", "__ia32_sys_getpid"]
+        );
+    }
+
+    #[test]
+    fn test_kernel_symbols_restricted() {
+        let data = "0000000000000000 T _stext
+                    0000000000000000 T try_to_wake_up
+"
+        .as_bytes();
+        let ksyms = KernelSymbols::parse(&mut BufReader::new(data)).unwrap();
+
+        assert_eq!(ksyms.iter().count(), 2);
+        assert_eq!(ksyms.symbols_named("try_to_wake_up").count(), 1);
+        assert!(ksyms.resolve(0x10).is_none());
+        assert_eq!(ksyms.address_of("try_to_wake_up"), None);
+    }
+
+    #[test]
+    fn test_parse_available_events() {
+        let data = "sched:sched_switch\nsyscalls:sys_enter_openat\n\n";
+        assert_eq!(
+            parse_available_events(data),
+            vec![
+                ("sched".to_owned(), "sched_switch".to_owned()),
+                ("syscalls".to_owned(), "sys_enter_openat".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gunzip_stored() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x0e, 0x00, 0xf1,
+            0xff, 0x43, 0x4f, 0x4e, 0x46, 0x49, 0x47, 0x5f, 0x48, 0x5a, 0x3d, 0x31, 0x30, 0x30,
+            0x0a, 0xfe, 0x32, 0x44, 0x15, 0x0e, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(gunzip(&data).unwrap(), b"CONFIG_HZ=100\n");
+    }
+
+    #[test]
+    fn test_gunzip_fixed() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x73, 0xf6, 0xf7, 0x73,
+            0xf3, 0x74, 0x8f, 0xf7, 0x88, 0xb2, 0x35, 0x32, 0x35, 0xe0, 0x52, 0x56, 0x70, 0x86,
+            0xf0, 0xdd, 0xfc, 0xfd, 0x15, 0x32, 0x8b, 0x15, 0xf2, 0xf2, 0x4b, 0x14, 0x8a, 0x53,
+            0x4b, 0xb8, 0xa0, 0xa2, 0x4e, 0x01, 0x6e, 0xb6, 0x95, 0x48, 0x9c, 0xf8, 0xe0, 0xc8,
+            0x60, 0x67, 0x47, 0x1f, 0x1f, 0x54, 0x41, 0x2f, 0xcf, 0x10, 0x84, 0x80, 0x8f, 0x3f,
+            0x50, 0x41, 0x98, 0x6b, 0x50, 0xb0, 0xa7, 0xbf, 0x9f, 0xad, 0x92, 0x12, 0x17, 0x00,
+            0x74, 0xdf, 0xe5, 0x51, 0x70, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            std::str::from_utf8(&gunzip(&data).unwrap()).unwrap(),
+            "CONFIG_HZ=250\n\
+             # CONFIG_FOO is not set\n\
+             CONFIG_BPF=y\n\
+             CONFIG_BPF_SYSCALL=y\n\
+             CONFIG_BPF_JIT=y\n\
+             CONFIG_LOCALVERSION=\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_gunzip_dynamic() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x6d, 0x92, 0x3b, 0x0a,
+            0x80, 0x40, 0x14, 0x03, 0x7b, 0x4f, 0xe3, 0x4b, 0xd6, 0x5f, 0xb1, 0x95, 0xa0, 0xd8,
+            0xb8, 0x16, 0xf6, 0x9e, 0xc0, 0x03, 0x78, 0x7b, 0xb1, 0xd4, 0xb1, 0x0d, 0x19, 0x18,
+            0x42, 0xc6, 0xb2, 0x4e, 0xcb, 0x7c, 0x94, 0x6d, 0x5f, 0xca, 0x7a, 0xd4, 0xf9, 0xac,
+            0xc6, 0x57, 0x12, 0xf9, 0xfa, 0x24, 0x42, 0x62, 0x50, 0x09, 0x9d, 0x06, 0x49, 0x0b,
+            0xaa, 0x43, 0xa7, 0x47, 0x32, 0xd0, 0xb0, 0x46, 0x29, 0x68, 0x1d, 0x22, 0x68, 0xb6,
+            0x28, 0x1e, 0x0d, 0xc1, 0x96, 0x2d, 0xba, 0x47, 0x4f, 0x70, 0xe0, 0x9a, 0xb4, 0x57,
+            0x00, 0x14, 0x47, 0x17, 0xed, 0x95, 0x08, 0x72, 0x77, 0xd1, 0x5e, 0x1d, 0x41, 0x4e,
+            0x2f, 0xda, 0x9b, 0x87, 0x31, 0xb7, 0xf7, 0xcf, 0x65, 0xf8, 0x19, 0x73, 0x7b, 0xd3,
+            0xde, 0xbc, 0x8d, 0xb9, 0xbd, 0x69, 0xef, 0xe7, 0x39, 0x37, 0xa5, 0xde, 0x3e, 0xe8,
+            0xee, 0x02, 0x00, 0x00,
+        ];
+        let expected = (0..40)
+            .map(|i| {
+                format!(
+                    "CONFIG_OPTION_{}={}\n",
+                    i,
+                    if i % 3 == 0 { "m" } else { "y" }
+                )
+            })
+            .collect::<String>();
+        assert_eq!(gunzip(&data).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_gunzip_invalid() {
+        assert!(gunzip(b"CONFIG_HZ=100\n").is_err());
+    }
+
+    const GZIP_FIXED: [u8; 34] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x73, 0xf6, 0xf7, 0x73, 0xf3,
+        0x74, 0x8f, 0xf7, 0x88, 0xb2, 0x35, 0x34, 0x30, 0xe0, 0x02, 0x00, 0xfe, 0x32, 0x44, 0x15,
+        0x0e, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_gunzip_truncated() {
+        let data = GZIP_FIXED.to_vec();
+        assert_eq!(gunzip(&data).unwrap(), b"CONFIG_HZ=100\n");
+        for len in 0..data.len() {
+            assert!(gunzip(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_gunzip_malformed() {
+        let data = GZIP_FIXED.to_vec();
+
+        // wrong CRC-32
+        let mut bad = data.clone();
+        bad[data.len() - 8] ^= 0xff;
+        assert!(gunzip(&bad).is_err());
+
+        // declared size smaller or larger than the data
+        for size in &[13u8, 15] {
+            let mut bad = data.clone();
+            bad[data.len() - 4] = *size;
+            assert!(gunzip(&bad).is_err());
+        }
+
+        // reserved block type
+        let mut bad = data.clone();
+        bad[10] |= 0x06;
+        assert!(gunzip(&bad).is_err());
+
+        // stored block with a bad length complement
+        let mut bad = vec![
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x01, 0x00, 0xfe,
+            0xfe, 0x41,
+        ];
+        bad.extend_from_slice(&crc32(b"A").to_le_bytes());
+        bad.extend_from_slice(&1u32.to_le_bytes());
+        assert!(gunzip(&bad).is_err());
+
+        // trailing garbage
+        let mut bad = data;
+        bad.insert(bad.len() - 8, 0);
+        assert!(gunzip(&bad).is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_parse_kernel_version() {
+        let version = "#1 SMP PREEMPT_DYNAMIC Thu Jan  1 00:00:00 UTC 2023";
+        assert_eq!(
+            parse_kernel_version("5.15.0-76-generic", version, None),
+            Some((5, 15, 0))
+        );
+        assert_eq!(
+            parse_kernel_version("6.1-rc1", version, None),
+            Some((6, 1, 0))
+        );
+        assert_eq!(
+            parse_kernel_version("4.18.0-305.el8.x86_64", version, None),
+            Some((4, 18, 0))
+        );
+        assert_eq!(parse_kernel_version("foo", version, None), None);
+    }
+
+    #[test]
+    fn test_parse_kernel_version_distro() {
+        assert_eq!(
+            parse_kernel_version(
+                "5.4.0-42-generic",
+                "#46-Ubuntu SMP Fri Jul 10 00:24:02 UTC 2020",
+                Some("Ubuntu 5.4.0-42.46-generic 5.4.44\n")
+            ),
+            Some((5, 4, 44))
+        );
+        assert_eq!(
+            parse_kernel_version(
+                "4.19.0-18-amd64",
+                "#1 SMP Debian 4.19.208-1 (2021-09-29)",
+                None
+            ),
+            Some((4, 19, 208))
+        );
+    }
+
+    #[test]
+    fn test_kernel_version_code() {
+        assert_eq!(KernelVersion::new(5, 4, 44).code(), 0x05042c);
+        assert_eq!(KernelVersion::new(4, 19, 300).code(), 0x0413ff);
+        assert!(KernelVersion::new(4, 19, 300) < KernelVersion::new(5, 0, 0));
+        assert_eq!(KernelVersion::new(6, 1, 0).to_string(), "6.1.0");
+    }
 }
@@ -0,0 +1,182 @@
+//! AF_XDP sockets.
+//!
+//! AF_XDP sockets (XSKs) receive the packets that [`Xdp`](crate::programs::Xdp)
+//! programs redirect to them through an [`XskMap`](crate::maps::XskMap), and
+//! can transmit packets directly from user space. Packet data lives in a
+//! [`Umem`] shared with the kernel, which with supporting drivers is used for
+//! DMA directly, so that packets are never copied.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # let mut bpf = aya::Bpf::load(&[])?;
+//! use std::convert::TryFrom;
+//! use aya::{
+//!     maps::XskMap,
+//!     programs::{Xdp, XdpFlags},
+//!     xdp::{Umem, UmemConfig, XdpDesc, XskConfig, XskSocket},
+//! };
+//!
+//! let mut umem = Umem::new(4096, UmemConfig::default())?;
+//! let mut socket = XskSocket::new(&umem, "eth0", 0, XskConfig::default())?;
+//!
+//! let mut sockets = XskMap::try_from(bpf.map_mut("XSKS")?)?;
+//! sockets.set(0, &socket, 0)?;
+//!
+//! let program = bpf.program_mut_typed::<Xdp>("redirect_xsk")?;
+//! program.load()?;
+//! program.attach("eth0", XdpFlags::default())?;
+//!
+//! // give all the frames to the kernel so it can receive packets into them
+//! let frames = (0..umem.frame_count())
+//!     .map(|i| umem.frame_addr(i))
+//!     .collect::<Vec<_>>();
+//! umem.fill_queue().produce(&frames);
+//!
+//! let mut descs = vec![XdpDesc::default(); 64];
+//! loop {
+//!     socket.poll(None)?;
+//!     let count = socket.rx_queue().unwrap().consume(&mut descs);
+//!     for desc in &descs[..count] {
+//!         // safe because the frame was just received and isn't handed back
+//!         // to the kernel until the loop is done with it
+//!         let packet = unsafe { umem.data(desc) }.unwrap();
+//!         println!("received {} bytes", packet.len());
+//!     }
+//!     // hand the frames back to the kernel
+//!     let frames = descs[..count].iter().map(|desc| desc.addr).collect::<Vec<_>>();
+//!     umem.fill_queue().produce(&frames);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+mod ring;
+mod socket;
+mod umem;
+
+use std::{
+    io, mem,
+    os::unix::io::{FromRawFd, OwnedFd, RawFd},
+};
+
+use libc::{c_int, c_void, socklen_t, SOCK_CLOEXEC, SOCK_RAW, SOL_XDP};
+use thiserror::Error;
+
+use crate::generated::{xdp_mmap_offsets, xdp_ring_offset, XDP_MMAP_OFFSETS};
+
+pub(crate) use ring::Ring;
+pub use ring::{CompletionQueue, FillQueue, RxQueue, TxQueue, XdpDesc};
+pub use socket::{XskBindFlags, XskConfig, XskSocket, XskStatistics};
+pub use umem::{Umem, UmemConfig};
+
+// libc only defines AF_XDP for glibc
+const AF_XDP: c_int = 44;
+
+/// AF_XDP socket error.
+#[derive(Debug, Error)]
+pub enum XskError {
+    /// A ring size is not a power of two.
+    #[error("invalid ring size {size}, the value must be a power of two")]
+    InvalidRingSize { size: u32 },
+
+    /// Neither the RX nor the TX ring of a socket was enabled.
+    #[error("at least one of the RX and TX rings must be enabled")]
+    NoRings,
+
+    /// The network interface does not exist.
+    #[error("unknown network interface {name}")]
+    UnknownInterface { name: String },
+
+    /// A syscall failed.
+    #[error("`{call}` failed")]
+    SyscallError {
+        /// The name of the syscall which failed.
+        call: String,
+        /// The [`io::Error`] returned by the syscall.
+        #[source]
+        io_error: io::Error,
+    },
+}
+
+fn check_ring_size(size: u32) -> Result<(), XskError> {
+    if !size.is_power_of_two() {
+        return Err(XskError::InvalidRingSize { size });
+    }
+    Ok(())
+}
+
+fn xsk_socket() -> Result<OwnedFd, XskError> {
+    let fd = unsafe { libc::socket(AF_XDP, SOCK_RAW | SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(XskError::SyscallError {
+            call: "socket".to_owned(),
+            io_error: io::Error::last_os_error(),
+        });
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn setsockopt<T>(
+    fd: RawFd,
+    level: c_int,
+    option: u32,
+    option_name: &str,
+    value: &T,
+) -> Result<(), XskError> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            option as c_int,
+            value as *const T as *const c_void,
+            mem::size_of::<T>() as socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(XskError::SyscallError {
+            call: format!("setsockopt({})", option_name),
+            io_error: io::Error::last_os_error(),
+        });
+    }
+    Ok(())
+}
+
+fn mmap_offsets(fd: RawFd) -> Result<xdp_mmap_offsets, XskError> {
+    let mut offsets = unsafe { mem::zeroed::<xdp_mmap_offsets>() };
+    let mut len = mem::size_of::<xdp_mmap_offsets>() as socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            SOL_XDP,
+            XDP_MMAP_OFFSETS as c_int,
+            &mut offsets as *mut _ as *mut c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(XskError::SyscallError {
+            call: "getsockopt(XDP_MMAP_OFFSETS)".to_owned(),
+            io_error: io::Error::last_os_error(),
+        });
+    }
+
+    // before 5.4 the offsets didn't include the flags
+    if len as usize == 4 * 3 * mem::size_of::<u64>() {
+        let v1 = unsafe { *(&offsets as *const _ as *const [[u64; 3]; 4]) };
+        let ring = |[producer, consumer, desc]: [u64; 3]| xdp_ring_offset {
+            producer,
+            consumer,
+            desc,
+            // point the flags at the padding after the consumer index, which
+            // is always zero
+            flags: consumer + mem::size_of::<u32>() as u64,
+        };
+        offsets = xdp_mmap_offsets {
+            rx: ring(v1[0]),
+            tx: ring(v1[1]),
+            fr: ring(v1[2]),
+            cr: ring(v1[3]),
+        };
+    }
+
+    Ok(offsets)
+}
@@ -0,0 +1,294 @@
+//! The rings shared with the kernel by AF_XDP sockets.
+use std::{
+    cmp,
+    ffi::c_void,
+    io, mem,
+    os::unix::io::RawFd,
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use libc::{MAP_FAILED, MAP_POPULATE, MAP_SHARED, PROT_READ, PROT_WRITE};
+
+use crate::{
+    generated::{xdp_ring_offset, XDP_RING_NEED_WAKEUP},
+    xdp::XskError,
+};
+
+/// A packet descriptor, as found in the RX and TX rings.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct XdpDesc {
+    /// The address of the packet data, as an offset into the
+    /// [`Umem`](crate::xdp::Umem).
+    pub addr: u64,
+    /// The length of the packet data.
+    pub len: u32,
+    /// Descriptor options.
+    pub options: u32,
+}
+
+struct RingMmap {
+    addr: *mut c_void,
+    len: usize,
+}
+
+impl Drop for RingMmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr, self.len) };
+    }
+}
+
+// A single producer, single consumer ring of `T`s. Depending on the ring, the
+// kernel is either the producer or the consumer.
+pub(crate) struct Ring<T> {
+    _mmap: Option<RingMmap>,
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    descs: *mut T,
+    size: u32,
+    // local copies of the indices, refreshed only when the ring looks full
+    // (or empty) so that we don't bounce the cache lines shared with the
+    // kernel on every operation
+    cached_prod: u32,
+    cached_cons: u32,
+}
+
+// Safety: the ring memory is only accessed through `&mut Ring`
+unsafe impl<T: Send> Send for Ring<T> {}
+
+impl<T: Copy> Ring<T> {
+    /// Maps the ring of `size` entries found at page offset `pgoff` of the
+    /// socket `fd`.
+    pub(crate) fn map(
+        fd: RawFd,
+        offsets: &xdp_ring_offset,
+        size: u32,
+        pgoff: u64,
+    ) -> Result<Ring<T>, XskError> {
+        let len = offsets.desc as usize + size as usize * mem::size_of::<T>();
+        let addr = unsafe {
+            libc::mmap64(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED | MAP_POPULATE,
+                fd,
+                pgoff as libc::off64_t,
+            )
+        };
+        if addr == MAP_FAILED {
+            return Err(XskError::SyscallError {
+                call: "mmap".to_owned(),
+                io_error: io::Error::last_os_error(),
+            });
+        }
+
+        let base = addr as *mut u8;
+        unsafe {
+            Ok(Ring::from_raw(
+                Some(RingMmap { addr, len }),
+                base.add(offsets.producer as usize) as *const AtomicU32,
+                base.add(offsets.consumer as usize) as *const AtomicU32,
+                base.add(offsets.flags as usize) as *const AtomicU32,
+                base.add(offsets.desc as usize) as *mut T,
+                size,
+            ))
+        }
+    }
+
+    unsafe fn from_raw(
+        mmap: Option<RingMmap>,
+        producer: *const AtomicU32,
+        consumer: *const AtomicU32,
+        flags: *const AtomicU32,
+        descs: *mut T,
+        size: u32,
+    ) -> Ring<T> {
+        Ring {
+            _mmap: mmap,
+            producer,
+            consumer,
+            flags,
+            descs,
+            size,
+            cached_prod: (*producer).load(Ordering::Relaxed),
+            cached_cons: (*consumer).load(Ordering::Relaxed),
+        }
+    }
+
+    fn producer(&self) -> &AtomicU32 {
+        unsafe { &*self.producer }
+    }
+
+    fn consumer(&self) -> &AtomicU32 {
+        unsafe { &*self.consumer }
+    }
+
+    /// Returns true if the kernel needs to be woken up to process the ring.
+    pub(crate) fn needs_wakeup(&self) -> bool {
+        unsafe { &*self.flags }.load(Ordering::Relaxed) & XDP_RING_NEED_WAKEUP != 0
+    }
+
+    /// Writes as many of `items` as fit in the ring and returns how many were
+    /// written.
+    pub(crate) fn produce(&mut self, items: &[T]) -> usize {
+        let mut free = self.size - self.cached_prod.wrapping_sub(self.cached_cons);
+        if (free as usize) < items.len() {
+            self.cached_cons = self.consumer().load(Ordering::Acquire);
+            free = self.size - self.cached_prod.wrapping_sub(self.cached_cons);
+        }
+
+        let count = cmp::min(free as usize, items.len());
+        let mask = self.size - 1;
+        for (i, item) in items[..count].iter().enumerate() {
+            let index = self.cached_prod.wrapping_add(i as u32) & mask;
+            unsafe { ptr::write_volatile(self.descs.add(index as usize), *item) };
+        }
+        self.cached_prod = self.cached_prod.wrapping_add(count as u32);
+        self.producer().store(self.cached_prod, Ordering::Release);
+
+        count
+    }
+
+    /// Reads as many entries as available into `items` and returns how many
+    /// were read.
+    pub(crate) fn consume(&mut self, items: &mut [T]) -> usize {
+        let mut available = self.cached_prod.wrapping_sub(self.cached_cons);
+        if (available as usize) < items.len() {
+            self.cached_prod = self.producer().load(Ordering::Acquire);
+            available = self.cached_prod.wrapping_sub(self.cached_cons);
+        }
+
+        let count = cmp::min(available as usize, items.len());
+        let mask = self.size - 1;
+        for (i, item) in items[..count].iter_mut().enumerate() {
+            let index = self.cached_cons.wrapping_add(i as u32) & mask;
+            *item = unsafe { ptr::read_volatile(self.descs.add(index as usize)) };
+        }
+        self.cached_cons = self.cached_cons.wrapping_add(count as u32);
+        self.consumer().store(self.cached_cons, Ordering::Release);
+
+        count
+    }
+}
+
+/// The fill ring of a [`Umem`](crate::xdp::Umem).
+///
+/// Frames must be placed in the fill ring before the kernel can use them to
+/// receive packets.
+pub struct FillQueue(pub(crate) Ring<u64>);
+
+impl FillQueue {
+    /// Gives the frames at `addrs` to the kernel. Returns the number of
+    /// frames that fit in the ring.
+    pub fn produce(&mut self, addrs: &[u64]) -> usize {
+        self.0.produce(addrs)
+    }
+
+    /// Returns true if the kernel must be woken up, for example with
+    /// [`XskSocket::poll`](crate::xdp::XskSocket::poll), to refill its
+    /// receive buffers.
+    ///
+    /// Always returns false unless the socket was bound with
+    /// [`XskBindFlags::USE_NEED_WAKEUP`](crate::xdp::XskBindFlags::USE_NEED_WAKEUP).
+    pub fn needs_wakeup(&self) -> bool {
+        self.0.needs_wakeup()
+    }
+}
+
+/// The completion ring of a [`Umem`](crate::xdp::Umem).
+///
+/// The kernel places frames in the completion ring once they have been
+/// transmitted.
+pub struct CompletionQueue(pub(crate) Ring<u64>);
+
+impl CompletionQueue {
+    /// Reads the addresses of the transmitted frames into `addrs`. Returns the
+    /// number of addresses read.
+    pub fn consume(&mut self, addrs: &mut [u64]) -> usize {
+        self.0.consume(addrs)
+    }
+}
+
+/// The RX ring of an [`XskSocket`](crate::xdp::XskSocket).
+pub struct RxQueue(pub(crate) Ring<XdpDesc>);
+
+impl RxQueue {
+    /// Reads the descriptors of the received packets into `descs`. Returns the
+    /// number of descriptors read.
+    pub fn consume(&mut self, descs: &mut [XdpDesc]) -> usize {
+        self.0.consume(descs)
+    }
+}
+
+/// The TX ring of an [`XskSocket`](crate::xdp::XskSocket).
+pub struct TxQueue(pub(crate) Ring<XdpDesc>);
+
+impl TxQueue {
+    /// Queues the packets described by `descs` for transmission. Returns the
+    /// number of descriptors that fit in the ring.
+    ///
+    /// Call [`XskSocket::wakeup`](crate::xdp::XskSocket::wakeup) to have the
+    /// kernel start sending.
+    pub fn produce(&mut self, descs: &[XdpDesc]) -> usize {
+        self.0.produce(descs)
+    }
+
+    /// Returns true if the kernel must be woken up with
+    /// [`XskSocket::wakeup`](crate::xdp::XskSocket::wakeup) to transmit the
+    /// queued packets.
+    ///
+    /// Always returns true unless the socket was bound with
+    /// [`XskBindFlags::USE_NEED_WAKEUP`](crate::xdp::XskBindFlags::USE_NEED_WAKEUP).
+    pub fn needs_wakeup(&self) -> bool {
+        self.0.needs_wakeup()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct RingMem {
+        producer: AtomicU32,
+        consumer: AtomicU32,
+        flags: AtomicU32,
+        descs: [u64; 4],
+    }
+
+    unsafe fn ring(mem: &mut RingMem) -> Ring<u64> {
+        Ring::from_raw(
+            None,
+            &mem.producer,
+            &mem.consumer,
+            &mem.flags,
+            mem.descs.as_mut_ptr(),
+            4,
+        )
+    }
+
+    #[test]
+    fn test_ring_wrap_around() {
+        let mut mem = RingMem::default();
+        let mut producer = unsafe { ring(&mut mem) };
+        let mut consumer = unsafe { ring(&mut mem) };
+        let mut out = [0u64; 4];
+
+        assert_eq!(producer.produce(&[1, 2, 3]), 3);
+        assert_eq!(consumer.consume(&mut out[..2]), 2);
+        assert_eq!(&out[..2], &[1, 2]);
+
+        // one entry is still in flight, so only three more fit
+        assert_eq!(producer.produce(&[4, 5, 6, 7]), 3);
+        assert_eq!(consumer.consume(&mut out), 4);
+        assert_eq!(out, [3, 4, 5, 6]);
+        assert_eq!(consumer.consume(&mut out), 0);
+
+        mem.flags.store(XDP_RING_NEED_WAKEUP, Ordering::Relaxed);
+        assert!(producer.needs_wakeup());
+    }
+}
@@ -0,0 +1,362 @@
+//! AF_XDP sockets.
+use std::{
+    ffi::CString,
+    io, mem,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+    ptr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use libc::{
+    c_int, c_void, if_nametoindex, sockaddr, socklen_t, EAGAIN, EBUSY, ENETDOWN, ENOBUFS,
+    MSG_DONTWAIT, SOL_SOCKET, SOL_XDP,
+};
+
+use crate::{
+    generated::{
+        sockaddr_xdp, xdp_statistics, SO_BUSY_POLL, SO_BUSY_POLL_BUDGET, SO_PREFER_BUSY_POLL,
+        XDP_COPY, XDP_PGOFF_RX_RING, XDP_PGOFF_TX_RING, XDP_RX_RING, XDP_SHARED_UMEM,
+        XDP_STATISTICS, XDP_TX_RING, XDP_USE_NEED_WAKEUP, XDP_ZEROCOPY,
+    },
+    util::poll_readable,
+    xdp::{
+        check_ring_size, mmap_offsets, setsockopt, umem::UmemArea, xsk_socket, Ring, RxQueue,
+        TxQueue, Umem, XskError, AF_XDP,
+    },
+};
+
+bitflags! {
+    /// Flags passed to `bind()` when creating an [`XskSocket`].
+    #[derive(Default)]
+    pub struct XskBindFlags: u16 {
+        /// Force copy mode.
+        const COPY = XDP_COPY as u16;
+        /// Force zero-copy mode. Binding fails if the driver doesn't support
+        /// it.
+        const ZEROCOPY = XDP_ZEROCOPY as u16;
+        /// Only require waking up the kernel when the rings say so, see
+        /// [`FillQueue::needs_wakeup`](crate::xdp::FillQueue::needs_wakeup)
+        /// and [`TxQueue::needs_wakeup`].
+        const USE_NEED_WAKEUP = XDP_USE_NEED_WAKEUP as u16;
+    }
+}
+
+/// [`XskSocket`] configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct XskConfig {
+    /// The number of entries of the RX ring. Must be a power of two, or 0 to
+    /// disable receiving.
+    pub rx_size: u32,
+    /// The number of entries of the TX ring. Must be a power of two, or 0 to
+    /// disable transmitting.
+    pub tx_size: u32,
+    /// The flags used to bind the socket. Ignored by sockets sharing the
+    /// [`Umem`] of another socket.
+    pub bind_flags: XskBindFlags,
+}
+
+impl Default for XskConfig {
+    fn default() -> Self {
+        XskConfig {
+            rx_size: 2048,
+            tx_size: 2048,
+            bind_flags: XskBindFlags::USE_NEED_WAKEUP,
+        }
+    }
+}
+
+/// AF_XDP socket statistics.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct XskStatistics {
+    /// Packets dropped for reasons other than invalid descriptors.
+    pub rx_dropped: u64,
+    /// Packets dropped because of invalid descriptors.
+    pub rx_invalid_descs: u64,
+    /// Packets not sent because of invalid descriptors.
+    pub tx_invalid_descs: u64,
+    /// Packets dropped because the RX ring was full. Requires kernel 5.9.
+    pub rx_ring_full: u64,
+    /// Times the fill ring was empty when the kernel needed a frame. Requires
+    /// kernel 5.9.
+    pub rx_fill_ring_empty_descs: u64,
+    /// Times the TX ring was empty when the kernel tried to transmit.
+    /// Requires kernel 5.9.
+    pub tx_ring_empty_descs: u64,
+}
+
+/// An AF_XDP socket.
+///
+/// An `XskSocket` is bound to a queue of a network interface, and receives
+/// the packets that arrive on that queue and that XDP programs redirect to
+/// it through an [`XskMap`](crate::maps::XskMap). Packets are received into
+/// and transmitted from the frames of a [`Umem`].
+///
+/// The first socket created with a UMEM uses the UMEM's fill and completion
+/// rings. Further sockets share them, and must be bound to the same
+/// interface and queue.
+///
+/// See the [module level documentation](crate::xdp) for a complete example.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.18.
+pub struct XskSocket {
+    // `None` when the socket is the one the UMEM was registered with
+    fd: Option<OwnedFd>,
+    umem: Arc<UmemArea>,
+    rx: Option<RxQueue>,
+    tx: Option<TxQueue>,
+    if_index: u32,
+    queue_id: u32,
+}
+
+impl XskSocket {
+    /// Creates a socket using `umem` and binds it to queue `queue_id` of
+    /// `interface`.
+    ///
+    /// If the given `interface` does not exist
+    /// [`XskError::UnknownInterface`] is returned.
+    pub fn new(
+        umem: &Umem,
+        interface: &str,
+        queue_id: u32,
+        config: XskConfig,
+    ) -> Result<XskSocket, XskError> {
+        if config.rx_size == 0 && config.tx_size == 0 {
+            return Err(XskError::NoRings);
+        }
+        for size in [config.rx_size, config.tx_size] {
+            if size != 0 {
+                check_ring_size(size)?;
+            }
+        }
+
+        let c_interface = CString::new(interface).unwrap();
+        let if_index = unsafe { if_nametoindex(c_interface.as_ptr()) };
+        if if_index == 0 {
+            return Err(XskError::UnknownInterface {
+                name: interface.to_string(),
+            });
+        }
+
+        let area = umem.area.clone();
+        let shared = area.fd_bound.swap(true, Ordering::SeqCst);
+        let fd = if shared { Some(xsk_socket()?) } else { None };
+        let mut socket = XskSocket {
+            fd,
+            umem: area,
+            rx: None,
+            tx: None,
+            if_index,
+            queue_id,
+        };
+        let ret = socket.bind(&config, shared);
+        if ret.is_err() && !shared {
+            socket.umem.fd_bound.store(false, Ordering::SeqCst);
+        }
+        ret.map(|_| socket)
+    }
+
+    fn bind(&mut self, config: &XskConfig, shared: bool) -> Result<(), XskError> {
+        let fd = self.as_raw_fd();
+        if config.rx_size != 0 {
+            setsockopt(fd, SOL_XDP, XDP_RX_RING, "XDP_RX_RING", &config.rx_size)?;
+        }
+        if config.tx_size != 0 {
+            setsockopt(fd, SOL_XDP, XDP_TX_RING, "XDP_TX_RING", &config.tx_size)?;
+        }
+
+        let offsets = mmap_offsets(fd)?;
+        if config.rx_size != 0 {
+            self.rx = Some(RxQueue(Ring::map(
+                fd,
+                &offsets.rx,
+                config.rx_size,
+                XDP_PGOFF_RX_RING as u64,
+            )?));
+        }
+        if config.tx_size != 0 {
+            self.tx = Some(TxQueue(Ring::map(
+                fd,
+                &offsets.tx,
+                config.tx_size,
+                XDP_PGOFF_TX_RING as u64,
+            )?));
+        }
+
+        let mut addr = unsafe { mem::zeroed::<sockaddr_xdp>() };
+        addr.sxdp_family = AF_XDP as u16;
+        addr.sxdp_ifindex = self.if_index;
+        addr.sxdp_queue_id = self.queue_id;
+        if shared {
+            // the kernel rejects any other flag when sharing a UMEM
+            addr.sxdp_flags = XDP_SHARED_UMEM as u16;
+            addr.sxdp_shared_umem_fd = self.umem.fd.as_raw_fd() as u32;
+        } else {
+            addr.sxdp_flags = config.bind_flags.bits();
+        }
+        let ret = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const _ as *const sockaddr,
+                mem::size_of::<sockaddr_xdp>() as socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(XskError::SyscallError {
+                call: "bind".to_owned(),
+                io_error: io::Error::last_os_error(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the index of the interface the socket is bound to.
+    pub fn if_index(&self) -> u32 {
+        self.if_index
+    }
+
+    /// Returns the queue the socket is bound to.
+    pub fn queue_id(&self) -> u32 {
+        self.queue_id
+    }
+
+    /// Returns the RX ring, or `None` if the socket was created with
+    /// [`XskConfig::rx_size`] set to 0.
+    pub fn rx_queue(&mut self) -> Option<&mut RxQueue> {
+        self.rx.as_mut()
+    }
+
+    /// Returns the TX ring, or `None` if the socket was created with
+    /// [`XskConfig::tx_size`] set to 0.
+    pub fn tx_queue(&mut self) -> Option<&mut TxQueue> {
+        self.tx.as_mut()
+    }
+
+    /// Blocks until packets are available in the RX ring or `timeout`
+    /// expires. A `timeout` of `None` waits forever.
+    ///
+    /// Polling also wakes up the kernel when the fill ring
+    /// [needs it](crate::xdp::FillQueue::needs_wakeup).
+    ///
+    /// Returns true if the socket is readable and false if the timeout
+    /// expired.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<bool, XskError> {
+        poll_readable(self.as_raw_fd(), timeout).map_err(|io_error| XskError::SyscallError {
+            call: "poll".to_owned(),
+            io_error,
+        })
+    }
+
+    /// Wakes up the kernel to transmit the packets queued in the TX ring.
+    pub fn wakeup(&self) -> Result<(), XskError> {
+        let ret = unsafe {
+            libc::sendto(
+                self.as_raw_fd(),
+                ptr::null(),
+                0,
+                MSG_DONTWAIT,
+                ptr::null(),
+                0,
+            )
+        };
+        if ret < 0 {
+            let io_error = io::Error::last_os_error();
+            // the kernel is already busy sending, or will be able to later
+            if !matches!(
+                io_error.raw_os_error(),
+                Some(ENOBUFS | EAGAIN | EBUSY | ENETDOWN)
+            ) {
+                return Err(XskError::SyscallError {
+                    call: "sendto".to_owned(),
+                    io_error,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables preferred busy polling.
+    ///
+    /// The socket busy polls the device queue for up to `timeout` when
+    /// [`XskSocket::poll`] and [`XskSocket::wakeup`] are called, processing
+    /// up to `budget` packets at a time, and the driver interrupts stay
+    /// disabled as long as the application keeps up. The interface should be
+    /// configured with `napi_defer_hard_irqs` and `gro_flush_timeout`.
+    ///
+    /// # Minimum kernel version
+    ///
+    /// The minimum kernel version required to use this feature is 5.11.
+    pub fn set_busy_poll(&self, timeout: Duration, budget: u32) -> Result<(), XskError> {
+        let fd = self.as_raw_fd();
+        let prefer: c_int = 1;
+        setsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_PREFER_BUSY_POLL,
+            "SO_PREFER_BUSY_POLL",
+            &prefer,
+        )?;
+        let usecs = timeout.as_micros().min(c_int::MAX as u128) as c_int;
+        setsockopt(fd, SOL_SOCKET, SO_BUSY_POLL, "SO_BUSY_POLL", &usecs)?;
+        let budget = budget as c_int;
+        setsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_BUSY_POLL_BUDGET,
+            "SO_BUSY_POLL_BUDGET",
+            &budget,
+        )
+    }
+
+    /// Returns the socket statistics.
+    pub fn statistics(&self) -> Result<XskStatistics, XskError> {
+        let mut stats = unsafe { mem::zeroed::<xdp_statistics>() };
+        let mut len = mem::size_of::<xdp_statistics>() as socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                self.as_raw_fd(),
+                SOL_XDP,
+                XDP_STATISTICS as c_int,
+                &mut stats as *mut _ as *mut c_void,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(XskError::SyscallError {
+                call: "getsockopt(XDP_STATISTICS)".to_owned(),
+                io_error: io::Error::last_os_error(),
+            });
+        }
+
+        // older kernels only fill the first three fields, which stay zeroed
+        Ok(XskStatistics {
+            rx_dropped: stats.rx_dropped,
+            rx_invalid_descs: stats.rx_invalid_descs,
+            tx_invalid_descs: stats.tx_invalid_descs,
+            rx_ring_full: stats.rx_ring_full,
+            rx_fill_ring_empty_descs: stats.rx_fill_ring_empty_descs,
+            tx_ring_empty_descs: stats.tx_ring_empty_descs,
+        })
+    }
+}
+
+impl AsRawFd for XskSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        match &self.fd {
+            Some(fd) => fd.as_raw_fd(),
+            None => self.umem.fd.as_raw_fd(),
+        }
+    }
+}
+
+impl AsFd for XskSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match &self.fd {
+            Some(fd) => fd.as_fd(),
+            None => self.umem.fd.as_fd(),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! The packet buffer area shared by AF_XDP sockets and the kernel.
+use std::{
+    ffi::c_void,
+    io, mem,
+    os::unix::io::{AsRawFd, OwnedFd, RawFd},
+    ptr, slice,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use libc::{MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_READ, PROT_WRITE, SOL_XDP};
+
+use crate::{
+    generated::{
+        xdp_umem_reg, XDP_UMEM_COMPLETION_RING, XDP_UMEM_FILL_RING, XDP_UMEM_PGOFF_COMPLETION_RING,
+        XDP_UMEM_PGOFF_FILL_RING, XDP_UMEM_REG, XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+    },
+    xdp::{
+        check_ring_size, mmap_offsets, setsockopt, xsk_socket, CompletionQueue, FillQueue, Ring,
+        XdpDesc, XskError,
+    },
+};
+
+// In unaligned chunk mode, the upper bits of a descriptor address contain an
+// offset to add to the lower bits.
+const XSK_UNALIGNED_BUF_OFFSET_SHIFT: u64 = 48;
+const XSK_UNALIGNED_BUF_ADDR_MASK: u64 = (1 << XSK_UNALIGNED_BUF_OFFSET_SHIFT) - 1;
+
+/// [`Umem`] configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UmemConfig {
+    /// The number of entries of the fill ring. Must be a power of two.
+    pub fill_size: u32,
+    /// The number of entries of the completion ring. Must be a power of two.
+    pub completion_size: u32,
+    /// The size of each frame. Unless `unaligned_chunks` is set, must be a
+    /// power of two between 2048 and the page size.
+    pub frame_size: u32,
+    /// The headroom the kernel reserves at the start of each frame.
+    pub frame_headroom: u32,
+    /// Allow packet buffers to start anywhere within the UMEM instead of at
+    /// frame boundaries.
+    pub unaligned_chunks: bool,
+}
+
+impl Default for UmemConfig {
+    fn default() -> Self {
+        UmemConfig {
+            fill_size: 2048,
+            completion_size: 2048,
+            frame_size: 4096,
+            frame_headroom: 0,
+            unaligned_chunks: false,
+        }
+    }
+}
+
+pub(crate) struct UmemArea {
+    addr: *mut c_void,
+    len: usize,
+    // the socket the UMEM is registered with
+    pub(crate) fd: OwnedFd,
+    // set once an XskSocket is bound using `fd` directly, the sockets created
+    // after that share the UMEM with XDP_SHARED_UMEM
+    pub(crate) fd_bound: AtomicBool,
+}
+
+// Safety: the area is only accessed through `Umem`, which enforces borrowing
+unsafe impl Send for UmemArea {}
+unsafe impl Sync for UmemArea {}
+
+impl Drop for UmemArea {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr, self.len) };
+    }
+}
+
+/// A memory area used to exchange packets with the kernel through AF_XDP
+/// sockets.
+///
+/// A UMEM is split into frames of [`UmemConfig::frame_size`] bytes, which
+/// are passed back and forth between user space and the kernel by address:
+///
+/// * frames placed in the [`FillQueue`] are filled by the kernel with
+///   received packets, which are then reported in the
+///   [`RxQueue`](crate::xdp::RxQueue) of a socket.
+/// * frames queued in the [`TxQueue`](crate::xdp::TxQueue) of a socket are
+///   transmitted, then returned in the [`CompletionQueue`].
+///
+/// A frame must only be accessed by user space while it isn't owned by the
+/// kernel.
+///
+/// A UMEM can be shared by several [`XskSocket`](crate::xdp::XskSocket)s
+/// bound to the same interface and queue.
+///
+/// # Minimum kernel version
+///
+/// The minimum kernel version required to use this feature is 4.18.
+pub struct Umem {
+    pub(crate) area: Arc<UmemArea>,
+    fill: FillQueue,
+    completion: CompletionQueue,
+    frame_size: u32,
+    frame_count: u32,
+}
+
+impl Umem {
+    /// Allocates and registers a UMEM of `frame_count` frames.
+    pub fn new(frame_count: u32, config: UmemConfig) -> Result<Umem, XskError> {
+        check_ring_size(config.fill_size)?;
+        check_ring_size(config.completion_size)?;
+
+        let len = frame_count as usize * config.frame_size as usize;
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if addr == MAP_FAILED {
+            return Err(XskError::SyscallError {
+                call: "mmap".to_owned(),
+                io_error: io::Error::last_os_error(),
+            });
+        }
+
+        let fd = match xsk_socket() {
+            Ok(fd) => fd,
+            Err(e) => {
+                unsafe { libc::munmap(addr, len) };
+                return Err(e);
+            }
+        };
+        let area = Arc::new(UmemArea {
+            addr,
+            len,
+            fd,
+            fd_bound: AtomicBool::new(false),
+        });
+        let fd = area.fd.as_raw_fd();
+
+        let mut reg = unsafe { mem::zeroed::<xdp_umem_reg>() };
+        reg.addr = addr as u64;
+        reg.len = len as u64;
+        reg.chunk_size = config.frame_size;
+        reg.headroom = config.frame_headroom;
+        if config.unaligned_chunks {
+            reg.flags = XDP_UMEM_UNALIGNED_CHUNK_FLAG;
+        }
+        setsockopt(fd, SOL_XDP, XDP_UMEM_REG, "XDP_UMEM_REG", &reg)?;
+        setsockopt(
+            fd,
+            SOL_XDP,
+            XDP_UMEM_FILL_RING,
+            "XDP_UMEM_FILL_RING",
+            &config.fill_size,
+        )?;
+        setsockopt(
+            fd,
+            SOL_XDP,
+            XDP_UMEM_COMPLETION_RING,
+            "XDP_UMEM_COMPLETION_RING",
+            &config.completion_size,
+        )?;
+
+        let offsets = mmap_offsets(fd)?;
+        let fill = FillQueue(Ring::map(
+            fd,
+            &offsets.fr,
+            config.fill_size,
+            XDP_UMEM_PGOFF_FILL_RING,
+        )?);
+        let completion = CompletionQueue(Ring::map(
+            fd,
+            &offsets.cr,
+            config.completion_size,
+            XDP_UMEM_PGOFF_COMPLETION_RING,
+        )?);
+
+        Ok(Umem {
+            area,
+            fill,
+            completion,
+            frame_size: config.frame_size,
+            frame_count,
+        })
+    }
+
+    /// Returns the size of the frames.
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+
+    /// Returns the number of frames.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Returns the address of the frame at `index`, to be placed in the
+    /// [`FillQueue`] or used in an [`XdpDesc`].
+    pub fn frame_addr(&self, index: u32) -> u64 {
+        index as u64 * self.frame_size as u64
+    }
+
+    /// Returns the fill ring.
+    pub fn fill_queue(&mut self) -> &mut FillQueue {
+        &mut self.fill
+    }
+
+    /// Returns the completion ring.
+    pub fn completion_queue(&mut self) -> &mut CompletionQueue {
+        &mut self.completion
+    }
+
+    /// Returns the packet data described by `desc`, or `None` if `desc`
+    /// points outside the UMEM.
+    ///
+    /// # Safety
+    ///
+    /// The kernel writes to the frames it owns at any time. The caller must
+    /// own the frame described by `desc`, that is it must have been received
+    /// from the RX ring or the [`CompletionQueue`] and not yet placed back in
+    /// the [`FillQueue`] or the TX ring, for as long as the returned slice is
+    /// alive.
+    pub unsafe fn data(&self, desc: &XdpDesc) -> Option<&[u8]> {
+        let offset = self.offset(desc)?;
+        Some(slice::from_raw_parts(
+            (self.area.addr as *const u8).add(offset),
+            desc.len as usize,
+        ))
+    }
+
+    /// Returns the packet data described by `desc` for writing, or `None` if
+    /// `desc` points outside the UMEM.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Umem::data`]: the caller must own the frame described by
+    /// `desc` for as long as the returned slice is alive.
+    pub unsafe fn data_mut(&mut self, desc: &XdpDesc) -> Option<&mut [u8]> {
+        let offset = self.offset(desc)?;
+        Some(slice::from_raw_parts_mut(
+            (self.area.addr as *mut u8).add(offset),
+            desc.len as usize,
+        ))
+    }
+
+    fn offset(&self, desc: &XdpDesc) -> Option<usize> {
+        let offset = ((desc.addr & XSK_UNALIGNED_BUF_ADDR_MASK)
+            + (desc.addr >> XSK_UNALIGNED_BUF_OFFSET_SHIFT)) as usize;
+        if offset.checked_add(desc.len as usize)? > self.area.len {
+            return None;
+        }
+        Some(offset)
+    }
+}
+
+impl AsRawFd for Umem {
+    fn as_raw_fd(&self) -> RawFd {
+        self.area.fd.as_raw_fd()
+    }
+}
@@ -65,6 +65,15 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         "bpf_prog_type",
         "bpf_attach_type",
         "bpf_prog_info",
+        "bpf_map_info",
+        "bpf_flow_keys",
+        "bpf_btf_info",
+        "bpf_func_info",
+        "bpf_line_info",
+        "bpf_cgroup_iter_order",
+        "bpf_iter_link_info",
+        "bpf_link_type",
+        "bpf_link_info",
         // BTF
         "btf_header",
         "btf_ext_info",
@@ -76,6 +85,8 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         "btf_param",
         "btf_var",
         "btf_var_secinfo",
+        "btf_decl_tag",
+        "btf_enum64",
         // PERF
         "perf_event_attr",
         "perf_sw_ids",
@@ -84,6 +95,7 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         "perf_hw_cache_op_id",
         "perf_hw_cache_op_result_id",
         "perf_event_sample_format",
+        "perf_event_read_format",
         "perf_event_mmap_page",
         "perf_event_header",
         "perf_type_id",
@@ -91,10 +103,35 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         // NETLINK
         "ifinfomsg",
         "tcmsg",
+        // AF_XDP
+        "sockaddr_xdp",
+        "xdp_ring_offset",
+        "xdp_mmap_offsets",
+        "xdp_umem_reg",
+        "xdp_statistics",
+        "xdp_desc",
+        // NETDEV
+        "netdev_xdp_act",
     ];
 
     let vars = [
         // BPF
+        "BPF_F_ALLOW_.*",
+        "BPF_F_REPLACE",
+        "BPF_F_BEFORE",
+        "BPF_F_AFTER",
+        "BPF_F_ID",
+        "BPF_F_LINK",
+        "BPF_F_SLEEPABLE",
+        "BPF_F_XDP_HAS_FRAGS",
+        "BPF_F_NO_PREALLOC",
+        "BPF_F_NUMA_NODE",
+        "BPF_F_LOCK",
+        "BPF_F_RDONLY_PROG",
+        "BPF_F_WRONLY_PROG",
+        "BPF_F_STACK_BUILD_ID",
+        "BPF_F_INNER_MAP",
+        "BPF_F_TOKEN_FD",
         "BPF_PSEUDO_.*",
         "BPF_ALU",
         "BPF_ALU64",
@@ -109,8 +146,21 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         "BPF_B",
         "BPF_JMP",
         "BPF_CALL",
+        "BPF_IMM",
+        "BPF_MEM",
+        "BPF_MOV",
+        "BPF_EXIT",
+        "BPF_X",
+        "BPF_AND",
+        "BPF_LSH",
+        "BPF_JEQ",
         "SO_ATTACH_BPF",
         "SO_DETACH_BPF",
+        "SO_ATTACH_REUSEPORT_EBPF",
+        "SO_DETACH_REUSEPORT_BPF",
+        "SO_BUSY_POLL",
+        "SO_PREFER_BUSY_POLL",
+        "SO_BUSY_POLL_BUDGET",
         // BTF
         "BTF_INT_.*",
         "BTF_KIND_.*",
@@ -123,12 +173,14 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         // NETLINK
         "NLMSG_ALIGNTO",
         "IFLA_XDP_FD",
+        "XDP_ATTACHED_.*",
         "TCA_KIND",
         "TCA_OPTIONS",
         "TCA_BPF_FD",
         "TCA_BPF_NAME",
         "TCA_BPF_FLAGS",
         "TCA_BPF_FLAG_ACT_DIRECT",
+        "TCA_CLS_FLAGS_.*",
         "XDP_FLAGS_.*",
         "TC_H_MAJ_MASK",
         "TC_H_MIN_MASK",
@@ -139,6 +191,27 @@ fn codegen_bindings(opts: &Options) -> Result<(), anyhow::Error> {
         "TC_H_MIN_PRIORITY",
         "TC_H_MIN_INGRESS",
         "TC_H_MIN_EGRESS",
+        // AF_XDP
+        "XDP_SHARED_UMEM",
+        "XDP_COPY",
+        "XDP_ZEROCOPY",
+        "XDP_USE_NEED_WAKEUP",
+        "XDP_UMEM_UNALIGNED_CHUNK_FLAG",
+        "XDP_RING_NEED_WAKEUP",
+        "XDP_MMAP_OFFSETS",
+        "XDP_RX_RING",
+        "XDP_TX_RING",
+        "XDP_UMEM_REG",
+        "XDP_UMEM_FILL_RING",
+        "XDP_UMEM_COMPLETION_RING",
+        "XDP_STATISTICS",
+        "XDP_PGOFF_.*",
+        "XDP_UMEM_PGOFF_.*",
+        // NETDEV
+        "NETDEV_FAMILY_NAME",
+        "NETDEV_FAMILY_VERSION",
+        "NETDEV_A_DEV_.*",
+        "NETDEV_CMD_DEV_GET",
     ];
 
     let dir = PathBuf::from("aya");